@@ -1,4 +1,7 @@
-mod manager;
+pub mod limits;
+pub mod logging;
+pub mod manager;
 pub mod pool;
 pub mod runtime;
 pub mod snapshot;
+pub mod telemetry;
@@ -0,0 +1,92 @@
+use deno_core::{v8, JsRuntime};
+use schemajs_config::SchemeJsLimitsConfig;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Heap limits of the isolate of the runtime, `None` when `limits` sets no maximum.
+pub fn create_params(limits: &SchemeJsLimitsConfig) -> Option<v8::CreateParams> {
+    limits.max_heap_size.map(|max_heap_size| {
+        v8::CreateParams::default()
+            .heap_limits(limits.initial_heap_size.unwrap_or(0), max_heap_size)
+    })
+}
+
+/// Terminates the script running in `js_runtime` when its heap is about to reach its limit, instead of letting
+/// V8 abort the whole process. The returned flag is set once it happened.
+///
+/// The limit is raised (doubled) so the terminated script has room to unwind.
+pub fn terminate_near_heap_limit(js_runtime: &mut JsRuntime) -> Arc<AtomicBool> {
+    let handle = js_runtime.v8_isolate().thread_safe_handle();
+    let reached = Arc::new(AtomicBool::new(false));
+    let reached_ref = reached.clone();
+
+    js_runtime.add_near_heap_limit_callback(move |current_limit, _initial_limit| {
+        reached_ref.store(true, Ordering::SeqCst);
+        handle.terminate_execution();
+        current_limit * 2
+    });
+
+    reached
+}
+
+/// Terminates the execution of an isolate if it is still running `timeout` after the watchdog was started.
+/// Dropping the watchdog (once the script returned) stops it.
+///
+/// # Fields:
+/// - `done`: Tells the thread of the watchdog the script returned.
+/// - `fired`: Set once the execution was terminated.
+/// - `thread`: Thread waiting for `timeout`.
+pub struct ExecutionWatchdog {
+    done: Option<Sender<()>>,
+    fired: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ExecutionWatchdog {
+    pub fn start(handle: v8::IsolateHandle, timeout: Duration) -> Self {
+        let (done, receiver) = channel::<()>();
+        let fired = Arc::new(AtomicBool::new(false));
+        let fired_ref = fired.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("schemajs-watchdog".to_string())
+            .spawn(move || {
+                if let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(timeout) {
+                    fired_ref.store(true, Ordering::SeqCst);
+                    handle.terminate_execution();
+                }
+            })
+            .unwrap();
+
+        Self {
+            done: Some(done),
+            fired,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the watchdog, returning whether the execution was terminated for running longer than the timeout.
+    pub fn stop(mut self) -> bool {
+        self.finish();
+        self.fired.load(Ordering::SeqCst)
+    }
+
+    fn finish(&mut self) {
+        if let Some(done) = self.done.take() {
+            let _ = done.send(());
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ExecutionWatchdog {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,35 @@
+use crate::telemetry::otlp_layer;
+use anyhow::Result;
+use schemajs_config::{LogFormat, SchemeJsConfig};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+/// Installs the subscriber of the process from `SchemeJS.toml`: logs written to stdout as `[logging]` sets
+/// (levels per crate and format), and spans exported over OTLP when there is a `[telemetry]` section.
+/// Events of crates logging through `log` (e.g. the module loader) are logged the same way.
+///
+/// A process has a single subscriber, calls made once it is installed are ignored.
+pub fn init_logging(config: &SchemeJsConfig) -> Result<()> {
+    if tracing::dispatcher::has_been_set() {
+        return Ok(());
+    }
+
+    let filter = EnvFilter::try_new(config.logging.directives())?;
+    let logs = match config.logging.format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_filter(filter).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_filter(filter)
+            .boxed(),
+    };
+
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = vec![logs];
+    if let Some(telemetry) = &config.telemetry {
+        layers.push(otlp_layer(telemetry)?);
+    }
+
+    tracing_subscriber::registry().with(layers).try_init()?;
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use crate::manager::cron::CronSchedule;
+use crate::manager::task::Task;
+use crate::manager::task_duration::TaskDuration;
+use anyhow::{anyhow, Result};
+use schemajs_config::{SchemeJsCompactionConfig, TimeWindow};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// When and what the compaction task compacts, from the `[compaction]` section of `SchemeJS.toml`.
+///
+/// # Fields:
+/// - `interval`: How often the task runs.
+/// - `cron`: When the task runs instead of every `interval`, if set.
+/// - `jitter`: Random delay of every run, up to it.
+/// - `off_peak`: Windows of the day (UTC) the task is allowed to compact in. Any time of the day when empty.
+/// - `min_dead_ratio`: Share of dead rows a table must hold before it is compacted.
+#[derive(Debug, Clone)]
+pub struct CompactionSchedule {
+    pub interval: Duration,
+    pub cron: Option<CronSchedule>,
+    pub jitter: Duration,
+    pub off_peak: Vec<TimeWindow>,
+    pub min_dead_ratio: f64,
+}
+
+impl CompactionSchedule {
+    pub fn from_config(config: &SchemeJsCompactionConfig) -> Result<Self> {
+        Ok(Self {
+            interval: config.interval()?,
+            cron: config
+                .schedule
+                .as_deref()
+                .map(CronSchedule::parse)
+                .transpose()?,
+            jitter: config.jitter()?,
+            off_peak: config.off_peak_windows()?,
+            min_dead_ratio: config.min_dead_ratio,
+        })
+    }
+
+    /// When the task runs: following `cron` if set, every `interval` otherwise.
+    pub fn duration(&self) -> TaskDuration {
+        match &self.cron {
+            Some(cron) => TaskDuration::Cron(cron.clone()),
+            None => TaskDuration::Defined(self.interval),
+        }
+    }
+
+    /// Whether compaction may run at `time_of_day` (elapsed since midnight, UTC).
+    pub fn is_off_peak(&self, time_of_day: Duration) -> bool {
+        self.off_peak.is_empty()
+            || self
+                .off_peak
+                .iter()
+                .any(|window| window.contains(time_of_day))
+    }
+}
+
+fn time_of_day() -> Duration {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    Duration::from_secs(now.as_secs() % (24 * 60 * 60))
+}
+
+/// Task compacting the tables holding dead rows (deleted, expired or previous versions of updated rows) across
+/// every database of the engine, which also drops the index entries pointing to them.
+/// Runs when `schedule` says so, skipping the runs that fall outside of its off-peak windows.
+pub fn compaction_task(schedule: CompactionSchedule) -> Task {
+    let duration = schedule.duration();
+    let jitter = schedule.jitter;
+
+    Task::new(
+        "compaction".to_string(),
+        Box::new(move |engine| {
+            if !schedule.is_off_peak(time_of_day()) {
+                return Ok(());
+            }
+
+            for db in engine.databases().iter() {
+                let compacted = db
+                    .query_manager
+                    .compact_all(schedule.min_dead_ratio)
+                    .map_err(|e| anyhow!("Could not compact the tables of '{}': {}", db.name, e))?;
+                if compacted > 0 {
+                    tracing::info!(database = %db.name, tables = compacted, "Compacted tables");
+                }
+            }
+
+            Ok(())
+        }),
+        duration,
+    )
+    .set_jitter(jitter)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::manager::compaction::CompactionSchedule;
+    use crate::manager::task_duration::TaskDuration;
+    use schemajs_config::SchemeJsCompactionConfig;
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_compaction_schedule() {
+        let hour = |hour: u64| Duration::from_secs(hour * 60 * 60);
+
+        let schedule = CompactionSchedule::from_config(&SchemeJsCompactionConfig {
+            interval: "30m".to_string(),
+            off_peak: vec!["01:00-05:00".to_string(), "22:30-00:30".to_string()],
+            min_dead_ratio: 0.2,
+            schedule: None,
+            jitter: None,
+        })
+        .unwrap();
+
+        assert_eq!(schedule.interval, Duration::from_secs(30 * 60));
+        assert!(schedule.is_off_peak(hour(1)));
+        assert!(schedule.is_off_peak(hour(4)));
+        assert!(!schedule.is_off_peak(hour(5)));
+        assert!(!schedule.is_off_peak(hour(12)));
+        // Windows wrap around midnight
+        assert!(schedule.is_off_peak(hour(23)));
+        assert!(schedule.is_off_peak(Duration::from_secs(10 * 60)));
+        assert!(!schedule.is_off_peak(hour(22)));
+
+        let anytime = CompactionSchedule::from_config(&SchemeJsCompactionConfig {
+            interval: "1h".to_string(),
+            off_peak: vec![],
+            min_dead_ratio: 0.0,
+            schedule: None,
+            jitter: None,
+        })
+        .unwrap();
+        assert!(anytime.is_off_peak(hour(12)));
+        assert!(matches!(anytime.duration(), TaskDuration::Defined(_)));
+        assert_eq!(anytime.jitter, Duration::ZERO);
+
+        let nightly = CompactionSchedule::from_config(&SchemeJsCompactionConfig {
+            interval: "1h".to_string(),
+            off_peak: vec![],
+            min_dead_ratio: 0.0,
+            schedule: Some("30 2 * * *".to_string()),
+            jitter: Some("10m".to_string()),
+        })
+        .unwrap();
+        assert!(matches!(nightly.duration(), TaskDuration::Cron(_)));
+        assert_eq!(nightly.jitter, Duration::from_secs(10 * 60));
+
+        for (interval, off_peak, schedule, jitter) in [
+            ("1x", "01:00-05:00", None, None),
+            ("1h", "01:00", None, None),
+            ("1h", "25:00-26:00", None, None),
+            ("1h", "01:00-05:00", Some("0 3 * *"), None),
+            ("1h", "01:00-05:00", None, Some("soon")),
+        ] {
+            assert!(CompactionSchedule::from_config(&SchemeJsCompactionConfig {
+                interval: interval.to_string(),
+                off_peak: vec![off_peak.to_string()],
+                min_dead_ratio: 0.0,
+                schedule: schedule.map(str::to_string),
+                jitter: jitter.map(str::to_string),
+            })
+            .is_err());
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, TimeZone, Timelike, Utc};
+
+/// Days looked ahead for the next match of a schedule. Every valid expression matches within a few years
+/// (e.g. `0 0 29 2 *` only on leap years), expressions that never match (`0 0 31 2 *`) give up past it.
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 8;
+
+/// Schedule of a task as a cron expression of five fields, evaluated in UTC:
+/// `minute (0-59) hour (0-23) day-of-month (1-31) month (1-12) day-of-week (0-6, 0 and 7 being Sunday)`.
+///
+/// Fields are `*`, values, ranges (`1-5`), steps (`*/15`, `0-30/10`) or lists of them (`1,15,30`).
+/// When both the day of the month and the day of the week are restricted, days matching either of them match,
+/// like cron does. `0 3 * * *` runs every day at 03:00, `*/30 1-5 * * 1-5` every 30 minutes from 01:00 to 05:59
+/// on weekdays.
+///
+/// # Fields:
+/// - `minutes`, `hours`, `days`, `months`, `weekdays`: Bit `n` is set when the value `n` matches.
+/// - `any_day`, `any_weekday`: Whether the day of the month / of the week is `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            bail!(
+                "Cron expression '{}' must have 5 fields (minute hour day-of-month month day-of-week)",
+                expression
+            );
+        };
+
+        // Sunday is both 0 and 7
+        let mut weekday_bits = Self::parse_field(weekdays, 0, 7)?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: Self::parse_field(minutes, 0, 59)?,
+            hours: Self::parse_field(hours, 0, 23)?,
+            days: Self::parse_field(days, 1, 31)?,
+            months: Self::parse_field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+
+    fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+        let mut bits = 0;
+
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, Self::parse_value(step, 1, max.max(1))?),
+                None => (part, 1),
+            };
+
+            let (start, end) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((start, end)) => (
+                        Self::parse_value(start, min, max)?,
+                        Self::parse_value(end, min, max)?,
+                    ),
+                    None => {
+                        let value = Self::parse_value(range, min, max)?;
+                        // `5/10` runs from 5 to the end of the field
+                        (value, if step > 1 { max } else { value })
+                    }
+                },
+            };
+
+            if start > end {
+                bail!("Range '{}' of cron field '{}' is reversed", range, field);
+            }
+
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+
+        Ok(bits)
+    }
+
+    fn parse_value(value: &str, min: u32, max: u32) -> Result<u32> {
+        let parsed: u32 = value
+            .parse()
+            .map_err(|_| anyhow!("'{}' is not a number", value))?;
+
+        if parsed < min || parsed > max {
+            bail!("'{}' is out of range ({}-{})", value, min, max);
+        }
+
+        Ok(parsed)
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// Whether the schedule runs at the minute `time` falls in.
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        self.matches_date(time.date_naive())
+            && self.hours & (1 << time.hour()) != 0
+            && self.minutes & (1 << time.minute()) != 0
+    }
+
+    /// First minute the schedule runs at strictly after `after`, `None` if it never does.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after
+            .with_second(0)?
+            .with_nanosecond(0)?
+            .checked_add_signed(ChronoDuration::minutes(1))?;
+
+        for day in 0..MAX_LOOKAHEAD_DAYS {
+            let date = start.date_naive() + ChronoDuration::days(day);
+            if !self.matches_date(date) {
+                continue;
+            }
+
+            for hour in (0..24).filter(|hour| self.hours & (1 << hour) != 0) {
+                for minute in (0..60).filter(|minute| self.minutes & (1 << minute) != 0) {
+                    let candidate = Utc.from_utc_datetime(&date.and_hms_opt(hour, minute, 0)?);
+                    if candidate >= start {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::manager::cron::CronSchedule;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    pub fn test_cron_schedule() {
+        let at = |y: i32, mo: u32, d: u32, h: u32, mi: u32| Utc.ymd(y, mo, d).and_hms(h, mi, 0);
+
+        // Every day at 03:00
+        let nightly = CronSchedule::parse("0 3 * * *").unwrap();
+        assert!(nightly.matches(at(2024, 5, 1, 3, 0)));
+        assert!(!nightly.matches(at(2024, 5, 1, 3, 1)));
+        assert_eq!(
+            nightly.next_after(at(2024, 5, 1, 2, 59)),
+            Some(at(2024, 5, 1, 3, 0))
+        );
+        // Strictly after, even within the matching minute
+        assert_eq!(
+            nightly.next_after(at(2024, 5, 1, 3, 0)),
+            Some(at(2024, 5, 2, 3, 0))
+        );
+        assert_eq!(
+            nightly.next_after(at(2024, 12, 31, 4, 0)),
+            Some(at(2025, 1, 1, 3, 0))
+        );
+
+        // Steps, ranges and weekdays (2024-05-04 is a Saturday)
+        let weekdays = CronSchedule::parse("*/30 1-5 * * 1-5").unwrap();
+        assert_eq!(
+            weekdays.next_after(at(2024, 5, 3, 5, 30)),
+            Some(at(2024, 5, 6, 1, 0))
+        );
+        assert_eq!(
+            weekdays.next_after(at(2024, 5, 6, 1, 0)),
+            Some(at(2024, 5, 6, 1, 30))
+        );
+
+        // Day of the month or day of the week, Sunday as 7
+        let either = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert_eq!(
+            either.next_after(at(2024, 5, 1, 0, 0)),
+            Some(at(2024, 5, 5, 0, 0))
+        );
+        assert_eq!(
+            either.next_after(at(2024, 5, 26, 0, 0)),
+            Some(at(2024, 6, 1, 0, 0))
+        );
+
+        // Lists, and steps from a value
+        let list = CronSchedule::parse("5/20 0 1,15 6 *").unwrap();
+        assert_eq!(
+            list.next_after(at(2024, 6, 1, 0, 25)),
+            Some(at(2024, 6, 1, 0, 45))
+        );
+        assert_eq!(
+            list.next_after(at(2024, 6, 1, 0, 45)),
+            Some(at(2024, 6, 15, 0, 5))
+        );
+
+        // Leap days only
+        let leap = CronSchedule::parse("0 12 29 2 *").unwrap();
+        assert_eq!(
+            leap.next_after(at(2024, 3, 1, 0, 0)),
+            Some(at(2028, 2, 29, 12, 0))
+        );
+        assert_eq!(
+            CronSchedule::parse("0 0 31 2 *")
+                .unwrap()
+                .next_after(at(2024, 1, 1, 0, 0)),
+            None
+        );
+
+        for invalid in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "a * * * *",
+        ] {
+            assert!(CronSchedule::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
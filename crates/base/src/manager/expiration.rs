@@ -0,0 +1,24 @@
+use crate::manager::task::Task;
+use crate::manager::task_duration::TaskDuration;
+use anyhow::anyhow;
+use std::time::Duration;
+
+/// How often the task registered by `SchemeJsManager` looks for expired rows.
+pub const EXPIRATION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Task deleting the expired rows of every table with a TTL, across every database of the engine.
+pub fn expiration_task(interval: Duration) -> Task {
+    Task::new(
+        "expiration".to_string(),
+        Box::new(|engine| {
+            for db in engine.databases().iter() {
+                db.query_manager
+                    .purge_all_expired()
+                    .map_err(|e| anyhow!("Could not purge expired rows of '{}': {}", db.name, e))?;
+            }
+
+            Ok(())
+        }),
+        TaskDuration::Defined(interval),
+    )
+}
@@ -0,0 +1,22 @@
+use crate::manager::task::Task;
+use crate::manager::task_duration::TaskDuration;
+use std::time::Duration;
+
+/// How often the task registered by `SchemeJsManager` looks for temporary shards to flush.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Task reconciling the temporary shards of every table once their flush policy says so (e.g. their rows waited
+/// for longer than its `max_age`), across every database of the engine.
+pub fn flush_task(interval: Duration) -> Task {
+    Task::new(
+        "flush".to_string(),
+        Box::new(|engine| {
+            for db in engine.databases().iter() {
+                db.query_manager.flush_due();
+            }
+
+            Ok(())
+        }),
+        TaskDuration::Defined(interval),
+    )
+}
@@ -0,0 +1,21 @@
+use crate::manager::task::Task;
+use crate::manager::task_duration::TaskDuration;
+use std::time::Duration;
+
+/// How often the task registered by `SchemeJsManager` refreshes the statistics of the indexes.
+pub const INDEX_STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Task refreshing the index statistics used by the planner, for every table across every database of the engine.
+pub fn index_stats_task(interval: Duration) -> Task {
+    Task::new(
+        "index_stats".to_string(),
+        Box::new(|engine| {
+            for db in engine.databases().iter() {
+                db.query_manager.refresh_index_stats();
+            }
+
+            Ok(())
+        }),
+        TaskDuration::Defined(interval),
+    )
+}
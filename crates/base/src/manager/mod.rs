@@ -1,11 +1,30 @@
+pub mod compaction;
+pub mod cron;
+pub mod expiration;
+pub mod flush;
+pub mod index_stats;
+pub mod retention;
+pub mod retry;
 pub mod task;
 pub mod task_duration;
+pub mod views;
 
+use crate::manager::compaction::{compaction_task, CompactionSchedule};
+use crate::manager::expiration::{expiration_task, EXPIRATION_INTERVAL};
+use crate::manager::flush::{flush_task, FLUSH_INTERVAL};
+use crate::manager::index_stats::{index_stats_task, INDEX_STATS_INTERVAL};
+use crate::manager::retention::{retention_task, RETENTION_INTERVAL};
 use crate::manager::task::Task;
 use crate::manager::task_duration::TaskDuration;
+use crate::manager::views::{views_task, VIEWS_INTERVAL};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use schemajs_config::SchemeJsConfig;
 use schemajs_engine::engine::SchemeJsEngine;
+use std::panic::AssertUnwindSafe;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::select;
 use tokio_util::sync::CancellationToken;
 
@@ -21,11 +40,31 @@ impl SchemeJsManager {
         Self {
             runtime,
             running: Arc::new(AtomicBool::new(true)),
-            tasks: vec![],
+            tasks: vec![
+                expiration_task(EXPIRATION_INTERVAL),
+                flush_task(FLUSH_INTERVAL),
+                index_stats_task(INDEX_STATS_INTERVAL),
+                retention_task(RETENTION_INTERVAL),
+                views_task(VIEWS_INTERVAL),
+            ],
             cancellation_token: CancellationToken::new(),
         }
     }
 
+    /// Creates a manager like `new`, adding the tasks enabled in `config` (e.g. the compaction task when
+    /// `SchemeJS.toml` has a `[compaction]` section).
+    pub fn from_config(runtime: Arc<SchemeJsEngine>, config: &SchemeJsConfig) -> Result<Self> {
+        let mut manager = Self::new(runtime);
+
+        if let Some(compaction) = &config.compaction {
+            manager.add_task(compaction_task(CompactionSchedule::from_config(
+                compaction,
+            )?));
+        }
+
+        Ok(manager)
+    }
+
     pub fn add_task(&mut self, task: Task) {
         self.tasks.push(task);
     }
@@ -35,6 +74,8 @@ impl SchemeJsManager {
         let running = self.running.clone();
 
         for task in &self.tasks {
+            engine.tasks.register(&task.id);
+
             let task_cancel_token = task.cancellation_token.clone();
             let task = task.clone();
             let engine = engine.clone();
@@ -55,27 +96,160 @@ impl SchemeJsManager {
     }
 
     async fn run_task(task: Task, engine: Arc<SchemeJsEngine>, running: Arc<AtomicBool>) {
-        match task.duration {
+        match &task.duration {
             TaskDuration::Defined(dur) => {
-                let mut interval = tokio::time::interval(dur);
+                let mut interval = tokio::time::interval(*dur);
                 while running.load(Ordering::Relaxed) {
                     interval.tick().await;
-                    let clone_rt_ref = engine.clone();
-                    let cb = task.func.cb.clone();
+                    Self::run_once(&task, &engine).await;
+                }
+            }
+            TaskDuration::Cron(schedule) => {
+                while running.load(Ordering::Relaxed) {
+                    let now = Utc::now();
+                    let next = match schedule.next_after(now) {
+                        Some(next) => next,
+                        None => {
+                            tracing::warn!(task = %task.id, "Cron schedule never runs");
+                            return;
+                        }
+                    };
 
-                    cb(clone_rt_ref).unwrap_or_else(|_| println!("Error executing task"));
+                    tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+                    Self::run_once(&task, &engine).await;
                 }
             }
-            TaskDuration::Once => {
-                let clone_rt_ref = engine.clone();
-                let cb = task.func.cb.clone();
-                cb(clone_rt_ref).unwrap_or_else(|_| println!("Error executing task"));
+            TaskDuration::Once => Self::run_once(&task, &engine).await,
+            TaskDuration::After(delay) => {
+                tokio::time::sleep(*delay).await;
+                Self::run_once(&task, &engine).await;
+            }
+        }
+    }
+
+    /// Runs `task` once, after waiting for its jitter, retrying it as its retry policy says while it fails.
+    /// Every attempt is recorded in the task registry of the engine (see `TaskRegistry`).
+    async fn run_once(task: &Task, engine: &Arc<SchemeJsEngine>) {
+        let jitter = task.next_jitter();
+        if !jitter.is_zero() {
+            tokio::time::sleep(jitter).await;
+        }
+
+        let mut retry = 0;
+        loop {
+            engine.tasks.started(&task.id);
+            let started = Instant::now();
+
+            let error = match Self::call(task, engine) {
+                Ok(()) => {
+                    engine.tasks.succeeded(&task.id, started.elapsed());
+                    return;
+                }
+                Err(e) => e,
+            };
+
+            tracing::error!(task = %task.id, error = %error, retry, "Error executing task");
+            engine
+                .tasks
+                .failed(&task.id, error.to_string(), started.elapsed());
+
+            if retry >= task.retry.max_retries {
+                return;
             }
+            retry += 1;
+            tokio::time::sleep(task.retry.backoff(retry)).await;
         }
     }
 
+    /// Calls the callback of `task`, turning its panics into errors unless the task lets them through.
+    fn call(task: &Task, engine: &Arc<SchemeJsEngine>) -> Result<()> {
+        let cb = task.func.cb.clone();
+        if !task.catch_panics {
+            return cb(engine.clone());
+        }
+
+        std::panic::catch_unwind(AssertUnwindSafe(|| cb(engine.clone()))).unwrap_or_else(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(anyhow!("Task panicked: {}", message))
+        })
+    }
+
     pub fn stop_tasks(&self) {
         self.running.store(false, Ordering::Relaxed);
         self.cancellation_token.cancel();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::manager::retry::RetryPolicy;
+    use crate::manager::task::Task;
+    use crate::manager::task_duration::TaskDuration;
+    use crate::manager::SchemeJsManager;
+    use anyhow::bail;
+    use schemajs_engine::engine::SchemeJsEngine;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    pub async fn test_manager_task_status() {
+        let engine = Arc::new(SchemeJsEngine::new(None));
+        let retry = RetryPolicy::new(2).set_initial_backoff(Duration::from_millis(1));
+
+        // Succeeds on its third attempt
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let flaky = Task::new(
+            "flaky".to_string(),
+            Box::new(move |_| {
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    bail!("not yet");
+                }
+                Ok(())
+            }),
+            TaskDuration::Once,
+        )
+        .set_retry(retry);
+        SchemeJsManager::run_once(&flaky, &engine).await;
+
+        let status = engine.tasks.get("flaky").unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!((status.runs, status.failures), (3, 2));
+        assert_eq!(status.consecutive_failures, 0);
+        assert_eq!(status.last_error.as_deref(), Some("not yet"));
+        assert!(status.last_success.is_some());
+        assert!(!status.running);
+
+        // Gives up once the retries are exhausted
+        let failing = Task::new(
+            "failing".to_string(),
+            Box::new(|_| bail!("broken")),
+            TaskDuration::Once,
+        )
+        .set_retry(retry);
+        SchemeJsManager::run_once(&failing, &engine).await;
+
+        let status = engine.tasks.get("failing").unwrap();
+        assert_eq!((status.runs, status.failures), (3, 3));
+        assert_eq!(status.consecutive_failures, 3);
+        assert_eq!(status.last_success, None);
+
+        // Panics are recorded as failures, the caller keeps going
+        let panicking = Task::new(
+            "panicking".to_string(),
+            Box::new(|_| panic!("boom")),
+            TaskDuration::Once,
+        );
+        SchemeJsManager::run_once(&panicking, &engine).await;
+
+        let status = engine.tasks.get("panicking").unwrap();
+        assert_eq!(status.failures, 1);
+        assert_eq!(status.last_error.as_deref(), Some("Task panicked: boom"));
+        assert_eq!(engine.tasks.all().len(), 3);
+    }
+}
@@ -0,0 +1,25 @@
+use crate::manager::task::Task;
+use crate::manager::task_duration::TaskDuration;
+use anyhow::anyhow;
+use std::time::Duration;
+
+/// How often the task registered by `SchemeJsManager` enforces the retention of the tables.
+pub const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// Task dropping the rows older than the retention (TTL) of their table and compacting the tables
+/// that hold dead rows, across every database of the engine. See `SingleQueryManager::enforce_retention`.
+pub fn retention_task(interval: Duration) -> Task {
+    Task::new(
+        "retention".to_string(),
+        Box::new(|engine| {
+            for db in engine.databases().iter() {
+                db.query_manager.enforce_retention().map_err(|e| {
+                    anyhow!("Could not enforce the retention of '{}': {}", db.name, e)
+                })?;
+            }
+
+            Ok(())
+        }),
+        TaskDuration::Defined(interval),
+    )
+}
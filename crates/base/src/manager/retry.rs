@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+/// How a failing `Task` run is retried before giving up until its next run.
+///
+/// # Fields:
+/// - `max_retries`: Attempts made after the first one fails, no retries when zero.
+/// - `initial_backoff`: Wait before the first retry.
+/// - `max_backoff`: Longest wait between two attempts.
+/// - `multiplier`: Factor the wait grows by after every retry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retries up to `max_retries` times with the default backoff.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn set_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn set_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Wait before the retry number `retry` (starting at 1): `initial_backoff` grown by `multiplier` for every
+    /// previous retry, up to `max_backoff`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(retry.saturating_sub(1) as i32);
+        let backoff = self.initial_backoff.as_secs_f64() * factor;
+
+        Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::manager::retry::RetryPolicy;
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_retry_policy_backoff() {
+        let policy = RetryPolicy::new(5)
+            .set_initial_backoff(Duration::from_millis(100))
+            .set_max_backoff(Duration::from_secs(1));
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_secs(1));
+        assert_eq!(policy.backoff(30), Duration::from_secs(1));
+
+        // Backoffs never shrink
+        let constant = RetryPolicy::new(3)
+            .set_initial_backoff(Duration::from_millis(50))
+            .set_multiplier(0.5);
+        assert_eq!(constant.backoff(3), Duration::from_millis(50));
+        assert_eq!(RetryPolicy::default().max_retries, 0);
+    }
+}
@@ -1,10 +1,13 @@
+use crate::manager::retry::RetryPolicy;
 use crate::manager::task_duration::TaskDuration;
+use anyhow::Result;
 use schemajs_engine::engine::SchemeJsEngine;
 use std::fmt::{Debug, Formatter};
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 
-pub type TaskSignature = Box<dyn Fn(Arc<SchemeJsEngine>) -> Result<(), ()> + Send + Sync + 'static>;
+pub type TaskSignature = Box<dyn Fn(Arc<SchemeJsEngine>) -> Result<()> + Send + Sync + 'static>;
 
 #[derive(Clone)]
 pub struct TaskCallback {
@@ -17,11 +20,22 @@ impl Debug for TaskCallback {
     }
 }
 
+/// Work run by `SchemeJsManager` in the background.
+///
+/// # Fields:
+/// - `duration`: When the task runs.
+/// - `jitter`: Runs are delayed by a random duration up to it, so tasks scheduled at the same time
+///   (e.g. on several nodes) don't all run at once. No delay when zero.
+/// - `retry`: How failing runs are retried, not retried by default.
+/// - `catch_panics`: Whether a panic of the callback is recorded as a failure of the run instead of stopping the task.
 #[derive(Clone)]
 pub struct Task {
     pub id: String,
     pub func: TaskCallback,
     pub duration: TaskDuration,
+    pub jitter: Duration,
+    pub retry: RetryPolicy,
+    pub catch_panics: bool,
     pub cancellation_token: CancellationToken,
 }
 
@@ -31,7 +45,33 @@ impl Task {
             id,
             func: TaskCallback { cb: Arc::new(func) },
             duration: task_duration,
+            jitter: Duration::ZERO,
+            retry: RetryPolicy::default(),
+            catch_panics: true,
             cancellation_token: CancellationToken::new(),
         }
     }
+
+    pub fn set_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn set_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn set_catch_panics(mut self, catch_panics: bool) -> Self {
+        self.catch_panics = catch_panics;
+        self
+    }
+
+    /// Random delay to wait before a run, up to `jitter`.
+    pub fn next_jitter(&self) -> Duration {
+        match self.jitter.is_zero() {
+            true => Duration::ZERO,
+            false => self.jitter.mul_f64(rand::random::<f64>()),
+        }
+    }
 }
@@ -1,7 +1,16 @@
+use crate::manager::cron::CronSchedule;
 use std::time::Duration;
 
+/// When a `Task` runs.
+///
+/// - `Defined`: Every interval, starting right away.
+/// - `Cron`: At every minute its schedule matches (see `CronSchedule`), in UTC.
+/// - `Once`: Once, right away.
+/// - `After`: Once, after the delay.
 #[derive(Clone)]
 pub enum TaskDuration {
     Defined(Duration),
+    Cron(CronSchedule),
     Once,
+    After(Duration),
 }
@@ -0,0 +1,25 @@
+use crate::manager::task::Task;
+use crate::manager::task_duration::TaskDuration;
+use anyhow::anyhow;
+use std::time::Duration;
+
+/// How often the task registered by `SchemeJsManager` looks for materialized views to refresh.
+pub const VIEWS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Task refreshing the materialized views that are due (see `MaterializedViews::refresh_due`), across every
+/// database of the engine.
+pub fn views_task(interval: Duration) -> Task {
+    Task::new(
+        "views".to_string(),
+        Box::new(|engine| {
+            for db in engine.databases().iter() {
+                db.views
+                    .refresh_due()
+                    .map_err(|e| anyhow!("Could not refresh the views of '{}': {}", db.name, e))?;
+            }
+
+            Ok(())
+        }),
+        TaskDuration::Defined(interval),
+    )
+}
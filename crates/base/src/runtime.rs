@@ -1,22 +1,37 @@
+use crate::limits::{create_params, terminate_near_heap_limit, ExecutionWatchdog};
+use crate::logging::init_logging;
+use crate::manager::SchemeJsManager;
 use crate::snapshot;
-use anyhow::{bail, Error, Result};
+use anyhow::{anyhow, bail, Error, Result};
 use deno_core::_ops::RustToV8;
 use deno_core::url::Url;
 use deno_core::{
-    located_script_name, serde_v8, v8, Extension, JsRuntime, ModuleCodeString, ModuleId,
-    ModuleSpecifier, RuntimeOptions,
+    located_script_name, serde_json, serde_v8, v8, Extension, JsRuntime, ModuleCodeString,
+    ModuleId, ModuleSpecifier, PollEventLoopOptions, RuntimeOptions,
 };
-use schemajs_config::SchemeJsConfig;
+use schemajs_config::{ReplicationRole, SchemeJsConfig};
+use schemajs_data::data_handler::set_mmap_reads;
+use schemajs_engine::access::Principal;
+use schemajs_engine::auth::Authenticator;
+use schemajs_engine::cdc::CdcLog;
 use schemajs_engine::engine::SchemeJsEngine;
+use schemajs_engine::migrations::{
+    list_migrations, migrations_table, pending_migrations, record_migration,
+};
 use schemajs_module_loader::ts_module_loader::TypescriptModuleLoader;
 use schemajs_primitives::database::Database;
 use schemajs_primitives::table::Table;
+use schemajs_query::managers::single::schema::SchemaChange;
 use schemajs_workers::context::{MainWorkerRuntimeOpts, WorkerRuntimeOpts};
+use schemajs_workers::query_pool::QueryWorkerPool;
 use serde::{Deserialize, Serialize};
 use std::cell::{RefCell, RefMut};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
 use walkdir::{DirEntry, WalkDir};
 
 pub struct SchemeJsRuntime {
@@ -26,6 +41,12 @@ pub struct SchemeJsRuntime {
     pub data_path_folder: Option<PathBuf>,
     pub current_folder: PathBuf,
     pub engine: Arc<SchemeJsEngine>,
+    // Time a script can run for (`max_execution_time` under `[limits]`), no limit when `None`.
+    max_execution_time: Option<Duration>,
+    // Set when a script was terminated for reaching the heap limit.
+    heap_limit_reached: Arc<AtomicBool>,
+    // Set once `shutdown` flushed the engine, dropping the runtime flushes it otherwise.
+    shut_down: AtomicBool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +78,8 @@ impl SchemeJsRuntime {
 
         let config = SchemeJsConfig::new(config_file.clone())?;
 
+        init_logging(&config)?;
+
         let extensions: Vec<Extension> = vec![
             schemajs_primitives::sjs_primitives::init_ops(),
             schemajs_core::sjs_core::init_ops(),
@@ -70,10 +93,13 @@ impl SchemeJsRuntime {
             compiled_wasm_module_store: None,
             startup_snapshot: snapshot::snapshot(),
             module_loader: Some(Rc::new(TypescriptModuleLoader::default())),
+            create_params: create_params(&config.limits),
             ..Default::default()
         };
 
+        let max_execution_time = config.limits.max_execution_time()?;
         let mut js_runtime = JsRuntime::new(runtime_opts);
+        let heap_limit_reached = terminate_near_heap_limit(&mut js_runtime);
 
         // Bootstrapping Stage
         {
@@ -83,6 +109,9 @@ impl SchemeJsRuntime {
                 .expect("Failed to execute bootstrap script");
         }
 
+        // Shard files are opened while the databases are loaded
+        set_mmap_reads(config.storage.mmap_reads);
+
         let config_opts = WorkerRuntimeOpts::Main(MainWorkerRuntimeOpts { config });
         let mut engine = SchemeJsEngine::new(data_path.clone());
         Self::load(&config_opts, &mut js_runtime, &folder_path, &mut engine)
@@ -95,16 +124,142 @@ impl SchemeJsRuntime {
             let op_state_rc = js_runtime.op_state();
             let mut op_state = op_state_rc.borrow_mut();
             op_state.put::<Arc<SchemeJsEngine>>(engine.clone());
+
+            // Searches are dispatched to the query workers, if any
+            let WorkerRuntimeOpts::Main(conf) = &config_opts;
+            if conf.config.workers.query_workers > 0 {
+                op_state.put::<Arc<QueryWorkerPool>>(Arc::new(QueryWorkerPool::new(
+                    conf.config.workers.query_workers,
+                )));
+            }
         }
 
-        Ok(Self {
+        let mut runtime = Self {
             js_runtime,
             config: config_opts,
             config_file,
             current_folder: folder_path,
             engine,
             data_path_folder: data_path.clone(),
-        })
+            max_execution_time,
+            heap_limit_reached,
+            shut_down: AtomicBool::new(false),
+        };
+        runtime.set_principal(Principal::System);
+        runtime.run_migrations().await?;
+
+        Ok(runtime)
+    }
+
+    /// Applies the migrations of every database not applied yet, in order, recording them in its `_migrations` table.
+    /// Migrations are the modules of the `migrations` folder of a database, their default export being
+    /// the (possibly async) function to run. A failing migration stops the startup, the next ones aren't applied.
+    async fn run_migrations(&mut self) -> Result<()> {
+        let WorkerRuntimeOpts::Main(conf) = &self.config;
+        let databases = conf.config.workspace.databases.clone();
+        let engine = self.engine.clone();
+
+        for database_path in databases {
+            let path = self.current_folder.join(&database_path);
+            let db_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let db = match engine.find_by_name_ref(db_name.clone()) {
+                Some(db) => db,
+                None => continue,
+            };
+
+            for migration in pending_migrations(&db, list_migrations(&path)?)? {
+                tracing::info!(database = %db_name, version = %migration.version, "Applying migration");
+
+                let mod_id = self
+                    .js_runtime
+                    .load_side_es_module(&migration.specifier)
+                    .await?;
+                let _ = self.js_runtime.mod_evaluate(mod_id).await?;
+
+                let up = {
+                    let mod_scope = self.js_runtime.get_module_namespace(mod_id)?;
+                    let scope = &mut self.js_runtime.handle_scope();
+                    let mod_obj = mod_scope.open(scope).to_object(scope).unwrap();
+                    let default_function_key = v8::String::new(scope, "default").unwrap();
+                    let func_obj =
+                        mod_obj
+                            .get(scope, default_function_key.into())
+                            .ok_or_else(|| {
+                                anyhow!("Migration '{}' has no default export", migration.version)
+                            })?;
+                    let func = v8::Local::<v8::Function>::try_from(func_obj)?;
+                    v8::Global::new(scope, func)
+                };
+
+                self.js_runtime
+                    .call_and_await(&up)
+                    .await
+                    .map_err(|e| anyhow!("Migration '{}' failed: {}", migration.version, e))?;
+
+                record_migration(&db, &migration.version)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `code` in the runtime like `JsRuntime::execute_script`, within the limits of `SchemeJS.toml`.
+    /// Scripts running for longer than `max_execution_time` or reaching the heap limit are terminated and fail,
+    /// the runtime keeps working afterwards.
+    pub fn execute_script(
+        &mut self,
+        name: &'static str,
+        code: String,
+    ) -> Result<v8::Global<v8::Value>> {
+        let watchdog = self.max_execution_time.map(|timeout| {
+            ExecutionWatchdog::start(self.js_runtime.v8_isolate().thread_safe_handle(), timeout)
+        });
+
+        let result = self
+            .js_runtime
+            .execute_script(name, ModuleCodeString::from(code));
+
+        let timed_out = watchdog.map_or(false, |watchdog| watchdog.stop());
+        let heap_limit_reached = self.heap_limit_reached.swap(false, Ordering::SeqCst);
+
+        if timed_out || heap_limit_reached {
+            self.js_runtime.v8_isolate().cancel_terminate_execution();
+        }
+        if timed_out {
+            bail!("Script '{}' exceeded the maximum execution time", name);
+        }
+        if heap_limit_reached {
+            bail!("Script '{}' reached the heap limit", name);
+        }
+
+        result
+    }
+
+    /// Runs `code` like `execute_script` and waits for the promise it evaluates to, if any, returning the value
+    /// as JSON (`None` for `undefined`). Values that can't be represented in JSON are returned as their string.
+    /// Meant for interactive use, such as `sjs repl`.
+    pub async fn evaluate(&mut self, code: String) -> Result<Option<serde_json::Value>> {
+        let value = self.execute_script("[repl]", code)?;
+        let value = {
+            let resolve = self.js_runtime.resolve(value);
+            self.js_runtime
+                .with_event_loop_promise(resolve, PollEventLoopOptions::default())
+                .await?
+        };
+
+        let scope = &mut self.js_runtime.handle_scope();
+        let value = v8::Local::new(scope, value);
+        if value.is_undefined() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            serde_v8::from_v8::<serde_json::Value>(scope, value)
+                .unwrap_or_else(|_| serde_json::Value::String(value.to_rust_string_lossy(scope))),
+        ))
     }
 
     pub async fn load(
@@ -116,18 +271,73 @@ impl SchemeJsRuntime {
         match &config {
             WorkerRuntimeOpts::Main(conf) => {
                 let databases = conf.config.workspace.databases.clone();
+                let max_execution_time = conf.config.limits.max_execution_time()?;
 
                 for database_path in databases {
                     let path = current_folder.join(&database_path);
-                    let (scheme_name, table_specifiers) = engine.load_database_schema(&path)?;
+                    let database_name = path.file_name().and_then(|name| name.to_str());
+                    let encryption =
+                        database_name.and_then(|name| conf.config.encryption_key(name));
+                    let storage_quota =
+                        database_name.and_then(|name| conf.config.storage_quota(name));
+                    let (scheme_name, table_specifiers) =
+                        engine.load_database_schema(&path, encryption, storage_quota)?;
+                    if let Some(db) = engine.find_by_name_ref(scheme_name.clone()) {
+                        db.query_manager.set_slow_query_threshold(
+                            conf.config.slow_query_threshold(&scheme_name)?,
+                        );
+                        if let Some(cdc) = &conf.config.cdc {
+                            CdcLog::enable(&db, cdc.fsync)?;
+                        }
+                        // Leaders keep the last changes, so followers can catch up from them
+                        if let Some(replication) = &conf.config.replication {
+                            if replication.role == ReplicationRole::Leader {
+                                db.query_manager
+                                    .changes()
+                                    .set_history_size(replication.log_size);
+                            }
+                        }
+                    }
                     let mut tables = vec![];
+                    let mut hooks = vec![];
                     for table_specifier in table_specifiers {
-                        let (_, _, tbl) =
-                            Self::load_table(js_runtime, table_specifier).await.unwrap();
-                        tables.push(tbl);
+                        let (_, _, mut tbl, table_hooks) =
+                            Self::load_table(js_runtime, table_specifier, max_execution_time)
+                                .await?;
+                        if let Some(table_hooks) = table_hooks {
+                            hooks.push((tbl.name.clone(), table_hooks));
+                        }
+                        // A retention in `SchemeJS.toml` takes precedence over the TTL of the table
+                        if let Some(retention) = conf.config.table_retention(&tbl.name)? {
+                            tbl = tbl.set_ttl(&retention.column, retention.retention);
+                        }
+                        let storage = conf.config.table_storage(&tbl.name)?;
+                        tables.push((tbl, storage));
+                    }
+                    // Registered before running the migrations, so the ones already applied are known
+                    if !list_migrations(&path)?.is_empty() {
+                        tables.push((migrations_table(), Default::default()));
+                    }
+
+                    engine.register_tables(scheme_name.as_str(), tables)?;
+
+                    for (table_name, table_hooks) in hooks {
+                        Self::register_hooks(
+                            js_runtime,
+                            &scheme_name,
+                            &table_name,
+                            Some(table_hooks),
+                        )?;
                     }
+                }
 
-                    engine.register_tables(scheme_name.as_str(), tables);
+                // Enabled once the workspace is loaded, the system databases aren't part of it
+                engine.enable_information_schema()?;
+                if let Some(access) = &conf.config.access {
+                    let access_control = engine.enable_access_control()?;
+                    if let Some(admin) = &access.admin {
+                        access_control.bootstrap_admin(admin)?;
+                    }
                 }
 
                 Ok(())
@@ -135,14 +345,155 @@ impl SchemeJsRuntime {
         }
     }
 
+    /// Stops the tasks of `manager`, if any, then flushes the rows buffered in temporary shards and waits until the
+    /// files of every database are on disk (see `SchemeJsEngine::shutdown`). Dropping the runtime without calling it
+    /// shuts the engine down as well, logging failures instead of returning them.
+    pub fn shutdown(&self, manager: Option<&SchemeJsManager>) -> Result<()> {
+        if let Some(manager) = manager {
+            manager.stop_tasks();
+        }
+
+        self.shut_down.store(true, Ordering::SeqCst);
+        self.engine.shutdown()
+    }
+
+    /// Runs the ops called from now on for `principal`, checked against the users and roles of the engine
+    /// when `[access]` is configured (see `SchemeJsEngine::authorize`). Runtimes are created for `Principal::System`,
+    /// scripts of the workspace being allowed everything.
+    pub fn set_principal(&mut self, principal: Principal) {
+        self.js_runtime.op_state().borrow_mut().put(principal);
+    }
+
+    /// Authenticator of the clients of the servers when `SchemeJS.toml` has an `[auth]` section.
+    fn authenticator(&self) -> Option<Arc<Authenticator>> {
+        let WorkerRuntimeOpts::Main(conf) = &self.config;
+        conf.config
+            .auth
+            .as_ref()
+            .map(|auth| Arc::new(Authenticator::new(auth)))
+    }
+
+    /// Starts serving the query API over HTTP when `SchemeJS.toml` has an `[http]` section (see `schemajs_http::server::serve`),
+    /// returning the task of the server.
+    pub fn serve_http(&self) -> Option<tokio::task::JoinHandle<Result<()>>> {
+        let WorkerRuntimeOpts::Main(conf) = &self.config;
+        let http = conf.config.http.clone()?;
+
+        Some(tokio::spawn(schemajs_http::server::serve(
+            self.engine.clone(),
+            http,
+            self.authenticator(),
+        )))
+    }
+
+    /// Starts serving the query API over gRPC when `SchemeJS.toml` has a `[grpc]` section (see `schemajs_grpc::server::serve`),
+    /// returning the task of the server.
+    pub fn serve_grpc(&self) -> Option<tokio::task::JoinHandle<Result<()>>> {
+        let WorkerRuntimeOpts::Main(conf) = &self.config;
+        let grpc = conf.config.grpc.clone()?;
+
+        Some(tokio::spawn(schemajs_grpc::server::serve(
+            self.engine.clone(),
+            grpc,
+            self.authenticator(),
+        )))
+    }
+
+    /// Starts replicating the databases of the leader when `SchemeJS.toml` has a `[replication]` section with
+    /// `role = "follower"` (see `schemajs_grpc::replication::follow`), returning the task of the follower.
+    /// Leaders stream their changes through their gRPC server.
+    pub fn replicate(&self) -> Option<tokio::task::JoinHandle<Result<()>>> {
+        let WorkerRuntimeOpts::Main(conf) = &self.config;
+        let replication = conf.config.replication.clone()?;
+        if replication.role != ReplicationRole::Follower {
+            return None;
+        }
+
+        Some(tokio::spawn(schemajs_grpc::replication::follow(
+            self.engine.clone(),
+            replication,
+        )))
+    }
+
+    /// Evaluates the table module at `path` again and applies the changes of its table (added or dropped columns
+    /// and indexes, see `SingleQueryManager::reload_table`) without restarting the runtime. The hooks of the table
+    /// are replaced by the ones of the module.
+    ///
+    /// `path` is the module of a table of a loaded database (`<database>/tables/<table>.ts`).
+    /// Changes that can't be applied (the type of a column or the primary key changed) fail, leaving the table as it was.
+    pub async fn reload_table(&mut self, path: &Path) -> Result<SchemaChange> {
+        let path = path.canonicalize()?;
+        let db_name = path
+            .parent()
+            .and_then(|tables| tables.parent())
+            .and_then(|db| db.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("'{}' is not a table module", path.display()))?;
+
+        let engine = self.engine.clone();
+        let db = engine
+            .find_by_name_ref(db_name.clone())
+            .ok_or_else(|| anyhow!("Database '{}' is not loaded", db_name))?;
+
+        // Modules are only evaluated once per specifier, the query makes it a new one
+        let mut specifier = ModuleSpecifier::from_file_path(&path)
+            .map_err(|_| anyhow!("'{}' is not a table module", path.display()))?;
+        specifier.set_query(Some(&format!("reload={}", Uuid::new_v4())));
+
+        let (_, _, mut tbl, hooks) =
+            Self::load_table(&mut self.js_runtime, specifier, self.max_execution_time).await?;
+
+        let WorkerRuntimeOpts::Main(conf) = &self.config;
+        if let Some(retention) = conf.config.table_retention(&tbl.name)? {
+            tbl = tbl.set_ttl(&retention.column, retention.retention);
+        }
+
+        let table_name = tbl.name.clone();
+        let change = db.query_manager.reload_table(tbl)?;
+        Self::register_hooks(&mut self.js_runtime, &db_name, &table_name, hooks)?;
+
+        Ok(change)
+    }
+
+    /// Evaluates the table module at `specifier`, terminating it if it runs for longer than `max_execution_time`.
     async fn load_table(
         js_runtime: &mut JsRuntime,
         specifier: ModuleSpecifier,
-    ) -> Result<(ModuleSpecifier, ModuleId, Table)> {
+        max_execution_time: Option<Duration>,
+    ) -> Result<(
+        ModuleSpecifier,
+        ModuleId,
+        Table,
+        Option<v8::Global<v8::Value>>,
+    )> {
+        let watchdog = max_execution_time.map(|timeout| {
+            ExecutionWatchdog::start(js_runtime.v8_isolate().thread_safe_handle(), timeout)
+        });
+
+        let table = Self::read_table(js_runtime, specifier).await;
+
+        if watchdog.map_or(false, |watchdog| watchdog.stop()) {
+            js_runtime.v8_isolate().cancel_terminate_execution();
+            bail!("Table module exceeded the maximum execution time");
+        }
+
+        table
+    }
+
+    /// Evaluates the table module at `specifier`, returning the table it defines along with its `hooks`, if any.
+    async fn read_table(
+        js_runtime: &mut JsRuntime,
+        specifier: ModuleSpecifier,
+    ) -> Result<(
+        ModuleSpecifier,
+        ModuleId,
+        Table,
+        Option<v8::Global<v8::Value>>,
+    )> {
         let mod_id = js_runtime.load_side_es_module(&specifier).await?;
         let _ = js_runtime.mod_evaluate(mod_id).await?;
 
-        let mut table = {
+        let (mut table, hooks) = {
             let mod_scope = js_runtime.get_module_namespace(mod_id)?;
             let scope = &mut js_runtime.handle_scope();
             {
@@ -152,9 +503,10 @@ impl SchemeJsRuntime {
                 let func = v8::Local::<v8::Function>::try_from(func_obj)?;
                 let undefined = v8::undefined(scope);
 
-                /// TODO: Handle this error
-                let mut exc = func.call(scope, undefined.into(), &[]).unwrap(); /*
-                                                                                .ok_or_else(Error::msg("Table could not be read"))?*/
+                // Calls only fail when the execution is terminated (e.g. by the limits of the runtime)
+                let mut exc = func
+                    .call(scope, undefined.into(), &[])
+                    .ok_or_else(|| Error::msg("Table could not be read"))?;
 
                 let is_promise = exc.is_promise();
 
@@ -168,7 +520,15 @@ impl SchemeJsRuntime {
                     }
                 }
 
-                deno_core::serde_v8::from_v8::<Table>(scope, exc)?
+                // Hooks are JS functions, they stay in the runtime rather than being part of the table
+                let hooks_key = v8::String::new(scope, "hooks").unwrap();
+                let hooks = exc
+                    .to_object(scope)
+                    .and_then(|obj| obj.get(scope, hooks_key.into()))
+                    .filter(|hooks| hooks.is_object())
+                    .map(|hooks| v8::Global::new(scope, hooks));
+
+                (deno_core::serde_v8::from_v8::<Table>(scope, exc)?, hooks)
             }
         };
 
@@ -176,7 +536,54 @@ impl SchemeJsRuntime {
 
         table.metadata.set_module_id(mod_id);
 
-        Ok((specifier, mod_id, table))
+        Ok((specifier, mod_id, table, hooks))
+    }
+
+    /// Registers the `hooks` of a table module through `SchemeJS.registerHooks`, so inserts from JS call them.
+    /// Without `hooks`, the ones previously registered for the table are removed.
+    fn register_hooks(
+        js_runtime: &mut JsRuntime,
+        db_name: &str,
+        table_name: &str,
+        hooks: Option<v8::Global<v8::Value>>,
+    ) -> Result<()> {
+        let scope = &mut js_runtime.handle_scope();
+        let global = scope.get_current_context().global(scope);
+
+        let sjs_key = v8::String::new(scope, "SchemeJS").unwrap();
+        let sjs = global
+            .get(scope, sjs_key.into())
+            .and_then(|sjs| sjs.to_object(scope))
+            .ok_or_else(|| Error::msg("SchemeJS global is not available"))?;
+        let register_key = v8::String::new(scope, "registerHooks").unwrap();
+        let register = sjs
+            .get(scope, register_key.into())
+            .ok_or_else(|| Error::msg("SchemeJS.registerHooks is not available"))?;
+        let register = v8::Local::<v8::Function>::try_from(register)?;
+
+        let args = [
+            v8::String::new(scope, db_name).unwrap().into(),
+            v8::String::new(scope, table_name).unwrap().into(),
+            match hooks {
+                Some(hooks) => v8::Local::new(scope, hooks),
+                None => v8::undefined(scope).into(),
+            },
+        ];
+        register
+            .call(scope, sjs.into(), &args)
+            .ok_or_else(|| Error::msg("Hooks could not be registered"))?;
+
+        Ok(())
+    }
+}
+
+impl Drop for SchemeJsRuntime {
+    fn drop(&mut self) {
+        if !self.shut_down.load(Ordering::SeqCst) {
+            if let Err(e) = self.shutdown(None) {
+                tracing::error!(error = %e, "Failed to shut down the engine");
+            }
+        }
     }
 }
 
@@ -187,6 +594,8 @@ mod test {
     use crate::manager::SchemeJsManager;
     use crate::runtime::{SchemeJsRuntime, WorkerContextInitOpts};
     use deno_core::{located_script_name, serde_json, v8};
+    use schemajs_engine::access::Principal;
+    use schemajs_engine::migrations::applied_migrations;
     use std::path::PathBuf;
     use std::sync::Arc;
     use std::time::Duration;
@@ -274,7 +683,7 @@ mod test {
             manager.add_task(Task::new(
                 "1".to_string(),
                 Box::new(move |rt| {
-                    for x in rt.databases.iter() {
+                    for x in rt.databases().iter() {
                         let query_manager = &x.query_manager;
                         for table in query_manager.table_names.read().unwrap().iter() {
                             let table = query_manager.tables.get(table).unwrap();
@@ -319,6 +728,282 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    pub async fn test_runtime_limits() -> anyhow::Result<()> {
+        let mut rt = SchemeJsRuntime::new(WorkerContextInitOpts {
+            config_path: PathBuf::from("./test_cases/limits-db"),
+            data_path: None,
+        })
+        .await?;
+
+        let err = rt
+            .execute_script(located_script_name!(), "while (true) {}".to_string())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("maximum execution time"));
+
+        let err = rt
+            .execute_script(
+                located_script_name!(),
+                "const chunks = []; while (true) { chunks.push(new Array(100000).fill(1)); }"
+                    .to_string(),
+            )
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("heap limit"));
+
+        // The runtime keeps working once a script was terminated
+        let value = rt.execute_script(located_script_name!(), "1 + 1".to_string())?;
+        let scope = &mut rt.js_runtime.handle_scope();
+        let value = v8::Local::new(scope, value);
+        assert_eq!(value.integer_value(scope), Some(2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_runtime_hooks() -> anyhow::Result<()> {
+        let data_path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let mut rt = SchemeJsRuntime::new(WorkerContextInitOpts {
+            config_path: PathBuf::from("./test_cases/hooks-db"),
+            data_path: Some(data_path.clone()),
+        })
+        .await?;
+
+        rt.execute_script(
+            located_script_name!(),
+            r#"
+            globalThis.rejected = null;
+            SchemeJS.insert("public", "users", { id: "1", username: "ANDRES" });
+            SchemeJS.insert("public", "users", { id: "2", username: "" })
+                .catch((e) => globalThis.rejected = e.message);
+            "#
+            .to_string(),
+        )?;
+        rt.js_runtime.run_event_loop(Default::default()).await?;
+
+        let result = rt.execute_script(
+            located_script_name!(),
+            "({ inserted: globalThis.inserted.length, username: globalThis.inserted[0].username, rejected: globalThis.rejected })"
+                .to_string(),
+        )?;
+        let result: serde_json::Value = {
+            let scope = &mut rt.js_runtime.handle_scope();
+            let result = v8::Local::new(scope, result);
+            deno_core::serde_v8::from_v8(scope, result)?
+        };
+        assert_eq!(result["inserted"], 1);
+        assert!(result["rejected"]
+            .as_str()
+            .unwrap()
+            .contains("rejected by the beforeInsert hook"));
+
+        // Rows are inserted as changed by `beforeInsert`
+        assert_eq!(result["username"], "andres");
+
+        let db = rt.engine.find_by_name_ref("public".to_string()).unwrap();
+        let users = db.query_manager.tables.get("users").unwrap();
+        users.temps.reconcile_all();
+        assert_eq!(users.data.read().unwrap().len(), 1);
+
+        std::fs::remove_dir_all(data_path).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_runtime_evaluate() -> anyhow::Result<()> {
+        let data_path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let mut rt = SchemeJsRuntime::new(WorkerContextInitOpts {
+            config_path: PathBuf::from("./test_cases/default-db"),
+            data_path: Some(data_path.clone()),
+        })
+        .await?;
+
+        assert_eq!(rt.evaluate("1 + 1".to_string()).await?, Some(2.into()));
+
+        // Declarations are kept between evaluations
+        assert_eq!(rt.evaluate("const answer = 21;".to_string()).await?, None);
+        assert_eq!(
+            rt.evaluate("answer * 2".to_string()).await?,
+            Some(42.into())
+        );
+
+        // Promises are awaited
+        let uid = rt
+            .evaluate(r#"SchemeJS.insert("public", "users", { id: "1" })"#.to_string())
+            .await?
+            .unwrap();
+        assert!(Uuid::parse_str(uid.as_str().unwrap()).is_ok());
+        assert!(rt
+            .evaluate(r#"Promise.reject(new Error("boom"))"#.to_string())
+            .await
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("boom"));
+
+        // Ops are denied to runtimes without a principal
+        rt.js_runtime.op_state().borrow_mut().take::<Principal>();
+        assert!(rt
+            .evaluate(r#"SchemeJS.insert("public", "users", { id: "2" })"#.to_string())
+            .await
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("no principal"));
+
+        std::fs::remove_dir_all(data_path).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_runtime_create_database() -> anyhow::Result<()> {
+        let data_path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let mut rt = SchemeJsRuntime::new(WorkerContextInitOpts {
+            config_path: PathBuf::from("./test_cases/default-db"),
+            data_path: Some(data_path.clone()),
+        })
+        .await?;
+
+        rt.execute_script(
+            located_script_name!(),
+            r#"
+            globalThis.errors = [];
+            const { Table, Column } = SchemeJS;
+            (async () => {
+                await SchemeJS.createDatabase("analytics");
+                await SchemeJS.createTable("analytics", new Table("events").addColumn(new Column("name")));
+                await SchemeJS.insert("analytics", "events", { name: "signup" });
+
+                await SchemeJS.createDatabase("analytics").catch((e) => globalThis.errors.push(e.message));
+                await SchemeJS.createTable("analytics", new Table("events")).catch((e) => globalThis.errors.push(e.message));
+                await SchemeJS.createTable("unknown", new Table("events")).catch((e) => globalThis.errors.push(e.message));
+            })();
+            "#
+            .to_string(),
+        )?;
+        rt.js_runtime.run_event_loop(Default::default()).await?;
+
+        let result = rt.execute_script(located_script_name!(), "globalThis.errors".to_string())?;
+        let errors: Vec<String> = {
+            let scope = &mut rt.js_runtime.handle_scope();
+            let result = v8::Local::new(scope, result);
+            deno_core::serde_v8::from_v8(scope, result)?
+        };
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("already exists"));
+        assert!(errors[1].contains("already exists"));
+        assert!(errors[2].contains("Unknown database"));
+
+        let db = rt.engine.find_by_name_ref("analytics".to_string()).unwrap();
+        let events = db.query_manager.tables.get("events").unwrap();
+        assert!(events.table.get_column("_uid").is_some());
+        events.temps.reconcile_all();
+        assert_eq!(events.data.read().unwrap().len(), 1);
+
+        std::fs::remove_dir_all(data_path).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_runtime_migrations() -> anyhow::Result<()> {
+        let data_path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+
+        {
+            let rt = SchemeJsRuntime::new(WorkerContextInitOpts {
+                config_path: PathBuf::from("./test_cases/migrations-db"),
+                data_path: Some(data_path.clone()),
+            })
+            .await?;
+
+            let db = rt.engine.find_by_name_ref("public".to_string()).unwrap();
+            let applied = applied_migrations(&db)?;
+            assert!(applied.contains("0001_seed_users"));
+            assert!(applied.contains("0002_seed_products"));
+
+            let users = db.query_manager.tables.get("users").unwrap();
+            users.temps.reconcile_all();
+            assert_eq!(users.data.read().unwrap().len(), 1);
+        }
+
+        // Applied migrations don't run again
+        let mut rt = SchemeJsRuntime::new(WorkerContextInitOpts {
+            config_path: PathBuf::from("./test_cases/migrations-db"),
+            data_path: Some(data_path.clone()),
+        })
+        .await?;
+        let migrated = rt.execute_script(
+            located_script_name!(),
+            "globalThis.migrated === undefined".to_string(),
+        )?;
+        let scope = &mut rt.js_runtime.handle_scope();
+        assert!(v8::Local::new(scope, migrated).is_true());
+
+        std::fs::remove_dir_all(data_path).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_runtime_reload_table() -> anyhow::Result<()> {
+        let workspace = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let tables = workspace.join("public").join("tables");
+        std::fs::create_dir_all(&tables)?;
+        std::fs::write(
+            workspace.join("SchemeJS.toml"),
+            "[workspace]\ndatabases = [\"./public\"]\n",
+        )?;
+
+        let users = tables.join("users.ts");
+        std::fs::write(
+            &users,
+            r#"export default function main() {
+                const { Table, Column } = SchemeJS;
+                return new Table("users").addColumn(new Column("username").string());
+            }"#,
+        )?;
+
+        let data_path = workspace.join("data");
+        let mut rt = SchemeJsRuntime::new(WorkerContextInitOpts {
+            config_path: workspace.clone(),
+            data_path: Some(data_path),
+        })
+        .await?;
+
+        std::fs::write(
+            &users,
+            r#"export default function main() {
+                const { Table, Column } = SchemeJS;
+                return new Table("users")
+                    .addColumn(new Column("username").string())
+                    .addColumn(new Column("email").string());
+            }"#,
+        )?;
+        let change = rt.reload_table(&users).await?;
+        assert_eq!(change.added_columns, vec!["email".to_string()]);
+
+        let db = rt.engine.find_by_name_ref("public".to_string()).unwrap();
+        let table = db.query_manager.tables.get("users").unwrap().table.clone();
+        assert!(table.get_column("email").is_some());
+
+        // Changing the type of a column can't be applied
+        std::fs::write(
+            &users,
+            r#"export default function main() {
+                const { Table, Column } = SchemeJS;
+                return new Table("users").addColumn(new Column("username").number());
+            }"#,
+        )?;
+        assert!(rt.reload_table(&users).await.is_err());
+
+        std::fs::remove_dir_all(workspace).unwrap();
+
+        Ok(())
+    }
+
     #[tokio::test]
     pub async fn test_runtime_config_as_file() -> anyhow::Result<()> {
         let create_rt = SchemeJsRuntime::new(WorkerContextInitOpts {
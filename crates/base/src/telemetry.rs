@@ -0,0 +1,41 @@
+use anyhow::Result;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use schemajs_config::SchemeJsTelemetryConfig;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+/// Layer exporting the spans of the process to the OTLP collector of `config`: the ops of `SchemeJS`, the query
+/// manager, temporary shards and the reads and writes of main shards, nested so a single request can be broken
+/// down layer by layer. It is installed along with the logs by `init_logging`.
+///
+/// Spans are batched and sent from the Tokio runtime the call is made from.
+pub fn otlp_layer(
+    config: &SchemeJsTelemetryConfig,
+) -> Result<Box<dyn Layer<Registry> + Send + Sync>> {
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(config.otlp_endpoint.clone()),
+        )
+        .with_trace_config(trace::Config::default().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", config.service_name.clone()),
+        ])))
+        .install_batch(runtime::Tokio)?;
+
+    let tracer = provider.tracer(config.service_name.clone());
+    opentelemetry::global::set_tracer_provider(provider);
+
+    Ok(tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(EnvFilter::try_new(&config.level)?)
+        .boxed())
+}
+
+/// Sends the spans still waiting in the batch to the collector, before the process exits.
+pub fn shutdown_telemetry() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
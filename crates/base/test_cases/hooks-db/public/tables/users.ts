@@ -0,0 +1,17 @@
+export default function main() {
+    const { Table, Column } = SchemeJS;
+    return new Table("users")
+        .addColumn(new Column("id").string())
+        .addColumn(new Column("username").string())
+        .withHooks({
+            beforeInsert(row) {
+                if (!row.username) {
+                    return false;
+                }
+                return { ...row, username: row.username.toLowerCase() };
+            },
+            afterInsert(row) {
+                globalThis.inserted = [...(globalThis.inserted ?? []), row];
+            }
+        })
+}
@@ -0,0 +1,3 @@
+export default async function up() {
+    await SchemeJS.insert("public", "users", { id: "1", username: "admin", password: "admin" });
+}
@@ -0,0 +1,5 @@
+export default function main() {
+    const { Table, Column } = SchemeJS;
+    return new Table("products")
+        .addColumn(new Column("id").string())
+}
\ No newline at end of file
@@ -16,6 +16,18 @@ fn main() {
             data_path: None,
         }).await.unwrap();
 
+        // Runs as a standalone database server when `[http]` or `[grpc]` are configured, following a leader
+        // when `[replication]` says so
+        let servers: Vec<_> = rt
+            .serve_http()
+            .into_iter()
+            .chain(rt.serve_grpc())
+            .chain(rt.replicate())
+            .collect();
+        for server in servers {
+            server.await??;
+        }
+
         Ok(())
     });
 }
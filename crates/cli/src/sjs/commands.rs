@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Result};
+use base::manager::SchemeJsManager;
+use base::runtime::{SchemeJsRuntime, WorkerContextInitOpts};
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_query::sql::{self, SqlResult};
+use schemajs_workers::context::WorkerRuntimeOpts;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Loads the workspace at `config`, its databases being stored under `data`.
+pub async fn load(config: PathBuf, data: Option<PathBuf>) -> Result<SchemeJsRuntime> {
+    SchemeJsRuntime::new(WorkerContextInitOpts {
+        config_path: config,
+        data_path: data,
+    })
+    .await
+}
+
+pub async fn start(rt: SchemeJsRuntime) -> Result<()> {
+    let WorkerRuntimeOpts::Main(conf) = &rt.config;
+    let manager = SchemeJsManager::from_config(rt.engine.clone(), &conf.config)?;
+    manager.start_tasks();
+
+    let servers: Vec<_> = rt
+        .serve_http()
+        .into_iter()
+        .chain(rt.serve_grpc())
+        .chain(rt.replicate())
+        .collect();
+    let servers = async {
+        for server in servers {
+            server.await??;
+        }
+
+        // Without servers, the background tasks keep running until stopped
+        std::future::pending::<Result<()>>().await
+    };
+
+    let res = tokio::select! {
+        res = servers => res,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    let shutdown = rt.shutdown(Some(&manager));
+
+    res.and(shutdown)
+}
+
+/// Reads JS from stdin and prints what it evaluates to, see `SchemeJsRuntime::evaluate`. Lines ending with `\`
+/// continue on the next one. `.exit` (or the end of stdin) leaves.
+pub async fn repl(rt: &mut SchemeJsRuntime) -> Result<()> {
+    let databases: Vec<String> = rt
+        .engine
+        .databases()
+        .iter()
+        .map(|db| db.name.clone())
+        .collect();
+    println!("SchemeJS REPL, databases: {}", databases.join(", "));
+    println!("Promises are awaited, e.g. SchemeJS.search(\"public\", \"users\", {{}})");
+
+    let stdin = std::io::stdin();
+    let mut code = String::new();
+    loop {
+        print!("{}", if code.is_empty() { "sjs> " } else { "...> " });
+        std::io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let line = line.trim_end();
+        if code.is_empty() && line.trim() == ".exit" {
+            return Ok(());
+        }
+        if let Some(line) = line.strip_suffix('\\') {
+            code.push_str(line);
+            code.push('\n');
+            continue;
+        }
+        code.push_str(line);
+
+        if !code.trim().is_empty() {
+            match rt.evaluate(std::mem::take(&mut code)).await {
+                Ok(Some(value)) => println!("{}", serde_json::to_string_pretty(&value)?),
+                Ok(None) => println!("undefined"),
+                Err(e) => eprintln!("error: {:#}", e),
+            }
+        }
+        code.clear();
+    }
+}
+
+pub fn list_tables(rt: &SchemeJsRuntime, database: Option<&str>) -> Result<()> {
+    let databases: Vec<_> = rt
+        .engine
+        .databases()
+        .into_iter()
+        .filter(|db| database.map_or(true, |name| db.name == name))
+        .collect();
+    if let Some(name) = database {
+        if databases.is_empty() {
+            return Err(anyhow!("Unknown database '{}'", name));
+        }
+    }
+
+    for db in databases {
+        let mut tables = db.query_manager.table_names.read().unwrap().clone();
+        tables.sort();
+        for table in tables {
+            println!("{}.{}", db.name, table);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn query(
+    rt: &SchemeJsRuntime,
+    database: &str,
+    table: &str,
+    filter: Option<&str>,
+) -> Result<()> {
+    let db = rt
+        .engine
+        .find_by_name_ref(database.to_string())
+        .ok_or_else(|| anyhow!("Unknown database '{}'", database))?;
+    let table_def = db
+        .query_manager
+        .tables
+        .get(table)
+        .map(|table_shard| table_shard.table.clone())
+        .ok_or_else(|| anyhow!("Unknown table '{}'", table))?;
+
+    let filter = match filter {
+        Some(filter) => serde_json::from_str(filter)?,
+        None => serde_json::Value::Null,
+    };
+    let ops = QueryOps::from_json(&table_def, &filter)?;
+
+    for row in db
+        .query_manager
+        .search_manager()
+        .search(table.to_string(), &ops)?
+    {
+        println!("{}", row.to_json(&table_def));
+    }
+
+    Ok(())
+}
+
+pub fn sql(rt: &SchemeJsRuntime, database: &str, statement: &str) -> Result<()> {
+    let db = rt
+        .engine
+        .find_by_name_ref(database.to_string())
+        .ok_or_else(|| anyhow!("Unknown database '{}'", database))?;
+
+    match sql::parse(statement)?.execute(&db.query_manager)? {
+        SqlResult::Rows(rows) => {
+            for row in rows {
+                println!("{}", row);
+            }
+        }
+        SqlResult::Inserted(uids) => {
+            for uid in uids {
+                println!("{}", uid);
+            }
+        }
+        SqlResult::Affected(count) => println!("{} rows affected", count),
+    }
+
+    Ok(())
+}
+
+pub fn backup(rt: &SchemeJsRuntime, database: &str, dest: &Path) -> Result<()> {
+    rt.engine.backup(database, dest)?;
+    println!("Backed up '{}' to {}", database, dest.display());
+
+    Ok(())
+}
+
+pub fn restore(rt: &SchemeJsRuntime, src: &Path) -> Result<()> {
+    let database = rt.engine.restore(src)?;
+    println!("Restored '{}' from {}", database, src.display());
+
+    Ok(())
+}
+
+pub fn compact(rt: &SchemeJsRuntime, database: &str, table: Option<&str>) -> Result<()> {
+    let db = rt
+        .engine
+        .find_by_name_ref(database.to_string())
+        .ok_or_else(|| anyhow!("Unknown database '{}'", database))?;
+
+    let tables = match table {
+        Some(table) => vec![table.to_string()],
+        None => db.query_manager.table_names.read().unwrap().clone(),
+    };
+    for table in tables {
+        let dropped = db.query_manager.compact_table(&table)?;
+        println!("{}.{}: {} dead rows dropped", database, table, dropped);
+    }
+
+    Ok(())
+}
+
+pub fn fsck(rt: &SchemeJsRuntime, database: &str, table: Option<&str>, repair: bool) -> Result<()> {
+    let db = rt
+        .engine
+        .find_by_name_ref(database.to_string())
+        .ok_or_else(|| anyhow!("Unknown database '{}'", database))?;
+
+    let tables = match table {
+        Some(table) => vec![table.to_string()],
+        None => db.query_manager.table_names.read().unwrap().clone(),
+    };
+    let mut unrepaired = 0;
+    for table in tables {
+        let report = db.check_integrity(&table, repair)?;
+        println!(
+            "{}.{}: {} rows, {} index entries, {} issues",
+            database,
+            table,
+            report.rows,
+            report.index_entries,
+            report.issues.len()
+        );
+        for issue in &report.issues {
+            println!("  {}", serde_json::to_string(issue)?);
+        }
+        for index in &report.repaired_indexes {
+            println!("  index '{}' rebuilt", index);
+        }
+
+        // Rebuilding indexes doesn't fix the rows themselves
+        unrepaired += report
+            .issues
+            .iter()
+            .filter(|issue| !repair || issue.index().is_none())
+            .count();
+    }
+
+    if unrepaired > 0 {
+        return Err(anyhow!("{} issues left unrepaired", unrepaired));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+mod commands;
+
+use clap::{Parser, Subcommand};
+use schemajs_engine::auth::hash_password;
+use std::path::PathBuf;
+
+/// Administration of a SchemeJS workspace: runs it as a server or operates on its databases.
+#[derive(Debug, Parser)]
+#[command(name = "sjs", version)]
+struct Cli {
+    /// Folder of the workspace, or its `SchemeJS.toml`.
+    #[arg(long, global = true, default_value = ".")]
+    config: PathBuf,
+
+    /// Folder the databases are stored in, the global SchemeJS folder unless set.
+    #[arg(long, global = true)]
+    data: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Loads the workspace and serves it (HTTP and gRPC, when configured) with its background tasks until stopped.
+    Start,
+
+    /// Interactive JS shell over the workspace, with the `SchemeJS` global at hand.
+    Repl,
+
+    /// Tables of the databases of the workspace.
+    Tables {
+        #[command(subcommand)]
+        command: TablesCommand,
+    },
+
+    /// Prints the rows of a table matched by a filter, one JSON object per line.
+    Query {
+        database: String,
+        table: String,
+        /// Query as JSON, written as for the JS API (e.g. `{"key":"user_name","filterType":"=","value":"andres"}`).
+        /// Every row unless set.
+        #[arg(long)]
+        filter: Option<String>,
+    },
+
+    /// Runs a SQL statement (`SELECT`, `INSERT`, `UPDATE` or `DELETE`) on a database and prints its result,
+    /// selected rows one JSON object per line.
+    Sql { database: String, statement: String },
+
+    /// Archives a database into a gzipped tarball.
+    Backup { database: String, dest: PathBuf },
+
+    /// Restores a database archived by `backup`, replacing its tables.
+    Restore { src: PathBuf },
+
+    /// Drops the dead rows (deleted, expired or previous versions) of the tables of a database.
+    Compact {
+        database: String,
+        /// Only compacts this table.
+        table: Option<String>,
+    },
+
+    /// Checks the rows and indexes of the tables of a database, printing the issues found. Fails when issues
+    /// are left unrepaired.
+    Fsck {
+        database: String,
+        /// Only checks this table.
+        table: Option<String>,
+        /// Rebuilds the indexes holding issues.
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Prints the hash of a password, for the `[[auth.passwords]]` of `SchemeJS.toml`.
+    HashPassword { password: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum TablesCommand {
+    /// Lists the tables of every database, or of one database.
+    List { database: Option<String> },
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    // Doesn't need the workspace
+    if let Command::HashPassword { password } = &cli.command {
+        println!("{}", hash_password(password));
+        return;
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .thread_name("sjs-main")
+        .build()
+        .unwrap();
+
+    // The JS runtime of the workspace can't leave the thread it was created on
+    let local = tokio::task::LocalSet::new();
+    let res = local.block_on(&runtime, async {
+        let mut rt = commands::load(cli.config, cli.data).await?;
+
+        let res = match cli.command {
+            Command::Start => return commands::start(rt).await,
+            Command::Repl => commands::repl(&mut rt).await,
+            Command::Tables {
+                command: TablesCommand::List { database },
+            } => commands::list_tables(&rt, database.as_deref()),
+            Command::Query {
+                database,
+                table,
+                filter,
+            } => commands::query(&rt, &database, &table, filter.as_deref()),
+            Command::Sql {
+                database,
+                statement,
+            } => commands::sql(&rt, &database, &statement),
+            Command::Backup { database, dest } => commands::backup(&rt, &database, &dest),
+            Command::Restore { src } => commands::restore(&rt, &src),
+            Command::Compact { database, table } => {
+                commands::compact(&rt, &database, table.as_deref())
+            }
+            Command::Fsck {
+                database,
+                table,
+                repair,
+            } => commands::fsck(&rt, &database, table.as_deref(), repair),
+            Command::HashPassword { .. } => unreachable!(),
+        };
+
+        // Rows written by the command may still be buffered in temporary shards
+        res.and(rt.shutdown(None))
+    });
+
+    if let Err(e) = res {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,15 +1,539 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+use schemajs_data::compression::Compression;
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::shard::flush_policy::FlushPolicy;
+use schemajs_data::shard::shards::data_shard::config::{RowEncoding, TableStorage};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SchemeJsWorkspace {
     pub databases: Vec<String>,
 }
 
+/// Storage settings of a table, under `[tables.<table name>]`.
+/// They apply to the tables with that name in every database of the workspace.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemeJsTableConfig {
+    /// Compression of the rows (`"none"`, `"lz4"` or `"zstd"`), only applied to the shards created from now on.
+    #[serde(default)]
+    pub compression: Compression,
+    /// Size in bytes a shard file of the table can reach before rows go to a new shard. No limit when unset.
+    #[serde(default)]
+    pub max_shard_size: Option<u64>,
+    /// Encoding of the rows (`"json"` or `"compact"`), only applied to the rows written from now on.
+    #[serde(default)]
+    pub encoding: RowEncoding,
+    /// How long rows are kept (`"30d"`, see `parse_duration`). Older rows are dropped and the table compacted.
+    #[serde(default)]
+    pub retention: Option<String>,
+    /// Timestamp column the age of a row is measured from, `_created_at` unless set.
+    #[serde(default)]
+    pub retention_column: Option<String>,
+    /// Rows inserted rows are buffered up to before being flushed into the table.
+    /// Follows the insert rate when unset, see `FlushPolicy`.
+    #[serde(default)]
+    pub flush_rows: Option<u64>,
+    /// Size in bytes inserted rows are buffered up to before being flushed into the table (16 MiB unless set).
+    #[serde(default)]
+    pub flush_bytes: Option<u64>,
+    /// How long inserted rows are buffered for at most before being flushed into the table (`"5s"` unless set,
+    /// see `parse_duration`).
+    #[serde(default)]
+    pub flush_age: Option<String>,
+}
+
+/// Column the retention of a table is measured from when `retention_column` is not set.
+pub const DEFAULT_RETENTION_COLUMN: &str = "_created_at";
+
+/// Retention of the rows of a table, as configured under `[tables.<table name>]`.
+///
+/// # Fields:
+/// - `column`: Timestamp column the age of a row is measured from.
+/// - `retention`: How long rows are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRetention {
+    pub column: String,
+    pub retention: Duration,
+}
+
+/// Parses durations such as `"30d"`: an amount followed by its unit, one of `ms`, `s`, `m`, `h`, `d` or `w`.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("Duration '{}' has no unit", value))?;
+    let (amount, unit) = value.split_at(unit_start);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| anyhow!("Duration '{}' has no amount", value))?;
+
+    let seconds = match unit {
+        "ms" => return Ok(Duration::from_millis(amount)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!("Unknown unit '{}' in duration '{}'", unit, value),
+    };
+
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+/// Prefix of the environment variables holding the encryption key of a database,
+/// followed by its name in uppercase (`SCHEMEJS_ENCRYPTION_KEY_PUBLIC`).
+pub const ENCRYPTION_KEY_ENV_PREFIX: &str = "SCHEMEJS_ENCRYPTION_KEY_";
+
+/// Settings of a database, under `[databases.<database name>]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemeJsDatabaseConfig {
+    /// Secret the files of the database are encrypted with. Databases without one are stored in plaintext.
+    #[serde(default)]
+    pub encryption_key: Option<String>,
+    /// Size in bytes the database can reach. Inserts fail with `QuotaExceeded` past it, no limit when unset.
+    #[serde(default)]
+    pub storage_quota: Option<u64>,
+    /// Searches and mutations taking this long or longer (e.g. `"100ms"`) are logged, nothing is logged when unset.
+    #[serde(default)]
+    pub slow_query_threshold: Option<String>,
+}
+
+/// Window of the day, in UTC, parsed from `"HH:MM-HH:MM"`. Windows ending before they start wrap around midnight.
+///
+/// # Fields:
+/// - `start`: Time of the day the window opens at.
+/// - `end`: Time of the day the window closes at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWindow {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl TimeWindow {
+    pub fn parse(value: &str) -> Result<Self> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| anyhow!("Time window '{}' must look like HH:MM-HH:MM", value))?;
+
+        Ok(Self {
+            start: Self::parse_time(start)?,
+            end: Self::parse_time(end)?,
+        })
+    }
+
+    fn parse_time(value: &str) -> Result<Duration> {
+        let value = value.trim();
+        let (hours, minutes) = value
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Time '{}' must look like HH:MM", value))?;
+        let hours: u64 = hours.parse()?;
+        let minutes: u64 = minutes.parse()?;
+
+        if hours > 24 || minutes > 59 || (hours == 24 && minutes > 0) {
+            bail!("Time '{}' is not a time of the day", value);
+        }
+
+        Ok(Duration::from_secs(hours * 60 * 60 + minutes * 60))
+    }
+
+    /// Whether `time_of_day` (elapsed since midnight) falls in the window.
+    pub fn contains(&self, time_of_day: Duration) -> bool {
+        match self.start <= self.end {
+            true => self.start <= time_of_day && time_of_day < self.end,
+            false => time_of_day >= self.start || time_of_day < self.end,
+        }
+    }
+}
+
+fn default_compaction_interval() -> String {
+    "1h".to_string()
+}
+
+/// Settings of the background compaction of tables, under `[compaction]`. Tables are only compacted in the
+/// background when the section is present.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsCompactionConfig {
+    /// How often tables are looked at (`"1h"` unless set, see `parse_duration`).
+    #[serde(default = "default_compaction_interval")]
+    pub interval: String,
+    /// Windows of the day compaction may run in (`["01:00-05:00"]`, see `TimeWindow`). Any time of the day when empty.
+    #[serde(default)]
+    pub off_peak: Vec<String>,
+    /// Share of dead rows (from 0 to 1) a table must hold before it is compacted. Any dead row is enough unless set.
+    #[serde(default)]
+    pub min_dead_ratio: f64,
+    /// Cron expression of when tables are looked at (`"0 3 * * *"`, in UTC), taking precedence over `interval`.
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Runs are delayed by a random duration up to it (e.g. `"10m"`, see `parse_duration`), so nodes sharing
+    /// a schedule don't compact at once.
+    #[serde(default)]
+    pub jitter: Option<String>,
+}
+
+impl SchemeJsCompactionConfig {
+    pub fn interval(&self) -> Result<Duration> {
+        parse_duration(&self.interval)
+    }
+
+    pub fn jitter(&self) -> Result<Duration> {
+        self.jitter
+            .as_deref()
+            .map_or(Ok(Duration::ZERO), parse_duration)
+    }
+
+    pub fn off_peak_windows(&self) -> Result<Vec<TimeWindow>> {
+        self.off_peak
+            .iter()
+            .map(|window| TimeWindow::parse(window))
+            .collect()
+    }
+}
+
+/// Limits of the JS runtime, under `[limits]`, so a runaway table module or script can't take down the whole
+/// process. Nothing is limited unless configured.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemeJsLimitsConfig {
+    /// Size in bytes the heap of the runtime starts with, V8 picks it when unset.
+    #[serde(default)]
+    pub initial_heap_size: Option<usize>,
+    /// Size in bytes the heap of the runtime can grow to. Scripts reaching it are terminated.
+    #[serde(default)]
+    pub max_heap_size: Option<usize>,
+    /// Time a script, or the evaluation of a table module, can run for (e.g. `"5s"`, see `parse_duration`).
+    #[serde(default)]
+    pub max_execution_time: Option<String>,
+}
+
+impl SchemeJsLimitsConfig {
+    pub fn max_execution_time(&self) -> Result<Option<Duration>> {
+        self.max_execution_time
+            .as_deref()
+            .map(parse_duration)
+            .transpose()
+    }
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+/// Format of the log lines.
+///
+/// - `Text`: Human readable lines (`2024-05-01T10:00:00Z  INFO schemajs_engine::engine: Loading database schema database="public"`).
+/// - `Json`: One JSON object per line, with the fields of the event as keys, for log aggregators.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Settings of the logs of the engine, under `[logging]`. Logs are written to stdout at the `info` level
+/// unless configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsLoggingConfig {
+    /// Level of the logs of every crate without a level of its own (`"info"` unless set).
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// Level of the logs of specific crates, e.g. `{ schemajs_data = "debug" }`.
+    #[serde(default)]
+    pub crates: HashMap<String, String>,
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+impl Default for SchemeJsLoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            crates: HashMap::new(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+impl SchemeJsLoggingConfig {
+    /// Levels as filter directives (`info,schemajs_data=debug`), crates sorted by name.
+    pub fn directives(&self) -> String {
+        let mut crates: Vec<_> = self.crates.iter().collect();
+        crates.sort();
+
+        std::iter::once(self.level.clone())
+            .chain(
+                crates
+                    .into_iter()
+                    .map(|(name, level)| format!("{}={}", name, level)),
+            )
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+fn default_service_name() -> String {
+    "schemajs".to_string()
+}
+
+fn default_trace_level() -> String {
+    "info".to_string()
+}
+
+/// Settings of the tracing of requests, under `[telemetry]`. Spans are only recorded and exported when the section
+/// is present.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsTelemetryConfig {
+    /// Endpoint of the OTLP (gRPC) collector spans are exported to, e.g. `"http://localhost:4317"`.
+    pub otlp_endpoint: String,
+    /// Name the spans are reported under (`"schemajs"` unless set).
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Most verbose level of the recorded spans (`"info"` unless set). `"debug"` adds the temporary shards,
+    /// `"trace"` every read of a shard.
+    #[serde(default = "default_trace_level")]
+    pub level: String,
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_http_port() -> u16 {
+    7070
+}
+
+fn default_max_body_size() -> usize {
+    16 * 1024 * 1024
+}
+
+/// Settings of the HTTP server exposing the query API, under `[http]`. The server only runs when the section
+/// is present.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsHttpConfig {
+    /// Address the server listens on (`"127.0.0.1"` unless set).
+    #[serde(default = "default_host")]
+    pub host: String,
+    /// Port the server listens on (`7070` unless set).
+    #[serde(default = "default_http_port")]
+    pub port: u16,
+    /// Size in bytes a request body can have (16 MiB unless set). Larger requests are rejected.
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// Serves requests without an `[auth]` section, running them for the engine itself (`false` unless set).
+    /// The server refuses to start without `[auth]` otherwise.
+    #[serde(default)]
+    pub allow_anonymous: bool,
+}
+
+impl SchemeJsHttpConfig {
+    /// Address to bind, as `host:port`.
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+fn default_grpc_port() -> u16 {
+    50051
+}
+
+/// Settings of the gRPC server exposing the query API (see `proto/schemajs.proto` of `schemajs_grpc`), under `[grpc]`.
+/// The server only runs when the section is present.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsGrpcConfig {
+    /// Address the server listens on (`"127.0.0.1"` unless set).
+    #[serde(default = "default_host")]
+    pub host: String,
+    /// Port the server listens on (`50051` unless set).
+    #[serde(default = "default_grpc_port")]
+    pub port: u16,
+    /// Serves requests without an `[auth]` section, running them for the engine itself (`false` unless set).
+    /// The server refuses to start without `[auth]` otherwise.
+    #[serde(default)]
+    pub allow_anonymous: bool,
+}
+
+impl SchemeJsGrpcConfig {
+    /// Address to bind, as `host:port`.
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+fn default_replication_log_size() -> usize {
+    10_000
+}
+
+/// Role of a node in replication.
+///
+/// - `Leader`: Keeps a log of the last changes of its databases, streamed to its followers by its gRPC server.
+/// - `Follower`: Applies the changes of the databases of its leader to its own databases, which must hold the same tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationRole {
+    Leader,
+    Follower,
+}
+
+/// Settings of the asynchronous replication of the databases, under `[replication]`. Databases are only replicated
+/// when the section is present.
+///
+/// Followers catch up from the log of their leader when reconnecting, or copy every row of the leader when
+/// they fell too far behind (or the leader restarted). Followers don't reject writes, clients are meant to only read from them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsReplicationConfig {
+    pub role: ReplicationRole,
+    /// Endpoint of the gRPC server of the leader (e.g. `"http://10.0.0.1:50051"`), required for followers.
+    #[serde(default)]
+    pub leader: Option<String>,
+    /// Credentials followers send to their leader when it authenticates its clients (see `[auth]`), as the value
+    /// of the `Authorization` header (e.g. `"Bearer <token>"`).
+    #[serde(default)]
+    pub authorization: Option<String>,
+    /// Amount of changes of every database kept by the leader (`10000` unless set), the ones followers can catch up from.
+    #[serde(default = "default_replication_log_size")]
+    pub log_size: usize,
+}
+
+/// Settings of the change data capture of the databases, under `[cdc]`. Changes are only written to the change log
+/// of every database (`<database>/cdc/changes.jsonl`) when the section is present.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemeJsCdcConfig {
+    /// Whether every change is flushed to disk before the write returns. Slower writes, but no change written
+    /// before a crash is lost.
+    #[serde(default)]
+    pub fsync: bool,
+}
+
+/// Settings of the access control of the engine, under `[access]`. Operations are only checked against the users
+/// and roles of the `_system` database when the section is present, scripts of the workspace being allowed everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemeJsAccessConfig {
+    /// User given the `admin` role (every privilege on every database) on startup, created if needed.
+    /// Admins manage the other users and roles.
+    #[serde(default)]
+    pub admin: Option<String>,
+}
+
+/// Static token of `[auth]`, `[[auth.tokens]]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsTokenConfig {
+    /// Token sent by clients, as `Authorization: Bearer <token>`.
+    pub token: String,
+    /// User the token authenticates.
+    pub user: String,
+}
+
+/// Password of a user of `[auth]`, `[[auth.passwords]]`. Clients send it as `Authorization: Basic <base64 of user:password>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsPasswordConfig {
+    pub user: String,
+    /// Hash of the password, as printed by `sjs hash-password`.
+    pub hash: String,
+}
+
+/// Validation of the JWTs of `[auth]`, under `[auth.jwt]`. Clients send them as `Authorization: Bearer <token>`,
+/// the user being the `sub` claim of the token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsJwtConfig {
+    /// Secret the tokens are signed with (HS256).
+    pub secret: String,
+    /// Issuer tokens must have (`iss`), any issuer unless set.
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// Audience tokens must have (`aud`), any audience unless set.
+    #[serde(default)]
+    pub audience: Option<String>,
+}
+
+/// Authentication of the clients of the HTTP and gRPC servers, under `[auth]`. When the section is present,
+/// requests without valid credentials are rejected before reaching the engine, the others running for their user
+/// (see `[access]`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemeJsAuthConfig {
+    #[serde(default)]
+    pub tokens: Vec<SchemeJsTokenConfig>,
+    #[serde(default)]
+    pub passwords: Vec<SchemeJsPasswordConfig>,
+    #[serde(default)]
+    pub jwt: Option<SchemeJsJwtConfig>,
+}
+
+fn default_query_workers() -> usize {
+    4
+}
+
+/// Settings of the workers of the runtime, under `[workers]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsWorkersConfig {
+    /// Amount of query workers running the searches of the JS runtime concurrently (`4` unless set).
+    /// With `0`, searches run on the thread of the runtime.
+    #[serde(default = "default_query_workers")]
+    pub query_workers: usize,
+}
+
+impl Default for SchemeJsWorkersConfig {
+    fn default() -> Self {
+        Self {
+            query_workers: default_query_workers(),
+        }
+    }
+}
+
+fn default_mmap_reads() -> bool {
+    true
+}
+
+/// Settings of how the files of the databases are accessed, under `[storage]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeJsStorageConfig {
+    /// Whether shard files are read through a memory map (`true` unless set), or with a read of the file for
+    /// every lookup, e.g. on filesystems where memory maps are unreliable.
+    #[serde(default = "default_mmap_reads")]
+    pub mmap_reads: bool,
+}
+
+impl Default for SchemeJsStorageConfig {
+    fn default() -> Self {
+        Self {
+            mmap_reads: default_mmap_reads(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SchemeJsConfig {
     pub workspace: SchemeJsWorkspace,
+    #[serde(default)]
+    pub databases: HashMap<String, SchemeJsDatabaseConfig>,
+    #[serde(default)]
+    pub tables: HashMap<String, SchemeJsTableConfig>,
+    #[serde(default)]
+    pub compaction: Option<SchemeJsCompactionConfig>,
+    #[serde(default)]
+    pub telemetry: Option<SchemeJsTelemetryConfig>,
+    #[serde(default)]
+    pub logging: SchemeJsLoggingConfig,
+    #[serde(default)]
+    pub limits: SchemeJsLimitsConfig,
+    #[serde(default)]
+    pub http: Option<SchemeJsHttpConfig>,
+    #[serde(default)]
+    pub grpc: Option<SchemeJsGrpcConfig>,
+    #[serde(default)]
+    pub replication: Option<SchemeJsReplicationConfig>,
+    #[serde(default)]
+    pub cdc: Option<SchemeJsCdcConfig>,
+    #[serde(default)]
+    pub workers: SchemeJsWorkersConfig,
+    #[serde(default)]
+    pub storage: SchemeJsStorageConfig,
+    #[serde(default)]
+    pub access: Option<SchemeJsAccessConfig>,
+    #[serde(default)]
+    pub auth: Option<SchemeJsAuthConfig>,
 }
 
 impl SchemeJsConfig {
@@ -22,4 +546,82 @@ impl SchemeJsConfig {
 
         Ok(config)
     }
+
+    /// Storage settings of the tables named `table_name`: no compression, no limit on the size of shards,
+    /// JSON rows and the default `FlushPolicy` unless configured. Fails if `flush_age` is not a valid duration.
+    pub fn table_storage(&self, table_name: &str) -> Result<TableStorage> {
+        let table = match self.tables.get(table_name) {
+            Some(table) => table,
+            None => return Ok(TableStorage::default()),
+        };
+
+        let mut flush = FlushPolicy::default().set_max_rows(table.flush_rows);
+        if let Some(max_bytes) = table.flush_bytes {
+            flush = flush.set_max_bytes(Some(max_bytes));
+        }
+        if let Some(max_age) = &table.flush_age {
+            flush = flush.set_max_age(Some(parse_duration(max_age)?));
+        }
+
+        Ok(TableStorage::new(table.compression)
+            .set_max_shard_size(table.max_shard_size)
+            .set_encoding(table.encoding)
+            .set_flush(flush))
+    }
+
+    /// Retention of the tables named `table_name`, if configured. Fails if `retention` is not a valid duration.
+    pub fn table_retention(&self, table_name: &str) -> Result<Option<TableRetention>> {
+        let table = match self.tables.get(table_name) {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+
+        match &table.retention {
+            Some(retention) => Ok(Some(TableRetention {
+                column: table
+                    .retention_column
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_RETENTION_COLUMN.to_string()),
+                retention: parse_duration(retention)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Storage quota of the database `database_name` in bytes, if it has one.
+    pub fn storage_quota(&self, database_name: &str) -> Option<u64> {
+        self.databases
+            .get(database_name)
+            .and_then(|database| database.storage_quota)
+    }
+
+    /// Slow query threshold of the database `database_name`, if it has one. Fails if it is not a valid duration.
+    pub fn slow_query_threshold(&self, database_name: &str) -> Result<Option<Duration>> {
+        self.databases
+            .get(database_name)
+            .and_then(|database| database.slow_query_threshold.as_deref())
+            .map(parse_duration)
+            .transpose()
+    }
+
+    /// Key of the database `database_name`, read from its environment variable (see `ENCRYPTION_KEY_ENV_PREFIX`),
+    /// which takes precedence, or from `SchemeJS.toml`.
+    pub fn encryption_key(&self, database_name: &str) -> Option<EncryptionKey> {
+        let env_name: String = database_name
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_uppercase(),
+                false => '_',
+            })
+            .collect();
+
+        std::env::var(format!("{}{}", ENCRYPTION_KEY_ENV_PREFIX, env_name))
+            .ok()
+            .or_else(|| {
+                self.databases
+                    .get(database_name)
+                    .and_then(|database| database.encryption_key.clone())
+            })
+            .map(|secret| EncryptionKey::from_secret(&secret))
+    }
 }
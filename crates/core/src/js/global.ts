@@ -1,5 +1,6 @@
 import * as SJsPrimitives from "ext:sjs_primitives/src/js/index.ts"
-import { insertRow } from "ext:sjs_engine/src/js/ops.ts";
+import { Query } from "ext:sjs_engine/src/js/query.ts";
+import { registerHooks, insertRow, insertMany, upsertRow, update, deleteRows, search, groupBy, aggregate, join, explain, sql, transaction, reindex, verify, checkIntegrity, backup, restore, snapshot, createDatabase, createTable, createView, refreshView, putRole, dropRole, putUser, dropUser, exportRows, importRows, metrics, stats } from "ext:sjs_engine/src/js/ops.ts";
 class SchemeJS {
 
     static get Table() {
@@ -14,10 +15,134 @@ class SchemeJS {
         return SJsPrimitives.DataTypes;
     }
 
+    static get Query() {
+        return Query;
+    }
+
+    static get registerHooks() {
+        return registerHooks;
+    }
+
     static get insert() {
         return insertRow;
     }
 
+    static get insertMany() {
+        return insertMany;
+    }
+
+    static get upsert() {
+        return upsertRow;
+    }
+
+    static get update() {
+        return update;
+    }
+
+    static get delete() {
+        return deleteRows;
+    }
+
+    static get search() {
+        return search;
+    }
+
+    static get groupBy() {
+        return groupBy;
+    }
+
+    static get aggregate() {
+        return aggregate;
+    }
+
+    static get join() {
+        return join;
+    }
+
+    static get explain() {
+        return explain;
+    }
+
+    static get sql() {
+        return sql;
+    }
+
+    static get transaction() {
+        return transaction;
+    }
+
+    static get reindex() {
+        return reindex;
+    }
+
+    static get verify() {
+        return verify;
+    }
+
+    static get checkIntegrity() {
+        return checkIntegrity;
+    }
+
+    static get backup() {
+        return backup;
+    }
+
+    static get restore() {
+        return restore;
+    }
+
+    static get snapshot() {
+        return snapshot;
+    }
+
+    static get export() {
+        return exportRows;
+    }
+
+    static get import() {
+        return importRows;
+    }
+
+    static get metrics() {
+        return metrics;
+    }
+
+    static get stats() {
+        return stats;
+    }
+
+    static get createDatabase() {
+        return createDatabase;
+    }
+
+    static get createTable() {
+        return createTable;
+    }
+
+    static get createView() {
+        return createView;
+    }
+
+    static get refreshView() {
+        return refreshView;
+    }
+
+    static get putRole() {
+        return putRole;
+    }
+
+    static get dropRole() {
+        return dropRole;
+    }
+
+    static get putUser() {
+        return putUser;
+    }
+
+    static get dropUser() {
+        return dropUser;
+    }
+
 }
 
 export const SJSGlobal = {
@@ -0,0 +1,149 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::RwLock;
+
+/// Bits used per expected item, which keeps the false positive rate around 1% with `HASHES` hashes.
+const BITS_PER_ITEM: usize = 10;
+const HASHES: u64 = 7;
+
+/// Probabilistic set of items: `might_contain` never misses an inserted item,
+/// but may report items that were never inserted (more often once it holds more than its capacity).
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    pub fn new(capacity: usize) -> Self {
+        let bits = capacity.max(1) * BITS_PER_ITEM;
+
+        Self {
+            bits: vec![0; (bits + 63) / 64],
+        }
+    }
+
+    /// Bits of `item`, derived from two hashes (`h1 + i * h2`).
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = |seed: u64| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            item.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let (h1, h2) = (hash(0), hash(1));
+        let len = (self.bits.len() * 64) as u64;
+
+        (0..HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        for position in self.positions(item).collect::<Vec<usize>>() {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+
+    pub fn might_contain(&self, item: &[u8]) -> bool {
+        self.positions(item)
+            .all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+}
+
+/// Bloom filters of the shards of a `MapShard`, one per shard, so lookups can skip the shards
+/// that can't hold a value without reading them.
+///
+/// Filters are only kept in memory, they are filled as items are written and rebuilt when the table is loaded.
+///
+/// # Fields:
+/// - `shard_size`: Positions held by every shard (its breaking point), `None` when the `MapShard` is a single shard.
+/// - `capacity`: Items every filter is sized for.
+/// - `filters`: Filter of every shard, by shard number. Created on the first item of the shard.
+#[derive(Debug)]
+pub struct ShardBlooms {
+    shard_size: Option<u64>,
+    capacity: usize,
+    filters: RwLock<Vec<Option<BloomFilter>>>,
+}
+
+impl ShardBlooms {
+    pub fn new(shard_size: Option<u64>, capacity: usize) -> Self {
+        Self {
+            shard_size,
+            capacity,
+            filters: RwLock::new(vec![]),
+        }
+    }
+
+    /// Number of the shard holding `position`.
+    pub fn shard_of(&self, position: u64) -> usize {
+        self.shard_size
+            .map_or(0, |shard_size| (position / shard_size) as usize)
+    }
+
+    /// First position after the shard holding `position`.
+    pub fn next_shard_start(&self, position: u64) -> u64 {
+        match self.shard_size {
+            Some(shard_size) => (position / shard_size + 1) * shard_size,
+            None => u64::MAX,
+        }
+    }
+
+    /// Adds `item` to the filter of the shard holding `position`.
+    pub fn insert(&self, position: u64, item: &[u8]) {
+        let shard = self.shard_of(position);
+        let mut filters = self.filters.write().unwrap();
+
+        if filters.len() <= shard {
+            filters.resize(shard + 1, None);
+        }
+
+        filters[shard]
+            .get_or_insert_with(|| BloomFilter::new(self.capacity))
+            .insert(item);
+    }
+
+    /// Whether the shard number `shard` might hold `item`. Shards without any item hold nothing.
+    pub fn might_contain(&self, shard: usize, item: &[u8]) -> bool {
+        match self.filters.read().unwrap().get(shard) {
+            Some(Some(filter)) => filter.might_contain(item),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bloom::{BloomFilter, ShardBlooms};
+
+    #[tokio::test]
+    pub async fn test_bloom_filter() {
+        let mut filter = BloomFilter::new(1_000);
+        for i in 0..1_000 {
+            filter.insert(format!("item{}", i).as_bytes());
+        }
+
+        for i in 0..1_000 {
+            assert!(filter.might_contain(format!("item{}", i).as_bytes()));
+        }
+
+        let false_positives = (0..1_000)
+            .filter(|i| filter.might_contain(format!("other{}", i).as_bytes()))
+            .count();
+        assert!(false_positives < 50);
+    }
+
+    #[tokio::test]
+    pub async fn test_shard_blooms() {
+        let blooms = ShardBlooms::new(Some(10), 100);
+        blooms.insert(3, b"a");
+        blooms.insert(25, b"b");
+
+        assert_eq!(blooms.shard_of(25), 2);
+        assert_eq!(blooms.next_shard_start(25), 30);
+        assert!(blooms.might_contain(0, b"a"));
+        assert!(!blooms.might_contain(0, b"b"));
+        assert!(!blooms.might_contain(1, b"a"));
+        assert!(blooms.might_contain(2, b"b"));
+        assert!(!blooms.might_contain(3, b"b"));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::errors::ShardErrors;
+use serde::{Deserialize, Serialize};
+
+/// Level used for zstd, which favours speed over ratio like lz4 does.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression of the items of a data shard, set per table (`compression = "lz4"` in `SchemeJS.toml`).
+///
+/// Items are compressed one by one, so reading an item only decompresses that item.
+/// The compression of a shard is stored in its header: shards keep the one they were created with
+/// even if the table is configured differently later on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    /// Identifier of the compression in shard headers.
+    pub fn to_id(&self) -> u64 {
+        match self {
+            Compression::None => 0,
+            Compression::Lz4 => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    pub fn from_id(id: u64) -> Option<Self> {
+        match id {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz4),
+            2 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn compress(&self, item: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::None => item.to_vec(),
+            Compression::Lz4 => lz4_flex::compress_prepend_size(item),
+            Compression::Zstd => zstd::bulk::compress(item, ZSTD_LEVEL).unwrap(),
+        }
+    }
+
+    pub fn decompress(&self, stored: &[u8]) -> Result<Vec<u8>, ShardErrors> {
+        match self {
+            Compression::None => Ok(stored.to_vec()),
+            Compression::Lz4 => lz4_flex::decompress_size_prepended(stored)
+                .map_err(|_| ShardErrors::DecompressionError),
+            Compression::Zstd => {
+                zstd::decode_all(stored).map_err(|_| ShardErrors::DecompressionError)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compression::Compression;
+
+    #[test]
+    pub fn test_compression() {
+        let item =
+            r#"{"user_name":"andreespirela","user_email":"andreespirela@outlook.com"}"#.repeat(10);
+
+        for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+            let stored = compression.compress(item.as_bytes());
+            assert_eq!(compression.decompress(&stored).unwrap(), item.as_bytes());
+            assert_eq!(Compression::from_id(compression.to_id()), Some(compression));
+
+            if compression != Compression::None {
+                assert!(stored.len() < item.len());
+                assert!(compression.decompress(&stored[..stored.len() - 1]).is_err());
+            }
+        }
+
+        assert_eq!(Compression::from_id(3), None);
+    }
+}
@@ -1,13 +1,35 @@
 use memmap2::Mmap;
+use std::borrow::Cow;
 use std::fs::{File, Metadata, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::RwLock;
 
+static MMAP_READS: AtomicBool = AtomicBool::new(true);
+
+/// Whether the files opened from now on are read through a memory map (the default) or through the file
+/// (`mmap_reads` under `[storage]` in `SchemeJS.toml`). Files already open keep the way they are read.
+pub fn set_mmap_reads(enabled: bool) {
+    MMAP_READS.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the files opened from now on are read through a memory map, see `set_mmap_reads`.
+pub fn mmap_reads() -> bool {
+    MMAP_READS.load(Ordering::Relaxed)
+}
+
+/// File of a shard along with a memory map of it.
+///
+/// Reads (`get_bytes`, `read_pointer`) are served from the memory map, so reading an item never goes through
+/// a system call: pages of hot shards stay in the page cache and lookups only copy the bytes of the item.
+/// Writes go through the file (`operate`), which maps the file again afterwards so the new bytes are readable.
+/// With `set_mmap_reads(false)` the file is not mapped and every read is a positioned read of the file instead.
 #[derive(Debug)]
 pub struct DataHandler {
     pub path: PathBuf,
-    mmap: Mmap,
+    mmap: Option<Mmap>,
+    len: usize,
     file: File,
 }
 
@@ -20,16 +42,12 @@ impl DataHandler {
             .open(path.clone())
             .expect("Failed to create shard file");
 
-        Ok(Self {
-            path: path.as_ref().to_path_buf(),
-            mmap: Mmap::map(&load_file)?,
-            file: load_file,
-        })
+        Self::new_from_file(path.as_ref().to_path_buf(), load_file)
     }
 
     #[cfg(test)]
     pub unsafe fn access_map(&self) -> &Mmap {
-        &self.mmap
+        self.mmap.as_ref().expect("Shard file is not memory mapped")
     }
 
     #[cfg(test)]
@@ -38,11 +56,33 @@ impl DataHandler {
     }
 
     unsafe fn new_from_file(path: PathBuf, file: File) -> std::io::Result<Self> {
-        Ok(Self {
+        Self::open(path, file, mmap_reads())
+    }
+
+    unsafe fn open(path: PathBuf, file: File, mmap_reads: bool) -> std::io::Result<Self> {
+        let mut handler = Self {
             path,
-            mmap: Mmap::map(&file)?,
+            mmap: None,
+            len: 0,
             file,
-        })
+        };
+        handler.refresh(mmap_reads)?;
+
+        Ok(handler)
+    }
+
+    /// Maps the file again (when it is memory mapped) and records its length, after it was written to.
+    fn refresh(&mut self, mmap_reads: bool) -> std::io::Result<()> {
+        match mmap_reads {
+            true => {
+                let mmap = unsafe { Mmap::map(&self.file)? };
+                self.len = mmap.len();
+                self.mmap = Some(mmap);
+            }
+            false => self.len = self.file.metadata()?.len() as usize,
+        }
+
+        Ok(())
     }
 
     pub unsafe fn new<P: AsRef<Path> + Clone>(path: P) -> std::io::Result<RwLock<Self>> {
@@ -57,17 +97,35 @@ impl DataHandler {
         self.file.metadata()
     }
 
+    /// Waits until the data and metadata of the file are on disk. Writes are flushed to the file as they are done,
+    /// but may sit in the page cache until then.
+    pub fn sync(&self) -> std::io::Result<()> {
+        self.file.sync_all()
+    }
+
     pub fn len(&self) -> usize {
-        self.mmap.len()
+        self.len
     }
 
-    pub fn get_bytes(&self, from: usize, to: usize) -> Option<&[u8]> {
-        self.mmap.get(from..to)
+    /// Bytes of the file from `from` to `to`, `None` if the range goes past the end of the file.
+    /// They are borrowed from the memory map, or read from the file when it is not mapped.
+    pub fn get_bytes(&self, from: usize, to: usize) -> Option<Cow<'_, [u8]>> {
+        if let Some(mmap) = &self.mmap {
+            return mmap.get(from..to).map(Cow::Borrowed);
+        }
+
+        if from > to || to > self.len {
+            return None;
+        }
+
+        let mut bytes = vec![0; to - from];
+        read_exact_at(&self.file, &mut bytes, from as u64).ok()?;
+        Some(Cow::Owned(bytes))
     }
 
     pub fn read_pointer(&self, start: u64, max_bytes: usize) -> Option<Vec<u8>> {
         self.get_bytes(start as usize, start as usize + max_bytes)
-            .map(|i| i.to_vec())
+            .map(|i| i.into_owned())
     }
 
     pub fn operate<F, R>(&mut self, callback: F) -> std::io::Result<R>
@@ -77,10 +135,72 @@ impl DataHandler {
         let cb = callback(&mut self.file)?;
 
         self.file.flush()?;
-
-        let new_mmap = unsafe { Mmap::map(&self.file) };
-        self.mmap = new_mmap?;
+        self.refresh(self.mmap.is_some())?;
 
         Ok(cb)
     }
 }
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, buf, offset)? {
+            0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                buf = &mut buf[read..];
+                offset += read as u64;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_handler::DataHandler;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    #[test]
+    pub fn test_data_handler_read_modes() {
+        let path = std::env::temp_dir().join(format!("{}.data", Uuid::new_v4()));
+
+        for mmap_reads in [true, false] {
+            let file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(&path)
+                .unwrap();
+            let mut handler =
+                unsafe { DataHandler::open(PathBuf::from(&path), file, mmap_reads) }.unwrap();
+            assert_eq!(handler.mmap.is_some(), mmap_reads);
+
+            handler.operate(|file| file.write_all(b"schemajs")).unwrap();
+
+            // The file holds the bytes written by the previous iteration too
+            let len = handler.len();
+            assert_eq!(len % 8, 0);
+            assert_eq!(
+                handler.get_bytes(len - 8, len).unwrap().as_ref(),
+                b"schemajs"
+            );
+            assert_eq!(
+                handler.read_pointer(len as u64 - 2, 2).unwrap(),
+                b"js".to_vec()
+            );
+            assert!(handler.get_bytes(len - 1, len + 1).is_none());
+            assert!(handler.read_pointer(len as u64, 1).is_none());
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
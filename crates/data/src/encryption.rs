@@ -0,0 +1,95 @@
+use crate::errors::ShardErrors;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Formatter};
+
+/// Size of the random nonce stored before every encrypted item.
+pub const NONCE_SIZE: usize = 12;
+
+/// Size of the authentication tag appended to every encrypted item.
+pub const TAG_SIZE: usize = 16;
+
+/// Bytes an item grows by once encrypted.
+pub const ENCRYPTION_OVERHEAD: usize = NONCE_SIZE + TAG_SIZE;
+
+/// Key encrypting the files of a database (AES-256-GCM), so its folder holds no plaintext user data.
+///
+/// Items are encrypted one by one with a random nonce, stored as `[nonce][ciphertext][tag]`.
+/// The tag authenticates the item: reading it with a different key, or after it was tampered with,
+/// fails with `DecryptionError` instead of returning garbage.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// Derives the key from the secret of a database (as set in `SchemeJS.toml` or the environment).
+    pub fn from_secret(secret: &str) -> Self {
+        Self(Sha256::digest(secret.as_bytes()).into())
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+
+    pub fn encrypt(&self, item: &[u8]) -> Vec<u8> {
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let ciphertext = self
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), item)
+            .unwrap();
+
+        let mut stored = Vec::with_capacity(ENCRYPTION_OVERHEAD + item.len());
+        stored.extend_from_slice(&nonce);
+        stored.extend_from_slice(&ciphertext);
+        stored
+    }
+
+    pub fn decrypt(&self, stored: &[u8]) -> Result<Vec<u8>, ShardErrors> {
+        if stored.len() < ENCRYPTION_OVERHEAD {
+            return Err(ShardErrors::DecryptionError);
+        }
+
+        let (nonce, ciphertext) = stored.split_at(NONCE_SIZE);
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| ShardErrors::DecryptionError)
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EncryptionKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encryption::{EncryptionKey, ENCRYPTION_OVERHEAD};
+
+    #[test]
+    pub fn test_encryption_key() {
+        let key = EncryptionKey::from_secret("secret");
+        assert_eq!(key, EncryptionKey::from_secret("secret"));
+        assert_eq!(format!("{:?}", key), "EncryptionKey(..)");
+
+        let stored = key.encrypt(b"Hello World");
+        assert_eq!(stored.len(), b"Hello World".len() + ENCRYPTION_OVERHEAD);
+        assert!(!stored.windows(5).any(|window| window == b"Hello"));
+        assert_eq!(key.decrypt(&stored).unwrap(), b"Hello World".to_vec());
+
+        // Nonces are random, so the same item is never stored the same way twice
+        assert_ne!(stored, key.encrypt(b"Hello World"));
+
+        let other = EncryptionKey::from_secret("other");
+        assert!(other.decrypt(&stored).err().unwrap().is_decryption_error());
+
+        let mut tampered = stored.clone();
+        tampered[ENCRYPTION_OVERHEAD] ^= 0xff;
+        assert!(key.decrypt(&tampered).is_err());
+        assert!(key.decrypt(&stored[..ENCRYPTION_OVERHEAD - 1]).is_err());
+    }
+}
@@ -27,4 +27,12 @@ pub enum ShardErrors {
     UnknownShard,
     #[error("Invalid locking detected")]
     InvalidLocking,
+    #[error("Item is corrupted, its checksum does not match")]
+    ChecksumMismatch,
+    #[error("Item could not be decompressed")]
+    DecompressionError,
+    #[error("Item could not be decrypted, the key is wrong or the item is corrupted")]
+    DecryptionError,
+    #[error("Shard is encrypted but no encryption key was provided")]
+    MissingEncryptionKey,
 }
@@ -0,0 +1,130 @@
+use once_cell::sync::Lazy;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Amount of threads of the pool returned by `IoPool::global`.
+pub const DEFAULT_IO_THREADS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+static GLOBAL_IO_POOL: Lazy<IoPool> = Lazy::new(|| IoPool::new(DEFAULT_IO_THREADS));
+
+/// Pool of threads dedicated to blocking disk work (writes to shards, reconciliation, imports).
+///
+/// `run` moves the work to the pool and returns a future resolving to its result, so async callers
+/// (e.g. the ops of the JS runtime) keep serving other tasks instead of stalling until the disk is done.
+/// Jobs run in the order they were submitted, as soon as a thread is free.
+#[derive(Debug)]
+pub struct IoPool {
+    sender: Mutex<Sender<Job>>,
+    threads: usize,
+}
+
+impl IoPool {
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (sender, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for number in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("schemajs-io-{}", number))
+                .spawn(move || Self::work(receiver))
+                .unwrap();
+        }
+
+        Self {
+            sender: Mutex::new(sender),
+            threads,
+        }
+    }
+
+    /// Pool shared by the whole process.
+    pub fn global() -> &'static IoPool {
+        &GLOBAL_IO_POOL
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    fn work(receiver: Arc<Mutex<Receiver<Job>>>) {
+        loop {
+            // The lock is only held while waiting for a job, not while running it
+            let job = match receiver.lock().unwrap().recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+
+            job();
+        }
+    }
+
+    /// Runs `job` on a thread of the pool, resolving to what it returns.
+    /// A panic in `job` is resumed in the caller once the future is awaited, the thread of the pool survives it.
+    /// `job` runs within the tracing span of the caller, so the spans it opens are nested under it.
+    pub async fn run<F, R>(&self, job: F) -> R
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = tokio::sync::oneshot::channel::<Result<R, Box<dyn Any + Send>>>();
+        let span = tracing::Span::current();
+
+        self.sender
+            .lock()
+            .unwrap()
+            .send(Box::new(move || {
+                let _entered = span.enter();
+                let _ = sender.send(std::panic::catch_unwind(AssertUnwindSafe(job)));
+            }))
+            .expect("IO pool threads are gone");
+
+        match receiver.await.expect("IO pool job was dropped") {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io_pool::IoPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    pub async fn test_io_pool() {
+        let pool = IoPool::new(2);
+        assert_eq!(pool.threads(), 2);
+
+        // Jobs run away from the calling thread
+        let caller = std::thread::current().id();
+        let worker = pool.run(|| std::thread::current().id()).await;
+        assert_ne!(caller, worker);
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        for expected in 0..10 {
+            let counter = counter.clone();
+            let previous = pool
+                .run(move || counter.fetch_add(1, Ordering::SeqCst))
+                .await;
+            assert_eq!(previous, expected);
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 10);
+
+        // Panics reach the caller and the pool keeps working
+        let panicked = tokio::spawn(async {
+            IoPool::global()
+                .run(|| -> usize { panic!("disk is gone") })
+                .await
+        })
+        .await;
+        assert!(panicked.is_err());
+        assert_eq!(pool.run(|| 1 + 1).await, 2);
+        assert_eq!(IoPool::global().run(|| "ok").await, "ok");
+    }
+}
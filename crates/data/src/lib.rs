@@ -1,8 +1,16 @@
+pub mod bloom;
+pub mod compression;
 pub mod data_handler;
+pub mod encryption;
 pub mod errors;
+pub mod io_pool;
+pub mod metrics;
 pub mod shard;
+pub mod snapshot;
 pub mod temp_offset_types;
+pub mod tombstones;
 pub mod utils;
+pub mod wal;
 
 // https://doc.rust-lang.org/std/mem/fn.size_of.html
 pub const U64_SIZE: usize = 8;
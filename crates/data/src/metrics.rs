@@ -0,0 +1,212 @@
+use once_cell::sync::Lazy;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the buckets of the duration histograms.
+pub const DURATION_BUCKETS: [f64; 10] =
+    [0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0];
+
+static GLOBAL_METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+/// Value that only goes up, such as the amount of inserted rows.
+#[derive(Debug, Default)]
+pub struct Counter {
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub fn inc_by(&self, amount: u64) {
+        self.value.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Value that goes up and down, such as the amount of rows waiting in temporary shards.
+#[derive(Debug, Default)]
+pub struct Gauge {
+    value: AtomicI64,
+}
+
+impl Gauge {
+    pub fn add(&self, amount: i64) {
+        self.value.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Distribution of durations over `DURATION_BUCKETS`.
+///
+/// # Fields:
+/// - `buckets`: Amount of observations falling in every bucket (not cumulative), the last one counting
+///   the observations above every bound.
+/// - `sum_micros`: Sum of every observation, in microseconds.
+/// - `count`: Amount of observations.
+#[derive(Debug)]
+pub struct Histogram {
+    buckets: Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..=DURATION_BUCKETS.len())
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/// Metrics of the process, rendered in the Prometheus text format by `render`.
+///
+/// # Fields:
+/// - `rows_inserted`: Rows inserted into tables, through any kind of insert (including imports and transactions).
+/// - `query_duration`: Time taken by searches, from planning to reading the matched rows.
+/// - `temp_shard_rows`: Rows waiting in temporary shards to be reconciled into a main shard.
+/// - `reconcile_duration`: Time taken to reconcile a temporary shard into its main shard.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub rows_inserted: Counter,
+    pub query_duration: Histogram,
+    pub temp_shard_rows: Gauge,
+    pub reconcile_duration: Histogram,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Metrics shared by the whole process, the ones the engine records.
+    pub fn global() -> &'static Metrics {
+        &GLOBAL_METRICS
+    }
+
+    /// Renders every metric in the Prometheus text exposition format (version 0.0.4).
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        Self::render_header(
+            &mut out,
+            "schemajs_rows_inserted_total",
+            "Rows inserted into tables.",
+            "counter",
+        );
+        let _ = writeln!(
+            out,
+            "schemajs_rows_inserted_total {}",
+            self.rows_inserted.get()
+        );
+
+        Self::render_header(
+            &mut out,
+            "schemajs_temp_shard_rows",
+            "Rows waiting in temporary shards to be reconciled.",
+            "gauge",
+        );
+        let _ = writeln!(
+            out,
+            "schemajs_temp_shard_rows {}",
+            self.temp_shard_rows.get()
+        );
+
+        Self::render_histogram(
+            &mut out,
+            "schemajs_query_duration_seconds",
+            "Time taken by searches.",
+            &self.query_duration,
+        );
+        Self::render_histogram(
+            &mut out,
+            "schemajs_reconcile_duration_seconds",
+            "Time taken to reconcile a temporary shard.",
+            &self.reconcile_duration,
+        );
+
+        out
+    }
+
+    fn render_header(out: &mut String, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    }
+
+    fn render_histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+        Self::render_header(out, name, help, "histogram");
+
+        let mut cumulative = 0;
+        for (bound, bucket) in DURATION_BUCKETS.iter().zip(histogram.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        cumulative += histogram.buckets[DURATION_BUCKETS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, cumulative);
+
+        let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, histogram.count());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::Metrics;
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_metrics_render() {
+        let metrics = Metrics::new();
+        metrics.rows_inserted.inc_by(3);
+        metrics.temp_shard_rows.add(5);
+        metrics.temp_shard_rows.add(-2);
+        metrics.query_duration.observe(Duration::from_micros(300));
+        metrics.query_duration.observe(Duration::from_millis(20));
+        metrics.query_duration.observe(Duration::from_secs(60));
+
+        let rendered = metrics.render();
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines.contains(&"# TYPE schemajs_rows_inserted_total counter"));
+        assert!(lines.contains(&"schemajs_rows_inserted_total 3"));
+        assert!(lines.contains(&"schemajs_temp_shard_rows 3"));
+        assert!(lines.contains(&"# TYPE schemajs_query_duration_seconds histogram"));
+        // Buckets are cumulative
+        assert!(lines.contains(&"schemajs_query_duration_seconds_bucket{le=\"0.0005\"} 1"));
+        assert!(lines.contains(&"schemajs_query_duration_seconds_bucket{le=\"0.01\"} 1"));
+        assert!(lines.contains(&"schemajs_query_duration_seconds_bucket{le=\"0.05\"} 2"));
+        assert!(lines.contains(&"schemajs_query_duration_seconds_bucket{le=\"10\"} 2"));
+        assert!(lines.contains(&"schemajs_query_duration_seconds_bucket{le=\"+Inf\"} 3"));
+        assert!(lines.contains(&"schemajs_query_duration_seconds_sum 60.0203"));
+        assert!(lines.contains(&"schemajs_query_duration_seconds_count 3"));
+        assert!(lines.contains(&"schemajs_reconcile_duration_seconds_count 0"));
+    }
+}
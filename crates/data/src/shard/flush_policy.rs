@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Rows a temporary shard holds before it is flushed (reconciled into the main shard) when the rows are left
+/// to the insert rate, until the rate is known.
+pub const DEFAULT_FLUSH_ROWS: u64 = 1000;
+
+/// Bounds of the rows a temporary shard holds before it is flushed when they are left to the insert rate.
+pub const MIN_FLUSH_ROWS: u64 = 100;
+pub const MAX_FLUSH_ROWS: u64 = 10_000;
+
+/// Temporary shards whose rows are left to the insert rate hold about the rows inserted in this long.
+pub const ADAPTIVE_FLUSH_WINDOW: Duration = Duration::from_secs(1);
+
+/// Bytes a temporary shard holds before it is flushed unless configured.
+pub const DEFAULT_FLUSH_BYTES: u64 = 16 * 1024 * 1024;
+
+/// How long the oldest row of a temporary shard waits before it is flushed unless configured.
+pub const DEFAULT_FLUSH_AGE: Duration = Duration::from_secs(5);
+
+/// When the rows of a temporary shard are flushed into the main shard (`flush_rows`, `flush_bytes` and `flush_age`
+/// under `[tables.<table name>]` in `SchemeJS.toml`). A temporary shard is flushed as soon as any limit is reached.
+///
+/// # Fields:
+/// - `max_rows`: Rows a temporary shard holds before it is flushed. When `None`, it follows the insert rate:
+///   about the rows inserted in `ADAPTIVE_FLUSH_WINDOW`, between `MIN_FLUSH_ROWS` and `MAX_FLUSH_ROWS`.
+/// - `max_bytes`: Bytes of rows a temporary shard holds before it is flushed, no limit when `None`.
+/// - `max_age`: How long the oldest row of a temporary shard waits before it is flushed, no limit when `None`.
+///   Shards are checked as rows are inserted and by the flush task of the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlushPolicy {
+    #[serde(default)]
+    pub max_rows: Option<u64>,
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    #[serde(default)]
+    pub max_age: Option<Duration>,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        Self {
+            max_rows: None,
+            max_bytes: Some(DEFAULT_FLUSH_BYTES),
+            max_age: Some(DEFAULT_FLUSH_AGE),
+        }
+    }
+}
+
+impl FlushPolicy {
+    pub fn set_max_rows(mut self, max_rows: Option<u64>) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    pub fn set_max_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn set_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Rows a temporary shard has room for, the most it can hold before being flushed.
+    pub fn capacity(&self) -> u64 {
+        self.max_rows.unwrap_or(MAX_FLUSH_ROWS)
+    }
+
+    /// Rows a temporary shard holds before its first flush.
+    pub fn initial_rows(&self) -> u64 {
+        self.max_rows.unwrap_or(DEFAULT_FLUSH_ROWS)
+    }
+
+    /// Rows a temporary shard holds before its next flush, once `rows` were inserted in `elapsed` since the
+    /// previous one. Rows left to the insert rate move halfway to the rows inserted in `ADAPTIVE_FLUSH_WINDOW`
+    /// at that rate, so a single burst doesn't swing them.
+    pub fn next_rows(&self, current: u64, rows: u64, elapsed: Duration) -> u64 {
+        if let Some(max_rows) = self.max_rows {
+            return max_rows;
+        }
+
+        let rate = rows as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        let target = (rate * ADAPTIVE_FLUSH_WINDOW.as_secs_f64()) as u64;
+        let target = target.clamp(MIN_FLUSH_ROWS, MAX_FLUSH_ROWS);
+
+        (current + target) / 2
+    }
+}
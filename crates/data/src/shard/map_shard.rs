@@ -4,16 +4,27 @@ use crate::utils::fs::list_files_with_prefix;
 use indexmap::IndexMap;
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::RwLock;
 use uuid::Uuid;
 
+/// Set of shard files holding a single sequence of items. Items are appended to the current master shard,
+/// which is rotated into the past master shards once it is full (its breaking point, in items) or, when the
+/// config has a `max_size`, once its file reaches that size.
+///
+/// # Fields:
+/// - `past_shard_starts`: Global index of the first item of every past master shard, in the same order.
+///   Shards rotated by size hold a varying amount of items, so global indexes are resolved through these.
+/// - `current_shard_start`: Global index of the first item of the current master shard.
 #[derive(Debug)]
 pub struct MapShard<S: Shard<Opts>, Opts: ShardConfig> {
     pub current_master_shard: S,
     pub past_master_shards: RwLock<IndexMap<String, S>>,
     pub shard_prefix: String,
     pub shards_folder: PathBuf,
+    past_shard_starts: Vec<usize>,
+    current_shard_start: usize,
     config: Opts,
 }
 
@@ -45,17 +56,19 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
             });
 
         let mut past_master_shards = IndexMap::new();
+        let mut past_shard_starts = vec![];
+        let mut current_shard_start = 0;
 
         for &(number, ref uuid, ref path) in &sorted_files {
             if path != &current_master_shard {
-                past_master_shards.insert(
-                    uuid.clone(),
-                    S::new(
-                        path.clone(),
-                        config.clone(),
-                        Some(Uuid::parse_str(uuid).unwrap()),
-                    ),
+                let shard = S::new(
+                    path.clone(),
+                    config.clone(),
+                    Some(Uuid::parse_str(uuid).unwrap()),
                 );
+                past_shard_starts.push(current_shard_start);
+                current_shard_start += (shard.get_last_index() + 1) as usize;
+                past_master_shards.insert(uuid.clone(), shard);
             }
         }
 
@@ -68,6 +81,8 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
             past_master_shards: RwLock::new(past_master_shards),
             shard_prefix: shard_prefix.to_string(),
             shards_folder,
+            past_shard_starts,
+            current_shard_start,
             config,
         }
     }
@@ -100,6 +115,7 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
         None
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(rows = data.len()))]
     pub fn insert_rows(&mut self, data: &[&[u8]]) -> usize {
         self.raw_insert_rows(data, false)
     }
@@ -110,6 +126,7 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
                 Self::extract_shard_signature(self.current_master_shard.get_path().clone())
                     .unwrap();
             let new_shard_number = shard_number + 1;
+            tracing::debug!(shard = new_shard_number, "rotating master shard");
 
             let shard = {
                 let shard_id = Uuid::new_v4();
@@ -125,6 +142,8 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
             // Add to past master
             {
                 let old_master = std::mem::replace(&mut self.current_master_shard, shard);
+                self.past_shard_starts.push(self.current_shard_start);
+                self.current_shard_start += (old_master.get_last_index() + 1) as usize;
                 let mut past_ms_writer = self.past_master_shards.write().unwrap();
                 let (_, shard_id, _) =
                     Self::extract_shard_signature(old_master.get_path()).unwrap();
@@ -132,6 +151,12 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
             }
         }
 
+        // A batch is written to a single shard until it is full in items, so it can take the file
+        // past `max_size`: the limit only decides when the next write goes to a new shard.
+        if !create_new_shard && self.reached_max_size() {
+            return self.raw_insert_rows(data, true);
+        }
+
         let available_space_in_master = self.current_master_shard.available_space();
 
         if let AvailableSpace::Fixed(size) = available_space_in_master {
@@ -147,34 +172,111 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
 
         let insert_data = &data[0..up_to];
 
-        let items_pos = {
-            let local_index = self.current_master_shard.insert_item(insert_data).unwrap();
-            let breaking_point = self.breaking_point();
-            match breaking_point {
-                None => local_index as usize,
-                Some(breaking_point) => {
-                    let reader = self.past_master_shards.read().unwrap();
-                    println!("{}", reader.len());
-                    let curr_items = reader.len() * breaking_point as usize;
-                    curr_items + local_index as usize
-                }
-            }
-        };
+        let local_index = self.current_master_shard.insert_item(insert_data).unwrap();
+        let items_pos = self.current_shard_start + local_index as usize;
 
         if data.len() > up_to {
             // Some data didn't fit, insert remaining data into a new shard
             let remaining_data = &data[up_to..];
-            let remaining_index = self.raw_insert_rows(remaining_data, true);
-            items_pos + remaining_index
+            self.raw_insert_rows(remaining_data, true)
         } else {
             items_pos
         }
     }
 
+    /// Whether the file of the current master shard reached the `max_size` of the config.
+    /// Empty shards never did, so a single item larger than the limit still gets written.
+    fn reached_max_size(&self) -> bool {
+        self.config.max_size().map_or(false, |max_size| {
+            self.current_master_shard.get_last_index() >= 0
+                && self.current_master_shard.size() >= max_size
+        })
+    }
+
     fn breaking_point(&self) -> Option<u64> {
         self.current_master_shard.breaking_point()
     }
 
+    /// Config the shards are opened and created with.
+    pub fn config(&self) -> &Opts {
+        &self.config
+    }
+
+    /// Positions held by every shard, `None` if shards have no limit (a single shard holds everything).
+    /// Shards rotated by `max_size` hold fewer, callers grouping positions by shard (e.g. bloom filters)
+    /// treat these as ranges of positions rather than as shard files.
+    pub fn shard_size(&self) -> Option<u64> {
+        self.breaking_point()
+    }
+
+    /// Global indexes of the items held by every shard file, past master shards first, in order.
+    /// Together they cover `0..len()`.
+    pub fn shard_ranges(&self) -> Vec<Range<usize>> {
+        let mut starts = self.past_shard_starts.clone();
+        starts.push(self.current_shard_start);
+
+        let ends = starts.iter().skip(1).copied().chain([self.len()]);
+        starts
+            .iter()
+            .zip(ends)
+            .map(|(start, end)| *start..end)
+            .collect()
+    }
+
+    /// Total amount of items across past master shards and the current master shard.
+    /// Every global index in `0..len()` can be resolved through `get_element`.
+    pub fn len(&self) -> usize {
+        self.current_shard_start + (self.current_master_shard.get_last_index() + 1) as usize
+    }
+
+    /// Size in bytes of every shard file, as found on disk.
+    pub fn file_size(&self) -> u64 {
+        let past_master_shards = self.past_master_shards.read().unwrap();
+
+        past_master_shards
+            .values()
+            .chain([&self.current_master_shard])
+            .filter_map(|shard| std::fs::metadata(shard.get_path()).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Waits until the items of every shard file are on disk.
+    pub fn sync(&self) -> Result<(), ShardErrors> {
+        for shard in self.past_master_shards.read().unwrap().values() {
+            shard.sync()?;
+        }
+
+        self.current_master_shard.sync()
+    }
+
+    /// Global indexes of the items whose bytes can't be located in their shard file, see `Shard::verify_framing`.
+    pub fn verify_framing(&self) -> Vec<u64> {
+        let past_master_shards = self.past_master_shards.read().unwrap();
+        let shards = past_master_shards
+            .values()
+            .chain([&self.current_master_shard]);
+
+        shards
+            .zip(self.shard_ranges())
+            .flat_map(|(shard, range)| {
+                shard
+                    .verify_framing()
+                    .into_iter()
+                    .map(move |index| (range.start + index) as u64)
+            })
+            .collect()
+    }
+
+    /// Reads every item back, returning the global index of the items that can't be read intact
+    /// (e.g. `ChecksumMismatch` on corrupted items).
+    pub fn verify(&self) -> Vec<u64> {
+        (0..self.len())
+            .filter(|index| self.get_element(*index).is_err())
+            .map(|index| index as u64)
+            .collect()
+    }
+
     pub fn get_element_from_specific(
         &self,
         shard: &S,
@@ -187,41 +289,32 @@ impl<S: Shard<Opts>, Opts: ShardConfig> MapShard<S, Opts> {
         self.get_element_from_specific(&self.current_master_shard, index)
     }
 
+    #[tracing::instrument(level = "trace", skip(self))]
     pub fn get_element(&self, index: usize) -> Result<Vec<u8>, ShardErrors> {
-        let breaking_point = self.breaking_point();
-
-        match breaking_point {
-            None => self.get_element_from_master(index),
-            Some(breaking_point) => {
-                let breaking_point_usize = breaking_point as usize;
-
-                let reader = self.past_master_shards.read().unwrap();
-                let shard_reversed = {
-                    let mut combined_shards: Vec<&S> = reader.values().rev().collect();
-                    combined_shards.push(&self.current_master_shard);
-                    combined_shards
-                };
-
-                // Calculate the total number of shards
-                let num_shards = shard_reversed.len();
-                // Determine which shard the index belongs to
-                let shard_index = index / breaking_point_usize;
-
-                if shard_index >= num_shards {
-                    return Err(ShardErrors::OutOfRange);
-                }
+        if index >= self.current_shard_start {
+            return self.get_element_from_master(index - self.current_shard_start);
+        }
 
-                // Calculate the local index within the selected shard
-                let local_index = index % breaking_point_usize;
+        let reader = self.past_master_shards.read().unwrap();
 
-                self.get_element_from_specific(shard_reversed[shard_index], local_index)
-            }
+        // Past shards are kept in insertion order, which is the same order as their starts.
+        // The first start is always 0, so the index belongs to the last shard starting at or before it.
+        let shard_index = self
+            .past_shard_starts
+            .partition_point(|start| *start <= index)
+            - 1;
+        let local_index = index - self.past_shard_starts[shard_index];
+
+        match reader.get_index(shard_index) {
+            Some((_, shard)) => self.get_element_from_specific(shard, local_index),
+            None => Err(ShardErrors::OutOfRange),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::compression::Compression;
     use crate::shard::map_shard::MapShard;
     use crate::shard::shards::data_shard::config::DataShardConfig;
     use crate::shard::shards::data_shard::shard::DataShard;
@@ -238,7 +331,12 @@ mod test {
         let context = MapShard::<DataShard, DataShardConfig>::new(
             fake_empty_table_path,
             "data_",
-            DataShardConfig { max_offsets: None },
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
         );
 
         assert!(context.past_master_shards.read().unwrap().is_empty());
@@ -262,7 +360,12 @@ mod test {
         let context = MapShard::<DataShard, DataShardConfig>::new(
             fake_partial_folder_path,
             "data_",
-            DataShardConfig { max_offsets: None },
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
         );
         assert!(!context.past_master_shards.read().unwrap().is_empty());
         assert_eq!(
@@ -291,6 +394,9 @@ mod test {
             "data_",
             DataShardConfig {
                 max_offsets: Some(1),
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
             },
         );
 
@@ -348,6 +454,9 @@ mod test {
             "data_",
             DataShardConfig {
                 max_offsets: Some(1),
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
             },
         );
 
@@ -359,5 +468,64 @@ mod test {
         ]);
 
         context.get_element(3).unwrap();
+
+        assert_eq!(context.len(), 4);
+        assert_eq!(context.get_element(0).unwrap(), b"1".to_vec());
+        assert_eq!(context.get_element(1).unwrap(), b"2".to_vec());
+
+        std::fs::remove_dir_all(fake_partial_folder_path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_shard_rotation_by_size() {
+        let fake_partial_folder_path = std::env::current_dir().unwrap().join(format!(
+            "./test_cases/fake-db-folder/{}",
+            Uuid::new_v4().to_string()
+        ));
+        std::fs::create_dir(&fake_partial_folder_path).unwrap();
+
+        let config = DataShardConfig {
+            max_offsets: Some(1000),
+            compression: Compression::None,
+            encryption: None,
+            max_size: Some(1),
+        };
+
+        let mut context = MapShard::<DataShard, DataShardConfig>::new(
+            fake_partial_folder_path.clone(),
+            "data_",
+            config.clone(),
+        );
+
+        // Every write goes to a new shard once the current one holds anything
+        context.insert_rows(&[&b"1".to_vec(), &b"2".to_vec()]);
+        let last = context.insert_rows(&[&b"3".to_vec()]);
+        assert_eq!(last, 2);
+        let last = context.insert_rows(&[&b"4".to_vec(), &b"5".to_vec(), &b"6".to_vec()]);
+        assert_eq!(last, 5);
+
+        assert_eq!(context.past_master_shards.read().unwrap().len(), 2);
+        assert_eq!(context.shard_ranges(), vec![0..2, 2..3, 3..6]);
+        assert_eq!(context.len(), 6);
+        for (index, item) in ["1", "2", "3", "4", "5", "6"].iter().enumerate() {
+            assert_eq!(
+                context.get_element(index).unwrap(),
+                item.as_bytes().to_vec()
+            );
+        }
+        assert!(context.get_element(6).is_err());
+
+        // Shards holding a varying amount of items are resolved the same way once reopened
+        drop(context);
+        let context = MapShard::<DataShard, DataShardConfig>::new(
+            fake_partial_folder_path.clone(),
+            "data_",
+            config,
+        );
+        assert_eq!(context.len(), 6);
+        assert_eq!(context.get_element(2).unwrap(), b"3".to_vec());
+        assert_eq!(context.get_element(5).unwrap(), b"6".to_vec());
+
+        std::fs::remove_dir_all(fake_partial_folder_path).unwrap();
     }
 }
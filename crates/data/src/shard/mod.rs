@@ -1,13 +1,25 @@
+use crate::encryption::EncryptionKey;
 use crate::errors::ShardErrors;
 use std::path::PathBuf;
 use uuid::Uuid;
+pub mod flush_policy;
 pub mod map_shard;
 pub mod shard_collection;
 pub mod shards;
 pub mod temp_collection;
 pub mod temp_map_shard;
 
-pub trait ShardConfig: Clone {}
+pub trait ShardConfig: Clone {
+    /// Key encrypting the items of the shards created with this config, if they are encrypted.
+    fn encryption(&self) -> Option<&EncryptionKey> {
+        None
+    }
+
+    /// Size in bytes a shard file can reach before the items go to a new shard, `None` for no limit.
+    fn max_size(&self) -> Option<u64> {
+        None
+    }
+}
 
 pub enum AvailableSpace {
     Fixed(usize),
@@ -82,6 +94,11 @@ pub trait Shard<Opts: ShardConfig> {
 
     fn get_path(&self) -> PathBuf;
 
+    /// Size in bytes of the file of the shard.
+    fn size(&self) -> u64 {
+        std::fs::metadata(self.get_path()).map_or(0, |metadata| metadata.len())
+    }
+
     fn get_last_index(&self) -> i64;
 
     fn read_item_from_index(&self, index: usize) -> Result<Vec<u8>, ShardErrors>;
@@ -90,6 +107,15 @@ pub trait Shard<Opts: ShardConfig> {
 
     fn insert_item(&self, data: &[&[u8]]) -> Result<u64, ShardErrors>;
 
+    /// Waits until the items written to the shard are on disk.
+    fn sync(&self) -> Result<(), ShardErrors>;
+
+    /// Indexes of the items whose bytes can't be located in the file of the shard (e.g. their offsets were
+    /// overwritten). Shards of fixed size items locate them by their index, so there is nothing to check.
+    fn verify_framing(&self) -> Vec<usize> {
+        vec![]
+    }
+
     fn get_id(&self) -> String;
 }
 
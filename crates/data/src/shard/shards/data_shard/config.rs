@@ -1,22 +1,114 @@
+use crate::compression::Compression;
+use crate::encryption::EncryptionKey;
+use crate::shard::flush_policy::FlushPolicy;
 use crate::shard::{ShardConfig, TempShardConfig};
 use crate::temp_offset_types::TempOffsetTypes;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug)]
 pub struct DataShardConfig {
     pub max_offsets: Option<u64>,
+    /// Compression of the items of the shards created with this config.
+    pub compression: Compression,
+    /// Key encrypting the items of the shards created with this config, `None` to store them in plaintext.
+    pub encryption: Option<EncryptionKey>,
+    /// Size in bytes a shard file can reach before rows go to a new shard, `None` to only rotate on `max_offsets`.
+    pub max_size: Option<u64>,
 }
 
-impl ShardConfig for DataShardConfig {}
+impl DataShardConfig {
+    /// Storage settings of the table the shards created with this config belong to, the encoding of its rows
+    /// and the flush policy of its temporary shards aside.
+    pub fn storage(&self) -> TableStorage {
+        TableStorage::new(self.compression).set_max_shard_size(self.max_size)
+    }
+}
+
+impl ShardConfig for DataShardConfig {
+    fn encryption(&self) -> Option<&EncryptionKey> {
+        self.encryption.as_ref()
+    }
+
+    fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+}
+
+/// How the rows of a table are encoded before being written (`encoding = "compact"` in `SchemeJS.toml`).
+///
+/// - `Json`: Rows are written as JSON, names of the columns included.
+/// - `Compact`: Rows are written positionally following the columns of the table, without their names.
+///
+/// Rows are read back whatever encoding they were written with, so the encoding of a table can change at any time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowEncoding {
+    #[default]
+    Json,
+    Compact,
+}
+
+/// Storage settings of the main shard of a table (`[tables.<table name>]` in `SchemeJS.toml`).
+///
+/// # Fields:
+/// - `compression`: Compression of the rows, only applied to the shards created from now on.
+/// - `max_shard_size`: Size in bytes a shard file can reach before rows go to a new shard, `None` for no limit
+///   other than the amount of rows a shard holds.
+/// - `encoding`: Encoding of the rows written from now on.
+/// - `flush`: When the rows of the temporary shards of the table are reconciled into the main shard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableStorage {
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub max_shard_size: Option<u64>,
+    #[serde(default)]
+    pub encoding: RowEncoding,
+    #[serde(default)]
+    pub flush: FlushPolicy,
+}
+
+impl TableStorage {
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            compression,
+            max_shard_size: None,
+            encoding: RowEncoding::Json,
+            flush: FlushPolicy::default(),
+        }
+    }
+
+    pub fn set_max_shard_size(mut self, max_shard_size: Option<u64>) -> Self {
+        self.max_shard_size = max_shard_size;
+        self
+    }
+
+    pub fn set_encoding(mut self, encoding: RowEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn set_flush(mut self, flush: FlushPolicy) -> Self {
+        self.flush = flush;
+        self
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct TempDataShardConfig {
     pub max_offsets: TempOffsetTypes,
+    /// Key encrypting the temporary shards (and their write-ahead logs), as they hold rows too.
+    pub encryption: Option<EncryptionKey>,
 }
 
 impl TempShardConfig<DataShardConfig> for TempDataShardConfig {
     fn to_config(&self) -> DataShardConfig {
         DataShardConfig {
             max_offsets: self.max_offsets.get_real_offset(),
+            // Temporary rows are compressed once reconciled into the main shard
+            compression: Compression::None,
+            encryption: self.encryption.clone(),
+            max_size: None,
         }
     }
 }
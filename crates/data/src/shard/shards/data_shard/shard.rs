@@ -1,4 +1,5 @@
 use crate::data_handler::DataHandler;
+use crate::encryption::EncryptionKey;
 use crate::errors::ShardErrors;
 use crate::shard::shards::data_shard::config::DataShardConfig;
 use crate::shard::shards::data_shard::shard_header::DataShardHeader;
@@ -11,12 +12,16 @@ use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
+/// Size of the checksum (crc32) stored before every item, computed over the item as stored (compressed and encrypted).
+const CHECKSUM_SIZE: usize = 4;
+
 #[derive(Debug)]
 pub struct DataShard {
     pub path: PathBuf,
     pub header: RwLock<DataShardHeader>,
     pub data: Arc<RwLock<DataHandler>>,
     pub id: Uuid,
+    encryption: Option<EncryptionKey>,
 }
 
 impl DataShard {
@@ -62,29 +67,106 @@ impl DataShard {
                     read_up_to
                 };
 
+                if end_pos < start_pos {
+                    return Err(ShardErrors::ErrorReadingByteRange);
+                }
                 let length = (end_pos - start_pos) as usize;
 
                 let read_bytes = data_reader.read_pointer(start_pos, length);
                 match read_bytes {
                     None => Err(ShardErrors::ErrorReadingByteRange),
-                    Some(b) => Ok(b),
+                    Some(b) => self.decode_item(&header_read, b),
                 }
             }
         }
     }
 }
 
+impl DataShard {
+    fn key(&self) -> Result<&EncryptionKey, ShardErrors> {
+        self.encryption
+            .as_ref()
+            .ok_or(ShardErrors::MissingEncryptionKey)
+    }
+
+    /// Turns `item` into the bytes stored in the shard: compressed, encrypted, then prefixed with its checksum,
+    /// as the header of the shard requires.
+    fn encode_item(&self, header: &DataShardHeader, item: &[u8]) -> Result<Vec<u8>, ShardErrors> {
+        let mut stored = header.compression.compress(item);
+
+        if header.encrypted {
+            stored = self.key()?.encrypt(&stored);
+        }
+
+        if header.checksums {
+            stored = Self::with_checksum(&stored);
+        }
+
+        Ok(stored)
+    }
+
+    /// Reverses `encode_item`.
+    fn decode_item(
+        &self,
+        header: &DataShardHeader,
+        stored: Vec<u8>,
+    ) -> Result<Vec<u8>, ShardErrors> {
+        let mut item = stored;
+
+        if header.checksums {
+            item = Self::verify_checksum(item)?;
+        }
+
+        if header.encrypted {
+            item = self.key()?.decrypt(&item)?;
+        }
+
+        header.compression.decompress(&item)
+    }
+
+    /// Prefixes `item` with its checksum, as items are stored in shards with checksums.
+    fn with_checksum(item: &[u8]) -> Vec<u8> {
+        let mut stored = Vec::with_capacity(CHECKSUM_SIZE + item.len());
+        stored.extend_from_slice(&crc32fast::hash(item).to_le_bytes());
+        stored.extend_from_slice(item);
+        stored
+    }
+
+    /// Strips the checksum off `stored`, failing with `ChecksumMismatch` if it doesn't match the item.
+    fn verify_checksum(mut stored: Vec<u8>) -> Result<Vec<u8>, ShardErrors> {
+        if stored.len() < CHECKSUM_SIZE {
+            return Err(ShardErrors::ChecksumMismatch);
+        }
+
+        let item = stored.split_off(CHECKSUM_SIZE);
+        let checksum = u32::from_le_bytes(stored.try_into().unwrap());
+
+        if crc32fast::hash(&item) != checksum {
+            return Err(ShardErrors::ChecksumMismatch);
+        }
+
+        Ok(item)
+    }
+}
+
 impl Shard<DataShardConfig> for DataShard {
     fn new(path: PathBuf, opts: DataShardConfig, uuid: Option<Uuid>) -> Self {
         let data_handler = unsafe { DataHandler::new(path.clone()) }.unwrap();
         let arc_dh = Arc::new(data_handler);
-        let header = DataShardHeader::new_from_file(arc_dh.clone(), opts.max_offsets, uuid);
+        let header = DataShardHeader::new_from_file(
+            arc_dh.clone(),
+            opts.max_offsets,
+            uuid,
+            opts.compression,
+            opts.encryption.is_some(),
+        );
 
         DataShard {
             path: path.clone(),
             data: arc_dh.clone(),
             id: header.id,
             header: RwLock::new(header),
+            encryption: opts.encryption,
         }
     }
 
@@ -102,6 +184,10 @@ impl Shard<DataShardConfig> for DataShard {
         self.path.clone()
     }
 
+    fn size(&self) -> u64 {
+        self.data.read().unwrap().len() as u64
+    }
+
     fn get_last_index(&self) -> i64 {
         let header_reader = self.header.read().unwrap();
         let last_index = header_reader.get_last_offset_index();
@@ -126,8 +212,14 @@ impl Shard<DataShardConfig> for DataShard {
 
     fn insert_item(&self, data: &[&[u8]]) -> Result<u64, ShardErrors> {
         let mut header_write = self.header.write().unwrap();
+        let stored = data
+            .iter()
+            .map(|item| self.encode_item(&header_write, item))
+            .collect::<Result<Vec<Vec<u8>>, ShardErrors>>()?;
+        let data: Vec<&[u8]> = stored.iter().map(|item| item.as_slice()).collect();
+
         let op = self.data.write().unwrap().operate(|file| {
-            let write_data = flatten(data);
+            let write_data = flatten(&data);
 
             // Calculate the current end of the file
             let end_of_file = file
@@ -140,7 +232,7 @@ impl Shard<DataShardConfig> for DataShard {
 
             let mut curr_offset = end_of_file;
 
-            for item in data {
+            for item in data.iter() {
                 header_write
                     .add_next_offset(curr_offset, file)
                     .map_err(|e| Error::new(ErrorKind::OutOfMemory, "Out of position"))?;
@@ -161,6 +253,49 @@ impl Shard<DataShardConfig> for DataShard {
         }
     }
 
+    /// Items are framed by their offset and the offset of the next item (the end of the file for the last one).
+    /// Frames have to start past the header, end before the file does and hold the checksum of their item.
+    fn verify_framing(&self) -> Vec<usize> {
+        let header = self.header.read().unwrap();
+        let file_len = self.data.read().unwrap().len() as u64;
+        let header_len = header.max_offset_positions as u64;
+        let min_len = match header.checksums {
+            true => CHECKSUM_SIZE as u64,
+            false => 0,
+        };
+        let last_index = header.get_last_offset_index();
+
+        let offset = |index: usize| {
+            header
+                .get_offset_pos_by_index(index)
+                .and_then(|pos| header.get_offset_value_from_offset_header(pos))
+        };
+
+        (0..(last_index + 1) as usize)
+            .filter(|index| {
+                let end = match *index as i64 == last_index {
+                    true => Some(file_len),
+                    false => offset(index + 1),
+                };
+
+                match (offset(*index), end) {
+                    (Some(start), Some(end)) => {
+                        start < header_len || end < start + min_len || end > file_len
+                    }
+                    _ => true,
+                }
+            })
+            .collect()
+    }
+
+    fn sync(&self) -> Result<(), ShardErrors> {
+        self.data
+            .read()
+            .unwrap()
+            .sync()
+            .map_err(|_| ShardErrors::FlushingError)
+    }
+
     fn get_id(&self) -> String {
         self.id.to_string()
     }
@@ -168,12 +303,16 @@ impl Shard<DataShardConfig> for DataShard {
 
 #[cfg(test)]
 mod test {
+    use crate::compression::Compression;
     use crate::errors::ShardErrors;
     use crate::shard::shards::data_shard::config::DataShardConfig;
     use crate::shard::shards::data_shard::shard::DataShard;
+    use crate::shard::shards::UUID_BYTE_LEN;
     use crate::shard::Shard;
+    use crate::{I64_SIZE, U64_SIZE};
     use std::fs::File;
     use std::io::Read;
+    use std::os::unix::fs::FileExt;
     use std::sync::{Arc, RwLock};
     use tempfile::{tempdir, tempfile};
     use uuid::Uuid;
@@ -187,6 +326,9 @@ mod test {
 
         let config = DataShardConfig {
             max_offsets: Some(10),
+            compression: Compression::None,
+            encryption: None,
+            max_size: None,
         };
 
         let data_shard = DataShard::new(file_path.clone(), config, None);
@@ -241,6 +383,9 @@ mod test {
 
         let config = DataShardConfig {
             max_offsets: Some(10),
+            compression: Compression::None,
+            encryption: None,
+            max_size: None,
         };
 
         let data_shard = DataShard::new(file_path.clone(), config, None);
@@ -268,6 +413,9 @@ mod test {
             file_path.clone(),
             DataShardConfig {
                 max_offsets: Some(10),
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
             },
             None,
         );
@@ -286,6 +434,9 @@ mod test {
             file_path,
             DataShardConfig {
                 max_offsets: Some(2),
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
             },
             None,
         );
@@ -315,4 +466,144 @@ mod test {
         /*let item = shard.read().unwrap().header.read().unwrap().offsets.len();
         assert_eq!(item, 2);*/
     }
+
+    #[tokio::test]
+    pub async fn test_data_shard_checksums() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir
+            .path()
+            .join(format!("{}.bin", Uuid::new_v4().to_string()));
+
+        let config = DataShardConfig {
+            max_offsets: Some(10),
+            compression: Compression::None,
+            encryption: None,
+            max_size: None,
+        };
+
+        {
+            let data_shard = DataShard::new(file_path.clone(), config.clone(), None);
+            data_shard
+                .insert_item(&[b"Hello World", b"Cats are cute", b"Venezuela"])
+                .unwrap();
+        }
+
+        // Flips a byte of "Cats are cute"
+        let mut bytes = std::fs::read(&file_path).unwrap();
+        let position = bytes
+            .windows(4)
+            .position(|window| window == b"Cats")
+            .unwrap();
+        bytes[position] ^= 0xff;
+        std::fs::write(&file_path, bytes).unwrap();
+
+        let data_shard = DataShard::new(file_path, config, None);
+        assert!(data_shard.header.read().unwrap().checksums);
+        assert_eq!(
+            data_shard.read_item_from_index(0).unwrap(),
+            b"Hello World".to_vec()
+        );
+        assert!(data_shard
+            .read_item_from_index(1)
+            .err()
+            .unwrap()
+            .is_checksum_mismatch());
+        assert_eq!(
+            data_shard.read_item_from_index(2).unwrap(),
+            b"Venezuela".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_data_shard_framing() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir
+            .path()
+            .join(format!("{}.bin", Uuid::new_v4().to_string()));
+
+        let config = DataShardConfig {
+            max_offsets: Some(10),
+            compression: Compression::None,
+            encryption: None,
+            max_size: None,
+        };
+
+        {
+            let data_shard = DataShard::new(file_path.clone(), config.clone(), None);
+            data_shard
+                .insert_item(&[b"Hello World", b"Cats are cute", b"Venezuela"])
+                .unwrap();
+            assert!(data_shard.verify_framing().is_empty());
+        }
+
+        // Points the offset of "Cats are cute" past the end of the file
+        let offset_position = U64_SIZE + I64_SIZE + UUID_BYTE_LEN as usize + U64_SIZE;
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .write_at(&u64::MAX.to_le_bytes(), offset_position as u64)
+            .unwrap();
+
+        let data_shard = DataShard::new(file_path, config, None);
+        assert_eq!(data_shard.verify_framing(), vec![0, 1]);
+        assert!(data_shard.read_item_from_index(1).is_err());
+        assert_eq!(
+            data_shard.read_item_from_index(2).unwrap(),
+            b"Venezuela".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_data_shard_compression() {
+        let temp_dir = tempdir().unwrap();
+        let row = r#"{"user_name":"andreespirela","user_email":"andreespirela@outlook.com"}"#;
+
+        for compression in [Compression::Lz4, Compression::Zstd] {
+            let file_path = temp_dir
+                .path()
+                .join(format!("{}.bin", Uuid::new_v4().to_string()));
+
+            {
+                let data_shard = DataShard::new(
+                    file_path.clone(),
+                    DataShardConfig {
+                        max_offsets: Some(10),
+                        compression,
+                        encryption: None,
+                        max_size: None,
+                    },
+                    None,
+                );
+                data_shard
+                    .insert_item(&[row.repeat(10).as_bytes(), b"Venezuela"])
+                    .unwrap();
+            }
+
+            // The whole shard, header included, takes less than the row
+            assert!(std::fs::metadata(&file_path).unwrap().len() < (row.len() * 10) as u64);
+
+            // Shards keep the compression they were created with
+            let data_shard = DataShard::new(
+                file_path,
+                DataShardConfig {
+                    max_offsets: Some(10),
+                    compression: Compression::None,
+                    encryption: None,
+                    max_size: None,
+                },
+                None,
+            );
+            assert_eq!(data_shard.header.read().unwrap().compression, compression);
+            assert_eq!(data_shard.header.read().unwrap().get_max_offsets(), 10);
+            assert_eq!(
+                data_shard.read_item_from_index(0).unwrap(),
+                row.repeat(10).into_bytes()
+            );
+            assert_eq!(
+                data_shard.read_item_from_index(1).unwrap(),
+                b"Venezuela".to_vec()
+            );
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::compression::Compression;
 use crate::data_handler::DataHandler;
 use crate::errors::ShardErrors;
 use crate::shard::shards::UUID_BYTE_LEN;
@@ -11,6 +12,20 @@ use uuid::Uuid;
 
 pub const DEFAULT_MAX_OFFSETS: u64 = 100;
 
+/// Bit of the stored `max_offsets` telling whether items are stored along with their checksum.
+/// Shards written before checksums existed don't have it set and are read as they are.
+const CHECKSUMS_FLAG: u64 = 1 << 63;
+
+/// Bits of the stored `max_offsets` holding the compression of the items (see `Compression::to_id`).
+/// Shards written before compression existed have them unset, as uncompressed shards do.
+const COMPRESSION_SHIFT: u64 = 61;
+const COMPRESSION_MASK: u64 = 0b11 << COMPRESSION_SHIFT;
+
+/// Bit of the stored `max_offsets` telling whether items are encrypted (see `EncryptionKey`).
+const ENCRYPTED_FLAG: u64 = 1 << 60;
+
+const FLAGS_MASK: u64 = CHECKSUMS_FLAG | COMPRESSION_MASK | ENCRYPTED_FLAG;
+
 // TODO: Header version
 
 #[derive(Debug)]
@@ -19,16 +34,28 @@ pub struct DataShardHeader {
     last_offset_index: i64, // Even though this is realistically a u64, we use i64 because if everything is empty, it will be -1 which can't be with u64
     pub max_offset_positions: usize,
     pub id: Uuid,
+    pub checksums: bool,
+    pub compression: Compression,
+    pub encrypted: bool,
     data: Arc<RwLock<DataHandler>>,
 }
 
 impl DataShardHeader {
-    pub fn new(max_offsets: u64, uuid: Option<Uuid>, data: Arc<RwLock<DataHandler>>) -> Self {
+    pub fn new(
+        max_offsets: u64,
+        uuid: Option<Uuid>,
+        compression: Compression,
+        encrypted: bool,
+        data: Arc<RwLock<DataHandler>>,
+    ) -> Self {
         Self {
             max_offsets,
             last_offset_index: -1,
             id: uuid.unwrap_or_else(|| Uuid::new_v4()),
             max_offset_positions: Self::calculate_offset_pos(max_offsets as usize),
+            checksums: true,
+            compression,
+            encrypted,
             data,
         }
     }
@@ -41,10 +68,14 @@ impl DataShardHeader {
         file: Arc<RwLock<DataHandler>>,
         max_offsets: Option<u64>,
         uuid: Option<Uuid>,
+        compression: Compression,
+        encrypted: bool,
     ) -> Self {
         let mut header = DataShardHeader::new(
             max_offsets.unwrap_or(DEFAULT_MAX_OFFSETS),
             uuid,
+            compression,
+            encrypted,
             file.clone(),
         );
 
@@ -87,7 +118,11 @@ impl DataShardHeader {
 
                 {
                     // Write max_offsets to the buffer
-                    let max_offsets_bytes = (self.max_offsets).to_le_bytes();
+                    let checksums = if self.checksums { CHECKSUMS_FLAG } else { 0 };
+                    let encrypted = if self.encrypted { ENCRYPTED_FLAG } else { 0 };
+                    let flags =
+                        checksums | encrypted | (self.compression.to_id() << COMPRESSION_SHIFT);
+                    let max_offsets_bytes = (self.max_offsets | flags).to_le_bytes();
                     buffer.extend_from_slice(&max_offsets_bytes);
                 }
 
@@ -122,8 +157,14 @@ impl DataShardHeader {
         let reader = self.data.read().unwrap();
         {
             let max_offset_bytes = reader.get_bytes(0, U64_SIZE).unwrap();
-            let max_offset_bytes: [u8; 8] = max_offset_bytes.try_into().unwrap();
-            self.max_offsets = u64::from_le_bytes(max_offset_bytes);
+            let max_offset_bytes: [u8; 8] = max_offset_bytes.as_ref().try_into().unwrap();
+            let max_offsets = u64::from_le_bytes(max_offset_bytes);
+            self.checksums = max_offsets & CHECKSUMS_FLAG != 0;
+            self.compression =
+                Compression::from_id((max_offsets & COMPRESSION_MASK) >> COMPRESSION_SHIFT)
+                    .expect("Unknown shard compression");
+            self.encrypted = max_offsets & ENCRYPTED_FLAG != 0;
+            self.max_offsets = max_offsets & !FLAGS_MASK;
         }
 
         self.max_offset_positions = Self::calculate_offset_pos(self.max_offsets as usize);
@@ -1,9 +1,16 @@
+use crate::encryption::EncryptionKey;
 use crate::shard::ShardConfig;
 
 #[derive(Debug, Clone)]
 pub struct KvShardConfig {
     pub value_size: usize,
     pub max_capacity: Option<u64>,
+    /// Key encrypting every element, `None` to store them in plaintext.
+    pub encryption: Option<EncryptionKey>,
 }
 
-impl ShardConfig for KvShardConfig {}
+impl ShardConfig for KvShardConfig {
+    fn encryption(&self) -> Option<&EncryptionKey> {
+        self.encryption.as_ref()
+    }
+}
@@ -1,4 +1,5 @@
 use crate::data_handler::DataHandler;
+use crate::encryption::{EncryptionKey, ENCRYPTION_OVERHEAD};
 use crate::errors::ShardErrors;
 use crate::shard::shards::kv::config::KvShardConfig;
 use crate::shard::shards::kv::shard_header::KvShardHeader;
@@ -6,7 +7,7 @@ use crate::shard::shards::kv::util::get_element_offset;
 use crate::shard::{AvailableSpace, Shard};
 use crate::utils::flatten;
 use std::fs::File;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::{Error, ErrorKind, Seek, SeekFrom, Write};
 use std::os::unix::fs::FileExt;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
@@ -18,15 +19,54 @@ pub struct KvShard {
     pub data: Arc<RwLock<DataHandler>>,
     pub header: RwLock<KvShardHeader>,
     pub value_size: usize,
+    /// Size of the elements as stored, `value_size` plus the encryption overhead in encrypted shards.
+    element_size: usize,
     max_capacity: usize,
     id: Uuid,
+    encryption: Option<EncryptionKey>,
 }
 
 impl KvShard {
     pub fn get_element(&self, index: usize) -> Option<Vec<u8>> {
+        self.read_element(index).ok()
+    }
+
+    fn read_element(&self, index: usize) -> Result<Vec<u8>, ShardErrors> {
         let reader = self.data.read().unwrap();
-        let starting_point = Self::get_element_offset(index, self.value_size) as u64;
-        reader.read_pointer(starting_point, self.value_size)
+        let starting_point = Self::get_element_offset(index, self.element_size) as u64;
+        let stored = reader
+            .read_pointer(starting_point, self.element_size)
+            .ok_or(ShardErrors::UnknownEntry)?;
+
+        match self.header.read().unwrap().encrypted {
+            true => self.key()?.decrypt(&stored),
+            false => Ok(stored),
+        }
+    }
+
+    fn key(&self) -> Result<&EncryptionKey, ShardErrors> {
+        self.encryption
+            .as_ref()
+            .ok_or(ShardErrors::MissingEncryptionKey)
+    }
+
+    /// Turns `element` into the bytes stored in the shard, encrypting it in encrypted shards.
+    fn encode_element(&self, element: &[u8]) -> Result<Vec<u8>, ShardErrors> {
+        match self.header.read().unwrap().encrypted {
+            true => Ok(self.key()?.encrypt(element)),
+            false => Ok(element.to_vec()),
+        }
+    }
+
+    fn write_element(&self, file: &mut File, i: usize, element: &[u8]) -> Result<(), Error> {
+        let stored = self
+            .encode_element(element)
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        file.write_at(
+            &stored,
+            Self::get_element_offset(i, self.element_size) as u64,
+        )?;
+        Ok(())
     }
 
     fn get_element_offset(index: usize, value_size: usize) -> usize {
@@ -40,16 +80,20 @@ impl KvShard {
         first_element: &[u8],
         second_element: &[u8],
     ) -> Result<(), std::io::Error> {
-        file.write_at(
-            second_element,
-            Self::get_element_offset(i, self.value_size) as u64,
-        )?;
-        file.write_at(
-            first_element,
-            Self::get_element_offset(i - 1, self.value_size) as u64,
-        )?;
+        self.write_element(file, i, second_element)?;
+        self.write_element(file, i - 1, first_element)?;
         Ok(())
     }
+
+    /// Overwrites the element at position `i` with `element`, which must be `value_size` long.
+    pub fn replace_element(
+        &self,
+        file: &mut File,
+        i: usize,
+        element: &[u8],
+    ) -> Result<(), std::io::Error> {
+        self.write_element(file, i, element)
+    }
 }
 
 impl Shard<KvShardConfig> for KvShard {
@@ -63,15 +107,23 @@ impl Shard<KvShardConfig> for KvShard {
             Some(0),
             opts.max_capacity,
             opts.value_size as u64,
+            opts.encryption.is_some(),
         );
 
+        let element_size = match header.encrypted {
+            true => header.value_size as usize + ENCRYPTION_OVERHEAD,
+            false => header.value_size as usize,
+        };
+
         Self {
             path,
             data: data.clone(),
             max_capacity: header.max_capacity.unwrap_or(0) as usize,
             value_size: header.value_size as usize,
+            element_size,
             id: header.id,
             header: RwLock::new(header),
+            encryption: opts.encryption,
         }
     }
 
@@ -105,10 +157,7 @@ impl Shard<KvShardConfig> for KvShard {
     }
 
     fn read_item_from_index(&self, index: usize) -> Result<Vec<u8>, ShardErrors> {
-        match self.get_element(index) {
-            None => Err(ShardErrors::UnknownEntry),
-            Some(v) => Ok(v),
-        }
+        self.read_element(index)
     }
 
     fn available_space(&self) -> AvailableSpace {
@@ -121,6 +170,12 @@ impl Shard<KvShardConfig> for KvShard {
     }
 
     fn insert_item(&self, data: &[&[u8]]) -> Result<u64, ShardErrors> {
+        let stored = data
+            .iter()
+            .map(|element| self.encode_element(element))
+            .collect::<Result<Vec<Vec<u8>>, ShardErrors>>()?;
+        let stored: Vec<&[u8]> = stored.iter().map(|element| element.as_slice()).collect();
+
         let mut writer = self.data.write().unwrap();
         writer
             .operate(|file| {
@@ -128,7 +183,7 @@ impl Shard<KvShardConfig> for KvShard {
                     .seek(SeekFrom::End(0))
                     .expect("Failed to seek to end of file");
 
-                let flat_items = flatten(data);
+                let flat_items = flatten(&stored);
 
                 file.write_all(&flat_items)
                     .expect("Failed to write item to file");
@@ -148,6 +203,14 @@ impl Shard<KvShardConfig> for KvShard {
             .map_err(|e| ShardErrors::ErrorAddingEntry)
     }
 
+    fn sync(&self) -> Result<(), ShardErrors> {
+        self.data
+            .read()
+            .unwrap()
+            .sync()
+            .map_err(|_| ShardErrors::FlushingError)
+    }
+
     fn get_id(&self) -> String {
         self.id.to_string()
     }
@@ -155,6 +218,7 @@ impl Shard<KvShardConfig> for KvShard {
 
 #[cfg(test)]
 mod test {
+    use crate::encryption::EncryptionKey;
     use crate::shard::shards::kv::config::KvShardConfig;
     use crate::shard::shards::kv::shard::KvShard;
     use crate::shard::Shard;
@@ -173,6 +237,7 @@ mod test {
             KvShardConfig {
                 value_size: 1,
                 max_capacity: None,
+                encryption: None,
             },
             None,
         );
@@ -202,4 +267,48 @@ mod test {
 
         assert!(kv_shard.get_element(3).is_none(),);
     }
+
+    #[tokio::test]
+    pub async fn test_kv_shard_encryption() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir
+            .path()
+            .join(format!("{}.index", Uuid::new_v4().to_string()));
+        let config = |encryption: Option<EncryptionKey>| KvShardConfig {
+            value_size: 5,
+            max_capacity: None,
+            encryption,
+        };
+        let key = EncryptionKey::from_secret("secret");
+
+        {
+            let kv_shard = KvShard::new(file_path.clone(), config(Some(key.clone())), None);
+            kv_shard
+                .insert_item(&[b"apple", b"grape", b"mango"])
+                .unwrap();
+
+            let mut writer = kv_shard.data.write().unwrap();
+            writer
+                .operate(|file| kv_shard.swap_elements(file, 1, b"grape", b"apple"))
+                .unwrap();
+        }
+
+        let bytes = std::fs::read(&file_path).unwrap();
+        assert!(!bytes.windows(5).any(|window| window == b"mango"));
+
+        let kv_shard = KvShard::new(file_path.clone(), config(Some(key)), None);
+        assert!(kv_shard.header.read().unwrap().encrypted);
+        assert_eq!(kv_shard.value_size, 5);
+        assert_eq!(kv_shard.get_element(0).unwrap(), b"grape".to_vec());
+        assert_eq!(kv_shard.get_element(1).unwrap(), b"apple".to_vec());
+        assert_eq!(kv_shard.get_element(2).unwrap(), b"mango".to_vec());
+        assert!(kv_shard.get_element(3).is_none());
+
+        let kv_shard = KvShard::new(file_path, config(None), None);
+        assert!(kv_shard
+            .read_item_from_index(0)
+            .err()
+            .unwrap()
+            .is_missing_encryption_key());
+    }
 }
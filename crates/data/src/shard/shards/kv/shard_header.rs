@@ -7,12 +7,16 @@ use std::os::unix::fs::FileExt;
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
+/// Bit of the stored `value_size` telling whether elements are encrypted (see `EncryptionKey`).
+const ENCRYPTED_FLAG: u64 = 1 << 63;
+
 #[derive(Debug)]
 pub struct KvShardHeader {
     pub max_capacity: Option<u64>,
     pub items_len: u64,
     pub value_size: u64,
     pub id: Uuid,
+    pub encrypted: bool,
     data: Arc<RwLock<DataHandler>>,
 }
 
@@ -22,6 +26,7 @@ impl KvShardHeader {
         max_capacity: Option<u64>,
         value_size: u64,
         uuid: Option<Uuid>,
+        encrypted: bool,
         data: Arc<RwLock<DataHandler>>,
     ) -> Self {
         Self {
@@ -30,6 +35,7 @@ impl KvShardHeader {
             data,
             id: uuid.unwrap_or_else(|| Uuid::new_v4()),
             value_size,
+            encrypted,
         }
     }
 
@@ -39,12 +45,14 @@ impl KvShardHeader {
         items_len: Option<u64>,
         max_capacity: Option<u64>,
         value_size: u64,
+        encrypted: bool,
     ) -> Self {
         let mut header = KvShardHeader::new(
             items_len.unwrap_or(0),
             max_capacity,
             value_size,
             uuid,
+            encrypted,
             file.clone(),
         );
 
@@ -100,7 +108,8 @@ impl KvShardHeader {
 
                 {
                     // Write value_size to the buffer
-                    let value_size_bytes = (self.value_size).to_le_bytes();
+                    let flags = if self.encrypted { ENCRYPTED_FLAG } else { 0 };
+                    let value_size_bytes = (self.value_size | flags).to_le_bytes();
                     buffer.extend_from_slice(&value_size_bytes);
                 }
 
@@ -110,8 +119,6 @@ impl KvShardHeader {
                     buffer.extend_from_slice(&id_bytes);
                 }
 
-                println!("{:?}", buffer);
-
                 // Write the buffer to the file
                 file.write_all(&buffer)
                     .expect("Failed to write Index header");
@@ -125,7 +132,7 @@ impl KvShardHeader {
         let reader = self.data.read().unwrap();
         {
             let max_capacity_bytes = reader.get_bytes(0, U64_SIZE).unwrap();
-            let max_capacity_bytes: [u8; 8] = max_capacity_bytes.try_into().unwrap();
+            let max_capacity_bytes: [u8; 8] = max_capacity_bytes.as_ref().try_into().unwrap();
             self.max_capacity = Some(u64::from_le_bytes(max_capacity_bytes));
         }
 
@@ -140,7 +147,9 @@ impl KvShardHeader {
                 .read_pointer(U64_SIZE as u64 + U64_SIZE as u64, U64_SIZE)
                 .unwrap();
             let value_size_bytes: [u8; 8] = value_size_bytes.try_into().unwrap();
-            self.value_size = u64::from_le_bytes(value_size_bytes);
+            let value_size = u64::from_le_bytes(value_size_bytes);
+            self.encrypted = value_size & ENCRYPTED_FLAG != 0;
+            self.value_size = value_size & !ENCRYPTED_FLAG;
         }
 
         {
@@ -154,12 +163,12 @@ impl KvShardHeader {
             self.id = Uuid::from_bytes_le(id_bytes);
         }
 
-        println!(
-            "{} {} {} {}",
-            self.max_capacity.unwrap(),
-            self.items_len,
-            self.value_size,
-            self.id.to_string()
+        tracing::trace!(
+            max_capacity = ?self.max_capacity,
+            items = self.items_len,
+            value_size = self.value_size,
+            id = %self.id,
+            "Read shard header"
         );
     }
 
@@ -1,16 +1,29 @@
 use crate::errors::ShardErrors;
+use crate::shard::flush_policy::FlushPolicy;
 use crate::shard::map_shard::MapShard;
 use crate::shard::temp_map_shard::TempMapShard;
 use crate::shard::{Shard, ShardConfig, TempShardConfig};
+use crate::utils::fs::list_files_with_prefix;
+use crate::wal::{WriteAheadLog, WAL_PREFIX};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use std::time::SystemTime;
+
+static NEXT_THREAD_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Temporary shard (modulo the amount of temporary shards) the current thread inserts into
+    static THREAD_SLOT: usize = NEXT_THREAD_SLOT.fetch_add(1, Ordering::Relaxed);
+}
 
 #[derive(Debug)]
 pub struct TempCollection<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>> {
     pub target_shard: Arc<RwLock<MapShard<S, Opts>>>,
     pub temps: Arc<Vec<RwLock<TempMapShard<S, Opts, TempOpts>>>>,
-    counter: AtomicUsize,
+    folder: PathBuf,
+    prefix: String,
+    flush_policy: Option<FlushPolicy>,
 }
 
 impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
@@ -37,13 +50,59 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
         Self {
             target_shard,
             temps: Arc::new(temps),
-            counter: AtomicUsize::new(0),
+            folder,
+            prefix: prefix.to_string(),
+            flush_policy: None,
+        }
+    }
+
+    /// Flushes every temporary shard following `policy`, see `TempMapShard::set_flush_policy`.
+    pub fn set_flush_policy(&mut self, policy: Option<FlushPolicy>) {
+        for temp in self.temps.iter() {
+            temp.write().unwrap().set_flush_policy(policy);
+        }
+        self.flush_policy = policy;
+    }
+
+    pub fn flush_policy(&self) -> Option<FlushPolicy> {
+        self.flush_policy
+    }
+
+    /// Reconciles the temporary shards a limit of the flush policy was reached for, e.g. the age of their rows
+    /// as no insert came to check it.
+    pub fn flush_due(&self) {
+        for temp in self.temps.iter() {
+            if temp.read().unwrap().is_flush_due() {
+                let mut temp = temp.write().unwrap();
+                if temp.is_flush_due() {
+                    temp.reconcile_all();
+                }
+            }
         }
     }
 
-    fn get_next_shard(&self) -> &RwLock<TempMapShard<S, Opts, TempOpts>> {
-        let index = self.counter.fetch_add(1, Ordering::Relaxed) % self.temps.len();
-        &self.temps[index]
+    /// Index of the temporary shard the current thread inserts into. Threads are spread over the temporary
+    /// shards as they first insert, so concurrent writers don't contend for the same shard.
+    pub fn thread_shard(&self) -> usize {
+        THREAD_SLOT.with(|slot| *slot) % self.temps.len()
+    }
+
+    /// Locks the temporary shard of the current thread for writing. If it is locked (e.g. being reconciled,
+    /// or shared with another thread), the other shards are tried, so inserts only wait when every shard is busy.
+    fn lock_next_shard(
+        &self,
+    ) -> Result<RwLockWriteGuard<'_, TempMapShard<S, Opts, TempOpts>>, ShardErrors> {
+        let start = self.thread_shard();
+
+        for offset in 0..self.temps.len() {
+            if let Ok(shard) = self.temps[(start + offset) % self.temps.len()].try_write() {
+                return Ok(shard);
+            }
+        }
+
+        self.temps[start % self.temps.len()]
+            .write()
+            .map_err(|_e| ShardErrors::InvalidLocking)
     }
 
     pub fn reconcile_all(&self) {
@@ -52,11 +111,238 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
         }
     }
 
+    /// Amount of rows waiting in the temporary shards to be reconciled.
+    pub fn pending_rows(&self) -> u64 {
+        self.temps
+            .iter()
+            .map(|temp| temp.read().unwrap().pending_rows())
+            .sum()
+    }
+
+    /// Rows waiting in the temporary shards to be reconciled, read along with the result of `during`.
+    ///
+    /// Reconciliations hold the temporary shard they empty locked for writing. As every temporary shard is locked
+    /// for reading meanwhile, a row is either returned or was moved to the main shard before `during` runs.
+    pub fn read_pending<R, F: FnOnce() -> R>(&self, during: F) -> (R, Vec<Vec<u8>>) {
+        let temps: Vec<_> = self.temps.iter().map(|temp| temp.read().unwrap()).collect();
+        let result = during();
+
+        (
+            result,
+            temps.iter().flat_map(|temp| temp.pending_items()).collect(),
+        )
+    }
+
+    /// Bytes of the rows inserted since the temporary shards were last emptied.
+    pub fn pending_bytes(&self) -> u64 {
+        self.temps
+            .iter()
+            .map(|temp| temp.read().unwrap().pending_bytes())
+            .sum()
+    }
+
+    /// When rows of any temporary shard were last moved to the main shard, `None` if they never were.
+    pub fn last_reconcile(&self) -> Option<SystemTime> {
+        self.temps
+            .iter()
+            .filter_map(|temp| temp.read().unwrap().last_reconcile())
+            .max()
+    }
+
+    /// Same as `reconcile_all`, but only the temporary shards holding rows are locked for writing,
+    /// so it is cheap to call when there is nothing to reconcile.
+    pub fn reconcile_pending(&self) {
+        for temp in self.temps.iter() {
+            if temp.read().unwrap().pending_rows() > 0 {
+                temp.write().unwrap().reconcile_all()
+            }
+        }
+    }
+
+    /// Restores the rows left in the folder by a previous run that didn't reconcile them (e.g. after a crash).
+    ///
+    /// Rows are restored from the write-ahead logs of the run, see `TempMapShard::replay`.
+    /// Its temporary shards only hold rows of the logs, so they are removed. Runs that had no logs only left
+    /// temporary shards behind, their items are written to the main shard (see `TempMapShard::replay_shard`).
+    ///
+    /// Restored rows go through the reconciliation callback of the first temporary shard,
+    /// so it must be called once the callbacks are set and before anything is inserted.
+    pub fn recover(&self) -> Result<(), ShardErrors> {
+        let own_logs: Vec<PathBuf> = self
+            .temps
+            .iter()
+            .map(|temp| temp.read().unwrap().wal.path.clone())
+            .collect();
+
+        let prefix = format!("{}{}", WAL_PREFIX, self.prefix);
+        let logs: Vec<PathBuf> = list_files_with_prefix(&self.folder, &prefix)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|log| !own_logs.contains(log))
+            .collect();
+        let had_logs = !logs.is_empty();
+        let encryption = self.temps[0].read().unwrap().wal.encryption().cloned();
+
+        for log in logs {
+            let wal = WriteAheadLog::new(&log, encryption.clone());
+
+            if !wal.entries().is_empty() {
+                self.temps[0].read().unwrap().replay(&wal)?;
+            }
+
+            std::fs::remove_file(&log).map_err(|_| ShardErrors::FlushingError)?;
+        }
+
+        let orphans = list_files_with_prefix(&self.folder, &self.prefix).unwrap_or_default();
+
+        for orphan in orphans {
+            if had_logs {
+                std::fs::remove_file(&orphan).map_err(|_| ShardErrors::FlushingError)?;
+            } else {
+                self.temps[0].read().unwrap().replay_shard(orphan)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn insert(&self, data: &[u8]) -> Result<u64, ShardErrors> {
-        let mut next_shard = self
-            .get_next_shard()
-            .write()
-            .map_err(|_e| ShardErrors::InvalidLocking)?;
+        let mut next_shard = self.lock_next_shard()?;
         next_shard.insert_row(data)
     }
+
+    /// Inserts every item of `data` in the temporary shard of the current thread, taking its lock only once.
+    pub fn insert_many(&self, data: &[&[u8]]) -> Result<(), ShardErrors> {
+        let mut next_shard = self.lock_next_shard()?;
+        next_shard.insert_rows(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compression::Compression;
+    use crate::shard::map_shard::MapShard;
+    use crate::shard::shards::data_shard::config::{DataShardConfig, TempDataShardConfig};
+    use crate::shard::shards::data_shard::shard::DataShard;
+    use crate::shard::temp_collection::TempCollection;
+    use crate::temp_offset_types::TempOffsetTypes;
+    use std::sync::{Arc, RwLock};
+
+    #[tokio::test]
+    pub async fn test_temp_collection_skips_locked_shards() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_path = temp_dir.path().to_path_buf();
+
+        let parent_shard = Arc::new(RwLock::new(MapShard::<DataShard, DataShardConfig>::new(
+            data_path.clone(),
+            "localdata_",
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
+        )));
+
+        let collection = TempCollection::new(
+            parent_shard.clone(),
+            3,
+            data_path.clone(),
+            "tempdata_",
+            TempDataShardConfig {
+                max_offsets: TempOffsetTypes::Custom(Some(100)),
+                encryption: None,
+            },
+        );
+
+        // A shard held for writing (e.g. being reconciled) doesn't hold inserts back
+        let busy = collection.temps[0].write().unwrap();
+        for i in 0..6 {
+            collection
+                .insert(format!("{}:Hello", i).as_bytes())
+                .unwrap();
+        }
+        assert_eq!(busy.pending_rows(), 0);
+        drop(busy);
+
+        assert_eq!(collection.pending_rows(), 6);
+        assert_eq!(collection.pending_bytes(), 6 * "0:Hello".len() as u64);
+        let (parent_len, pending) = collection.read_pending(|| parent_shard.read().unwrap().len());
+        assert_eq!(parent_len, 0);
+        assert_eq!(pending.len(), 6);
+        assert!(pending.contains(&b"5:Hello".to_vec()));
+        assert!(collection.last_reconcile().is_none());
+        collection.reconcile_pending();
+        assert_eq!(collection.pending_rows(), 0);
+        assert_eq!(collection.pending_bytes(), 0);
+        assert!(collection.last_reconcile().is_some());
+        assert_eq!(parent_shard.read().unwrap().len(), 6);
+    }
+
+    #[tokio::test]
+    pub async fn test_temp_collection_thread_shards() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_path = temp_dir.path().to_path_buf();
+
+        let parent_shard = Arc::new(RwLock::new(MapShard::<DataShard, DataShardConfig>::new(
+            data_path.clone(),
+            "localdata_",
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
+        )));
+
+        let collection = TempCollection::new(
+            parent_shard.clone(),
+            4,
+            data_path.clone(),
+            "tempdata_",
+            TempDataShardConfig {
+                max_offsets: TempOffsetTypes::Custom(Some(1000)),
+                encryption: None,
+            },
+        );
+
+        // A thread keeps inserting into its own temporary shard
+        let slot = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    for i in 0..50 {
+                        collection
+                            .insert(format!("{}:Hello", i).as_bytes())
+                            .unwrap();
+                    }
+                    collection.thread_shard()
+                })
+                .join()
+                .unwrap()
+        });
+        for (i, temp) in collection.temps.iter().enumerate() {
+            let expected = if i == slot { 50 } else { 0 };
+            assert_eq!(temp.read().unwrap().pending_rows(), expected);
+        }
+        collection.reconcile_all();
+
+        // Concurrent threads don't lose rows
+        std::thread::scope(|scope| {
+            for t in 0..8 {
+                let collection = &collection;
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        collection
+                            .insert(format!("{}-{}:Hello", t, i).as_bytes())
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(collection.pending_rows(), 400);
+
+        collection.reconcile_all();
+        assert_eq!(collection.pending_rows(), 0);
+        assert_eq!(parent_shard.read().unwrap().len(), 450);
+    }
 }
@@ -1,12 +1,20 @@
 use crate::errors::ShardErrors;
+use crate::metrics::Metrics;
+use crate::shard::flush_policy::FlushPolicy;
 use crate::shard::map_shard::MapShard;
-use crate::shard::{Shard, ShardConfig, TempShardConfig};
+use crate::shard::{AvailableSpace, Shard, ShardConfig, TempShardConfig};
+use crate::wal::{WriteAheadLog, WAL_PREFIX};
 use std::fmt::{Debug, Formatter};
 use std::ops::Range;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::time::{Instant, SystemTime};
 use uuid::Uuid;
 
+/// Rows moved to the main shard at once by a reconciliation. The main shard is only locked for writing while a batch
+/// is written and goes through the reconciliation callback, so searches and other writers get through in between.
+pub const RECONCILE_BATCH_SIZE: usize = 1024;
+
 pub struct DataWithIndex {
     pub data: Vec<u8>,
     pub index: u64,
@@ -30,6 +38,13 @@ pub struct TempMapShard<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardCo
     pub temp_shards: Vec<S>,
     temp_opts: TempOpts,
     on_reconcile: OnReconcileCb,
+    reconcile_batch_size: usize,
+    flush_policy: Option<FlushPolicy>,
+    flush_rows: u64,
+    pending_bytes: u64,
+    pending_since: Option<Instant>,
+    last_reconcile: Option<SystemTime>,
+    pub wal: WriteAheadLog,
 }
 
 impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
@@ -41,6 +56,16 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
         parent_shard: Arc<RwLock<MapShard<S, Opts>>>,
         temp_opts: TempOpts,
     ) -> Self {
+        let wal = WriteAheadLog::new(
+            folder.join(format!(
+                "{}{}{}",
+                WAL_PREFIX,
+                prefix,
+                Uuid::new_v4().to_string()
+            )),
+            temp_opts.to_config().encryption().cloned(),
+        );
+
         TempMapShard {
             parent_shard,
             folder,
@@ -48,6 +73,13 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
             temp_shards: vec![],
             temp_opts,
             on_reconcile: OnReconcileCb { func: None },
+            reconcile_batch_size: RECONCILE_BATCH_SIZE,
+            flush_policy: None,
+            flush_rows: 0,
+            pending_bytes: 0,
+            pending_since: None,
+            last_reconcile: None,
+            wal,
         }
     }
 
@@ -58,6 +90,55 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
         self.on_reconcile = OnReconcileCb { func: Some(data) };
     }
 
+    /// Amount of rows moved to the main shard at once, `RECONCILE_BATCH_SIZE` unless set.
+    pub fn set_reconcile_batch_size(&mut self, size: usize) {
+        self.reconcile_batch_size = size.max(1);
+    }
+
+    /// Flushes the temporary shards (reconciles them into the main shard) following `policy` on top of when they
+    /// are full. With `None`, they are only flushed once full.
+    pub fn set_flush_policy(&mut self, policy: Option<FlushPolicy>) {
+        self.flush_rows = policy.map_or(0, |policy| policy.initial_rows());
+        self.flush_policy = policy;
+    }
+
+    /// Whether a limit of the flush policy is reached, see `FlushPolicy`.
+    pub fn is_flush_due(&self) -> bool {
+        let (policy, since) = match (&self.flush_policy, self.pending_since) {
+            (Some(policy), Some(since)) => (policy, since),
+            _ => return false,
+        };
+
+        self.pending_rows() >= self.flush_rows
+            || policy
+                .max_bytes
+                .is_some_and(|max_bytes| self.pending_bytes >= max_bytes)
+            || policy
+                .max_age
+                .is_some_and(|max_age| since.elapsed() >= max_age)
+    }
+
+    /// Keeps track of `rows` just inserted, flushing the temporary shards if the flush policy says so.
+    fn inserted(&mut self, rows: &[&[u8]]) {
+        self.pending_since.get_or_insert_with(Instant::now);
+        self.pending_bytes += rows.iter().map(|row| row.len() as u64).sum::<u64>();
+
+        if self.is_flush_due() {
+            self.reconcile_all();
+        }
+    }
+
+    /// Resets the tracking of the flush policy once the temporary shards are empty, adapting the rows they hold
+    /// to the rate the `rows` that were just flushed were inserted at.
+    fn flushed(&mut self, rows: u64) {
+        if let (Some(policy), Some(since)) = (&self.flush_policy, self.pending_since) {
+            self.flush_rows = policy.next_rows(self.flush_rows, rows, since.elapsed());
+        }
+
+        self.pending_since = None;
+        self.pending_bytes = 0;
+    }
+
     fn create_shard(&self) -> S {
         let shard_path = self.folder.join(format!(
             "{}{}",
@@ -67,10 +148,12 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
         S::new(shard_path, self.temp_opts.to_config(), None)
     }
 
-    pub fn insert_row(&mut self, data: &[u8]) -> Result<u64, ShardErrors> {
+    /// Returns the position of a temporary shard with space left.
+    /// If every shard is full, the last one is reconciled and a new one is created.
+    fn usable_shard_index(&mut self) -> usize {
         let find_usable_shard = { self.temp_shards.iter().position(|i| i.has_space()) };
 
-        let shard_index = match find_usable_shard {
+        match find_usable_shard {
             None => {
                 self.reconcile_specific(None);
                 let shard = self.create_shard();
@@ -78,14 +161,52 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
                 self.temp_shards.len() - 1
             }
             Some(shard) => shard,
-        };
+        }
+    }
 
-        {
-            self.temp_shards
+    /// Inserts `data` in a temporary shard. The row is logged in the write-ahead log first.
+    #[tracing::instrument(level = "debug", skip_all, fields(bytes = data.len()))]
+    pub fn insert_row(&mut self, data: &[u8]) -> Result<u64, ShardErrors> {
+        let shard_index = self.usable_shard_index();
+        self.wal.append_rows(&[data])?;
+
+        let position = self
+            .temp_shards
+            .get(shard_index)
+            .ok_or(ShardErrors::UnknownShard)?
+            .insert_item(&[data])?;
+        Metrics::global().temp_shard_rows.add(1);
+        self.inserted(&[data]);
+
+        Ok(position)
+    }
+
+    /// Inserts every item of `data`, writing as many items as fit in a temporary shard at once
+    /// instead of going through `insert_row` one item at a time.
+    #[tracing::instrument(level = "debug", skip_all, fields(rows = data.len()))]
+    pub fn insert_rows(&mut self, data: &[&[u8]]) -> Result<(), ShardErrors> {
+        let mut remaining = data;
+
+        while !remaining.is_empty() {
+            let shard_index = self.usable_shard_index();
+            let shard = self
+                .temp_shards
                 .get(shard_index)
-                .ok_or(ShardErrors::UnknownShard)?
-                .insert_item(&[data])
+                .ok_or(ShardErrors::UnknownShard)?;
+
+            let up_to = match shard.available_space() {
+                AvailableSpace::Fixed(size) => std::cmp::min(size, remaining.len()),
+                AvailableSpace::Unlimited => remaining.len(),
+            };
+
+            self.wal.append_rows(&remaining[..up_to])?;
+            shard.insert_item(&remaining[..up_to])?;
+            Metrics::global().temp_shard_rows.add(up_to as i64);
+            self.inserted(&remaining[..up_to]);
+            remaining = &remaining[up_to..];
         }
+
+        Ok(())
     }
 
     fn get_reconciliation_data(shard: &S) -> (&S, Range<i64>) {
@@ -109,10 +230,11 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
         }
     }
 
-    fn reconcile(&self, from: &S, target: &mut MapShard<S, Opts>) {
+    /// Writes the items of `from` to `target`, returning them along with their position in `target`.
+    fn move_items(from: &S, target: &mut MapShard<S, Opts>) -> Vec<DataWithIndex> {
         let (shard, indexes) = Self::get_reconciliation_data(from);
         let mut reconciling_items = vec![];
-        // TODO: What if the row is inserted `target.insert_rows` but, the reconciling (call_on_reconcile) fails?
+
         for item_index in indexes {
             let binary_item = shard.read_item_from_index(item_index as usize).unwrap();
             let pos = target.insert_rows(&[&binary_item]);
@@ -121,27 +243,119 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
                 index: pos as u64,
             });
         }
-        self.call_on_reconcile(reconciling_items).unwrap();
+
+        reconciling_items
     }
 
-    pub fn reconcile_all(&mut self) {
-        let mut parent_writer = self.parent_shard.write().unwrap();
+    /// Moves the items of `from` to the main shard in batches of `reconcile_batch_size` rows.
+    ///
+    /// Items are read before the main shard is locked. Each batch is logged, written in a single write and goes through
+    /// the reconciliation callback (e.g. indexed) under the lock, so its rows become visible at once and a crash
+    /// between batches leaves the log telling which rows made it (see `WriteAheadLog::recover`).
+    fn reconcile(&self, from: &S) {
+        let started = Instant::now();
+        let count = (from.get_last_index() + 1) as usize;
+        let _span = tracing::debug_span!("reconcile", rows = count).entered();
+        let metrics = Metrics::global();
+
+        let mut start = 0;
+        while start < count {
+            let end = std::cmp::min(start + self.reconcile_batch_size, count);
+            let batch: Vec<Vec<u8>> = (start..end)
+                .map(|item_index| from.read_item_from_index(item_index).unwrap())
+                .collect();
+
+            {
+                let mut target = self.parent_shard.write().unwrap();
+                let first = target.len() as u64;
+                self.wal
+                    .append_reconcile(first, batch.len() as u64)
+                    .unwrap();
+
+                let refs: Vec<&[u8]> = batch.iter().map(|item| item.as_slice()).collect();
+                target.insert_rows(&refs);
+
+                // TODO: What if the row is inserted `target.insert_rows` but, the reconciling (call_on_reconcile) fails?
+                let reconciling_items = batch
+                    .into_iter()
+                    .enumerate()
+                    .map(|(offset, data)| DataWithIndex {
+                        data,
+                        index: first + offset as u64,
+                    })
+                    .collect();
+                self.call_on_reconcile(reconciling_items).unwrap();
+            }
 
+            metrics.temp_shard_rows.add(-((end - start) as i64));
+            start = end;
+        }
+
+        let elapsed = started.elapsed();
+        metrics.reconcile_duration.observe(elapsed);
+        tracing::debug!(rows = count, elapsed = ?elapsed, "Reconciled temporary shard");
+    }
+
+    /// Removes the file of a temporary shard whose items are in the main shard.
+    fn remove_shard_file(shard: &S) {
+        let _ = std::fs::remove_file(shard.get_path());
+    }
+
+    /// Amount of rows waiting in the temporary shards to be reconciled.
+    pub fn pending_rows(&self) -> u64 {
+        self.temp_shards
+            .iter()
+            .map(|shard| (shard.get_last_index() + 1) as u64)
+            .sum()
+    }
+
+    /// Rows waiting in the temporary shards to be reconciled, in the order they are reconciled in.
+    pub fn pending_items(&self) -> Vec<Vec<u8>> {
+        self.temp_shards
+            .iter()
+            .flat_map(|shard| {
+                let (shard, indexes) = Self::get_reconciliation_data(shard);
+                indexes.map(|item_index| shard.read_item_from_index(item_index as usize).unwrap())
+            })
+            .collect()
+    }
+
+    /// Bytes of the rows inserted since the temporary shards were last emptied.
+    pub fn pending_bytes(&self) -> u64 {
+        self.pending_bytes
+    }
+
+    /// When rows were last moved to the main shard, `None` if they never were.
+    pub fn last_reconcile(&self) -> Option<SystemTime> {
+        self.last_reconcile
+    }
+
+    /// Moves every row of the temporary shards to the main shard, in batches (see `reconcile`).
+    pub fn reconcile_all(&mut self) {
+        let rows = self.pending_rows();
         for from_shard in self.temp_shards.iter() {
-            self.reconcile(from_shard, &mut parent_writer);
+            self.reconcile(from_shard);
+        }
+        if rows > 0 {
+            self.last_reconcile = Some(SystemTime::now());
         }
 
-        self.temp_shards.clear()
+        for shard in self.temp_shards.drain(..) {
+            Self::remove_shard_file(&shard);
+        }
+        self.wal.truncate().unwrap();
+        self.flushed(rows);
     }
 
     pub fn reconcile_specific(&mut self, shard_position: Option<usize>) {
+        let rows = self.pending_rows();
         let pos = {
             let index = shard_position.or_else(|| self.temp_shards.len().checked_sub(1));
 
             if let Some(index) = index {
                 if let Some(shard) = self.temp_shards.get(index) {
-                    let mut parent_shard = self.parent_shard.write().unwrap();
-                    self.reconcile(shard, &mut parent_shard);
+                    self.reconcile(shard);
+                    self.last_reconcile = Some(SystemTime::now());
                     index
                 } else {
                     return;
@@ -151,12 +365,66 @@ impl<S: Shard<Opts>, Opts: ShardConfig, TempOpts: TempShardConfig<Opts>>
             }
         };
 
-        self.temp_shards.remove(pos);
+        let shard = self.temp_shards.remove(pos);
+        Self::remove_shard_file(&shard);
+
+        // Every logged row is in the main shard
+        if self.temp_shards.is_empty() {
+            self.wal.truncate().unwrap();
+            self.flushed(rows);
+        }
+    }
+
+    /// Restores the rows of `wal`, the write-ahead log of a previous run: rows that never made it to the main shard
+    /// are written to it, and every restored row goes through the reconciliation callback (e.g. to index it again).
+    /// The move is logged in `wal` first, so recovering the same log twice doesn't write rows twice.
+    pub fn replay(&self, wal: &WriteAheadLog) -> Result<(), ShardErrors> {
+        let mut target = self.parent_shard.write().unwrap();
+        let recovery = wal.recover(target.len() as u64);
+
+        wal.append_reconcile(target.len() as u64, recovery.missing.len() as u64)?;
+
+        let mut items: Vec<DataWithIndex> = recovery
+            .written
+            .into_iter()
+            .map(|(index, data)| DataWithIndex { data, index })
+            .collect();
+
+        for data in recovery.missing {
+            let pos = target.insert_rows(&[&data]);
+            items.push(DataWithIndex {
+                data,
+                index: pos as u64,
+            });
+        }
+
+        self.call_on_reconcile(items)
+            .map_err(|_| ShardErrors::ErrorAddingEntry)
+    }
+
+    /// Restores the items of the temporary shard at `path`, left behind by a previous run that had no write-ahead log:
+    /// they are written to the main shard and go through the reconciliation callback. The file is removed afterwards.
+    pub fn replay_shard(&self, path: PathBuf) -> Result<(), ShardErrors> {
+        let shard = S::new(path, self.temp_opts.to_config(), None);
+
+        {
+            let mut target = self.parent_shard.write().unwrap();
+            let items = Self::move_items(&shard, &mut target);
+            self.call_on_reconcile(items)
+                .map_err(|_| ShardErrors::ErrorAddingEntry)?;
+        }
+
+        Self::remove_shard_file(&shard);
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::compression::Compression;
+    use crate::shard::flush_policy::{
+        FlushPolicy, DEFAULT_FLUSH_ROWS, MAX_FLUSH_ROWS, MIN_FLUSH_ROWS,
+    };
     use crate::shard::map_shard::MapShard;
     use crate::shard::shards::data_shard::config::{DataShardConfig, TempDataShardConfig};
     use crate::shard::shards::data_shard::shard::DataShard;
@@ -164,7 +432,8 @@ mod test {
     use crate::shard::Shard;
     use crate::temp_offset_types::TempOffsetTypes;
     use std::path::PathBuf;
-    use std::sync::{Arc, RwLock};
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::time::Duration;
 
     #[tokio::test]
     pub async fn test_temp_shard() {
@@ -179,7 +448,12 @@ mod test {
         let ctx = MapShard::<DataShard, DataShardConfig>::new(
             data_path.clone(),
             "localdata_",
-            DataShardConfig { max_offsets: None },
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
         );
 
         let parent_shard = Arc::new(RwLock::new(ctx));
@@ -190,6 +464,7 @@ mod test {
             parent_shard.clone(),
             TempDataShardConfig {
                 max_offsets: TempOffsetTypes::Custom(Some(2)),
+                encryption: None,
             },
         );
 
@@ -267,4 +542,210 @@ mod test {
 
         std::fs::remove_dir_all(data_path).unwrap()
     }
+
+    #[tokio::test]
+    pub async fn test_temp_shard_insert_rows() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_path = temp_dir.path().to_path_buf();
+
+        let ctx = MapShard::<DataShard, DataShardConfig>::new(
+            data_path.clone(),
+            "localdata_",
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
+        );
+
+        let parent_shard = Arc::new(RwLock::new(ctx));
+
+        let mut shard = TempMapShard::<DataShard, DataShardConfig, TempDataShardConfig>::new(
+            data_path.clone(),
+            "tempdata_",
+            parent_shard.clone(),
+            TempDataShardConfig {
+                max_offsets: TempOffsetTypes::Custom(Some(2)),
+                encryption: None,
+            },
+        );
+
+        let items: Vec<Vec<u8>> = (0..5)
+            .map(|i| format!("{}:Hello", i).as_bytes().to_vec())
+            .collect();
+        let refs: Vec<&[u8]> = items.iter().map(|i| i.as_slice()).collect();
+
+        shard.insert_rows(&refs).unwrap();
+
+        // Two full shards were reconciled, the last item is still in a temporary shard
+        assert_eq!(shard.temp_shards.len(), 1);
+        assert_eq!(
+            shard.temp_shards[0].read_item_from_index(0).unwrap(),
+            items[4]
+        );
+        assert_eq!(parent_shard.read().unwrap().len(), 4);
+
+        shard.reconcile_all();
+
+        let parent = parent_shard.read().unwrap();
+        assert_eq!(parent.len(), 5);
+        for (i, item) in items.iter().enumerate() {
+            assert_eq!(&parent.get_element(i).unwrap(), item);
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_temp_shard_reconcile_batches() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_path = temp_dir.path().to_path_buf();
+
+        let ctx = MapShard::<DataShard, DataShardConfig>::new(
+            data_path.clone(),
+            "localdata_",
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
+        );
+
+        let parent_shard = Arc::new(RwLock::new(ctx));
+
+        let mut shard = TempMapShard::<DataShard, DataShardConfig, TempDataShardConfig>::new(
+            data_path.clone(),
+            "tempdata_",
+            parent_shard.clone(),
+            TempDataShardConfig {
+                max_offsets: TempOffsetTypes::Custom(Some(10)),
+                encryption: None,
+            },
+        );
+        shard.set_reconcile_batch_size(2);
+
+        let batches = Arc::new(Mutex::new(vec![]));
+        {
+            let batches = batches.clone();
+            let parent_shard = parent_shard.clone();
+            shard.set_on_reconcile(Box::new(move |rows| {
+                // The main shard is locked while a batch is reconciled, and only holds the batches done so far
+                assert!(parent_shard.try_read().is_err());
+                batches
+                    .lock()
+                    .unwrap()
+                    .push(rows.iter().map(|row| row.index).collect::<Vec<u64>>());
+                Ok(())
+            }));
+        }
+
+        let items: Vec<Vec<u8>> = (0..5)
+            .map(|i| format!("{}:Hello", i).as_bytes().to_vec())
+            .collect();
+        let refs: Vec<&[u8]> = items.iter().map(|i| i.as_slice()).collect();
+        shard.insert_rows(&refs).unwrap();
+        assert_eq!(shard.pending_rows(), 5);
+
+        shard.reconcile_all();
+
+        assert_eq!(shard.pending_rows(), 0);
+        assert_eq!(
+            *batches.lock().unwrap(),
+            vec![vec![0, 1], vec![2, 3], vec![4]]
+        );
+        assert!(shard.wal.entries().is_empty());
+
+        let parent = parent_shard.read().unwrap();
+        assert_eq!(parent.len(), 5);
+        for (i, item) in items.iter().enumerate() {
+            assert_eq!(&parent.get_element(i).unwrap(), item);
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_temp_shard_flush_policy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_path = temp_dir.path().to_path_buf();
+
+        let parent_shard = Arc::new(RwLock::new(MapShard::<DataShard, DataShardConfig>::new(
+            data_path.clone(),
+            "localdata_",
+            DataShardConfig {
+                max_offsets: None,
+                compression: Compression::None,
+                encryption: None,
+                max_size: None,
+            },
+        )));
+
+        let mut shard = TempMapShard::<DataShard, DataShardConfig, TempDataShardConfig>::new(
+            data_path.clone(),
+            "tempdata_",
+            parent_shard.clone(),
+            TempDataShardConfig {
+                max_offsets: TempOffsetTypes::Custom(Some(100)),
+                encryption: None,
+            },
+        );
+
+        // Flushed by rows, well before the shard is full
+        shard.set_flush_policy(Some(FlushPolicy {
+            max_rows: Some(3),
+            max_bytes: None,
+            max_age: None,
+        }));
+        for i in 0..2 {
+            shard.insert_row(format!("{}:Hello", i).as_bytes()).unwrap();
+        }
+        assert_eq!(shard.pending_rows(), 2);
+        assert!(!shard.is_flush_due());
+        shard.insert_row(b"2:Hello").unwrap();
+        assert_eq!(shard.pending_rows(), 0);
+        assert_eq!(parent_shard.read().unwrap().len(), 3);
+
+        // Flushed by bytes
+        shard.set_flush_policy(Some(FlushPolicy {
+            max_rows: None,
+            max_bytes: Some(10),
+            max_age: None,
+        }));
+        shard.insert_row(b"12345").unwrap();
+        assert_eq!(shard.pending_rows(), 1);
+        shard.insert_rows(&[b"67890"]).unwrap();
+        assert_eq!(shard.pending_rows(), 0);
+        assert_eq!(parent_shard.read().unwrap().len(), 5);
+
+        // Flushed by age, once checked
+        shard.set_flush_policy(Some(FlushPolicy {
+            max_rows: None,
+            max_bytes: None,
+            max_age: Some(Duration::from_millis(50)),
+        }));
+        shard.insert_row(b"old").unwrap();
+        assert!(!shard.is_flush_due());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(shard.is_flush_due());
+        shard.insert_row(b"new").unwrap();
+        assert_eq!(shard.pending_rows(), 0);
+        assert_eq!(parent_shard.read().unwrap().len(), 7);
+
+        // Rows left to the insert rate move towards the rows inserted in a second
+        let policy = FlushPolicy::default();
+        assert_eq!(policy.initial_rows(), DEFAULT_FLUSH_ROWS);
+        assert_eq!(
+            policy.next_rows(1000, 10, Duration::from_secs(1)),
+            (1000 + MIN_FLUSH_ROWS) / 2
+        );
+        assert_eq!(policy.next_rows(1000, 3000, Duration::from_secs(1)), 2000);
+        assert_eq!(
+            policy.next_rows(MAX_FLUSH_ROWS, 1_000_000, Duration::from_millis(1)),
+            MAX_FLUSH_ROWS
+        );
+        assert_eq!(
+            policy
+                .set_max_rows(Some(7))
+                .next_rows(1000, 10, Duration::ZERO),
+            7
+        );
+    }
 }
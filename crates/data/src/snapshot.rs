@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Point in time view of a `MapShard`.
+///
+/// # Fields:
+/// - `len`: Amount of positions visible in the snapshot. Positions written later are not part of it.
+/// - `epoch`: Amount of published writes. Tombstones placed on a later epoch are not part of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Snapshot {
+    pub len: u64,
+    pub epoch: u64,
+}
+
+impl Snapshot {
+    pub fn contains(&self, position: u64) -> bool {
+        position < self.len
+    }
+}
+
+/// Hands out snapshots and publishes writes.
+///
+/// Writers append their data, stamp their tombstones with `pending_epoch` and call `publish` once everything
+/// (e.g. indexes) is in place. Readers take a `snapshot` and ignore anything written after it, so they never
+/// observe a write half way through, without holding any lock while they read.
+///
+/// Writers are expected to be serialized (e.g. by the write lock of the `MapShard`).
+/// Several writes can be made visible at once by publishing them while the clock is held (see `hold`).
+///
+/// # Fields:
+/// - `current`: Snapshot of the published writes.
+/// - `held`: Snapshot of the writes published while the clock is held, made visible once it's released.
+/// - `active`: Lengths of the snapshots handed out by `acquire` and not dropped yet, with how many share each.
+#[derive(Debug)]
+pub struct SnapshotClock {
+    current: RwLock<Snapshot>,
+    held: Mutex<Option<Snapshot>>,
+    active: Mutex<BTreeMap<u64, usize>>,
+}
+
+/// Hold of a `SnapshotClock`, see `SnapshotClock::hold`. Releasing it (dropping it) publishes the writes
+/// published while it was held.
+#[derive(Debug)]
+pub struct ClockHold {
+    clock: Arc<SnapshotClock>,
+}
+
+impl Drop for ClockHold {
+    fn drop(&mut self) {
+        let mut held = self.clock.held.lock().unwrap();
+        if let Some(held) = held.take() {
+            let mut current = self.clock.current.write().unwrap();
+            current.len = current.len.max(held.len);
+            current.epoch = held.epoch;
+        }
+    }
+}
+
+/// Snapshot handed out by `SnapshotClock::acquire`, counted as in use until dropped.
+#[derive(Debug)]
+pub struct SnapshotGuard {
+    clock: Arc<SnapshotClock>,
+    snapshot: Snapshot,
+}
+
+impl Deref for SnapshotGuard {
+    type Target = Snapshot;
+
+    fn deref(&self) -> &Self::Target {
+        &self.snapshot
+    }
+}
+
+impl Drop for SnapshotGuard {
+    fn drop(&mut self) {
+        let mut active = self.clock.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.snapshot.len) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.snapshot.len);
+            }
+        }
+    }
+}
+
+impl SnapshotClock {
+    pub fn new(len: u64) -> Self {
+        Self {
+            current: RwLock::new(Snapshot { len, epoch: 0 }),
+            held: Mutex::new(None),
+            active: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        *self.current.read().unwrap()
+    }
+
+    /// Takes a snapshot like `snapshot`, counting it as in use until the guard is dropped (see `horizon`).
+    pub fn acquire(self: &Arc<Self>) -> SnapshotGuard {
+        let mut active = self.active.lock().unwrap();
+        let snapshot = self.snapshot();
+        *active.entry(snapshot.len).or_default() += 1;
+
+        SnapshotGuard {
+            clock: self.clone(),
+            snapshot,
+        }
+    }
+
+    /// Length of the oldest snapshot in use, the current one when none is. Every snapshot in use, and every
+    /// snapshot acquired from now on, contains the positions below it.
+    pub fn horizon(&self) -> u64 {
+        let active = self.active.lock().unwrap();
+        let current = self.snapshot().len;
+        active
+            .keys()
+            .next()
+            .map_or(current, |oldest| (*oldest).min(current))
+    }
+
+    /// Epoch the next call to `publish` will make visible.
+    pub fn pending_epoch(&self) -> u64 {
+        self.snapshot().epoch + 1
+    }
+
+    /// Makes every position below `len` and every tombstone of the pending epoch visible, once the clock is
+    /// released when it's held.
+    pub fn publish(&self, len: u64) -> Snapshot {
+        let mut held = self.held.lock().unwrap();
+        if let Some(held) = held.as_mut() {
+            held.len = held.len.max(len);
+            return *held;
+        }
+
+        let mut current = self.current.write().unwrap();
+        current.len = current.len.max(len);
+        current.epoch += 1;
+        *current
+    }
+
+    /// Holds back the writes published from now on until the returned hold is dropped, when they all become
+    /// visible at once: snapshots taken meanwhile don't see any of them. Writes stamp their tombstones with the
+    /// same pending epoch while the clock is held.
+    ///
+    /// The clock must not be held already.
+    pub fn hold(self: &Arc<Self>) -> ClockHold {
+        let mut held = self.held.lock().unwrap();
+        assert!(held.is_none(), "The snapshot clock is already held");
+        let current = self.snapshot();
+        *held = Some(Snapshot {
+            len: current.len,
+            epoch: current.epoch + 1,
+        });
+
+        ClockHold {
+            clock: self.clone(),
+        }
+    }
+
+    /// Snapshot of every write published so far, including the ones held back (see `hold`).
+    pub fn latest(&self) -> Snapshot {
+        let held = self.held.lock().unwrap();
+        held.unwrap_or_else(|| self.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::snapshot::{Snapshot, SnapshotClock};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    pub async fn test_snapshot_clock() {
+        let clock = SnapshotClock::new(2);
+        let before = clock.snapshot();
+        assert!(before.contains(1));
+        assert!(!before.contains(2));
+
+        assert_eq!(clock.pending_epoch(), 1);
+        let after = clock.publish(5);
+        assert_eq!(after.len, 5);
+        assert_eq!(after.epoch, 1);
+
+        // Publishing never shrinks the visible positions
+        let after = clock.publish(3);
+        assert_eq!(after.len, 5);
+        assert_eq!(after.epoch, 2);
+
+        // Snapshots taken before are not affected
+        assert_eq!(before.len, 2);
+        assert_eq!(before.epoch, 0);
+    }
+
+    #[tokio::test]
+    pub async fn test_snapshot_clock_horizon() {
+        let clock = Arc::new(SnapshotClock::new(2));
+        assert_eq!(clock.horizon(), 2);
+
+        let oldest = clock.acquire();
+        clock.publish(4);
+        let newest = clock.acquire();
+        clock.publish(6);
+        assert_eq!(clock.horizon(), 2);
+
+        // The horizon moves forward as the snapshots in use are dropped
+        drop(oldest);
+        assert_eq!(clock.horizon(), 4);
+        drop(newest);
+        assert_eq!(clock.horizon(), 6);
+    }
+
+    #[tokio::test]
+    pub async fn test_snapshot_clock_hold() {
+        let clock = Arc::new(SnapshotClock::new(2));
+        let before = clock.snapshot();
+
+        let hold = clock.hold();
+        assert_eq!(clock.pending_epoch(), 1);
+        clock.publish(4);
+        clock.publish(3);
+        assert_eq!(clock.pending_epoch(), 1);
+
+        // Held writes are only seen by `latest`
+        assert_eq!(clock.snapshot(), before);
+        assert_eq!(clock.latest(), Snapshot { len: 4, epoch: 1 });
+
+        drop(hold);
+        assert_eq!(clock.snapshot(), Snapshot { len: 4, epoch: 1 });
+        assert_eq!(clock.publish(5), Snapshot { len: 5, epoch: 2 });
+    }
+}
@@ -0,0 +1,144 @@
+use crate::errors::ShardErrors;
+use crate::U64_SIZE;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+/// Set of row positions of a `MapShard` that are no longer alive (e.g. because a newer version
+/// of the row was written somewhere else or because the row was deleted).
+///
+/// Positions are persisted in an append-only file of little endian `u64`s and kept in memory
+/// for fast lookups, along with the epoch (see `SnapshotClock`) they were marked on.
+/// Epochs are not persisted: positions loaded from disk are dead for every snapshot.
+#[derive(Debug)]
+pub struct Tombstones {
+    pub path: PathBuf,
+    file: Mutex<File>,
+    positions: RwLock<HashMap<u64, u64>>,
+}
+
+impl Tombstones {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .unwrap();
+
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer).unwrap();
+
+        // A trailing incomplete position (e.g. an interrupted write) is ignored
+        let positions = buffer
+            .chunks_exact(U64_SIZE)
+            .map(|chunk| (u64::from_le_bytes(chunk.try_into().unwrap()), 0))
+            .collect();
+
+        Self {
+            path,
+            file: Mutex::new(file),
+            positions: RwLock::new(positions),
+        }
+    }
+
+    /// Marks `position` as dead from `epoch` onwards. Marking an already dead position is a no-op.
+    pub fn mark(&self, position: u64, epoch: u64) -> Result<(), ShardErrors> {
+        let mut positions = self.positions.write().unwrap();
+        if positions.contains_key(&position) {
+            return Ok(());
+        }
+
+        let mut file = self.file.lock().map_err(|_| ShardErrors::InvalidLocking)?;
+        file.write_all(&position.to_le_bytes())
+            .and_then(|_| file.flush())
+            .map_err(|_| ShardErrors::FlushingError)?;
+
+        positions.insert(position, epoch);
+
+        Ok(())
+    }
+
+    /// Waits until the marked positions are on disk.
+    pub fn sync(&self) -> Result<(), ShardErrors> {
+        let file = self.file.lock().map_err(|_| ShardErrors::InvalidLocking)?;
+        file.sync_data().map_err(|_| ShardErrors::FlushingError)
+    }
+
+    pub fn contains(&self, position: u64) -> bool {
+        self.positions.read().unwrap().contains_key(&position)
+    }
+
+    /// Whether `position` is dead for a snapshot taken at `epoch`.
+    pub fn is_dead_at(&self, position: u64, epoch: u64) -> bool {
+        match self.positions.read().unwrap().get(&position) {
+            Some(marked_at) => *marked_at <= epoch,
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.read().unwrap().len()
+    }
+
+    /// Positions marked after the first `count` ones, in the order they were marked (e.g. `count` being `len`
+    /// at some point, the positions marked since then).
+    pub fn marked_since(&self, count: usize) -> Result<Vec<u64>, ShardErrors> {
+        let _file = self.file.lock().map_err(|_| ShardErrors::InvalidLocking)?;
+        let buffer = std::fs::read(&self.path).map_err(|_| ShardErrors::ErrorReadingByteRange)?;
+
+        Ok(buffer
+            .get(count * U64_SIZE..)
+            .unwrap_or_default()
+            .chunks_exact(U64_SIZE)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tombstones::Tombstones;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    pub async fn test_tombstones() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("tombstones");
+
+        {
+            let tombstones = Tombstones::new(&path);
+            assert!(tombstones.is_empty());
+
+            tombstones.mark(3, 1).unwrap();
+            tombstones.mark(10, 2).unwrap();
+            tombstones.mark(3, 4).unwrap();
+
+            assert!(tombstones.contains(3));
+            assert!(!tombstones.contains(4));
+            assert_eq!(tombstones.len(), 2);
+
+            assert!(!tombstones.is_dead_at(10, 1));
+            assert!(tombstones.is_dead_at(10, 2));
+            assert!(tombstones.is_dead_at(3, 1));
+        }
+
+        // Positions survive reopening the file
+        let tombstones = Tombstones::new(&path);
+        assert_eq!(tombstones.len(), 2);
+        assert!(tombstones.contains(10));
+        assert!(tombstones.is_dead_at(10, 0));
+
+        tombstones.mark(7, 0).unwrap();
+        assert_eq!(tombstones.marked_since(1).unwrap(), vec![10, 7]);
+        assert!(tombstones.marked_since(3).unwrap().is_empty());
+    }
+}
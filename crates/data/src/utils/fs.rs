@@ -19,3 +19,24 @@ pub fn list_files_with_prefix<P: AsRef<Path> + Clone>(
         .collect::<Vec<_>>()
         .into())
 }
+
+/// Total size in bytes of the files under `directory`, walking its subfolders. Missing folders are empty.
+pub fn folder_size<P: AsRef<Path>>(directory: P) -> io::Result<u64> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut size = 0;
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += match metadata.is_dir() {
+            true => folder_size(entry.path())?,
+            false => metadata.len(),
+        };
+    }
+
+    Ok(size)
+}
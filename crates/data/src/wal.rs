@@ -0,0 +1,282 @@
+use crate::encryption::EncryptionKey;
+use crate::errors::ShardErrors;
+use crate::U64_SIZE;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Prefix of the write-ahead log files, placed before the prefix of the shards they log.
+pub const WAL_PREFIX: &str = "wal_";
+
+const ROW_ENTRY: u8 = 0;
+const RECONCILE_ENTRY: u8 = 1;
+
+/// Kind, length and checksum of the payload.
+const ENTRY_HEADER_SIZE: usize = 1 + U64_SIZE + 4;
+
+/// Entry of a `WriteAheadLog`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalEntry {
+    /// Row about to be written to a temporary shard.
+    Row(Vec<u8>),
+    /// The `count` oldest rows of the log that are not in the main shard yet are about to be written to it,
+    /// from `position` onwards.
+    Reconcile { position: u64, count: u64 },
+}
+
+/// Rows of a `WriteAheadLog` to restore after a crash.
+///
+/// # Fields:
+/// - `written`: Rows that made it to the main shard, along with their position. Their indexes may be missing.
+/// - `missing`: Rows that never made it to the main shard, in the order they were logged.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WalRecovery {
+    pub written: Vec<(u64, Vec<u8>)>,
+    pub missing: Vec<Vec<u8>>,
+}
+
+/// Append-only log of the rows written to temporary shards, so they survive a crash before being reconciled.
+///
+/// Rows are logged (and synced to disk) before they are written, and the moves to the main shard are logged
+/// before they happen, so `recover` can tell which rows made it to the main shard.
+/// Once every logged row is in the main shard the log is truncated.
+///
+/// Entries are stored as `[kind: u8][length: u64][crc32: u32][payload]`, little endian.
+/// A trailing incomplete or corrupted entry (e.g. an interrupted write) ends the log.
+/// Rows are encrypted when the log has a key, like the shards they are written to.
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    pub path: PathBuf,
+    file: Mutex<File>,
+    encryption: Option<EncryptionKey>,
+}
+
+impl WriteAheadLog {
+    pub fn new<P: AsRef<Path>>(path: P, encryption: Option<EncryptionKey>) -> Self {
+        let path = path.as_ref().to_path_buf();
+
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .unwrap();
+
+        Self {
+            path,
+            file: Mutex::new(file),
+            encryption,
+        }
+    }
+
+    pub fn encryption(&self) -> Option<&EncryptionKey> {
+        self.encryption.as_ref()
+    }
+
+    fn encode(kind: u8, payload: &[u8], buffer: &mut Vec<u8>) {
+        buffer.push(kind);
+        buffer.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        buffer.extend_from_slice(payload);
+    }
+
+    fn write(&self, buffer: &[u8]) -> Result<(), ShardErrors> {
+        let mut file = self.file.lock().map_err(|_| ShardErrors::InvalidLocking)?;
+        file.write_all(buffer)
+            .and_then(|_| file.sync_data())
+            .map_err(|_| ShardErrors::FlushingError)
+    }
+
+    /// Logs `rows`, returning once they are on disk.
+    pub fn append_rows(&self, rows: &[&[u8]]) -> Result<(), ShardErrors> {
+        let mut buffer = vec![];
+        for row in rows {
+            match &self.encryption {
+                Some(key) => Self::encode(ROW_ENTRY, &key.encrypt(row), &mut buffer),
+                None => Self::encode(ROW_ENTRY, row, &mut buffer),
+            }
+        }
+
+        self.write(&buffer)
+    }
+
+    /// Logs that the `count` oldest rows not in the main shard are about to be written to it from `position` onwards.
+    pub fn append_reconcile(&self, position: u64, count: u64) -> Result<(), ShardErrors> {
+        let mut payload = position.to_le_bytes().to_vec();
+        payload.extend_from_slice(&count.to_le_bytes());
+
+        let mut buffer = vec![];
+        Self::encode(RECONCILE_ENTRY, &payload, &mut buffer);
+
+        self.write(&buffer)
+    }
+
+    /// Drops every entry, once the logged rows are all in the main shard.
+    pub fn truncate(&self) -> Result<(), ShardErrors> {
+        let file = self.file.lock().map_err(|_| ShardErrors::InvalidLocking)?;
+        file.set_len(0)
+            .and_then(|_| file.sync_data())
+            .map_err(|_| ShardErrors::FlushingError)
+    }
+
+    /// Reads the entries of the log, up to the first incomplete or corrupted one.
+    pub fn entries(&self) -> Vec<WalEntry> {
+        let mut buffer = vec![];
+        File::open(&self.path)
+            .and_then(|mut file| file.read_to_end(&mut buffer))
+            .unwrap_or(0);
+
+        let mut entries = vec![];
+        let mut rest = buffer.as_slice();
+
+        while rest.len() >= ENTRY_HEADER_SIZE {
+            let kind = rest[0];
+            let len = u64::from_le_bytes(rest[1..1 + U64_SIZE].try_into().unwrap()) as usize;
+            let crc = u32::from_le_bytes(rest[1 + U64_SIZE..ENTRY_HEADER_SIZE].try_into().unwrap());
+
+            let payload = match rest[ENTRY_HEADER_SIZE..].get(..len) {
+                Some(payload) if crc32fast::hash(payload) == crc => payload,
+                _ => break,
+            };
+
+            let entry = match (kind, payload.len(), &self.encryption) {
+                (ROW_ENTRY, _, None) => WalEntry::Row(payload.to_vec()),
+                (ROW_ENTRY, _, Some(key)) => match key.decrypt(payload) {
+                    Ok(row) => WalEntry::Row(row),
+                    Err(_) => break,
+                },
+                (RECONCILE_ENTRY, 16, _) => WalEntry::Reconcile {
+                    position: u64::from_le_bytes(payload[..U64_SIZE].try_into().unwrap()),
+                    count: u64::from_le_bytes(payload[U64_SIZE..].try_into().unwrap()),
+                },
+                _ => break,
+            };
+
+            entries.push(entry);
+            rest = &rest[ENTRY_HEADER_SIZE + len..];
+        }
+
+        entries
+    }
+
+    /// Splits the rows of the log between the ones the main shard holds, now that it has `len` items,
+    /// and the ones it lost.
+    pub fn recover(&self, len: u64) -> WalRecovery {
+        let mut recovery = WalRecovery::default();
+        let mut pending = VecDeque::new();
+
+        for entry in self.entries() {
+            match entry {
+                WalEntry::Row(row) => pending.push_back(row),
+                WalEntry::Reconcile { position, count } => {
+                    let mut unplaced = vec![];
+
+                    for offset in 0..count {
+                        let row = match pending.pop_front() {
+                            Some(row) => row,
+                            None => break,
+                        };
+
+                        if position + offset < len {
+                            recovery.written.push((position + offset, row));
+                        } else {
+                            unplaced.push(row);
+                        }
+                    }
+
+                    // Rows of an interrupted move are still the oldest ones waiting for the main shard
+                    for row in unplaced.into_iter().rev() {
+                        pending.push_front(row);
+                    }
+                }
+            }
+        }
+
+        recovery.missing = pending.into_iter().collect();
+        recovery
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encryption::EncryptionKey;
+    use crate::wal::{WalEntry, WalRecovery, WriteAheadLog};
+    use std::io::Write;
+
+    #[tokio::test]
+    pub async fn test_write_ahead_log() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("wal_test");
+
+        let wal = WriteAheadLog::new(&path, None);
+        wal.append_rows(&[b"a", b"b"]).unwrap();
+        wal.append_reconcile(10, 2).unwrap();
+        wal.append_rows(&[b"c"]).unwrap();
+
+        // Reopening the log keeps its entries
+        let wal = WriteAheadLog::new(&path, None);
+        assert_eq!(
+            wal.entries(),
+            vec![
+                WalEntry::Row(b"a".to_vec()),
+                WalEntry::Row(b"b".to_vec()),
+                WalEntry::Reconcile {
+                    position: 10,
+                    count: 2
+                },
+                WalEntry::Row(b"c".to_vec()),
+            ]
+        );
+
+        // The move to the main shard was interrupted after the first row
+        assert_eq!(
+            wal.recover(11),
+            WalRecovery {
+                written: vec![(10, b"a".to_vec())],
+                missing: vec![b"b".to_vec(), b"c".to_vec()],
+            }
+        );
+
+        // Replaying the missing rows is logged like any other move
+        wal.append_reconcile(11, 2).unwrap();
+        assert_eq!(wal.recover(13).missing.len(), 0);
+        assert_eq!(wal.recover(13).written.len(), 3);
+
+        // An interrupted write ends the log
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[0, 5, 0, 0])
+            .unwrap();
+        assert_eq!(wal.entries().len(), 5);
+
+        wal.truncate().unwrap();
+        assert!(wal.entries().is_empty());
+        wal.append_rows(&[b"d"]).unwrap();
+        assert_eq!(wal.entries(), vec![WalEntry::Row(b"d".to_vec())]);
+    }
+
+    #[tokio::test]
+    pub async fn test_write_ahead_log_encryption() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("wal_test");
+        let key = EncryptionKey::from_secret("secret");
+
+        let wal = WriteAheadLog::new(&path, Some(key.clone()));
+        wal.append_rows(&[b"Hello World"]).unwrap();
+        wal.append_reconcile(0, 1).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(!bytes.windows(5).any(|window| window == b"Hello"));
+
+        let wal = WriteAheadLog::new(&path, Some(key));
+        assert_eq!(wal.recover(1).written, vec![(0, b"Hello World".to_vec())]);
+
+        // Rows can't be read with another key
+        let wal = WriteAheadLog::new(&path, Some(EncryptionKey::from_secret("other")));
+        assert!(wal.entries().is_empty());
+    }
+}
@@ -13,8 +13,8 @@ pub fn create_scheme_js_folder(base_path: Option<PathBuf>) {
     .into_iter();
 
     for path in paths {
-        println!("{}", path.to_str().unwrap());
         if !path.exists() {
+            tracing::info!(path = %path.display(), "Creating SchemeJS folder");
             std::fs::create_dir(path).unwrap();
         }
     }
@@ -0,0 +1,507 @@
+use crate::engine_db::EngineDb;
+use schemajs_data::shard::shards::data_shard::config::TableStorage;
+use schemajs_index::index_type::IndexType;
+use schemajs_primitives::column::types::{DataTypes, DataValue};
+use schemajs_primitives::column::Column;
+use schemajs_primitives::index::Index;
+use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+use schemajs_query::row_json::{RowData, RowJson};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/// Database holding the users and roles of the engine, created when access control is enabled.
+pub const SYSTEM_DATABASE: &str = "_system";
+
+/// Table of the `SYSTEM_DATABASE` holding the users.
+pub const USERS_TABLE: &str = "users";
+
+/// Table of the `SYSTEM_DATABASE` holding the roles.
+pub const ROLES_TABLE: &str = "roles";
+
+/// Role granting every privilege on every database, the `SYSTEM_DATABASE` included, see `AccessControl::bootstrap_admin`.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Database of a `Grant` covering every database but the `SYSTEM_DATABASE`, which is only granted by name.
+pub const ANY_DATABASE: &str = "*";
+
+/// What a principal can do, every privilege including the ones before it.
+///
+/// - `Read`: Search, export, back up and snapshot.
+/// - `Write`: Insert, update, delete and import.
+/// - `Admin`: Create tables and databases, rebuild indexes and restore. Admins of the `SYSTEM_DATABASE`
+///   manage the users and roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Privilege {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Display for Privilege {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Privilege::Read => write!(f, "read"),
+            Privilege::Write => write!(f, "write"),
+            Privilege::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// Privilege of a role on a database, or on a table of it.
+///
+/// # Fields:
+/// - `database`: Database the privilege is granted on, `ANY_DATABASE` for every database.
+/// - `table`: Table the privilege is granted on, every table of the database when `None`.
+///   Grants on a table don't cover operations on the whole database (e.g. creating tables).
+/// - `privilege`: The privilege granted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grant {
+    pub database: String,
+    #[serde(default)]
+    pub table: Option<String>,
+    pub privilege: Privilege,
+}
+
+impl Grant {
+    /// Whether the grant allows `privilege` on `table` of `db_name`, on the whole database when `table` is `None`.
+    pub fn covers(&self, db_name: &str, table: Option<&str>, privilege: Privilege) -> bool {
+        let database = match self.database == ANY_DATABASE {
+            true => db_name != SYSTEM_DATABASE,
+            false => self.database == db_name,
+        };
+        if !database {
+            return false;
+        }
+        if privilege > self.privilege {
+            return false;
+        }
+
+        match (&self.table, table) {
+            (None, _) => true,
+            (Some(granted), Some(table)) => granted == table,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Named set of grants, given to users.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Role {
+    pub name: String,
+    #[serde(default)]
+    pub grants: Vec<Grant>,
+}
+
+/// User of the remote interfaces and runtimes, holding the privileges of its roles.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// Who an operation runs for.
+///
+/// - `System`: The engine itself, e.g. scripts of the workspace and migrations. Never checked.
+/// - `User`: A user of the `SYSTEM_DATABASE`, by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Principal {
+    System,
+    User(String),
+}
+
+impl Display for Principal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Principal::System => write!(f, "system"),
+            Principal::User(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Definition of a table of the `SYSTEM_DATABASE`, `definition` being the JSON document of the user or role.
+fn system_table(name: &str) -> Table {
+    Table::new(name)
+        .add_column(Column::new("name", DataTypes::String))
+        .add_column(Column::new("definition", DataTypes::String))
+        .add_index(Index {
+            name: "name_indx".to_string(),
+            members: vec![String::from("name")],
+            index_type: IndexType::Hash,
+            unique: true,
+        })
+}
+
+/// Users and roles of the engine, stored in the `SYSTEM_DATABASE` and kept in memory to check every operation
+/// against them (see `SchemeJsEngine::authorize`).
+///
+/// # Fields:
+/// - `db`: The `SYSTEM_DATABASE`.
+/// - `roles`: Every role, by name.
+/// - `users`: Every user, by name.
+#[derive(Debug)]
+pub struct AccessControl {
+    db: Arc<EngineDb>,
+    roles: RwLock<HashMap<String, Role>>,
+    users: RwLock<HashMap<String, User>>,
+}
+
+impl AccessControl {
+    /// Loads the users and roles stored in `db`, registering its tables if needed.
+    pub fn load(db: Arc<EngineDb>) -> Result<Self, QueryError> {
+        for table_name in [USERS_TABLE, ROLES_TABLE] {
+            if db.query_manager.tables.get(table_name).is_none() {
+                db.add_table(system_table(table_name), TableStorage::default())?;
+            }
+        }
+
+        let access = Self {
+            roles: RwLock::new(Self::read_definitions::<Role>(&db, ROLES_TABLE)?),
+            users: RwLock::new(Self::read_definitions::<User>(&db, USERS_TABLE)?),
+            db,
+        };
+
+        Ok(access)
+    }
+
+    fn read_definitions<D: for<'de> Deserialize<'de>>(
+        db: &EngineDb,
+        table_name: &str,
+    ) -> Result<HashMap<String, D>, QueryError> {
+        let rows = db
+            .query_manager
+            .search_manager()
+            .search(table_name.to_string(), &QueryOps::And(vec![]))?;
+
+        let mut definitions = HashMap::new();
+        for row in rows {
+            let name = row.value.value.get("name").and_then(Value::as_str);
+            let definition = row
+                .value
+                .value
+                .get("definition")
+                .and_then(Value::as_str)
+                .and_then(|definition| serde_json::from_str(definition).ok());
+            if let (Some(name), Some(definition)) = (name, definition) {
+                definitions.insert(name.to_string(), definition);
+            }
+        }
+
+        Ok(definitions)
+    }
+
+    fn by_name(name: &str) -> QueryOps {
+        QueryOps::Condition(QueryVal {
+            key: "name".to_string(),
+            filter_type: "=".to_string(),
+            value: DataValue::String(name.to_string()),
+        })
+    }
+
+    fn store<D: Serialize>(
+        &self,
+        table_name: &str,
+        name: &str,
+        definition: &D,
+    ) -> Result<(), QueryError> {
+        let definition =
+            serde_json::to_string(definition).map_err(|_| QueryError::InvalidSerialization)?;
+        self.db.query_manager.upsert(
+            RowJson::from(RowData {
+                table: table_name.to_string(),
+                value: json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "name": name,
+                    "definition": definition
+                }),
+            }),
+            Some("name_indx"),
+        )?;
+
+        // Upserts match the reconciled rows only, the next change of the same name has to find this one
+        if let Some(table) = self.db.query_manager.tables.get(table_name) {
+            table.temps.reconcile_all();
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, table_name: &str, name: &str) -> Result<(), QueryError> {
+        self.db
+            .query_manager
+            .delete(table_name.to_string(), &Self::by_name(name))?;
+        Ok(())
+    }
+
+    pub fn role(&self, name: &str) -> Option<Role> {
+        self.roles.read().unwrap().get(name).cloned()
+    }
+
+    pub fn user(&self, name: &str) -> Option<User> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+
+    /// Creates `role`, replacing the role with its name if any.
+    pub fn put_role(&self, role: Role) -> Result<(), QueryError> {
+        self.store(ROLES_TABLE, &role.name, &role)?;
+        self.roles.write().unwrap().insert(role.name.clone(), role);
+        Ok(())
+    }
+
+    /// Drops the role `name`, returning whether it existed. Users holding it lose its privileges.
+    pub fn drop_role(&self, name: &str) -> Result<bool, QueryError> {
+        self.remove(ROLES_TABLE, name)?;
+        Ok(self.roles.write().unwrap().remove(name).is_some())
+    }
+
+    /// Creates `user`, replacing the user with its name if any. Fails with `InvalidRole` when one of its roles doesn't exist.
+    pub fn put_user(&self, user: User) -> Result<(), QueryError> {
+        {
+            let roles = self.roles.read().unwrap();
+            if let Some(missing) = user.roles.iter().find(|role| !roles.contains_key(*role)) {
+                return Err(QueryError::InvalidRole(missing.clone()));
+            }
+        }
+
+        self.store(USERS_TABLE, &user.name, &user)?;
+        self.users.write().unwrap().insert(user.name.clone(), user);
+        Ok(())
+    }
+
+    /// Drops the user `name`, returning whether it existed.
+    pub fn drop_user(&self, name: &str) -> Result<bool, QueryError> {
+        self.remove(USERS_TABLE, name)?;
+        Ok(self.users.write().unwrap().remove(name).is_some())
+    }
+
+    /// Gives the user `name` the `ADMIN_ROLE`, creating both if needed, so the engine always has an administrator.
+    /// The grants of the role are restored if they were changed.
+    pub fn bootstrap_admin(&self, name: &str) -> Result<(), QueryError> {
+        let mut role = self.role(ADMIN_ROLE).unwrap_or(Role {
+            name: ADMIN_ROLE.to_string(),
+            grants: vec![],
+        });
+        let missing: Vec<Grant> = [ANY_DATABASE, SYSTEM_DATABASE]
+            .into_iter()
+            .map(|database| Grant {
+                database: database.to_string(),
+                table: None,
+                privilege: Privilege::Admin,
+            })
+            .filter(|grant| !role.grants.contains(grant))
+            .collect();
+        if !missing.is_empty() {
+            role.grants.extend(missing);
+            self.put_role(role)?;
+        }
+
+        let mut user = self.user(name).unwrap_or(User {
+            name: name.to_string(),
+            roles: vec![],
+        });
+        if !user.roles.iter().any(|role| role == ADMIN_ROLE) {
+            user.roles.push(ADMIN_ROLE.to_string());
+            self.put_user(user)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `principal` holds `privilege` on `table` of `db_name` (on the whole database when `table` is `None`)
+    /// through one of its roles. Fails with `AccessDenied` otherwise, unknown users holding no privilege.
+    ///
+    /// Only the engine itself reads and writes the tables of the `SYSTEM_DATABASE`: users and roles are changed
+    /// through `put_user`, `put_role`.. (admins of the `SYSTEM_DATABASE`), so the ones stored are always the ones checked.
+    pub fn check(
+        &self,
+        principal: &Principal,
+        db_name: &str,
+        table: Option<&str>,
+        privilege: Privilege,
+    ) -> Result<(), QueryError> {
+        let name = match principal {
+            Principal::System => return Ok(()),
+            Principal::User(name) => name,
+        };
+        if db_name == SYSTEM_DATABASE && table.is_some() {
+            return Err(QueryError::AccessDenied(format!(
+                "'{}' can't access the tables of '{}' directly",
+                principal, SYSTEM_DATABASE
+            )));
+        }
+
+        let allowed = self.user(name).map_or(false, |user| {
+            let roles = self.roles.read().unwrap();
+            user.roles
+                .iter()
+                .filter_map(|role| roles.get(role))
+                .flat_map(|role| role.grants.iter())
+                .any(|grant| grant.covers(db_name, table, privilege))
+        });
+
+        match allowed {
+            true => Ok(()),
+            false => Err(QueryError::AccessDenied(format!(
+                "'{}' lacks {} on '{}'",
+                principal,
+                privilege,
+                match table {
+                    Some(table) => format!("{}.{}", db_name, table),
+                    None => db_name.to_string(),
+                }
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::access::{
+        AccessControl, Grant, Principal, Privilege, Role, User, ADMIN_ROLE, ANY_DATABASE,
+        ROLES_TABLE, SYSTEM_DATABASE, USERS_TABLE,
+    };
+    use crate::engine::SchemeJsEngine;
+    use schemajs_query::errors::QueryError;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_access_control() {
+        // Stands for the system database, which is shared by every engine using the default data folder
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let system_db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        let access = Arc::new(AccessControl::load(system_db.clone()).unwrap());
+        engine.access = Some(access.clone());
+
+        access
+            .put_role(Role {
+                name: "analyst".to_string(),
+                grants: vec![
+                    Grant {
+                        database: "sales".to_string(),
+                        table: None,
+                        privilege: Privilege::Read,
+                    },
+                    Grant {
+                        database: "sales".to_string(),
+                        table: Some("reports".to_string()),
+                        privilege: Privilege::Write,
+                    },
+                ],
+            })
+            .unwrap();
+        assert!(matches!(
+            access.put_user(User {
+                name: "andres".to_string(),
+                roles: vec!["unknown".to_string()],
+            }),
+            Err(QueryError::InvalidRole(_))
+        ));
+        access
+            .put_user(User {
+                name: "andres".to_string(),
+                roles: vec!["analyst".to_string()],
+            })
+            .unwrap();
+
+        let andres = Principal::User("andres".to_string());
+        let allowed = |principal: &Principal, db: &str, table: Option<&str>, privilege| {
+            engine.authorize(principal, db, table, privilege).is_ok()
+        };
+        assert!(allowed(&andres, "sales", Some("orders"), Privilege::Read));
+        assert!(!allowed(&andres, "sales", Some("orders"), Privilege::Write));
+        assert!(allowed(&andres, "sales", Some("reports"), Privilege::Write));
+        // Table grants don't cover the whole database
+        assert!(!allowed(&andres, "sales", None, Privilege::Write));
+        assert!(!allowed(
+            &andres,
+            "sales",
+            Some("reports"),
+            Privilege::Admin
+        ));
+        assert!(!allowed(&andres, "hr", Some("employees"), Privilege::Read));
+        assert!(!allowed(
+            &Principal::User("carlos".to_string()),
+            "sales",
+            None,
+            Privilege::Read
+        ));
+        assert!(allowed(&Principal::System, "hr", None, Privilege::Admin));
+
+        access.bootstrap_admin("carlos").unwrap();
+        let carlos = Principal::User("carlos".to_string());
+        assert!(allowed(&carlos, "hr", None, Privilege::Admin));
+        assert!(allowed(&carlos, SYSTEM_DATABASE, None, Privilege::Admin));
+
+        // Grants on every database leave out the system database, whose tables only the engine itself touches
+        access
+            .put_role(Role {
+                name: "writer".to_string(),
+                grants: vec![Grant {
+                    database: ANY_DATABASE.to_string(),
+                    table: None,
+                    privilege: Privilege::Write,
+                }],
+            })
+            .unwrap();
+        access
+            .put_user(User {
+                name: "luis".to_string(),
+                roles: vec!["writer".to_string()],
+            })
+            .unwrap();
+        let luis = Principal::User("luis".to_string());
+        assert!(allowed(&luis, "hr", Some("employees"), Privilege::Write));
+        for table in [USERS_TABLE, ROLES_TABLE] {
+            assert!(!allowed(
+                &luis,
+                SYSTEM_DATABASE,
+                Some(table),
+                Privilege::Write
+            ));
+            assert!(!allowed(
+                &luis,
+                SYSTEM_DATABASE,
+                Some(table),
+                Privilege::Read
+            ));
+            assert!(!allowed(
+                &carlos,
+                SYSTEM_DATABASE,
+                Some(table),
+                Privilege::Write
+            ));
+            assert!(allowed(
+                &Principal::System,
+                SYSTEM_DATABASE,
+                Some(table),
+                Privilege::Write
+            ));
+        }
+        assert!(!allowed(&luis, SYSTEM_DATABASE, None, Privilege::Read));
+
+        // Users and roles are stored in the system database
+        let reloaded = AccessControl::load(system_db.clone()).unwrap();
+        assert_eq!(reloaded.user("andres").unwrap().roles, vec!["analyst"]);
+        assert!(reloaded.role(ADMIN_ROLE).is_some());
+
+        assert!(access.drop_role("analyst").unwrap());
+        assert!(!allowed(&andres, "sales", Some("orders"), Privilege::Read));
+        assert!(access.drop_user("andres").unwrap());
+        assert!(!access.drop_user("andres").unwrap());
+        let reloaded = AccessControl::load(system_db).unwrap();
+        assert!(reloaded.user("andres").is_none());
+        assert!(reloaded.role("analyst").is_none());
+
+        std::fs::remove_dir_all(&system_db.db_folder).unwrap();
+    }
+}
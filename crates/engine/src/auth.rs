@@ -0,0 +1,432 @@
+use crate::access::Principal;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use enum_as_inner::EnumAsInner;
+use hmac::{Hmac, Mac};
+use schemajs_config::{SchemeJsAuthConfig, SchemeJsJwtConfig};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Error, EnumAsInner)]
+pub enum AuthError {
+    #[error("Credentials are required")]
+    MissingCredentials,
+
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+}
+
+/// Credentials sent by a client of the remote interfaces, as the `Authorization` header (or metadata) of its requests.
+///
+/// - `Bearer`: A static token or a JWT, `Bearer <token>`.
+/// - `Basic`: A user and its password, `Basic <base64 of user:password>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    Bearer(String),
+    Basic { user: String, password: String },
+}
+
+impl Credentials {
+    /// Credentials of the `Authorization` header `value`, `None` when it isn't a bearer token nor basic credentials.
+    pub fn from_authorization(value: &str) -> Option<Self> {
+        let (scheme, credentials) = value.trim().split_once(' ')?;
+        let credentials = credentials.trim();
+
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return Some(Credentials::Bearer(credentials.to_string()));
+        }
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = String::from_utf8(STANDARD.decode(credentials).ok()?).ok()?;
+            let (user, password) = decoded.split_once(':')?;
+            return Some(Credentials::Basic {
+                user: user.to_string(),
+                password: password.to_string(),
+            });
+        }
+
+        None
+    }
+}
+
+/// Way of authenticating clients. Providers answer `None` for credentials they don't handle (e.g. a JWT
+/// for the static tokens), so the next provider can try them.
+pub trait AuthProvider: Debug + Send + Sync {
+    fn authenticate(&self, credentials: &Credentials) -> Option<Result<Principal, AuthError>>;
+}
+
+// Compares without returning early, so the time taken doesn't tell how much of a secret was guessed
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// HMAC-SHA256 of `message` with `key`, as used by HS256 JWTs.
+fn hs256(key: &[u8], message: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(message);
+    mac
+}
+
+/// Signature of `message` with `key`, as used by HS256 JWTs.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    hs256(key, message).finalize().into_bytes().into()
+}
+
+/// Hash of `password` for `[[auth.passwords]]`, an Argon2id PHC string (`$argon2id$v=19$...`) with a random salt.
+pub fn hash_password(password: &str) -> String {
+    let salt =
+        SaltString::encode_b64(Uuid::new_v4().as_bytes()).expect("16 bytes are a valid salt");
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Argon2 hashes passwords of any size with the default parameters")
+        .to_string()
+}
+
+/// Whether `password` matches `hash`, as returned by `hash_password`. Hashes that aren't PHC strings never match.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).map_or(false, |hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Static bearer tokens, each one authenticating a user.
+#[derive(Debug)]
+pub struct StaticTokens {
+    tokens: Vec<(String, String)>,
+}
+
+impl StaticTokens {
+    /// # Parameters:
+    /// - `tokens`: Every token along with the user it authenticates.
+    pub fn new(tokens: Vec<(String, String)>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl AuthProvider for StaticTokens {
+    fn authenticate(&self, credentials: &Credentials) -> Option<Result<Principal, AuthError>> {
+        let token = match credentials {
+            Credentials::Bearer(token) => token,
+            Credentials::Basic { .. } => return None,
+        };
+
+        self.tokens
+            .iter()
+            .find(|(known, _)| constant_time_eq(known.as_bytes(), token.as_bytes()))
+            .map(|(_, user)| Ok(Principal::User(user.clone())))
+    }
+}
+
+/// Users authenticated by their password, checked against its hash (see `hash_password`).
+#[derive(Debug)]
+pub struct PasswordHashes {
+    hashes: HashMap<String, String>,
+}
+
+impl PasswordHashes {
+    /// # Parameters:
+    /// - `hashes`: Hash of the password of every user, by user name.
+    pub fn new(hashes: HashMap<String, String>) -> Self {
+        Self { hashes }
+    }
+}
+
+impl AuthProvider for PasswordHashes {
+    fn authenticate(&self, credentials: &Credentials) -> Option<Result<Principal, AuthError>> {
+        let (user, password) = match credentials {
+            Credentials::Basic { user, password } => (user, password),
+            Credentials::Bearer(_) => return None,
+        };
+
+        // Unknown users are rejected here, no other provider handles passwords
+        Some(match self.hashes.get(user) {
+            Some(hash) if verify_password(password, hash) => Ok(Principal::User(user.clone())),
+            _ => Err(AuthError::InvalidCredentials),
+        })
+    }
+}
+
+/// JWTs signed with HS256, authenticating the user of their `sub` claim.
+///
+/// Tokens are rejected once expired (`exp`) or before they are valid (`nbf`), and when they aren't issued by
+/// `issuer` (`iss`) or for `audience` (`aud`) if these are configured.
+#[derive(Debug)]
+pub struct JwtValidator {
+    secret: Vec<u8>,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl JwtValidator {
+    pub fn new(config: &SchemeJsJwtConfig) -> Self {
+        Self {
+            secret: config.secret.as_bytes().to_vec(),
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+        }
+    }
+
+    /// Claims of `token` once its signature is checked.
+    pub fn claims(&self, token: &str) -> Result<Value, AuthError> {
+        let invalid = |reason: &str| AuthError::InvalidToken(reason.to_string());
+
+        let parts: Vec<&str> = token.split('.').collect();
+        let [header, payload, signature] = parts.as_slice() else {
+            return Err(invalid("expected a JWT"));
+        };
+        let decode = |part: &str| -> Result<Value, AuthError> {
+            let json = URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| invalid("malformed"))?;
+            serde_json::from_slice(&json).map_err(|_| invalid("malformed"))
+        };
+
+        if decode(header)?.get("alg").and_then(Value::as_str) != Some("HS256") {
+            return Err(invalid("only HS256 is supported"));
+        }
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| invalid("malformed"))?;
+        hs256(&self.secret, format!("{}.{}", header, payload).as_bytes())
+            .verify_slice(&signature)
+            .map_err(|_| invalid("bad signature"))?;
+
+        decode(payload)
+    }
+
+    fn validate(&self, claims: &Value) -> Result<Principal, AuthError> {
+        let invalid = |reason: &str| AuthError::InvalidToken(reason.to_string());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+
+        if let Some(exp) = claims.get("exp") {
+            if exp.as_u64().map_or(true, |exp| exp <= now) {
+                return Err(invalid("expired"));
+            }
+        }
+        if let Some(nbf) = claims.get("nbf") {
+            if nbf.as_u64().map_or(true, |nbf| nbf > now) {
+                return Err(invalid("not valid yet"));
+            }
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+                return Err(invalid("unexpected issuer"));
+            }
+        }
+        if let Some(audience) = &self.audience {
+            let allowed = match claims.get("aud") {
+                Some(Value::String(aud)) => aud == audience,
+                Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+                _ => false,
+            };
+            if !allowed {
+                return Err(invalid("unexpected audience"));
+            }
+        }
+
+        claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .map(|sub| Principal::User(sub.to_string()))
+            .ok_or_else(|| invalid("missing 'sub'"))
+    }
+}
+
+impl AuthProvider for JwtValidator {
+    fn authenticate(&self, credentials: &Credentials) -> Option<Result<Principal, AuthError>> {
+        match credentials {
+            // Static tokens are never JWTs, those are tried first
+            Credentials::Bearer(token) if token.split('.').count() == 3 => {
+                Some(self.claims(token).and_then(|claims| self.validate(&claims)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Authenticates the clients of the remote interfaces (HTTP and gRPC), resolving their credentials to the principal
+/// their requests run for (see `SchemeJsEngine::authorize`).
+///
+/// Credentials are tried against every provider, in order, the first one handling them deciding.
+/// Providers are the ones of `[auth]` in `SchemeJS.toml`, custom ones are added with `add_provider`.
+#[derive(Debug, Default)]
+pub struct Authenticator {
+    providers: Vec<Box<dyn AuthProvider>>,
+}
+
+impl Authenticator {
+    /// Authenticator with the static tokens, password hashes and JWT validation of `config`.
+    pub fn new(config: &SchemeJsAuthConfig) -> Self {
+        let mut authenticator = Self::default();
+        if !config.tokens.is_empty() {
+            authenticator = authenticator.add_provider(Box::new(StaticTokens::new(
+                config
+                    .tokens
+                    .iter()
+                    .map(|token| (token.token.clone(), token.user.clone()))
+                    .collect(),
+            )));
+        }
+        if !config.passwords.is_empty() {
+            authenticator = authenticator.add_provider(Box::new(PasswordHashes::new(
+                config
+                    .passwords
+                    .iter()
+                    .map(|password| (password.user.clone(), password.hash.clone()))
+                    .collect(),
+            )));
+        }
+        if let Some(jwt) = &config.jwt {
+            authenticator = authenticator.add_provider(Box::new(JwtValidator::new(jwt)));
+        }
+
+        authenticator
+    }
+
+    pub fn add_provider(mut self, provider: Box<dyn AuthProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+
+    /// Principal of the client sending `authorization` (the value of its `Authorization` header, if any).
+    pub fn authenticate(&self, authorization: Option<&str>) -> Result<Principal, AuthError> {
+        let credentials = authorization
+            .and_then(Credentials::from_authorization)
+            .ok_or(AuthError::MissingCredentials)?;
+
+        self.providers
+            .iter()
+            .find_map(|provider| provider.authenticate(&credentials))
+            .unwrap_or(Err(AuthError::InvalidCredentials))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::access::Principal;
+    use crate::auth::{hash_password, hmac_sha256, verify_password, AuthError, Authenticator};
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+    use base64::Engine;
+    use schemajs_config::{
+        SchemeJsAuthConfig, SchemeJsJwtConfig, SchemeJsPasswordConfig, SchemeJsTokenConfig,
+    };
+    use serde_json::{json, Value};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn jwt(secret: &str, claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "HS256", "typ": "JWT" }).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signature = hmac_sha256(
+            secret.as_bytes(),
+            format!("{}.{}", header, payload).as_bytes(),
+        );
+        format!(
+            "{}.{}.{}",
+            header,
+            payload,
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    #[test]
+    pub fn test_authenticator() {
+        let hash = hash_password("secret");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("secret", &hash));
+        assert!(!verify_password("wrong", &hash));
+        // Every hash has its own salt
+        assert_ne!(hash_password("secret"), hash);
+        assert!(!verify_password("secret", "not a hash"));
+
+        let authenticator = Authenticator::new(&SchemeJsAuthConfig {
+            tokens: vec![SchemeJsTokenConfig {
+                token: "andres-token".to_string(),
+                user: "andres".to_string(),
+            }],
+            passwords: vec![SchemeJsPasswordConfig {
+                user: "carlos".to_string(),
+                hash,
+            }],
+            jwt: Some(SchemeJsJwtConfig {
+                secret: "jwt-secret".to_string(),
+                issuer: Some("schemajs".to_string()),
+                audience: None,
+            }),
+        });
+        let user = |name: &str| Principal::User(name.to_string());
+
+        assert_eq!(
+            authenticator
+                .authenticate(Some("Bearer andres-token"))
+                .unwrap(),
+            user("andres")
+        );
+        let basic = |credentials: &str| format!("Basic {}", STANDARD.encode(credentials));
+        assert_eq!(
+            authenticator
+                .authenticate(Some(basic("carlos:secret").as_str()))
+                .unwrap(),
+            user("carlos")
+        );
+        assert!(authenticator
+            .authenticate(Some(basic("carlos:wrong").as_str()))
+            .unwrap_err()
+            .is_invalid_credentials());
+        assert!(authenticator
+            .authenticate(Some("Bearer unknown"))
+            .unwrap_err()
+            .is_invalid_credentials());
+        assert!(authenticator
+            .authenticate(None)
+            .unwrap_err()
+            .is_missing_credentials());
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let token = jwt(
+            "jwt-secret",
+            json!({ "sub": "luis", "iss": "schemajs", "exp": now + 60 }),
+        );
+        assert_eq!(
+            authenticator
+                .authenticate(Some(format!("Bearer {}", token).as_str()))
+                .unwrap(),
+            user("luis")
+        );
+
+        let rejected = |token: String| {
+            matches!(
+                authenticator.authenticate(Some(format!("Bearer {}", token).as_str())),
+                Err(AuthError::InvalidToken(_))
+            )
+        };
+        assert!(rejected(jwt(
+            "jwt-secret",
+            json!({ "sub": "luis", "iss": "schemajs", "exp": now - 60 })
+        )));
+        assert!(rejected(jwt(
+            "other-secret",
+            json!({ "sub": "luis", "iss": "schemajs" })
+        )));
+        assert!(rejected(jwt(
+            "jwt-secret",
+            json!({ "sub": "luis", "iss": "someone-else" })
+        )));
+    }
+}
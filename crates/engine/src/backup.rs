@@ -0,0 +1,182 @@
+use crate::engine::SchemeJsEngine;
+use crate::engine_db::EngineDb;
+use anyhow::{bail, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression as GzCompression;
+use schemajs_dirs::get_base_path;
+use schemajs_query::managers::single::swap::TableSwap;
+use std::fs::File;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Archives the tables of `db` into `dest`, a gzipped tarball holding the folder of every table under the
+/// name of the database (`<database>/<table>/..`), just like the `dbs` folder.
+///
+/// Tables are archived one by one, each of them once its rows are reconciled and while writes to it wait
+/// (see `SingleQueryManager::backup_table`). Files of encrypted databases are archived encrypted.
+pub fn backup_database(db: &EngineDb, dest: &Path) -> Result<()> {
+    let encoder = GzEncoder::new(File::create(dest)?, GzCompression::default());
+    let mut archive = tar::Builder::new(encoder);
+
+    let table_names = db.query_manager.table_names.read().unwrap().clone();
+    for table_name in table_names {
+        let archive_path = Path::new(&db.name).join(&table_name);
+        db.query_manager.backup_table(&table_name, |path| {
+            archive.append_dir_all(&archive_path, path)
+        })??;
+    }
+
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Restores the database archived in `src` by `backup_database`, returning its name.
+///
+/// The archive is unpacked next to the databases first, so a broken archive leaves them untouched.
+/// Registered tables are reopened from the archived files (see `SingleQueryManager::restore_table`),
+/// the folders of the other tables are replaced and picked up once their database is loaded.
+pub fn restore_database(engine: &SchemeJsEngine, src: &Path) -> Result<String> {
+    let dbs_folder = get_base_path(engine.data_path_dir.clone()).join("dbs");
+    let staging = dbs_folder.join(format!(".restore-{}", Uuid::new_v4()));
+
+    let restored = restore_from_staging(engine, &dbs_folder, &staging, src);
+    let _ = std::fs::remove_dir_all(&staging);
+
+    restored
+}
+
+fn restore_from_staging(
+    engine: &SchemeJsEngine,
+    dbs_folder: &Path,
+    staging: &Path,
+    src: &Path,
+) -> Result<String> {
+    tar::Archive::new(GzDecoder::new(File::open(src)?)).unpack(staging)?;
+
+    let mut databases = std::fs::read_dir(staging)?.collect::<std::io::Result<Vec<_>>>()?;
+    if databases.len() != 1 {
+        bail!("Backup archives must hold a single database");
+    }
+
+    let db_name = databases
+        .remove(0)
+        .file_name()
+        .to_string_lossy()
+        .to_string();
+    let db = engine.find_by_name_ref(db_name.clone());
+    let db_folder = db
+        .as_ref()
+        .map_or_else(|| dbs_folder.join(&db_name), |db| db.db_folder.clone());
+    std::fs::create_dir_all(&db_folder)?;
+
+    for table in std::fs::read_dir(staging.join(&db_name))? {
+        let table = table?;
+        let table_name = table.file_name().to_string_lossy().to_string();
+
+        match db
+            .as_ref()
+            .filter(|db| db.query_manager.tables.contains_key(&table_name))
+        {
+            Some(db) => db.query_manager.restore_table(&table_name, &table.path())?,
+            None => {
+                let path = db_folder.join(&table_name);
+                match path.exists() {
+                    true => TableSwap::start(&path, &table.path())?.finish()?,
+                    false => std::fs::rename(table.path(), path)?,
+                }
+            }
+        }
+    }
+
+    Ok(db_name)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::SchemeJsEngine;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::row_json::{RowData, RowJson};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_backup_restore() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let engine = engine;
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            TableStorage::default(),
+        )
+        .unwrap();
+
+        let insert = |name: &str| {
+            db.query_manager
+                .insert(RowJson::from(RowData {
+                    table: "users".to_string(),
+                    value: json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name
+                    }),
+                }))
+                .unwrap();
+        };
+
+        insert("andres");
+        insert("carlos");
+
+        let archive = std::env::temp_dir().join(format!("{}.tar.gz", Uuid::new_v4()));
+        engine.backup(&db_name, &archive).unwrap();
+        assert!(engine.backup("unknown", &archive).is_err());
+
+        // Rows written after the backup are gone once it is restored
+        insert("luis");
+        assert_eq!(engine.restore(&archive).unwrap(), db_name);
+
+        {
+            let tbl = db.query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+
+            let data = tbl.data.read().unwrap();
+            assert_eq!(data.len(), 2);
+
+            let names: Vec<String> = (0..2)
+                .map(|i| {
+                    let row = RowJson::from(data.get_element(i).unwrap().as_slice());
+                    row.value.value["user_name"].as_str().unwrap().to_string()
+                })
+                .collect();
+            assert_eq!(names, vec!["andres", "carlos"]);
+        }
+
+        // The restored table keeps taking writes
+        insert("juan");
+        db.query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+        assert_eq!(
+            db.query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .data
+                .read()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        std::fs::remove_file(archive).unwrap();
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
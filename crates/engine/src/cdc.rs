@@ -0,0 +1,528 @@
+use crate::engine_db::EngineDb;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chashmap::CHashMap;
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_query::managers::single::changes::{ChangeEvent, ChangeKind, ChangeSink};
+use schemajs_query::managers::single::table_shard::TableShard;
+use schemajs_query::row_json::RowJson;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Folder of a database holding its change log.
+pub const CDC_FOLDER: &str = "cdc";
+
+/// File of the `CDC_FOLDER` the changes are appended to, one `CdcRecord` per line: its JSON, or for encrypted
+/// databases the base64 of its JSON encrypted with the key of the database.
+pub const CDC_FILE: &str = "changes.jsonl";
+
+/// Bytes of the change log read in a row when looking for a change, instead of bisecting the file further.
+const SCAN_BYTES: u64 = 64 * 1024;
+
+/// Change of a row, as written to the change log of its database.
+///
+/// # Fields:
+/// - `sequence`: Position of the change in the log. Sequences keep growing across restarts.
+/// - `timestamp`: When the change was written, in epoch millis.
+/// - `table`: Table holding the row.
+/// - `op`: Whether the row was inserted, updated or deleted.
+/// - `before`: The row before the change, `None` for inserts.
+/// - `after`: The row after the change, `None` for deletes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CdcRecord {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub table: String,
+    pub op: ChangeKind,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Change log of a database: every change of its rows, in the order they were made, appended to
+/// `<database>/cdc/changes.jsonl` so downstream systems can mirror the database by tailing the file
+/// (or reading it through `read_records`). Rows of encrypted databases are never written in clear.
+///
+/// # Fields:
+/// - `path`: File the changes are appended to.
+/// - `tail`: The open file and where it ends, appends being serialized by the change feed.
+/// - `tables`: Tables of the database, rows are written the way they are exposed to JS (see `RowJson::to_json`).
+/// - `encryption`: Key of the database the records are encrypted with, if any.
+/// - `fsync`: Whether every change is flushed to disk before the write returns.
+#[derive(Debug)]
+pub struct CdcLog {
+    path: PathBuf,
+    tail: Mutex<CdcTail>,
+    tables: Arc<CHashMap<String, TableShard<RowJson>>>,
+    encryption: Option<EncryptionKey>,
+    fsync: bool,
+}
+
+/// End of the change log, kept as changes are appended so it's never looked for again in the file.
+///
+/// # Fields:
+/// - `file`: The open file.
+/// - `sequence`: Sequence of the last change in the file, 0 when there is none.
+/// - `len`: Offset the next change is written at.
+#[derive(Debug)]
+struct CdcTail {
+    file: File,
+    sequence: u64,
+    len: u64,
+}
+
+impl CdcLog {
+    /// Change log of the database stored in `db_folder`.
+    pub fn path(db_folder: &Path) -> PathBuf {
+        db_folder.join(CDC_FOLDER).join(CDC_FILE)
+    }
+
+    /// Writes every change of `db` made from now on to its change log, continuing the sequences of the changes
+    /// already in it. Only the end of the file is read to find the last of them.
+    pub fn enable(db: &EngineDb, fsync: bool) -> std::io::Result<Arc<Self>> {
+        let path = Self::path(&db.db_folder);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+
+        let encryption = db.query_manager.encryption().cloned();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        let sequence = match last_record(&file, encryption.as_ref())? {
+            Some(last) => {
+                db.query_manager.changes().resume_sequence(last.sequence);
+                last.sequence
+            }
+            None => 0,
+        };
+
+        // Ends the line the process may have stopped writing, so the next change doesn't get appended to it
+        let mut len = file.metadata()?.len();
+        if len > 0 && !ends_line(&file, len)? {
+            file.write_all(b"\n")?;
+            len += 1;
+        }
+
+        let log = Arc::new(Self {
+            path,
+            tail: Mutex::new(CdcTail {
+                file,
+                sequence,
+                len,
+            }),
+            tables: db.query_manager.tables.clone(),
+            encryption,
+            fsync,
+        });
+        db.query_manager.changes().set_sink(Some(log.clone()));
+
+        Ok(log)
+    }
+
+    pub fn file_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sequence of the last change written to the log, 0 when there is none.
+    pub fn last_sequence(&self) -> u64 {
+        self.tail.lock().unwrap().sequence
+    }
+
+    /// Records following the change at `after`, up to `limit` of them, see `read_records`.
+    pub fn read(&self, after: u64, limit: Option<usize>) -> std::io::Result<Vec<CdcRecord>> {
+        let (sequence, len) = {
+            let tail = self.tail.lock().unwrap();
+            (tail.sequence, tail.len)
+        };
+        if after >= sequence {
+            return Ok(vec![]);
+        }
+
+        let file = File::open(&self.path)?;
+        read_from(&file, self.encryption.as_ref(), after, limit, len)
+    }
+
+    fn row(&self, table_name: &str, data: &[u8]) -> Value {
+        let row = RowJson::from(data);
+        match self.tables.get(table_name) {
+            Some(table_shard) => row.to_json(&table_shard.table),
+            None => row.value.value,
+        }
+    }
+
+    pub fn record(&self, event: &ChangeEvent) -> CdcRecord {
+        let row = Some(self.row(&event.table, &event.row));
+        let (before, after) = match event.kind {
+            ChangeKind::Insert => (None, row),
+            ChangeKind::Update => (
+                event
+                    .before
+                    .as_ref()
+                    .map(|before| self.row(&event.table, before)),
+                row,
+            ),
+            ChangeKind::Delete => (row, None),
+        };
+
+        CdcRecord {
+            sequence: event.sequence,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+            table: event.table.clone(),
+            op: event.kind,
+            before,
+            after,
+        }
+    }
+}
+
+impl ChangeSink for CdcLog {
+    fn write(&self, event: &ChangeEvent) -> std::io::Result<()> {
+        let record = self.record(event);
+        let mut line = encode(&record, self.encryption.as_ref())?;
+        line.push(b'\n');
+
+        let mut tail = self.tail.lock().unwrap();
+        tail.file.write_all(&line)?;
+        if self.fsync {
+            tail.file.sync_data()?;
+        }
+        tail.sequence = record.sequence;
+        tail.len += line.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Line of the change log holding `record`, without its newline.
+fn encode(record: &CdcRecord, encryption: Option<&EncryptionKey>) -> std::io::Result<Vec<u8>> {
+    let json = serde_json::to_vec(record)?;
+    Ok(match encryption {
+        Some(key) => STANDARD.encode(key.encrypt(&json)).into_bytes(),
+        None => json,
+    })
+}
+
+/// Record held by a `line` of the change log, `None` if it can't be read (or was encrypted with another key).
+fn decode(line: &[u8], encryption: Option<&EncryptionKey>) -> Option<CdcRecord> {
+    match encryption {
+        Some(key) => {
+            let encrypted = STANDARD.decode(line).ok()?;
+            serde_json::from_slice(&key.decrypt(&encrypted).ok()?).ok()
+        }
+        None => serde_json::from_slice(line).ok(),
+    }
+}
+
+/// Whether the byte right before `offset` in `file` is a newline.
+fn ends_line(file: &File, offset: u64) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(file);
+    reader.seek(SeekFrom::Start(offset - 1))?;
+    Ok(reader.fill_buf()?.first() == Some(&b'\n'))
+}
+
+/// Reads the records of the lines of `file` starting at or after `offset` (the line `offset` falls in is skipped),
+/// calling `each` with every record until it returns `false`.
+/// Lines that can't be read are skipped, and the last one is left out while it has no newline (it's being written,
+/// or the process stopped while writing it).
+fn scan(
+    file: &File,
+    offset: u64,
+    encryption: Option<&EncryptionKey>,
+    mut each: impl FnMut(CdcRecord) -> bool,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(file);
+    let mut line = vec![];
+    reader.seek(SeekFrom::Start(offset.saturating_sub(1)))?;
+    if offset > 0 {
+        reader.read_until(b'\n', &mut line)?;
+    }
+
+    loop {
+        line.clear();
+        reader.read_until(b'\n', &mut line)?;
+        if line.pop() != Some(b'\n') {
+            return Ok(());
+        }
+
+        if let Some(record) = decode(&line, encryption) {
+            if !each(record) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Last record of the change log `file`, reading back from its end until one is found.
+fn last_record(
+    file: &File,
+    encryption: Option<&EncryptionKey>,
+) -> std::io::Result<Option<CdcRecord>> {
+    let len = file.metadata()?.len();
+    let mut window = SCAN_BYTES;
+    loop {
+        let start = len.saturating_sub(window);
+        let mut last = None;
+        scan(file, start, encryption, |record| {
+            last = Some(record);
+            true
+        })?;
+
+        if last.is_some() || start == 0 {
+            return Ok(last);
+        }
+        window *= 2;
+    }
+}
+
+/// Offset of `file` no change following `after` is written before, found by bisecting the first `len` bytes of the
+/// change log (changes are appended in the order of their sequences) so reads skip the changes before `after`
+/// without going through them.
+fn seek(
+    file: &File,
+    encryption: Option<&EncryptionKey>,
+    after: u64,
+    len: u64,
+) -> std::io::Result<u64> {
+    let (mut low, mut high) = (0, len);
+    while low + SCAN_BYTES < high {
+        let middle = low + (high - low) / 2;
+        let mut first = None;
+        scan(file, middle, encryption, |record| {
+            first = Some(record.sequence);
+            false
+        })?;
+
+        match first {
+            Some(sequence) if sequence <= after => low = middle,
+            _ => high = middle,
+        }
+    }
+
+    Ok(low)
+}
+
+/// Records of the change log at `path` following the change at `after`, up to `limit` of them, read with the key of
+/// its database when it's encrypted. Lines that can't be read (e.g. the last one, when the process stopped while
+/// writing it) are skipped.
+pub fn read_records(
+    path: &Path,
+    encryption: Option<&EncryptionKey>,
+    after: u64,
+    limit: Option<usize>,
+) -> std::io::Result<Vec<CdcRecord>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let file = File::open(path)?;
+    read_from(&file, encryption, after, limit, file.metadata()?.len())
+}
+
+/// Records of the change log `file` following the change at `after`, up to `limit` of them, looking for the first
+/// of them in its first `len` bytes.
+fn read_from(
+    file: &File,
+    encryption: Option<&EncryptionKey>,
+    after: u64,
+    limit: Option<usize>,
+    len: u64,
+) -> std::io::Result<Vec<CdcRecord>> {
+    let mut records = vec![];
+    scan(
+        file,
+        seek(file, encryption, after, len)?,
+        encryption,
+        |record| {
+            if record.sequence > after {
+                records.push(record);
+            }
+            limit.map_or(true, |limit| records.len() < limit)
+        },
+    )?;
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cdc::{last_record, read_records, CdcLog, CdcRecord, CDC_FILE};
+    use crate::engine::SchemeJsEngine;
+    use schemajs_data::encryption::EncryptionKey;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::managers::single::changes::ChangeKind;
+    use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+    use schemajs_query::row_json::{RowData, RowJson};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_cdc_log() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            TableStorage::default(),
+        )
+        .unwrap();
+        let log = CdcLog::enable(&db, false).unwrap();
+
+        let by_name = |name: &str| {
+            QueryOps::Condition(QueryVal {
+                key: "user_name".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(name.to_string()),
+            })
+        };
+        db.query_manager
+            .insert(RowJson::from(RowData {
+                table: "users".to_string(),
+                value: json!({ "_uid": Uuid::new_v4().to_string(), "user_name": "andres" }),
+            }))
+            .unwrap();
+        let mut new_values = HashMap::new();
+        new_values.insert(
+            "user_name".to_string(),
+            DataValue::String("andy".to_string()),
+        );
+        db.query_manager
+            .update("users".to_string(), &by_name("andres"), &new_values)
+            .unwrap();
+        db.query_manager
+            .delete("users".to_string(), &by_name("andy"))
+            .unwrap();
+
+        let records = read_records(log.file_path(), None, 0, None).unwrap();
+        let ops: Vec<(u64, ChangeKind)> = records.iter().map(|r| (r.sequence, r.op)).collect();
+        assert_eq!(
+            ops,
+            vec![
+                (1, ChangeKind::Insert),
+                (2, ChangeKind::Update),
+                (3, ChangeKind::Delete)
+            ]
+        );
+        assert!(records[0].before.is_none());
+        assert_eq!(records[0].after.as_ref().unwrap()["user_name"], "andres");
+        assert_eq!(records[1].before.as_ref().unwrap()["user_name"], "andres");
+        assert_eq!(records[1].after.as_ref().unwrap()["user_name"], "andy");
+        assert_eq!(records[2].before.as_ref().unwrap()["user_name"], "andy");
+        assert!(records[2].after.is_none());
+
+        assert_eq!(
+            read_records(log.file_path(), None, 1, Some(1))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Sequences continue where the log stopped, e.g. after a restart
+        let mut restarted = SchemeJsEngine::new(None);
+        restarted.add_database(&db_name, None);
+        let restarted_db = restarted.find_by_name_ref(db_name.clone()).unwrap();
+        CdcLog::enable(&restarted_db, false).unwrap();
+        assert_eq!(restarted_db.query_manager.changes().sequence(), 3);
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_cdc_log_encrypted() {
+        let db_name = Uuid::new_v4().to_string();
+        let key = EncryptionKey::from_secret("cdc-secret");
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, Some(key.clone()));
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            TableStorage::default(),
+        )
+        .unwrap();
+        let log = CdcLog::enable(&db, false).unwrap();
+
+        db.query_manager
+            .insert(RowJson::from(RowData {
+                table: "users".to_string(),
+                value: json!({ "_uid": Uuid::new_v4().to_string(), "user_name": "andres" }),
+            }))
+            .unwrap();
+
+        // Rows are never written in clear
+        let contents = std::fs::read_to_string(log.file_path()).unwrap();
+        assert!(!contents.contains("andres"));
+        assert!(!contents.contains("users"));
+
+        let records = log.read(0, None).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].after.as_ref().unwrap()["user_name"], "andres");
+        assert_eq!(
+            read_records(log.file_path(), Some(&key), 0, None).unwrap(),
+            records
+        );
+        assert!(read_records(log.file_path(), None, 0, None)
+            .unwrap()
+            .is_empty());
+        assert!(log.read(1, None).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+
+    #[test]
+    pub fn test_cdc_log_offsets() {
+        let folder = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join(CDC_FILE);
+
+        let mut contents = String::new();
+        for sequence in 1..=10_000 {
+            let record = CdcRecord {
+                sequence,
+                timestamp: 0,
+                table: "users".to_string(),
+                op: ChangeKind::Insert,
+                before: None,
+                after: Some(json!({ "user_name": "andres" })),
+            };
+            contents.push_str(&serde_json::to_string(&record).unwrap());
+            contents.push('\n');
+            if sequence == 5_000 {
+                contents.push_str("not a record\n");
+            }
+        }
+        // The process stopped while writing the last change
+        contents.push_str("{\"sequence\":10001");
+        std::fs::write(&path, contents).unwrap();
+
+        let sequences = |after: u64, limit: Option<usize>| -> Vec<u64> {
+            read_records(&path, None, after, limit)
+                .unwrap()
+                .iter()
+                .map(|r| r.sequence)
+                .collect()
+        };
+        assert_eq!(sequences(0, Some(2)), vec![1, 2]);
+        assert_eq!(sequences(4_999, Some(2)), vec![5_000, 5_001]);
+        assert_eq!(sequences(7_000, Some(3)), vec![7_001, 7_002, 7_003]);
+        assert_eq!(sequences(9_998, None), vec![9_999, 10_000]);
+        assert!(sequences(10_000, None).is_empty());
+
+        let file = File::open(&path).unwrap();
+        assert_eq!(last_record(&file, None).unwrap().unwrap().sequence, 10_000);
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
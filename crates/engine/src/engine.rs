@@ -1,16 +1,37 @@
+use crate::access::{AccessControl, Principal, Privilege, SYSTEM_DATABASE};
+use crate::backup::{backup_database, restore_database};
 use crate::engine_db::EngineDb;
+use crate::export::{export_rows, ExportFormat};
+use crate::import::{import_rows, ImportReport};
+use crate::information_schema::{InformationSchema, INFORMATION_SCHEMA};
+use crate::snapshot::{snapshot_database, SnapshotManifest};
+use crate::tasks::TaskRegistry;
 use crate::utils::fs::is_js_or_ts;
-use anyhow::bail;
+use crate::views::ViewDefinition;
+use anyhow::{anyhow, bail};
 use deno_core::{ModuleId, ModuleSpecifier};
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::shard::shards::data_shard::config::TableStorage;
 use schemajs_dirs::create_scheme_js_folder;
 use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::stats::DatabaseStats;
+use schemajs_query::ops::query_ops::QueryOps;
 use std::future::Future;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use walkdir::WalkDir;
 
 pub struct SchemeJsEngine {
-    pub databases: Vec<EngineDb>,
+    // Databases are shared with the ops, which can add new ones while the engine is in use (see `create_database`).
+    pub databases: RwLock<Vec<Arc<EngineDb>>>,
     pub data_path_dir: Option<PathBuf>,
+    // Users and roles every operation is checked against, every operation is allowed when `None` (see `authorize`).
+    pub access: Option<Arc<AccessControl>>,
+    // Status of the background tasks run against the engine, see `TaskRegistry`.
+    pub tasks: Arc<TaskRegistry>,
+    // System tables describing the schemas of the databases, refreshed as they are looked up (see `find_by_name_ref`).
+    pub information_schema: Option<Arc<InformationSchema>>,
 }
 
 impl SchemeJsEngine {
@@ -18,14 +39,66 @@ impl SchemeJsEngine {
         create_scheme_js_folder(data_path.clone());
 
         Self {
-            databases: vec![],
+            databases: RwLock::new(vec![]),
             data_path_dir: data_path,
+            access: None,
+            tasks: Arc::new(TaskRegistry::default()),
+            information_schema: None,
         }
     }
 
+    /// Describes the databases of the engine in the tables of the `INFORMATION_SCHEMA`, adding it if needed.
+    pub fn enable_information_schema(&mut self) -> Result<Arc<InformationSchema>, QueryError> {
+        if self
+            .find_by_name_ref(INFORMATION_SCHEMA.to_string())
+            .is_none()
+        {
+            self.add_database(INFORMATION_SCHEMA, None);
+        }
+
+        let db = self
+            .find_by_name_ref(INFORMATION_SCHEMA.to_string())
+            .unwrap();
+        let information_schema = Arc::new(InformationSchema::load(db)?);
+        self.information_schema = Some(information_schema.clone());
+        Ok(information_schema)
+    }
+
+    /// Checks every operation made through the ops and the remote interfaces against the users and roles
+    /// of the `SYSTEM_DATABASE`, adding it if needed.
+    pub fn enable_access_control(&mut self) -> Result<Arc<AccessControl>, QueryError> {
+        if self.find_by_name_ref(SYSTEM_DATABASE.to_string()).is_none() {
+            self.add_database(SYSTEM_DATABASE, None);
+        }
+
+        let db = self.find_by_name_ref(SYSTEM_DATABASE.to_string()).unwrap();
+        let access = Arc::new(AccessControl::load(db)?);
+        self.access = Some(access.clone());
+        Ok(access)
+    }
+
+    /// Checks that `principal` holds `privilege` on `table` of `db_name`, or on the whole database when `table`
+    /// is `None`. Every operation is allowed until `enable_access_control` is called.
+    pub fn authorize(
+        &self,
+        principal: &Principal,
+        db_name: &str,
+        table: Option<&str>,
+        privilege: Privilege,
+    ) -> Result<(), QueryError> {
+        match &self.access {
+            Some(access) => access.check(principal, db_name, table, privilege),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds the database at `path`, encrypting its files with `encryption` if given and limiting its size
+    /// to `storage_quota` bytes if given, and returns its name along with the modules of its tables.
     pub fn load_database_schema(
         &mut self,
         path: &PathBuf,
+        encryption: Option<EncryptionKey>,
+        storage_quota: Option<u64>,
     ) -> anyhow::Result<(String, Vec<ModuleSpecifier>)> {
         if !path.exists() {
             bail!(
@@ -35,9 +108,10 @@ impl SchemeJsEngine {
         }
 
         let schema_name = path.file_name().unwrap().to_str().unwrap();
+        tracing::info!(database = schema_name, path = %path.display(), "Loading database schema");
 
         {
-            self.add_database(schema_name);
+            self.add_database_with_quota(schema_name, encryption, storage_quota);
         }
 
         let table_path = path.join("tables").canonicalize()?;
@@ -52,36 +126,238 @@ impl SchemeJsEngine {
             }
         }
 
+        tracing::info!(
+            database = schema_name,
+            tables = table_specifiers.len(),
+            "Loaded database schema"
+        );
         Ok((schema_name.to_string(), table_specifiers))
     }
 
-    pub fn register_tables(&mut self, schema_name: &str, loaded_tables: Vec<Table>) {
-        let mut db = self.find_by_name(schema_name.to_string()).unwrap();
-        for table in loaded_tables {
-            db.add_table(table);
+    /// Registers the tables of the database `schema_name`, along with the storage settings of their main shard.
+    pub fn register_tables(
+        &self,
+        schema_name: &str,
+        loaded_tables: Vec<(Table, TableStorage)>,
+    ) -> Result<(), QueryError> {
+        let db = self.find_by_name_ref(schema_name.to_string()).unwrap();
+        for (table, storage) in loaded_tables {
+            db.add_table(table, storage)?;
+        }
+        Ok(())
+    }
+
+    /// Finds the database `name`. The tables of the `INFORMATION_SCHEMA` are brought up to date with the schemas
+    /// of the databases before it is returned, when enabled.
+    pub fn find_by_name_ref(&self, name: String) -> Option<Arc<EngineDb>> {
+        if name == INFORMATION_SCHEMA {
+            if let Some(information_schema) = &self.information_schema {
+                if let Err(e) = information_schema.refresh(&self.databases()) {
+                    tracing::warn!(error = %e, "Failed to refresh the information schema");
+                }
+                return Some(information_schema.db());
+            }
         }
+
+        self.databases
+            .read()
+            .unwrap()
+            .iter()
+            .find(|i| i.name == name)
+            .cloned()
+    }
+
+    /// Every database of the engine, at the time of the call.
+    pub fn databases(&self) -> Vec<Arc<EngineDb>> {
+        self.databases.read().unwrap().clone()
+    }
+
+    /// Row counts, file sizes and backlog of the tables of every database, see `SingleQueryManager::stats`.
+    pub fn stats(&self) -> Vec<DatabaseStats> {
+        self.databases()
+            .iter()
+            .map(|db| db.query_manager.stats())
+            .collect()
+    }
+
+    /// Flushes the rows buffered in temporary shards of every database and waits until their files are on disk,
+    /// see `SingleQueryManager::shutdown`. Background tasks writing to the databases must be stopped beforehand.
+    pub fn shutdown(&self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+
+        for db in self.databases() {
+            let synced = db
+                .query_manager
+                .shutdown()
+                .map_err(|e| anyhow!("Failed to shut down '{}': {}", db.name, e));
+            if result.is_ok() {
+                result = synced;
+            }
+        }
+
+        result
+    }
+
+    /// Archives the database `db_name` into `dest` while it keeps serving queries, see `backup_database`.
+    pub fn backup(&self, db_name: &str, dest: &Path) -> anyhow::Result<()> {
+        let db = self
+            .find_by_name_ref(db_name.to_string())
+            .ok_or_else(|| anyhow!("Unknown database '{}'", db_name))?;
+
+        backup_database(&db, dest)
+    }
+
+    /// Restores the database archived in `src` by `backup`, returning its name. See `restore_database`.
+    pub fn restore(&self, src: &Path) -> anyhow::Result<String> {
+        restore_database(self, src)
+    }
+
+    /// Writes the rows of `table_name` in the database `db_name` matched by `ops` into the file `dest`,
+    /// in `format`, returning how many rows were written. See `export_rows`.
+    pub fn export(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        ops: &QueryOps,
+        format: ExportFormat,
+        dest: &Path,
+    ) -> anyhow::Result<usize> {
+        let db = self
+            .find_by_name_ref(db_name.to_string())
+            .ok_or_else(|| anyhow!("Unknown database '{}'", db_name))?;
+
+        export_rows(&db, table_name, ops, format, dest)
+    }
+
+    /// Loads the rows of the file `src`, in `format`, into `table_name` in the database `db_name`.
+    /// See `import_rows`.
+    pub fn import(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        format: ExportFormat,
+        src: &Path,
+    ) -> anyhow::Result<ImportReport> {
+        let db = self
+            .find_by_name_ref(db_name.to_string())
+            .ok_or_else(|| anyhow!("Unknown database '{}'", db_name))?;
+
+        import_rows(&db, table_name, format, src)
     }
 
-    pub fn find_by_name(&mut self, name: String) -> Option<&mut EngineDb> {
-        self.databases.iter_mut().find(|i| i.name == name)
+    /// Takes a point-in-time snapshot of the database `db_name` into the folder `dest` while it keeps serving queries,
+    /// see `snapshot_database`. Snapshots are opened read-only through `snapshot::open_snapshot`.
+    pub fn snapshot(&self, db_name: &str, dest: &Path) -> anyhow::Result<SnapshotManifest> {
+        let db = self
+            .find_by_name_ref(db_name.to_string())
+            .ok_or_else(|| anyhow!("Unknown database '{}'", db_name))?;
+
+        snapshot_database(&db, dest)
     }
 
-    pub fn find_by_name_ref(&self, name: String) -> Option<&EngineDb> {
-        self.databases.iter().find(|i| i.name == name)
+    pub fn add_database(&mut self, name: &str, encryption: Option<EncryptionKey>) {
+        self.add_database_with_quota(name, encryption, None)
     }
 
-    pub fn add_database(&mut self, name: &str) {
+    /// Adds the database `name` like `add_database`, making inserts fail once it uses more than `storage_quota` bytes.
+    pub fn add_database_with_quota(
+        &mut self,
+        name: &str,
+        encryption: Option<EncryptionKey>,
+        storage_quota: Option<u64>,
+    ) {
         self.databases
-            .push(EngineDb::new(self.data_path_dir.clone(), name))
+            .get_mut()
+            .unwrap()
+            .push(Arc::new(EngineDb::new(
+                self.data_path_dir.clone(),
+                name,
+                encryption,
+                storage_quota,
+            )))
+    }
+
+    /// Adds the database `name` while the engine is in use (e.g. from JS through `SchemeJS.createDatabase`),
+    /// failing if it already exists. Its tables are created through `EngineDb::add_table`.
+    ///
+    /// Databases created this way are not part of the workspace, they are only loaded again on startup
+    /// if they are added to `SchemeJS.toml`.
+    pub fn create_database(&self, name: &str) -> anyhow::Result<Arc<EngineDb>> {
+        validate_name("database", name)?;
+
+        let mut databases = self.databases.write().unwrap();
+        if databases.iter().any(|db| db.name == name) {
+            bail!("Database '{}' already exists", name);
+        }
+
+        let db = Arc::new(EngineDb::new(self.data_path_dir.clone(), name, None, None));
+        databases.push(db.clone());
+        Ok(db)
+    }
+
+    /// Adds `table` to the database `db_name` while the engine is in use (e.g. from JS through
+    /// `SchemeJS.createTable`), failing if the database doesn't exist or already holds a table with its name,
+    /// or if the table uses an unknown serializer or indexes an unknown column.
+    pub fn create_table(&self, db_name: &str, mut table: Table) -> anyhow::Result<()> {
+        validate_name("table", &table.name)?;
+
+        let db = self
+            .find_by_name_ref(db_name.to_string())
+            .ok_or_else(|| anyhow!("Unknown database '{}'", db_name))?;
+        if db.query_manager.tables.contains_key(&table.name) {
+            bail!("Table '{}' already exists in '{}'", table.name, db_name);
+        }
+
+        table.init();
+        db.add_table(table, TableStorage::default())?;
+        Ok(())
+    }
+
+    /// Defines the materialized view `definition.name` in the database `db_name` (see `MaterializedView`) and
+    /// computes its rows, replacing the view of the same name if any. Fails if the database doesn't exist or
+    /// already holds a table with its name.
+    pub fn create_view(&self, db_name: &str, definition: ViewDefinition) -> anyhow::Result<()> {
+        let db = self
+            .find_by_name_ref(db_name.to_string())
+            .ok_or_else(|| anyhow!("Unknown database '{}'", db_name))?;
+        if db.views.get(&definition.name).is_none()
+            && db.query_manager.tables.contains_key(&definition.name)
+        {
+            bail!(
+                "Table '{}' already exists in '{}'",
+                definition.name,
+                db_name
+            );
+        }
+
+        db.views.create(definition)?;
+        Ok(())
     }
 }
 
+/// Checks that `name` can name a database or a table (`kind`) created from JS. Names are used as folder names, so
+/// they can't be empty, hold path separators or `..`, nor start with `.` like the files the engine keeps next to them.
+fn validate_name(kind: &str, name: &str) -> anyhow::Result<()> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains("..")
+        || name.contains(['/', '\\', '\0'])
+    {
+        bail!("Invalid {} name '{}'", kind, name);
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use crate::engine::SchemeJsEngine;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
     use schemajs_data::shard::Shard;
+    use schemajs_index::index_type::IndexType;
     use schemajs_primitives::column::types::{DataTypes, DataValue};
     use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
     use schemajs_primitives::table::Table;
     use schemajs_query::row::Row;
     use schemajs_query::row_json::{RowData, RowJson};
@@ -89,6 +365,7 @@ mod test {
     use std::collections::HashMap;
     use std::sync::{Arc, RwLock};
     use std::thread;
+    use uuid::Uuid;
 
     #[flaky_test::flaky_test]
     pub fn test_db_engine() {
@@ -97,7 +374,7 @@ mod test {
         // Add database
         {
             let mut writer = db_engine.write().unwrap();
-            writer.add_database("rust-test-random");
+            writer.add_database("rust-test-random", None);
         } // Release the write lock
 
         {
@@ -119,8 +396,11 @@ mod test {
                         data_type: DataTypes::String,
                         default_value: None,
                         required: false,
+                        nullable: true,
                         comment: None,
                         primary_key: false,
+                        check: None,
+                        collation: Default::default(),
                     },
                 );
 
@@ -129,12 +409,17 @@ mod test {
                     columns: cols,
                     indexes: vec![],
                     primary_key: "".to_string(),
+                    timestamps: false,
+                    ttl: None,
+                    serializer: None,
                     metadata: Default::default(),
                 };
 
                 let mut writer = db_engine.write().unwrap();
-                let mut db = writer.find_by_name("rust-test-random".to_string()).unwrap();
-                db.add_table(table);
+                let db = writer
+                    .find_by_name_ref("rust-test-random".to_string())
+                    .unwrap();
+                db.add_table(table, TableStorage::default()).unwrap();
             }
         }
 
@@ -184,7 +469,9 @@ mod test {
         // Assuming `temp_shards` is part of `EngineTable` and is a `RwLock<HashMap<String, Shard>>`
         {
             let mut reader = db_engine.write().unwrap();
-            let mut db = reader.find_by_name("rust-test-random".to_string()).unwrap();
+            let db = reader
+                .find_by_name_ref("rust-test-random".to_string())
+                .unwrap();
             let tbl = db.query_manager.tables.get("users").unwrap();
             tbl.temps.reconcile_all();
 
@@ -204,4 +491,53 @@ mod test {
             );
         }
     }
+
+    #[test]
+    pub fn test_engine_create_validation() {
+        let data_path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let engine = SchemeJsEngine::new(Some(data_path.clone()));
+
+        for name in ["", "..", "../escape", "a/b", "a\\b", ".hidden"] {
+            assert!(engine.create_database(name).is_err(), "{:?}", name);
+        }
+        assert!(!data_path.join("escape").exists());
+
+        let db = engine.create_database("analytics").unwrap();
+        let users = || Table::new("users").add_column(Column::new("name", DataTypes::String));
+
+        let invalid = engine.create_table("analytics", Table::new("../users"));
+        assert!(invalid
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid table name"));
+
+        let invalid = engine.create_table("analytics", users().set_serializer("unknown"));
+        assert!(invalid
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown serializer"));
+
+        let invalid = engine.create_table(
+            "analytics",
+            users().add_index(Index {
+                name: "age_indx".to_string(),
+                members: vec!["age".to_string()],
+                index_type: IndexType::BTree,
+                unique: false,
+            }),
+        );
+        assert!(invalid
+            .unwrap_err()
+            .to_string()
+            .contains("unknown column 'age'"));
+
+        // Invalid tables are rejected before anything is written
+        assert!(db.query_manager.tables.get("users").is_none());
+        assert!(!db.db_folder.join("users").exists());
+
+        engine.create_table("analytics", users()).unwrap();
+        assert!(db.query_manager.tables.get("users").is_some());
+
+        std::fs::remove_dir_all(data_path).unwrap();
+    }
 }
@@ -1,5 +1,11 @@
+use crate::views::MaterializedViews;
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::shard::shards::data_shard::config::TableStorage;
 use schemajs_dirs::create_scheme_js_db;
 use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::integrity::IntegrityReport;
+use schemajs_query::managers::single::quota::StorageQuota;
 use schemajs_query::managers::single::SingleQueryManager;
 use schemajs_query::row_json::RowJson;
 use std::path::PathBuf;
@@ -10,20 +16,53 @@ pub struct EngineDb {
     pub db_folder: PathBuf,
     pub query_manager: Arc<SingleQueryManager<RowJson>>,
     pub name: String,
+    pub views: MaterializedViews,
 }
 
 impl EngineDb {
-    pub fn new(base_path: Option<PathBuf>, name: &str) -> Self {
+    /// Opens the database `name`, encrypting its files with `encryption` if given.
+    /// With a `storage_quota` (in bytes), inserts fail once the folder of the database grows past it.
+    pub fn new(
+        base_path: Option<PathBuf>,
+        name: &str,
+        encryption: Option<EncryptionKey>,
+        storage_quota: Option<u64>,
+    ) -> Self {
         let db_folder = create_scheme_js_db(base_path, name);
+        let quota = storage_quota.map(|limit| StorageQuota::new(limit, &db_folder).unwrap());
+
+        let query_manager = Arc::new(
+            SingleQueryManager::new(name.to_string())
+                .set_encryption(encryption)
+                .set_quota(quota),
+        );
 
         EngineDb {
             name: name.to_string(),
             db_folder,
-            query_manager: Arc::new(SingleQueryManager::new(name.to_string())),
+            views: MaterializedViews::new(query_manager.clone()),
+            query_manager,
         }
     }
 
-    pub fn add_table(&self, table: Table) {
-        self.query_manager.register_table(table);
+    pub fn add_table(&self, table: Table, storage: TableStorage) -> Result<(), QueryError> {
+        self.query_manager
+            .register_table_with_storage(table, storage)
+    }
+
+    pub fn reindex(&self, table_name: &str, index_name: &str) -> Result<(), QueryError> {
+        self.query_manager.reindex(table_name, index_name)
+    }
+
+    pub fn verify(&self, table_name: &str) -> Result<Vec<u64>, QueryError> {
+        self.query_manager.verify(table_name)
+    }
+
+    pub fn check_integrity(
+        &self,
+        table_name: &str,
+        repair: bool,
+    ) -> Result<IntegrityReport, QueryError> {
+        self.query_manager.check_integrity(table_name, repair)
     }
 }
@@ -0,0 +1,284 @@
+use crate::engine_db::EngineDb;
+use anyhow::Result;
+use schemajs_primitives::column::types::{timestamp_to_iso, DataTypes, DataValue};
+use schemajs_primitives::column::Column;
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_query::row::Row;
+use schemajs_query::search::search_opts::SearchOpts;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Amount of rows read from the table at once while exporting.
+pub const EXPORT_PAGE_SIZE: usize = 1000;
+
+/// Format of the files written by `export_rows`.
+///
+/// - `Jsonl`: One JSON object per line (JSON Lines), holding every column of the row.
+/// - `Csv`: A header line with the name of the columns followed by one line per row (RFC 4180).
+///   Arrays and objects are written as JSON, nulls as empty fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+}
+
+/// JSON form of `value` in an export. Values are written as `DataValue::to_json` does,
+/// except for timestamps which are written as ISO-8601 strings rather than epoch millis.
+pub fn export_value(data_type: &DataTypes, value: &DataValue) -> Value {
+    match (data_type, value) {
+        (DataTypes::Timestamp, DataValue::Number(millis)) => millis
+            .as_i64()
+            .and_then(timestamp_to_iso)
+            .map_or_else(|| value.to_json(), Value::String),
+        (DataTypes::Array(inner), DataValue::Array(items)) => {
+            Value::Array(items.iter().map(|item| export_value(inner, item)).collect())
+        }
+        (DataTypes::Object(columns), DataValue::Object(fields)) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| {
+                    let exported = match columns.iter().find(|column| column.name == *name) {
+                        Some(column) => export_value(&column.data_type, field),
+                        None => field.to_json(),
+                    };
+                    (name.clone(), exported)
+                })
+                .collect(),
+        ),
+        _ => value.to_json(),
+    }
+}
+
+/// Field of a CSV line holding `value`, quoted when it contains a separator, a quote or a line break.
+pub fn csv_field(value: &Value) -> String {
+    let field = match value {
+        Value::Null => return String::new(),
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    };
+
+    if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Writes the rows of `table_name` matched by `ops` into the file `dest`, in `format`.
+///
+/// Rows are read page by page (see `EXPORT_PAGE_SIZE`) and written as they are read, so exporting a large table
+/// doesn't hold it in memory. Every column of the table is exported, in alphabetical order. Rows still in
+/// temporary shards are not exported until they are reconciled, just like they are not matched by searches.
+///
+/// # Returns:
+/// - `Result<usize>`: The amount of exported rows.
+pub fn export_rows(
+    db: &EngineDb,
+    table_name: &str,
+    ops: &QueryOps,
+    format: ExportFormat,
+    dest: &Path,
+) -> Result<usize> {
+    let mut columns: Vec<Column> = db
+        .query_manager
+        .tables
+        .get(table_name)
+        .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?
+        .table
+        .columns
+        .values()
+        .cloned()
+        .collect();
+    columns.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut writer = BufWriter::new(File::create(dest)?);
+
+    if format == ExportFormat::Csv {
+        let header: Vec<String> = columns
+            .iter()
+            .map(|column| csv_field(&Value::String(column.name.clone())))
+            .collect();
+        writeln!(writer, "{}", header.join(","))?;
+    }
+
+    let search_manager = db.query_manager.search_manager();
+    let mut opts = SearchOpts::new().limit(EXPORT_PAGE_SIZE);
+    let mut exported = 0;
+
+    loop {
+        let page = search_manager.search_page(table_name.to_string(), ops, &opts)?;
+
+        for row in page.rows.iter() {
+            let values = columns.iter().map(|column| {
+                let value = row
+                    .get_value(column)
+                    .map_or(Value::Null, |value| export_value(&column.data_type, &value));
+                (column.name.clone(), value)
+            });
+
+            match format {
+                ExportFormat::Jsonl => {
+                    let object: Map<String, Value> = values.collect();
+                    serde_json::to_writer(&mut writer, &object)?;
+                    writeln!(writer)?;
+                }
+                ExportFormat::Csv => {
+                    let fields: Vec<String> = values.map(|(_, value)| csv_field(&value)).collect();
+                    writeln!(writer, "{}", fields.join(","))?;
+                }
+            }
+        }
+
+        exported += page.rows.len();
+
+        match page.cursor {
+            Some(cursor) => opts = opts.after(&cursor),
+            None => break,
+        }
+    }
+
+    writer.flush()?;
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::SchemeJsEngine;
+    use crate::export::{csv_field, ExportFormat};
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+    use schemajs_query::row_json::{RowData, RowJson};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    #[test]
+    pub fn test_csv_field() {
+        assert_eq!(csv_field(&json!("andres")), "andres");
+        assert_eq!(csv_field(&json!("a, b")), "\"a, b\"");
+        assert_eq!(csv_field(&json!("say \"hi\"")), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(&json!(null)), "");
+        assert_eq!(csv_field(&json!(1.5)), "1.5");
+        assert_eq!(csv_field(&json!(["a", "b"])), "\"[\"\"a\"\",\"\"b\"\"]\"");
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_export() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let engine = engine;
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users")
+                .add_column(Column::new("user_name", DataTypes::String))
+                .add_column(Column::new("user_age", DataTypes::Number))
+                .add_column(Column::new("created", DataTypes::Timestamp))
+                .add_column(Column::new(
+                    "tags",
+                    DataTypes::Array(Box::new(DataTypes::String)),
+                )),
+            TableStorage::default(),
+        )
+        .unwrap();
+
+        let uids: Vec<String> = (0..3).map(|_| Uuid::new_v4().to_string()).collect();
+        for (i, (name, age)) in [("andres", 25), ("carlos, jr", 30), ("luis", 40)]
+            .into_iter()
+            .enumerate()
+        {
+            db.query_manager
+                .insert(RowJson::from(RowData {
+                    table: "users".to_string(),
+                    value: json!({
+                        "_uid": uids[i],
+                        "user_name": name,
+                        "user_age": age,
+                        "created": "2024-05-01",
+                        "tags": ["a", "b"]
+                    }),
+                }))
+                .unwrap();
+        }
+        db.query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        let dest = std::env::temp_dir().join(format!("{}.jsonl", Uuid::new_v4()));
+        let exported = engine
+            .export(
+                &db_name,
+                "users",
+                &QueryOps::And(vec![]),
+                ExportFormat::Jsonl,
+                &dest,
+            )
+            .unwrap();
+        assert_eq!(exported, 3);
+
+        let lines: Vec<Value> = std::fs::read_to_string(&dest)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines[0],
+            json!({
+                "_uid": uids[0],
+                "created": "2024-05-01T00:00:00.000Z",
+                "tags": ["a", "b"],
+                "user_age": 25,
+                "user_name": "andres"
+            })
+        );
+        std::fs::remove_file(&dest).unwrap();
+
+        // Query results are exported the same way
+        let dest = std::env::temp_dir().join(format!("{}.csv", Uuid::new_v4()));
+        let older = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">".to_string(),
+            value: DataValue::Number(26.into()),
+        });
+        let exported = engine
+            .export(&db_name, "users", &older, ExportFormat::Csv, &dest)
+            .unwrap();
+        assert_eq!(exported, 2);
+
+        let csv = std::fs::read_to_string(&dest).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "_uid,created,tags,user_age,user_name");
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},2024-05-01T00:00:00.000Z,\"[\"\"a\"\",\"\"b\"\"]\",30,\"carlos, jr\"",
+                uids[1]
+            )
+        );
+        assert_eq!(lines.len(), 3);
+        std::fs::remove_file(&dest).unwrap();
+
+        assert!(engine
+            .export(
+                &db_name,
+                "unknown",
+                &QueryOps::And(vec![]),
+                ExportFormat::Csv,
+                &dest
+            )
+            .is_err());
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
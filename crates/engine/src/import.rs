@@ -0,0 +1,463 @@
+use crate::engine_db::EngineDb;
+use crate::export::ExportFormat;
+use anyhow::{bail, Result};
+use schemajs_primitives::column::types::DataTypes;
+use schemajs_primitives::column::Column;
+use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::row_json::{RowData, RowJson};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Amount of rows loaded into the table at once while importing.
+pub const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// Line of an imported file that couldn't be loaded, and why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportLineError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Outcome of `import_rows`.
+///
+/// # Fields:
+/// - `imported`: Amount of rows loaded into the table.
+/// - `errors`: Lines left out, in the order they appear in the file. Lines are numbered from 1.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub errors: Vec<ImportLineError>,
+}
+
+/// Records of a CSV file (RFC 4180) along with the line they start on.
+/// Quoted fields can hold separators, escaped quotes (`""`) and line breaks.
+pub struct CsvRecords<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> CsvRecords<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CsvRecords<R> {
+    type Item = std::io::Result<(usize, Vec<String>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut start = None;
+
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e)),
+                // An unterminated quoted field ends with the file
+                None => {
+                    return start.map(|start| {
+                        fields.push(field);
+                        Ok((start, fields))
+                    })
+                }
+            };
+            self.line += 1;
+
+            if start.is_none() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                start = Some(self.line);
+            }
+
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (true, '"') => quoted = false,
+                    (false, '"') => quoted = true,
+                    (false, ',') => fields.push(std::mem::take(&mut field)),
+                    (_, c) => field.push(c),
+                }
+            }
+
+            if quoted {
+                field.push('\n');
+                continue;
+            }
+
+            fields.push(field);
+            return start.map(|start| Ok((start, fields)));
+        }
+    }
+}
+
+/// Reads the CSV `field` of `column` into the JSON value it is inserted as, the way `export_rows` writes them:
+/// numbers and booleans as text, arrays, objects and points as JSON. Empty fields are missing values.
+pub fn csv_value(column: &Column, field: &str) -> Result<Option<Value>, QueryError> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+
+    let invalid = || QueryError::InvalidColumnValue(column.name.clone());
+
+    let value = match column.data_type {
+        DataTypes::Null => Value::Null,
+        DataTypes::Uuid | DataTypes::String | DataTypes::Enum(_) => {
+            Value::String(field.to_string())
+        }
+        DataTypes::Boolean => match field.to_ascii_lowercase().as_str() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => return Err(invalid()),
+        },
+        DataTypes::Number | DataTypes::Int | DataTypes::Uint | DataTypes::Float => {
+            Value::Number(serde_json::from_str(field).map_err(|_| invalid())?)
+        }
+        // Epoch millis or an ISO-8601 string
+        DataTypes::Timestamp => match field.parse::<i64>() {
+            Ok(millis) => Value::from(millis),
+            Err(_) => Value::String(field.to_string()),
+        },
+        DataTypes::Array(_) | DataTypes::Object(_) | DataTypes::Point => {
+            serde_json::from_str(field).map_err(|_| invalid())?
+        }
+    };
+
+    Ok(Some(value))
+}
+
+/// Parses `line` of a JSON Lines file, checking that every key of the object is a column of `table`.
+fn jsonl_row(table: &Table, line: &str) -> Result<Map<String, Value>, String> {
+    let value = match serde_json::from_str(line) {
+        Ok(Value::Object(value)) => value,
+        Ok(_) => return Err("Expected a JSON object".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    match value.keys().find(|key| table.get_column(key).is_none()) {
+        Some(key) => Err(QueryError::InvalidColumn(key.clone()).to_string()),
+        None => Ok(value),
+    }
+}
+
+/// Rows read from a file that are waiting to be loaded, along with their line.
+struct ImportBatch<'a> {
+    db: &'a EngineDb,
+    table_name: &'a str,
+    rows: Vec<(usize, RowJson)>,
+    report: ImportReport,
+}
+
+impl<'a> ImportBatch<'a> {
+    fn push(&mut self, line: usize, mut row: Map<String, Value>) -> Result<()> {
+        let missing_uid = row
+            .get(Table::get_internal_uid().name.as_str())
+            .map_or(true, |uid| uid.is_null());
+        if missing_uid {
+            row.insert(
+                Table::get_internal_uid().name,
+                Value::String(Uuid::new_v4().to_string()),
+            );
+        }
+
+        self.rows.push((
+            line,
+            RowJson::from(RowData {
+                table: self.table_name.to_string(),
+                value: Value::Object(row),
+            }),
+        ));
+
+        match self.rows.len() >= IMPORT_BATCH_SIZE {
+            true => self.flush(),
+            false => Ok(()),
+        }
+    }
+
+    fn error(&mut self, line: usize, message: String) {
+        self.report.errors.push(ImportLineError { line, message });
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let (lines, rows): (Vec<usize>, Vec<RowJson>) =
+            std::mem::take(&mut self.rows).into_iter().unzip();
+        let results = self.db.query_manager.load_rows(self.table_name, rows)?;
+
+        for (line, result) in lines.into_iter().zip(results) {
+            match result {
+                Ok(_) => self.report.imported += 1,
+                Err(e) => self.error(line, e.to_string()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads the rows of the file `src`, in `format`, into `table_name`.
+///
+/// Values are checked against the columns of the table: CSV headers and JSON keys must be columns of the table,
+/// and values must fit their type, like for any insert. Rows without a `_uid` get a new one.
+/// Rows are loaded in batches (see `IMPORT_BATCH_SIZE`) straight into the main shard (see `SingleQueryManager::load_rows`).
+///
+/// Invalid lines don't stop the import, they are reported at the end along with the reason they were left out.
+/// The import fails as a whole when the file can't be read, the table is unknown or the CSV header holds unknown columns.
+pub fn import_rows(
+    db: &EngineDb,
+    table_name: &str,
+    format: ExportFormat,
+    src: &Path,
+) -> Result<ImportReport> {
+    let table = db
+        .query_manager
+        .tables
+        .get(table_name)
+        .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?
+        .table
+        .clone();
+
+    let reader = BufReader::new(File::open(src)?);
+    let mut batch = ImportBatch {
+        db,
+        table_name,
+        rows: vec![],
+        report: ImportReport::default(),
+    };
+
+    match format {
+        ExportFormat::Jsonl => {
+            for (position, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match jsonl_row(&table, &line) {
+                    Ok(row) => batch.push(position + 1, row)?,
+                    Err(message) => batch.error(position + 1, message),
+                }
+            }
+        }
+        ExportFormat::Csv => {
+            let mut records = CsvRecords::new(reader);
+
+            let header = match records.next() {
+                Some(header) => header?.1,
+                None => return Ok(batch.report),
+            };
+
+            let mut columns = vec![];
+            for name in header {
+                match table.get_column(&name) {
+                    Some(column) => columns.push(column.clone()),
+                    None => bail!(QueryError::InvalidColumn(name)),
+                }
+            }
+
+            for record in records {
+                let (line, fields) = record?;
+                if fields.len() != columns.len() {
+                    batch.error(
+                        line,
+                        format!("Expected {} fields, found {}", columns.len(), fields.len()),
+                    );
+                    continue;
+                }
+
+                let row: Result<Map<String, Value>, QueryError> = columns
+                    .iter()
+                    .zip(fields)
+                    .filter_map(|(column, field)| {
+                        csv_value(column, &field)
+                            .transpose()
+                            .map(|value| value.map(|value| (column.name.clone(), value)))
+                    })
+                    .collect();
+
+                match row {
+                    Ok(row) => batch.push(line, row)?,
+                    Err(e) => batch.error(line, e.to_string()),
+                }
+            }
+        }
+    }
+
+    batch.flush()?;
+    batch.report.errors.sort_by_key(|error| error.line);
+
+    Ok(batch.report)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::SchemeJsEngine;
+    use crate::export::ExportFormat;
+    use crate::import::CsvRecords;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_index::index_type::IndexType;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+    use uuid::Uuid;
+
+    #[test]
+    pub fn test_csv_records() {
+        let csv =
+            "name,bio\nandres,\"likes \"\"rust\"\", go\"\n\ncarlos,\"line 1\nline 2\"\nluis\n";
+        let records: Vec<(usize, Vec<String>)> = CsvRecords::new(csv.as_bytes())
+            .map(|record| record.unwrap())
+            .collect();
+
+        assert_eq!(
+            records,
+            vec![
+                (1, vec!["name".to_string(), "bio".to_string()]),
+                (
+                    2,
+                    vec!["andres".to_string(), "likes \"rust\", go".to_string()]
+                ),
+                (4, vec!["carlos".to_string(), "line 1\nline 2".to_string()]),
+                (6, vec!["luis".to_string()]),
+            ]
+        );
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_import() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let engine = engine;
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users")
+                .add_column(Column::new("user_name", DataTypes::String))
+                .add_column(Column::new("user_age", DataTypes::Number))
+                .add_column(Column::new("created", DataTypes::Timestamp))
+                .add_column(Column::new("active", DataTypes::Boolean))
+                .add_index(Index {
+                    name: "user_name_indx".to_string(),
+                    members: vec![String::from("user_name")],
+                    index_type: IndexType::Hash,
+                    unique: true,
+                }),
+            TableStorage::default(),
+        )
+        .unwrap();
+
+        let src = std::env::temp_dir().join(format!("{}.csv", Uuid::new_v4()));
+        std::fs::write(
+            &src,
+            "user_name,user_age,created,active\n\
+             andres,25,2024-05-01,true\n\
+             carlos,thirty,,false\n\
+             \"luis, jr\",40,,\n\
+             andres,50,,\n\
+             juan,20\n",
+        )
+        .unwrap();
+
+        let report = engine
+            .import(&db_name, "users", ExportFormat::Csv, &src)
+            .unwrap();
+        assert_eq!(report.imported, 2);
+        assert_eq!(
+            report
+                .errors
+                .iter()
+                .map(|error| error.line)
+                .collect::<Vec<usize>>(),
+            vec![3, 5, 6]
+        );
+        assert!(report.errors[0].message.contains("user_age"));
+        assert!(report.errors[1].message.contains("andres"));
+        std::fs::remove_file(&src).unwrap();
+
+        // Loaded rows skip the temporary shards and are searchable right away
+        let by_name = |name: &str| {
+            QueryOps::Condition(QueryVal {
+                key: "user_name".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(name.to_string()),
+            })
+        };
+        let search = db.query_manager.search_manager();
+        assert_eq!(
+            search
+                .search("users".to_string(), &by_name("luis, jr"))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let src = std::env::temp_dir().join(format!("{}.jsonl", Uuid::new_v4()));
+        std::fs::write(
+            &src,
+            "{\"user_name\": \"pedro\", \"created\": \"2024-05-01T00:00:00.000Z\"}\n\
+             {\"user_name\": \"maria\", \"unknown\": 1}\n\
+             not json\n\
+             \n\
+             {\"user_name\": \"ana\", \"user_age\": 30}\n",
+        )
+        .unwrap();
+
+        let report = engine
+            .import(&db_name, "users", ExportFormat::Jsonl, &src)
+            .unwrap();
+        assert_eq!(report.imported, 2);
+        assert_eq!(
+            report
+                .errors
+                .iter()
+                .map(|error| error.line)
+                .collect::<Vec<usize>>(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            search
+                .search("users".to_string(), &by_name("ana"))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            db.query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .data
+                .read()
+                .unwrap()
+                .len(),
+            4
+        );
+        std::fs::remove_file(&src).unwrap();
+
+        // Unknown columns in the header fail the whole import
+        let src = std::env::temp_dir().join(format!("{}.csv", Uuid::new_v4()));
+        std::fs::write(&src, "user_name,unknown\nandres,1\n").unwrap();
+        assert!(engine
+            .import(&db_name, "users", ExportFormat::Csv, &src)
+            .is_err());
+        std::fs::remove_file(&src).unwrap();
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+use crate::engine_db::EngineDb;
+use schemajs_data::shard::shards::data_shard::config::TableStorage;
+use schemajs_primitives::column::types::DataTypes;
+use schemajs_primitives::column::Column;
+use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_query::row_json::{RowData, RowJson};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Database describing the databases of the engine, see `InformationSchema`.
+pub const INFORMATION_SCHEMA: &str = "information_schema";
+
+/// Table of the `INFORMATION_SCHEMA` listing the databases.
+pub const DATABASES_TABLE: &str = "databases";
+
+/// Table of the `INFORMATION_SCHEMA` listing the tables of every database.
+pub const TABLES_TABLE: &str = "tables";
+
+/// Table of the `INFORMATION_SCHEMA` listing the columns of every table.
+pub const COLUMNS_TABLE: &str = "columns";
+
+/// Table of the `INFORMATION_SCHEMA` listing the indexes of every table.
+pub const INDEXES_TABLE: &str = "indexes";
+
+/// Definition of the tables of the `INFORMATION_SCHEMA`.
+fn schema_tables() -> Vec<Table> {
+    vec![
+        Table::new(DATABASES_TABLE)
+            .add_column(Column::new("name", DataTypes::String))
+            .add_column(Column::new("table_count", DataTypes::Number)),
+        Table::new(TABLES_TABLE)
+            .add_column(Column::new("database", DataTypes::String))
+            .add_column(Column::new("name", DataTypes::String))
+            .add_column(Column::new("primary_key", DataTypes::String))
+            .add_column(Column::new("timestamps", DataTypes::Boolean))
+            .add_column(Column::new("ttl_column", DataTypes::String))
+            .add_column(Column::new("ttl_ms", DataTypes::Number))
+            .add_column(Column::new("serializer", DataTypes::String)),
+        Table::new(COLUMNS_TABLE)
+            .add_column(Column::new("database", DataTypes::String))
+            .add_column(Column::new("table", DataTypes::String))
+            .add_column(Column::new("name", DataTypes::String))
+            .add_column(Column::new("data_type", DataTypes::String))
+            .add_column(Column::new("required", DataTypes::Boolean))
+            .add_column(Column::new("nullable", DataTypes::Boolean))
+            .add_column(Column::new("primary_key", DataTypes::Boolean))
+            .add_column(Column::new("default_value", DataTypes::String))
+            .add_column(Column::new("check", DataTypes::String))
+            .add_column(Column::new("collation", DataTypes::String))
+            .add_column(Column::new("comment", DataTypes::String)),
+        Table::new(INDEXES_TABLE)
+            .add_column(Column::new("database", DataTypes::String))
+            .add_column(Column::new("table", DataTypes::String))
+            .add_column(Column::new("name", DataTypes::String))
+            .add_column(Column::new("index_type", DataTypes::String))
+            .add_column(Column::new("unique", DataTypes::Boolean))
+            .add_column(Column::new("members", DataTypes::String)),
+    ]
+}
+
+/// Name of a type as listed in the `COLUMNS_TABLE`, e.g. `String` or `{"Array":"Number"}`.
+fn type_name<S: serde::Serialize>(data_type: &S) -> String {
+    match serde_json::to_value(data_type) {
+        Ok(Value::String(name)) => name,
+        Ok(value) => value.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Schemas of the databases of the engine as system tables (`databases`, `tables`, `columns` and `indexes`)
+/// of the `INFORMATION_SCHEMA`, so tooling can discover them through the usual search path (search, SQL, the
+/// remote interfaces) like any table.
+///
+/// The tables are rewritten from the databases of the engine when they are looked up (see
+/// `SchemeJsEngine::find_by_name_ref`), only the ones whose rows changed since the previous refresh.
+/// Rows written to them by anything else are dropped the next time the schemas they list change.
+///
+/// # Fields:
+/// - `db`: The `INFORMATION_SCHEMA`.
+/// - `rows`: Rows of every table as of the last refresh, without their `_uid`, by table name.
+#[derive(Debug)]
+pub struct InformationSchema {
+    db: Arc<EngineDb>,
+    rows: Mutex<HashMap<String, Vec<Value>>>,
+}
+
+impl InformationSchema {
+    /// Uses `db` as the information schema, registering its tables if needed. Rows left by a previous run
+    /// are replaced on the first refresh.
+    pub fn load(db: Arc<EngineDb>) -> Result<Self, QueryError> {
+        for table in schema_tables() {
+            if db.query_manager.tables.get(&table.name).is_none() {
+                db.add_table(table, TableStorage::default())?;
+            }
+        }
+
+        Ok(Self {
+            db,
+            rows: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn db(&self) -> Arc<EngineDb> {
+        self.db.clone()
+    }
+
+    /// Rows describing `databases`, by table of the information schema. Databases, tables and columns are listed
+    /// by name, indexes as declared.
+    pub fn describe(databases: &[Arc<EngineDb>]) -> HashMap<String, Vec<Value>> {
+        let mut rows: HashMap<String, Vec<Value>> = HashMap::new();
+
+        let mut databases: Vec<&Arc<EngineDb>> = databases.iter().collect();
+        databases.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for db in databases {
+            let mut tables: Vec<Arc<Table>> = db
+                .query_manager
+                .table_names
+                .read()
+                .unwrap()
+                .iter()
+                .filter_map(|table_name| {
+                    let table_shard = db.query_manager.tables.get(table_name)?;
+                    Some(table_shard.table.clone())
+                })
+                .collect();
+            tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+            rows.entry(DATABASES_TABLE.to_string())
+                .or_default()
+                .push(json!({ "name": db.name, "table_count": tables.len() }));
+
+            for table in tables {
+                rows.entry(TABLES_TABLE.to_string())
+                    .or_default()
+                    .push(json!({
+                        "database": db.name,
+                        "name": table.name,
+                        "primary_key": table.primary_key,
+                        "timestamps": table.timestamps,
+                        "ttl_column": table.ttl.as_ref().map(|ttl| ttl.column.clone()),
+                        "ttl_ms": table.ttl.as_ref().map(|ttl| ttl.retention_ms),
+                        "serializer": table.serializer,
+                    }));
+
+                let mut columns: Vec<&Column> = table.columns.values().collect();
+                columns.sort_by(|a, b| a.name.cmp(&b.name));
+                for column in columns {
+                    rows.entry(COLUMNS_TABLE.to_string())
+                        .or_default()
+                        .push(json!({
+                            "database": db.name,
+                            "table": table.name,
+                            "name": column.name,
+                            "data_type": type_name(&column.data_type),
+                            "required": column.required,
+                            "nullable": column.nullable,
+                            "primary_key": column.primary_key,
+                            "default_value": column.default_value,
+                            "check": column.check,
+                            "collation": type_name(&column.collation),
+                            "comment": column.comment,
+                        }));
+                }
+
+                for index in table.indexes.iter() {
+                    rows.entry(INDEXES_TABLE.to_string())
+                        .or_default()
+                        .push(json!({
+                            "database": db.name,
+                            "table": table.name,
+                            "name": index.name,
+                            "index_type": type_name(&index.index_type),
+                            "unique": index.unique,
+                            "members": index.members.join(","),
+                        }));
+                }
+            }
+        }
+
+        rows
+    }
+
+    /// Rewrites the tables of the information schema whose rows changed to describe `databases`.
+    pub fn refresh(&self, databases: &[Arc<EngineDb>]) -> Result<(), QueryError> {
+        let mut described = Self::describe(databases);
+        let mut rows = self.rows.lock().unwrap();
+
+        for table in schema_tables() {
+            let table_rows = described.remove(&table.name).unwrap_or_default();
+            if rows.get(&table.name) == Some(&table_rows) {
+                continue;
+            }
+
+            let query_manager = &self.db.query_manager;
+            query_manager.delete(table.name.clone(), &QueryOps::And(vec![]))?;
+            query_manager.insert_many(
+                table_rows
+                    .iter()
+                    .map(|row| {
+                        let mut value = row.clone();
+                        value["_uid"] = Value::String(Uuid::new_v4().to_string());
+                        RowJson::from(RowData {
+                            table: table.name.clone(),
+                            value,
+                        })
+                    })
+                    .collect(),
+            )?;
+            query_manager.compact_table(&table.name)?;
+
+            rows.insert(table.name.clone(), table_rows);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::SchemeJsEngine;
+    use crate::information_schema::{
+        InformationSchema, COLUMNS_TABLE, INDEXES_TABLE, TABLES_TABLE,
+    };
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_information_schema() {
+        // Stands for the information schema, which is shared by every engine using the default data folder
+        let schema_name = Uuid::new_v4().to_string();
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&schema_name, None);
+        engine.add_database(&db_name, None);
+
+        let schema_db = engine.find_by_name_ref(schema_name.clone()).unwrap();
+        let information_schema = Arc::new(InformationSchema::load(schema_db.clone()).unwrap());
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users")
+                .add_column(Column::new("user_name", DataTypes::String).set_required(true))
+                .add_column(Column::new("user_age", DataTypes::Number)),
+            TableStorage::default(),
+        )
+        .unwrap();
+
+        information_schema.refresh(&engine.databases()).unwrap();
+
+        let search = |table: &str, ops: &QueryOps| {
+            schema_db
+                .query_manager
+                .search_manager()
+                .search(table.to_string(), ops)
+                .unwrap()
+        };
+        let of_db = QueryOps::Condition(QueryVal {
+            key: "database".to_string(),
+            filter_type: "=".to_string(),
+            value: DataValue::String(db_name.clone()),
+        });
+
+        let tables = search(TABLES_TABLE, &of_db);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].value.value["name"], "users");
+        assert_eq!(tables[0].value.value["primary_key"], "_uid");
+
+        let columns = search(
+            COLUMNS_TABLE,
+            &QueryOps::And(vec![
+                of_db.clone(),
+                QueryOps::Condition(QueryVal {
+                    key: "name".to_string(),
+                    filter_type: "=".to_string(),
+                    value: DataValue::String("user_name".to_string()),
+                }),
+            ]),
+        );
+        assert_eq!(columns.len(), 1);
+        assert_eq!(columns[0].value.value["data_type"], "String");
+        assert_eq!(columns[0].value.value["required"], true);
+        assert_eq!(columns[0].value.value["collation"], "binary");
+        assert_eq!(search(COLUMNS_TABLE, &of_db).len(), 3);
+
+        let indexes = search(INDEXES_TABLE, &of_db);
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(indexes[0].value.value["members"], "_uid");
+
+        // Only tables whose rows changed are rewritten, a new table shows up on the next refresh
+        information_schema.refresh(&engine.databases()).unwrap();
+        assert_eq!(search(TABLES_TABLE, &of_db).len(), 1);
+        db.add_table(
+            Table::new("products").add_column(Column::new("product_name", DataTypes::String)),
+            TableStorage::default(),
+        )
+        .unwrap();
+        information_schema.refresh(&engine.databases()).unwrap();
+        let mut names: Vec<String> = search(TABLES_TABLE, &of_db)
+            .iter()
+            .map(|row| row.value.value["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["products", "users"]);
+        assert_eq!(search(COLUMNS_TABLE, &of_db).len(), 5);
+
+        std::fs::remove_dir_all(&schema_db.db_folder).unwrap();
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
@@ -1,8 +1,399 @@
+import { toQuery } from "ext:sjs_engine/src/js/query.ts";
+
 const core = globalThis.Deno.core;
+
+const tableHooks = new Map<string, { beforeInsert?: (row: any) => any, afterInsert?: (row: any) => any }>();
+
+/**
+ * Registers the hooks called around the inserts into `tableName`, replacing its previous ones (removing them without `hooks`).
+ * Hooks defined by table modules (`hooks` of the table) are registered when the runtime loads them.
+ */
+export const registerHooks = (dbName: string, tableName: string, hooks?: { beforeInsert?: (row: any) => any, afterInsert?: (row: any) => any }) => {
+    if (hooks) {
+        tableHooks.set(`${dbName}.${tableName}`, hooks);
+    } else {
+        tableHooks.delete(`${dbName}.${tableName}`);
+    }
+}
+
+const beforeInsert = async (dbName: string, tableName: string, data: any) => {
+    const hooks = tableHooks.get(`${dbName}.${tableName}`);
+    if (!hooks?.beforeInsert) {
+        return data;
+    }
+
+    const row = await hooks.beforeInsert(data);
+    if (row === false) {
+        throw new Error(`Row rejected by the beforeInsert hook of '${tableName}'`);
+    }
+    return row === undefined ? data : row;
+}
+
+const afterInsert = async (dbName: string, tableName: string, data: any, uid: string) => {
+    const hooks = tableHooks.get(`${dbName}.${tableName}`);
+    if (hooks?.afterInsert) {
+        await hooks.afterInsert({ ...data, _uid: uid });
+    }
+}
+
 export const insertRow = async (dbName: string, tableName: string, data: any) => {
-    return await core.ops.op_engine_insert_row(
+    const row = await beforeInsert(dbName, tableName, data);
+    const uid = await core.ops.op_engine_insert_row(
         dbName,
         tableName,
-        data
+        row
     );
-}
\ No newline at end of file
+    await afterInsert(dbName, tableName, row, uid);
+    return uid;
+}
+
+export const insertMany = async (dbName: string, tableName: string, data: any[]) => {
+    const rows = [];
+    for (const item of data) {
+        rows.push(await beforeInsert(dbName, tableName, item));
+    }
+
+    const uids = await core.ops.op_engine_insert_rows(
+        dbName,
+        tableName,
+        rows
+    );
+    for (let i = 0; i < rows.length; i++) {
+        await afterInsert(dbName, tableName, rows[i], uids[i]);
+    }
+    return uids;
+}
+
+export const upsertRow = async (dbName: string, tableName: string, data: any, indexName?: string) => {
+    return await core.ops.op_engine_upsert_row(
+        dbName,
+        tableName,
+        data,
+        indexName
+    );
+}
+
+/**
+ * Reads every row of `tableName` back and returns the position of the rows whose checksum doesn't match.
+ */
+export const verify = async (dbName: string, tableName: string) => {
+    return await core.ops.op_engine_verify(
+        dbName,
+        tableName
+    );
+}
+
+/**
+ * Checks the files of `tableName`: the framing and checksums of its rows and every index entry against the row it
+ * points to. Returns the issues found (bad framing, corrupted rows, dangling pointers, mismatched keys and missing
+ * entries). With `repair`, the indexes holding issues are rebuilt and listed in `repairedIndexes`.
+ */
+export const checkIntegrity = async (dbName: string, tableName: string, repair: boolean = false) => {
+    return await core.ops.op_engine_check_integrity(
+        dbName,
+        tableName,
+        repair
+    );
+}
+
+/**
+ * Archives the database `dbName` into the file `dest` while it keeps serving queries.
+ * Each table is archived once its pending rows are written, so backups can be taken at any time.
+ */
+export const backup = async (dbName: string, dest: string) => {
+    return await core.ops.op_engine_backup(
+        dbName,
+        dest
+    );
+}
+
+/**
+ * Restores the database archived in the file `src` by `backup`, replacing its tables. Returns the name of the database.
+ */
+export const restore = async (src: string) => {
+    return await core.ops.op_engine_restore(
+        src
+    );
+}
+
+/**
+ * Creates the database `dbName` at runtime, failing if it already exists.
+ * Databases created this way are not part of the workspace, add them to `SchemeJS.toml` to load them on startup.
+ */
+export const createDatabase = async (dbName: string) => {
+    return await core.ops.op_engine_create_database(
+        dbName
+    );
+}
+
+/**
+ * Creates the table defined by `table` (a `Table` of `SchemeJS`) in the database `dbName`, failing if it already holds it.
+ */
+export const createTable = async (dbName: string, table: any) => {
+    return await core.ops.op_engine_create_table(
+        dbName,
+        table
+    );
+}
+
+/**
+ * Defines the materialized view `view.name` in the database `dbName`: the results of the aggregation pipeline `view.pipeline`
+ * (see `aggregate`) over `view.table`, stored so they are searched like a table under the view name.
+ * `view.refresh` is `{ mode: "changes" }` (the default) to apply the changes of the table as they are made, or
+ * `{ mode: "interval", everyMs }` to recompute the view on a schedule. Views are not kept across restarts.
+ */
+export const createView = async (dbName: string, view: { name: string, table: string, pipeline: any[], refresh?: { mode: "changes" } | { mode: "interval", everyMs: number } }) => {
+    return await core.ops.op_engine_create_view(
+        dbName,
+        { ...view, pipeline: view.pipeline.map((stage) => "$match" in stage ? { $match: toQuery(stage.$match) } : stage) }
+    );
+}
+
+/**
+ * Recomputes the materialized view `viewName` of the database `dbName` right away, returning the amount of rows it holds.
+ */
+export const refreshView = async (dbName: string, viewName: string) => {
+    return await core.ops.op_engine_refresh_view(
+        dbName,
+        viewName
+    );
+}
+
+/**
+ * Creates the role `role` (`{ name, grants: [{ database, table?, privilege: "read" | "write" | "admin" }] }`),
+ * replacing the role with its name. `database` is `"*"` for every database. Requires admin on `_system`.
+ */
+export const putRole = async (role: any) => {
+    return await core.ops.op_engine_put_role(
+        role
+    );
+}
+
+/**
+ * Drops the role `name`, returning whether it existed. Requires admin on `_system`.
+ */
+export const dropRole = async (name: string) => {
+    return await core.ops.op_engine_drop_role(
+        name
+    );
+}
+
+/**
+ * Creates the user `user` (`{ name, roles: [<role name>] }`), replacing the user with its name. Its roles must exist.
+ * Requires admin on `_system`.
+ */
+export const putUser = async (user: any) => {
+    return await core.ops.op_engine_put_user(
+        user
+    );
+}
+
+/**
+ * Drops the user `name`, returning whether it existed. Requires admin on `_system`.
+ */
+export const dropUser = async (name: string) => {
+    return await core.ops.op_engine_drop_user(
+        name
+    );
+}
+
+/**
+ * Takes a point-in-time snapshot of the database `dbName` into the folder `dest`, which must not exist yet.
+ * Full shards are hard-linked, so snapshots are cheap to take while the database is live. Returns the manifest of the snapshot.
+ */
+export const snapshot = async (dbName: string, dest: string) => {
+    return await core.ops.op_engine_snapshot(
+        dbName,
+        dest
+    );
+}
+
+/**
+ * Writes the rows of `tableName` matched by `query` (every row when `null`) into the file `dest`,
+ * as JSON Lines (`"jsonl"`) or CSV (`"csv"`). Returns the amount of exported rows.
+ */
+export const exportRows = async (dbName: string, tableName: string, query: any, format: "jsonl" | "csv", dest: string) => {
+    return await core.ops.op_engine_export(
+        dbName,
+        tableName,
+        toQuery(query),
+        format,
+        dest
+    );
+}
+
+/**
+ * Loads the rows of the file `src`, as JSON Lines (`"jsonl"`) or CSV (`"csv"`), into `tableName`.
+ * Invalid lines are skipped and reported at the end: returns `{ imported, errors: [{ line, message }] }`.
+ */
+export const importRows = async (dbName: string, tableName: string, format: "jsonl" | "csv", src: string) => {
+    return await core.ops.op_engine_import(
+        dbName,
+        tableName,
+        format,
+        src
+    );
+}
+
+/**
+ * Renders the metrics of the process (rows inserted, query latency, rows waiting in temporary shards,
+ * reconcile duration) in the Prometheus text format, e.g. to serve them from a `/metrics` endpoint.
+ */
+export const metrics = () => {
+    return core.ops.op_engine_metrics();
+}
+
+/**
+ * Returns the statistics of the databases the caller can read: for every table, its row counts (`rows`, `deadRows`),
+ * the rows waiting in temporary shards (`pendingRows`, `pendingBytes`), the size of its files (`dataSize`, `tempsSize`
+ * and `size` of every index) and when rows were last reconciled (`lastReconcile`, in milliseconds since the epoch).
+ */
+export const stats = async () => {
+    return await core.ops.op_engine_stats();
+}
+
+/**
+ * Sets `changes` (`{ column: value }`) on the rows of `tableName` matched by `query`. Returns the amount of updated rows.
+ */
+export const update = async (dbName: string, tableName: string, query: any, changes: any) => {
+    return await core.ops.op_engine_update(
+        dbName,
+        tableName,
+        toQuery(query),
+        changes
+    );
+}
+
+/**
+ * Deletes the rows of `tableName` matched by `query`. Returns the amount of deleted rows.
+ */
+export const deleteRows = async (dbName: string, tableName: string, query: any) => {
+    return await core.ops.op_engine_delete(
+        dbName,
+        tableName,
+        toQuery(query)
+    );
+}
+
+/**
+ * Returns the rows of `tableName` matched by `query` (every row when `null`), as objects.
+ * `query` is `{ and: [...] }`, `{ or: [...] }`, a condition such as `{ key: "user_age", filterType: ">", value: 20 }`,
+ * a `Query` builder or a MongoDB-style filter such as `{ user_age: { $gt: 20 }, $or: [{ user_country: "US" }, { vip: true }] }`.
+ */
+export const search = async (dbName: string, tableName: string, query: any, options?: { timeout?: number }) => {
+    return await core.ops.op_engine_search(
+        dbName,
+        tableName,
+        toQuery(query),
+        options?.timeout
+    );
+}
+
+/**
+ * Queries fail once `options.timeout` milliseconds elapsed, instead of scanning for as long as it takes.
+ */
+export const groupBy = async (dbName: string, tableName: string, query: any, groupBy: string[], aggregates: { function: "count" | "sum" | "min" | "max" | "avg", column?: string }[], options?: { timeout?: number }) => {
+    return await core.ops.op_engine_group_by(
+        dbName,
+        tableName,
+        toQuery(query),
+        groupBy,
+        aggregates,
+        options?.timeout
+    );
+}
+
+/**
+ * Runs an aggregation pipeline over the rows of `tableName`, every stage reading the documents produced by the previous one:
+ * `[{ $match: { user_age: { $gt: 21 } } }, { $group: { by: ["user_country"], aggregates: [{ function: "count" }] } }, { $project: ["user_country", "count"] }, { $sort: [{ column: "count", direction: "desc" }] }, { $limit: 10 }]`.
+ * `$match` takes any query `search` does.
+ */
+export const aggregate = async (dbName: string, tableName: string, pipeline: any[], options?: { timeout?: number }) => {
+    return await core.ops.op_engine_aggregate(
+        dbName,
+        tableName,
+        pipeline.map((stage) => "$match" in stage ? { $match: toQuery(stage.$match) } : stage),
+        options?.timeout
+    );
+}
+
+export const join = async (dbName: string, tableName: string, query: any, join: { table: string, leftColumn: string, rightColumn: string, type?: "inner" | "left", query?: any }, options?: { timeout?: number }) => {
+    return await core.ops.op_engine_join(
+        dbName,
+        tableName,
+        toQuery(query),
+        { ...join, query: toQuery(join.query) },
+        options?.timeout
+    );
+}
+
+export const explain = async (dbName: string, tableName: string, query: any) => {
+    return await core.ops.op_engine_explain(
+        dbName,
+        tableName,
+        toQuery(query)
+    );
+}
+
+/**
+ * Runs a SQL statement (`SELECT`, `INSERT`, `UPDATE` or `DELETE`) on `dbName`, e.g. `sql("public", "SELECT * FROM users WHERE user_age > 21 LIMIT 10")`.
+ * Returns the selected rows, the uids of the inserted rows, or the amount of updated or deleted rows.
+ */
+export const sql = async (dbName: string, statement: string) => {
+    return await core.ops.op_engine_sql(
+        dbName,
+        statement
+    );
+}
+
+/**
+ * Drops the index `indexName` of `tableName` and builds it again from the rows of the table.
+ */
+export const reindex = async (dbName: string, tableName: string, indexName: string) => {
+    return await core.ops.op_engine_reindex(
+        dbName,
+        tableName,
+        indexName
+    );
+}
+
+/**
+ * Runs `fn` with a transaction object where inserts, updates and deletes are staged.
+ * Staged operations are committed together once `fn` returns, or discarded if it throws.
+ * Staging through `tx` once `fn` returned (e.g. from a promise that wasn't awaited) throws, rather than being lost.
+ */
+export const transaction = async (dbName: string, fn: (tx: any) => any) => {
+    const ops: any[] = [];
+    let closed = false;
+    const stage = (op: any) => {
+        if (closed) {
+            throw new Error("Transaction is already closed, its operations must be staged before the callback returns");
+        }
+        ops.push(op);
+    };
+    const tx = {
+        insert: (tableName: string, data: any) => {
+            stage({ type: "insert", table: tableName, row: data });
+        },
+        update: (tableName: string, query: any, changes: any) => {
+            stage({ type: "update", table: tableName, query: toQuery(query), changes });
+        },
+        delete: (tableName: string, query: any) => {
+            stage({ type: "delete", table: tableName, query: toQuery(query) });
+        },
+    };
+
+    let result;
+    try {
+        result = await fn(tx);
+    } finally {
+        closed = true;
+    }
+
+    await core.ops.op_engine_commit_transaction(
+        dbName,
+        ops
+    );
+
+    return result;
+}
@@ -0,0 +1,123 @@
+/**
+ * Fluent builder of queries, the counterpart of the `Query` builder of the query crate:
+ * `Query.table("users").eq("user_country", "US").and().gt("user_age", 21)`.
+ * Conditions are joined with `and` unless separated by `or`, which binds looser. `nest` adds a query as a single condition.
+ * Queries can be passed wherever a query object is expected (`SchemeJS.search`, `SchemeJS.update`...).
+ */
+export class Query {
+    public tableName: string;
+    private groups: any[][] = [[]];
+
+    constructor(tableName: string) {
+        this.tableName = tableName;
+    }
+
+    static table(tableName: string) {
+        return new Query(tableName);
+    }
+
+    where(key: string, filterType: string, value?: any) {
+        const condition = filterType === "is_null" || filterType === "is_not_null"
+            ? { key, filterType }
+            : { key, filterType, value };
+        this.groups[this.groups.length - 1].push(condition);
+        return this;
+    }
+
+    eq(key: string, value: any) {
+        return this.where(key, "=", value);
+    }
+
+    ne(key: string, value: any) {
+        return this.where(key, "!=", value);
+    }
+
+    gt(key: string, value: any) {
+        return this.where(key, ">", value);
+    }
+
+    gte(key: string, value: any) {
+        return this.where(key, ">=", value);
+    }
+
+    lt(key: string, value: any) {
+        return this.where(key, "<", value);
+    }
+
+    lte(key: string, value: any) {
+        return this.where(key, "<=", value);
+    }
+
+    in(key: string, values: any[]) {
+        return this.where(key, "in", values);
+    }
+
+    contains(key: string, value: any) {
+        return this.where(key, "contains", value);
+    }
+
+    between(key: string, min: any, max: any, minExclusive = false, maxExclusive = false) {
+        return this.where(key, "between", { min, max, minExclusive, maxExclusive });
+    }
+
+    like(key: string, pattern: string) {
+        return this.where(key, "like", pattern);
+    }
+
+    regex(key: string, pattern: string) {
+        return this.where(key, "regex", pattern);
+    }
+
+    startsWith(key: string, prefix: string) {
+        return this.where(key, "starts_with", prefix);
+    }
+
+    path(key: string, path: string, filterType: string, value?: any) {
+        return this.where(key, "path", { path, filterType, value });
+    }
+
+    isNull(key: string) {
+        return this.where(key, "is_null");
+    }
+
+    isNotNull(key: string) {
+        return this.where(key, "is_not_null");
+    }
+
+    nest(query: Query) {
+        const json = query.toJSON();
+        if (json !== null) {
+            this.groups[this.groups.length - 1].push(json);
+        }
+        return this;
+    }
+
+    and() {
+        return this;
+    }
+
+    or() {
+        if (this.groups[this.groups.length - 1].length > 0) {
+            this.groups.push([]);
+        }
+        return this;
+    }
+
+    toJSON(): any {
+        const groups = this.groups
+            .filter((group) => group.length > 0)
+            .map((group) => group.length === 1 ? group[0] : { and: group });
+
+        if (groups.length === 0) {
+            return null;
+        }
+        return groups.length === 1 ? groups[0] : { or: groups };
+    }
+}
+
+/**
+ * Query objects as sent to the ops: `Query` builders are turned into their JSON representation.
+ */
+export const toQuery = (query: any) => {
+    return query instanceof Query ? query.toJSON() : query;
+}
@@ -1,14 +1,72 @@
-use crate::ops::insert::op_engine_insert_row;
+use crate::ops::access::{
+    op_engine_drop_role, op_engine_drop_user, op_engine_put_role, op_engine_put_user,
+};
+use crate::ops::backup::{op_engine_backup, op_engine_restore, op_engine_snapshot};
+use crate::ops::database::{op_engine_create_database, op_engine_create_table};
+use crate::ops::export::op_engine_export;
+use crate::ops::import::op_engine_import;
+use crate::ops::index::{op_engine_check_integrity, op_engine_reindex, op_engine_verify};
+use crate::ops::insert::{op_engine_insert_row, op_engine_insert_rows, op_engine_upsert_row};
+use crate::ops::metrics::{op_engine_metrics, op_engine_stats};
+use crate::ops::mutation::{op_engine_delete, op_engine_update};
+use crate::ops::query::{
+    op_engine_aggregate, op_engine_explain, op_engine_group_by, op_engine_join, op_engine_search,
+};
+use crate::ops::sql::op_engine_sql;
+use crate::ops::transaction::op_engine_commit_transaction;
+use crate::ops::view::{op_engine_create_view, op_engine_refresh_view};
 
+pub mod access;
+pub mod auth;
+pub mod backup;
+pub mod cdc;
 pub mod engine;
 pub mod engine_db;
+pub mod export;
+pub mod import;
+pub mod information_schema;
+pub mod migrations;
 mod ops;
 mod query_error;
+pub mod snapshot;
+pub mod tasks;
 pub mod utils;
 pub mod validation_error;
+pub mod views;
 
 deno_core::extension!(
     sjs_engine,
-    ops = [op_engine_insert_row],
-    esm = ["src/js/ops.ts",]
+    ops = [
+        op_engine_insert_row,
+        op_engine_insert_rows,
+        op_engine_upsert_row,
+        op_engine_update,
+        op_engine_delete,
+        op_engine_search,
+        op_engine_group_by,
+        op_engine_aggregate,
+        op_engine_join,
+        op_engine_explain,
+        op_engine_sql,
+        op_engine_commit_transaction,
+        op_engine_reindex,
+        op_engine_verify,
+        op_engine_check_integrity,
+        op_engine_backup,
+        op_engine_restore,
+        op_engine_snapshot,
+        op_engine_export,
+        op_engine_import,
+        op_engine_metrics,
+        op_engine_stats,
+        op_engine_create_database,
+        op_engine_create_table,
+        op_engine_create_view,
+        op_engine_refresh_view,
+        op_engine_put_role,
+        op_engine_drop_role,
+        op_engine_put_user,
+        op_engine_drop_user
+    ],
+    esm = ["src/js/query.ts", "src/js/ops.ts",]
 );
@@ -0,0 +1,193 @@
+use crate::engine_db::EngineDb;
+use crate::utils::fs::is_js_or_ts;
+use anyhow::Result;
+use deno_core::ModuleSpecifier;
+use schemajs_data::shard::shards::data_shard::config::TableStorage;
+use schemajs_index::index_type::IndexType;
+use schemajs_primitives::column::types::{DataTypes, DataValue};
+use schemajs_primitives::column::Column;
+use schemajs_primitives::index::Index;
+use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_query::row::Row;
+use schemajs_query::row_json::{RowData, RowJson};
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+/// Folder of a database holding its migrations.
+pub const MIGRATIONS_FOLDER: &str = "migrations";
+
+/// System table tracking the migrations applied to a database.
+pub const MIGRATIONS_TABLE: &str = "_migrations";
+
+/// Migration of a database, a module of its `migrations` folder whose default export runs the migration.
+///
+/// # Fields:
+/// - `version`: Name of the module without its extension (e.g. `0001_add_users`). Migrations are applied
+///   in the order of their versions.
+/// - `specifier`: Module of the migration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub version: String,
+    pub specifier: ModuleSpecifier,
+}
+
+/// Definition of the system table tracking the migrations applied to a database (`MIGRATIONS_TABLE`).
+pub fn migrations_table() -> Table {
+    Table::new(MIGRATIONS_TABLE)
+        .add_column(Column::new("version", DataTypes::String))
+        .add_column(Column::new("applied_at", DataTypes::Timestamp))
+        .add_index(Index {
+            name: "version_indx".to_string(),
+            members: vec![String::from("version")],
+            index_type: IndexType::Hash,
+            unique: true,
+        })
+}
+
+/// Migrations of the database at `db_path`, sorted by version. Databases without a `migrations` folder have none.
+pub fn list_migrations(db_path: &Path) -> Result<Vec<Migration>> {
+    let folder = db_path.join(MIGRATIONS_FOLDER);
+    if !folder.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut migrations = vec![];
+    for entry in WalkDir::new(folder.canonicalize()?)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if !is_js_or_ts(&entry) {
+            continue;
+        }
+
+        let version = entry
+            .path()
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        migrations.push(Migration {
+            version,
+            specifier: ModuleSpecifier::from_file_path(entry.path()).unwrap(),
+        });
+    }
+    migrations.sort_by(|a, b| a.version.cmp(&b.version));
+
+    Ok(migrations)
+}
+
+/// Versions of the migrations applied to `db`, empty when it has no `MIGRATIONS_TABLE`.
+pub fn applied_migrations(db: &EngineDb) -> Result<HashSet<String>, QueryError> {
+    if db.query_manager.tables.get(MIGRATIONS_TABLE).is_none() {
+        return Ok(HashSet::new());
+    }
+
+    let rows = db
+        .query_manager
+        .search_manager()
+        .search(MIGRATIONS_TABLE.to_string(), &QueryOps::And(vec![]))?;
+
+    let version_column = Column::new("version", DataTypes::String);
+    Ok(rows
+        .iter()
+        .filter_map(|row| match row.get_value(&version_column) {
+            Some(DataValue::String(version)) => Some(version),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Records `version` as applied to `db`, registering the `MIGRATIONS_TABLE` if needed.
+pub fn record_migration(db: &EngineDb, version: &str) -> Result<(), QueryError> {
+    if db.query_manager.tables.get(MIGRATIONS_TABLE).is_none() {
+        db.add_table(migrations_table(), TableStorage::default())?;
+    }
+
+    let applied_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default();
+
+    db.query_manager.insert(RowJson::from(RowData {
+        table: MIGRATIONS_TABLE.to_string(),
+        value: json!({
+            "_uid": Uuid::new_v4().to_string(),
+            "version": version,
+            "applied_at": applied_at
+        }),
+    }))?;
+
+    // Searches only see reconciled rows, the migration has to be seen as applied right away
+    if let Some(table) = db.query_manager.tables.get(MIGRATIONS_TABLE) {
+        table.temps.reconcile_all();
+    }
+
+    Ok(())
+}
+
+/// Migrations of `migrations` not applied to `db` yet, in the order they have to be applied.
+pub fn pending_migrations(
+    db: &EngineDb,
+    migrations: Vec<Migration>,
+) -> Result<Vec<Migration>, QueryError> {
+    let applied = applied_migrations(db)?;
+    Ok(migrations
+        .into_iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::SchemeJsEngine;
+    use crate::migrations::{
+        applied_migrations, list_migrations, pending_migrations, record_migration,
+    };
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_migrations() {
+        let db_path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let migrations_folder = db_path.join("migrations");
+        std::fs::create_dir_all(&migrations_folder).unwrap();
+        for file in ["0002_add_email.ts", "0001_create_users.ts", "notes.md"] {
+            std::fs::write(migrations_folder.join(file), "export default () => {}").unwrap();
+        }
+
+        let migrations = list_migrations(&db_path).unwrap();
+        let versions: Vec<&str> = migrations.iter().map(|m| m.version.as_str()).collect();
+        assert_eq!(versions, vec!["0001_create_users", "0002_add_email"]);
+        assert!(list_migrations(&db_path.join("unknown"))
+            .unwrap()
+            .is_empty());
+
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+
+        assert!(applied_migrations(&db).unwrap().is_empty());
+        assert_eq!(
+            pending_migrations(&db, migrations.clone()).unwrap().len(),
+            2
+        );
+
+        record_migration(&db, "0001_create_users").unwrap();
+        assert!(applied_migrations(&db)
+            .unwrap()
+            .contains("0001_create_users"));
+
+        let pending = pending_migrations(&db, migrations).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].version, "0002_add_email");
+
+        std::fs::remove_dir_all(db_path).unwrap();
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+use crate::access::{AccessControl, Privilege, Role, User, SYSTEM_DATABASE};
+use crate::engine::SchemeJsEngine;
+use crate::ops::authorize;
+use deno_core::{op2, OpState};
+use schemajs_query::errors::QueryError;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+// Users and roles of the engine, once the principal is checked to be an admin of the system database
+fn access_control(state: &Rc<RefCell<OpState>>) -> Result<Arc<AccessControl>, QueryError> {
+    authorize(state, SYSTEM_DATABASE, None, Privilege::Admin)?;
+
+    let state = state.borrow();
+    state
+        .borrow::<Arc<SchemeJsEngine>>()
+        .access
+        .clone()
+        .ok_or(QueryError::AccessControlDisabled)
+}
+
+#[op2(async)]
+pub async fn op_engine_put_role(
+    state: Rc<RefCell<OpState>>,
+    #[serde] role: Role,
+) -> Result<(), QueryError> {
+    access_control(&state)?.put_role(role)
+}
+
+#[op2(async)]
+pub async fn op_engine_drop_role(
+    state: Rc<RefCell<OpState>>,
+    #[string] name: String,
+) -> Result<bool, QueryError> {
+    access_control(&state)?.drop_role(&name)
+}
+
+#[op2(async)]
+pub async fn op_engine_put_user(
+    state: Rc<RefCell<OpState>>,
+    #[serde] user: User,
+) -> Result<(), QueryError> {
+    access_control(&state)?.put_user(user)
+}
+
+#[op2(async)]
+pub async fn op_engine_drop_user(
+    state: Rc<RefCell<OpState>>,
+    #[string] name: String,
+) -> Result<bool, QueryError> {
+    access_control(&state)?.drop_user(&name)
+}
@@ -0,0 +1,51 @@
+use crate::access::{Privilege, ANY_DATABASE};
+use crate::engine::SchemeJsEngine;
+use crate::ops::authorize;
+use crate::snapshot::SnapshotManifest;
+use deno_core::error::AnyError;
+use deno_core::{op2, OpState};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[op2(async)]
+pub async fn op_engine_backup(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] dest: String,
+) -> Result<(), AnyError> {
+    authorize(&state, &db_name, None, Privilege::Read)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    state.backup(&db_name, &PathBuf::from(dest))
+}
+
+#[op2(async)]
+#[string]
+pub async fn op_engine_restore(
+    state: Rc<RefCell<OpState>>,
+    #[string] src: String,
+) -> Result<String, AnyError> {
+    // The database of the archive is only known once restored, restoring requires admin on every database
+    authorize(&state, ANY_DATABASE, None, Privilege::Admin)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    state.restore(&PathBuf::from(src))
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_snapshot(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] dest: String,
+) -> Result<SnapshotManifest, AnyError> {
+    authorize(&state, &db_name, None, Privilege::Read)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    state.snapshot(&db_name, &PathBuf::from(dest))
+}
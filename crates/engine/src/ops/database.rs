@@ -0,0 +1,34 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::authorize;
+use deno_core::error::AnyError;
+use deno_core::{op2, OpState};
+use schemajs_primitives::table::Table;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[op2(async)]
+pub async fn op_engine_create_database(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+) -> Result<(), AnyError> {
+    authorize(&state, &db_name, None, Privilege::Admin)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    state.create_database(&db_name).map(|_| ())
+}
+
+#[op2(async)]
+pub async fn op_engine_create_table(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[serde] table: Table,
+) -> Result<(), AnyError> {
+    authorize(&state, &db_name, None, Privilege::Admin)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    state.create_table(&db_name, table)
+}
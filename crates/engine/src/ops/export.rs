@@ -0,0 +1,39 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::export::ExportFormat;
+use crate::ops::{authorize, find_db};
+use deno_core::error::AnyError;
+use deno_core::{op2, serde_json, OpState};
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::query_ops::QueryOps;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_export(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] query: serde_json::Value,
+    #[serde] format: ExportFormat,
+    #[string] dest: String,
+) -> Result<usize, AnyError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Read)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let ops = {
+        let db = find_db(&state, &db_name)?;
+        let table = db
+            .query_manager
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+        QueryOps::from_json(&table.table, &query)?
+    };
+
+    state.export(&db_name, &table_name, &ops, format, &PathBuf::from(dest))
+}
@@ -0,0 +1,33 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::export::ExportFormat;
+use crate::import::ImportReport;
+use crate::ops::authorize;
+use deno_core::error::AnyError;
+use deno_core::{op2, OpState};
+use schemajs_data::io_pool::IoPool;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_import(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] format: ExportFormat,
+    #[string] src: String,
+) -> Result<ImportReport, AnyError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Write)?;
+    let state = {
+        let mut mut_state = state.borrow_mut();
+        mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone()
+    };
+
+    // Imports can take a while, the runtime keeps serving other ops in the meantime
+    IoPool::global()
+        .run(move || state.import(&db_name, &table_name, format, &PathBuf::from(src)))
+        .await
+}
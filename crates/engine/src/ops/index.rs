@@ -0,0 +1,55 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::{authorize, find_db};
+use deno_core::{op2, OpState};
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::integrity::IntegrityReport;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[op2(async)]
+pub async fn op_engine_reindex(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[string] index_name: String,
+) -> Result<(), QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Admin)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let db = find_db(&state, &db_name)?;
+    db.reindex(&table_name, &index_name)
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_verify(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+) -> Result<Vec<u64>, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Admin)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let db = find_db(&state, &db_name)?;
+    db.verify(&table_name)
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_check_integrity(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    repair: bool,
+) -> Result<IntegrityReport, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Admin)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let db = find_db(&state, &db_name)?;
+    db.check_integrity(&table_name, repair)
+}
@@ -1,10 +1,14 @@
+use crate::access::Privilege;
 use crate::engine::SchemeJsEngine;
+use crate::ops::{authorize, find_db};
 use deno_core::{op2, serde_json, OpState};
+use schemajs_data::io_pool::IoPool;
 use schemajs_query::errors::QueryError;
 use schemajs_query::row_json::{RowData, RowJson};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
+use tracing::Instrument;
 use uuid::Uuid;
 
 #[op2(async)]
@@ -15,11 +19,12 @@ pub async fn op_engine_insert_row(
     #[string] table_name: String,
     #[serde] mut row: serde_json::Value,
 ) -> Result<Uuid, QueryError> {
-    let mut mut_state = state.borrow_mut();
-    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
-
+    authorize(&state, &db_name, Some(&table_name), Privilege::Write)?;
+    // The state is released before writing, so other ops can run while the write waits for the disk
     let query_manager = {
-        let db = state.find_by_name_ref(db_name.clone()).unwrap();
+        let mut mut_state = state.borrow_mut();
+        let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>();
+        let db = find_db(&state, &db_name)?;
         db.query_manager.clone()
     };
 
@@ -32,14 +37,109 @@ pub async fn op_engine_insert_row(
         );
     }
 
-    let insert = query_manager.insert(RowJson::from(RowData {
-        table: table_name,
-        value: row,
-    }));
+    let span = tracing::info_span!("op_engine_insert_row", db = %db_name, table = %table_name);
+    let insert = IoPool::global()
+        .run(move || {
+            query_manager.insert(RowJson::from(RowData {
+                table: table_name,
+                value: row,
+            }))
+        })
+        .instrument(span)
+        .await;
 
-    if insert.is_err() {
-        println!("Error");
+    if let Err(e) = &insert {
+        tracing::error!(database = %db_name, error = %e, "Could not insert row");
     }
 
     insert
 }
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_upsert_row(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] mut row: serde_json::Value,
+    #[serde] index_name: Option<String>,
+) -> Result<Uuid, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Write)?;
+    // The state is released before writing, so other ops can run while the write waits for the disk
+    let query_manager = {
+        let mut mut_state = state.borrow_mut();
+        let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>();
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    // The uid is only used if the row ends up being inserted
+    if let serde_json::Value::Object(ref mut obj) = row {
+        if !obj.contains_key("_uid") {
+            obj.insert(
+                "_uid".to_string(),
+                serde_json::Value::String(Uuid::new_v4().to_string()),
+            );
+        }
+    }
+
+    let span = tracing::info_span!("op_engine_upsert_row", db = %db_name, table = %table_name);
+    IoPool::global()
+        .run(move || {
+            query_manager.upsert(
+                RowJson::from(RowData {
+                    table: table_name,
+                    value: row,
+                }),
+                index_name.as_deref(),
+            )
+        })
+        .instrument(span)
+        .await
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_insert_rows(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] rows: Vec<serde_json::Value>,
+) -> Result<Vec<Uuid>, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Write)?;
+    // The state is released before writing, so other ops can run while the write waits for the disk
+    let query_manager = {
+        let mut mut_state = state.borrow_mut();
+        let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>();
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let span = tracing::info_span!(
+        "op_engine_insert_rows",
+        db = %db_name,
+        table = %table_name,
+        rows = rows.len()
+    );
+    let rows = rows
+        .into_iter()
+        .map(|mut row| {
+            if let serde_json::Value::Object(ref mut obj) = row {
+                obj.insert(
+                    "_uid".to_string(),
+                    serde_json::Value::String(Uuid::new_v4().to_string()),
+                );
+            }
+
+            RowJson::from(RowData {
+                table: table_name.clone(),
+                value: row,
+            })
+        })
+        .collect();
+
+    IoPool::global()
+        .run(move || query_manager.insert_many(rows))
+        .instrument(span)
+        .await
+}
@@ -0,0 +1,28 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::authorize;
+use deno_core::{op2, OpState};
+use schemajs_data::metrics::Metrics;
+use schemajs_query::managers::single::stats::DatabaseStats;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[op2]
+#[string]
+pub fn op_engine_metrics() -> String {
+    Metrics::global().render()
+}
+
+/// Statistics of the databases the principal of the runtime can read.
+#[op2(async)]
+#[serde]
+pub async fn op_engine_stats(state: Rc<RefCell<OpState>>) -> Vec<DatabaseStats> {
+    let engine = state.borrow().borrow::<Arc<SchemeJsEngine>>().clone();
+
+    engine
+        .stats()
+        .into_iter()
+        .filter(|db| authorize(&state, &db.name, None, Privilege::Read).is_ok())
+        .collect()
+}
@@ -1 +1,51 @@
+pub mod access;
+pub mod backup;
+pub mod database;
+pub mod export;
+pub mod import;
+pub mod index;
 pub mod insert;
+pub mod metrics;
+pub mod mutation;
+pub mod query;
+pub mod sql;
+pub mod transaction;
+pub mod view;
+
+use crate::access::{Principal, Privilege};
+use crate::engine::SchemeJsEngine;
+use crate::engine_db::EngineDb;
+use deno_core::OpState;
+use schemajs_query::errors::QueryError;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Checks that the principal of the runtime (see `SchemeJsRuntime::set_principal`) holds `privilege` on `table`
+/// of `db_name`, or on the whole database when `table` is `None`. Runtimes without a principal are denied everything.
+/// Every op checks it before touching the engine, the state is only borrowed for the check.
+pub(crate) fn authorize(
+    state: &Rc<RefCell<OpState>>,
+    db_name: &str,
+    table: Option<&str>,
+    privilege: Privilege,
+) -> Result<(), QueryError> {
+    let state = state.borrow();
+    let principal = state.try_borrow::<Principal>().ok_or_else(|| {
+        QueryError::AccessDenied("The runtime has no principal to run ops for".to_string())
+    })?;
+
+    state
+        .borrow::<Arc<SchemeJsEngine>>()
+        .authorize(principal, db_name, table, privilege)
+}
+
+/// Database `db_name` of `engine`, as named by an op.
+///
+/// # Returns:
+/// - `Result<Arc<EngineDb>, QueryError>`: `InvalidDatabase` when the engine has no such database.
+pub(crate) fn find_db(engine: &SchemeJsEngine, db_name: &str) -> Result<Arc<EngineDb>, QueryError> {
+    engine
+        .find_by_name_ref(db_name.to_string())
+        .ok_or_else(|| QueryError::InvalidDatabase(db_name.to_string()))
+}
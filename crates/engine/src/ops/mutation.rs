@@ -0,0 +1,79 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::{authorize, find_db};
+use deno_core::{op2, serde_json, OpState};
+use schemajs_data::io_pool::IoPool;
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::query_ops::{values_from_json, QueryOps};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use tracing::Instrument;
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_update(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] query: serde_json::Value,
+    #[serde] changes: serde_json::Value,
+) -> Result<usize, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Write)?;
+    // The state is released before writing, so other ops can run while the write waits for the disk
+    let query_manager = {
+        let mut mut_state = state.borrow_mut();
+        let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>();
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let (ops, values) = {
+        let table = query_manager
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+        (
+            QueryOps::from_json(&table.table, &query)?,
+            values_from_json(&table.table, &changes)?,
+        )
+    };
+
+    let span = tracing::info_span!("op_engine_update", db = %db_name, table = %table_name);
+    IoPool::global()
+        .run(move || query_manager.update(table_name, &ops, &values))
+        .instrument(span)
+        .await
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_delete(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] query: serde_json::Value,
+) -> Result<usize, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Write)?;
+    // The state is released before writing, so other ops can run while the write waits for the disk
+    let query_manager = {
+        let mut mut_state = state.borrow_mut();
+        let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>();
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let ops = {
+        let table = query_manager
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+        QueryOps::from_json(&table.table, &query)?
+    };
+
+    let span = tracing::info_span!("op_engine_delete", db = %db_name, table = %table_name);
+    IoPool::global()
+        .run(move || query_manager.delete(table_name, &ops))
+        .instrument(span)
+        .await
+}
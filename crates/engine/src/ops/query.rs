@@ -0,0 +1,248 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::{authorize, find_db};
+use deno_core::{op2, serde_json, OpState};
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::aggregate::Aggregate;
+use schemajs_query::ops::join::{Join, JoinType};
+use schemajs_query::ops::pipeline::Pipeline;
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_workers::query_pool::{QueryWorkerPool, SearchJob};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_search(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] query: serde_json::Value,
+    #[serde] timeout: Option<u64>,
+) -> Result<Vec<serde_json::Value>, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Read)?;
+    let span = tracing::info_span!("op_engine_search", db = %db_name, table = %table_name);
+    let (engine, pool) = {
+        let mut mut_state = state.borrow_mut();
+        (
+            mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone(),
+            mut_state.try_borrow::<Arc<QueryWorkerPool>>().cloned(),
+        )
+    };
+
+    let query_manager = {
+        let db = find_db(&engine, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let (table, ops) = {
+        let table = query_manager
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+        let ops = QueryOps::from_json(&table.table, &query)?;
+        (table.table.clone(), ops)
+    };
+
+    let timeout = timeout.map(Duration::from_millis);
+    let rows = match pool {
+        Some(pool) => {
+            pool.search(SearchJob {
+                query_manager,
+                table: table_name,
+                ops,
+                timeout,
+            })
+            .instrument(span)
+            .await?
+        }
+        None => span.in_scope(|| {
+            query_manager
+                .search_manager()
+                .set_timeout(timeout)
+                .search(table_name, &ops)
+        })?,
+    };
+
+    Ok(rows.iter().map(|row| row.to_json(&table)).collect())
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_group_by(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] query: serde_json::Value,
+    #[serde] group_by: Vec<String>,
+    #[serde] aggregates: Vec<Aggregate>,
+    #[serde] timeout: Option<u64>,
+) -> Result<Vec<serde_json::Value>, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Read)?;
+    let _span =
+        tracing::info_span!("op_engine_group_by", db = %db_name, table = %table_name).entered();
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let query_manager = {
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let ops = {
+        let table = query_manager
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+        QueryOps::from_json(&table.table, &query)?
+    };
+
+    let groups = query_manager
+        .search_manager()
+        .set_timeout(timeout.map(Duration::from_millis))
+        .group_by(table_name, &ops, &group_by, &aggregates)?;
+
+    Ok(groups.iter().map(|group| group.to_json()).collect())
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_aggregate(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] pipeline: serde_json::Value,
+    #[serde] timeout: Option<u64>,
+) -> Result<Vec<serde_json::Value>, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Read)?;
+    let _span =
+        tracing::info_span!("op_engine_aggregate", db = %db_name, table = %table_name).entered();
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let query_manager = {
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    query_manager
+        .search_manager()
+        .set_timeout(timeout.map(Duration::from_millis))
+        .pipeline(table_name, &Pipeline::from_json(&pipeline)?)
+}
+
+/// Join as sent from JS through `SchemeJS.join`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinRequest {
+    pub table: String,
+    pub left_column: String,
+    pub right_column: String,
+    #[serde(rename = "type", default = "default_join_type")]
+    pub join_type: JoinType,
+    #[serde(default)]
+    pub query: serde_json::Value,
+}
+
+fn default_join_type() -> JoinType {
+    JoinType::Inner
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_join(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] query: serde_json::Value,
+    #[serde] join: JoinRequest,
+    #[serde] timeout: Option<u64>,
+) -> Result<Vec<serde_json::Value>, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Read)?;
+    authorize(&state, &db_name, Some(&join.table), Privilege::Read)?;
+    let _span = tracing::info_span!("op_engine_join", db = %db_name, table = %table_name).entered();
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let query_manager = {
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let parse_query = |table_name: &String, query: &serde_json::Value| {
+        let table = query_manager
+            .tables
+            .get(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+        QueryOps::from_json(&table.table, query)
+    };
+
+    let ops = parse_query(&table_name, &query)?;
+    let join_ops = parse_query(&join.table, &join.query)?;
+
+    let get_table = |table_name: &String| {
+        query_manager
+            .tables
+            .get(table_name)
+            .map(|table| table.table.clone())
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))
+    };
+    let (left_table, right_table) = (get_table(&table_name)?, get_table(&join.table)?);
+
+    let rows = query_manager
+        .search_manager()
+        .set_timeout(timeout.map(Duration::from_millis))
+        .join(
+            table_name,
+            &ops,
+            &Join::new(
+                join.table.as_str(),
+                join.left_column.as_str(),
+                join.right_column.as_str(),
+                join.join_type,
+            )
+            .set_query(join_ops),
+        )?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "left": row.left.to_json(&left_table),
+                "right": row.right.map(|right| right.to_json(&right_table)),
+            })
+        })
+        .collect())
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_explain(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] table_name: String,
+    #[serde] query: serde_json::Value,
+) -> Result<serde_json::Value, QueryError> {
+    authorize(&state, &db_name, Some(&table_name), Privilege::Read)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let query_manager = {
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let ops = {
+        let table = query_manager
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+        QueryOps::from_json(&table.table, &query)?
+    };
+
+    query_manager.search_manager().explain(table_name, &ops)
+}
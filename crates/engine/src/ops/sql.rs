@@ -0,0 +1,40 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::{authorize, find_db};
+use deno_core::{op2, OpState};
+use schemajs_data::io_pool::IoPool;
+use schemajs_query::errors::QueryError;
+use schemajs_query::sql::{self, SqlResult};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use tracing::Instrument;
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_sql(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] sql: String,
+) -> Result<SqlResult, QueryError> {
+    let statement = sql::parse(&sql)?;
+    let privilege = match statement.is_read_only() {
+        true => Privilege::Read,
+        false => Privilege::Write,
+    };
+    authorize(&state, &db_name, Some(statement.table()), privilege)?;
+
+    // The state is released before running the statement, so other ops can run while it waits for the disk
+    let query_manager = {
+        let mut mut_state = state.borrow_mut();
+        let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>();
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let span = tracing::info_span!("op_engine_sql", db = %db_name, table = %statement.table());
+    IoPool::global()
+        .run(move || statement.execute(&query_manager))
+        .instrument(span)
+        .await
+}
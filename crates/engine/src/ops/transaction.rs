@@ -0,0 +1,115 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::{authorize, find_db};
+use deno_core::{op2, serde_json, OpState};
+use schemajs_data::io_pool::IoPool;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::transaction::TransactionResult;
+use schemajs_query::ops::query_ops::{values_from_json, QueryOps};
+use schemajs_query::row_json::{RowData, RowJson};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Operation staged from JS through `SchemeJS.transaction`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TransactionRequest {
+    Insert {
+        table: String,
+        row: serde_json::Value,
+    },
+    Update {
+        table: String,
+        #[serde(default)]
+        query: serde_json::Value,
+        changes: serde_json::Value,
+    },
+    Delete {
+        table: String,
+        #[serde(default)]
+        query: serde_json::Value,
+    },
+}
+
+impl TransactionRequest {
+    pub fn table(&self) -> &str {
+        match self {
+            TransactionRequest::Insert { table, .. }
+            | TransactionRequest::Update { table, .. }
+            | TransactionRequest::Delete { table, .. } => table,
+        }
+    }
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_commit_transaction(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[serde] ops: Vec<TransactionRequest>,
+) -> Result<TransactionResult, QueryError> {
+    for op in ops.iter() {
+        authorize(&state, &db_name, Some(op.table()), Privilege::Write)?;
+    }
+
+    // The state is released before writing, so other ops can run while the write waits for the disk
+    let query_manager = {
+        let mut mut_state = state.borrow_mut();
+        let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>();
+        let db = find_db(&state, &db_name)?;
+        db.query_manager.clone()
+    };
+
+    let mut transaction = query_manager.begin();
+
+    for op in ops {
+        match op {
+            TransactionRequest::Insert { table, mut row } => {
+                if let serde_json::Value::Object(ref mut obj) = row {
+                    obj.insert(
+                        "_uid".to_string(),
+                        serde_json::Value::String(Uuid::new_v4().to_string()),
+                    );
+                }
+
+                transaction.insert(RowJson::from(RowData { table, value: row }));
+            }
+            TransactionRequest::Update {
+                table,
+                query,
+                changes,
+            } => {
+                let (ops, values) = {
+                    let table_shard = query_manager
+                        .tables
+                        .get(&table)
+                        .ok_or_else(|| QueryError::InvalidTable(table.clone()))?;
+                    (
+                        QueryOps::from_json(&table_shard.table, &query)?,
+                        values_from_json(&table_shard.table, &changes)?,
+                    )
+                };
+
+                transaction.update(table.as_str(), ops, values);
+            }
+            TransactionRequest::Delete { table, query } => {
+                let ops = {
+                    let table_shard = query_manager
+                        .tables
+                        .get(&table)
+                        .ok_or_else(|| QueryError::InvalidTable(table.clone()))?;
+                    QueryOps::from_json(&table_shard.table, &query)?
+                };
+
+                transaction.delete(table.as_str(), ops);
+            }
+        }
+    }
+
+    IoPool::global()
+        .run(move || query_manager.commit(transaction))
+        .await
+}
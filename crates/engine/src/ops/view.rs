@@ -0,0 +1,62 @@
+use crate::access::Privilege;
+use crate::engine::SchemeJsEngine;
+use crate::ops::{authorize, find_db};
+use crate::views::{ViewDefinition, ViewRefresh};
+use deno_core::error::AnyError;
+use deno_core::{op2, serde_json, OpState};
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::pipeline::Pipeline;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Materialized view as sent from JS through `SchemeJS.createView`.
+#[derive(Debug, Deserialize)]
+pub struct ViewRequest {
+    pub name: String,
+    pub table: String,
+    pub pipeline: serde_json::Value,
+    #[serde(default = "default_view_refresh")]
+    pub refresh: ViewRefresh,
+}
+
+fn default_view_refresh() -> ViewRefresh {
+    ViewRefresh::Changes
+}
+
+#[op2(async)]
+pub async fn op_engine_create_view(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[serde] view: ViewRequest,
+) -> Result<(), AnyError> {
+    authorize(&state, &db_name, None, Privilege::Admin)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    state.create_view(
+        &db_name,
+        ViewDefinition {
+            name: view.name,
+            table: view.table,
+            pipeline: Pipeline::from_json(&view.pipeline)?,
+            refresh: view.refresh,
+        },
+    )
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_engine_refresh_view(
+    state: Rc<RefCell<OpState>>,
+    #[string] db_name: String,
+    #[string] view_name: String,
+) -> Result<usize, QueryError> {
+    authorize(&state, &db_name, Some(&view_name), Privilege::Write)?;
+    let mut mut_state = state.borrow_mut();
+    let state = mut_state.borrow_mut::<Arc<SchemeJsEngine>>().clone();
+
+    let db = find_db(&state, &db_name)?;
+    db.views.refresh(&view_name)
+}
@@ -0,0 +1,310 @@
+use crate::engine_db::EngineDb;
+use crate::views::MaterializedViews;
+use anyhow::{bail, Result};
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::shard::shards::data_shard::config::TableStorage;
+use schemajs_data::utils::fs::list_files_with_prefix;
+use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::SingleQueryManager;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the manifest at the root of every snapshot folder.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Files holding an index in a snapshot, relative to the `indx` folder of its table.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SnapshotIndex {
+    pub name: String,
+    pub files: Vec<String>,
+}
+
+/// Table held by a snapshot.
+///
+/// # Fields:
+/// - `table`: Definition of the table when the snapshot was taken.
+/// - `storage`: Storage settings of the main shard of the table (compression, size of its shards), kept to reopen it the same way.
+/// - `rows`: Amount of rows of the main shard, tombstoned rows included.
+/// - `shards`: Files of the main shard, relative to the folder of the table.
+/// - `indexes`: Files of every index of the table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotTable {
+    pub table: Table,
+    pub storage: TableStorage,
+    pub rows: usize,
+    pub shards: Vec<String>,
+    pub indexes: Vec<SnapshotIndex>,
+}
+
+/// Manifest of a snapshot, stored as `manifest.json` next to the `dbs` folder holding its files
+/// (`<snapshot>/dbs/<database>/<table>/..`).
+///
+/// # Fields:
+/// - `database`: Name of the database the snapshot was taken from.
+/// - `created_at`: When the snapshot was taken, in milliseconds since the Unix epoch.
+/// - `tables`: Every table of the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub database: String,
+    pub created_at: u64,
+    pub tables: Vec<SnapshotTable>,
+}
+
+fn file_names(paths: Vec<PathBuf>) -> Vec<String> {
+    let mut names: Vec<String> = paths
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Takes a point-in-time snapshot of the tables of `db` into the folder `dest`, which must not exist yet.
+///
+/// Tables are copied one by one, each of them once its rows are reconciled and while writes to it wait
+/// (see `SingleQueryManager::snapshot_table`). Full shards are hard-linked rather than copied, so taking
+/// a snapshot of a live database is cheap even for large tables. Files of encrypted databases stay encrypted.
+pub fn snapshot_database(db: &EngineDb, dest: &Path) -> Result<SnapshotManifest> {
+    if dest.exists() {
+        bail!(
+            "Snapshot folder '{}' already exists",
+            dest.to_string_lossy()
+        );
+    }
+
+    let db_folder = dest.join("dbs").join(&db.name);
+    std::fs::create_dir_all(&db_folder)?;
+
+    let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    let mut tables = vec![];
+
+    let table_names = db.query_manager.table_names.read().unwrap().clone();
+    for table_name in table_names {
+        let (table, storage) = {
+            let table_shard = db
+                .query_manager
+                .tables
+                .get(&table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+            let storage = table_shard.storage();
+            ((*table_shard.table).clone(), storage)
+        };
+
+        let table_folder = db_folder.join(&table_name);
+        let rows = db
+            .query_manager
+            .snapshot_table(&table_name, &table_folder)?;
+
+        let shards = file_names(list_files_with_prefix(&table_folder, "data_")?);
+        let indexes = table
+            .indexes
+            .iter()
+            .map(|index| SnapshotIndex {
+                name: index.name.clone(),
+                files: file_names(
+                    list_files_with_prefix(
+                        table_folder.join("indx"),
+                        &format!("indx{}_", index.name),
+                    )
+                    .unwrap_or_default(),
+                ),
+            })
+            .collect();
+
+        tables.push(SnapshotTable {
+            table,
+            storage,
+            rows,
+            shards,
+            indexes,
+        });
+    }
+
+    let manifest = SnapshotManifest {
+        database: db.name.clone(),
+        created_at,
+        tables,
+    };
+    std::fs::write(
+        dest.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )?;
+
+    Ok(manifest)
+}
+
+/// Opens the snapshot taken into `path` by `snapshot_database` as a read-only database, for reporting or debugging.
+/// Searches work as usual, writes fail with `QueryError::ReadOnly`.
+///
+/// Snapshots of encrypted databases need the `encryption` key of the database they were taken from.
+pub fn open_snapshot(path: &Path, encryption: Option<EncryptionKey>) -> Result<EngineDb> {
+    let manifest: SnapshotManifest =
+        serde_json::from_slice(&std::fs::read(path.join(MANIFEST_FILE))?)?;
+
+    let query_manager = SingleQueryManager::new(manifest.database.clone())
+        .set_encryption(encryption)
+        .set_base_path(Some(path.to_path_buf()))
+        .set_read_only(true);
+
+    for table in manifest.tables {
+        query_manager.register_table_with_storage(table.table, table.storage)?;
+    }
+
+    let query_manager = Arc::new(query_manager);
+    Ok(EngineDb {
+        db_folder: path.join("dbs").join(&manifest.database),
+        views: MaterializedViews::new(query_manager.clone()),
+        query_manager,
+        name: manifest.database,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::SchemeJsEngine;
+    use crate::snapshot::{open_snapshot, MANIFEST_FILE};
+    use schemajs_data::compression::Compression;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_index::index_type::IndexType;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+    use schemajs_query::row_json::{RowData, RowJson};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_snapshot() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let engine = engine;
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users")
+                .add_column(Column::new("user_name", DataTypes::String))
+                .add_index(Index {
+                    name: "user_name_indx".to_string(),
+                    members: vec![String::from("user_name")],
+                    index_type: IndexType::Hash,
+                    unique: false,
+                }),
+            TableStorage::new(Compression::Lz4),
+        )
+        .unwrap();
+
+        let user = |name: &str| {
+            RowJson::from(RowData {
+                table: "users".to_string(),
+                value: json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name
+                }),
+            })
+        };
+
+        db.query_manager.insert(user("andres")).unwrap();
+        db.query_manager.insert(user("carlos")).unwrap();
+
+        let dest = std::env::temp_dir().join(format!("snapshot-{}", Uuid::new_v4()));
+        let manifest = engine.snapshot(&db_name, &dest).unwrap();
+        assert!(dest.join(MANIFEST_FILE).exists());
+        assert!(engine.snapshot(&db_name, &dest).is_err());
+        assert!(engine
+            .snapshot(
+                "unknown",
+                &std::env::temp_dir().join(Uuid::new_v4().to_string())
+            )
+            .is_err());
+
+        assert_eq!(manifest.database, db_name);
+        assert_eq!(manifest.tables.len(), 1);
+        assert_eq!(manifest.tables[0].rows, 2);
+        assert_eq!(
+            manifest.tables[0].storage,
+            TableStorage::new(Compression::Lz4)
+        );
+        assert_eq!(manifest.tables[0].shards.len(), 1);
+        assert_eq!(manifest.tables[0].indexes[0].name, "user_name_indx");
+        assert!(!manifest.tables[0].indexes[0].files.is_empty());
+
+        // Writes after the snapshot don't reach it
+        db.query_manager.insert(user("luis")).unwrap();
+        db.query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        let snapshot = open_snapshot(&dest, None).unwrap();
+        assert_eq!(snapshot.name, db_name);
+        assert_eq!(
+            snapshot
+                .query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .data
+                .read()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let by_name = |name: &str| {
+            QueryOps::Condition(QueryVal {
+                key: "user_name".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(name.to_string()),
+            })
+        };
+
+        let search = snapshot.query_manager.search_manager();
+        assert_eq!(
+            search
+                .search("users".to_string(), &by_name("carlos"))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(search
+            .search("users".to_string(), &by_name("luis"))
+            .unwrap()
+            .is_empty());
+
+        // Snapshots are read-only
+        assert!(snapshot
+            .query_manager
+            .insert(user("juan"))
+            .err()
+            .unwrap()
+            .is_read_only());
+        assert!(snapshot
+            .query_manager
+            .delete("users".to_string(), &by_name("carlos"))
+            .err()
+            .unwrap()
+            .is_read_only());
+
+        // The live database keeps every row
+        assert_eq!(
+            db.query_manager
+                .search_manager()
+                .search("users".to_string(), &by_name("luis"))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        std::fs::remove_dir_all(dest).unwrap();
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Outcome of the runs of a background task so far.
+///
+/// # Fields:
+/// - `runs`: Attempts made, retries included.
+/// - `failures`: Attempts that failed (returned an error or panicked).
+/// - `consecutive_failures`: Attempts that failed since the last successful one.
+/// - `running`: Whether an attempt is in progress.
+/// - `last_run`: When the last attempt started, in milliseconds since the Unix epoch.
+/// - `last_success`: When the last successful attempt finished, in milliseconds since the Unix epoch.
+/// - `last_duration`: Time taken by the last finished attempt.
+/// - `last_error`: Error of the last failed attempt, kept after the task succeeds again.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskStatus {
+    pub runs: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub running: bool,
+    pub last_run: Option<u64>,
+    pub last_success: Option<u64>,
+    pub last_duration: Option<Duration>,
+    pub last_error: Option<String>,
+}
+
+/// Status of the background tasks run against the engine (e.g. by the manager of `base`), by task id.
+#[derive(Debug, Default)]
+pub struct TaskRegistry {
+    statuses: RwLock<BTreeMap<String, TaskStatus>>,
+}
+
+impl TaskRegistry {
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    fn update<F: FnOnce(&mut TaskStatus)>(&self, id: &str, f: F) {
+        let mut statuses = self.statuses.write().unwrap();
+        f(statuses.entry(id.to_string()).or_default());
+    }
+
+    /// Lists the task `id` before it ever runs.
+    pub fn register(&self, id: &str) {
+        self.update(id, |_| {});
+    }
+
+    /// Records that an attempt of the task `id` started.
+    pub fn started(&self, id: &str) {
+        self.update(id, |status| {
+            status.runs += 1;
+            status.running = true;
+            status.last_run = Some(Self::now());
+        });
+    }
+
+    /// Records that the attempt of the task `id` in progress succeeded after `elapsed`.
+    pub fn succeeded(&self, id: &str, elapsed: Duration) {
+        self.update(id, |status| {
+            status.running = false;
+            status.consecutive_failures = 0;
+            status.last_success = Some(Self::now());
+            status.last_duration = Some(elapsed);
+        });
+    }
+
+    /// Records that the attempt of the task `id` in progress failed with `error` after `elapsed`.
+    pub fn failed(&self, id: &str, error: String, elapsed: Duration) {
+        self.update(id, |status| {
+            status.running = false;
+            status.failures += 1;
+            status.consecutive_failures += 1;
+            status.last_duration = Some(elapsed);
+            status.last_error = Some(error);
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<TaskStatus> {
+        self.statuses.read().unwrap().get(id).cloned()
+    }
+
+    /// Status of every task, by id.
+    pub fn all(&self) -> BTreeMap<String, TaskStatus> {
+        self.statuses.read().unwrap().clone()
+    }
+}
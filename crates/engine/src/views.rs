@@ -0,0 +1,660 @@
+use schemajs_data::shard::shards::data_shard::config::TableStorage;
+use schemajs_index::index_type::IndexType;
+use schemajs_primitives::column::types::{DataTypes, DataValue};
+use schemajs_primitives::column::Column;
+use schemajs_primitives::index::Index;
+use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::changes::ChangeEvent;
+use schemajs_query::managers::single::SingleQueryManager;
+use schemajs_query::ops::pipeline::{Pipeline, PipelineStage};
+use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+use schemajs_query::row::Row;
+use schemajs_query::row_json::{RowData, RowJson};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::TryRecvError;
+use uuid::Uuid;
+
+/// Column of the table of a view holding the key of the rows it was computed from, see `MaterializedView`.
+pub const VIEW_KEY_COLUMN: &str = "_view_key";
+
+/// Keys a batch of changes can touch before the view is recomputed as a whole instead.
+pub const MAX_INCREMENTAL_KEYS: usize = 256;
+
+/// How a `MaterializedView` is kept up to date, in JSON `{ "mode": "changes" }` or
+/// `{ "mode": "interval", "everyMs": 60000 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum ViewRefresh {
+    /// Applies the changes made to the source table since the previous refresh.
+    Changes,
+    /// Recomputes the whole view every `every_ms` milliseconds.
+    Interval {
+        #[serde(rename = "everyMs")]
+        every_ms: u64,
+    },
+}
+
+/// Named query whose results are stored, see `MaterializedView`.
+///
+/// # Fields:
+/// - `name`: Name of the view, which is also the name its results are searched by.
+/// - `table`: Table the pipeline reads.
+/// - `pipeline`: Query computing the results.
+/// - `refresh`: How the results are kept up to date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewDefinition {
+    pub name: String,
+    pub table: String,
+    pub pipeline: Pipeline,
+    pub refresh: ViewRefresh,
+}
+
+#[derive(Debug)]
+struct ViewState {
+    changes: Option<broadcast::Receiver<ChangeEvent>>,
+    refreshed_at: Option<Instant>,
+}
+
+/// Results of a `Pipeline` stored in a hidden table of the database (see `SingleQueryManager::register_hidden_table`)
+/// named after the view, so expensive aggregations are computed once and read like any table, e.g.
+/// `SchemeJS.search("public", "orders_by_country", ...)`.
+///
+/// Views refreshed from the changes of their table are updated incrementally when their pipeline allows it: every
+/// stage after the leading `Match` stages and an optional `Group` is a `Match` or a `Project`. Every stored row
+/// is then tagged (`VIEW_KEY_COLUMN`) with the key it was computed from, the values of the grouping columns or the
+/// primary key of the source row, and a change only recomputes the rows of the keys it touched. Other pipelines,
+/// batches touching more than `MAX_INCREMENTAL_KEYS` keys and changes missed by a lagging subscription recompute
+/// the whole view. `Project` stages of incremental views keep the key columns.
+///
+/// Definitions live as long as the engine, they are not persisted across restarts.
+///
+/// # Fields:
+/// - `definition`: The view as it was defined.
+/// - `pipeline`: Pipeline computing its rows.
+/// - `key_columns`: Columns of the source table the rows are keyed by, `None` when the view can't be refreshed
+///   incrementally.
+/// - `state`: Changes not applied yet and time of the last refresh. Refreshes hold its lock.
+#[derive(Debug)]
+pub struct MaterializedView {
+    pub definition: ViewDefinition,
+    pipeline: Pipeline,
+    key_columns: Option<Vec<Column>>,
+    state: Mutex<ViewState>,
+}
+
+impl MaterializedView {
+    /// Whether changes are applied incrementally rather than by recomputing the view.
+    pub fn is_incremental(&self) -> bool {
+        self.key_columns.is_some()
+    }
+
+    /// Keys of the source rows (before and after the change) touched by `events`, `None` when the view isn't
+    /// incremental.
+    fn affected_keys(&self, events: &[ChangeEvent]) -> Option<BTreeSet<Vec<DataValue>>> {
+        let key_columns = self.key_columns.as_ref()?;
+
+        let mut keys = BTreeSet::new();
+        for event in events {
+            for row in std::iter::once(&event.row).chain(event.before.iter()) {
+                let row = RowJson::from(row.as_slice());
+                keys.insert(
+                    key_columns
+                        .iter()
+                        .map(|column| row.get_value(column).unwrap_or(DataValue::Null))
+                        .collect(),
+                );
+            }
+        }
+
+        Some(keys)
+    }
+
+    /// Value of `VIEW_KEY_COLUMN` for the rows computed from `key`, the JSON array of its values.
+    fn view_key(key: Vec<Value>) -> String {
+        Value::Array(key).to_string()
+    }
+
+    /// Value of `VIEW_KEY_COLUMN` for a row of the results, `None` when the view isn't incremental.
+    fn document_key(&self, document: &Value) -> Option<String> {
+        let key_columns = self.key_columns.as_ref()?;
+        Some(Self::view_key(
+            key_columns
+                .iter()
+                .map(|column| document.get(&column.name).cloned().unwrap_or(Value::Null))
+                .collect(),
+        ))
+    }
+}
+
+/// Columns of the source table the rows of an incremental view are keyed by, see `MaterializedView`.
+fn incremental_key(pipeline: &Pipeline, table: &Table) -> Option<Vec<String>> {
+    let mut stages = pipeline.stages.as_slice();
+    while let Some((PipelineStage::Match(_), rest)) = stages.split_first() {
+        stages = rest;
+    }
+
+    let (key, rest) = match stages.split_first() {
+        Some((PipelineStage::Group { by, .. }, rest)) => (by.clone(), rest),
+        _ => (vec![table.primary_key.clone()], stages),
+    };
+
+    rest.iter()
+        .all(|stage| matches!(stage, PipelineStage::Match(_) | PipelineStage::Project(_)))
+        .then_some(key)
+}
+
+/// Table storing the results of the view `name`, whose rows are described by `schema`. Columns accept any value
+/// the results hold: nothing is required, checked or defaulted.
+fn view_table(name: &str, schema: &Table) -> Table {
+    let mut table = Table::new(name)
+        .add_column(Column::new(VIEW_KEY_COLUMN, DataTypes::String))
+        .add_index(Index {
+            name: "viewkeyindx".to_string(),
+            members: vec![VIEW_KEY_COLUMN.to_string()],
+            index_type: IndexType::Hash,
+            unique: false,
+        });
+
+    for column in schema.columns.values() {
+        if table.columns.contains_key(&column.name) {
+            continue;
+        }
+
+        let mut column = column.clone();
+        column.required = false;
+        column.primary_key = false;
+        column.default_value = None;
+        column.check = None;
+        table = table.add_column(column);
+    }
+
+    table
+}
+
+/// Materialized views of a database, refreshed by `refresh_due` (see the views task of `SchemeJsManager`).
+///
+/// # Fields:
+/// - `query_manager`: Query manager of the database, holding both the source tables and the tables of the views.
+/// - `views`: Views by name.
+#[derive(Debug)]
+pub struct MaterializedViews {
+    query_manager: Arc<SingleQueryManager<RowJson>>,
+    views: RwLock<HashMap<String, Arc<MaterializedView>>>,
+}
+
+impl MaterializedViews {
+    pub fn new(query_manager: Arc<SingleQueryManager<RowJson>>) -> Self {
+        Self {
+            query_manager,
+            views: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Defines a view and computes its rows, replacing the view of the same name if any.
+    ///
+    /// # Returns:
+    /// - `Result<Arc<MaterializedView>, QueryError>`: `InvalidTable` for an unknown source table, `InvalidColumn`
+    ///   when the pipeline reads a field its previous stages don't produce.
+    pub fn create(&self, definition: ViewDefinition) -> Result<Arc<MaterializedView>, QueryError> {
+        let table = self
+            .query_manager
+            .tables
+            .get(&definition.table)
+            .map(|table_shard| table_shard.table.clone())
+            .ok_or_else(|| QueryError::InvalidTable(definition.table.clone()))?;
+
+        let key = incremental_key(&definition.pipeline, &table);
+        let pipeline = match &key {
+            Some(key) => Pipeline {
+                stages: definition
+                    .pipeline
+                    .stages
+                    .iter()
+                    .map(|stage| match stage {
+                        PipelineStage::Project(fields) => {
+                            let mut fields = fields.clone();
+                            for column in key {
+                                if !fields.contains(column) {
+                                    fields.push(column.clone());
+                                }
+                            }
+                            PipelineStage::Project(fields)
+                        }
+                        stage => stage.clone(),
+                    })
+                    .collect(),
+            },
+            None => definition.pipeline.clone(),
+        };
+        let key_columns = key
+            .map(|key| {
+                key.iter()
+                    .map(|name| {
+                        table
+                            .resolve_column(name.as_str())
+                            .map(|column| column.into_owned())
+                            .ok_or_else(|| QueryError::InvalidColumn(name.clone()))
+                    })
+                    .collect::<Result<Vec<Column>, QueryError>>()
+            })
+            .transpose()?;
+        let schema = pipeline.schema(&table)?;
+
+        // Subscribed before the rows are computed, so changes made meanwhile are applied on the next refresh
+        let changes = match definition.refresh {
+            ViewRefresh::Changes => Some(self.query_manager.changes().subscribe()),
+            ViewRefresh::Interval { .. } => None,
+        };
+
+        self.query_manager.register_hidden_table(
+            view_table(&definition.name, &schema),
+            TableStorage::default(),
+        )?;
+
+        let view = Arc::new(MaterializedView {
+            definition,
+            pipeline,
+            key_columns,
+            state: Mutex::new(ViewState {
+                changes,
+                refreshed_at: None,
+            }),
+        });
+        self.rebuild(&view, &mut view.state.lock().unwrap())?;
+
+        self.views
+            .write()
+            .unwrap()
+            .insert(view.definition.name.clone(), view.clone());
+        Ok(view)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<MaterializedView>> {
+        self.views.read().unwrap().get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.views.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Recomputes the whole view `name`.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of rows of the view, `InvalidView` for an unknown view.
+    pub fn refresh(&self, name: &str) -> Result<usize, QueryError> {
+        let view = self
+            .get(name)
+            .ok_or_else(|| QueryError::InvalidView(name.to_string()))?;
+        let mut state = view.state.lock().unwrap();
+        self.rebuild(&view, &mut state)
+    }
+
+    /// Refreshes the views that are due: the ones whose source table changed since their previous refresh and the
+    /// ones whose interval elapsed.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of views refreshed.
+    pub fn refresh_due(&self) -> Result<usize, QueryError> {
+        let views: Vec<Arc<MaterializedView>> =
+            self.views.read().unwrap().values().cloned().collect();
+
+        let mut refreshed = 0;
+        for view in views {
+            let mut state = view.state.lock().unwrap();
+            let due = match view.definition.refresh {
+                ViewRefresh::Changes => self.apply_changes(&view, &mut state)?,
+                ViewRefresh::Interval { every_ms } => {
+                    let due = state.refreshed_at.map_or(true, |refreshed_at| {
+                        refreshed_at.elapsed() >= Duration::from_millis(every_ms)
+                    });
+                    if due {
+                        self.rebuild(&view, &mut state)?;
+                    }
+                    due
+                }
+            };
+
+            if due {
+                refreshed += 1;
+            }
+        }
+
+        Ok(refreshed)
+    }
+
+    /// Applies the changes of the source table received since the previous refresh.
+    ///
+    /// # Returns:
+    /// - `Result<bool, QueryError>`: Whether there were any.
+    fn apply_changes(
+        &self,
+        view: &MaterializedView,
+        state: &mut ViewState,
+    ) -> Result<bool, QueryError> {
+        let Some(changes) = state.changes.as_mut() else {
+            return Ok(false);
+        };
+
+        let mut events = vec![];
+        let mut lagged = false;
+        loop {
+            match changes.try_recv() {
+                Ok(event) if event.table == view.definition.table => events.push(event),
+                Ok(_) => {}
+                Err(TryRecvError::Lagged(_)) => lagged = true,
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+            }
+        }
+        if events.is_empty() && !lagged {
+            return Ok(false);
+        }
+
+        let keys = match lagged {
+            true => None,
+            false => view.affected_keys(&events),
+        };
+        match keys {
+            Some(keys) if keys.len() <= MAX_INCREMENTAL_KEYS => {
+                self.refresh_keys(view, keys)?;
+                state.refreshed_at = Some(Instant::now());
+            }
+            _ => {
+                self.rebuild(view, state)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Recomputes the rows of the view computed from the source rows holding `keys`.
+    fn refresh_keys(
+        &self,
+        view: &MaterializedView,
+        keys: BTreeSet<Vec<DataValue>>,
+    ) -> Result<(), QueryError> {
+        let key_columns = view.key_columns.as_deref().unwrap_or_default();
+
+        let restriction = json!({
+            "or": keys
+                .iter()
+                .map(|key| {
+                    json!({
+                        "and": key_columns
+                            .iter()
+                            .zip(key.iter())
+                            .map(|(column, value)| match value {
+                                DataValue::Null => {
+                                    json!({ "key": column.name, "filterType": "is_null" })
+                                }
+                                value => json!({
+                                    "key": column.name,
+                                    "filterType": "=",
+                                    "value": value.to_json(),
+                                }),
+                            })
+                            .collect::<Vec<Value>>()
+                    })
+                })
+                .collect::<Vec<Value>>()
+        });
+        let mut pipeline = view.pipeline.clone();
+        pipeline.stages.insert(0, PipelineStage::Match(restriction));
+
+        let documents = self
+            .query_manager
+            .search_manager()
+            .pipeline(view.definition.table.clone(), &pipeline)?;
+
+        let view_keys = keys
+            .into_iter()
+            .map(|key| {
+                DataValue::String(MaterializedView::view_key(
+                    key.iter().map(|value| value.to_json()).collect(),
+                ))
+            })
+            .collect();
+        self.query_manager.delete(
+            view.definition.name.clone(),
+            &QueryOps::Condition(QueryVal {
+                key: VIEW_KEY_COLUMN.to_string(),
+                filter_type: "in".to_string(),
+                value: DataValue::Array(view_keys),
+            }),
+        )?;
+        self.insert_documents(view, documents)?;
+
+        Ok(())
+    }
+
+    /// Replaces the rows of the view with the results of its pipeline.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of rows of the view.
+    fn rebuild(&self, view: &MaterializedView, state: &mut ViewState) -> Result<usize, QueryError> {
+        // The results cover every change received until now
+        if let Some(changes) = state.changes.as_mut() {
+            while !matches!(
+                changes.try_recv(),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed)
+            ) {}
+        }
+
+        let documents = self
+            .query_manager
+            .search_manager()
+            .pipeline(view.definition.table.clone(), &view.pipeline)?;
+
+        self.query_manager
+            .delete(view.definition.name.clone(), &QueryOps::And(vec![]))?;
+        let rows = self.insert_documents(view, documents)?;
+        self.query_manager.compact_table(&view.definition.name)?;
+
+        state.refreshed_at = Some(Instant::now());
+        Ok(rows)
+    }
+
+    fn insert_documents(
+        &self,
+        view: &MaterializedView,
+        documents: Vec<Value>,
+    ) -> Result<usize, QueryError> {
+        let rows: Vec<RowJson> = documents
+            .into_iter()
+            .map(|mut document| {
+                if let Some(key) = view.document_key(&document) {
+                    document[VIEW_KEY_COLUMN] = Value::String(key);
+                }
+                document["_uid"] = Value::String(Uuid::new_v4().to_string());
+                RowJson::from(RowData {
+                    table: view.definition.name.clone(),
+                    value: document,
+                })
+            })
+            .collect();
+
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        Ok(self.query_manager.insert_many(rows)?.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::SchemeJsEngine;
+    use crate::views::{ViewDefinition, ViewRefresh, VIEW_KEY_COLUMN};
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::ops::aggregate::Aggregate;
+    use schemajs_query::ops::pipeline::Pipeline;
+    use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+    use schemajs_query::row_json::{RowData, RowJson};
+    use schemajs_query::search::search_opts::SortDirection;
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_materialized_views() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users")
+                .add_column(Column::new("user_country", DataTypes::String))
+                .add_column(Column::new("user_age", DataTypes::Number)),
+            TableStorage::default(),
+        )
+        .unwrap();
+
+        let insert = |country: &str, age: u64| {
+            db.query_manager
+                .insert(RowJson::from(RowData {
+                    table: "users".to_string(),
+                    value: json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_country": country,
+                        "user_age": age,
+                    }),
+                }))
+                .unwrap()
+        };
+        let condition = |key: &str, value: DataValue| {
+            QueryOps::Condition(QueryVal {
+                key: key.to_string(),
+                filter_type: "=".to_string(),
+                value,
+            })
+        };
+        let search = |table: &str, ops: &QueryOps| -> Vec<Value> {
+            db.query_manager
+                .search_manager()
+                .search(table.to_string(), ops)
+                .unwrap()
+                .into_iter()
+                .map(|row| row.value.value)
+                .collect()
+        };
+        let count_of = |country: &str| {
+            let rows = search(
+                "users_by_country",
+                &condition("user_country", DataValue::String(country.to_string())),
+            );
+            rows.first().map(|row| row["count"].clone())
+        };
+
+        insert("US", 30);
+        insert("US", 40);
+        insert("CO", 25);
+
+        // The grouping column is kept by the projection, it keys the rows of the view
+        engine
+            .create_view(
+                &db_name,
+                ViewDefinition {
+                    name: "users_by_country".to_string(),
+                    table: "users".to_string(),
+                    pipeline: Pipeline::new()
+                        .group(&["user_country"], vec![Aggregate::count()])
+                        .project(&["count"]),
+                    refresh: ViewRefresh::Changes,
+                },
+            )
+            .unwrap();
+        let view = db.views.get("users_by_country").unwrap();
+        assert!(view.is_incremental());
+        assert_eq!(count_of("US"), Some(json!(2)));
+        assert_eq!(count_of("CO"), Some(json!(1)));
+        assert_eq!(
+            search("users_by_country", &QueryOps::And(vec![]))[0][VIEW_KEY_COLUMN]
+                .as_str()
+                .map(|key| key.starts_with('[')),
+            Some(true)
+        );
+        assert!(!db
+            .query_manager
+            .table_names
+            .read()
+            .unwrap()
+            .contains(&"users_by_country".to_string()));
+
+        // Only the keys touched by the changes are recomputed
+        insert("CO", 50);
+        db.query_manager
+            .delete(
+                "users".to_string(),
+                &condition("user_age", DataValue::Number(30.into())),
+            )
+            .unwrap();
+        assert_eq!(db.views.refresh_due().unwrap(), 1);
+        assert_eq!(count_of("US"), Some(json!(1)));
+        assert_eq!(count_of("CO"), Some(json!(2)));
+        assert_eq!(db.views.refresh_due().unwrap(), 0);
+
+        db.query_manager
+            .delete(
+                "users".to_string(),
+                &condition("user_country", DataValue::String("US".to_string())),
+            )
+            .unwrap();
+        db.views.refresh_due().unwrap();
+        assert_eq!(count_of("US"), None);
+        assert_eq!(search("users_by_country", &QueryOps::And(vec![])).len(), 1);
+
+        // Views refreshed on a schedule wait for their interval, unless refreshed by hand
+        engine
+            .create_view(
+                &db_name,
+                ViewDefinition {
+                    name: "oldest_user".to_string(),
+                    table: "users".to_string(),
+                    pipeline: Pipeline::new()
+                        .sort("user_age", SortDirection::Desc)
+                        .limit(1),
+                    refresh: ViewRefresh::Interval {
+                        every_ms: 3_600_000,
+                    },
+                },
+            )
+            .unwrap();
+        assert!(!db.views.get("oldest_user").unwrap().is_incremental());
+        let oldest = || search("oldest_user", &QueryOps::And(vec![]))[0]["user_age"].clone();
+        assert_eq!(oldest(), json!(50));
+
+        insert("MX", 70);
+        assert_eq!(db.views.refresh_due().unwrap(), 1);
+        assert_eq!(oldest(), json!(50));
+        assert_eq!(db.views.refresh("oldest_user").unwrap(), 1);
+        assert_eq!(oldest(), json!(70));
+        assert_eq!(db.views.names(), vec!["oldest_user", "users_by_country"]);
+
+        // Views can't take the name of a table nor read an unknown one
+        let definition = |name: &str, table: &str| ViewDefinition {
+            name: name.to_string(),
+            table: table.to_string(),
+            pipeline: Pipeline::new(),
+            refresh: ViewRefresh::Changes,
+        };
+        assert!(engine
+            .create_view(&db_name, definition("users", "users"))
+            .is_err());
+        assert!(engine
+            .create_view(&db_name, definition("products_view", "products"))
+            .is_err());
+        assert!(db.views.refresh("missing").unwrap_err().is_invalid_view());
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
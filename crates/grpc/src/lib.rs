@@ -0,0 +1,8 @@
+pub mod replication;
+pub mod server;
+pub mod service;
+
+/// Types and service generated from `proto/schemajs.proto`.
+pub mod proto {
+    tonic::include_proto!("schemajs");
+}
@@ -0,0 +1,164 @@
+use crate::proto::scheme_js_client::SchemeJsClient;
+use crate::proto::{ChangeKind as ProtoChangeKind, ReplicateRequest, ReplicatedChange};
+use anyhow::{anyhow, Result};
+use schemajs_config::SchemeJsReplicationConfig;
+use schemajs_data::io_pool::IoPool;
+use schemajs_engine::engine::SchemeJsEngine;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::changes::{ChangeEvent, ChangeKind};
+use schemajs_query::managers::single::SingleQueryManager;
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_query::row_json::RowJson;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use tonic::metadata::MetadataValue;
+use tonic::Request;
+
+/// Time a follower waits before reconnecting to its leader.
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Position of a follower in the change log of a database of its leader.
+///
+/// # Fields:
+/// - `log_id`: Log the changes were applied from, empty until the first change.
+/// - `sequence`: Sequence of the last applied change.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplicationPosition {
+    pub log_id: String,
+    pub sequence: u64,
+}
+
+/// Applies a change streamed by the leader (see `Replicate` in `proto/schemajs.proto`) to `query_manager`.
+/// A `Reset` drops every row of its table, the rows of the leader follow it.
+/// `Synced` and `Lagged` (never sent by `Replicate`) don't change anything.
+pub fn apply_change(
+    query_manager: &SingleQueryManager<RowJson>,
+    change: ReplicatedChange,
+) -> Result<(), QueryError> {
+    let kind = match ProtoChangeKind::try_from(change.kind) {
+        Ok(ProtoChangeKind::Insert) => ChangeKind::Insert,
+        Ok(ProtoChangeKind::Update) => ChangeKind::Update,
+        Ok(ProtoChangeKind::Delete) => ChangeKind::Delete,
+        Ok(ProtoChangeKind::Reset) => {
+            query_manager.delete(change.table, &QueryOps::And(vec![]))?;
+            return Ok(());
+        }
+        Ok(ProtoChangeKind::Synced) | Ok(ProtoChangeKind::Lagged) | Err(_) => return Ok(()),
+    };
+
+    query_manager.apply_change(&ChangeEvent::new(&change.table, kind, change.row))
+}
+
+/// Replicates the database `db_name` of the leader at `leader` into the one of `engine` with the same name,
+/// from `position` on, sending `authorization` to the leader if given. Returns once the stream of the leader
+/// ends or fails, or once a change can't be applied (changes of tables the follower doesn't have are skipped),
+/// `position` being the last applied change.
+pub async fn follow_database(
+    engine: &SchemeJsEngine,
+    leader: &str,
+    authorization: Option<&str>,
+    db_name: &str,
+    position: &mut ReplicationPosition,
+) -> Result<()> {
+    let query_manager = engine
+        .find_by_name_ref(db_name.to_string())
+        .ok_or_else(|| anyhow!("Unknown database '{}'", db_name))?
+        .query_manager
+        .clone();
+
+    let mut client = SchemeJsClient::connect(leader.to_string()).await?;
+    let mut request = Request::new(ReplicateRequest {
+        database: db_name.to_string(),
+        log_id: position.log_id.clone(),
+        after: position.sequence,
+    });
+    if let Some(authorization) = authorization {
+        request
+            .metadata_mut()
+            .insert("authorization", MetadataValue::try_from(authorization)?);
+    }
+    let mut changes = client.replicate(request).await?.into_inner();
+
+    // The position only moves once the rows of the leader are copied, a copy that didn't complete starts over
+    let mut copying = false;
+    while let Some(change) = changes.next().await {
+        let change = change?;
+        let (log_id, sequence, table) =
+            (change.log_id.clone(), change.sequence, change.table.clone());
+        match ProtoChangeKind::try_from(change.kind) {
+            Ok(ProtoChangeKind::Reset) => {
+                copying = true;
+                *position = ReplicationPosition::default();
+            }
+            Ok(ProtoChangeKind::Synced) => copying = false,
+            _ => {}
+        }
+
+        let query_manager = query_manager.clone();
+        match IoPool::global()
+            .run(move || apply_change(&query_manager, change))
+            .await
+        {
+            Ok(()) => {}
+            // Tables missing from the follower are skipped, the rest of the database keeps being replicated
+            Err(e @ QueryError::InvalidTable(_)) => {
+                tracing::warn!(db = %db_name, table = %table, sequence, error = %e, "Skipping replicated change");
+            }
+            // The position stays before the change, so it's applied again once reconnected
+            Err(e) => {
+                return Err(anyhow!(
+                    "Could not apply change {} of table '{}': {}",
+                    sequence,
+                    table,
+                    e
+                ))
+            }
+        }
+
+        if !copying {
+            *position = ReplicationPosition { log_id, sequence };
+        }
+    }
+
+    Ok(())
+}
+
+/// Replicates every database of `engine` from the leader of `config`, reconnecting whenever its stream ends.
+/// Runs until the process stops.
+pub async fn follow(engine: Arc<SchemeJsEngine>, config: SchemeJsReplicationConfig) -> Result<()> {
+    let leader = config
+        .leader
+        .ok_or_else(|| anyhow!("Followers require the `leader` of `[replication]`"))?;
+    tracing::info!(leader = %leader, "Following leader");
+
+    let mut followers = vec![];
+    for db in engine.databases() {
+        let engine = engine.clone();
+        let leader = leader.clone();
+        let authorization = config.authorization.clone();
+        followers.push(tokio::spawn(async move {
+            let mut position = ReplicationPosition::default();
+            loop {
+                if let Err(e) = follow_database(
+                    &engine,
+                    &leader,
+                    authorization.as_deref(),
+                    &db.name,
+                    &mut position,
+                )
+                .await
+                {
+                    tracing::warn!(db = %db.name, error = %e, "Lost the leader, reconnecting");
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }));
+    }
+
+    for follower in followers {
+        follower.await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::proto::scheme_js_server::SchemeJsServer;
+use crate::service::SchemeJsService;
+use schemajs_config::SchemeJsGrpcConfig;
+use schemajs_engine::access::Principal;
+use schemajs_engine::auth::Authenticator;
+use schemajs_engine::engine::SchemeJsEngine;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tonic::transport::Server;
+use tonic::{Request, Status};
+
+/// Authenticates `request` against `auth` from its `authorization` metadata, setting the principal it runs for
+/// as an extension of the request (see `service::principal`). Requests run for the engine itself without `auth`,
+/// which `serve` refuses when the engine has access control.
+pub fn authenticate(
+    auth: Option<&Authenticator>,
+    mut request: Request<()>,
+) -> Result<Request<()>, Status> {
+    let principal = match auth {
+        Some(auth) => {
+            let authorization = request
+                .metadata()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok());
+            auth.authenticate(authorization)
+                .map_err(|e| Status::unauthenticated(e.to_string()))?
+        }
+        None => Principal::System,
+    };
+
+    request.extensions_mut().insert(principal);
+    Ok(request)
+}
+
+/// Serves the query API of `engine` over gRPC (see `proto/schemajs.proto`), as configured under `[grpc]`
+/// in `SchemeJS.toml`. Requests are authenticated by `auth` when given, before reaching the service.
+/// Fails without serving anything when there is no `auth`, every request would be allowed everything, unless
+/// `allow_anonymous` is set (and the engine has no access control). Runs until the server fails.
+pub async fn serve(
+    engine: Arc<SchemeJsEngine>,
+    config: SchemeJsGrpcConfig,
+    auth: Option<Arc<Authenticator>>,
+) -> anyhow::Result<()> {
+    if engine.access.is_some() && auth.is_none() {
+        anyhow::bail!(
+            "Serving the gRPC API with access control ([access]) requires an [auth] section"
+        );
+    }
+    if !config.allow_anonymous && auth.is_none() {
+        anyhow::bail!(
+            "Serving the gRPC API requires an [auth] section, or `allow_anonymous = true` under [grpc]"
+        );
+    }
+
+    let address: SocketAddr = config.address().parse()?;
+    tracing::info!(address = %address, "Serving the gRPC API");
+
+    Server::builder()
+        .add_service(SchemeJsServer::with_interceptor(
+            SchemeJsService::new(engine),
+            move |request: Request<()>| authenticate(auth.as_deref(), request),
+        ))
+        .serve(address)
+        .await?;
+
+    Ok(())
+}
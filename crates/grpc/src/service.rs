@@ -0,0 +1,849 @@
+use crate::proto::scheme_js_server::SchemeJs;
+use crate::proto::{
+    Change, ChangeKind as ProtoChangeKind, DeleteRequest, DeleteResponse, InsertRequest,
+    InsertResponse, ReplicateRequest, ReplicatedChange, SearchRequest, SearchResponse,
+    StreamChangesRequest, UpdateRequest, UpdateResponse,
+};
+use schemajs_data::io_pool::IoPool;
+use schemajs_engine::access::{Principal, Privilege};
+use schemajs_engine::engine::SchemeJsEngine;
+use schemajs_primitives::table::Table;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::changes::{ChangeEvent, ChangeKind};
+use schemajs_query::managers::single::SingleQueryManager;
+use schemajs_query::ops::query_ops::{values_from_json, QueryOps};
+use schemajs_query::row_json::{RowData, RowJson};
+use schemajs_query::serializer::compact::is_compact;
+use schemajs_query::serializer::registry::is_registered;
+use schemajs_query::serializer::RowSerializer;
+use serde_json::Value;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Status of the gRPC response of a query failing with `e`.
+pub fn query_status(e: QueryError) -> Status {
+    let message = e.to_string();
+    match e {
+        QueryError::InvalidTable(_) => Status::not_found(message),
+        QueryError::UniqueViolation(_, _) | QueryError::DuplicatePrimaryKey(_, _) => {
+            Status::already_exists(message)
+        }
+        QueryError::ReadOnly(_) => Status::failed_precondition(message),
+        QueryError::AccessDenied(_) => Status::permission_denied(message),
+        QueryError::QuotaExceeded(_) => Status::resource_exhausted(message),
+        QueryError::Timeout => Status::deadline_exceeded(message),
+        QueryError::ShardError(_) => Status::internal(message),
+        _ => Status::invalid_argument(message),
+    }
+}
+
+/// Principal a request runs for, set as an extension of the request by `server::authenticate`.
+/// Requests that didn't go through it are rejected rather than run for anyone.
+pub fn principal<T>(request: &Request<T>) -> Result<Principal, Status> {
+    request
+        .extensions()
+        .get::<Principal>()
+        .cloned()
+        .ok_or_else(|| Status::unauthenticated("The request has no principal"))
+}
+
+// JSON document of the field `name` of a request, `Value::Null` when the field is empty
+fn parse_json(value: &str, name: &str) -> Result<Value, Status> {
+    match value.is_empty() {
+        true => Ok(Value::Null),
+        false => serde_json::from_str(value)
+            .map_err(|e| Status::invalid_argument(format!("Invalid '{}': {}", name, e))),
+    }
+}
+
+/// Kind of the gRPC change of a change of the query manager.
+pub fn proto_kind(kind: ChangeKind) -> ProtoChangeKind {
+    match kind {
+        ChangeKind::Insert => ProtoChangeKind::Insert,
+        ChangeKind::Update => ProtoChangeKind::Update,
+        ChangeKind::Delete => ProtoChangeKind::Delete,
+    }
+}
+
+fn replicated(log_id: &str, event: ChangeEvent) -> ReplicatedChange {
+    // Rows are sent as JSON, followers may not know the layout or the serializer they were written with
+    let row = match is_compact(&event.row) || is_registered(&event.row) {
+        true => RowJson::from(event.row.as_slice())
+            .serialize()
+            .unwrap_or(event.row),
+        false => event.row,
+    };
+
+    ReplicatedChange {
+        log_id: log_id.to_string(),
+        sequence: event.sequence,
+        table: event.table,
+        kind: proto_kind(event.kind) as i32,
+        row,
+    }
+}
+
+// Every row of every table of `query_manager` as of the change at `sequence`, each table preceded by a `Reset`,
+// followed by `Synced`
+fn copy_rows(
+    query_manager: &SingleQueryManager<RowJson>,
+    log_id: &str,
+    sequence: u64,
+) -> Result<Vec<ReplicatedChange>, QueryError> {
+    let mut changes = vec![];
+    let table_names = query_manager.table_names.read().unwrap().clone();
+    for table_name in table_names {
+        if let Some(table_shard) = query_manager.tables.get(&table_name) {
+            table_shard.temps.reconcile_all();
+        }
+        let rows = query_manager
+            .search_manager()
+            .search(table_name.clone(), &QueryOps::And(vec![]))?;
+
+        changes.push(ReplicatedChange {
+            log_id: log_id.to_string(),
+            sequence,
+            table: table_name.clone(),
+            kind: ProtoChangeKind::Reset as i32,
+            row: vec![],
+        });
+        for row in rows {
+            changes.push(ReplicatedChange {
+                log_id: log_id.to_string(),
+                sequence,
+                table: table_name.clone(),
+                kind: ProtoChangeKind::Insert as i32,
+                row: row
+                    .serialize()
+                    .map_err(|_| QueryError::InvalidSerialization)?,
+            });
+        }
+    }
+    changes.push(ReplicatedChange {
+        log_id: log_id.to_string(),
+        sequence,
+        table: String::new(),
+        kind: ProtoChangeKind::Synced as i32,
+        row: vec![],
+    });
+
+    Ok(changes)
+}
+
+/// Implementation of the `SchemeJs` gRPC service (see `proto/schemajs.proto`) over the databases of an engine.
+pub struct SchemeJsService {
+    engine: Arc<SchemeJsEngine>,
+}
+
+impl SchemeJsService {
+    pub fn new(engine: Arc<SchemeJsEngine>) -> Self {
+        Self { engine }
+    }
+
+    // Table `table_name` of `db_name`, once `principal` is checked to hold `privilege` on it
+    fn table(
+        &self,
+        principal: &Principal,
+        db_name: &str,
+        table_name: &str,
+        privilege: Privilege,
+    ) -> Result<(Arc<SingleQueryManager<RowJson>>, Arc<Table>), Status> {
+        self.engine
+            .authorize(principal, db_name, Some(table_name), privilege)
+            .map_err(query_status)?;
+
+        let query_manager = self
+            .engine
+            .find_by_name_ref(db_name.to_string())
+            .ok_or_else(|| Status::not_found(format!("Unknown database '{}'", db_name)))?
+            .query_manager
+            .clone();
+        let table = query_manager
+            .tables
+            .get(table_name)
+            .map(|table_shard| table_shard.table.clone())
+            .ok_or_else(|| query_status(QueryError::InvalidTable(table_name.to_string())))?;
+
+        Ok((query_manager, table))
+    }
+}
+
+#[tonic::async_trait]
+impl SchemeJs for SchemeJsService {
+    async fn insert(
+        &self,
+        request: Request<InsertRequest>,
+    ) -> Result<Response<InsertResponse>, Status> {
+        let principal = principal(&request)?;
+        let request = request.into_inner();
+        let (query_manager, _) = self.table(
+            &principal,
+            &request.database,
+            &request.table,
+            Privilege::Write,
+        )?;
+
+        let mut rows = Vec::with_capacity(request.rows.len());
+        for row in request.rows.iter() {
+            let mut row = parse_json(row, "rows")?;
+            match row {
+                Value::Object(ref mut obj) => {
+                    obj.insert(
+                        "_uid".to_string(),
+                        Value::String(Uuid::new_v4().to_string()),
+                    );
+                }
+                _ => return Err(Status::invalid_argument("Rows must be JSON objects")),
+            }
+
+            rows.push(RowJson::from(RowData {
+                table: request.table.clone(),
+                value: row,
+            }));
+        }
+
+        let span =
+            tracing::info_span!("grpc_insert", db = %request.database, table = %request.table);
+        let uids = IoPool::global()
+            .run(move || query_manager.insert_many(rows))
+            .instrument(span)
+            .await
+            .map_err(query_status)?;
+
+        Ok(Response::new(InsertResponse {
+            uids: uids.iter().map(|uid| uid.to_string()).collect(),
+        }))
+    }
+
+    async fn search(
+        &self,
+        request: Request<SearchRequest>,
+    ) -> Result<Response<SearchResponse>, Status> {
+        let principal = principal(&request)?;
+        let request = request.into_inner();
+        let (query_manager, table) = self.table(
+            &principal,
+            &request.database,
+            &request.table,
+            Privilege::Read,
+        )?;
+        let ops = QueryOps::from_json(&table, &parse_json(&request.query, "query")?)
+            .map_err(query_status)?;
+
+        let _span =
+            tracing::info_span!("grpc_search", db = %request.database, table = %request.table)
+                .entered();
+        let rows = query_manager
+            .search_manager()
+            .set_timeout(request.timeout.map(Duration::from_millis))
+            .search(request.table, &ops)
+            .map_err(query_status)?;
+
+        Ok(Response::new(SearchResponse {
+            rows: rows
+                .iter()
+                .map(|row| row.to_json(&table).to_string())
+                .collect(),
+        }))
+    }
+
+    async fn update(
+        &self,
+        request: Request<UpdateRequest>,
+    ) -> Result<Response<UpdateResponse>, Status> {
+        let principal = principal(&request)?;
+        let request = request.into_inner();
+        let (query_manager, table) = self.table(
+            &principal,
+            &request.database,
+            &request.table,
+            Privilege::Write,
+        )?;
+        let ops = QueryOps::from_json(&table, &parse_json(&request.query, "query")?)
+            .map_err(query_status)?;
+        let values = values_from_json(&table, &parse_json(&request.changes, "changes")?)
+            .map_err(query_status)?;
+
+        let span =
+            tracing::info_span!("grpc_update", db = %request.database, table = %request.table);
+        let table_name = request.table;
+        let updated = IoPool::global()
+            .run(move || query_manager.update(table_name, &ops, &values))
+            .instrument(span)
+            .await
+            .map_err(query_status)?;
+
+        Ok(Response::new(UpdateResponse {
+            updated: updated as u64,
+        }))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        let principal = principal(&request)?;
+        let request = request.into_inner();
+        if request.query.is_empty() {
+            return Err(Status::invalid_argument(
+                "A query is required, '{}' deletes every row",
+            ));
+        }
+
+        let (query_manager, table) = self.table(
+            &principal,
+            &request.database,
+            &request.table,
+            Privilege::Write,
+        )?;
+        let ops = QueryOps::from_json(&table, &parse_json(&request.query, "query")?)
+            .map_err(query_status)?;
+
+        let span =
+            tracing::info_span!("grpc_delete", db = %request.database, table = %request.table);
+        let table_name = request.table;
+        let deleted = IoPool::global()
+            .run(move || query_manager.delete(table_name, &ops))
+            .instrument(span)
+            .await
+            .map_err(query_status)?;
+
+        Ok(Response::new(DeleteResponse {
+            deleted: deleted as u64,
+        }))
+    }
+
+    type StreamChangesStream = Pin<Box<dyn Stream<Item = Result<Change, Status>> + Send>>;
+
+    async fn stream_changes(
+        &self,
+        request: Request<StreamChangesRequest>,
+    ) -> Result<Response<Self::StreamChangesStream>, Status> {
+        let principal = principal(&request)?;
+        let request = request.into_inner();
+        let (query_manager, table) = self.table(
+            &principal,
+            &request.database,
+            &request.table,
+            Privilege::Read,
+        )?;
+        let ops = QueryOps::from_json(&table, &parse_json(&request.query, "query")?)
+            .map_err(query_status)?;
+
+        let table_name = request.table;
+        let changes =
+            BroadcastStream::new(query_manager.changes().subscribe()).filter_map(move |change| {
+                let event = match change {
+                    Ok(event) => event,
+                    Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                        return Some(Ok(Change {
+                            kind: ProtoChangeKind::Lagged as i32,
+                            table: table_name.clone(),
+                            row: String::new(),
+                            missed,
+                        }))
+                    }
+                };
+                if event.table != table_name {
+                    return None;
+                }
+
+                // The table is read for every change, as it can be reloaded while the stream is open
+                let table = query_manager.tables.get(&event.table)?.table.clone();
+                let row = RowJson::from(event.row.as_slice());
+                if !ops.matches(&table, &row) {
+                    return None;
+                }
+
+                Some(Ok(Change {
+                    kind: proto_kind(event.kind) as i32,
+                    table: event.table,
+                    row: row.to_json(&table).to_string(),
+                    missed: 0,
+                }))
+            });
+
+        Ok(Response::new(Box::pin(changes)))
+    }
+
+    type ReplicateStream = Pin<Box<dyn Stream<Item = Result<ReplicatedChange, Status>> + Send>>;
+
+    async fn replicate(
+        &self,
+        request: Request<ReplicateRequest>,
+    ) -> Result<Response<Self::ReplicateStream>, Status> {
+        let principal = principal(&request)?;
+        let request = request.into_inner();
+        // Followers copy every row, they have to be allowed to read the whole database
+        self.engine
+            .authorize(&principal, &request.database, None, Privilege::Read)
+            .map_err(query_status)?;
+        let query_manager = self
+            .engine
+            .find_by_name_ref(request.database.clone())
+            .ok_or_else(|| Status::not_found(format!("Unknown database '{}'", request.database)))?
+            .query_manager
+            .clone();
+        if query_manager.changes().history_size() == 0 {
+            return Err(Status::failed_precondition(format!(
+                "Database '{}' is not replicated, replication has to be configured with `role = \"leader\"`",
+                request.database
+            )));
+        }
+
+        let log_id = query_manager.id.to_string();
+        let resumed = match request.log_id == log_id {
+            true => query_manager.changes().subscribe_from(request.after),
+            false => None,
+        };
+
+        // Followers that can't catch up from the log copy every row first
+        let (backlog, receiver) = match resumed {
+            Some((backlog, receiver)) => (
+                backlog
+                    .into_iter()
+                    .map(|event| replicated(&log_id, event))
+                    .collect(),
+                receiver,
+            ),
+            None => {
+                let (sequence, receiver) = query_manager.changes().subscribe_at_sequence();
+                let span = tracing::info_span!("grpc_replicate_copy", db = %request.database);
+                let copy_log_id = log_id.clone();
+                let copy = IoPool::global()
+                    .run(move || copy_rows(&query_manager, &copy_log_id, sequence))
+                    .instrument(span)
+                    .await
+                    .map_err(query_status)?;
+                (copy, receiver)
+            }
+        };
+
+        // Falling behind ends the stream, the follower catches up from the log when reconnecting
+        let live = BroadcastStream::new(receiver).map(move |change| match change {
+            Ok(event) => Ok(replicated(&log_id, event)),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => Err(Status::data_loss(format!(
+                "Fell behind the leader by {} changes",
+                missed
+            ))),
+        });
+
+        Ok(Response::new(Box::pin(
+            tokio_stream::iter(backlog.into_iter().map(Ok)).chain(live),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proto::scheme_js_server::SchemeJs;
+    use crate::proto::{
+        ChangeKind, DeleteRequest, InsertRequest, ReplicateRequest, SearchRequest,
+        StreamChangesRequest, UpdateRequest,
+    };
+    use crate::replication::apply_change;
+    use crate::service::SchemeJsService;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_engine::access::{
+        AccessControl, Grant, Principal, Privilege, Role, User, ANY_DATABASE, SYSTEM_DATABASE,
+        USERS_TABLE,
+    };
+    use schemajs_engine::engine::SchemeJsEngine;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::ops::query_ops::QueryOps;
+    use schemajs_query::row_json::{RowData, RowJson};
+    use serde_json::json;
+    use std::sync::Arc;
+    use tokio_stream::StreamExt;
+    use tonic::{Code, Request};
+    use uuid::Uuid;
+
+    // Request run for the engine itself, as authenticated by the server without `[auth]`
+    fn request<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.extensions_mut().insert(Principal::System);
+        request
+    }
+
+    #[tokio::test]
+    pub async fn test_grpc_service() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let engine = Arc::new(engine);
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            TableStorage::default(),
+        )
+        .unwrap();
+        let service = SchemeJsService::new(engine.clone());
+        let by_name = |name: &str| {
+            json!({ "key": "user_name", "filterType": "=", "value": name }).to_string()
+        };
+
+        let mut changes = service
+            .stream_changes(request(StreamChangesRequest {
+                database: db_name.clone(),
+                table: "users".to_string(),
+                query: by_name("andres"),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let inserted = service
+            .insert(request(InsertRequest {
+                database: db_name.clone(),
+                table: "users".to_string(),
+                rows: vec![
+                    json!({ "user_name": "andres" }).to_string(),
+                    json!({ "user_name": "carlos" }).to_string(),
+                ],
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(inserted.uids.len(), 2);
+
+        // Only the changes of the rows matched by the query are streamed
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.kind, ChangeKind::Insert as i32);
+        let row: serde_json::Value = serde_json::from_str(&change.row).unwrap();
+        assert_eq!(row["_uid"], inserted.uids[0]);
+
+        db.query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+        let search = |query: String| SearchRequest {
+            database: db_name.clone(),
+            table: "users".to_string(),
+            query,
+            timeout: None,
+        };
+        let rows = service
+            .search(request(search(String::new())))
+            .await
+            .unwrap()
+            .into_inner()
+            .rows;
+        assert_eq!(rows.len(), 2);
+
+        let updated = service
+            .update(request(UpdateRequest {
+                database: db_name.clone(),
+                table: "users".to_string(),
+                query: by_name("carlos"),
+                changes: json!({ "user_name": "charles" }).to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updated.updated, 1);
+
+        let delete = |query: String| DeleteRequest {
+            database: db_name.clone(),
+            table: "users".to_string(),
+            query,
+        };
+        let status = service
+            .delete(request(delete(String::new())))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let deleted = service
+            .delete(request(delete(by_name("andres"))))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(deleted.deleted, 1);
+
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.kind, ChangeKind::Delete as i32);
+
+        let status = service
+            .search(request(SearchRequest {
+                table: "unknown".to_string(),
+                ..search(String::new())
+            }))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let status = service
+            .search(request(search("{".to_string())))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_grpc_replication() {
+        let db_name = Uuid::new_v4().to_string();
+        let users = || Table::new("users").add_column(Column::new("user_name", DataTypes::String));
+
+        let mut leader = SchemeJsEngine::new(None);
+        leader.add_database(&db_name, None);
+        let leader = Arc::new(leader);
+        let leader_db = leader.find_by_name_ref(db_name.clone()).unwrap();
+        leader_db
+            .add_table(users(), TableStorage::default())
+            .unwrap();
+
+        let follower_path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let mut follower = SchemeJsEngine::new(Some(follower_path.clone()));
+        follower.add_database(&db_name, None);
+        let follower_db = follower.find_by_name_ref(db_name.clone()).unwrap();
+        follower_db
+            .add_table(users(), TableStorage::default())
+            .unwrap();
+
+        let service = SchemeJsService::new(leader.clone());
+        let insert = |name: &str| InsertRequest {
+            database: db_name.clone(),
+            table: "users".to_string(),
+            rows: vec![json!({ "user_name": name }).to_string()],
+        };
+        let replicate = |log_id: String, after: u64| ReplicateRequest {
+            database: db_name.clone(),
+            log_id,
+            after,
+        };
+
+        // Databases of nodes that are not leaders are not replicated
+        let status = service
+            .replicate(request(replicate(String::new(), 0)))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        leader_db.query_manager.changes().set_history_size(10);
+
+        service.insert(request(insert("andres"))).await.unwrap();
+        // Rows of the follower the leader doesn't have are dropped by the copy
+        follower_db
+            .query_manager
+            .insert(RowJson::from(RowData {
+                table: "users".to_string(),
+                value: json!({ "_uid": Uuid::new_v4().to_string(), "user_name": "stale" }),
+            }))
+            .unwrap();
+
+        // New followers get a copy of every row first
+        let mut changes = service
+            .replicate(request(replicate(String::new(), 0)))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut kinds = vec![];
+        let position = loop {
+            let change = changes.next().await.unwrap().unwrap();
+            kinds.push(change.kind);
+            let position = (change.log_id.clone(), change.sequence);
+            let synced = change.kind == ChangeKind::Synced as i32;
+            apply_change(&follower_db.query_manager, change).unwrap();
+            if synced {
+                break position;
+            }
+        };
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::Reset as i32,
+                ChangeKind::Insert as i32,
+                ChangeKind::Synced as i32
+            ]
+        );
+        assert_eq!(position, (leader_db.query_manager.id.to_string(), 1));
+
+        // Then the changes made from then on
+        service.insert(request(insert("carlos"))).await.unwrap();
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.sequence, 2);
+        apply_change(&follower_db.query_manager, change).unwrap();
+        drop(changes);
+
+        // Followers reconnecting catch up from the log
+        service.insert(request(insert("luis"))).await.unwrap();
+        let mut changes = service
+            .replicate(request(replicate(position.0.clone(), 2)))
+            .await
+            .unwrap()
+            .into_inner();
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.kind, ChangeKind::Insert as i32);
+        assert_eq!(change.sequence, 3);
+        apply_change(&follower_db.query_manager, change).unwrap();
+
+        let rows = follower_db
+            .query_manager
+            .search_manager()
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+
+        // Followers of a previous log (the leader restarted) get a copy again
+        let mut changes = service
+            .replicate(request(replicate(Uuid::new_v4().to_string(), 3)))
+            .await
+            .unwrap()
+            .into_inner();
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.kind, ChangeKind::Reset as i32);
+
+        std::fs::remove_dir_all(&leader_db.db_folder).unwrap();
+        std::fs::remove_dir_all(follower_path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_grpc_access_control() {
+        let db_name = Uuid::new_v4().to_string();
+        // Stands for the system database, which is shared by every engine using the default data folder
+        let system_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        engine.add_database(&system_name, None);
+        let system_db = engine.find_by_name_ref(system_name).unwrap();
+        let access = Arc::new(AccessControl::load(system_db.clone()).unwrap());
+        engine.access = Some(access.clone());
+        let engine = Arc::new(engine);
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            TableStorage::default(),
+        )
+        .unwrap();
+        access
+            .put_role(Role {
+                name: "reader".to_string(),
+                grants: vec![Grant {
+                    database: db_name.clone(),
+                    table: None,
+                    privilege: Privilege::Read,
+                }],
+            })
+            .unwrap();
+        access
+            .put_user(User {
+                name: "andres".to_string(),
+                roles: vec!["reader".to_string()],
+            })
+            .unwrap();
+
+        let service = SchemeJsService::new(engine.clone());
+        let as_user = |name: &str, mut request: Request<_>| {
+            request
+                .extensions_mut()
+                .insert(Principal::User(name.to_string()));
+            request
+        };
+        let insert = || InsertRequest {
+            database: db_name.clone(),
+            table: "users".to_string(),
+            rows: vec![json!({ "user_name": "andres" }).to_string()],
+        };
+        let search = || SearchRequest {
+            database: db_name.clone(),
+            table: "users".to_string(),
+            query: String::new(),
+            timeout: None,
+        };
+
+        let status = service
+            .insert(as_user("andres", Request::new(insert())))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert!(service
+            .search(as_user("andres", Request::new(search())))
+            .await
+            .is_ok());
+        let status = service
+            .search(as_user("carlos", Request::new(search())))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        // Users allowed to write every database can't write their own user
+        access
+            .put_role(Role {
+                name: "writer".to_string(),
+                grants: vec![Grant {
+                    database: ANY_DATABASE.to_string(),
+                    table: None,
+                    privilege: Privilege::Write,
+                }],
+            })
+            .unwrap();
+        access
+            .put_user(User {
+                name: "luis".to_string(),
+                roles: vec!["writer".to_string()],
+            })
+            .unwrap();
+        let admin_user = json!({
+            "name": "luis",
+            "definition": json!({ "name": "luis", "roles": ["admin"] }).to_string()
+        });
+        let status = service
+            .insert(as_user(
+                "luis",
+                Request::new(InsertRequest {
+                    database: SYSTEM_DATABASE.to_string(),
+                    table: USERS_TABLE.to_string(),
+                    rows: vec![admin_user.to_string()],
+                }),
+            ))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = service
+            .update(as_user(
+                "luis",
+                Request::new(UpdateRequest {
+                    database: SYSTEM_DATABASE.to_string(),
+                    table: USERS_TABLE.to_string(),
+                    query: String::new(),
+                    changes: json!({ "definition": admin_user["definition"] }).to_string(),
+                }),
+            ))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert!(service
+            .insert(as_user("luis", Request::new(insert())))
+            .await
+            .is_ok());
+
+        // Requests without a principal are rejected
+        let status = service.insert(Request::new(insert())).await.err().unwrap();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        assert!(service.insert(request(insert())).await.is_ok());
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+        std::fs::remove_dir_all(&system_db.db_folder).unwrap();
+    }
+}
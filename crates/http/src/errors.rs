@@ -0,0 +1,61 @@
+use enum_as_inner::EnumAsInner;
+use http::StatusCode;
+use schemajs_query::errors::QueryError;
+use thiserror::Error;
+
+#[derive(Debug, Error, EnumAsInner)]
+pub enum HttpError {
+    #[error("Unknown database '{0}'")]
+    InvalidDatabase(String),
+
+    #[error("No route for {0} '{1}'")]
+    InvalidRoute(String, String),
+
+    #[error("Invalid body: {0}")]
+    InvalidBody(String),
+
+    #[error("Body exceeds {0} bytes")]
+    BodyTooLarge(usize),
+
+    #[error("Subscriptions require a WebSocket connection")]
+    UpgradeRequired,
+
+    #[error("Invalid WebSocket handshake")]
+    InvalidHandshake,
+
+    #[error("Database '{0}' has no change log, `[cdc]` has to be configured")]
+    NoChangeLog(String),
+
+    #[error("Could not read the change log: {0}")]
+    ChangeLog(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error(transparent)]
+    Query(#[from] QueryError),
+}
+
+impl HttpError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            HttpError::InvalidDatabase(_)
+            | HttpError::InvalidRoute(_, _)
+            | HttpError::NoChangeLog(_) => StatusCode::NOT_FOUND,
+            HttpError::ChangeLog(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            HttpError::InvalidBody(_) | HttpError::InvalidHandshake => StatusCode::BAD_REQUEST,
+            HttpError::BodyTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            HttpError::UpgradeRequired => StatusCode::UPGRADE_REQUIRED,
+            HttpError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            HttpError::Query(QueryError::InvalidTable(_)) => StatusCode::NOT_FOUND,
+            HttpError::Query(QueryError::UniqueViolation(_, _))
+            | HttpError::Query(QueryError::DuplicatePrimaryKey(_, _)) => StatusCode::CONFLICT,
+            HttpError::Query(QueryError::ReadOnly(_))
+            | HttpError::Query(QueryError::AccessDenied(_)) => StatusCode::FORBIDDEN,
+            HttpError::Query(QueryError::QuotaExceeded(_)) => StatusCode::INSUFFICIENT_STORAGE,
+            HttpError::Query(QueryError::Timeout) => StatusCode::REQUEST_TIMEOUT,
+            HttpError::Query(QueryError::ShardError(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            HttpError::Query(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
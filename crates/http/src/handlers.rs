@@ -0,0 +1,433 @@
+use crate::errors::HttpError;
+use http::{Method, StatusCode};
+use schemajs_data::io_pool::IoPool;
+use schemajs_engine::access::{Principal, Privilege};
+use schemajs_engine::cdc::{read_records, CdcLog};
+use schemajs_engine::engine::SchemeJsEngine;
+use schemajs_engine::engine_db::EngineDb;
+use schemajs_query::errors::QueryError;
+use schemajs_query::ops::query_ops::{values_from_json, QueryOps};
+use schemajs_query::row_json::{RowData, RowJson};
+use serde_json::{json, Value};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Operations of the query API.
+///
+/// - `Insert`: `POST /<database>/<table>`, the body being a row or an array of rows. Returns the uid of the row
+///   (or the uids of the rows).
+/// - `Search`: `POST /<database>/<table>/search`, the body being a query (every row without a body).
+///   Returns the matched rows.
+/// - `Update`: `PATCH /<database>/<table>`, the body being `{ "query": .., "changes": .. }`.
+///   Returns `{ "updated": <amount of rows> }`.
+/// - `Delete`: `DELETE /<database>/<table>`, the body being `{ "query": .. }`. Returns `{ "deleted": <amount of rows> }`.
+/// - `Subscribe`: `GET /<database>/<table>/subscribe`, upgraded to a WebSocket receiving the changes of the table
+///   (see `Subscription`).
+/// - `Changes`: `POST /<database>/_changes`, the body being `{ "after": <sequence>, "limit": <amount> }` (both optional).
+///   Returns the records of the change log of the database following `after` (see `CdcLog`), in order.
+///
+/// Queries and changes are written as for the JS API (see `QueryOps::from_json`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Insert,
+    Search,
+    Update,
+    Delete,
+    Subscribe,
+    Changes,
+}
+
+impl Operation {
+    /// Privilege required on the table of the request (on the database for `Changes`).
+    pub fn privilege(&self) -> Privilege {
+        match self {
+            Operation::Insert | Operation::Update | Operation::Delete => Privilege::Write,
+            Operation::Search | Operation::Subscribe | Operation::Changes => Privilege::Read,
+        }
+    }
+}
+
+/// Request of the query API, see `Operation`. `table` is empty for operations on the whole database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub db: String,
+    pub table: String,
+    pub operation: Operation,
+}
+
+impl Route {
+    /// Checks that `principal` can run the request against `engine`, see `SchemeJsEngine::authorize`.
+    pub fn authorize(
+        &self,
+        engine: &SchemeJsEngine,
+        principal: &Principal,
+    ) -> Result<(), HttpError> {
+        let table = match self.table.is_empty() {
+            true => None,
+            false => Some(self.table.as_str()),
+        };
+        engine.authorize(principal, &self.db, table, self.operation.privilege())?;
+        Ok(())
+    }
+
+    pub fn parse(method: &Method, path: &str) -> Result<Self, HttpError> {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        let (db, table, operation) = match (method, segments.as_slice()) {
+            (&Method::POST, [db, "_changes"]) => (db, &"", Operation::Changes),
+            (&Method::POST, [db, table]) => (db, table, Operation::Insert),
+            (&Method::POST, [db, table, "search"]) => (db, table, Operation::Search),
+            (&Method::PATCH, [db, table]) => (db, table, Operation::Update),
+            (&Method::DELETE, [db, table]) => (db, table, Operation::Delete),
+            (&Method::GET, [db, table, "subscribe"]) => (db, table, Operation::Subscribe),
+            _ => {
+                return Err(HttpError::InvalidRoute(
+                    method.to_string(),
+                    path.to_string(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            db: db.to_string(),
+            table: table.to_string(),
+            operation,
+        })
+    }
+}
+
+fn field(body: &Value, name: &str) -> Result<Value, HttpError> {
+    body.get(name)
+        .cloned()
+        .ok_or_else(|| HttpError::InvalidBody(format!("missing '{}'", name)))
+}
+
+/// Runs the request `route` against `engine` for `principal`, `body` being the JSON body of the request (possibly empty).
+pub async fn handle(
+    engine: &SchemeJsEngine,
+    principal: &Principal,
+    route: Route,
+    body: &[u8],
+) -> Result<Value, HttpError> {
+    route.authorize(engine, principal)?;
+
+    let body: Value = match body.is_empty() {
+        true => Value::Null,
+        false => serde_json::from_slice(body).map_err(|e| HttpError::InvalidBody(e.to_string()))?,
+    };
+
+    let Route {
+        db: db_name,
+        table: table_name,
+        operation,
+    } = route;
+    let db = engine
+        .find_by_name_ref(db_name.clone())
+        .ok_or_else(|| HttpError::InvalidDatabase(db_name.clone()))?;
+    if operation == Operation::Changes {
+        return changes(&db, &body).await;
+    }
+
+    let query_manager = db.query_manager.clone();
+    let table = query_manager
+        .tables
+        .get(&table_name)
+        .map(|table| table.table.clone())
+        .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+    let span =
+        tracing::info_span!("http", db = %db_name, table = %table_name, operation = ?operation);
+    match operation {
+        Operation::Insert => {
+            let with_uid = |mut row: Value| {
+                if let Value::Object(ref mut obj) = row {
+                    obj.insert(
+                        "_uid".to_string(),
+                        Value::String(Uuid::new_v4().to_string()),
+                    );
+                }
+
+                RowJson::from(RowData {
+                    table: table_name.clone(),
+                    value: row,
+                })
+            };
+
+            match body {
+                Value::Object(_) => {
+                    let row = with_uid(body);
+                    let uid = IoPool::global()
+                        .run(move || query_manager.insert(row))
+                        .instrument(span)
+                        .await?;
+                    Ok(json!(uid))
+                }
+                Value::Array(rows) => {
+                    let rows = rows.into_iter().map(with_uid).collect();
+                    let uids = IoPool::global()
+                        .run(move || query_manager.insert_many(rows))
+                        .instrument(span)
+                        .await?;
+                    Ok(json!(uids))
+                }
+                _ => Err(HttpError::InvalidBody(
+                    "expected a row or an array of rows".to_string(),
+                )),
+            }
+        }
+        Operation::Search => {
+            let ops = QueryOps::from_json(&table, &body)?;
+            let rows = span.in_scope(|| query_manager.search_manager().search(table_name, &ops))?;
+
+            Ok(Value::Array(
+                rows.iter().map(|row| row.to_json(&table)).collect(),
+            ))
+        }
+        Operation::Update => {
+            let ops = QueryOps::from_json(&table, &field(&body, "query")?)?;
+            let values = values_from_json(&table, &field(&body, "changes")?)?;
+            let updated = IoPool::global()
+                .run(move || query_manager.update(table_name, &ops, &values))
+                .instrument(span)
+                .await?;
+            Ok(json!({ "updated": updated }))
+        }
+        Operation::Delete => {
+            // Deleting every row has to be asked for explicitly, with an empty query
+            let ops = QueryOps::from_json(&table, &field(&body, "query")?)?;
+            let deleted = IoPool::global()
+                .run(move || query_manager.delete(table_name, &ops))
+                .instrument(span)
+                .await?;
+            Ok(json!({ "deleted": deleted }))
+        }
+        // Requests upgraded to a WebSocket never get here, see `subscribe`
+        Operation::Subscribe => Err(HttpError::UpgradeRequired),
+    }
+}
+
+// Records of the change log of `db` requested by `body`, see `Operation::Changes`
+async fn changes(db: &EngineDb, body: &Value) -> Result<Value, HttpError> {
+    let path = CdcLog::path(&db.db_folder);
+    if !path.exists() {
+        return Err(HttpError::NoChangeLog(db.name.clone()));
+    }
+
+    let number = |name: &str| match body.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            HttpError::InvalidBody(format!("'{}' must be a positive integer", name))
+        }),
+    };
+    let after = number("after")?.unwrap_or(0);
+    let limit = number("limit")?.map(|limit| limit as usize);
+    let encryption = db.query_manager.encryption().cloned();
+
+    let span = tracing::info_span!("http", db = %db.name, operation = ?Operation::Changes);
+    let records = IoPool::global()
+        .run(move || read_records(&path, encryption.as_ref(), after, limit))
+        .instrument(span)
+        .await
+        .map_err(|e| HttpError::ChangeLog(e.to_string()))?;
+
+    Ok(json!(records))
+}
+
+/// Answers a request of the query API: the status of the response and its JSON body, `{ "error": .. }` on failure.
+pub async fn respond(
+    engine: &SchemeJsEngine,
+    principal: &Principal,
+    method: &Method,
+    path: &str,
+    body: &[u8],
+) -> (StatusCode, Value) {
+    let result = match Route::parse(method, path) {
+        Ok(route) => handle(engine, principal, route, body).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(value) => (StatusCode::OK, value),
+        Err(e) => {
+            if e.status().is_server_error() {
+                tracing::error!(method = %method, path = %path, error = %e, "Request failed");
+            }
+            (e.status(), json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handlers::respond;
+    use http::{Method, StatusCode};
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_engine::access::Principal;
+    use schemajs_engine::cdc::CdcLog;
+    use schemajs_engine::engine::SchemeJsEngine;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    pub async fn test_http_handlers() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let engine = engine;
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        db.add_table(
+            Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            TableStorage::default(),
+        )
+        .unwrap();
+        let users = format!("/{}/users", db_name);
+        let changes = format!("/{}/_changes", db_name);
+
+        // Without `[cdc]` there is no change log
+        let (status, _) = respond(&engine, &Principal::System, &Method::POST, &changes, b"").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        CdcLog::enable(&db, false).unwrap();
+
+        let reconcile = || {
+            db.query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .temps
+                .reconcile_all();
+        };
+
+        let (status, uid) = respond(
+            &engine,
+            &Principal::System,
+            &Method::POST,
+            &users,
+            json!({ "user_name": "andres" }).to_string().as_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(uid.is_string());
+
+        let (status, uids) = respond(
+            &engine,
+            &Principal::System,
+            &Method::POST,
+            &users,
+            json!([{ "user_name": "carlos" }, { "user_name": "luis" }])
+                .to_string()
+                .as_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(uids.as_array().unwrap().len(), 2);
+        reconcile();
+
+        // Every row without a body
+        let search = format!("{}/search", users);
+        let (status, rows) =
+            respond(&engine, &Principal::System, &Method::POST, &search, b"").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(rows.as_array().unwrap().len(), 3);
+
+        let by_name = |name: &str| json!({ "key": "user_name", "filterType": "=", "value": name });
+        let (status, rows) = respond(
+            &engine,
+            &Principal::System,
+            &Method::POST,
+            &search,
+            by_name("andres").to_string().as_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(rows[0]["_uid"], uid);
+
+        let (status, updated) = respond(
+            &engine,
+            &Principal::System,
+            &Method::PATCH,
+            &users,
+            json!({ "query": by_name("carlos"), "changes": { "user_name": "charles" } })
+                .to_string()
+                .as_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["updated"], 1);
+
+        let (status, deleted) = respond(
+            &engine,
+            &Principal::System,
+            &Method::DELETE,
+            &users,
+            json!({ "query": by_name("luis") }).to_string().as_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(deleted["deleted"], 1);
+
+        // Deleting requires a query
+        let (status, _) = respond(&engine, &Principal::System, &Method::DELETE, &users, b"").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = respond(&engine, &Principal::System, &Method::POST, &users, b"{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, error) = respond(
+            &engine,
+            &Principal::System,
+            &Method::POST,
+            &format!("/{}/unknown/search", db_name),
+            b"",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["error"], "Unknown table 'unknown'");
+
+        let (status, _) = respond(
+            &engine,
+            &Principal::System,
+            &Method::POST,
+            "/unknown/users/search",
+            b"",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = respond(&engine, &Principal::System, &Method::GET, &users, b"").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = respond(
+            &engine,
+            &Principal::System,
+            &Method::GET,
+            &format!("{}/subscribe", users),
+            b"",
+        )
+        .await;
+        assert_eq!(status, StatusCode::UPGRADE_REQUIRED);
+
+        // 3 inserts, an update and a delete
+        let (status, records) =
+            respond(&engine, &Principal::System, &Method::POST, &changes, b"").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(records.as_array().unwrap().len(), 5);
+        let (status, records) = respond(
+            &engine,
+            &Principal::System,
+            &Method::POST,
+            &changes,
+            json!({ "after": 3, "limit": 1 }).to_string().as_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(records[0]["op"], "update");
+        assert_eq!(records[0]["before"]["user_name"], "carlos");
+        assert_eq!(records[0]["after"]["user_name"], "charles");
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+pub mod errors;
+pub mod handlers;
+pub mod server;
+pub mod subscriptions;
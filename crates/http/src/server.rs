@@ -0,0 +1,191 @@
+use crate::errors::HttpError;
+use crate::handlers::respond;
+use crate::subscriptions::{is_websocket_request, subscribe};
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::{HeaderMap, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use schemajs_config::SchemeJsHttpConfig;
+use schemajs_engine::access::Principal;
+use schemajs_engine::auth::Authenticator;
+use schemajs_engine::engine::SchemeJsEngine;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Serves the query API of `engine` (see `Operation`) and the subscriptions to its changes (see `Subscription`) over HTTP, as configured under `[http]` in `SchemeJS.toml`.
+/// Requests are authenticated by `auth` when given (see `Authenticator`), running for the engine itself otherwise.
+/// Fails without serving anything when there is no `auth`, every request would be allowed everything, unless
+/// `allow_anonymous` is set (and the engine has no access control). Runs until binding the address or accepting
+/// a connection fails.
+pub async fn serve(
+    engine: Arc<SchemeJsEngine>,
+    config: SchemeJsHttpConfig,
+    auth: Option<Arc<Authenticator>>,
+) -> anyhow::Result<()> {
+    if engine.access.is_some() && auth.is_none() {
+        anyhow::bail!(
+            "Serving the HTTP API with access control ([access]) requires an [auth] section"
+        );
+    }
+    if !config.allow_anonymous && auth.is_none() {
+        anyhow::bail!(
+            "Serving the HTTP API requires an [auth] section, or `allow_anonymous = true` under [http]"
+        );
+    }
+
+    let listener = TcpListener::bind(config.address()).await?;
+    tracing::info!(address = %config.address(), "Serving the HTTP API");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let engine = engine.clone();
+        let auth = auth.clone();
+        let max_body_size = config.max_body_size;
+
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                handle_request(engine.clone(), auth.clone(), request, max_body_size)
+            });
+
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await
+            {
+                tracing::debug!(error = %e, "HTTP connection closed");
+            }
+        });
+    }
+}
+
+/// Principal a request with `headers` runs for, rejecting it (before its body is read) when `auth` doesn't
+/// accept its `Authorization` header. Requests only run for the engine itself without `auth`, which `serve`
+/// refuses when the engine has access control.
+pub fn authenticate(
+    auth: Option<&Authenticator>,
+    headers: &HeaderMap,
+) -> Result<Principal, HttpError> {
+    let auth = match auth {
+        Some(auth) => auth,
+        None => return Ok(Principal::System),
+    };
+
+    let authorization = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    auth.authenticate(authorization)
+        .map_err(|e| HttpError::Unauthorized(e.to_string()))
+}
+
+async fn handle_request(
+    engine: Arc<SchemeJsEngine>,
+    auth: Option<Arc<Authenticator>>,
+    request: Request<Incoming>,
+    max_body_size: usize,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let principal = match authenticate(auth.as_deref(), request.headers()) {
+        Ok(principal) => principal,
+        Err(e) => return Ok(json_response(e.status(), json!({ "error": e.to_string() }))),
+    };
+
+    if is_websocket_request(&request) {
+        return Ok(subscribe(&engine, &principal, request)
+            .unwrap_or_else(|e| json_response(e.status(), json!({ "error": e.to_string() }))));
+    }
+
+    let (parts, body) = request.into_parts();
+
+    let (status, body) = match Limited::new(body, max_body_size).collect().await {
+        Ok(body) => {
+            respond(
+                &engine,
+                &principal,
+                &parts.method,
+                parts.uri.path(),
+                &body.to_bytes(),
+            )
+            .await
+        }
+        Err(e) => {
+            let e = match e.is::<LengthLimitError>() {
+                true => HttpError::BodyTooLarge(max_body_size),
+                false => HttpError::InvalidBody(e.to_string()),
+            };
+            (e.status(), json!({ "error": e.to_string() }))
+        }
+    };
+
+    Ok(json_response(status, body))
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::{authenticate, serve};
+    use http::header::AUTHORIZATION;
+    use http::{HeaderMap, HeaderValue, StatusCode};
+    use schemajs_config::{SchemeJsAuthConfig, SchemeJsHttpConfig, SchemeJsTokenConfig};
+    use schemajs_engine::access::Principal;
+    use schemajs_engine::auth::Authenticator;
+    use schemajs_engine::engine::SchemeJsEngine;
+    use std::sync::Arc;
+
+    #[test]
+    pub fn test_http_authenticate() {
+        let auth = Authenticator::new(&SchemeJsAuthConfig {
+            tokens: vec![SchemeJsTokenConfig {
+                token: "andres-token".to_string(),
+                user: "andres".to_string(),
+            }],
+            ..Default::default()
+        });
+        let headers = |authorization: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_static(authorization));
+            headers
+        };
+
+        // Without `[auth]` requests run for the engine itself
+        assert_eq!(
+            authenticate(None, &HeaderMap::new()).unwrap(),
+            Principal::System
+        );
+        assert_eq!(
+            authenticate(Some(&auth), &headers("Bearer andres-token")).unwrap(),
+            Principal::User("andres".to_string())
+        );
+
+        let rejected = authenticate(Some(&auth), &HeaderMap::new()).unwrap_err();
+        assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+        let rejected = authenticate(Some(&auth), &headers("Bearer unknown")).unwrap_err();
+        assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    pub async fn test_http_serve_anonymous() {
+        let config = SchemeJsHttpConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            max_body_size: 1024,
+            allow_anonymous: false,
+        };
+
+        // Without `[auth]` nothing is served unless anonymous requests are allowed explicitly
+        let refused = serve(Arc::new(SchemeJsEngine::new(None)), config, None)
+            .await
+            .unwrap_err();
+        assert!(refused.to_string().contains("allow_anonymous"));
+    }
+}
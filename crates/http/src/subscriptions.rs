@@ -0,0 +1,263 @@
+use crate::errors::HttpError;
+use crate::handlers::{Operation, Route};
+use futures_util::{SinkExt, StreamExt};
+use http::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+use http::{Request, Response, StatusCode, Uri};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use percent_encoding::percent_decode_str;
+use schemajs_engine::access::Principal;
+use schemajs_engine::engine::SchemeJsEngine;
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::changes::ChangeEvent;
+use schemajs_query::managers::single::SingleQueryManager;
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_query::row_json::RowJson;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Subscription of a WebSocket client to the changes of a table (`GET /<database>/<table>/subscribe`), optionally
+/// to the rows matching a query only, given as percent-encoded JSON in the `query` parameter
+/// (`?query={"key":"user_name","filterType":"=","value":"andres"}`).
+///
+/// Every change is sent as a text message, `{ "type": "insert" | "update" | "delete", "table": .., "row": .. }`.
+/// Rows updated so they no longer match the query are not sent. Clients falling too far behind get
+/// `{ "type": "lagged", "missed": <amount of changes> }` and should search the table again.
+///
+/// # Fields:
+/// - `table`: Table whose changes are sent.
+/// - `query`: Rows whose changes are sent, the new version of the row for updates.
+/// - `changes`: Changes of the database, received since the subscription was created.
+pub struct Subscription {
+    pub table: String,
+    pub query: QueryOps,
+    changes: broadcast::Receiver<ChangeEvent>,
+}
+
+impl Subscription {
+    /// Subscribes to the changes of `table` of `query_manager` matching `query`, every change without one.
+    pub fn new(
+        query_manager: &SingleQueryManager<RowJson>,
+        table: &str,
+        query: Option<&str>,
+    ) -> Result<Self, HttpError> {
+        let table_def = query_manager
+            .tables
+            .get(table)
+            .map(|table_shard| table_shard.table.clone())
+            .ok_or_else(|| QueryError::InvalidTable(table.to_string()))?;
+
+        let query = match query {
+            Some(query) => serde_json::from_str(query)
+                .map_err(|_| QueryError::InvalidQuery(query.to_string()))?,
+            None => Value::Null,
+        };
+
+        Ok(Self {
+            table: table.to_string(),
+            query: QueryOps::from_json(&table_def, &query)?,
+            changes: query_manager.changes().subscribe(),
+        })
+    }
+
+    /// Message sent to the client for `event`, `None` when it is not about the subscribed rows.
+    pub fn message(
+        &self,
+        query_manager: &SingleQueryManager<RowJson>,
+        event: &ChangeEvent,
+    ) -> Option<Value> {
+        if event.table != self.table {
+            return None;
+        }
+
+        // The table is read for every change, as it can be reloaded while clients are subscribed
+        let table = query_manager.tables.get(&event.table)?.table.clone();
+        let row = RowJson::from(event.row.as_slice());
+        if !self.query.matches(&table, &row) {
+            return None;
+        }
+
+        Some(json!({
+            "type": event.kind,
+            "table": event.table,
+            "row": row.to_json(&table)
+        }))
+    }
+
+    /// Sends the changes to the client of `socket` until it disconnects.
+    pub async fn run(
+        mut self,
+        socket: WebSocketStream<TokioIo<Upgraded>>,
+        query_manager: Arc<SingleQueryManager<RowJson>>,
+    ) {
+        let (mut sink, mut stream) = socket.split();
+
+        loop {
+            tokio::select! {
+                change = self.changes.recv() => {
+                    let message = match change {
+                        Ok(event) => match self.message(&query_manager, &event) {
+                            Some(message) => message,
+                            None => continue,
+                        },
+                        Err(RecvError::Lagged(missed)) => json!({ "type": "lagged", "missed": missed }),
+                        Err(RecvError::Closed) => break,
+                    };
+
+                    if sink.send(Message::Text(message.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+                // Clients only send control frames, answered while reading
+                message = stream.next() => {
+                    if let None | Some(Err(_)) | Some(Ok(Message::Close(_))) = message {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether `request` asks to upgrade its connection to a WebSocket.
+pub fn is_websocket_request<B>(request: &Request<B>) -> bool {
+    request
+        .headers()
+        .get(UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .map_or(false, |upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+fn query_param(uri: &Uri) -> Option<String> {
+    uri.query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("query="))
+        .map(|query| percent_decode_str(query).decode_utf8_lossy().to_string())
+}
+
+/// Accepts the WebSocket `request` of `principal` subscribing to the changes of a table (see `Subscription`),
+/// the changes being sent once the connection is upgraded.
+pub fn subscribe(
+    engine: &SchemeJsEngine,
+    principal: &Principal,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, HttpError> {
+    let route = Route::parse(request.method(), request.uri().path())?;
+    if route.operation != Operation::Subscribe {
+        return Err(HttpError::InvalidRoute(
+            request.method().to_string(),
+            request.uri().path().to_string(),
+        ));
+    }
+    route.authorize(engine, principal)?;
+
+    let accept = request
+        .headers()
+        .get(SEC_WEBSOCKET_KEY)
+        .map(|key| derive_accept_key(key.as_bytes()))
+        .ok_or(HttpError::InvalidHandshake)?;
+
+    let query_manager = engine
+        .find_by_name_ref(route.db.clone())
+        .ok_or_else(|| HttpError::InvalidDatabase(route.db.clone()))?
+        .query_manager
+        .clone();
+    // Subscribed before answering, so no change made after the handshake is missed
+    let subscription = Subscription::new(
+        &query_manager,
+        &route.table,
+        query_param(request.uri()).as_deref(),
+    )?;
+
+    let upgrade = hyper::upgrade::on(request);
+    tokio::spawn(async move {
+        match upgrade.await {
+            Ok(upgraded) => {
+                let socket =
+                    WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None)
+                        .await;
+                subscription.run(socket, query_manager).await;
+            }
+            Err(e) => tracing::debug!(error = %e, "Could not upgrade to a WebSocket"),
+        }
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Full::new(Bytes::new()))
+        .unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::subscriptions::Subscription;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_engine::engine::SchemeJsEngine;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::row_json::{RowData, RowJson};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    pub async fn test_subscription_messages() {
+        let db_name = Uuid::new_v4().to_string();
+        let mut engine = SchemeJsEngine::new(None);
+        engine.add_database(&db_name, None);
+        let engine = engine;
+
+        let db = engine.find_by_name_ref(db_name.clone()).unwrap();
+        for table in ["users", "products"] {
+            db.add_table(
+                Table::new(table).add_column(Column::new("name", DataTypes::String)),
+                TableStorage::default(),
+            )
+            .unwrap();
+        }
+        let query_manager = db.query_manager.clone();
+
+        let query = json!({ "key": "name", "filterType": "=", "value": "andres" }).to_string();
+        let mut subscription =
+            Subscription::new(&query_manager, "users", Some(query.as_str())).unwrap();
+        assert!(Subscription::new(&query_manager, "unknown", None).is_err());
+        assert!(Subscription::new(&query_manager, "users", Some("{")).is_err());
+
+        for (table, name) in [
+            ("users", "carlos"),
+            ("products", "andres"),
+            ("users", "andres"),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: table.to_string(),
+                    value: json!({ "_uid": Uuid::new_v4().to_string(), "name": name }),
+                }))
+                .unwrap();
+        }
+
+        let mut messages = vec![];
+        while let Ok(event) = subscription.changes.try_recv() {
+            messages.extend(subscription.message(&query_manager, &event));
+        }
+
+        // Only the changes of the subscribed rows are sent
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["type"], "insert");
+        assert_eq!(messages[0]["table"], "users");
+        assert_eq!(messages[0]["row"]["name"], "andres");
+
+        std::fs::remove_dir_all(&db.db_folder).unwrap();
+    }
+}
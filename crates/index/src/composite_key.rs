@@ -1,2 +1,2 @@
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub struct CompositeKey(pub Vec<(String, String)>);
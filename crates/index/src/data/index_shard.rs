@@ -1,6 +1,7 @@
 use crate::data::index_data_unit::IndexDataUnit;
 use crate::types::{IndexKey, IndexValue};
 use crate::utils::get_entry_size;
+use schemajs_data::encryption::EncryptionKey;
 use schemajs_data::errors::ShardErrors;
 use schemajs_data::shard::map_shard::MapShard;
 use schemajs_data::shard::shards::kv::config::KvShardConfig;
@@ -10,6 +11,7 @@ use schemajs_data::U64_SIZE;
 use std::cmp::Ordering;
 use std::io::{Seek, Write};
 use std::marker::PhantomData;
+use std::ops::Bound;
 use std::os::unix::fs::FileExt;
 use std::path::Path;
 use std::sync::RwLock;
@@ -36,6 +38,7 @@ impl<K: IndexKey, V: IndexValue> IndexShard<K, V> {
         value_size: usize,
         max_capacity: Option<u64>,
         binary_order: Option<bool>,
+        encryption: Option<EncryptionKey>,
     ) -> Self {
         let shard_collection = MapShard::new(
             shard_folder.as_ref().to_path_buf(),
@@ -43,6 +46,7 @@ impl<K: IndexKey, V: IndexValue> IndexShard<K, V> {
             KvShardConfig {
                 value_size: get_entry_size(key_size, value_size),
                 max_capacity: max_capacity.clone(),
+                encryption,
             },
         );
 
@@ -103,7 +107,12 @@ impl<K: IndexKey, V: IndexValue> IndexShard<K, V> {
         (K::from(key_unit), V::from(val_unit), el)
     }
 
-    pub fn raw_insert(&self, data: Vec<(K, V)>) {
+    pub fn raw_insert(&self, mut data: Vec<(K, V)>) {
+        if self.binary_order {
+            data.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        let first_key = data.first().map(|(key, _)| key.clone());
+
         let data_units: Vec<Vec<u8>> = data
             .into_iter()
             .map(|(k, v)| {
@@ -120,13 +129,93 @@ impl<K: IndexKey, V: IndexValue> IndexShard<K, V> {
 
         let entries: Vec<&[u8]> = data_units.iter().map(|i| i.as_slice()).collect();
 
-        self.data.write().unwrap().insert_rows(&entries);
+        let mut writer = self.data.write().unwrap();
+        let master_path = writer.current_master_shard.get_path();
+        let master_len = (writer.current_master_shard.get_last_index() + 1) as usize;
+        let merge_from = match (&first_key, self.binary_order) {
+            (Some(first_key), true) => self.lower_bound(
+                &writer.current_master_shard,
+                &Bound::Included(first_key.clone()),
+            ),
+            _ => master_len,
+        };
 
-        if self.binary_order {
-            self.keep_binary_order();
+        writer.insert_rows(&entries);
+
+        if merge_from < master_len {
+            self.merge_tail(&writer, &master_path, merge_from, master_len);
         }
     }
 
+    /// Restores the binary order of the shard at `path` after a sorted batch was appended to it at `appended_at`:
+    /// the entries from `from` on (the ones not below the first appended key) are merged with the batch and
+    /// written back in place. Entries of the batch past the capacity of the shard went to new shards, which only
+    /// hold sorted entries of the batch.
+    fn merge_tail(
+        &self,
+        data: &MapShard<KvShard, KvShardConfig>,
+        path: &Path,
+        from: usize,
+        appended_at: usize,
+    ) {
+        let past_master_shards = data.past_master_shards.read().unwrap();
+        let shard = if data.current_master_shard.get_path() == *path {
+            &data.current_master_shard
+        } else {
+            match past_master_shards
+                .values()
+                .find(|shard| shard.get_path() == *path)
+            {
+                Some(shard) => shard,
+                None => return,
+            }
+        };
+
+        let len = (shard.get_last_index() + 1) as usize;
+        if appended_at >= len {
+            return;
+        }
+
+        let mut tail: Vec<(K, Vec<u8>)> = (from..len)
+            .filter_map(|i| {
+                let el = self.get_entry_from_shard(shard, i).ok()?;
+                let (key_unit, val_unit, el) = self.build_entry_from_vec(el)?;
+                let (key, _, el) = self.build_kv(key_unit, val_unit, el);
+                Some((key, el))
+            })
+            .collect();
+        // Made of two sorted runs, which the stable sort merges in linear time. Entries already in the index
+        // stay before appended ones with the same key.
+        tail.sort_by(|a, b| a.0.cmp(&b.0));
+
+        shard
+            .data
+            .write()
+            .unwrap()
+            .operate(|file| {
+                for (offset, (_, el)) in tail.iter().enumerate() {
+                    shard.replace_element(file, from + offset, el)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    /// Waits until the entries of the index are on disk.
+    pub fn sync(&self) -> Result<(), ShardErrors> {
+        self.data.read().unwrap().sync()
+    }
+
+    /// Amount of entries across the shards of the index.
+    pub fn entry_count(&self) -> u64 {
+        self.data.read().unwrap().len() as u64
+    }
+
+    /// Size in bytes of the shard files of the index.
+    pub fn file_size(&self) -> u64 {
+        self.data.read().unwrap().file_size()
+    }
+
     pub fn insert(&self, key: K, value: V) {
         self.raw_insert(vec![(key, value)]);
     }
@@ -188,6 +277,144 @@ impl<K: IndexKey, V: IndexValue> IndexShard<K, V> {
         None
     }
 
+    fn read_kv_from_shard(&self, shard: &KvShard, index: usize) -> Option<(K, V)> {
+        let entry = self.get_entry_from_shard(shard, index).ok()?;
+        let (key_unit, val_unit, el) = self.build_entry_from_vec(entry)?;
+        let (key, value, _) = self.build_kv(key_unit, val_unit, el);
+        Some((key, value))
+    }
+
+    /// Returns the position of the first entry in `shard` that is not below `from`.
+    fn lower_bound(&self, shard: &KvShard, from: &Bound<K>) -> usize {
+        let mut left = 0usize;
+        let mut right = (shard.get_last_index() + 1) as usize;
+
+        while left < right {
+            let mid = left + (right - left) / 2;
+            let (key, _) = self.read_kv_from_shard(shard, mid).unwrap();
+
+            let is_below = match from {
+                Bound::Included(target) => key.cmp(target) == Ordering::Less,
+                Bound::Excluded(target) => key.cmp(target) != Ordering::Greater,
+                Bound::Unbounded => false,
+            };
+
+            if is_below {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+
+        left
+    }
+
+    /// Collects every entry whose key falls between `from` and `to`.
+    /// Entries are returned in key order as long as the index is made of a single shard,
+    /// which is the case for indexes without a max capacity.
+    pub fn range_search(&self, from: Bound<K>, to: Bound<K>) -> Vec<(K, V)> {
+        let reader = self.data.read().unwrap();
+        let past_master_shards = reader.past_master_shards.read().unwrap();
+
+        let shards = {
+            let mut shards: Vec<&KvShard> = past_master_shards.values().collect();
+            shards.push(&reader.current_master_shard);
+            shards
+        };
+
+        let mut results = vec![];
+
+        for shard in shards {
+            let len = (shard.get_last_index() + 1) as usize;
+            let mut i = self.lower_bound(shard, &from);
+
+            while i < len {
+                let (key, value) = match self.read_kv_from_shard(shard, i) {
+                    Some(kv) => kv,
+                    None => break,
+                };
+
+                let is_within = match &to {
+                    Bound::Included(target) => key.cmp(target) != Ordering::Greater,
+                    Bound::Excluded(target) => key.cmp(target) == Ordering::Less,
+                    Bound::Unbounded => true,
+                };
+
+                if !is_within {
+                    break;
+                }
+
+                results.push((key, value));
+                i += 1;
+            }
+        }
+
+        // Every shard is ordered on its own, entries are merged so the result follows key order.
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+
+        results
+    }
+
+    /// Collects the values of every entry whose key is `key`.
+    pub fn get_all(&self, key: K) -> Vec<V> {
+        self.range_search(Bound::Included(key.clone()), Bound::Included(key))
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Points the entry `key -> old_value` to `new_value` in place.
+    /// Keys are left untouched, therefore the binary order is kept.
+    ///
+    /// Returns `false` if there is no such entry.
+    pub fn replace_value(&self, key: K, old_value: V, new_value: V) -> bool {
+        let old_value: Vec<u8> = old_value.into();
+        let new_entry: Vec<u8> = self
+            .build_entry(key.clone().into(), new_value.into())
+            .into();
+
+        let reader = self.data.read().unwrap();
+        let past_master_shards = reader.past_master_shards.read().unwrap();
+
+        let shards = {
+            let mut shards: Vec<&KvShard> = past_master_shards.values().collect();
+            shards.push(&reader.current_master_shard);
+            shards
+        };
+
+        for shard in shards {
+            let len = (shard.get_last_index() + 1) as usize;
+            // Entries sharing the same key are contiguous
+            let mut i = self.lower_bound(shard, &Bound::Included(key.clone()));
+
+            while i < len {
+                let (entry_key, entry_value) = match self.read_kv_from_shard(shard, i) {
+                    Some(kv) => kv,
+                    None => break,
+                };
+
+                if entry_key.cmp(&key) != Ordering::Equal {
+                    break;
+                }
+
+                let entry_value: Vec<u8> = entry_value.into();
+                if entry_value == old_value {
+                    shard
+                        .data
+                        .write()
+                        .unwrap()
+                        .operate(|file| shard.replace_element(file, i, &new_entry))
+                        .unwrap();
+                    return true;
+                }
+
+                i += 1;
+            }
+        }
+
+        false
+    }
+
     fn build_entry(&self, key: Vec<u8>, value: Vec<u8>) -> IndexDataUnit {
         let build_entry = {
             let mut entry: Vec<u8> = Vec::new();
@@ -205,44 +432,6 @@ impl<K: IndexKey, V: IndexValue> IndexShard<K, V> {
 
         IndexDataUnit::new(build_entry)
     }
-
-    fn keep_binary_order(&self) {
-        let mut i = {
-            self.data
-                .read()
-                .unwrap()
-                .current_master_shard
-                .get_last_index()
-        };
-
-        while i > 0 {
-            let (curr_index, _, curr_original_el) = self.get_kv(i as usize, false).unwrap();
-            let (prev_index, _, prev_original_el) = self.get_kv(i as usize - 1, false).unwrap();
-
-            match curr_index.cmp(&prev_index) {
-                Ordering::Less => {
-                    let mut writer = self.data.write().unwrap();
-                    let mut curr_shard = writer.current_master_shard.data.write().unwrap();
-                    curr_shard
-                        .operate(|file| {
-                            writer
-                                .current_master_shard
-                                .swap_elements(
-                                    file,
-                                    i as usize,
-                                    &curr_original_el,
-                                    &prev_original_el,
-                                )
-                                .unwrap();
-                            i -= 1;
-                            Ok(())
-                        })
-                        .unwrap();
-                }
-                _ => break,
-            }
-        }
-    }
 }
 
 #[cfg(test)]
@@ -251,6 +440,7 @@ mod test {
     use crate::keys::string_index::StringIndexKey;
     use crate::utils::get_entry_size;
     use crate::vals::raw_value::RawIndexValue;
+    use std::ops::Bound;
     use tempfile::tempdir;
     use uuid::Uuid;
 
@@ -274,6 +464,7 @@ mod test {
             1024,
             None,
             Some(true),
+            None,
         );
 
         let key_size = 32;
@@ -325,6 +516,7 @@ mod test {
             1024,
             None,
             Some(true),
+            None,
         );
 
         let key_size = 32;
@@ -349,6 +541,124 @@ mod test {
         assert_eq!(index.get_kv(5, true).unwrap().0 .0, "j".repeat(key_size));
         assert_eq!(index.get_kv(6, true).unwrap().0 .0, "z".repeat(key_size));
 
+        // Batches are sorted and merged with the entries they fall between
+        index.raw_insert(vec![
+            (StringIndexKey("y".repeat(key_size)), vec![0u8; 1024].into()),
+            (StringIndexKey("a".repeat(key_size)), vec![0u8; 1024].into()),
+            (StringIndexKey("f".repeat(key_size)), vec![0u8; 1024].into()),
+        ]);
+        let keys: Vec<String> = (0..10)
+            .map(|i| index.get_kv(i, true).unwrap().0 .0[..1].to_string())
+            .collect();
+        assert_eq!(keys, vec!["a", "b", "d", "e", "f", "h", "i", "j", "y", "z"]);
+
+        std::fs::remove_dir_all(index_folder).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_range_search() {
+        let temp_dir = tempdir().unwrap();
+        let index_folder = temp_dir.path().join("indx");
+
+        std::fs::create_dir(index_folder.clone()).unwrap();
+
+        let index = IndexShard::new(
+            index_folder.clone(),
+            "indx".to_string(),
+            32,
+            8,
+            None,
+            Some(true),
+            None,
+        );
+
+        let key_size = 32;
+
+        // Inserted in bulk and out of order
+        index.raw_insert(vec![
+            (StringIndexKey("d".repeat(key_size)), vec![3u8; 8].into()),
+            (StringIndexKey("a".repeat(key_size)), vec![0u8; 8].into()),
+            (StringIndexKey("c".repeat(key_size)), vec![2u8; 8].into()),
+            (StringIndexKey("b".repeat(key_size)), vec![1u8; 8].into()),
+        ]);
+
+        let keys = |from: Bound<StringIndexKey>, to: Bound<StringIndexKey>| {
+            index
+                .range_search(from, to)
+                .into_iter()
+                .map(|(k, _)| k.0)
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            keys(Bound::Unbounded, Bound::Unbounded),
+            vec![
+                "a".repeat(key_size),
+                "b".repeat(key_size),
+                "c".repeat(key_size),
+                "d".repeat(key_size)
+            ]
+        );
+        assert_eq!(
+            keys(
+                Bound::Excluded(StringIndexKey("a".repeat(key_size))),
+                Bound::Included(StringIndexKey("c".repeat(key_size)))
+            ),
+            vec!["b".repeat(key_size), "c".repeat(key_size)]
+        );
+        assert_eq!(
+            keys(
+                Bound::Included(StringIndexKey("b".repeat(key_size))),
+                Bound::Excluded(StringIndexKey("d".repeat(key_size)))
+            ),
+            vec!["b".repeat(key_size), "c".repeat(key_size)]
+        );
+
+        std::fs::remove_dir_all(index_folder).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_replace_value() {
+        let temp_dir = tempdir().unwrap();
+        let index_folder = temp_dir.path().join("indx");
+
+        std::fs::create_dir(index_folder.clone()).unwrap();
+
+        let index: IndexShard<StringIndexKey, RawIndexValue> = IndexShard::new(
+            index_folder.clone(),
+            "indx".to_string(),
+            32,
+            8,
+            None,
+            Some(true),
+            None,
+        );
+
+        let key = |s: &str| StringIndexKey(s.repeat(32));
+        let value = |v: u8| -> RawIndexValue { vec![v; 8].into() };
+
+        // Two entries share the same key
+        index.raw_insert(vec![
+            (key("b"), value(1)),
+            (key("a"), value(0)),
+            (key("b"), value(2)),
+        ]);
+
+        assert!(index.replace_value(key("b"), value(2), value(7)));
+        assert!(!index.replace_value(key("b"), value(2), value(7)));
+        assert!(!index.replace_value(key("c"), value(1), value(7)));
+
+        let entries: Vec<(String, Vec<u8>)> = index
+            .range_search(Bound::Unbounded, Bound::Unbounded)
+            .into_iter()
+            .map(|(k, v)| (k.0, v.0))
+            .collect();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], ("a".repeat(32), vec![0u8; 8]));
+        assert!(entries.contains(&("b".repeat(32), vec![1u8; 8])));
+        assert!(entries.contains(&("b".repeat(32), vec![7u8; 8])));
+
         std::fs::remove_dir_all(index_folder).unwrap();
     }
 
@@ -366,6 +676,7 @@ mod test {
             1024,
             None,
             Some(true),
+            None,
         );
 
         let pad_key = |s: &str| -> String {
@@ -0,0 +1,226 @@
+use crate::composite_key::CompositeKey;
+use crate::data::index_shard::IndexShard;
+use crate::implementations::btree::btree_index_header::{
+    BTREE_INDEX_KEY_SEPARATOR, BTREE_INDEX_KEY_SIZE, BTREE_INDEX_VALUE_SIZE,
+};
+use crate::index_keys::IndexKeyType;
+use crate::keys::string_index::StringIndexKey;
+use crate::types::Index;
+use crate::vals::raw_value::RawIndexValue;
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::errors::ShardErrors;
+use std::fmt::Debug;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Ordered index. Keys are stored as fixed size strings (padded with `\0`) in binary order,
+/// which allows answering range predicates by finding the lower bound and walking forward.
+///
+/// Keys longer than `BTREE_INDEX_KEY_SIZE` bytes are truncated, therefore they are only ordered by their prefix.
+#[derive(Debug)]
+pub struct BTreeIndex {
+    pub index: Arc<IndexShard<StringIndexKey, RawIndexValue>>,
+}
+
+impl BTreeIndex {
+    pub fn new_from_path<P: AsRef<Path> + Clone>(
+        path: P,
+        index_name: Option<String>,
+        capacity: Option<u64>,
+        encryption: Option<EncryptionKey>,
+    ) -> Self {
+        let index_shard = IndexShard::new(
+            path,
+            index_name.unwrap_or_else(|| "btreeindx".to_string()),
+            BTREE_INDEX_KEY_SIZE,
+            BTREE_INDEX_VALUE_SIZE,
+            capacity,
+            Some(true),
+            encryption,
+        );
+
+        Self {
+            index: Arc::new(index_shard),
+        }
+    }
+
+    fn pad_key(key: String) -> String {
+        let mut end = std::cmp::min(key.len(), BTREE_INDEX_KEY_SIZE);
+        while !key.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let mut padded = key[..end].to_string();
+        padded.push_str("\0".repeat(BTREE_INDEX_KEY_SIZE - end).as_str());
+        padded
+    }
+
+    fn to_pointer(value: RawIndexValue) -> u64 {
+        u64::from_le_bytes(value.0.as_slice().try_into().unwrap())
+    }
+
+    fn to_bound(bound: Bound<IndexKeyType>) -> Bound<StringIndexKey> {
+        match bound {
+            Bound::Included(key) => Bound::Included(key.into_string().unwrap()),
+            Bound::Excluded(key) => Bound::Excluded(key.into_string().unwrap()),
+            Bound::Unbounded => Bound::Unbounded,
+        }
+    }
+
+    pub fn find_index(&self, find: StringIndexKey) -> Option<u64> {
+        self.index
+            .binary_search(find)
+            .map(|(_, _, val)| Self::to_pointer(val))
+    }
+}
+
+impl Index for BTreeIndex {
+    fn to_key(&self, key: CompositeKey) -> IndexKeyType {
+        let joined = key
+            .0
+            .into_iter()
+            .map(|(_, val)| val)
+            .collect::<Vec<String>>()
+            .join(BTREE_INDEX_KEY_SEPARATOR.to_string().as_str());
+
+        IndexKeyType::String(StringIndexKey(Self::pad_key(joined)))
+    }
+
+    fn bulk_insert(&self, data: Vec<(IndexKeyType, u64)>) {
+        self.index.raw_insert(
+            data.into_iter()
+                .map(|i| {
+                    (
+                        i.0.into_string().unwrap(),
+                        i.1.to_le_bytes().to_vec().into(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn insert(&self, key: IndexKeyType, row_position: u64) {
+        let key = key.into_string().unwrap();
+        self.index
+            .insert(key, row_position.to_le_bytes().to_vec().into());
+    }
+
+    fn get(&self, key: &IndexKeyType) -> Option<u64> {
+        self.find_index(key.clone().into_string().unwrap())
+    }
+
+    fn get_all(&self, key: &IndexKeyType) -> Vec<u64> {
+        self.index
+            .get_all(key.clone().into_string().unwrap())
+            .into_iter()
+            .map(Self::to_pointer)
+            .collect()
+    }
+
+    /// Entries are never removed from the shards of the index: deleted rows are tombstoned in their table and
+    /// skipped when resolved, and compaction rebuilds the index without them. Returns `None`.
+    fn remove(&mut self, _key: &IndexKeyType) -> Option<u64> {
+        None
+    }
+
+    fn replace(&self, key: IndexKeyType, old_row_position: u64, new_row_position: u64) -> bool {
+        self.index.replace_value(
+            key.into_string().unwrap(),
+            old_row_position.to_le_bytes().to_vec().into(),
+            new_row_position.to_le_bytes().to_vec().into(),
+        )
+    }
+
+    fn supported_search_operators(&self) -> Vec<String> {
+        vec![
+            String::from("="),
+            String::from(">"),
+            String::from(">="),
+            String::from("<"),
+            String::from("<="),
+            String::from("starts_with"),
+        ]
+    }
+
+    fn sync(&self) -> Result<(), ShardErrors> {
+        self.index.sync()
+    }
+
+    fn entry_count(&self) -> u64 {
+        self.index.entry_count()
+    }
+
+    fn file_size(&self) -> u64 {
+        self.index.file_size()
+    }
+
+    fn entries(&self) -> Vec<(IndexKeyType, u64)> {
+        self.index
+            .range_search(Bound::Unbounded, Bound::Unbounded)
+            .into_iter()
+            .map(|(key, val)| (IndexKeyType::String(key), Self::to_pointer(val)))
+            .collect()
+    }
+
+    fn range(&self, from: Bound<IndexKeyType>, to: Bound<IndexKeyType>) -> Option<Vec<u64>> {
+        let entries = self
+            .index
+            .range_search(Self::to_bound(from), Self::to_bound(to));
+
+        Some(
+            entries
+                .into_iter()
+                .map(|(_, val)| Self::to_pointer(val))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::composite_key::CompositeKey;
+    use crate::implementations::btree::btree_index::BTreeIndex;
+    use crate::types::Index;
+    use std::ops::Bound;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    pub async fn test_btree_range() {
+        let temp_dir = tempdir().unwrap();
+
+        let btreeindx = temp_dir.as_ref().to_path_buf().join("btreeindx");
+        std::fs::create_dir(btreeindx.clone()).unwrap();
+
+        let index = BTreeIndex::new_from_path(btreeindx.clone(), None, None, None);
+
+        let key = |val: &str| {
+            index.to_key(CompositeKey(vec![(
+                String::from("user_email"),
+                String::from(val),
+            )]))
+        };
+
+        index.bulk_insert(vec![
+            (key("c@outlook.com"), 2),
+            (key("a@outlook.com"), 0),
+            (key("d@gmail.com"), 3),
+            (key("b@outlook.com"), 1),
+        ]);
+
+        assert_eq!(index.get(&key("d@gmail.com")), Some(3));
+        assert_eq!(index.get(&key("e@gmail.com")), None);
+
+        let range = index
+            .range(Bound::Included(key("b@outlook.com")), Bound::Unbounded)
+            .unwrap();
+        assert_eq!(range, vec![1, 2, 3]);
+
+        let range = index
+            .range(Bound::Unbounded, Bound::Excluded(key("c@outlook.com")))
+            .unwrap();
+        assert_eq!(range, vec![0, 1]);
+
+        std::fs::remove_dir_all(btreeindx).unwrap();
+    }
+}
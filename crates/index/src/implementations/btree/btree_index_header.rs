@@ -0,0 +1,5 @@
+pub const BTREE_INDEX_KEY_SIZE: usize = 128;
+pub const BTREE_INDEX_VALUE_SIZE: usize = 8;
+/// Separates the values of a composite key. It sorts below any printable character,
+/// so `("a", "b")` is always placed before `("ab", ...)`.
+pub const BTREE_INDEX_KEY_SEPARATOR: char = '\u{1f}';
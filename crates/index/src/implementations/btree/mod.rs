@@ -0,0 +1,2 @@
+pub mod btree_index;
+mod btree_index_header;
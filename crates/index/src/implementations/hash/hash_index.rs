@@ -7,8 +7,11 @@ use crate::index_keys::IndexKeyType;
 use crate::keys::index_key_sha256::IndexKeySha256;
 use crate::types::Index;
 use crate::vals::raw_value::RawIndexValue;
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::errors::ShardErrors;
 use std::fmt::Debug;
 use std::io::{Seek, Write};
+use std::ops::Bound;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -22,6 +25,7 @@ impl HashIndex {
         path: P,
         index_name: Option<String>,
         capacity: Option<u64>,
+        encryption: Option<EncryptionKey>,
     ) -> Self {
         let index_shard = IndexShard::new(
             path,
@@ -30,6 +34,7 @@ impl HashIndex {
             HASH_INDEX_VALUE_SIZE,
             capacity,
             Some(true),
+            encryption,
         );
 
         Self {
@@ -77,13 +82,54 @@ impl Index for HashIndex {
         self.find_index(key.clone().into_sha256().unwrap())
     }
 
+    fn get_all(&self, key: &IndexKeyType) -> Vec<u64> {
+        self.index
+            .get_all(key.clone().into_sha256().unwrap())
+            .into_iter()
+            .map(|val| u64::from_le_bytes(val.0.as_slice().try_into().unwrap()))
+            .collect()
+    }
+
     fn remove(&mut self, key: &IndexKeyType) -> Option<u64> {
         todo!()
     }
 
+    fn replace(&self, key: IndexKeyType, old_row_position: u64, new_row_position: u64) -> bool {
+        self.index.replace_value(
+            key.into_sha256().unwrap(),
+            old_row_position.to_le_bytes().to_vec().into(),
+            new_row_position.to_le_bytes().to_vec().into(),
+        )
+    }
+
     fn supported_search_operators(&self) -> Vec<String> {
         vec![String::from("=")]
     }
+
+    fn sync(&self) -> Result<(), ShardErrors> {
+        self.index.sync()
+    }
+
+    fn entry_count(&self) -> u64 {
+        self.index.entry_count()
+    }
+
+    fn file_size(&self) -> u64 {
+        self.index.file_size()
+    }
+
+    fn entries(&self) -> Vec<(IndexKeyType, u64)> {
+        self.index
+            .range_search(Bound::Unbounded, Bound::Unbounded)
+            .into_iter()
+            .map(|(key, val)| {
+                (
+                    IndexKeyType::Sha256(key),
+                    u64::from_le_bytes(val.0.as_slice().try_into().unwrap()),
+                )
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -103,7 +149,7 @@ mod test {
         let hashindx = temp_dir.as_ref().to_path_buf().join("hashindx");
         std::fs::create_dir(hashindx.clone()).unwrap();
 
-        let mut index = HashIndex::new_from_path(hashindx.clone(), None, None);
+        let mut index = HashIndex::new_from_path(hashindx.clone(), None, None, None);
 
         add_data(&mut index);
 
@@ -118,13 +164,44 @@ mod test {
         std::fs::create_dir(hashindx.clone()).unwrap();
 
         // This will create a shard every two elements
-        let mut index = HashIndex::new_from_path(hashindx.clone(), None, Some(2));
+        let mut index = HashIndex::new_from_path(hashindx.clone(), None, Some(2), None);
 
         add_data(&mut index);
 
         std::fs::remove_dir_all(hashindx).unwrap();
     }
 
+    #[tokio::test]
+    pub async fn test_get_all() {
+        let temp_dir = tempdir().unwrap();
+
+        let hashindx = temp_dir.as_ref().to_path_buf().join("hashindx");
+        std::fs::create_dir(hashindx.clone()).unwrap();
+
+        // Entries of the same key end up in different shards
+        let index = HashIndex::new_from_path(hashindx.clone(), None, Some(2), None);
+        let key = |city: &str| {
+            index.to_key(CompositeKey(vec![(
+                String::from("city"),
+                String::from(city),
+            )]))
+        };
+
+        index.insert(key("City1"), 1);
+        index.insert(key("City2"), 2);
+        index.insert(key("City1"), 3);
+        index.insert(key("City3"), 4);
+        index.insert(key("City1"), 5);
+
+        let mut pointers = index.get_all(&key("City1"));
+        pointers.sort();
+        assert_eq!(pointers, vec![1, 3, 5]);
+        assert_eq!(index.get_all(&key("City2")), vec![2]);
+        assert!(index.get_all(&key("City4")).is_empty());
+
+        std::fs::remove_dir_all(hashindx).unwrap();
+    }
+
     fn add_data(index: &mut HashIndex) {
         let usernames = vec![
             String::from("user1"),
@@ -1,3 +1,4 @@
+use crate::implementations::btree::btree_index::BTreeIndex;
 use crate::implementations::hash::hash_index::HashIndex;
 use crate::types::{Index, IndexKey};
 use enum_as_inner::EnumAsInner;
@@ -6,17 +7,24 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, EnumAsInner, Clone, PartialEq, Serialize, Deserialize)]
 pub enum IndexType {
     Hash,
+    BTree,
+    /// Index over a `Point` column, stored as a `BTreeIndex` keyed by the geohash of the points.
+    Geo,
+    /// Index over `Array` columns, stored as a `HashIndex` holding an entry per item of the arrays.
+    MultiEntry,
 }
 
 #[derive(Debug)]
 pub enum IndexTypeValue {
     Hash(HashIndex),
+    BTree(BTreeIndex),
 }
 
 impl IndexTypeValue {
     pub fn as_index(&self) -> Box<&dyn Index> {
         match self {
             IndexTypeValue::Hash(indx) => Box::new(indx),
+            IndexTypeValue::BTree(indx) => Box::new(indx),
         }
     }
 }
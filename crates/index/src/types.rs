@@ -1,7 +1,9 @@
 use crate::composite_key::CompositeKey;
 use crate::data::index_data_unit::IndexDataUnit;
 use crate::index_keys::IndexKeyType;
+use schemajs_data::errors::ShardErrors;
 use std::fmt::Debug;
+use std::ops::Bound;
 
 pub trait IndexKey:
     From<Vec<u8>> + Into<Vec<u8>> + Ord + Clone + Into<String> + From<IndexDataUnit>
@@ -19,7 +21,33 @@ pub trait Index: Debug {
 
     fn get(&self, key: &IndexKeyType) -> Option<u64>;
 
+    /// Returns the row positions of every entry whose key is `key`.
+    /// Unlike `get`, keys held by several rows (non unique indexes) resolve to all of them.
+    fn get_all(&self, key: &IndexKeyType) -> Vec<u64>;
+
     fn remove(&mut self, key: &IndexKeyType) -> Option<u64>;
 
+    /// Moves the entry `key -> old_row_position` to `new_row_position`.
+    /// Returns `false` if there is no such entry.
+    fn replace(&self, key: IndexKeyType, old_row_position: u64, new_row_position: u64) -> bool;
+
     fn supported_search_operators(&self) -> Vec<String>;
+
+    /// Waits until the entries of the index are on disk.
+    fn sync(&self) -> Result<(), ShardErrors>;
+
+    /// Amount of entries held by the index.
+    fn entry_count(&self) -> u64;
+
+    /// Size in bytes of the files of the index.
+    fn file_size(&self) -> u64;
+
+    /// Every entry of the index as its key and row position, e.g. to check them against the rows.
+    fn entries(&self) -> Vec<(IndexKeyType, u64)>;
+
+    /// Returns the row positions whose key falls between `from` and `to`, in key order.
+    /// Indexes with no notion of order (such as `HashIndex`) return `None`.
+    fn range(&self, from: Bound<IndexKeyType>, to: Bound<IndexKeyType>) -> Option<Vec<u64>> {
+        None
+    }
 }
@@ -0,0 +1,369 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments,
+    Type,
+};
+
+/// Column of the internal uid of every row, see `Table::get_internal_uid`.
+const UID_COLUMN: &str = "_uid";
+
+/// Field of a struct deriving `SchemaRow`.
+///
+/// # Fields:
+/// - `ident`: Name of the field.
+/// - `column`: Column the field is stored in, the name of the field unless renamed.
+/// - `data_type`: Expression building the `DataTypes` of the column.
+/// - `optional`: Whether the field is an `Option`, which makes the column nullable.
+/// - `uid`: Whether the field holds the uid of the row.
+struct SchemaField {
+    ident: Ident,
+    column: String,
+    data_type: TokenStream2,
+    optional: bool,
+    uid: bool,
+}
+
+/// Implements `Row`, `RowSerializer` and `From<&[u8]>` for a struct with named fields, so it can be stored
+/// through a `SingleQueryManager` like `RowJson` rows, and generates `table()`, the definition of its table.
+///
+/// Rows are stored the way `RowJson` stores them, so the tables of typed rows can be read as JSON too.
+///
+/// # Attributes:
+/// - `#[schema(table = "users")]` on the struct: Name of the table, the name of the struct in snake case unless set.
+/// - `#[schema(uid)]` on a `Uuid` field: Field holding the `_uid` of the row. Required, exactly once.
+/// - `#[schema(rename = "user_name")]` on a field: Column of the field, the name of the field unless set.
+///
+/// Fields are mapped to columns by type: `String` to `String`, `bool` to `Boolean`, signed integers to `Int`,
+/// unsigned ones to `Uint`, `f32` and `f64` to `Float`, `Uuid` to `Uuid`, `Vec<T>` to `Array` and `Option<T>`
+/// to a nullable column of `T`. Field types must implement `Serialize`, `DeserializeOwned` and `Default`
+/// (the value of fields missing from a stored row). The struct must implement `Debug`, and its crate depend on
+/// `schemajs_query`, `schemajs_primitives` and `serde_json`.
+#[proc_macro_derive(SchemaRow, attributes(schema))]
+pub fn derive_schema_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "`SchemaRow` can't be derived for generic structs",
+        ));
+    }
+
+    let mut table = snake_case(&input.ident.to_string());
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("schema"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("unknown `schema` attribute, expected `table`"))
+            }
+        })?;
+    }
+
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`SchemaRow` requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`SchemaRow` can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut fields = vec![];
+    for field in named {
+        let ident = field.ident.clone().unwrap();
+        let mut column = ident.to_string();
+        let mut uid = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("schema"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("uid") {
+                    uid = true;
+                    Ok(())
+                } else if meta.path.is_ident("rename") {
+                    column = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown `schema` attribute, expected `uid` or `rename`"))
+                }
+            })?;
+        }
+        if uid {
+            column = UID_COLUMN.to_string();
+        }
+
+        let (data_type, optional) = match option_inner(&field.ty) {
+            Some(inner) => (data_type(inner)?, true),
+            None => (data_type(&field.ty)?, false),
+        };
+
+        fields.push(SchemaField {
+            ident,
+            column,
+            data_type,
+            optional,
+            uid,
+        });
+    }
+
+    if fields.iter().filter(|field| field.uid).count() != 1 {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`SchemaRow` requires exactly one `Uuid` field marked `#[schema(uid)]`",
+        ));
+    }
+
+    let name = &input.ident;
+    let idents: Vec<&Ident> = fields.iter().map(|field| &field.ident).collect();
+    let columns: Vec<&String> = fields.iter().map(|field| &field.column).collect();
+    // `Table::new` already adds the uid column
+    let definitions = fields.iter().filter(|field| !field.uid).map(|field| {
+        let column = &field.column;
+        let data_type = &field.data_type;
+        let required = !field.optional;
+        quote! {
+            .add_column(
+                ::schemajs_primitives::column::Column::new(#column, #data_type)
+                    .set_required(#required)
+                    .set_nullable(!#required)
+            )
+        }
+    });
+
+    Ok(quote! {
+        impl #name {
+            /// Definition of the table holding the rows.
+            pub fn table() -> ::schemajs_primitives::table::Table {
+                ::schemajs_primitives::table::Table::new(#table)
+                    #(#definitions)*
+            }
+
+            /// The row as stored, see `RowJson`.
+            pub fn to_row_data(&self) -> ::schemajs_query::row_json::RowData {
+                let mut value = ::serde_json::Map::new();
+                #(
+                    value.insert(
+                        #columns.to_string(),
+                        ::serde_json::to_value(&self.#idents).unwrap_or(::serde_json::Value::Null),
+                    );
+                )*
+
+                ::schemajs_query::row_json::RowData {
+                    table: #table.to_string(),
+                    value: ::serde_json::Value::Object(value),
+                }
+            }
+
+            /// Reads a stored row back. Fields missing from the row, or holding a value of another type,
+            /// get their default.
+            pub fn from_row_data(data: ::schemajs_query::row_json::RowData) -> Self {
+                Self {
+                    #(
+                        #idents: data
+                            .value
+                            .get(#columns)
+                            .cloned()
+                            .and_then(|value| ::serde_json::from_value(value).ok())
+                            .unwrap_or_default(),
+                    )*
+                }
+            }
+
+            fn as_row_json(&self) -> ::schemajs_query::row_json::RowJson {
+                ::schemajs_query::row_json::RowJson::from(self.to_row_data())
+            }
+        }
+
+        impl ::schemajs_query::serializer::RowSerializer<#name> for #name {
+            fn serialize(
+                &self,
+            ) -> Result<Vec<u8>, ::schemajs_query::serializer::RowSerializationError> {
+                <::schemajs_query::row_json::RowJson as ::schemajs_query::serializer::RowSerializer<
+                    ::schemajs_query::row_json::RowJson,
+                >>::serialize(&self.as_row_json())
+            }
+
+            fn deserialize(
+                &self,
+                data: &[u8],
+            ) -> Result<#name, ::schemajs_query::serializer::RowSerializationError> {
+                ::schemajs_query::row_json::RowJson::from_bytes(data)
+                    .map(|row| Self::from_row_data(row.value))
+            }
+        }
+
+        impl From<&[u8]> for #name {
+            fn from(data: &[u8]) -> Self {
+                Self::from_row_data(::schemajs_query::row_json::RowJson::from(data).value)
+            }
+        }
+
+        impl ::schemajs_query::row::Row<#name> for #name {
+            fn get_value(
+                &self,
+                column: &::schemajs_primitives::column::Column,
+            ) -> Option<::schemajs_primitives::column::types::DataValue> {
+                match column.name.as_str() {
+                    #(
+                        #columns => ::serde_json::to_value(&self.#idents).ok().map(|value| {
+                            ::schemajs_primitives::column::types::DataValue::from((column, &value))
+                        }),
+                    )*
+                    _ => column.literal_default_value(),
+                }
+            }
+
+            fn has_value(&self, column: &::schemajs_primitives::column::Column) -> bool {
+                matches!(column.name.as_str(), #(#columns)|*)
+            }
+
+            fn set_value(
+                &mut self,
+                column: &::schemajs_primitives::column::Column,
+                value: ::schemajs_primitives::column::types::DataValue,
+            ) {
+                let value = column.data_type.to_stored_json(&value);
+                match column.name.as_str() {
+                    #(
+                        #columns => {
+                            if let Ok(value) = ::serde_json::from_value(value) {
+                                self.#idents = value;
+                            }
+                        }
+                    )*
+                    // Columns without a field (e.g. timestamps) are not kept
+                    _ => {}
+                }
+            }
+
+            fn get_table_name(&self) -> String {
+                #table.to_string()
+            }
+
+            fn validate(
+                &self,
+                table: &::schemajs_primitives::table::Table,
+            ) -> Result<(), ::schemajs_query::errors::QueryError> {
+                <::schemajs_query::row_json::RowJson as ::schemajs_query::row::Row<
+                    ::schemajs_query::row_json::RowJson,
+                >>::validate(&self.as_row_json(), table)
+            }
+
+            fn serialize_compact(
+                &self,
+                layout: &::schemajs_query::serializer::compact::CompactLayout,
+            ) -> Result<Vec<u8>, ::schemajs_query::serializer::RowSerializationError> {
+                <::schemajs_query::row_json::RowJson as ::schemajs_query::row::Row<
+                    ::schemajs_query::row_json::RowJson,
+                >>::serialize_compact(&self.as_row_json(), layout)
+            }
+
+            fn serialize_with(
+                &self,
+                serializer: &::schemajs_query::serializer::registry::RegisteredSerializer,
+                table: &::schemajs_primitives::table::Table,
+            ) -> Result<Vec<u8>, ::schemajs_query::serializer::RowSerializationError> {
+                <::schemajs_query::row_json::RowJson as ::schemajs_query::row::Row<
+                    ::schemajs_query::row_json::RowJson,
+                >>::serialize_with(&self.as_row_json(), serializer, table)
+            }
+        }
+    })
+}
+
+/// `T` of `Option<T>`, `None` for any other type.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    generic_inner(ty, "Option")
+}
+
+fn generic_inner<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let segment = match ty {
+        Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Expression building the `DataTypes` of the column of a field of type `ty`.
+fn data_type(ty: &Type) -> syn::Result<TokenStream2> {
+    let types = quote! { ::schemajs_primitives::column::types::DataTypes };
+
+    if let Some(inner) = generic_inner(ty, "Vec") {
+        let inner = data_type(inner)?;
+        return Ok(quote! { #types::Array(Box::new(#inner)) });
+    }
+
+    let ident = match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    };
+
+    match ident.as_deref() {
+        Some("String") => Ok(quote! { #types::String }),
+        Some("bool") => Ok(quote! { #types::Boolean }),
+        Some("i8" | "i16" | "i32" | "i64" | "isize") => Ok(quote! { #types::Int }),
+        Some("u8" | "u16" | "u32" | "u64" | "usize") => Ok(quote! { #types::Uint }),
+        Some("f32" | "f64") => Ok(quote! { #types::Float }),
+        Some("Uuid") => Ok(quote! { #types::Uuid }),
+        _ => Err(syn::Error::new(
+            ty.span(),
+            "`SchemaRow` can't map this type to a column type",
+        )),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
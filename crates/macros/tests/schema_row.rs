@@ -0,0 +1,119 @@
+use schemajs_dirs::create_scheme_js_db;
+use schemajs_macros::SchemaRow;
+use schemajs_primitives::column::types::{DataTypes, DataValue};
+use schemajs_query::managers::single::SingleQueryManager;
+use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+use schemajs_query::row::Row;
+use schemajs_query::row_json::RowJson;
+use schemajs_query::serializer::RowSerializer;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, SchemaRow)]
+#[schema(table = "users")]
+struct User {
+    #[schema(uid)]
+    id: Uuid,
+    #[schema(rename = "user_name")]
+    name: String,
+    age: u32,
+    score: f64,
+    tags: Vec<String>,
+    email: Option<String>,
+}
+
+#[derive(Debug, SchemaRow)]
+struct AuditEntry {
+    #[schema(uid)]
+    id: Uuid,
+    action: String,
+}
+
+fn user(name: &str, age: u32) -> User {
+    User {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        age,
+        score: 1.5,
+        tags: vec!["admin".to_string()],
+        email: None,
+    }
+}
+
+#[flaky_test::flaky_test]
+fn test_schema_row() {
+    let table = User::table();
+    assert_eq!(table.name, "users");
+    assert!(table.get_column("_uid").is_some());
+    assert!(table.get_column("id").is_none());
+    assert!(matches!(
+        table.get_column("user_name").unwrap().data_type,
+        DataTypes::String
+    ));
+    assert!(matches!(
+        table.get_column("age").unwrap().data_type,
+        DataTypes::Uint
+    ));
+    assert!(matches!(
+        table.get_column("tags").unwrap().data_type,
+        DataTypes::Array(_)
+    ));
+    assert!(table.get_column("email").unwrap().nullable);
+    assert!(!table.get_column("age").unwrap().nullable);
+    assert_eq!(AuditEntry::table().name, "audit_entry");
+
+    // Rows are stored like `RowJson` rows
+    let andres = user("andres", 28);
+    let data = andres.serialize().unwrap();
+    assert_eq!(User::from(data.as_slice()), andres);
+    assert_eq!(
+        RowJson::from(data.as_slice()).value.value["user_name"],
+        "andres"
+    );
+
+    let db_name = Uuid::new_v4().to_string();
+    let db_folder = create_scheme_js_db(None, &db_name);
+    let query_manager: SingleQueryManager<User> = SingleQueryManager::new(db_name);
+    query_manager.register_table(table).unwrap();
+
+    query_manager.insert(andres.clone()).unwrap();
+    query_manager.insert(user("carlos", 30)).unwrap();
+    query_manager
+        .tables
+        .get("users")
+        .unwrap()
+        .temps
+        .reconcile_all();
+
+    let by_name = |name: &str| {
+        QueryOps::Condition(QueryVal {
+            key: "user_name".to_string(),
+            filter_type: "=".to_string(),
+            value: DataValue::String(name.to_string()),
+        })
+    };
+    let mut new_values = HashMap::new();
+    new_values.insert(
+        "email".to_string(),
+        DataValue::String("carlos@schemajs.com".to_string()),
+    );
+    query_manager
+        .update("users".to_string(), &by_name("carlos"), &new_values)
+        .unwrap();
+
+    let found = query_manager
+        .search_manager()
+        .search("users".to_string(), &by_name("andres"))
+        .unwrap();
+    assert_eq!(found, vec![andres]);
+
+    let carlos = query_manager
+        .search_manager()
+        .search("users".to_string(), &by_name("carlos"))
+        .unwrap();
+    assert_eq!(carlos.len(), 1);
+    assert_eq!(carlos[0].email.as_deref(), Some("carlos@schemajs.com"));
+    assert_eq!(carlos[0].get_table_name(), "users");
+
+    std::fs::remove_dir_all(db_folder).unwrap();
+}
@@ -78,7 +78,7 @@ impl ModuleLoader for TypescriptModuleLoader {
                 _ => bail!("Unknown extension {:?}", path.extension()),
             };
 
-            println!("{}", &path.to_str().unwrap());
+            log::debug!("Loading module {}", path.display());
 
             let code = std::fs::read_to_string(&path)?;
             let code = if should_transpile {
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+
+/// How the string values of a column compare (`collation` of the column).
+///
+/// Values are stored as received, the collation only decides which values are equal and how they sort: unique
+/// constraints, index keys, filters and sorting all go through `collate`, so with `CaseInsensitive`, `US` and `us`
+/// are the same key and match each other.
+///
+/// - `Binary`: Byte-wise, `US` and `us` differ.
+/// - `CaseInsensitive`: Ignores the case of ASCII letters (`US` matches `us`, `Ñ` doesn't match `ñ`).
+/// - `Unicode`: Ignores the case of every letter (`Ñ` matches `ñ`) and the way characters are encoded: values are
+///   compared in their compatibility form (NFKC), so `é` matches `e` followed by a combining accent and `ﬁ` matches `fi`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Collation {
+    #[default]
+    Binary,
+    CaseInsensitive,
+    Unicode,
+}
+
+impl Collation {
+    /// Form of `value` values are compared in: two values are equal under the collation when their forms are equal.
+    pub fn collate<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        match self {
+            Collation::Binary => Cow::Borrowed(value),
+            Collation::CaseInsensitive if !value.bytes().any(|b| b.is_ascii_uppercase()) => {
+                Cow::Borrowed(value)
+            }
+            Collation::CaseInsensitive => Cow::Owned(value.to_ascii_lowercase()),
+            Collation::Unicode => Cow::Owned(value.nfkc().collect::<String>().to_lowercase()),
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Collation::Binary)
+    }
+}
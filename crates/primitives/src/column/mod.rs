@@ -1,6 +1,17 @@
+pub mod collation;
 pub mod types;
-use crate::column::types::DataTypes;
+use crate::column::collation::Collation;
+use crate::column::types::{timestamp_from_json, DataTypes, DataValue};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Default value generating the current time: epoch millis for `Number`, `Int` and `Timestamp` columns,
+/// RFC 3339 for `String` columns.
+pub const DEFAULT_NOW: &str = "now()";
+/// Default value generating a new v4 uuid, for `Uuid` and `String` columns.
+pub const DEFAULT_UUID: &str = "uuid()";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -9,8 +20,20 @@ pub struct Column {
     pub data_type: DataTypes,
     pub default_value: Option<String>,
     pub required: bool,
+    /// Whether the column accepts null (or missing) values. Columns are nullable unless stated otherwise.
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
     pub comment: Option<String>,
     pub primary_key: bool,
+    /// Expression every value of the column must satisfy, such as `user_age >= 0 && user_age <= 150`.
+    pub check: Option<String>,
+    /// How the string values of the column compare, see `Collation`.
+    #[serde(default)]
+    pub collation: Collation,
+}
+
+fn default_nullable() -> bool {
+    true
 }
 
 impl Column {
@@ -21,7 +44,10 @@ impl Column {
             default_value: None,
             comment: None,
             required: false,
+            nullable: true,
             primary_key: false,
+            check: None,
+            collation: Collation::Binary,
         }
     }
 
@@ -35,6 +61,11 @@ impl Column {
         self
     }
 
+    pub fn set_nullable(mut self, nullable: bool) -> Self {
+        self.nullable = nullable;
+        self
+    }
+
     pub fn set_default_value(mut self, default_value: &str) -> Self {
         self.default_value = Some(default_value.to_string());
         self
@@ -44,4 +75,71 @@ impl Column {
         self.comment = Some(comment.to_string());
         self
     }
+
+    pub fn set_check(mut self, check: &str) -> Self {
+        self.check = Some(check.to_string());
+        self
+    }
+
+    pub fn set_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    /// Form of `value` it is compared in under the collation of the column, see `DataValue::collate`.
+    pub fn collate(&self, value: DataValue) -> DataValue {
+        value.collate(self.collation)
+    }
+
+    /// Column describing the field `name` of the values of this column, for `Object` columns.
+    pub fn field(&self, name: &str) -> Option<&Column> {
+        match &self.data_type {
+            DataTypes::Object(columns) => columns.iter().find(|column| column.name == name),
+            _ => None,
+        }
+    }
+
+    /// Value given to this column when a row is inserted without it.
+    ///
+    /// `DEFAULT_NOW` and `DEFAULT_UUID` generate a new value on every call, any other default is a literal
+    /// parsed after the column type (anything but strings and uuids is expected as JSON).
+    ///
+    /// # Returns:
+    /// - `Option<DataValue>`: `None` if the column has no default or it does not fit the column type.
+    pub fn generate_default_value(&self) -> Option<DataValue> {
+        let default_value = self.default_value.as_ref()?;
+
+        match (default_value.as_str(), &self.data_type) {
+            (DEFAULT_NOW, DataTypes::Number | DataTypes::Int | DataTypes::Timestamp) => Some(
+                DataValue::Number(chrono::Utc::now().timestamp_millis().into()),
+            ),
+            (DEFAULT_NOW, DataTypes::String) => {
+                Some(DataValue::String(chrono::Utc::now().to_rfc3339()))
+            }
+            (DEFAULT_UUID, DataTypes::Uuid) => Some(DataValue::Uuid(Uuid::new_v4())),
+            (DEFAULT_UUID, DataTypes::String) => {
+                Some(DataValue::String(Uuid::new_v4().to_string()))
+            }
+            (literal, DataTypes::String) => Some(DataValue::String(literal.to_string())),
+            (literal, DataTypes::Uuid) => Uuid::from_str(literal).ok().map(DataValue::Uuid),
+            (literal, DataTypes::Timestamp) => {
+                let value = serde_json::from_str::<Value>(literal)
+                    .unwrap_or_else(|_| Value::String(literal.to_string()));
+                timestamp_from_json(&value).map(|millis| DataValue::Number(millis.into()))
+            }
+            (literal, _) => serde_json::from_str::<Value>(literal)
+                .ok()
+                .filter(|value| self.data_type.accepts(value))
+                .map(|value| DataValue::from((self, &value))),
+        }
+    }
+
+    /// Default of this column if it is a literal, the value rows written before the column existed are read with.
+    /// Generated defaults (`DEFAULT_NOW`, `DEFAULT_UUID`) have no such value.
+    pub fn literal_default_value(&self) -> Option<DataValue> {
+        match self.default_value.as_deref()? {
+            DEFAULT_NOW | DEFAULT_UUID => None,
+            _ => self.generate_default_value(),
+        }
+    }
 }
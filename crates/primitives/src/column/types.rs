@@ -1,9 +1,12 @@
+use crate::column::collation::Collation;
 use crate::column::Column;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
 use enum_as_inner::EnumAsInner;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use serde_json::Value;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use uuid::Uuid;
 
@@ -14,6 +17,130 @@ pub enum DataTypes {
     String,
     Boolean,
     Number,
+    /// Signed integer, stored as an `i64`.
+    Int,
+    /// Unsigned integer, stored as an `u64`.
+    Uint,
+    /// Floating point number, stored as an `f64`.
+    Float,
+    /// Point in time, received as an ISO-8601 string or epoch millis and stored as epoch millis.
+    Timestamp,
+    Array(Box<DataTypes>),
+    /// String restricted to the given variants, stored as the ordinal of the variant.
+    Enum(Vec<String>),
+    /// Nested document whose fields are described by their own columns.
+    /// Fields not described by a column are rejected.
+    Object(Vec<Column>),
+    /// Geographic point, received as `{ "lat": .., "lng": .. }` in degrees and stored with `f64` coordinates.
+    Point,
+}
+
+impl DataTypes {
+    /// Whether `value` (as received from JS) fits this type. `null` fits every type,
+    /// whether a column can be left empty is up to `required`.
+    pub fn accepts(&self, value: &Value) -> bool {
+        match (self, value) {
+            (_, Value::Null) => true,
+            (DataTypes::Uuid, Value::String(val)) => Uuid::from_str(val).is_ok(),
+            (DataTypes::String, Value::String(_)) => true,
+            (DataTypes::Boolean, Value::Bool(_)) => true,
+            (DataTypes::Number | DataTypes::Float, Value::Number(_)) => true,
+            (DataTypes::Int, Value::Number(n)) => n.is_i64(),
+            (DataTypes::Uint, Value::Number(n)) => n.is_u64(),
+            (DataTypes::Timestamp, value) => timestamp_from_json(value).is_some(),
+            (DataTypes::Enum(variants), Value::String(variant)) => variants.contains(variant),
+            (DataTypes::Enum(variants), Value::Number(ordinal)) => ordinal
+                .as_u64()
+                .map_or(false, |ordinal| (ordinal as usize) < variants.len()),
+            (DataTypes::Array(inner), Value::Array(items)) => {
+                items.iter().all(|item| inner.accepts(item))
+            }
+            (DataTypes::Point, Value::Object(fields)) => {
+                let coordinate = |name: &str, max: f64| {
+                    fields
+                        .get(name)
+                        .and_then(|value| value.as_f64())
+                        .map_or(false, |value| value.abs() <= max)
+                };
+                fields.len() == 2 && coordinate("lat", 90.0) && coordinate("lng", 180.0)
+            }
+            (DataTypes::Object(columns), Value::Object(fields)) => {
+                fields.iter().all(|(name, field)| {
+                    columns
+                        .iter()
+                        .find(|column| column.name == *name)
+                        .map_or(false, |column| column.data_type.accepts(field))
+                })
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether values of this type are stored as received. Values of other types are rewritten
+    /// to the form `DataValue` gives them (e.g. timestamps are stored as epoch millis).
+    pub fn is_normalized(&self) -> bool {
+        match self {
+            DataTypes::Float | DataTypes::Timestamp | DataTypes::Enum(_) | DataTypes::Point => {
+                false
+            }
+            DataTypes::Array(inner) => inner.is_normalized(),
+            DataTypes::Object(columns) => columns
+                .iter()
+                .all(|column| column.data_type.is_normalized()),
+            _ => true,
+        }
+    }
+
+    /// JSON form `value` is stored in for this type: the ordinal for enum variants, `DataValue::to_json` otherwise.
+    /// `DataValue::from` reads both forms back.
+    pub fn to_stored_json(&self, value: &DataValue) -> Value {
+        match (self, value) {
+            (DataTypes::Enum(variants), DataValue::String(variant)) => variants
+                .iter()
+                .position(|candidate| candidate == variant)
+                .map_or_else(|| value.to_json(), Value::from),
+            (DataTypes::Array(inner), DataValue::Array(items)) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| inner.to_stored_json(item))
+                    .collect(),
+            ),
+            (DataTypes::Object(columns), DataValue::Object(fields)) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, field)| {
+                        let stored = match columns.iter().find(|column| column.name == *name) {
+                            Some(column) => column.data_type.to_stored_json(field),
+                            None => field.to_json(),
+                        };
+                        (name.clone(), stored)
+                    })
+                    .collect(),
+            ),
+            _ => value.to_json(),
+        }
+    }
+}
+
+/// Reads a timestamp as sent from JS: either epoch millis or an ISO-8601 string
+/// (a date such as `2024-05-01` is read as midnight UTC).
+///
+/// # Returns:
+/// - `Option<i64>`: The timestamp in epoch millis, `None` if `value` is not a valid timestamp.
+pub fn timestamp_from_json(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .map(|date| date.timestamp_millis())
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+                    .map(|date| date.timestamp_millis())
+            }),
+        _ => None,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, EnumAsInner, Eq)]
@@ -23,6 +150,18 @@ pub enum DataValue {
     String(String),
     Boolean(bool),
     Number(serde_json::Number),
+    Array(Vec<DataValue>),
+    Object(BTreeMap<String, DataValue>),
+}
+
+/// Formats a timestamp stored as epoch millis as an ISO-8601 string in UTC (`2024-05-01T00:00:00.000Z`),
+/// which `timestamp_from_json` reads back.
+pub fn timestamp_to_iso(millis: i64) -> Option<String> {
+    NaiveDateTime::from_timestamp_opt(
+        millis.div_euclid(1000),
+        (millis.rem_euclid(1000) * 1_000_000) as u32,
+    )
+    .map(|date| DateTime::<Utc>::from_utc(date, Utc).to_rfc3339_opts(SecondsFormat::Millis, true))
 }
 
 impl DataValue {
@@ -33,6 +172,18 @@ impl DataValue {
             DataValue::Boolean(_) => DataTypes::Boolean,
             DataValue::Number(_) => DataTypes::Number,
             DataValue::Uuid(_) => DataTypes::Uuid,
+            DataValue::Array(items) => DataTypes::Array(Box::new(
+                items
+                    .first()
+                    .map(|item| item.get_type())
+                    .unwrap_or(DataTypes::Null),
+            )),
+            DataValue::Object(fields) => DataTypes::Object(
+                fields
+                    .iter()
+                    .map(|(name, field)| Column::new(name, field.get_type()))
+                    .collect(),
+            ),
         }
     }
 
@@ -43,12 +194,126 @@ impl DataValue {
             DataValue::Boolean(b) => b.to_string(),
             DataValue::Number(n) => n.to_string().to_string(),
             DataValue::Uuid(val) => val.to_string(),
+            DataValue::Array(items) => format!(
+                "[{}]",
+                items
+                    .iter()
+                    .map(|item| item.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+            DataValue::Object(_) => self.to_json().to_string(),
+        }
+    }
+}
+
+impl DataValue {
+    /// String representation whose lexicographic order matches the order of the values (see `Ord for DataValue`)
+    /// among booleans, numbers and strings. Used to build keys for ordered indexes.
+    ///
+    /// Numbers are encoded from their `NumberParts`: the band they fall in, followed by the hex representation of
+    /// their integer part (offset to be positive) and of the sortable bits of their fractional part, or by the
+    /// sortable bits of the float itself for floats beyond the integers. Equal numbers (e.g. `1` and `1.0`) get
+    /// the same key. Any other value uses `to_string`.
+    pub fn to_sortable_string(&self) -> String {
+        match self {
+            DataValue::Number(n) => match NumberParts::of(n) {
+                NumberParts::Below(float) => format!("0{:016x}", sortable_bits(float)),
+                NumberParts::Within(integer, fraction) => format!(
+                    "1{:017x}{:016x}",
+                    integer - i64::MIN as i128,
+                    sortable_bits(fraction)
+                ),
+                NumberParts::Above(float) => format!("2{:016x}", sortable_bits(float)),
+            },
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl DataValue {
+    /// Form of the value it is compared in under `collation`: strings (including the ones nested in arrays and
+    /// objects) are collated, any other value is returned as is.
+    pub fn collate(self, collation: Collation) -> DataValue {
+        if collation.is_binary() {
+            return self;
+        }
+
+        match self {
+            DataValue::String(s) => DataValue::String(collation.collate(&s).into_owned()),
+            DataValue::Array(items) => DataValue::Array(
+                items
+                    .into_iter()
+                    .map(|item| item.collate(collation))
+                    .collect(),
+            ),
+            DataValue::Object(fields) => DataValue::Object(
+                fields
+                    .into_iter()
+                    .map(|(name, field)| (name, field.collate(collation)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+}
+
+impl DataValue {
+    /// Value the dotted `path` leads to inside this value: fields of objects by name, items of arrays by
+    /// position (`tags.0.label`). `None` if a segment of the path is missing.
+    pub fn get_path(&self, path: &str) -> Option<&DataValue> {
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                DataValue::Object(fields) => fields.get(segment),
+                DataValue::Array(items) => items.get(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+}
+
+impl DataValue {
+    /// Numeric representation of the value, used when a number is expected (e.g. aggregations).
+    ///
+    /// - Numbers are returned as is.
+    /// - Booleans are coerced to `1` or `0`.
+    /// - Strings are coerced when they contain a valid number.
+    /// - Any other value can't be coerced and returns `None`.
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            DataValue::Number(n) => n.as_f64(),
+            DataValue::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+            DataValue::String(s) => s.trim().parse::<f64>().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl DataValue {
+    /// JSON representation of the value, as exposed to JS.
+    pub fn to_json(&self) -> Value {
+        match self {
+            DataValue::Null => Value::Null,
+            DataValue::Uuid(val) => Value::String(val.to_string()),
+            DataValue::String(s) => Value::String(s.clone()),
+            DataValue::Boolean(b) => Value::Bool(*b),
+            DataValue::Number(n) => Value::Number(n.clone()),
+            DataValue::Array(items) => Value::Array(items.iter().map(|i| i.to_json()).collect()),
+            DataValue::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), field.to_json()))
+                    .collect(),
+            ),
         }
     }
 }
 
 impl From<(&Column, &Value)> for DataValue {
     fn from(value: (&Column, &Value)) -> Self {
+        if value.1.is_null() {
+            return DataValue::Null;
+        }
+
         match value.0.data_type {
             DataTypes::Null => DataValue::Null,
             DataTypes::Uuid => {
@@ -59,53 +324,187 @@ impl From<(&Column, &Value)> for DataValue {
             }
             DataTypes::String => DataValue::String(value.1.as_str().unwrap().to_string()),
             DataTypes::Boolean => DataValue::Boolean(value.1.as_bool().unwrap()),
-            DataTypes::Number => DataValue::Number(value.1.as_number().unwrap().clone()),
+            DataTypes::Number | DataTypes::Int | DataTypes::Uint => {
+                DataValue::Number(value.1.as_number().unwrap().clone())
+            }
+            DataTypes::Timestamp => DataValue::Number(timestamp_from_json(value.1).unwrap().into()),
+            DataTypes::Enum(ref variants) => match value.1.as_u64() {
+                Some(ordinal) => DataValue::String(variants[ordinal as usize].clone()),
+                None => DataValue::String(value.1.as_str().unwrap().to_string()),
+            },
+            // Stored as `f64` so `1` and `1.0` are the same value (and index key)
+            DataTypes::Float => {
+                DataValue::Number(serde_json::Number::from_f64(value.1.as_f64().unwrap()).unwrap())
+            }
+            DataTypes::Array(ref inner) => {
+                let inner_column = Column::new(value.0.name.as_str(), *inner.clone());
+                DataValue::Array(
+                    value
+                        .1
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|item| DataValue::from((&inner_column, item)))
+                        .collect(),
+                )
+            }
+            DataTypes::Point => {
+                let coordinate = |name: &str| {
+                    let value = value.1.get(name).and_then(|value| value.as_f64()).unwrap();
+                    (
+                        name.to_string(),
+                        DataValue::Number(serde_json::Number::from_f64(value).unwrap()),
+                    )
+                };
+                DataValue::Object(BTreeMap::from([coordinate("lat"), coordinate("lng")]))
+            }
+            DataTypes::Object(ref columns) => {
+                let fields = value.1.as_object().unwrap();
+                DataValue::Object(
+                    columns
+                        .iter()
+                        .filter_map(|column| {
+                            fields.get(&column.name).map(|field| {
+                                (column.name.clone(), DataValue::from((column, field)))
+                            })
+                        })
+                        .collect(),
+                )
+            }
         }
     }
 }
 
-impl PartialEq for DataValue {
-    fn eq(&self, other: &DataValue) -> bool {
+impl DataValue {
+    /// Rank of the kind of the value in the order of `DataValue`: values of different kinds are ordered by it.
+    fn kind_rank(&self) -> u8 {
         match self {
-            DataValue::Null => other.is_null(),
-            DataValue::Uuid(uuid) => self.as_uuid().unwrap() == uuid,
-            DataValue::String(val) => val == other.as_string().unwrap(),
-            DataValue::Boolean(val) => val == other.as_boolean().unwrap(),
-            DataValue::Number(n) => n == other.as_number().unwrap(),
+            DataValue::Null => 0,
+            DataValue::Boolean(_) => 1,
+            DataValue::Number(_) => 2,
+            DataValue::String(_) => 3,
+            DataValue::Uuid(_) => 4,
+            DataValue::Array(_) => 5,
+            DataValue::Object(_) => 6,
         }
     }
+
+    /// Whether both values are of the same kind (e.g. both numbers), so comparing them compares their contents.
+    /// `Null` is only of the same kind as `Null`.
+    pub fn same_kind(&self, other: &DataValue) -> bool {
+        self.kind_rank() == other.kind_rank()
+    }
+}
+
+impl PartialEq for DataValue {
+    fn eq(&self, other: &DataValue) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
 impl PartialOrd for DataValue {
     fn partial_cmp(&self, other: &DataValue) -> Option<Ordering> {
-        match (self, other) {
-            // Define ordering between variants
-            (DataValue::Null, DataValue::Null) => Some(Ordering::Equal),
-            (DataValue::Null, _) => Some(Ordering::Less),
-            (_, DataValue::Null) => Some(Ordering::Greater),
+        Some(self.cmp(other))
+    }
+}
 
-            (DataValue::Boolean(lhs), DataValue::Boolean(rhs)) => lhs.partial_cmp(rhs),
-            (DataValue::Boolean(_), _) => Some(Ordering::Less),
-            (_, DataValue::Boolean(_)) => Some(Ordering::Greater),
+/// Lowest integer a JSON number holds (`i64::MIN`) and the one following the highest (`u64::MAX + 1`), as floats.
+/// Both are powers of two, so they are exact.
+const MIN_INTEGER: f64 = -9_223_372_036_854_775_808.0;
+const MAX_INTEGER: f64 = 18_446_744_073_709_551_616.0;
 
-            (DataValue::Number(lhs), DataValue::Number(rhs)) => {
-                lhs.as_f64().partial_cmp(&rhs.as_f64())
-            }
-            (DataValue::Number(_), _) => Some(Ordering::Less),
-            (_, DataValue::Number(_)) => Some(Ordering::Greater),
+/// Exact position of a JSON number, shared by `compare_numbers` and `to_sortable_string` so both follow the
+/// same order. Going through `f64` would lose precision above 2^53 (`2^53 + 1` would equal `2^53` as a float,
+/// but not as an integer), which makes the order non transitive once integers and floats are mixed.
+///
+/// Numbers within the range of the integers are split in their integer part (truncated towards zero) and their
+/// fractional part, both exact. Floats below or above it are kept as they are, they only compare with each other.
+#[derive(Debug, Clone, Copy)]
+enum NumberParts {
+    Below(f64),
+    Within(i128, f64),
+    Above(f64),
+}
 
-            (DataValue::String(lhs), DataValue::String(rhs)) => lhs.partial_cmp(rhs),
-            (DataValue::String(_), _) => Some(Ordering::Less),
-            (_, DataValue::String(_)) => Some(Ordering::Greater),
+impl NumberParts {
+    fn of(number: &serde_json::Number) -> Self {
+        if let Some(integer) = number.as_i64() {
+            return NumberParts::Within(integer as i128, 0.0);
+        }
+        if let Some(integer) = number.as_u64() {
+            return NumberParts::Within(integer as i128, 0.0);
+        }
 
-            (DataValue::Uuid(lhs), DataValue::Uuid(rhs)) => lhs.partial_cmp(rhs),
+        // JSON numbers are never `NaN`, `0.0` and `-0.0` are the same number
+        let float = number.as_f64().unwrap_or(0.0) + 0.0;
+        if float < MIN_INTEGER {
+            NumberParts::Below(float)
+        } else if float >= MAX_INTEGER {
+            NumberParts::Above(float)
+        } else {
+            let integer = float.trunc();
+            NumberParts::Within(integer as i128, (float - integer) + 0.0)
         }
     }
+
+    fn band(&self) -> u8 {
+        match self {
+            NumberParts::Below(_) => 0,
+            NumberParts::Within(..) => 1,
+            NumberParts::Above(_) => 2,
+        }
+    }
+}
+
+/// Bits of `float` whose unsigned order matches the order of the floats: the sign is flipped, and every other
+/// bit too for negative floats.
+fn sortable_bits(float: f64) -> u64 {
+    let bits = float.to_bits();
+    if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | (1 << 63)
+    }
+}
+
+/// Compares numbers exactly whatever their representation (see `NumberParts`).
+fn compare_numbers(lhs: &serde_json::Number, rhs: &serde_json::Number) -> Ordering {
+    match (NumberParts::of(lhs), NumberParts::of(rhs)) {
+        (NumberParts::Within(lhs, lhs_fraction), NumberParts::Within(rhs, rhs_fraction)) => {
+            lhs.cmp(&rhs).then(lhs_fraction.total_cmp(&rhs_fraction))
+        }
+        (NumberParts::Below(lhs), NumberParts::Below(rhs))
+        | (NumberParts::Above(lhs), NumberParts::Above(rhs)) => lhs.total_cmp(&rhs),
+        (lhs, rhs) => lhs.band().cmp(&rhs.band()),
+    }
 }
 
+/// Total order of values, shared by sorting, range filters, ordered indexes (see `to_sortable_string`) and the
+/// `min` / `max` aggregations.
+///
+/// Values of the same kind compare by their contents:
+/// - Booleans: `false` before `true`.
+/// - Numbers: by value whatever their representation, `1` equals `1.0`. Integers and floats compare exactly.
+/// - Strings: byte-wise, which is the order of their code points.
+/// - Uuids: by their bytes.
+/// - Arrays: item by item, a prefix before the longer array.
+/// - Objects: field by field in the order of their names, comparing names then values.
+///
+/// Values of different kinds are never equal and are ordered by kind:
+/// `Null < Boolean < Number < String < Uuid < Array < Object`, so nulls come first when sorting.
+/// Range filters don't rely on it, they only match values of the same kind (see `FilterType::evaluate`).
 impl Ord for DataValue {
     fn cmp(&self, other: &DataValue) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        match (self, other) {
+            (DataValue::Null, DataValue::Null) => Ordering::Equal,
+            (DataValue::Boolean(lhs), DataValue::Boolean(rhs)) => lhs.cmp(rhs),
+            (DataValue::Number(lhs), DataValue::Number(rhs)) => compare_numbers(lhs, rhs),
+            (DataValue::String(lhs), DataValue::String(rhs)) => lhs.cmp(rhs),
+            (DataValue::Uuid(lhs), DataValue::Uuid(rhs)) => lhs.cmp(rhs),
+            (DataValue::Array(lhs), DataValue::Array(rhs)) => lhs.cmp(rhs),
+            (DataValue::Object(lhs), DataValue::Object(rhs)) => lhs.cmp(rhs),
+            _ => self.kind_rank().cmp(&other.kind_rank()),
+        }
     }
 }
 
@@ -132,3 +531,5 @@ data_value_from!(String, &str, |v: &str| v.to_string());
 data_value_from!(Boolean, bool);
 data_value_from!(Number, serde_json::Number);
 data_value_from!(Uuid, Uuid);
+data_value_from!(Array, Vec<DataValue>);
+data_value_from!(Object, BTreeMap<String, DataValue>);
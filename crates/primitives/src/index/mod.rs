@@ -4,6 +4,11 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Index {
     pub name: String,
+    /// Columns the key is made of. Fields of nested documents are named by their dotted path
+    /// (`profile.address.country`), see `Table::resolve_column`.
     pub members: Vec<String>,
     pub index_type: IndexType,
+    /// Rejects rows whose key is already held by another row.
+    #[serde(default)]
+    pub unique: bool,
 }
@@ -6,7 +6,10 @@ export class Column {
     public defaultValue?: string;
     public comment?: string;
     public required: boolean = false;
+    public nullable: boolean = true;
     public primaryKey: boolean = false;
+    public check?: string;
+    public collation: "binary" | "caseInsensitive" | "unicode" = "binary";
 
     constructor(name: string, dataType?: DataTypes) {
         this.name = name;
@@ -23,11 +26,71 @@ export class Column {
         return this;
     }
 
+    number() {
+        this.dataType = DataTypes.Number;
+        return this;
+    }
+
+    int() {
+        this.dataType = DataTypes.Int;
+        return this;
+    }
+
+    uint() {
+        this.dataType = DataTypes.Uint;
+        return this;
+    }
+
+    float() {
+        this.dataType = DataTypes.Float;
+        return this;
+    }
+
+    timestamp() {
+        this.dataType = DataTypes.Timestamp;
+        return this;
+    }
+
+    point() {
+        this.dataType = DataTypes.Point;
+        return this;
+    }
+
+    array(inner: DataTypes | object) {
+        this.dataType = { Array: inner } as any;
+        return this;
+    }
+
+    object(columns: Column[]) {
+        this.dataType = { Object: columns } as any;
+        return this;
+    }
+
+    enumOf(variants: string[]) {
+        this.dataType = { Enum: variants } as any;
+        return this;
+    }
+
     require(data: boolean) {
         this.required = data;
         return this;
     }
 
+    allowNull(data: boolean) {
+        this.nullable = data;
+        return this;
+    }
+
+    withCheck(expression: string) {
+        this.check = expression;
+        return this;
+    }
+
+    withCollation(collation: "binary" | "caseInsensitive" | "unicode") {
+        this.collation = collation;
+        return this;
+    }
+
     withComment(comment: string) {
         this.comment = comment;
         return this;
@@ -42,11 +105,32 @@ export class Column {
             [DataTypes.Boolean]: {
                 type: 'boolean',
                 validator: (x:any) => typeof x === 'boolean'
+            },
+            [DataTypes.Number]: {
+                type: 'number',
+                validator: (x:any) => typeof x === 'number'
+            },
+            [DataTypes.Int]: {
+                type: 'int',
+                validator: (x:any) => Number.isInteger(x)
+            },
+            [DataTypes.Uint]: {
+                type: 'uint',
+                validator: (x:any) => Number.isInteger(x) && x >= 0
+            },
+            [DataTypes.Float]: {
+                type: 'float',
+                validator: (x:any) => typeof x === 'number'
+            },
+            [DataTypes.Timestamp]: {
+                type: 'timestamp',
+                validator: (x:any) => Number.isInteger(x) || (typeof x === 'string' && !isNaN(Date.parse(x)))
             }
         };
 
         const mapEntry = mapping[this.dataType];
-        if (mapEntry && mapEntry.validator && !mapEntry.validator(val)) {
+        const isGenerator = val === "now()" || val === "uuid()";
+        if (mapEntry && mapEntry.validator && !isGenerator && !mapEntry.validator(val)) {
             throw new Error(`Default value does not match column type. ${this.name} is of type '${mapEntry.type}'.`);
         }
 
@@ -1,4 +1,10 @@
 export enum DataTypes {
     String = "String",
-    Boolean = "Boolean"
+    Boolean = "Boolean",
+    Number = "Number",
+    Int = "Int",
+    Uint = "Uint",
+    Float = "Float",
+    Timestamp = "Timestamp",
+    Point = "Point"
 }
\ No newline at end of file
@@ -1,10 +1,24 @@
 import { Column } from "ext:sjs_primitives/src/js/column.ts";
 
+/**
+ * Functions called around the inserts into a table.
+ * `beforeInsert` receives the row and can return a changed row, returning `false` or throwing rejects the insert.
+ * `afterInsert` receives the inserted row, including its `_uid`.
+ */
+export interface TableHooks {
+    beforeInsert?: (row: any) => any;
+    afterInsert?: (row: any) => any;
+}
+
 export class Table {
     public name: string;
     public columns: Record<string, Column> = {};
     public indexes = [];
     public primary_key = "_uid";
+    public timestamps = false;
+    public ttl?: { column: string, retention_ms: number };
+    public serializer?: string;
+    public hooks?: TableHooks;
 
     constructor(name: string) {
         this.name = name;
@@ -14,4 +28,24 @@ export class Table {
         this.columns[col.name] = col;
         return this;
     }
+
+    withTimestamps() {
+        this.timestamps = true;
+        return this;
+    }
+
+    withTtl(column: string, retentionMs: number) {
+        this.ttl = { column, retention_ms: retentionMs };
+        return this;
+    }
+
+    withSerializer(name: string) {
+        this.serializer = name;
+        return this;
+    }
+
+    withHooks(hooks: TableHooks) {
+        this.hooks = hooks;
+        return this;
+    }
 }
\ No newline at end of file
@@ -1,9 +1,19 @@
 use deno_core::ModuleId;
 use serde::{Deserialize, Serialize};
 
+/// Runtime information about a table, not part of its definition.
+///
+/// # Fields:
+/// - `module_id`: Module the table was defined in, for tables defined in JS.
+/// - `schema_version`: Version of the columns of the table as stored on disk, bumped every time columns are
+///   added, dropped or change type.
+/// - `dropped_columns`: Columns of previous versions of the table that no longer exist. Rows written before
+///   they were dropped still hold them, they are left out when reading them back.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TableMetadata {
     pub module_id: Option<ModuleId>,
+    pub schema_version: u32,
+    pub dropped_columns: Vec<String>,
 }
 
 impl TableMetadata {
@@ -1,12 +1,17 @@
 pub mod metadata;
+pub mod ttl;
 
+use crate::column::collation::Collation;
 use crate::column::types::DataTypes;
-use crate::column::Column;
+use crate::column::{Column, DEFAULT_NOW};
 use crate::index::Index;
 use crate::table::metadata::TableMetadata;
+use crate::table::ttl::TableTtl;
 use schemajs_index::index_type::IndexType;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Table {
@@ -14,6 +19,16 @@ pub struct Table {
     pub columns: HashMap<String, Column>,
     pub indexes: Vec<Index>,
     pub primary_key: String,
+    /// Whether the engine keeps the `_created_at` and `_updated_at` columns of every row.
+    #[serde(default)]
+    pub timestamps: bool,
+    /// Expiration of the rows of the table, if they expire.
+    #[serde(default)]
+    pub ttl: Option<TableTtl>,
+    /// Name of the serializer the rows of the table are written with, registered in the serializer registry of
+    /// the query crate. The storage settings of the table decide when unset.
+    #[serde(default)]
+    pub serializer: Option<String>,
     #[serde(skip_serializing, skip_deserializing)]
     pub metadata: TableMetadata,
 }
@@ -26,6 +41,9 @@ impl Table {
             metadata: Default::default(),
             primary_key: "_uid".to_string(),
             indexes: vec![Self::get_internal_uid_index()],
+            timestamps: false,
+            ttl: None,
+            serializer: None,
         }
     }
 
@@ -33,6 +51,23 @@ impl Table {
         self.columns
             .insert("_uid".to_string(), Self::get_internal_uid());
         self.indexes.push(Self::get_internal_uid_index());
+
+        // Tables defined in JS flag their primary key on the column
+        let primary_column = self
+            .columns
+            .values()
+            .find(|column| column.primary_key && column.name != "_uid")
+            .map(|column| column.name.clone());
+
+        if let Some(primary_column) = primary_column {
+            self.primary_key = primary_column;
+        }
+
+        self.ensure_primary_key_index();
+
+        if self.timestamps {
+            self.add_timestamp_columns();
+        }
     }
 
     pub fn get_internal_uid() -> Column {
@@ -41,11 +76,59 @@ impl Table {
             .set_primary_key(true)
     }
 
+    /// Column holding when the row was inserted, in epoch millis.
+    pub fn get_created_at() -> Column {
+        Column::new("_created_at", DataTypes::Timestamp).set_default_value(DEFAULT_NOW)
+    }
+
+    /// Column holding when the row was last written, in epoch millis.
+    pub fn get_updated_at() -> Column {
+        Column::new("_updated_at", DataTypes::Timestamp).set_default_value(DEFAULT_NOW)
+    }
+
+    /// Whether `column_name` is one of the columns kept by the engine when `timestamps` is enabled.
+    pub fn is_timestamp_column(column_name: &str) -> bool {
+        column_name == "_created_at" || column_name == "_updated_at"
+    }
+
+    fn add_timestamp_columns(&mut self) {
+        for column in [Self::get_created_at(), Self::get_updated_at()] {
+            self.columns.insert(column.name.clone(), column);
+        }
+    }
+
     fn get_internal_uid_index() -> Index {
         Index {
             name: "uidindx".to_string(),
             members: vec!["_uid".to_string()],
             index_type: IndexType::Hash,
+            unique: true,
+        }
+    }
+
+    /// Returns the unique index backing the primary key, if any.
+    pub fn get_primary_key_index(&self) -> Option<&Index> {
+        self.indexes.iter().find(|index| {
+            index.unique && index.members.len() == 1 && index.members[0] == self.primary_key
+        })
+    }
+
+    /// Whether `index` is the index backing the primary key or the internal uid.
+    pub fn is_primary_key_index(&self, index: &Index) -> bool {
+        index.unique
+            && index.members.len() == 1
+            && (index.members[0] == self.primary_key || index.members[0] == "_uid")
+    }
+
+    /// Adds a unique index on the primary key if there is none, since the primary key must be unique.
+    fn ensure_primary_key_index(&mut self) {
+        if self.get_primary_key_index().is_none() {
+            self.indexes.push(Index {
+                name: format!("{}_pkindx", self.primary_key),
+                members: vec![self.primary_key.clone()],
+                index_type: IndexType::Hash,
+                unique: true,
+            });
         }
     }
 
@@ -64,10 +147,68 @@ impl Table {
             }
         }
 
+        let is_primary_key = column.primary_key;
         self.columns.insert(column.name.clone(), column);
+
+        if is_primary_key {
+            self.ensure_primary_key_index();
+        }
+
+        self
+    }
+
+    /// Enables the `_created_at` and `_updated_at` columns, filled by the engine on insert and update.
+    pub fn set_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        if timestamps {
+            self.add_timestamp_columns();
+        }
+
+        self
+    }
+
+    /// Makes rows expire once the timestamp held in `column` is older than `retention`.
+    pub fn set_ttl(mut self, column: &str, retention: Duration) -> Self {
+        self.ttl = Some(TableTtl::new(column, retention));
         self
     }
 
+    /// Writes the rows of the table with the serializer registered as `name`.
+    pub fn set_serializer(mut self, name: &str) -> Self {
+        self.serializer = Some(name.to_string());
+        self
+    }
+
+    /// Collation of the column `column_name` (or nested field, see `resolve_column`), `Binary` for unknown columns.
+    pub fn collation(&self, column_name: &str) -> Collation {
+        self.resolve_column(column_name)
+            .map_or(Collation::Binary, |column| column.collation)
+    }
+
+    /// Column named `path`, or the field of a nested document a dotted path leads to
+    /// (`profile.address.country` being the `country` field of the `address` field of the `profile` column).
+    /// Fields are returned named after their path, which is how rows read them (see `Row::get_value`),
+    /// so they can be indexed and filtered on like any column.
+    ///
+    /// # Returns:
+    /// - `Option<Cow<Column>>`: `None` if there is no such column, or a segment of the path is not an `Object` field.
+    pub fn resolve_column(&self, path: &str) -> Option<Cow<Column>> {
+        if let Some(column) = self.get_column(path) {
+            return Some(Cow::Borrowed(column));
+        }
+
+        // A path without dots was looked up already
+        let mut segments = path.split('.');
+        let mut column = self.get_column(segments.next()?)?;
+        for segment in segments {
+            column = column.field(segment)?;
+        }
+
+        let mut field = column.clone();
+        field.name = path.to_string();
+        Some(Cow::Owned(field))
+    }
+
     pub fn get_column(&self, column_name: &str) -> Option<&Column> {
         self.columns.get(column_name)
     }
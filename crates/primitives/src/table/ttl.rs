@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Expiration of the rows of a table.
+///
+/// # Fields:
+/// - `column`: Column holding the timestamp (epoch millis) the age of a row is measured from, such as `_created_at`.
+/// - `retention_ms`: How long a row is kept, in millis, before it expires.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableTtl {
+    pub column: String,
+    pub retention_ms: u64,
+}
+
+impl TableTtl {
+    pub fn new(column: &str, retention: Duration) -> Self {
+        Self {
+            column: column.to_string(),
+            retention_ms: retention.as_millis() as u64,
+        }
+    }
+
+    /// Rows whose `column` holds a timestamp older than the returned one (epoch millis) are expired.
+    pub fn expiration_cutoff(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis() - self.retention_ms as i64
+    }
+}
@@ -8,6 +8,9 @@ pub enum QueryError {
     #[error("Unknown table '{0}'")]
     InvalidTable(String),
 
+    #[error("Unknown database '{0}'")]
+    InvalidDatabase(String),
+
     #[error("Primary column '{0}' is not present in table")]
     UnknownPrimaryColumn(String),
 
@@ -20,9 +23,96 @@ pub enum QueryError {
     #[error("Uid not present")]
     UnknownUid,
 
+    #[error("Unknown column '{0}'")]
+    InvalidColumn(String),
+
+    #[error("Column '{0}' cannot be updated")]
+    ImmutableColumn(String),
+
+    #[error("Unknown index '{0}'")]
+    InvalidIndex(String),
+
+    #[error("Index '{0}' already exists")]
+    DuplicateIndex(String),
+
+    #[error("Index '{0}' covers unknown column '{1}'")]
+    InvalidIndexMember(String, String),
+
+    #[error("Unknown serializer '{0}'")]
+    InvalidSerializer(String),
+
+    #[error("Value '{1}' already exists in unique index '{0}'")]
+    UniqueViolation(String, String),
+
+    #[error("Duplicate value '{1}' for primary key '{0}'")]
+    DuplicatePrimaryKey(String, String),
+
+    #[error("Column '{0}' cannot be null")]
+    NullValue(String),
+
+    #[error("Value of column '{0}' does not match its type")]
+    InvalidColumnValue(String),
+
+    #[error("Invalid default value for column '{0}'")]
+    InvalidDefaultValue(String),
+
+    #[error("Invalid check '{0}'")]
+    InvalidCheck(String),
+
+    #[error("Value of column '{0}' does not satisfy check '{1}'")]
+    CheckViolation(String, String),
+
+    #[error("Invalid query '{0}'")]
+    InvalidQuery(String),
+
+    #[error("Invalid value for column '{0}' in query")]
+    InvalidQueryValue(String),
+
+    #[error("Unknown aggregate function '{0}'")]
+    InvalidAggregation(String),
+
+    #[error("Cannot apply '{0}' to non numeric value '{1}'")]
+    InvalidAggregationValue(String, String),
+
+    #[error("Invalid pipeline stage '{0}'")]
+    InvalidPipeline(String),
+
+    #[error("Unknown view '{0}'")]
+    InvalidView(String),
+
+    #[error("Invalid cursor '{0}'")]
+    InvalidCursor(String),
+
+    #[error("Unknown filter type '{0}'")]
+    InvalidFilterType(String),
+
+    #[error("Invalid SQL: {0}")]
+    InvalidSql(String),
+
     #[error("Invalid Insertion")]
     InvalidInsertion,
 
+    #[error("Database '{0}' is read-only")]
+    ReadOnly(String),
+
+    #[error("Database '{0}' exceeded its storage quota")]
+    QuotaExceeded(String),
+
+    #[error("Query was cancelled or exceeded its deadline")]
+    Timeout,
+
+    #[error("Table '{0}' cannot be reloaded: {1}")]
+    IncompatibleSchema(String, String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("Unknown role '{0}'")]
+    InvalidRole(String),
+
+    #[error("Access control is not enabled, `[access]` has to be configured")]
+    AccessControlDisabled,
+
     #[error("A Shard Error has occured")]
     ShardError(#[from] ShardErrors),
 }
@@ -1,7 +1,9 @@
 pub mod errors;
 pub mod managers;
-mod ops;
+pub mod ops;
+pub mod partial_row;
 pub mod row;
 pub mod row_json;
-mod search;
+pub mod search;
 pub mod serializer;
+pub mod sql;
@@ -0,0 +1,344 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::broadcast;
+
+/// Amount of changes a subscriber can fall behind before it starts missing the oldest ones.
+pub const CHANGE_FEED_CAPACITY: usize = 1024;
+
+/// What happened to the row of a `ChangeEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Change of a row, published by `ChangeFeed`.
+///
+/// # Fields:
+/// - `table`: Table holding the row.
+/// - `kind`: Whether the row was inserted, updated or deleted.
+/// - `row`: The serialized row: its new version for inserts and updates, the deleted version for deletes.
+/// - `before`: The serialized version of the row before an update, `None` for inserts and deletes.
+/// - `sequence`: Position of the change in its feed, starting at 1. Set by `ChangeFeed::publish`.
+#[derive(Debug, Clone)]
+pub struct ChangeEvent {
+    pub table: String,
+    pub kind: ChangeKind,
+    pub row: Vec<u8>,
+    pub before: Option<Vec<u8>>,
+    pub sequence: u64,
+}
+
+impl ChangeEvent {
+    pub fn new(table: &str, kind: ChangeKind, row: Vec<u8>) -> Self {
+        Self {
+            table: table.to_string(),
+            kind,
+            row,
+            before: None,
+            sequence: 0,
+        }
+    }
+
+    pub fn set_before(mut self, before: Option<Vec<u8>>) -> Self {
+        self.before = before;
+        self
+    }
+}
+
+/// Destination every change of a `ChangeFeed` is written to as it is published (see `ChangeFeed::set_sink`),
+/// e.g. a change log on disk. Unlike subscribers, sinks never miss a change.
+pub trait ChangeSink: Debug + Send + Sync {
+    fn write(&self, event: &ChangeEvent) -> std::io::Result<()>;
+}
+
+/// Publishes the changes made to the rows of a database to its subscribers, e.g. to keep live UIs up to date
+/// or to replicate the database.
+///
+/// Changes are published once written (rows inserted through `insert` before being reconciled), the changes
+/// of a transaction once it commits. Subscribers falling behind by more than `CHANGE_FEED_CAPACITY` changes miss
+/// the oldest ones (see `broadcast::error::RecvError::Lagged`).
+///
+/// Nothing is kept while there are no subscribers, unless the feed has a history (see `set_history_size`),
+/// which lets subscribers resume from a sequence they already got (see `subscribe_from`).
+///
+/// # Fields:
+/// - `sender`: Channel the changes are sent through.
+/// - `history`: Last published changes (up to `history_size`), along with the sequence of the last one.
+///   Publishing holds its lock, so subscribing from it sees every change exactly once.
+/// - `history_size`: Amount of changes kept, none by default.
+/// - `sink`: Written every change as it is published, in order.
+/// - `hidden_tables`: Tables whose changes are not published, see `hide_table`.
+#[derive(Debug)]
+pub struct ChangeFeed {
+    sender: broadcast::Sender<ChangeEvent>,
+    history: Mutex<(u64, VecDeque<ChangeEvent>)>,
+    history_size: AtomicUsize,
+    sink: RwLock<Option<Arc<dyn ChangeSink>>>,
+    hidden_tables: RwLock<HashSet<String>>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANGE_FEED_CAPACITY);
+        Self {
+            sender,
+            history: Mutex::new((0, VecDeque::new())),
+            history_size: AtomicUsize::new(0),
+            sink: RwLock::new(None),
+            hidden_tables: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Writes every change published from now on to `sink`, `None` stops writing them.
+    pub fn set_sink(&self, sink: Option<Arc<dyn ChangeSink>>) {
+        *self.sink.write().unwrap() = sink;
+    }
+
+    /// Stops publishing the changes of `table`, e.g. a table only written by the database itself whose changes
+    /// replicas and subscribers have no use for. They don't take a sequence either.
+    pub fn hide_table(&self, table: &str) {
+        self.hidden_tables
+            .write()
+            .unwrap()
+            .insert(table.to_string());
+    }
+
+    /// Continues numbering changes after `sequence`, e.g. the last change of a sink written before a restart.
+    /// Sequences never go back, nothing changes if changes were already published past it.
+    pub fn resume_sequence(&self, sequence: u64) {
+        let (last, _) = &mut *self.history.lock().unwrap();
+        *last = (*last).max(sequence);
+    }
+
+    /// Keeps the last `size` changes, so subscribers can resume from them (see `subscribe_from`).
+    pub fn set_history_size(&self, size: usize) {
+        self.history_size.store(size, Ordering::Relaxed);
+
+        let (_, history) = &mut *self.history.lock().unwrap();
+        while history.len() > size {
+            history.pop_front();
+        }
+    }
+
+    pub fn history_size(&self) -> usize {
+        self.history_size.load(Ordering::Relaxed)
+    }
+
+    /// Sequence of the last published change, `0` before any.
+    pub fn sequence(&self) -> u64 {
+        self.history.lock().unwrap().0
+    }
+
+    /// Receives every change published from now on, until the receiver is dropped.
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Receives the changes published after the one at `sequence`: the ones still in the history, then every change
+    /// published from now on. `None` when changes after `sequence` are no longer kept (or were never published),
+    /// the subscriber can't resume from it.
+    pub fn subscribe_from(
+        &self,
+        sequence: u64,
+    ) -> Option<(Vec<ChangeEvent>, broadcast::Receiver<ChangeEvent>)> {
+        let (last, history) = &*self.history.lock().unwrap();
+        if sequence > *last {
+            return None;
+        }
+
+        let backlog: Vec<ChangeEvent> = history
+            .iter()
+            .filter(|event| event.sequence > sequence)
+            .cloned()
+            .collect();
+        if backlog.len() as u64 != *last - sequence {
+            return None;
+        }
+
+        Some((backlog, self.sender.subscribe()))
+    }
+
+    /// Receives every change published from now on, along with the sequence of the last change published before.
+    pub fn subscribe_at_sequence(&self) -> (u64, broadcast::Receiver<ChangeEvent>) {
+        let (last, _) = &*self.history.lock().unwrap();
+        (*last, self.sender.subscribe())
+    }
+
+    /// Whether anyone is subscribed (or changes are kept or written to a sink), so changes are only read back
+    /// when someone will get them.
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+            || self.history_size() > 0
+            || self.sink.read().unwrap().is_some()
+    }
+
+    pub fn publish(&self, mut event: ChangeEvent) {
+        if self.hidden_tables.read().unwrap().contains(&event.table) {
+            return;
+        }
+
+        let (last, history) = &mut *self.history.lock().unwrap();
+        *last += 1;
+        event.sequence = *last;
+
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            // The row is already written, a failing sink can't undo it
+            if let Err(e) = sink.write(&event) {
+                tracing::error!(table = %event.table, sequence = event.sequence, error = %e, "Could not write change to its sink");
+            }
+        }
+
+        let history_size = self.history_size();
+        if history_size > 0 {
+            while history.len() >= history_size {
+                history.pop_front();
+            }
+            history.push_back(event.clone());
+        }
+
+        // Sending only fails when there are no subscribers
+        let _ = self.sender.send(event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::changes::ChangeKind;
+    use crate::managers::single::fixtures::{
+        equals, test_db, user, user_names, users, users_manager,
+    };
+    use crate::managers::single::SingleQueryManager;
+    use crate::ops::query_ops::QueryOps;
+    use crate::row::Row;
+    use crate::row_json::RowJson;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use std::collections::HashMap;
+
+    #[flaky_test::flaky_test]
+    pub fn test_change_feed() {
+        // Nothing is published before subscribing
+        let (query_manager, db_folder) = users_manager(&["andres"]);
+        assert!(!query_manager.changes().has_subscribers());
+
+        let by_name = |name: &str| equals("user_name", name);
+        let mut changes = query_manager.changes().subscribe();
+        query_manager.insert(user("carlos")).unwrap();
+        query_manager
+            .insert_many(vec![user("luis"), user("maria")])
+            .unwrap();
+        let mut new_values = HashMap::new();
+        new_values.insert(
+            "user_name".to_string(),
+            DataValue::String("charles".to_string()),
+        );
+        query_manager
+            .update("users".to_string(), &by_name("carlos"), &new_values)
+            .unwrap();
+        query_manager
+            .delete("users".to_string(), &by_name("luis"))
+            .unwrap();
+
+        let mut transaction = query_manager.begin();
+        transaction
+            .insert(user("pedro"))
+            .delete("users", by_name("maria"));
+        query_manager.commit(transaction).unwrap();
+
+        // Failed transactions publish nothing
+        let mut transaction = query_manager.begin();
+        transaction
+            .insert(user("jose"))
+            .delete("unknown", by_name("pedro"));
+        assert!(query_manager.commit(transaction).is_err());
+
+        let name_column = Column::new("user_name", DataTypes::String);
+        let mut received = vec![];
+        while let Ok(change) = changes.try_recv() {
+            assert_eq!(change.table, "users");
+            let row = RowJson::from(change.row.as_slice());
+            received.push((change.kind, row.get_value(&name_column).unwrap()));
+        }
+
+        let name = |name: &str| DataValue::String(name.to_string());
+        assert_eq!(
+            received,
+            vec![
+                (ChangeKind::Insert, name("carlos")),
+                (ChangeKind::Insert, name("luis")),
+                (ChangeKind::Insert, name("maria")),
+                (ChangeKind::Update, name("charles")),
+                (ChangeKind::Delete, name("luis")),
+                (ChangeKind::Insert, name("pedro")),
+                (ChangeKind::Delete, name("maria")),
+            ]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_apply_change() {
+        let (leader, leader_folder) = users_manager(&[]);
+        let (follower_db, follower_folder) = test_db();
+        let follower: SingleQueryManager<RowJson> =
+            SingleQueryManager::new(follower_db).set_read_only(true);
+        follower.register_table(users()).unwrap();
+        leader.changes().set_history_size(3);
+
+        let by_name = |name: &str| equals("user_name", name);
+        leader.insert(user("andres")).unwrap();
+        leader.insert(user("carlos")).unwrap();
+        let mut new_values = HashMap::new();
+        new_values.insert(
+            "user_name".to_string(),
+            DataValue::String("charles".to_string()),
+        );
+        leader
+            .update("users".to_string(), &by_name("carlos"), &new_values)
+            .unwrap();
+        leader
+            .delete("users".to_string(), &by_name("andres"))
+            .unwrap();
+        assert_eq!(leader.changes().sequence(), 4);
+
+        // Only the last 3 changes are kept
+        assert!(leader.changes().subscribe_from(0).is_none());
+        assert!(leader.changes().subscribe_from(5).is_none());
+        let (backlog, _) = leader.changes().subscribe_from(4).unwrap();
+        assert!(backlog.is_empty());
+        let (backlog, mut changes) = leader.changes().subscribe_from(1).unwrap();
+        let sequences: Vec<u64> = backlog.iter().map(|change| change.sequence).collect();
+        assert_eq!(sequences, vec![2, 3, 4]);
+
+        leader.insert(user("luis")).unwrap();
+        let live = changes.try_recv().unwrap();
+        assert_eq!(live.sequence, 5);
+
+        // Followers are read-only, but changes are applied to them. Replaying changes is harmless.
+        assert!(follower.insert(user("maria")).is_err());
+        for change in backlog.iter().chain(backlog.iter()).chain([&live]) {
+            follower.apply_change(change).unwrap();
+        }
+
+        assert_eq!(
+            user_names(&follower, "users", &QueryOps::And(vec![])),
+            vec!["charles", "luis"]
+        );
+
+        std::fs::remove_dir_all(leader_folder).unwrap();
+        std::fs::remove_dir_all(follower_folder).unwrap();
+    }
+}
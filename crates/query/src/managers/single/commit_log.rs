@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File of the folder of a database recording the transaction being committed, see `CommitLog`.
+pub const COMMIT_LOG: &str = ".commit.json";
+
+/// Where the tables written by a transaction stood before it was committed.
+///
+/// The log is written before anything of the transaction is applied and removed once every write of it is on disk.
+/// A crash in between leaves the log behind, and the writes of the transaction are undone when its tables are
+/// opened again (see `SingleQueryManager::recover_commit`), so transactions are either applied whole or not at all.
+///
+/// # Fields:
+/// - `tables`: Where each table written by the transaction stood, by name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CommitLog {
+    pub tables: HashMap<String, CommitStart>,
+}
+
+/// Where a table stood before a transaction was committed.
+///
+/// # Fields:
+/// - `len`: Rows of the main shard, the ones written by the transaction come after them.
+/// - `tombstones`: Positions marked as dead, the ones marked by the transaction come after them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CommitStart {
+    pub len: u64,
+    pub tombstones: usize,
+}
+
+impl CommitLog {
+    pub fn path(db_folder: &Path) -> PathBuf {
+        db_folder.join(COMMIT_LOG)
+    }
+
+    /// Log of the commit interrupted in the database stored in `db_folder`, if any.
+    pub fn load(db_folder: &Path) -> std::io::Result<Option<Self>> {
+        let path = Self::path(db_folder);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(serde_json::from_slice(&std::fs::read(path)?)?))
+    }
+
+    /// Records where `tables` stand before a transaction writing them is applied, along with the tables of commits
+    /// interrupted before and not recovered yet.
+    pub fn start(
+        db_folder: &Path,
+        tables: impl IntoIterator<Item = (String, CommitStart)>,
+    ) -> std::io::Result<()> {
+        let mut log = Self::load(db_folder)?.unwrap_or_default();
+        log.tables.extend(tables);
+        log.save(db_folder)
+    }
+
+    /// Forgets `tables`, once the writes of the transaction to them are on disk (or undone). The log is removed
+    /// once no table is left in it.
+    pub fn finish(db_folder: &Path, tables: &[String]) -> std::io::Result<()> {
+        let mut log = match Self::load(db_folder)? {
+            Some(log) => log,
+            None => return Ok(()),
+        };
+
+        for table in tables {
+            log.tables.remove(table);
+        }
+
+        match log.tables.is_empty() {
+            true => Self::remove(db_folder),
+            false => log.save(db_folder),
+        }
+    }
+
+    /// Writes the log, replacing it in a single rename so it's never read half written.
+    pub fn save(&self, db_folder: &Path) -> std::io::Result<()> {
+        let path = Self::path(db_folder);
+        let written = path.with_extension("tmp");
+
+        let mut file = File::create(&written)?;
+        file.write_all(&serde_json::to_vec(self)?)?;
+        file.sync_all()?;
+
+        std::fs::rename(written, path)
+    }
+
+    /// Removes the log, the commit being complete.
+    pub fn remove(db_folder: &Path) -> std::io::Result<()> {
+        match std::fs::remove_file(Self::path(db_folder)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::commit_log::{CommitLog, CommitStart};
+    use crate::managers::single::SingleQueryManager;
+    use crate::ops::query_ops::QueryOps;
+    use crate::row_json::{RowData, RowJson};
+    use schemajs_dirs::create_scheme_js_db;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use std::collections::HashSet;
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_commit_log_recover() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let users = || Table::new("users").add_column(Column::new("user_name", DataTypes::String));
+        let row = |name: &str| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name
+                }),
+            })
+        };
+
+        {
+            let query_manager: SingleQueryManager<RowJson> =
+                SingleQueryManager::new(test_db.clone());
+            query_manager.register_table(users()).unwrap();
+            query_manager.insert(row("andres")).unwrap();
+            query_manager.insert(row("luis")).unwrap();
+
+            // The process dies half way through a commit deleting `andres` and inserting `carlos`
+            let table_shard = query_manager.tables.get("users").unwrap();
+            table_shard.sync().unwrap();
+            let start = CommitStart {
+                len: table_shard.data.read().unwrap().len() as u64,
+                tombstones: table_shard.tombstones.len(),
+            };
+            CommitLog::start(&db_folder, [(String::from("users"), start)]).unwrap();
+
+            table_shard.delete_row(0).unwrap();
+            let carlos = table_shard.serialize_row(&row("carlos")).unwrap();
+            table_shard.insert_row(&carlos).unwrap();
+            table_shard.sync().unwrap();
+        }
+
+        // The writes of the commit are undone once the table is opened again
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+        query_manager.register_table(users()).unwrap();
+
+        let names: HashSet<String> = query_manager
+            .search_manager()
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap()
+            .into_iter()
+            .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            HashSet::from(["andres".to_string(), "luis".to_string()])
+        );
+        assert!(CommitLog::load(&db_folder).unwrap().is_none());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
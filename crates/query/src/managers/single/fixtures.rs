@@ -0,0 +1,89 @@
+use crate::managers::single::SingleQueryManager;
+use crate::ops::query_ops::{QueryOps, QueryVal};
+use crate::row_json::{RowData, RowJson};
+use schemajs_dirs::create_scheme_js_db;
+use schemajs_index::index_type::IndexType;
+use schemajs_primitives::column::types::{DataTypes, DataValue};
+use schemajs_primitives::column::Column;
+use schemajs_primitives::index::Index;
+use schemajs_primitives::table::Table;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Name and folder of a new database, the folder is removed by the test using it.
+pub fn test_db() -> (String, PathBuf) {
+    let test_db = Uuid::new_v4().to_string();
+    let db_folder = create_scheme_js_db(None, test_db.as_str());
+    (test_db, db_folder)
+}
+
+/// `users` table holding a `user_name` column.
+pub fn users() -> Table {
+    Table::new("users").add_column(Column::new("user_name", DataTypes::String))
+}
+
+/// `users` table holding a `user_country` column too, indexed by `countryindx`.
+pub fn users_by_country() -> Table {
+    users()
+        .add_column(Column::new("user_country", DataTypes::String))
+        .add_index(Index {
+            name: "countryindx".to_string(),
+            members: vec![String::from("user_country")],
+            index_type: IndexType::Hash,
+            unique: false,
+        })
+}
+
+/// Row of the `users` table holding the columns of `value`, under a new `_uid`.
+pub fn user_row(mut value: serde_json::Value) -> RowJson {
+    value["_uid"] = serde_json::json!(Uuid::new_v4().to_string());
+    RowJson::from(RowData {
+        table: String::from("users"),
+        value,
+    })
+}
+
+/// Row of the `users` table named `name`.
+pub fn user(name: &str) -> RowJson {
+    user_row(serde_json::json!({ "user_name": name }))
+}
+
+/// Manager of a new database holding the `users` table of `users()` with a row named after each of `rows`,
+/// and the folder of the database.
+pub fn users_manager(rows: &[&str]) -> (SingleQueryManager<RowJson>, PathBuf) {
+    let (test_db, db_folder) = test_db();
+    let query_manager = SingleQueryManager::new(test_db);
+    query_manager.register_table(users()).unwrap();
+
+    for name in rows {
+        query_manager.insert(user(name)).unwrap();
+    }
+
+    (query_manager, db_folder)
+}
+
+/// Condition matching the rows whose `key` equals `value`.
+pub fn equals(key: &str, value: impl Into<DataValue>) -> QueryOps {
+    QueryOps::Condition(QueryVal {
+        key: key.to_string(),
+        filter_type: "=".to_string(),
+        value: value.into(),
+    })
+}
+
+/// Names of the users of `table_name` matched by `query`, sorted.
+pub fn user_names(
+    query_manager: &SingleQueryManager<RowJson>,
+    table_name: &str,
+    query: &QueryOps,
+) -> Vec<String> {
+    let mut names: Vec<String> = query_manager
+        .search_manager()
+        .search(table_name.to_string(), query)
+        .unwrap()
+        .iter()
+        .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
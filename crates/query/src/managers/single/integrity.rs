@@ -0,0 +1,144 @@
+use enum_as_inner::EnumAsInner;
+use serde::Serialize;
+
+/// Problem found in the files of a table by `TableShard::check_integrity`. Positions are positions of rows
+/// of the main shard.
+#[derive(Debug, Clone, PartialEq, Serialize, EnumAsInner)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum IntegrityIssue {
+    /// The bytes of the row can't be located in its shard file (see `Shard::verify_framing`).
+    BadFraming { position: u64 },
+    /// The row can't be read intact, e.g. its checksum doesn't match.
+    CorruptedRow { position: u64 },
+    /// Entry of `index` pointing past the rows of the main shard.
+    DanglingPointer { index: String, position: u64 },
+    /// Entry of `index` whose key is not the key of the row it points to.
+    MismatchedKey { index: String, position: u64 },
+    /// Row holding a key without an entry of `index` pointing to it.
+    MissingEntry { index: String, position: u64 },
+}
+
+impl IntegrityIssue {
+    /// Index the issue was found in, `None` for issues of the rows themselves.
+    pub fn index(&self) -> Option<&str> {
+        match self {
+            IntegrityIssue::BadFraming { .. } | IntegrityIssue::CorruptedRow { .. } => None,
+            IntegrityIssue::DanglingPointer { index, .. }
+            | IntegrityIssue::MismatchedKey { index, .. }
+            | IntegrityIssue::MissingEntry { index, .. } => Some(index),
+        }
+    }
+}
+
+/// Outcome of checking the integrity of a table.
+///
+/// # Fields:
+/// - `table`: Table checked.
+/// - `rows`: Rows of the main shard checked.
+/// - `index_entries`: Index entries checked, across every index of the table.
+/// - `issues`: Problems found, before anything was repaired.
+/// - `repaired_indexes`: Indexes rebuilt from the rows of the table because of the issues found in them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub table: String,
+    pub rows: u64,
+    pub index_entries: u64,
+    pub issues: Vec<IntegrityIssue>,
+    pub repaired_indexes: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// Whether no issue was found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Indexes holding issues, in the order they were found. Rebuilding them fixes their issues.
+    pub fn damaged_indexes(&self) -> Vec<String> {
+        let mut indexes: Vec<String> = vec![];
+        for index in self.issues.iter().filter_map(|issue| issue.index()) {
+            if !indexes.iter().any(|damaged| damaged == index) {
+                indexes.push(index.to_string());
+            }
+        }
+
+        indexes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{
+        equals, test_db, user_names, user_row, users_by_country,
+    };
+    use crate::managers::single::integrity::IntegrityIssue;
+    use crate::managers::single::SingleQueryManager;
+    use crate::row_json::RowJson;
+    use serde_json::json;
+
+    #[flaky_test::flaky_test]
+    pub fn test_check_integrity() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+        query_manager.register_table(users_by_country()).unwrap();
+
+        for (name, country) in [("andres", "US"), ("luis", "VE"), ("carlos", "US")] {
+            query_manager
+                .insert(user_row(
+                    json!({ "user_name": name, "user_country": country }),
+                ))
+                .unwrap();
+        }
+
+        let report = query_manager.check_integrity("users", false).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.rows, 3);
+        assert_eq!(report.index_entries, 3);
+
+        {
+            let table_shard = query_manager.tables.get("users").unwrap();
+            let index = table_shard.indexes.get("countryindx").unwrap();
+            let indx = index.as_index();
+            let (ve_key, ve_position) = indx
+                .entries()
+                .into_iter()
+                .find(|(_, position)| *position == 1)
+                .unwrap();
+            assert_eq!(ve_position, 1);
+
+            // Past the rows of the table, and a key that isn't the one of the row
+            indx.insert(ve_key.clone(), 42);
+            indx.insert(ve_key, 0);
+        }
+
+        let report = query_manager.check_integrity("users", false).unwrap();
+        assert_eq!(report.index_entries, 5);
+        assert_eq!(report.issues.len(), 2);
+        assert!(report.issues.contains(&IntegrityIssue::DanglingPointer {
+            index: "countryindx".to_string(),
+            position: 42,
+        }));
+        assert!(report.issues.contains(&IntegrityIssue::MismatchedKey {
+            index: "countryindx".to_string(),
+            position: 0,
+        }));
+        assert_eq!(report.damaged_indexes(), vec!["countryindx".to_string()]);
+        assert!(report.repaired_indexes.is_empty());
+
+        let report = query_manager.check_integrity("users", true).unwrap();
+        assert_eq!(report.issues.len(), 2);
+        assert_eq!(report.repaired_indexes, vec!["countryindx".to_string()]);
+
+        let report = query_manager.check_integrity("users", false).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.index_entries, 3);
+
+        assert_eq!(
+            user_names(&query_manager, "users", &equals("user_country", "VE")),
+            vec!["luis"]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
@@ -1,16 +1,54 @@
+pub mod changes;
+pub mod commit_log;
+#[cfg(test)]
+pub mod fixtures;
+pub mod integrity;
+pub mod quota;
+pub mod schema;
+pub mod stats;
+pub mod swap;
 pub mod table_shard;
+pub mod transaction;
 
 use crate::errors::QueryError;
+use crate::managers::single::changes::{ChangeEvent, ChangeFeed, ChangeKind};
+use crate::managers::single::commit_log::{CommitLog, CommitStart};
+use crate::managers::single::integrity::IntegrityReport;
+use crate::managers::single::quota::StorageQuota;
+use crate::managers::single::schema::SchemaChange;
+use crate::managers::single::stats::DatabaseStats;
+use crate::managers::single::swap::TableSwap;
 use crate::managers::single::table_shard::TableShard;
+use crate::managers::single::transaction::{Transaction, TransactionOp, TransactionResult};
+use crate::ops::query_ops::{QueryOps, QueryVal};
 use crate::row::Row;
+use crate::search::search_manager::QuerySearchManager;
+use crate::search::slow_query::SlowQueryLog;
 use chashmap::CHashMap;
-use schemajs_data::shard::shards::data_shard::config::TempDataShardConfig;
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::errors::ShardErrors;
+use schemajs_data::metrics::Metrics;
+use schemajs_data::shard::shards::data_shard::config::{TableStorage, TempDataShardConfig};
 use schemajs_data::temp_offset_types::TempOffsetTypes;
+use schemajs_dirs::create_scheme_js_db;
+use schemajs_index::composite_key::CompositeKey;
+use schemajs_index::index_type::IndexTypeValue;
+use schemajs_primitives::column::types::DataValue;
+use schemajs_primitives::column::Column;
+use schemajs_primitives::index::Index as TableIndex;
 use schemajs_primitives::table::Table;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+/// Rows `create_index` leaves to index once the table is locked, the ones written before are indexed
+/// while writes keep going.
+pub const INDEX_CATCH_UP_ROWS: u64 = 1_000;
+
 #[derive(Debug)]
 pub struct SingleQueryManager<T: Row<T>> {
     // A thread-safe vector that holds the names of registered tables.
@@ -28,6 +66,31 @@ pub struct SingleQueryManager<T: Row<T>> {
     // A unique identifier for this instance of SingleQueryManager.
     // This UUID helps in distinguishing different query managers in the system.
     pub id: Uuid,
+
+    // Taken for writing by transaction commits and for reading by every other write, so the writes of a
+    // transaction are never interleaved with other writes.
+    commit_lock: RwLock<()>,
+
+    // Key encrypting the files of the tables registered from now on, if the database is encrypted.
+    encryption: Option<EncryptionKey>,
+
+    // Folder holding the `dbs` folder the tables are stored in, the global SchemeJS folder when `None`.
+    base_path: Option<PathBuf>,
+
+    // Rejects every write when set, e.g. for snapshots opened for reporting.
+    read_only: bool,
+
+    // Storage quota of the database, inserts are not limited when `None`.
+    quota: Option<StorageQuota>,
+
+    // Searches and mutations taking longer than its threshold, nothing is logged until a threshold is set.
+    slow_queries: Arc<SlowQueryLog>,
+
+    // Changes made to the rows of the tables, published to its subscribers.
+    changes: ChangeFeed,
+
+    // Indexes being built by `create_index`, as (table, index) names.
+    index_builds: Arc<Mutex<HashSet<(String, String)>>>,
 }
 
 /// `SingleQueryManager` is responsible for managing all query-related operations
@@ -65,7 +128,103 @@ impl<T: Row<T>> SingleQueryManager<T> {
             tables: Arc::new(CHashMap::default()),
             scheme,
             id: uuid,
+            commit_lock: RwLock::new(()),
+            encryption: None,
+            base_path: None,
+            read_only: false,
+            quota: None,
+            slow_queries: Arc::new(SlowQueryLog::default()),
+            changes: ChangeFeed::new(),
+            index_builds: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Encrypts the files (shards, write-ahead logs and indexes) of the tables registered from now on with `encryption`.
+    /// Files that already exist keep being read and written the way they were created.
+    pub fn set_encryption(mut self, encryption: Option<EncryptionKey>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Key the files of the database are encrypted with, if any.
+    pub fn encryption(&self) -> Option<&EncryptionKey> {
+        self.encryption.as_ref()
+    }
+
+    /// Stores the tables registered from now on under `base_path` (`<base_path>/dbs/<scheme>/<table>`)
+    /// instead of the global SchemeJS folder.
+    pub fn set_base_path(mut self, base_path: Option<PathBuf>) -> Self {
+        self.base_path = base_path;
+        self
+    }
+
+    /// Makes every write (inserts, updates, deletes, transactions, index changes and restores) fail with `ReadOnly`.
+    pub fn set_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    fn check_writable(&self) -> Result<(), QueryError> {
+        match self.read_only {
+            true => Err(QueryError::ReadOnly(self.scheme.clone())),
+            false => Ok(()),
+        }
+    }
+
+    /// Makes inserts (including upserts inserting a row and inserts of transactions) fail with `QuotaExceeded`
+    /// once the database uses more than the limit of `quota`.
+    pub fn set_quota(mut self, quota: Option<StorageQuota>) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    pub fn quota(&self) -> Option<&StorageQuota> {
+        self.quota.as_ref()
+    }
+
+    /// Logs the searches, updates and deletes taking `threshold` or longer (see `slow_queries`), `None` disables it.
+    /// It can be changed at any time, e.g. while investigating a slow database.
+    pub fn set_slow_query_threshold(&self, threshold: Option<Duration>) {
+        self.slow_queries.set_threshold(threshold);
+    }
+
+    pub fn slow_queries(&self) -> &SlowQueryLog {
+        &self.slow_queries
+    }
+
+    /// Changes made to the rows of the tables (inserts, updates and deletes), see `ChangeFeed`.
+    pub fn changes(&self) -> &ChangeFeed {
+        &self.changes
+    }
+
+    /// Serialized row at `pointer` of `table_shard`, read back only when the change feed has subscribers.
+    fn changed_row(&self, table_shard: &TableShard<T>, pointer: u64) -> Option<Vec<u8>> {
+        if !self.changes.has_subscribers() {
+            return None;
+        }
+
+        table_shard
+            .data
+            .read()
+            .unwrap()
+            .get_element(pointer as usize)
+            .ok()
+    }
+
+    fn check_quota(&self) -> Result<(), QueryError> {
+        match &self.quota {
+            Some(quota) => quota.check(&self.scheme),
+            None => Ok(()),
+        }
+    }
+
+    /// Records the insertion of `rows` rows taking `bytes` bytes: adds them to the usage of the quota and to
+    /// the metrics of the process.
+    fn record_inserted(&self, rows: usize, bytes: usize) {
+        if let Some(quota) = &self.quota {
+            quota.record(bytes as u64);
         }
+        Metrics::global().rows_inserted.inc_by(rows as u64);
     }
 
     /// Register a table and creates a shard manager for insertions (`TableShard`)
@@ -80,27 +239,413 @@ impl<T: Row<T>> SingleQueryManager<T> {
     /// use schemajs_query::row_json::RowJson;
     ///
     /// let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new("database-name".to_string());
-    /// query_manager.register_table(Table::new("users"));
+    /// query_manager.register_table(Table::new("users")).unwrap();
     /// ```
     ///
     /// Note `register_table` will panic due to `No such file or directory` due to the database must have a folder already created in system.
-    pub fn register_table(&self, table: Table) {
-        self.table_names.write().unwrap().push(table.name.clone());
-        self.tables.insert(
-            table.name.clone(),
-            TableShard::<T>::new(
-                table,
-                None,
-                self.scheme.as_str(),
-                TempDataShardConfig {
-                    max_offsets: TempOffsetTypes::Custom(Some(1000)),
-                },
-            ),
+    pub fn register_table(&self, table: Table) -> Result<(), QueryError> {
+        self.register_table_with_storage(table, TableStorage::default())
+    }
+
+    /// Registers a table like `register_table`, storing its main shard as `storage` sets (compression of the rows
+    /// once they are reconciled into it, size of its shard files).
+    ///
+    /// Fails, leaving the table unregistered, if it references a serializer that is not registered or has an index
+    /// covering an unknown column.
+    pub fn register_table_with_storage(
+        &self,
+        table: Table,
+        storage: TableStorage,
+    ) -> Result<(), QueryError> {
+        tracing::info!(
+            database = %self.scheme,
+            table = %table.name,
+            compression = ?storage.compression,
+            "Registering table"
+        );
+        let table_shard = self.open_table(table, storage)?;
+        self.table_names
+            .write()
+            .unwrap()
+            .push(table_shard.table.name.clone());
+        self.tables
+            .insert(table_shard.table.name.clone(), table_shard);
+        Ok(())
+    }
+
+    /// Registers a table like `register_table_with_storage`, leaving it out of `table_names` and of the change feed:
+    /// it's searched and written by name like any table, but it's not listed with the tables of the database (so
+    /// backups, snapshots, stats and maintenance tasks skip it) and its changes are not published. Registering it
+    /// again replaces it.
+    ///
+    /// Meant for tables maintained by the database itself out of other tables, e.g. the rows of materialized views.
+    pub fn register_hidden_table(
+        &self,
+        table: Table,
+        storage: TableStorage,
+    ) -> Result<(), QueryError> {
+        tracing::info!(
+            database = %self.scheme,
+            table = %table.name,
+            "Registering hidden table"
+        );
+        let table_shard = self.open_table(table, storage)?;
+        self.changes.hide_table(&table_shard.table.name);
+        self.tables
+            .insert(table_shard.table.name.clone(), table_shard);
+        Ok(())
+    }
+
+    fn open_table(&self, table: Table, storage: TableStorage) -> Result<TableShard<T>, QueryError> {
+        let table_shard = TableShard::<T>::new(
+            table,
+            self.base_path.clone(),
+            self.scheme.as_str(),
+            TempDataShardConfig {
+                max_offsets: TempOffsetTypes::Custom(Some(storage.flush.capacity())),
+                encryption: self.encryption.clone(),
+            },
+            storage,
+            self.encryption.clone(),
+        )?;
+
+        if let Err(e) = self.recover_commit(&table_shard) {
+            tracing::error!(
+                database = %self.scheme,
+                table = %table_shard.table.name,
+                error = %e,
+                "Could not undo the interrupted transaction commit of the table"
+            );
+        }
+
+        Ok(table_shard)
+    }
+
+    /// Undoes the writes to `table_shard` of a transaction commit interrupted by a crash, if any (see `CommitLog`):
+    /// the rows the commit updated or deleted are written again and the rows it wrote are deleted.
+    fn recover_commit(&self, table_shard: &TableShard<T>) -> Result<(), QueryError> {
+        let db_folder = self.db_folder();
+        let table_name = &table_shard.table.name;
+
+        let log = CommitLog::load(&db_folder).map_err(|_| ShardErrors::FlushingError)?;
+        let start = match log.and_then(|log| log.tables.get(table_name).copied()) {
+            Some(start) => start,
+            None => return Ok(()),
+        };
+
+        tracing::warn!(
+            database = %self.scheme,
+            table = %table_name,
+            "Undoing an interrupted transaction commit"
+        );
+
+        let len = table_shard.data.read().unwrap().len() as u64;
+
+        // Positions the commit wrote come after `start.len`, the ones marked below it were alive before the commit
+        for pointer in table_shard.tombstones.marked_since(start.tombstones)? {
+            if pointer < start.len {
+                let data = table_shard
+                    .data
+                    .read()
+                    .unwrap()
+                    .get_element(pointer as usize)?;
+                table_shard.insert_row(&data)?;
+            }
+        }
+
+        for pointer in start.len..len {
+            table_shard.delete_row(pointer)?;
+        }
+
+        table_shard.sync()?;
+        CommitLog::finish(&db_folder, &[table_name.clone()])
+            .map_err(|_| ShardErrors::FlushingError)?;
+
+        Ok(())
+    }
+
+    /// Runs `backup` on the folder of `table_name` once every row is reconciled into the main shard and the indexes.
+    /// Writes to the table wait until `backup` returns, so the files it reads are consistent with each other.
+    pub fn backup_table<R>(
+        &self,
+        table_name: &str,
+        backup: impl FnOnce(&Path) -> R,
+    ) -> Result<R, QueryError> {
+        let table_shard = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        table_shard.temps.reconcile_all();
+        Ok(backup(&table_shard.path))
+    }
+
+    /// Copies the files of `table_name` into `dest` as a point-in-time snapshot of the table (see `TableShard::snapshot`),
+    /// once every row is reconciled into the main shard and the indexes. Writes to the table wait until it is copied.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of rows of the main shard held by the snapshot.
+    pub fn snapshot_table(&self, table_name: &str, dest: &Path) -> Result<usize, QueryError> {
+        let table_shard = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        table_shard.temps.reconcile_all();
+        table_shard
+            .snapshot(dest)
+            .map_err(|_| ShardErrors::FlushingError)?;
+
+        let rows = table_shard.data.read().unwrap().len();
+        Ok(rows)
+    }
+
+    /// Replaces the files of `table_name` with the ones in `folder` (as copied by `backup_table`) and reopens
+    /// the table from them. The table keeps its definition and settings, `folder` is moved into the table.
+    ///
+    /// Searches and writes wait for the table to be reopened. Rows written before are lost.
+    pub fn restore_table(&self, table_name: &str, folder: &Path) -> Result<(), QueryError> {
+        self.check_writable()?;
+        let _commits = self.commit_lock.read().unwrap();
+
+        let mut table_shard = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        let table = (*table_shard.table).clone();
+        let storage = table_shard.storage();
+        let path = table_shard.path.clone();
+
+        // The current files stay open (and readable) until the table is reopened, so they are only moved away
+        let swap = TableSwap::start(&path, folder).map_err(|_| ShardErrors::FlushingError)?;
+
+        *table_shard = self.open_table(table, storage)?;
+        drop(table_shard);
+
+        swap.finish().map_err(|_| ShardErrors::FlushingError)?;
+        Ok(())
+    }
+
+    /// Replaces the definition of the registered table named after `table` with `table`, reopening the table with it.
+    /// Rows written before are read after the new definition (see `TableSchema`) and the indexes added by `table`
+    /// are built from the rows of the table. The table keeps its storage settings.
+    ///
+    /// Searches and writes wait for the table to be reopened.
+    ///
+    /// # Returns:
+    /// - `Result<SchemaChange, QueryError>`: What changed. Fails with `IncompatibleSchema` (leaving the table as it was)
+    ///   if the type of a column or the primary key changed.
+    pub fn reload_table(&self, table: Table) -> Result<SchemaChange, QueryError> {
+        self.check_writable()?;
+
+        let mut table_shard = self
+            .tables
+            .get_mut(&table.name)
+            .ok_or_else(|| QueryError::InvalidTable(table.name.clone()))?;
+
+        let change = SchemaChange::between(&table_shard.table, &table);
+        if let Some(reason) = change.incompatibility() {
+            return Err(QueryError::IncompatibleSchema(table.name, reason));
+        }
+
+        tracing::info!(
+            database = %self.scheme,
+            table = %table.name,
+            change = ?change,
+            "Reloading table"
         );
+
+        table_shard.temps.reconcile_all();
+        let storage = table_shard.storage();
+        *table_shard = self.open_table(table, storage)?;
+
+        for index_name in change.added_indexes.iter() {
+            table_shard.rebuild_index(index_name)?;
+        }
+
+        Ok(change)
+    }
+
+    /// Creates `index` on the registered table `table_name`, indexing the rows it already holds.
+    ///
+    /// The index is built online in a background thread, writes keep going while it is built:
+    /// - The rows of the main shard are indexed without blocking the table. The rows written in the meantime
+    ///   are indexed the same way, pass after pass, until fewer than `INDEX_CATCH_UP_ROWS` are left.
+    /// - The table is then locked while the last rows written are reconciled and indexed, and the index
+    ///   is published. Searches only use the index once it holds every row.
+    ///
+    /// Rows updated or deleted while the index is built keep their entries, which searches skip like they
+    /// skip the stale entries of every index.
+    ///
+    /// # Returns:
+    /// - `Result<JoinHandle<Result<(), QueryError>>, QueryError>`: The thread building the index, which fails with
+    ///   `UniqueViolation` if the index is unique and two live rows share a key. Fails right away with
+    ///   `InvalidTable` for unknown tables, `DuplicateIndex` for index names taken or being built and
+    ///   `InvalidColumn` for unknown members.
+    pub fn create_index(
+        &self,
+        table_name: &str,
+        index: TableIndex,
+    ) -> Result<JoinHandle<Result<(), QueryError>>, QueryError>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.check_writable()?;
+
+        let index_obj = {
+            let table_shard = self
+                .tables
+                .get(table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+            if table_shard
+                .table
+                .indexes
+                .iter()
+                .any(|i| i.name == index.name)
+            {
+                return Err(QueryError::DuplicateIndex(index.name));
+            }
+
+            if let Some(member) = index
+                .members
+                .iter()
+                .find(|member| table_shard.table.resolve_column(member).is_none())
+            {
+                return Err(QueryError::InvalidColumn(member.clone()));
+            }
+
+            // Two builds of the same index would write to the same files
+            let build = (table_name.to_string(), index.name.clone());
+            if !self.index_builds.lock().unwrap().insert(build) {
+                return Err(QueryError::DuplicateIndex(index.name));
+            }
+
+            TableShard::<T>::open_index(&table_shard.path, &index, table_shard.encryption.clone())
+        };
+
+        let tables = self.tables.clone();
+        let index_builds = self.index_builds.clone();
+        let table_name = table_name.to_string();
+
+        Ok(std::thread::spawn(move || {
+            let build = (table_name.clone(), index.name.clone());
+            let result = Self::build_index(&tables, &table_name, index, index_obj);
+            index_builds.lock().unwrap().remove(&build);
+            result
+        }))
+    }
+
+    // Indexes the rows of `table_name` into `index_obj` and publishes it, see `create_index`
+    fn build_index(
+        tables: &CHashMap<String, TableShard<T>>,
+        table_name: &str,
+        index: TableIndex,
+        index_obj: IndexTypeValue,
+    ) -> Result<(), QueryError> {
+        let mut unique_keys = HashMap::new();
+        let mut indexed = 0;
+
+        loop {
+            let table_shard = tables
+                .get(table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+            table_shard.temps.reconcile_pending();
+            let len = table_shard.data.read().unwrap().len() as u64;
+            if len - indexed < INDEX_CATCH_UP_ROWS {
+                break;
+            }
+
+            table_shard.backfill_index(&index, &index_obj, indexed, len, &mut unique_keys)?;
+            indexed = len;
+        }
+
+        // No row can be written to the table while the guard is held
+        let mut table_shard = tables
+            .get_mut(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        table_shard.temps.reconcile_all();
+        let len = table_shard.data.read().unwrap().len() as u64;
+        table_shard.backfill_index(&index, &index_obj, indexed, len, &mut unique_keys)?;
+
+        table_shard.publish_index(index, index_obj)
+    }
+
+    /// Drops the index `index_name` of `table_name` and builds it again from the rows of the table.
+    /// The table is locked while the index is rebuilt, see `TableShard::rebuild_index`.
+    pub fn reindex(&self, table_name: &str, index_name: &str) -> Result<(), QueryError> {
+        self.check_writable()?;
+
+        let mut table_shard = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        table_shard.rebuild_index(index_name)
+    }
+
+    /// Checks the rows of `table_name` against their checksums, see `TableShard::verify`.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u64>, QueryError>`: The position of the corrupted rows, empty if every row is intact.
+    pub fn verify(&self, table_name: &str) -> Result<Vec<u64>, QueryError> {
+        let table_shard = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        Ok(table_shard.verify())
+    }
+
+    /// Checks the integrity of the files of `table_name`, see `TableShard::check_integrity`.
+    ///
+    /// With `repair`, the indexes holding issues are rebuilt from the rows of the table (see
+    /// `TableShard::rebuild_index`) and listed in `repaired_indexes`. Rows that can't be read are only reported.
+    ///
+    /// # Returns:
+    /// - `Result<IntegrityReport, QueryError>`: Fails for unknown tables, or when a damaged index can't be rebuilt
+    ///   (e.g. `ReadOnly`, or an unreadable row while indexing the table).
+    pub fn check_integrity(
+        &self,
+        table_name: &str,
+        repair: bool,
+    ) -> Result<IntegrityReport, QueryError> {
+        let mut report = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?
+            .check_integrity();
+
+        let damaged = report.damaged_indexes();
+        if repair && !damaged.is_empty() {
+            self.check_writable()?;
+
+            let mut table_shard = self
+                .tables
+                .get_mut(table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+            for index_name in damaged {
+                table_shard.rebuild_index(&index_name)?;
+                report.repaired_indexes.push(index_name);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Creates a `QuerySearchManager` over the tables registered in this manager.
+    pub fn search_manager(&self) -> QuerySearchManager<T> {
+        QuerySearchManager::new(self.tables.clone())
+            .set_slow_queries(Some(self.slow_queries.clone()))
     }
 
     /// Inserts a row in the first available temporary shard.
     /// This method will intentionally reconcile to the master shard IF and only IF the temporary shard runs out of spots.
+    /// Searches read the rows still in temporary shards along with the main shard, so inserted rows can be
+    /// read right away.
     ///
     /// # Examples
     ///
@@ -112,7 +657,7 @@ impl<T: Row<T>> SingleQueryManager<T> {
     ///
     ///
     /// let query_manager = SingleQueryManager::new("database-name".to_string());
-    /// query_manager.register_table(Table::new("users"));
+    /// query_manager.register_table(Table::new("users")).unwrap();
     ///
     /// let uuid = query_manager.insert(RowJson {
     ///   value: RowData {
@@ -129,26 +674,1137 @@ impl<T: Row<T>> SingleQueryManager<T> {
     ///
     /// `SingleQueryManager` will require a folder to be created for `database-name` otherwise it will panic.
     /// For a reference on how this is plugged: crates/query/src/search/search_manager.rs#test_search_manager
-    pub fn insert(&self, row: T) -> Result<Uuid, QueryError> {
+    ///
+    /// Inserts fail with `QueryError::UniqueViolation` when the row holds a key of a unique index that is already
+    /// held by another row, including rows that were not reconciled yet. The primary key must be present in the row
+    /// (`ValueNotPresent`) and unique (`DuplicatePrimaryKey`), and so must the `_uid`.
+    /// Columns missing from the row are filled with their default value first.
+    /// Once the database uses more than its storage quota (see `set_quota`), inserts fail with `QuotaExceeded`.
+    #[tracing::instrument(skip_all, fields(db = %self.scheme, table = %row.get_table_name()))]
+    pub fn insert(&self, mut row: T) -> Result<Uuid, QueryError> {
+        self.check_writable()?;
+        self.check_quota()?;
+        let _commits = self.commit_lock.read().unwrap();
+
         let table_name = row.get_table_name();
         let table = self.tables.get(&table_name);
 
         // TODO: Config to generate an UUID if not present
 
         if let Some(table_shard) = table {
+            table_shard.prepare_row(&mut row)?;
+
             let uuid = row
                 .get_value(&Table::get_internal_uid())
                 .ok_or(QueryError::UnknownUid)?;
 
-            let serialized_value = row
-                .serialize()
-                .map_err(|e| QueryError::InvalidSerialization)?;
+            let serialized_value = table_shard.serialize_row(&row)?;
+
+            table_shard.validate_primary_key(&row)?;
+            table_shard.validate_row(&row)?;
+            let unique_keys = table_shard
+                .reserve_unique_keys(TableShard::unique_keys(&table_shard.table, &row))?;
 
-            table_shard.temps.insert(&serialized_value)?;
+            if let Err(e) = table_shard.temps.insert(&serialized_value) {
+                TableShard::<T>::release_unique_keys(
+                    &table_shard.pending_unique_keys,
+                    &unique_keys,
+                );
+                return Err(e.into());
+            }
+            self.record_inserted(1, serialized_value.len());
+            self.changes.publish(ChangeEvent::new(
+                &table_name,
+                ChangeKind::Insert,
+                serialized_value,
+            ));
 
             Ok(uuid.as_uuid().unwrap().clone())
         } else {
             Err(QueryError::InvalidTable(table_name))
         }
     }
+
+    /// Inserts a batch of rows. Rows are validated and serialized first, then the rows of each table
+    /// are appended to a single temporary shard in one pass, instead of locking a temporary shard per row.
+    /// If any row is invalid (unknown table, missing uid, serialization error, unique key already taken) nothing is inserted.
+    ///
+    /// # Parameters:
+    /// - `rows`: The rows to insert. They can belong to different tables.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<Uuid>, QueryError>`: The `_uid` of every row, in the same order as `rows`.
+    #[tracing::instrument(skip_all, fields(db = %self.scheme, rows = rows.len()))]
+    pub fn insert_many(&self, rows: Vec<T>) -> Result<Vec<Uuid>, QueryError> {
+        self.check_writable()?;
+        self.check_quota()?;
+        let _commits = self.commit_lock.read().unwrap();
+
+        let mut uuids = Vec::with_capacity(rows.len());
+        let mut batches: Vec<(String, Vec<Vec<u8>>, Vec<(String, CompositeKey)>)> = vec![];
+
+        for mut row in rows {
+            let table_name = row.get_table_name();
+            let table_shard = self
+                .tables
+                .get(&table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+            table_shard.prepare_row(&mut row)?;
+
+            let uuid = row
+                .get_value(&Table::get_internal_uid())
+                .ok_or(QueryError::UnknownUid)?;
+
+            let serialized_value = table_shard.serialize_row(&row)?;
+
+            table_shard.validate_primary_key(&row)?;
+            table_shard.validate_row(&row)?;
+            let unique_keys = TableShard::unique_keys(&table_shard.table, &row);
+
+            match batches.iter_mut().find(|(name, _, _)| *name == table_name) {
+                Some((_, batch, keys)) => {
+                    batch.push(serialized_value);
+                    keys.extend(unique_keys);
+                }
+                None => batches.push((table_name, vec![serialized_value], unique_keys)),
+            }
+
+            uuids.push(uuid.as_uuid().unwrap().clone());
+        }
+
+        // Keys are reserved for every table before writing, so a violation leaves every table untouched
+        let mut reserved: Vec<(String, Vec<(String, CompositeKey)>)> = vec![];
+        for (table_name, _, keys) in batches.iter_mut() {
+            let table_shard = self
+                .tables
+                .get(table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+            match table_shard.reserve_unique_keys(std::mem::take(keys)) {
+                Ok(keys) => reserved.push((table_name.clone(), keys)),
+                Err(e) => {
+                    drop(table_shard);
+                    self.release_reserved(&reserved);
+                    return Err(e);
+                }
+            }
+        }
+
+        for (position, (table_name, batch, _)) in batches.iter().enumerate() {
+            let inserted = match self.tables.get(table_name) {
+                Some(table_shard) => {
+                    let items: Vec<&[u8]> = batch.iter().map(|item| item.as_slice()).collect();
+                    table_shard
+                        .temps
+                        .insert_many(&items)
+                        .map_err(QueryError::from)
+                }
+                None => Err(QueryError::InvalidTable(table_name.clone())),
+            };
+
+            if let Err(e) = inserted {
+                self.release_reserved(&reserved[position..]);
+                return Err(e);
+            }
+            self.record_inserted(batch.len(), batch.iter().map(|item| item.len()).sum());
+            if self.changes.has_subscribers() {
+                for item in batch {
+                    self.changes.publish(ChangeEvent::new(
+                        table_name,
+                        ChangeKind::Insert,
+                        item.clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(uuids)
+    }
+
+    /// Loads `rows` into `table_name` with a single write to the main shard, skipping the temporary shards.
+    /// Meant for bulk imports: rows are searchable as soon as this returns.
+    ///
+    /// Rows are validated like in `insert`. Invalid rows are left out while the valid ones are still written.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<Result<Uuid, QueryError>>, QueryError>`: The `_uid` of every row, or the reason it was left out,
+    ///   in the same order as `rows`. Fails as a whole for unknown tables or if the main shard can't be written.
+    #[tracing::instrument(skip_all, fields(db = %self.scheme, table = table_name, rows = rows.len()))]
+    pub fn load_rows(
+        &self,
+        table_name: &str,
+        rows: Vec<T>,
+    ) -> Result<Vec<Result<Uuid, QueryError>>, QueryError> {
+        self.check_writable()?;
+        self.check_quota()?;
+        let _commits = self.commit_lock.read().unwrap();
+
+        let table_shard = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        let mut items = vec![];
+        let mut reserved = vec![];
+
+        for mut row in rows {
+            results.push(Self::stage_load(&table_shard, &mut row).map(
+                |(uuid, item, unique_keys)| {
+                    items.push(item);
+                    reserved.extend(unique_keys);
+                    uuid
+                },
+            ));
+        }
+
+        let items: Vec<&[u8]> = items.iter().map(|item| item.as_slice()).collect();
+        let loaded = table_shard.insert_rows(&items);
+
+        // Loaded rows are indexed by now
+        TableShard::<T>::release_unique_keys(&table_shard.pending_unique_keys, &reserved);
+        loaded?;
+        self.record_inserted(items.len(), items.iter().map(|item| item.len()).sum());
+        if self.changes.has_subscribers() {
+            for item in items {
+                self.changes.publish(ChangeEvent::new(
+                    table_name,
+                    ChangeKind::Insert,
+                    item.to_vec(),
+                ));
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Validates and serializes a row about to be loaded into `table_shard`, reserving its unique keys.
+    fn stage_load(
+        table_shard: &TableShard<T>,
+        row: &mut T,
+    ) -> Result<(Uuid, Vec<u8>, Vec<(String, CompositeKey)>), QueryError> {
+        table_shard.prepare_row(row)?;
+
+        let uuid = row
+            .get_value(&Table::get_internal_uid())
+            .and_then(|uuid| uuid.as_uuid().cloned())
+            .ok_or(QueryError::UnknownUid)?;
+
+        let serialized_value = table_shard.serialize_row(row)?;
+
+        table_shard.validate_primary_key(row)?;
+        table_shard.validate_row(row)?;
+        let unique_keys =
+            table_shard.reserve_unique_keys(TableShard::unique_keys(&table_shard.table, row))?;
+
+        Ok((uuid, serialized_value, unique_keys))
+    }
+
+    /// Releases unique keys reserved through `TableShard::reserve_unique_keys`, by table name.
+    fn release_reserved(&self, reserved: &[(String, Vec<(String, CompositeKey)>)]) {
+        for (table_name, keys) in reserved {
+            if let Some(table_shard) = self.tables.get(table_name) {
+                TableShard::<T>::release_unique_keys(&table_shard.pending_unique_keys, keys);
+            }
+        }
+    }
+
+    /// Updates the rows of `table_name` matched by `query`, setting the columns in `new_values`.
+    /// Rows still sitting in temporary shards are reconciled first so they can be matched.
+    ///
+    /// Each row is updated atomically (see `TableShard::update_row`): its new version is written
+    /// and its index entries are fixed before the next row is processed.
+    ///
+    /// # Parameters:
+    /// - `table_name`: The table holding the rows.
+    /// - `query`: Filter selecting the rows to update.
+    /// - `new_values`: Column names and the value they should be set to.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of updated rows.
+    ///   Fails with `InvalidColumn` for unknown columns and `ImmutableColumn` for the primary key.
+    #[tracing::instrument(skip_all, fields(db = %self.scheme, table = %table_name))]
+    pub fn update(
+        &self,
+        table_name: String,
+        query: &QueryOps,
+        new_values: &HashMap<String, DataValue>,
+    ) -> Result<usize, QueryError> {
+        self.check_writable()?;
+        let _commits = self.commit_lock.read().unwrap();
+        let started = Instant::now();
+
+        let table_shard = self
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+        let values = Self::update_columns(&table_shard.table, new_values)?;
+
+        table_shard.temps.reconcile_all();
+
+        let search_manager = self.search_manager();
+        let mut pointers = search_manager.execute_query(&table_shard, query);
+        pointers.sort_unstable();
+
+        for pointer in pointers.iter() {
+            let before = self.changed_row(&table_shard, *pointer);
+            let new_pointer = table_shard.update_row(*pointer, &values)?;
+            if let Some(row) = self.changed_row(&table_shard, new_pointer) {
+                self.changes.publish(
+                    ChangeEvent::new(&table_name, ChangeKind::Update, row).set_before(before),
+                );
+            }
+        }
+
+        search_manager.log_if_slow("update", &table_shard, query, pointers.len(), started);
+        Ok(pointers.len())
+    }
+
+    /// Inserts `row`, or updates the row holding the same key if there is one, in a single operation.
+    ///
+    /// The key is made of the columns of the index named `index_name`, or the primary key of the table when `None`.
+    /// Upserts on the same table are serialized, which avoids the race of searching for a row and inserting it
+    /// when it is missing from two places at once.
+    ///
+    /// When the row exists, every column present in `row` except the primary key is written to it.
+    ///
+    /// # Parameters:
+    /// - `row`: The row to insert or merge into the existing one.
+    /// - `index_name`: The index whose members identify the row.
+    ///
+    /// # Returns:
+    /// - `Result<Uuid, QueryError>`: The `_uid` of the inserted or updated row.
+    ///   Fails with `InvalidIndex` for unknown indexes and `ValueNotPresent` when `row` lacks a member of the key.
+    #[tracing::instrument(skip_all, fields(db = %self.scheme, table = %row.get_table_name()))]
+    pub fn upsert(&self, mut row: T, index_name: Option<&str>) -> Result<Uuid, QueryError> {
+        self.check_writable()?;
+        let _commits = self.commit_lock.read().unwrap();
+
+        let table_name = row.get_table_name();
+        let table_shard = self
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+        table_shard.prepare_row(&mut row)?;
+
+        let key_columns = match index_name {
+            Some(index_name) => table_shard
+                .table
+                .indexes
+                .iter()
+                .find(|index| index.name == index_name)
+                .map(|index| index.members.clone())
+                .ok_or_else(|| QueryError::InvalidIndex(index_name.to_string()))?,
+            None => vec![table_shard.table.primary_key.clone()],
+        };
+
+        let mut conditions = vec![];
+        for column_name in key_columns {
+            let value = table_shard
+                .table
+                .resolve_column(&column_name)
+                .and_then(|column| row.get_value(&column))
+                .filter(|value| !value.is_null())
+                .ok_or_else(|| QueryError::ValueNotPresent(column_name.clone()))?;
+
+            conditions.push(QueryOps::Condition(QueryVal {
+                key: column_name,
+                filter_type: "=".to_string(),
+                value,
+            }));
+        }
+
+        let _upsert_guard = table_shard.upsert_lock.lock().unwrap();
+
+        table_shard.temps.reconcile_all();
+
+        let mut pointers = self
+            .search_manager()
+            .execute_query(&table_shard, &QueryOps::And(conditions));
+        pointers.sort_unstable();
+
+        match pointers.first() {
+            None => {
+                self.check_quota()?;
+
+                let uuid = row
+                    .get_value(&Table::get_internal_uid())
+                    .ok_or(QueryError::UnknownUid)?;
+
+                let serialized_value = table_shard.serialize_row(&row)?;
+
+                table_shard.validate_primary_key(&row)?;
+                table_shard.validate_row(&row)?;
+                let unique_keys = table_shard
+                    .reserve_unique_keys(TableShard::unique_keys(&table_shard.table, &row))?;
+
+                if let Err(e) = table_shard.temps.insert(&serialized_value) {
+                    TableShard::<T>::release_unique_keys(
+                        &table_shard.pending_unique_keys,
+                        &unique_keys,
+                    );
+                    return Err(e.into());
+                }
+                self.record_inserted(1, serialized_value.len());
+                self.changes.publish(ChangeEvent::new(
+                    &table_name,
+                    ChangeKind::Insert,
+                    serialized_value,
+                ));
+
+                Ok(uuid.as_uuid().unwrap().clone())
+            }
+            Some(pointer) => {
+                let values: Vec<_> = table_shard
+                    .table
+                    .columns
+                    .values()
+                    .filter(|column| {
+                        !column.primary_key
+                            && column.name != table_shard.table.primary_key
+                            && !(table_shard.table.timestamps
+                                && Table::is_timestamp_column(&column.name))
+                    })
+                    .filter_map(|column| row.get_value(column).map(|value| (column.clone(), value)))
+                    .collect();
+
+                let existing_uid = {
+                    let data = table_shard.data.read().unwrap();
+                    let existing = T::from(&data.get_element(*pointer as usize)?);
+                    existing
+                        .get_value(&Table::get_internal_uid())
+                        .ok_or(QueryError::UnknownUid)?
+                };
+
+                let before = self.changed_row(&table_shard, *pointer);
+                let new_pointer = table_shard.update_row(*pointer, &values)?;
+                if let Some(row) = self.changed_row(&table_shard, new_pointer) {
+                    self.changes.publish(
+                        ChangeEvent::new(&table_name, ChangeKind::Update, row).set_before(before),
+                    );
+                }
+
+                Ok(existing_uid.as_uuid().unwrap().clone())
+            }
+        }
+    }
+
+    /// Resolves the columns of `new_values`, rejecting unknown columns and the primary key.
+    fn update_columns(
+        table: &Table,
+        new_values: &HashMap<String, DataValue>,
+    ) -> Result<Vec<(Column, DataValue)>, QueryError> {
+        let mut values = vec![];
+        for (column_name, value) in new_values {
+            let column = table
+                .get_column(column_name)
+                .ok_or_else(|| QueryError::InvalidColumn(column_name.clone()))?;
+
+            if column.primary_key
+                || *column_name == table.primary_key
+                || (table.timestamps && Table::is_timestamp_column(column_name))
+            {
+                return Err(QueryError::ImmutableColumn(column_name.clone()));
+            }
+
+            values.push((column.clone(), value.clone()));
+        }
+
+        Ok(values)
+    }
+
+    /// Deletes the rows of `table_name` matched by `query`.
+    /// Rows still sitting in temporary shards are reconciled first so they can be matched.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of deleted rows.
+    #[tracing::instrument(skip_all, fields(db = %self.scheme, table = %table_name))]
+    pub fn delete(&self, table_name: String, query: &QueryOps) -> Result<usize, QueryError> {
+        self.check_writable()?;
+        let _commits = self.commit_lock.read().unwrap();
+        let started = Instant::now();
+
+        let table_shard = self
+            .tables
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+        table_shard.temps.reconcile_all();
+
+        let search_manager = self.search_manager();
+        let pointers = search_manager.execute_query(&table_shard, query);
+
+        for pointer in pointers.iter() {
+            let row = self.changed_row(&table_shard, *pointer);
+            table_shard.delete_row(*pointer)?;
+            if let Some(row) = row {
+                self.changes
+                    .publish(ChangeEvent::new(&table_name, ChangeKind::Delete, row));
+            }
+        }
+
+        search_manager.log_if_slow("delete", &table_shard, query, pointers.len(), started);
+        Ok(pointers.len())
+    }
+
+    /// Applies a change published by the change feed of another database holding the same tables, e.g. the leader
+    /// this database replicates. Rows are matched through their `_uid`, so applying a change twice has no effect:
+    /// inserting a row that already exists replaces it, as does updating one, and deleting a missing row is a no-op.
+    ///
+    /// Rows are written as they are, they were already prepared and validated by the database they come from.
+    /// Changes are applied to read-only databases too, as replicas are meant to only be written by their leader.
+    /// Once applied, the change is published to the change feed of this database.
+    #[tracing::instrument(skip_all, fields(db = %self.scheme, table = %event.table))]
+    pub fn apply_change(&self, event: &ChangeEvent) -> Result<(), QueryError> {
+        let _commits = self.commit_lock.read().unwrap();
+
+        let table_shard = self
+            .tables
+            .get(&event.table)
+            .ok_or_else(|| QueryError::InvalidTable(event.table.clone()))?;
+
+        let uid = T::from(event.row.as_slice())
+            .get_value(&Table::get_internal_uid())
+            .ok_or(QueryError::UnknownUid)?;
+
+        table_shard.temps.reconcile_all();
+
+        let pointers = self.search_manager().execute_query(
+            &table_shard,
+            &QueryOps::Condition(QueryVal {
+                key: Table::get_internal_uid().name,
+                filter_type: "=".to_string(),
+                value: uid,
+            }),
+        );
+
+        let before = match (&event.kind, pointers.first()) {
+            (ChangeKind::Delete, None) => return Ok(()),
+            (ChangeKind::Delete, Some(pointer)) => {
+                table_shard.delete_row(*pointer)?;
+                None
+            }
+            (_, None) => {
+                table_shard.insert_row(&event.row)?;
+                self.record_inserted(1, event.row.len());
+                None
+            }
+            (_, Some(pointer)) => {
+                let before = self.changed_row(&table_shard, *pointer);
+                table_shard.replace_row(*pointer, &event.row)?;
+                before
+            }
+        };
+
+        self.changes.publish(
+            ChangeEvent::new(&event.table, event.kind, event.row.clone()).set_before(before),
+        );
+        Ok(())
+    }
+
+    /// Deletes the rows of `table_name` that expired according to the TTL of the table.
+    /// Like any deleted row, their index entries stop being matched right away.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of deleted rows, `0` for tables without a TTL.
+    pub fn purge_expired(&self, table_name: String) -> Result<usize, QueryError> {
+        let ttl = {
+            let table_shard = self
+                .tables
+                .get(&table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+            match &table_shard.table.ttl {
+                Some(ttl) => ttl.clone(),
+                None => return Ok(0),
+            }
+        };
+
+        let expired = QueryOps::Condition(QueryVal {
+            key: ttl.column.clone(),
+            filter_type: "<".to_string(),
+            value: DataValue::Number(ttl.expiration_cutoff().into()),
+        });
+
+        self.delete(table_name, &expired)
+    }
+
+    /// Rewrites the main shard of `table_name` without the rows that are no longer alive (deleted, expired or
+    /// previous versions of updated rows), freeing the space they take. Indexes are rebuilt, as rows move.
+    /// Searches and writes wait until the table is compacted. Tables without dead rows are left untouched.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of rows dropped from the main shard.
+    pub fn compact_table(&self, table_name: &str) -> Result<usize, QueryError> {
+        self.check_writable()?;
+        let _commits = self.commit_lock.read().unwrap();
+
+        let mut table_shard = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+
+        table_shard.temps.reconcile_all();
+        if table_shard.tombstones.is_empty() {
+            return Ok(0);
+        }
+
+        let table = (*table_shard.table).clone();
+        let rows = table_shard.data.read().unwrap().len();
+        let storage = table_shard.storage();
+        let path = table_shard.path.clone();
+
+        let compacted = path.with_file_name(format!(".{}-compact-{}", table_name, Uuid::new_v4()));
+        let alive = match table_shard.compact_into(&compacted) {
+            Ok(alive) => alive,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&compacted);
+                return Err(e);
+            }
+        };
+
+        // Same swap as `restore_table`, the current files stay readable until the table is reopened
+        let swap = TableSwap::start(&path, &compacted).map_err(|_| ShardErrors::FlushingError)?;
+
+        *table_shard = self.open_table(table, storage)?;
+        for index in table_shard.table.indexes.clone() {
+            table_shard.rebuild_index(&index.name)?;
+        }
+        drop(table_shard);
+
+        swap.finish().map_err(|_| ShardErrors::FlushingError)?;
+        Ok(rows - alive)
+    }
+
+    /// Compacts every table whose dead rows make up at least `min_dead_ratio` (from 0 to 1) of its main shard,
+    /// see `compact_table`. Compacting rebuilds the indexes of the table, which drops their entries pointing to
+    /// dead rows.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of rows dropped from the main shards.
+    pub fn compact_all(&self, min_dead_ratio: f64) -> Result<usize, QueryError> {
+        let table_names = self.table_names.read().unwrap().clone();
+
+        let mut dropped = 0;
+        for table_name in table_names {
+            let dead_ratio = match self.tables.get(&table_name) {
+                Some(table_shard) => {
+                    let rows = table_shard.data.read().unwrap().len().max(1);
+                    table_shard.tombstones.len() as f64 / rows as f64
+                }
+                None => continue,
+            };
+
+            if dead_ratio > 0.0 && dead_ratio >= min_dead_ratio {
+                dropped += self.compact_table(&table_name)?;
+            }
+        }
+
+        Ok(dropped)
+    }
+
+    /// Enforces the retention of every table with a TTL: expired rows are deleted (see `purge_expired`)
+    /// and the tables holding dead rows are compacted (see `compact_table`).
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of rows dropped from the main shards.
+    pub fn enforce_retention(&self) -> Result<usize, QueryError> {
+        let table_names = self.table_names.read().unwrap().clone();
+
+        let mut dropped = 0;
+        for table_name in table_names {
+            let has_ttl = self
+                .tables
+                .get(&table_name)
+                .map_or(false, |table_shard| table_shard.table.ttl.is_some());
+
+            if has_ttl {
+                self.purge_expired(table_name.clone())?;
+                dropped += self.compact_table(&table_name)?;
+            }
+        }
+
+        Ok(dropped)
+    }
+
+    /// Runs `purge_expired` on every table.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of deleted rows across every table.
+    pub fn purge_all_expired(&self) -> Result<usize, QueryError> {
+        let table_names = self.table_names.read().unwrap().clone();
+
+        let mut purged = 0;
+        for table_name in table_names {
+            purged += self.purge_expired(table_name)?;
+        }
+
+        Ok(purged)
+    }
+
+    /// Recomputes the index statistics of every table, see `TableShard::refresh_index_stats`.
+    pub fn refresh_index_stats(&self) {
+        let table_names = self.table_names.read().unwrap().clone();
+
+        for table_name in table_names {
+            if let Some(table_shard) = self.tables.get(&table_name) {
+                table_shard.refresh_index_stats();
+            }
+        }
+    }
+
+    /// Reconciles the temporary shards of every table whose flush policy says so, see `TempCollection::flush_due`.
+    pub fn flush_due(&self) {
+        let table_names = self.table_names.read().unwrap().clone();
+
+        for table_name in table_names {
+            if let Some(table_shard) = self.tables.get(&table_name) {
+                table_shard.temps.flush_due();
+            }
+        }
+    }
+
+    /// Flushes every table to disk before the database is closed, see `TableShard::sync`. Tables keep working
+    /// afterwards, rows written later are buffered again until the next flush.
+    ///
+    /// # Returns:
+    /// - `Result<(), QueryError>`: Fails on the first table whose files can't be synced, the following ones are
+    ///   still synced.
+    pub fn shutdown(&self) -> Result<(), QueryError> {
+        let table_names = self.table_names.read().unwrap().clone();
+        let mut result = Ok(());
+
+        for table_name in table_names {
+            if let Some(table_shard) = self.tables.get(&table_name) {
+                let synced = table_shard.sync();
+                if result.is_ok() {
+                    result = synced;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Statistics of every table of the database, see `TableShard::stats`.
+    pub fn stats(&self) -> DatabaseStats {
+        let mut table_names = self.table_names.read().unwrap().clone();
+        table_names.sort();
+
+        let tables: Vec<_> = table_names
+            .iter()
+            .filter_map(|table_name| Some(self.tables.get(table_name)?.stats()))
+            .collect();
+
+        DatabaseStats {
+            name: self.scheme.clone(),
+            size: tables.iter().map(|table| table.size()).sum(),
+            tables,
+        }
+    }
+
+    /// Starts a new `Transaction`. Operations staged on it are only applied by `commit`.
+    pub fn begin(&self) -> Transaction<T> {
+        Transaction::new()
+    }
+
+    /// Applies every operation of `transaction`, in the order they were staged.
+    ///
+    /// Operations are validated before anything is written (unknown tables or columns, missing uids, serialization,
+    /// unique keys already taken).
+    /// Rows inserted through a transaction are written to the main shard directly, so later operations of the same
+    /// transaction can match them. If an operation fails while being applied, the ones already applied are undone
+    /// (inserted rows are deleted, updated rows get their previous version back and deleted rows are written again).
+    ///
+    /// Commits are atomic: every other write waits until the commit is done, searches only see the writes of the
+    /// transaction once all of them are applied (or none of them, if it's undone), and a commit interrupted by a
+    /// crash is undone when its tables are opened again (see `CommitLog`).
+    ///
+    /// # Returns:
+    /// - `Result<TransactionResult, QueryError>`: What the transaction did, or the error that made it fail.
+    pub fn commit(&self, transaction: Transaction<T>) -> Result<TransactionResult, QueryError> {
+        self.check_writable()?;
+
+        let _commit_guard = self.commit_lock.write().unwrap();
+
+        // Validation pass, nothing is written until every operation is known to be valid
+        let mut reserved = vec![];
+        let staged = match self.stage(transaction.into_ops(), &mut reserved) {
+            Ok(staged) => staged,
+            Err(e) => {
+                self.release_reserved(&reserved);
+                return Err(e);
+            }
+        };
+
+        let applied = self.apply_all(staged);
+
+        // Inserted rows are indexed by now
+        self.release_reserved(&reserved);
+
+        let (result, changes) = applied?;
+        for change in changes {
+            self.changes.publish(change);
+        }
+
+        Ok(result)
+    }
+
+    /// Applies `staged` as a whole: the clocks of the tables it writes are held until every operation is applied
+    /// and on disk, and where the tables stood before is logged so the commit can be undone if it's interrupted.
+    fn apply_all(
+        &self,
+        staged: Vec<StagedOp>,
+    ) -> Result<(TransactionResult, Vec<ChangeEvent>), QueryError> {
+        let mut table_names: Vec<String> = staged
+            .iter()
+            .map(|op| op.table_name().to_string())
+            .collect();
+        table_names.sort_unstable();
+        table_names.dedup();
+
+        let mut starts = vec![];
+        let mut clocks = vec![];
+        for table_name in &table_names {
+            let table_shard = self
+                .tables
+                .get(table_name)
+                .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+            // Rows of the transaction are written to the main shard, so it must hold every row written before
+            table_shard.temps.reconcile_all();
+            let start = CommitStart {
+                len: table_shard.data.read().unwrap().len() as u64,
+                tombstones: table_shard.tombstones.len(),
+            };
+            starts.push((table_name.clone(), start));
+            clocks.push(table_shard.clock.clone());
+        }
+
+        let db_folder = self.db_folder();
+        CommitLog::start(&db_folder, starts).map_err(|_| ShardErrors::FlushingError)?;
+        let holds: Vec<_> = clocks.iter().map(|clock| clock.hold()).collect();
+
+        let mut undo_log = vec![];
+        let mut result = TransactionResult::default();
+        let mut changes = vec![];
+
+        let applied = staged
+            .into_iter()
+            .try_for_each(|op| self.apply_staged(op, &mut undo_log, &mut result, &mut changes))
+            .and_then(|_| self.sync_tables(&table_names));
+
+        let synced = match &applied {
+            Ok(_) => Ok(()),
+            Err(_) => {
+                self.undo(undo_log);
+                self.sync_tables(&table_names)
+            }
+        };
+
+        // The log is kept until the tables are on disk, as they are until then the commit is undone on open
+        match synced {
+            Ok(_) => CommitLog::finish(&db_folder, &table_names)
+                .map_err(|_| ShardErrors::FlushingError)?,
+            Err(e) => tracing::error!(
+                database = %self.scheme,
+                error = %e,
+                "Could not write the transaction, it is undone when the database is opened again"
+            ),
+        }
+
+        // Searches see every write of the transaction at once
+        drop(holds);
+
+        applied.map(|_| (result, changes))
+    }
+
+    /// Waits until `table_names` are on disk (see `TableShard::sync`).
+    fn sync_tables(&self, table_names: &[String]) -> Result<(), QueryError> {
+        for table_name in table_names {
+            if let Some(table_shard) = self.tables.get(table_name) {
+                table_shard.sync()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Folder the tables of the database are stored in.
+    fn db_folder(&self) -> PathBuf {
+        create_scheme_js_db(self.base_path.clone(), &self.scheme)
+    }
+
+    /// Validates `ops`, reserving the unique keys of the inserted rows in `reserved`.
+    fn stage(
+        &self,
+        ops: Vec<TransactionOp<T>>,
+        reserved: &mut Vec<(String, Vec<(String, CompositeKey)>)>,
+    ) -> Result<Vec<StagedOp>, QueryError> {
+        let mut staged = vec![];
+        for op in ops {
+            staged.push(match op {
+                TransactionOp::Insert(mut row) => {
+                    self.check_quota()?;
+
+                    let table_name = row.get_table_name();
+                    let table_shard = self
+                        .tables
+                        .get(&table_name)
+                        .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+                    table_shard.prepare_row(&mut row)?;
+
+                    let uuid = row
+                        .get_value(&Table::get_internal_uid())
+                        .ok_or(QueryError::UnknownUid)?;
+
+                    let serialized_value = table_shard.serialize_row(&row)?;
+
+                    table_shard.validate_primary_key(&row)?;
+                    table_shard.validate_row(&row)?;
+                    let unique_keys = table_shard
+                        .reserve_unique_keys(TableShard::unique_keys(&table_shard.table, &row))?;
+                    reserved.push((table_name.clone(), unique_keys));
+
+                    StagedOp::Insert(
+                        table_name,
+                        uuid.as_uuid().unwrap().clone(),
+                        serialized_value,
+                    )
+                }
+                TransactionOp::Update {
+                    table,
+                    query,
+                    values,
+                } => {
+                    let table_shard = self
+                        .tables
+                        .get(&table)
+                        .ok_or_else(|| QueryError::InvalidTable(table.clone()))?;
+                    let values = Self::update_columns(&table_shard.table, &values)?;
+
+                    StagedOp::Update(table, query, values)
+                }
+                TransactionOp::Delete { table, query } => {
+                    if !self.tables.contains_key(&table) {
+                        return Err(QueryError::InvalidTable(table));
+                    }
+
+                    StagedOp::Delete(table, query)
+                }
+            });
+        }
+
+        Ok(staged)
+    }
+
+    fn apply_staged(
+        &self,
+        op: StagedOp,
+        undo_log: &mut Vec<UndoOp>,
+        result: &mut TransactionResult,
+        changes: &mut Vec<ChangeEvent>,
+    ) -> Result<(), QueryError> {
+        match op {
+            StagedOp::Insert(table_name, uuid, data) => {
+                let table_shard = self
+                    .tables
+                    .get(&table_name)
+                    .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+                let pointer = table_shard.insert_row(&data)?;
+                self.record_inserted(1, data.len());
+                if self.changes.has_subscribers() {
+                    changes.push(ChangeEvent::new(&table_name, ChangeKind::Insert, data));
+                }
+                undo_log.push(UndoOp::Remove(table_name, pointer));
+                result.inserted.push(uuid);
+            }
+            StagedOp::Update(table_name, query, values) => {
+                let table_shard = self
+                    .tables
+                    .get(&table_name)
+                    .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+                // Rows written by the transaction so far are held back, only `latest` sees them
+                let mut pointers = self.search_manager().execute_query_at(
+                    &table_shard,
+                    &query,
+                    &table_shard.clock.latest(),
+                );
+                pointers.sort_unstable();
+
+                for pointer in pointers {
+                    let old_data = table_shard
+                        .data
+                        .read()
+                        .unwrap()
+                        .get_element(pointer as usize)?;
+                    let new_pointer = table_shard.update_row(pointer, &values)?;
+                    if let Some(row) = self.changed_row(&table_shard, new_pointer) {
+                        changes.push(
+                            ChangeEvent::new(&table_name, ChangeKind::Update, row)
+                                .set_before(Some(old_data.clone())),
+                        );
+                    }
+                    undo_log.push(UndoOp::Restore(table_name.clone(), new_pointer, old_data));
+                    result.updated += 1;
+                }
+            }
+            StagedOp::Delete(table_name, query) => {
+                let table_shard = self
+                    .tables
+                    .get(&table_name)
+                    .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+                let pointers = self.search_manager().execute_query_at(
+                    &table_shard,
+                    &query,
+                    &table_shard.clock.latest(),
+                );
+
+                for pointer in pointers {
+                    let old_data = table_shard
+                        .data
+                        .read()
+                        .unwrap()
+                        .get_element(pointer as usize)?;
+                    table_shard.delete_row(pointer)?;
+                    if self.changes.has_subscribers() {
+                        changes.push(ChangeEvent::new(
+                            &table_name,
+                            ChangeKind::Delete,
+                            old_data.clone(),
+                        ));
+                    }
+                    undo_log.push(UndoOp::Reinsert(table_name.clone(), old_data));
+                    result.deleted += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reverts the operations of `undo_log`, latest first.
+    fn undo(&self, undo_log: Vec<UndoOp>) {
+        for op in undo_log.into_iter().rev() {
+            let (table_name, outcome) = match op {
+                UndoOp::Remove(table_name, pointer) => {
+                    let outcome = self
+                        .tables
+                        .get(&table_name)
+                        .map(|shard| shard.delete_row(pointer));
+                    (table_name, outcome)
+                }
+                UndoOp::Restore(table_name, pointer, data) => {
+                    let outcome = self
+                        .tables
+                        .get(&table_name)
+                        .map(|shard| shard.replace_row(pointer, &data).map(|_| ()));
+                    (table_name, outcome)
+                }
+                UndoOp::Reinsert(table_name, data) => {
+                    let outcome = self
+                        .tables
+                        .get(&table_name)
+                        .map(|shard| shard.insert_row(&data).map(|_| ()));
+                    (table_name, outcome)
+                }
+            };
+
+            if let Some(Err(e)) = outcome {
+                tracing::error!(
+                    database = %self.scheme,
+                    table = %table_name,
+                    error = %e,
+                    "Could not undo transaction write"
+                );
+            }
+        }
+    }
+}
+
+/// A validated `TransactionOp`, ready to be applied.
+enum StagedOp {
+    Insert(String, Uuid, Vec<u8>),
+    Update(String, QueryOps, Vec<(Column, DataValue)>),
+    Delete(String, QueryOps),
+}
+
+impl StagedOp {
+    fn table_name(&self) -> &str {
+        match self {
+            StagedOp::Insert(table_name, ..)
+            | StagedOp::Update(table_name, ..)
+            | StagedOp::Delete(table_name, ..) => table_name,
+        }
+    }
+}
+
+/// Reverts a write applied by a transaction that ended up failing.
+enum UndoOp {
+    /// Deletes a row inserted by the transaction.
+    Remove(String, u64),
+    /// Writes the previous version of an updated row, currently at the given position.
+    Restore(String, u64, Vec<u8>),
+    /// Writes a deleted row again.
+    Reinsert(String, Vec<u8>),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{
+        equals, test_db, user_names, user_row, users_by_country,
+    };
+    use crate::managers::single::SingleQueryManager;
+    use crate::row_json::RowJson;
+    use schemajs_data::utils::fs::list_files_with_prefix;
+    use serde_json::json;
+
+    #[flaky_test::flaky_test]
+    pub fn test_query_manager_shutdown() {
+        let (test_db, db_folder) = test_db();
+
+        {
+            let query_manager: SingleQueryManager<RowJson> =
+                SingleQueryManager::new(test_db.clone());
+            query_manager.register_table(users_by_country()).unwrap();
+
+            for (name, country) in [("andres", "US"), ("luis", "VE"), ("carlos", "US")] {
+                query_manager
+                    .insert(user_row(
+                        json!({ "user_name": name, "user_country": country }),
+                    ))
+                    .unwrap();
+            }
+
+            query_manager.shutdown().unwrap();
+
+            // Buffered rows are in the main shard and indexed, nothing is left to recover
+            let table_shard = query_manager.tables.get("users").unwrap();
+            assert_eq!(table_shard.temps.pending_rows(), 0);
+            assert_eq!(table_shard.data.read().unwrap().len(), 3);
+            let logged: u64 = list_files_with_prefix(table_shard.path.join("temps"), "wal_")
+                .unwrap()
+                .iter()
+                .map(|log| std::fs::metadata(log).unwrap().len())
+                .sum();
+            assert_eq!(logged, 0);
+        }
+
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+        query_manager.register_table(users_by_country()).unwrap();
+
+        assert_eq!(
+            user_names(&query_manager, "users", &equals("user_country", "US")),
+            vec!["andres", "carlos"]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
 }
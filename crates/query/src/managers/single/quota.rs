@@ -0,0 +1,120 @@
+use crate::errors::QueryError;
+use schemajs_data::utils::fs::folder_size;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Storage quota of a database (`storage_quota` under `[databases.<database name>]` in `SchemeJS.toml`).
+///
+/// The folder of the database is only measured once, when the quota is created. From then on, the bytes
+/// of the rows inserted are added to the usage as they are written, so inserts don't stat the folder.
+/// The usage is an estimate between restarts: it doesn't account for headers, indexes or rows written
+/// twice (once in a temporary shard, once in the main shard).
+///
+/// # Fields:
+/// - `limit`: Size in bytes the database can reach, inserts fail with `QuotaExceeded` once the usage passes it.
+/// - `usage`: Bytes the database is known to use.
+#[derive(Debug)]
+pub struct StorageQuota {
+    limit: u64,
+    usage: AtomicU64,
+}
+
+impl StorageQuota {
+    /// Creates a quota of `limit` bytes for the database stored in `db_folder`, measuring its current usage.
+    pub fn new(limit: u64, db_folder: &Path) -> std::io::Result<Self> {
+        Ok(Self::with_usage(limit, folder_size(db_folder)?))
+    }
+
+    pub fn with_usage(limit: u64, usage: u64) -> Self {
+        Self {
+            limit,
+            usage: AtomicU64::new(usage),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn usage(&self) -> u64 {
+        self.usage.load(Ordering::SeqCst)
+    }
+
+    /// Fails with `QuotaExceeded` for the database `scheme` once its usage is past the limit.
+    pub fn check(&self, scheme: &str) -> Result<(), QueryError> {
+        match self.usage() > self.limit {
+            true => Err(QueryError::QuotaExceeded(scheme.to_string())),
+            false => Ok(()),
+        }
+    }
+
+    /// Adds `bytes` written to the database to its usage.
+    pub fn record(&self, bytes: u64) {
+        self.usage.fetch_add(bytes, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{equals, test_db, user, users};
+    use crate::managers::single::quota::StorageQuota;
+    use crate::managers::single::SingleQueryManager;
+    use crate::row_json::RowJson;
+
+    #[flaky_test::flaky_test]
+    pub fn test_storage_quota() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db)
+            .set_quota(Some(StorageQuota::new(200, &db_folder).unwrap()));
+        query_manager.register_table(users()).unwrap();
+
+        // Rows large enough to pass the limit in a few inserts
+        let row = |name: &str| user(&name.repeat(20));
+
+        // Usage grows with every insert until it passes the limit
+        let mut inserted = 0;
+        while query_manager.insert(row("andres")).is_ok() {
+            inserted += 1;
+        }
+        assert!(inserted > 0);
+        let quota = query_manager.quota().unwrap();
+        assert!(quota.usage() > quota.limit());
+
+        assert!(query_manager
+            .insert(row("carlos"))
+            .err()
+            .unwrap()
+            .is_quota_exceeded());
+        assert!(query_manager
+            .insert_many(vec![row("luis")])
+            .err()
+            .unwrap()
+            .is_quota_exceeded());
+        assert!(query_manager
+            .upsert(row("juan"), None)
+            .err()
+            .unwrap()
+            .is_quota_exceeded());
+
+        let mut transaction = query_manager.begin();
+        transaction.insert(row("pedro"));
+        assert!(query_manager
+            .commit(transaction)
+            .err()
+            .unwrap()
+            .is_quota_exceeded());
+
+        // Reads and deletes still work
+        assert_eq!(
+            query_manager
+                .delete(
+                    "users".to_string(),
+                    &equals("user_name", "andres".repeat(20))
+                )
+                .unwrap(),
+            inserted
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
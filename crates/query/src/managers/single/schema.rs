@@ -0,0 +1,409 @@
+use schemajs_primitives::column::types::DataTypes;
+use schemajs_primitives::table::Table;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// `DataTypes` has no `PartialEq`, types are compared through their serialized form
+fn same<S: Serialize>(a: &S, b: &S) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Differences between two definitions of a table, see `SchemaChange::between`.
+///
+/// # Fields:
+/// - `added_columns`: Columns only held by the new definition.
+/// - `dropped_columns`: Columns only held by the previous definition.
+/// - `changed_columns`: Columns whose type changed. Rows hold values of the previous type, so these changes
+///   can't be applied to a table that holds rows.
+/// - `added_indexes`: Indexes only held by the new definition, or whose members, type, uniqueness or the
+///   collation of their members changed. They have to be built from the rows of the table.
+/// - `dropped_indexes`: Indexes only held by the previous definition.
+/// - `primary_key_changed`: Whether the primary key changed, which can't be applied either.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChange {
+    pub added_columns: Vec<String>,
+    pub dropped_columns: Vec<String>,
+    pub changed_columns: Vec<String>,
+    pub added_indexes: Vec<String>,
+    pub dropped_indexes: Vec<String>,
+    pub primary_key_changed: bool,
+}
+
+impl SchemaChange {
+    pub fn between(previous: &Table, table: &Table) -> Self {
+        let mut change = Self {
+            primary_key_changed: previous.primary_key != table.primary_key,
+            ..Default::default()
+        };
+
+        for column in table.columns.values() {
+            match previous.get_column(&column.name) {
+                None => change.added_columns.push(column.name.clone()),
+                Some(previous_column) => {
+                    if !same(&previous_column.data_type, &column.data_type) {
+                        change.changed_columns.push(column.name.clone());
+                    }
+                }
+            }
+        }
+        for column in previous.columns.values() {
+            if table.get_column(&column.name).is_none() {
+                change.dropped_columns.push(column.name.clone());
+            }
+        }
+
+        for index in table.indexes.iter() {
+            // Keys are stored collated, so indexes over a column whose collation changed hold stale keys
+            let recollated = index
+                .members
+                .iter()
+                .any(|member| previous.collation(member) != table.collation(member));
+            if recollated || !previous.indexes.contains(index) {
+                change.added_indexes.push(index.name.clone());
+            }
+        }
+        for index in previous.indexes.iter() {
+            if !table.indexes.iter().any(|i| i.name == index.name) {
+                change.dropped_indexes.push(index.name.clone());
+            }
+        }
+
+        change.added_columns.sort();
+        change.dropped_columns.sort();
+        change.changed_columns.sort();
+        change
+    }
+
+    /// Why the change can't be applied to a table holding rows, `None` if it can.
+    pub fn incompatibility(&self) -> Option<String> {
+        if self.primary_key_changed {
+            return Some("its primary key changed".to_string());
+        }
+        if !self.changed_columns.is_empty() {
+            return Some(format!(
+                "the type of {} changed",
+                self.changed_columns.join(", ")
+            ));
+        }
+        None
+    }
+}
+
+/// File of the folder of a table holding its `TableSchema`.
+pub const SCHEMA_FILE: &str = "schema.json";
+
+/// Columns of a table as stored on disk, next to its shards.
+///
+/// Rows are stored as written, so rows written by previous versions of the table may miss columns added since
+/// (they are read with the default of the column) or hold columns dropped since (they are left out).
+///
+/// # Fields:
+/// - `version`: Starts at 1, bumped every time the columns of the table change.
+/// - `columns`: Type of every column, by column name.
+/// - `dropped`: Columns of previous versions that no longer exist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub version: u32,
+    pub columns: BTreeMap<String, DataTypes>,
+    #[serde(default)]
+    pub dropped: Vec<String>,
+}
+
+impl TableSchema {
+    fn columns_of(table: &Table) -> BTreeMap<String, DataTypes> {
+        table
+            .columns
+            .values()
+            .map(|column| (column.name.clone(), column.data_type.clone()))
+            .collect()
+    }
+
+    /// Schema stored in the folder `table_path`, `None` for tables stored before schemas were.
+    pub fn load(table_path: &Path) -> Option<Self> {
+        let data = std::fs::read(table_path.join(SCHEMA_FILE)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn save(&self, table_path: &Path) -> std::io::Result<()> {
+        std::fs::write(
+            table_path.join(SCHEMA_FILE),
+            serde_json::to_vec_pretty(self)?,
+        )
+    }
+
+    /// Schema of `table` following the `previous` one stored for it. The version is only bumped
+    /// when the columns changed.
+    pub fn evolve(previous: Option<Self>, table: &Table) -> Self {
+        let columns = Self::columns_of(table);
+
+        let previous = match previous {
+            Some(previous) => previous,
+            None => {
+                return Self {
+                    version: 1,
+                    columns,
+                    dropped: vec![],
+                }
+            }
+        };
+
+        if same(&previous.columns, &columns) {
+            return previous;
+        }
+
+        let mut dropped: Vec<String> = previous
+            .dropped
+            .into_iter()
+            .chain(previous.columns.into_keys())
+            .filter(|name| !columns.contains_key(name))
+            .collect();
+        dropped.sort();
+        dropped.dedup();
+
+        Self {
+            version: previous.version + 1,
+            columns,
+            dropped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{equals, test_db, user_row};
+    use crate::managers::single::schema::{SchemaChange, TableSchema};
+    use crate::managers::single::SingleQueryManager;
+    use crate::ops::query_ops::{QueryOps, QueryVal};
+    use crate::row_json::RowJson;
+    use crate::search::search_opts::SearchOpts;
+    use schemajs_index::index_type::IndexType;
+    use schemajs_primitives::column::collation::Collation;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+
+    #[test]
+    pub fn test_schema_change_between() {
+        let by_name = |index_type: IndexType| Index {
+            name: "user_name_indx".to_string(),
+            members: vec![String::from("user_name")],
+            index_type,
+            unique: false,
+        };
+        let users = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_index(by_name(IndexType::Hash));
+
+        assert_eq!(
+            SchemaChange::between(&users, &users),
+            SchemaChange::default()
+        );
+
+        let reloaded = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_index(by_name(IndexType::BTree));
+        let change = SchemaChange::between(&users, &reloaded);
+        assert_eq!(change.added_columns, vec!["user_country".to_string()]);
+        assert_eq!(change.dropped_columns, vec!["user_age".to_string()]);
+        // Indexes whose definition changed are built again
+        assert_eq!(change.added_indexes, vec!["user_name_indx".to_string()]);
+        assert!(change.incompatibility().is_none());
+
+        let retyped = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::Uuid))
+            .add_column(Column::new("user_age", DataTypes::Number));
+        let change = SchemaChange::between(&users, &retyped);
+        assert_eq!(change.changed_columns, vec!["user_name".to_string()]);
+        assert_eq!(change.dropped_indexes, vec!["user_name_indx".to_string()]);
+        assert!(change.incompatibility().is_some());
+
+        // Keys of indexes over a column whose collation changed are built again
+        let recollated = Table::new("users")
+            .add_column(
+                Column::new("user_name", DataTypes::String)
+                    .set_collation(Collation::CaseInsensitive),
+            )
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_index(by_name(IndexType::Hash));
+        let change = SchemaChange::between(&users, &recollated);
+        assert!(change.changed_columns.is_empty());
+        assert_eq!(change.added_indexes, vec!["user_name_indx".to_string()]);
+        assert!(change.incompatibility().is_none());
+    }
+
+    #[test]
+    pub fn test_table_schema_evolve() {
+        let users = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        let first = TableSchema::evolve(None, &users);
+        assert_eq!(first.version, 1);
+        assert_eq!(first.columns.len(), 3);
+
+        // Same columns, same version
+        let same = TableSchema::evolve(Some(first.clone()), &users);
+        assert_eq!(same.version, 1);
+
+        let users = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String));
+        let second = TableSchema::evolve(Some(first), &users);
+        assert_eq!(second.version, 2);
+        assert_eq!(second.dropped, vec!["user_age".to_string()]);
+
+        // Changing the type of a column is a new version too
+        let users = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::Uuid))
+            .add_column(Column::new("user_age", DataTypes::Int));
+        let third = TableSchema::evolve(Some(second), &users);
+        assert_eq!(third.version, 3);
+        // Columns added back are no longer dropped
+        assert!(third.dropped.is_empty());
+    }
+
+    /// Manager of `test_db` holding a `users` table with `user_name` and `user_age`, and a row for each of `users`.
+    fn aged_users(test_db: &str, users: &[(&str, i32)]) -> SingleQueryManager<RowJson> {
+        let query_manager = SingleQueryManager::new(test_db.to_string());
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Number)),
+            )
+            .unwrap();
+
+        for (name, age) in users {
+            query_manager
+                .insert(user_row(json!({ "user_name": name, "user_age": age })))
+                .unwrap();
+        }
+
+        query_manager
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_schema_evolution() {
+        let (test_db, db_folder) = test_db();
+
+        {
+            let query_manager = aged_users(&test_db, &[("andres", 25), ("carlos", 30)]);
+            let table = query_manager.tables.get("users").unwrap();
+            table.temps.reconcile_all();
+            assert_eq!(table.table.metadata.schema_version, 1);
+        }
+
+        // The table is opened again with a new column and without `user_age`
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+        let users = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String).set_default_value("US"));
+        query_manager.register_table(users).unwrap();
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        assert_eq!(table.metadata.schema_version, 2);
+        assert_eq!(table.metadata.dropped_columns, vec!["user_age".to_string()]);
+
+        let rows = query_manager
+            .search_manager()
+            .search("users".to_string(), &equals("user_country", "US"))
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let row = rows[0].to_json(&table);
+        assert_eq!(row["user_country"], "US");
+        assert!(row.get("user_age").is_none());
+
+        let partial = query_manager
+            .search_manager()
+            .search_partial(
+                "users".to_string(),
+                &QueryOps::And(vec![]),
+                &SearchOpts::new().select(&["user_country"]),
+            )
+            .unwrap();
+        assert_eq!(
+            partial[0].get_value("user_country"),
+            Some(&DataValue::String("US".to_string()))
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_reload_table() {
+        let (test_db, db_folder) = test_db();
+        let query_manager = aged_users(&test_db, &[("andres", 25), ("carlos", 30), ("luis", 40)]);
+
+        let by_age_indx = Index {
+            name: "user_age_indx".to_string(),
+            members: vec![String::from("user_age")],
+            index_type: IndexType::BTree,
+            unique: false,
+        };
+        let change = query_manager
+            .reload_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Number))
+                    .add_column(
+                        Column::new("user_country", DataTypes::String).set_default_value("US"),
+                    )
+                    .add_index(by_age_indx),
+            )
+            .unwrap();
+        assert_eq!(change.added_columns, vec!["user_country".to_string()]);
+        assert_eq!(change.added_indexes, vec!["user_age_indx".to_string()]);
+
+        // Rows written before the reload are indexed and read with the new columns
+        let older = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">".to_string(),
+            value: DataValue::Number(26.into()),
+        });
+        let aged_30 = equals("user_age", serde_json::Number::from(30));
+        let search_manager = query_manager.search_manager();
+        let plan = search_manager
+            .query_plan("users".to_string(), &aged_30)
+            .unwrap();
+        assert_eq!(plan.indexes(), vec!["user_age_indx".to_string()]);
+        assert_eq!(
+            search_manager
+                .search("users".to_string(), &aged_30)
+                .unwrap()
+                .len(),
+            1
+        );
+        let rows = search_manager.search("users".to_string(), &older).unwrap();
+        assert_eq!(rows.len(), 2);
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        assert_eq!(rows[0].to_json(&table)["user_country"], "US");
+
+        // Changing the type of a column is rejected
+        assert!(query_manager
+            .reload_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::String)),
+            )
+            .err()
+            .unwrap()
+            .is_incompatible_schema());
+        assert!(query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .table
+            .get_column("user_country")
+            .is_some());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+use schemajs_index::index_type::IndexType;
+use serde::Serialize;
+
+/// Size of an index of a table.
+///
+/// # Fields:
+/// - `name`: Name of the index.
+/// - `index_type`: Kind of index (`Hash`, `BTree` or `Geo`).
+/// - `entries`: Entries held by the index, including the ones of dead rows until the table is compacted.
+/// - `size`: Size in bytes of the files of the index.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableIndexStats {
+    pub name: String,
+    pub index_type: IndexType,
+    pub entries: u64,
+    pub size: u64,
+}
+
+/// Statistics of a table, see `TableShard::stats`.
+///
+/// # Fields:
+/// - `table`: Name of the table.
+/// - `rows`: Rows of the main shard, dead ones included.
+/// - `dead_rows`: Rows of the main shard that are deleted, expired or previous versions, until compacted.
+/// - `pending_rows`: Rows waiting in temporary shards to be reconciled into the main shard.
+/// - `pending_bytes`: Bytes of the rows inserted in temporary shards since they were last emptied.
+/// - `data_size`: Size in bytes of the shard files of the main shard.
+/// - `temps_size`: Size in bytes of the temporary shards and their write-ahead logs.
+/// - `indexes`: Size of every index of the table.
+/// - `last_reconcile`: When rows were last moved from temporary shards to the main shard, in milliseconds since
+///   the Unix epoch. `None` if they weren't since the table was opened.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableStats {
+    pub table: String,
+    pub rows: u64,
+    pub dead_rows: u64,
+    pub pending_rows: u64,
+    pub pending_bytes: u64,
+    pub data_size: u64,
+    pub temps_size: u64,
+    pub indexes: Vec<TableIndexStats>,
+    pub last_reconcile: Option<u64>,
+}
+
+impl TableStats {
+    /// Size in bytes of every file of the table.
+    pub fn size(&self) -> u64 {
+        self.data_size + self.temps_size + self.indexes.iter().map(|index| index.size).sum::<u64>()
+    }
+}
+
+/// Statistics of a database, see `SingleQueryManager::stats`.
+///
+/// # Fields:
+/// - `name`: Name of the database.
+/// - `tables`: Statistics of every table, sorted by name.
+/// - `size`: Size in bytes of the files of every table.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseStats {
+    pub name: String,
+    pub tables: Vec<TableStats>,
+    pub size: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{equals, test_db, user};
+    use crate::managers::single::SingleQueryManager;
+    use crate::row_json::RowJson;
+    use schemajs_index::index_type::IndexType;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
+    use schemajs_primitives::table::Table;
+
+    #[flaky_test::flaky_test]
+    pub fn test_database_stats() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_index(Index {
+                        name: "nameindx".to_string(),
+                        members: vec![String::from("user_name")],
+                        index_type: IndexType::Hash,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+        query_manager
+            .register_table(
+                Table::new("products").add_column(Column::new("product_name", DataTypes::String)),
+            )
+            .unwrap();
+
+        for name in ["andres", "luis", "carlos"] {
+            query_manager.insert(user(name)).unwrap();
+        }
+
+        // Rows are still in temporary shards
+        let stats = query_manager.stats();
+        assert_eq!(stats.name, test_db);
+        let table_names: Vec<&str> = stats.tables.iter().map(|t| t.table.as_str()).collect();
+        assert_eq!(table_names, vec!["products", "users"]);
+        let users = &stats.tables[1];
+        assert_eq!(users.rows, 0);
+        assert_eq!(users.pending_rows, 3);
+        assert!(users.pending_bytes > 0);
+        assert!(users.temps_size > 0);
+        assert_eq!(users.last_reconcile, None);
+        assert_eq!(users.indexes[0].entries, 0);
+
+        // Deleting reconciles the rows first
+        assert_eq!(
+            query_manager
+                .delete("users".to_string(), &equals("user_name", "luis"))
+                .unwrap(),
+            1
+        );
+
+        let stats = query_manager.stats();
+        let users = &stats.tables[1];
+        assert_eq!(users.rows, 3);
+        assert_eq!(users.dead_rows, 1);
+        assert_eq!(users.pending_rows, 0);
+        assert_eq!(users.pending_bytes, 0);
+        assert!(users.data_size > 0);
+        assert!(users.last_reconcile.is_some());
+        assert_eq!(users.indexes.len(), 1);
+        assert_eq!(users.indexes[0].name, "nameindx");
+        assert_eq!(users.indexes[0].index_type, IndexType::Hash);
+        assert_eq!(users.indexes[0].entries, 3);
+        assert!(users.indexes[0].size > 0);
+        assert_eq!(stats.tables[0].rows, 0);
+        assert_eq!(
+            stats.size,
+            stats.tables.iter().map(|table| table.size()).sum::<u64>()
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Replacement of the folder of a table with another folder (e.g. its compacted files or a backup of it).
+///
+/// A folder can't be replaced with a single rename, so the swap is written to a marker file next to the folder
+/// before anything is moved and the marker is only removed once the previous folder is gone. A crash in between
+/// leaves the marker behind, and `recover` finishes the swap the next time the table is opened: the replacement
+/// is complete before the swap starts, so it's always moved in place.
+///
+/// # Fields:
+/// - `path`: Folder of the table.
+/// - `previous`: Where the current folder of the table is moved to, removed by `finish`.
+/// - `replacement`: Folder taking the place of the current one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableSwap {
+    pub path: PathBuf,
+    pub previous: PathBuf,
+    pub replacement: PathBuf,
+}
+
+impl TableSwap {
+    /// Moves `replacement` in place of the folder `path`, keeping the current folder aside until `finish`.
+    /// The files of the current folder stay readable by the handles already open on them.
+    pub fn start(path: &Path, replacement: &Path) -> std::io::Result<Self> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let swap = Self {
+            path: path.to_path_buf(),
+            previous: path.with_file_name(format!(".{}-{}", name, Uuid::new_v4())),
+            replacement: replacement.to_path_buf(),
+        };
+
+        swap.save()?;
+        swap.resume()?;
+
+        Ok(swap)
+    }
+
+    /// Removes the previous folder of the table and the marker of the swap.
+    pub fn finish(self) -> std::io::Result<()> {
+        if self.previous.exists() {
+            std::fs::remove_dir_all(&self.previous)?;
+        }
+
+        std::fs::remove_file(Self::marker(&self.path))
+    }
+
+    /// Finishes the swap of the folder `path` interrupted by a crash, if any.
+    pub fn recover(path: &Path) -> std::io::Result<()> {
+        let marker = Self::marker(path);
+        if !marker.exists() {
+            return Ok(());
+        }
+
+        let swap: Self = serde_json::from_slice(&std::fs::read(&marker)?)?;
+        tracing::warn!(path = %path.display(), "Finishing an interrupted swap of the table folder");
+        swap.resume()?;
+        swap.finish()
+    }
+
+    /// Does the renames of the swap not done yet.
+    fn resume(&self) -> std::io::Result<()> {
+        if !self.previous.exists() && self.path.exists() {
+            std::fs::rename(&self.path, &self.previous)?;
+        }
+
+        if self.replacement.exists() && !self.path.exists() {
+            std::fs::rename(&self.replacement, &self.path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the marker of the swap, replacing it in a single rename so it's never read half written.
+    fn save(&self) -> std::io::Result<()> {
+        let marker = Self::marker(&self.path);
+        let written = marker.with_extension("tmp");
+
+        let mut file = File::create(&written)?;
+        file.write_all(&serde_json::to_vec(self)?)?;
+        file.sync_all()?;
+
+        std::fs::rename(written, marker)
+    }
+
+    fn marker(path: &Path) -> PathBuf {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!(".{}-swap.json", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::swap::TableSwap;
+    use uuid::Uuid;
+
+    #[test]
+    pub fn test_table_swap_recover() {
+        let folder = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let path = folder.join("users");
+        let replacement = folder.join(".users-compact");
+
+        let reset = || {
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("data"), b"old").unwrap();
+            std::fs::create_dir_all(&replacement).unwrap();
+            std::fs::write(replacement.join("data"), b"new").unwrap();
+        };
+
+        reset();
+        TableSwap::start(&path, &replacement)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(std::fs::read(path.join("data")).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&folder).unwrap().count(), 1);
+
+        // Crashing right after the current folder was moved aside
+        std::fs::remove_dir_all(&path).unwrap();
+        reset();
+        TableSwap::start(&path, &replacement).unwrap();
+        std::fs::rename(&path, &replacement).unwrap();
+        assert!(!path.exists());
+
+        TableSwap::recover(&path).unwrap();
+        assert_eq!(std::fs::read(path.join("data")).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&folder).unwrap().count(), 1);
+
+        // Nothing to do without a marker
+        TableSwap::recover(&path).unwrap();
+        assert_eq!(std::fs::read(path.join("data")).unwrap(), b"new");
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
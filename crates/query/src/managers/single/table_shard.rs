@@ -1,22 +1,51 @@
+use crate::errors::QueryError;
+use crate::managers::single::integrity::{IntegrityIssue, IntegrityReport};
+use crate::managers::single::schema::TableSchema;
+use crate::managers::single::stats::{TableIndexStats, TableStats};
+use crate::managers::single::swap::TableSwap;
+use crate::ops::check::parse_check;
+use crate::ops::geo::{GeoPoint, GEOHASH_PRECISION};
 use crate::row::Row;
+use crate::search::index_stats::IndexStats;
+use crate::serializer::compact::{CompactLayout, LAYOUTS_FILE};
+use crate::serializer::registry::{RegisteredSerializer, SerializerRegistry};
 use chashmap::CHashMap;
+use schemajs_data::bloom::ShardBlooms;
+use schemajs_data::encryption::EncryptionKey;
+use schemajs_data::errors::ShardErrors;
 use schemajs_data::shard::map_shard::MapShard;
-use schemajs_data::shard::shards::data_shard::config::{DataShardConfig, TempDataShardConfig};
+use schemajs_data::shard::shards::data_shard::config::{
+    DataShardConfig, RowEncoding, TableStorage, TempDataShardConfig,
+};
 use schemajs_data::shard::shards::data_shard::shard::DataShard;
 use schemajs_data::shard::temp_collection::TempCollection;
 use schemajs_data::shard::temp_map_shard::DataWithIndex;
-use schemajs_dirs::create_schema_js_table;
+use schemajs_data::shard::Shard;
+use schemajs_data::snapshot::{Snapshot, SnapshotClock, SnapshotGuard};
+use schemajs_data::tombstones::Tombstones;
+use schemajs_data::utils::fs::{folder_size, list_files_with_prefix};
+use schemajs_dirs::{create_schema_js_table, create_scheme_js_db};
 use schemajs_index::composite_key::CompositeKey;
+use schemajs_index::implementations::btree::btree_index::BTreeIndex;
 use schemajs_index::implementations::hash::hash_index::HashIndex;
 use schemajs_index::index_keys::IndexKeyType;
 use schemajs_index::index_type::{IndexType, IndexTypeValue};
 use schemajs_index::types::{Index, IndexKey};
 use schemajs_primitives::column::types::DataValue;
+use schemajs_primitives::column::Column;
+use schemajs_primitives::index::Index as TableIndex;
 use schemajs_primitives::table::Table;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
-use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::UNIX_EPOCH;
+
+/// Rows the bloom filter of a shard is sized for when shards have no limit.
+const DEFAULT_BLOOM_CAPACITY: usize = 1_000_000;
+
+/// Rows written at once by `compact_into`.
+const COMPACTION_BATCH_SIZE: usize = 10_000;
 
 /// `TableShard` is a structure that manages the sharding of a specific table's data.
 /// It is responsible for storing the table's data in a main shard, handling temporary shards
@@ -32,6 +61,24 @@ use std::sync::{Arc, RwLock};
 ///   before it is reconciled into the main shard. Temporary shards allow for faster writes and efficient sharding operations.
 /// - `indexes`: An `Arc<CHashMap<String, IndexTypeValue>>` that contains the table's indexes, stored in a thread-safe concurrent hash map.
 ///   The key is the index name, and the value is an `IndexTypeValue`, which holds the actual index structure.
+/// - `tombstones`: Positions of the main shard holding rows that are no longer alive (e.g. previous versions of updated rows).
+///   Searches skip these positions.
+/// - `upsert_lock`: Serializes upserts on the table, so two upserts on the same key cannot both insert a row.
+/// - `clock`: Publishes the writes done to the main shard. Searches only see the rows of the snapshot they start with.
+/// - `previous_versions`: Position of the previous version of every row written by an update, keyed by the position of
+///   the new version. Lets searches running on an older snapshot find the version of the row they are allowed to see.
+///   Entries below the `horizon` of the clock are dropped, no snapshot in use can follow them.
+/// - `pending_unique_keys`: Keys of unique indexes held by rows that are not indexed yet (e.g. still in temporary shards),
+///   by index name. Inserts check them along with the indexes to reject duplicates.
+/// - `path`: Folder holding the files of the table (main shard, temporary shards, indexes).
+/// - `encryption`: Key encrypting the files of the table (main shard, temporary shards, indexes), if they are encrypted.
+/// - `blooms`: Bloom filters of the shards of the main shard over the values of the indexed columns (see `bloom_key`).
+///   Scans looking for a value skip the shards that can't hold it.
+/// - `index_stats`: Statistics of every index, by index name (see `refresh_index_stats`). The planner uses them to pick
+///   the cheapest index for a query.
+/// - `layout`: Columns new rows are encoded with when the table uses `RowEncoding::Compact`, `None` for JSON rows.
+/// - `serializer`: Serializer of the registry new rows are written with, if the table references one
+///   (see `Table::set_serializer`). It takes precedence over the encoding of the storage settings.
 ///
 /// - `_marker`: A `PhantomData<T>` used to indicate the generic type `T` in the struct.
 ///   It is a marker used to tell the Rust compiler that this struct works with a specific row type,
@@ -42,6 +89,17 @@ pub struct TableShard<T: Row<T>> {
     pub data: Arc<RwLock<MapShard<DataShard, DataShardConfig>>>,
     pub temps: TempCollection<DataShard, DataShardConfig, TempDataShardConfig>,
     pub indexes: Arc<CHashMap<String, IndexTypeValue>>,
+    pub tombstones: Arc<Tombstones>,
+    pub upsert_lock: Mutex<()>,
+    pub clock: Arc<SnapshotClock>,
+    pub previous_versions: Arc<RwLock<BTreeMap<u64, u64>>>,
+    pub pending_unique_keys: Arc<Mutex<HashMap<String, HashSet<CompositeKey>>>>,
+    pub path: PathBuf,
+    pub encryption: Option<EncryptionKey>,
+    pub blooms: Arc<ShardBlooms>,
+    pub index_stats: RwLock<HashMap<String, IndexStats>>,
+    pub layout: Option<Arc<CompactLayout>>,
+    pub serializer: Option<RegisteredSerializer>,
     _marker: PhantomData<T>,
 }
 
@@ -54,25 +112,82 @@ impl<T: Row<T>> TableShard<T> {
     /// - `base_path`: An optional base path for the table files. If not provided, a default path will be used.
     /// - `scheme`: The database schema that organizes how the table's data and indexes are structured.
     /// - `temp_config`: Configuration for the temporary shard that handles data before being reconciled with the main shard.
+    /// - `storage`: Compression of the rows of the main shard, applied as they are reconciled into it, size
+    ///   its shard files can reach before rows go to a new one, encoding of the rows, and flush policy
+    ///   of the temporary shards.
+    /// - `encryption`: Key encrypting the main shard and the indexes. Temporary shards use the one of `temp_config`.
     ///
     /// # Returns:
-    /// - A `TableShard` instance that handles data storage, sharding, and indexing for the provided table.
+    /// - `Result<TableShard, QueryError>`: A `TableShard` instance that handles data storage, sharding, and indexing
+    ///   for the provided table. Fails before anything is written if the table references a serializer that is not
+    ///   registered or an index covering an unknown column.
     pub fn new(
-        table: Table,
+        mut table: Table,
         base_path: Option<PathBuf>,
         scheme: &str,
         temp_config: TempDataShardConfig,
-    ) -> Self {
+        storage: TableStorage,
+        encryption: Option<EncryptionKey>,
+    ) -> Result<Self, QueryError> {
+        let serializer = match &table.serializer {
+            Some(name) => Some(
+                SerializerRegistry::global()
+                    .get(name)
+                    .ok_or_else(|| QueryError::InvalidSerializer(name.clone()))?,
+            ),
+            None => None,
+        };
+        for index in &table.indexes {
+            if let Some(member) = index
+                .members
+                .iter()
+                .find(|member| table.resolve_column(member).is_none())
+            {
+                return Err(QueryError::InvalidIndexMember(
+                    index.name.clone(),
+                    member.clone(),
+                ));
+            }
+        }
+
+        // A crash while the folder of the table was swapped (e.g. by a compaction) leaves it half moved
+        let db_path = create_scheme_js_db(base_path.clone(), scheme);
+        TableSwap::recover(&db_path.join(&table.name)).map_err(|_| ShardErrors::FlushingError)?;
         let table_path = create_schema_js_table(base_path, scheme, table.name.as_str());
 
+        // Rows written by previous versions of the table are read with the columns of this one
+        let schema = TableSchema::evolve(TableSchema::load(&table_path), &table);
+        schema
+            .save(&table_path)
+            .map_err(|_| ShardErrors::FlushingError)?;
+        table.metadata.schema_version = schema.version;
+        table.metadata.dropped_columns = schema.dropped;
+
+        // Layouts are loaded even for JSON tables, as they may hold rows written while they were compact
+        let layout = match storage.encoding {
+            RowEncoding::Compact => Some(
+                CompactLayout::open(&table_path, &table)
+                    .map_err(|_| ShardErrors::ErrorReadingByteRange)?,
+            ),
+            RowEncoding::Json => {
+                CompactLayout::load(&table_path).map_err(|_| ShardErrors::ErrorReadingByteRange)?;
+                None
+            }
+        };
+
         let map_shard = MapShard::new(
             table_path.clone(),
             "data_",
             DataShardConfig {
                 max_offsets: Some(2_500_000),
+                compression: storage.compression,
+                encryption: encryption.clone(),
+                max_size: storage.max_shard_size,
             },
         );
 
+        let clock = SnapshotClock::new(map_shard.len() as u64);
+        let blooms = Self::new_blooms(&table, map_shard.shard_size());
         let refs = Arc::new(RwLock::new(map_shard));
 
         let temps_folder = table_path.join("temps");
@@ -81,27 +196,19 @@ impl<T: Row<T>> TableShard<T> {
             std::fs::create_dir_all(temps_folder.clone()).unwrap();
         }
 
-        let temp_collection =
+        let mut temp_collection =
             TempCollection::new(refs.clone(), 5, temps_folder, "temp_", temp_config);
+        temp_collection.set_flush_policy(Some(storage.flush));
+
+        let tombstones = Tombstones::new(table_path.join("tombstones"));
 
         let mut indexes = CHashMap::new();
 
         for index in &table.indexes {
-            let path = table_path.join("indx");
-
-            if !path.exists() {
-                std::fs::create_dir(path.clone()).unwrap();
-            }
-
-            let index_obj = match index.index_type {
-                IndexType::Hash => IndexTypeValue::Hash(HashIndex::new_from_path(
-                    path,
-                    Some(format!("{}", index.name)),
-                    Some(10_000_000),
-                )),
-            };
-
-            indexes.insert(index.name.clone(), index_obj);
+            indexes.insert(
+                index.name.clone(),
+                Self::open_index(&table_path, index, encryption.clone()),
+            );
         }
 
         let mut tbl_shard = Self {
@@ -109,12 +216,28 @@ impl<T: Row<T>> TableShard<T> {
             data: refs.clone(),
             table: Arc::new(table),
             temps: temp_collection,
+            tombstones: Arc::new(tombstones),
+            upsert_lock: Mutex::new(()),
+            clock: Arc::new(clock),
+            previous_versions: Arc::new(RwLock::new(BTreeMap::new())),
+            pending_unique_keys: Arc::new(Mutex::new(HashMap::new())),
+            path: table_path,
+            encryption,
+            blooms: Arc::new(blooms),
+            index_stats: RwLock::new(HashMap::new()),
+            layout,
+            serializer,
             _marker: PhantomData,
         };
 
+        tbl_shard.fill_blooms();
         tbl_shard.init();
 
-        tbl_shard
+        // Rows left in temporary shards by a previous run are restored through the reconciliation callbacks
+        tbl_shard.temps.recover()?;
+        tbl_shard.refresh_index_stats();
+
+        Ok(tbl_shard)
     }
 
     /// Initializes everything related to the current table context.
@@ -126,48 +249,945 @@ impl<T: Row<T>> TableShard<T> {
 
         for temp_shard in self.temps.temps.iter() {
             let indexes = indexes.clone();
+            let blooms = self.blooms.clone();
             let table = self.table.clone();
+            let clock = self.clock.clone();
+            let pending_unique_keys = self.pending_unique_keys.clone();
 
             temp_shard
                 .write()
                 .unwrap()
                 .set_on_reconcile(Box::new(move |rows| {
-                    Self::insert_indexes(table.clone(), indexes.clone(), rows);
+                    // Reconciled rows only become visible once they are indexed
+                    let len = rows.iter().map(|row| row.index + 1).max();
+                    let unique_keys: Vec<(String, CompositeKey)> =
+                        if table.indexes.iter().any(|index| index.unique) {
+                            rows.iter()
+                                .flat_map(|row| Self::unique_keys(&table, &T::from(&row.data)))
+                                .collect()
+                        } else {
+                            vec![]
+                        };
+                    Self::insert_indexes(table.clone(), indexes.clone(), blooms.clone(), rows);
+                    if let Some(len) = len {
+                        clock.publish(len);
+                    }
+                    Self::release_unique_keys(&pending_unique_keys, &unique_keys);
                     Ok(())
                 }))
         }
     }
 
+    /// Opens the files of `index` in the `indx` folder of `table_path`, creating them if they don't exist.
+    pub fn open_index(
+        table_path: &Path,
+        index: &TableIndex,
+        encryption: Option<EncryptionKey>,
+    ) -> IndexTypeValue {
+        let path = table_path.join("indx");
+
+        if !path.exists() {
+            std::fs::create_dir(path.clone()).unwrap();
+        }
+
+        match index.index_type {
+            IndexType::Hash | IndexType::MultiEntry => {
+                IndexTypeValue::Hash(HashIndex::new_from_path(
+                    path,
+                    Some(format!("{}", index.name)),
+                    Some(10_000_000),
+                    encryption,
+                ))
+            }
+            IndexType::BTree | IndexType::Geo => IndexTypeValue::BTree(BTreeIndex::new_from_path(
+                path,
+                Some(format!("{}", index.name)),
+                None,
+                encryption,
+            )),
+        }
+    }
+
+    /// Keys held by the live rows of the main shard from position `from` up to `to` for `index`,
+    /// along with the position of their row (see `composite_keys`). Rows whose key is entirely null are left out.
+    ///
+    /// # Parameters:
+    /// - `unique_keys`: Keys already collected by previous calls, along with the position of their row.
+    ///   Only tracked for unique indexes, fails with `UniqueViolation` when two live rows hold the same key.
+    ///   Rows collected earlier that died since (e.g. updated while an index is built) give their key up.
+    pub fn index_entries(
+        &self,
+        index: &TableIndex,
+        from: u64,
+        to: u64,
+        unique_keys: &mut HashMap<CompositeKey, u64>,
+    ) -> Result<Vec<(CompositeKey, u64)>, QueryError> {
+        let mut entries = vec![];
+
+        for pointer in from..to {
+            if self.tombstones.contains(pointer) {
+                continue;
+            }
+
+            let data = self.data.read().unwrap().get_element(pointer as usize)?;
+            let row = T::from(&data);
+
+            for key in Self::composite_keys(&self.table, index, &row) {
+                if index.unique {
+                    if let Some(holder) = unique_keys.insert(key.clone(), pointer) {
+                        if !self.tombstones.contains(holder) {
+                            return Err(QueryError::UniqueViolation(
+                                index.name.clone(),
+                                Self::key_value(&key),
+                            ));
+                        }
+                    }
+                }
+
+                entries.push((key, pointer));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Indexes the live rows of the main shard from position `from` up to `to` into `index_obj`,
+    /// an index built after the definition `index` that is not part of the table yet.
+    /// See `index_entries` for `unique_keys`.
+    pub fn backfill_index(
+        &self,
+        index: &TableIndex,
+        index_obj: &IndexTypeValue,
+        from: u64,
+        to: u64,
+        unique_keys: &mut HashMap<CompositeKey, u64>,
+    ) -> Result<(), QueryError> {
+        let indx = index_obj.as_index();
+        let entries = self.index_entries(index, from, to, unique_keys)?;
+
+        indx.bulk_insert(
+            entries
+                .into_iter()
+                .map(|(key, pointer)| (indx.to_key(key), pointer))
+                .collect(),
+        );
+
+        Ok(())
+    }
+
+    /// Drops the index named `index_name` and builds it again from the rows of the table, e.g. when its files
+    /// are corrupted. Rows still in temporary shards are reconciled first.
+    ///
+    /// The previous index is kept if the rows can't be indexed (`UniqueViolation` for unique indexes).
+    /// Fails with `InvalidIndex` if the table has no index named `index_name`.
+    pub fn rebuild_index(&mut self, index_name: &str) -> Result<(), QueryError> {
+        let index = self
+            .table
+            .indexes
+            .iter()
+            .find(|index| index.name == index_name)
+            .cloned()
+            .ok_or_else(|| QueryError::InvalidIndex(index_name.to_string()))?;
+
+        self.temps.reconcile_all();
+        let len = self.data.read().unwrap().len() as u64;
+        let entries = self.index_entries(&index, 0, len, &mut HashMap::new())?;
+
+        // Files can only be removed once nothing holds them open
+        self.indexes.remove(&index.name);
+        self.remove_index_files(&index.name);
+
+        let index_obj = Self::open_index(&self.path, &index, self.encryption.clone());
+        {
+            let indx = index_obj.as_index();
+            indx.bulk_insert(
+                entries
+                    .into_iter()
+                    .map(|(key, pointer)| (indx.to_key(key), pointer))
+                    .collect(),
+            );
+        }
+        self.indexes.insert(index.name.clone(), index_obj);
+
+        Ok(())
+    }
+
+    /// Reads every row of the main shard back, returning the position of the rows that are corrupted
+    /// (their checksum doesn't match). Rows still in temporary shards are reconciled first.
+    pub fn verify(&self) -> Vec<u64> {
+        self.temps.reconcile_all();
+        self.data.read().unwrap().verify()
+    }
+
+    /// Checks the files of the table: the framing and checksums of every row of the main shard, and every entry of
+    /// its indexes against the row it points to (dangling pointers, keys not matching their row and rows missing
+    /// from an index). Rows still in temporary shards are reconciled first, writes wait until the check is done.
+    ///
+    /// Entries pointing to dead rows (e.g. deleted ones) are expected until the table is compacted, they only
+    /// have to match the row they point to.
+    pub fn check_integrity(&self) -> IntegrityReport {
+        self.temps.reconcile_all();
+
+        let data = self.data.read().unwrap();
+        let len = data.len() as u64;
+        let mut report = IntegrityReport {
+            table: self.table.name.clone(),
+            rows: len,
+            ..Default::default()
+        };
+
+        let bad_framing: HashSet<u64> = data.verify_framing().into_iter().collect();
+        let mut unreadable = HashSet::new();
+        for position in 0..len {
+            if bad_framing.contains(&position) {
+                report.issues.push(IntegrityIssue::BadFraming { position });
+                unreadable.insert(position);
+            } else if data.get_element(position as usize).is_err() {
+                report
+                    .issues
+                    .push(IntegrityIssue::CorruptedRow { position });
+                unreadable.insert(position);
+            }
+        }
+
+        let row = |position: u64| match unreadable.contains(&position) {
+            true => None,
+            false => data
+                .get_element(position as usize)
+                .ok()
+                .map(|item| T::from(&item)),
+        };
+
+        for index in &self.table.indexes {
+            let index_obj = match self.indexes.get(&index.name) {
+                Some(index_obj) => index_obj,
+                None => continue,
+            };
+            let indx = index_obj.as_index();
+            let entries = indx.entries();
+            report.index_entries += entries.len() as u64;
+
+            // Entries matching their row, by position
+            let mut indexed: HashMap<u64, usize> = HashMap::new();
+            for (key, position) in entries {
+                if position >= len {
+                    report.issues.push(IntegrityIssue::DanglingPointer {
+                        index: index.name.clone(),
+                        position,
+                    });
+                    continue;
+                }
+
+                // Unreadable rows are already reported
+                let row = match row(position) {
+                    Some(row) => row,
+                    None => continue,
+                };
+
+                let row_keys = Self::composite_keys(&self.table, index, &row);
+                match row_keys
+                    .into_iter()
+                    .any(|row_key| indx.to_key(row_key) == key)
+                {
+                    true => {
+                        *indexed.entry(position).or_default() += 1;
+                    }
+                    false => report.issues.push(IntegrityIssue::MismatchedKey {
+                        index: index.name.clone(),
+                        position,
+                    }),
+                }
+            }
+
+            for position in 0..len {
+                if self.tombstones.contains(position) {
+                    continue;
+                }
+
+                let keys = row(position).map_or(0, |row| {
+                    Self::composite_keys(&self.table, index, &row).len()
+                });
+                if indexed.get(&position).copied().unwrap_or_default() < keys {
+                    report.issues.push(IntegrityIssue::MissingEntry {
+                        index: index.name.clone(),
+                        position,
+                    });
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Row counts, file sizes and backlog of the table. Rows still in temporary shards are left there, they are
+    /// reported as `pending_rows`.
+    pub fn stats(&self) -> TableStats {
+        let (rows, data_size) = {
+            let data = self.data.read().unwrap();
+            (data.len() as u64, data.file_size())
+        };
+
+        let indexes = self
+            .table
+            .indexes
+            .iter()
+            .filter_map(|index| {
+                let index_obj = self.indexes.get(&index.name)?;
+                let indx = index_obj.as_index();
+                Some(TableIndexStats {
+                    name: index.name.clone(),
+                    index_type: index.index_type.clone(),
+                    entries: indx.entry_count(),
+                    size: indx.file_size(),
+                })
+            })
+            .collect();
+
+        TableStats {
+            table: self.table.name.clone(),
+            rows,
+            dead_rows: self.tombstones.len() as u64,
+            pending_rows: self.temps.pending_rows(),
+            pending_bytes: self.temps.pending_bytes(),
+            data_size,
+            temps_size: folder_size(self.path.join("temps")).unwrap_or_default(),
+            indexes,
+            last_reconcile: self
+                .temps
+                .last_reconcile()
+                .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                .map(|elapsed| elapsed.as_millis() as u64),
+        }
+    }
+
+    /// Reconciles the rows still in temporary shards and waits until the main shard, its indexes and its tombstones
+    /// are on disk, so nothing written so far is lost once the table is closed.
+    pub fn sync(&self) -> Result<(), QueryError> {
+        self.temps.reconcile_all();
+        self.data.read().unwrap().sync()?;
+
+        for index in &self.table.indexes {
+            if let Some(index_obj) = self.indexes.get(&index.name) {
+                index_obj.as_index().sync()?;
+            }
+        }
+
+        self.tombstones.sync()?;
+
+        Ok(())
+    }
+
+    /// Writes the rows of the main shard that are still alive (not tombstoned) into a new main shard in the
+    /// folder `dest`, keeping their order. Rows must be reconciled beforehand, writes must wait until it is done.
+    ///
+    /// # Returns:
+    /// - `Result<usize, QueryError>`: The amount of rows written to `dest`.
+    pub fn compact_into(&self, dest: &Path) -> Result<usize, QueryError> {
+        let data = self.data.read().unwrap();
+        std::fs::create_dir_all(dest).map_err(|_| ShardErrors::FlushingError)?;
+
+        let mut compacted =
+            MapShard::<DataShard, DataShardConfig>::new(dest, "data_", data.config().clone());
+        let mut batch = Vec::with_capacity(COMPACTION_BATCH_SIZE);
+        let mut written = 0;
+
+        for position in 0..data.len() {
+            if self.tombstones.contains(position as u64) {
+                continue;
+            }
+
+            batch.push(data.get_element(position)?);
+
+            if batch.len() == COMPACTION_BATCH_SIZE {
+                written += Self::write_batch(&mut compacted, &mut batch);
+            }
+        }
+        written += Self::write_batch(&mut compacted, &mut batch);
+
+        // Compact rows are decoded with the layout they were written with
+        let layouts = self.path.join(LAYOUTS_FILE);
+        if layouts.exists() {
+            std::fs::copy(layouts, dest.join(LAYOUTS_FILE))
+                .map_err(|_| ShardErrors::FlushingError)?;
+        }
+
+        Ok(written)
+    }
+
+    fn write_batch(
+        shard: &mut MapShard<DataShard, DataShardConfig>,
+        batch: &mut Vec<Vec<u8>>,
+    ) -> usize {
+        if !batch.is_empty() {
+            let items: Vec<&[u8]> = batch.iter().map(|item| item.as_slice()).collect();
+            shard.insert_rows(&items);
+        }
+
+        std::mem::take(batch).len()
+    }
+
+    /// Copies the files of the table (main shard, tombstones and indexes) into `dest` as they are now.
+    ///
+    /// Shards of the main shard other than the current one are full and never written again, so they are
+    /// hard-linked (copied if `dest` is on another file system), which keeps snapshots cheap on large tables.
+    /// Everything else is still written in place, so it is copied. Temporary shards are left out:
+    /// rows must be reconciled beforehand, writes must wait until the copy is done.
+    pub fn snapshot(&self, dest: &Path) -> std::io::Result<()> {
+        let data = self.data.read().unwrap();
+        let full_shards: HashSet<PathBuf> = data
+            .past_master_shards
+            .read()
+            .unwrap()
+            .values()
+            .map(|shard| shard.get_path())
+            .collect();
+
+        Self::snapshot_folder(&self.path, dest, &full_shards)
+    }
+
+    fn snapshot_folder(
+        src: &Path,
+        dest: &Path,
+        full_shards: &HashSet<PathBuf>,
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(dest)?;
+
+        for entry in std::fs::read_dir(src)? {
+            let path = entry?.path();
+            let target = dest.join(path.file_name().unwrap());
+
+            if path.is_dir() {
+                match path.file_name().unwrap() == "temps" {
+                    true => std::fs::create_dir_all(target)?,
+                    false => Self::snapshot_folder(&path, &target, full_shards)?,
+                }
+            } else if full_shards.contains(&path) {
+                std::fs::hard_link(&path, &target)
+                    .or_else(|_| std::fs::copy(&path, &target).map(|_| ()))?;
+            } else {
+                std::fs::copy(&path, &target)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the shards holding the index named `index_name` from the `indx` folder of the table.
+    fn remove_index_files(&self, index_name: &str) {
+        let prefix = format!("indx{}_", index_name);
+        let files = list_files_with_prefix(self.path.join("indx"), &prefix).unwrap_or_default();
+
+        for file in files {
+            // Shards are named `{prefix}{uuid}_{number}`, skips the ones of indexes whose name starts the same
+            let is_index_shard = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name[prefix.len()..].split('_').count() == 2);
+
+            if is_index_shard {
+                std::fs::remove_file(file).unwrap();
+            }
+        }
+    }
+
+    /// Adds `index` to the table along with `index_obj`, its already filled index.
+    /// Searches use it from then on and reconciled rows are indexed into it.
+    /// Fails with `DuplicateIndex` if the table already has an index with the same name.
+    pub fn publish_index(
+        &mut self,
+        index: TableIndex,
+        index_obj: IndexTypeValue,
+    ) -> Result<(), QueryError> {
+        if self.table.indexes.iter().any(|i| i.name == index.name) {
+            return Err(QueryError::DuplicateIndex(index.name));
+        }
+
+        let tracks_members = {
+            let bloom_columns = Self::bloom_columns(&self.table);
+            index
+                .members
+                .iter()
+                .all(|member| bloom_columns.contains(member))
+        };
+
+        self.indexes.insert(index.name.clone(), index_obj);
+        self.table = Arc::new((*self.table).clone().add_index(index));
+
+        if !tracks_members {
+            self.blooms = Arc::new(Self::new_blooms(
+                &self.table,
+                self.data.read().unwrap().shard_size(),
+            ));
+            self.fill_blooms();
+        }
+
+        // The reconciliation callbacks hold the previous table and blooms
+        self.init();
+        self.refresh_index_stats();
+
+        Ok(())
+    }
+
+    /// Computes the statistics of every index of the table from the live rows of the main shard,
+    /// replacing the previous ones. Rows still in temporary shards are not counted.
+    pub fn refresh_index_stats(&self) {
+        let mut stats: HashMap<String, (u64, HashSet<CompositeKey>)> = HashMap::new();
+
+        {
+            let data = self.data.read().unwrap();
+
+            for pointer in 0..data.len() {
+                if self.tombstones.contains(pointer as u64) {
+                    continue;
+                }
+
+                let row = match data.get_element(pointer) {
+                    Ok(item) => T::from(&item),
+                    Err(_) => continue,
+                };
+
+                for index in &self.table.indexes {
+                    for key in Self::composite_keys(&self.table, index, &row) {
+                        let (entries, keys) = stats.entry(index.name.clone()).or_default();
+                        *entries += 1;
+                        keys.insert(key);
+                    }
+                }
+            }
+        }
+
+        *self.index_stats.write().unwrap() = self
+            .table
+            .indexes
+            .iter()
+            .map(|index| {
+                let index_stats =
+                    stats
+                        .remove(&index.name)
+                        .map_or(IndexStats::default(), |(entries, keys)| IndexStats {
+                            entries,
+                            distinct_keys: keys.len() as u64,
+                        });
+                (index.name.clone(), index_stats)
+            })
+            .collect();
+    }
+
+    /// Creates empty bloom filters for the shards of `table`, sized after the amount of indexed columns.
+    fn new_blooms(table: &Table, shard_size: Option<u64>) -> ShardBlooms {
+        let rows = shard_size.map_or(DEFAULT_BLOOM_CAPACITY, |shard_size| shard_size as usize);
+        ShardBlooms::new(shard_size, rows * Self::bloom_columns(table).len().max(1))
+    }
+
+    /// Adds the rows of the main shard to `blooms`. Filters are not persisted, so they are filled when the table is loaded.
+    fn fill_blooms(&self) {
+        let data = self.data.read().unwrap();
+
+        for pointer in 0..data.len() {
+            if let Ok(item) = data.get_element(pointer) {
+                Self::add_to_blooms(&self.table, &self.blooms, pointer as u64, &T::from(&item));
+            }
+        }
+    }
+
+    /// Columns whose values are added to the bloom filters: the members of the indexes of `table`.
+    pub fn bloom_columns(table: &Table) -> HashSet<String> {
+        table
+            .indexes
+            .iter()
+            .flat_map(|index| index.members.iter().cloned())
+            .collect()
+    }
+
+    /// Item representing `value` of `column` in the bloom filters, `value` being collated already.
+    /// Values that are equal must share their item (e.g. `1` and `1.0`), false positives are fine.
+    pub fn bloom_key(column: &str, value: &DataValue) -> String {
+        let value = match value {
+            // Adding `0.0` turns `-0.0` into `0.0`
+            DataValue::Number(n) => (n.as_f64().unwrap_or(0.0) + 0.0).to_string(),
+            value => value.to_string(),
+        };
+
+        format!("{}\u{1f}{}", column, value)
+    }
+
+    /// Adds the values of `row`, written at `position` of the main shard, to `blooms`. Nulls are left out.
+    fn add_to_blooms(table: &Table, blooms: &ShardBlooms, position: u64, row: &T) {
+        for column_name in Self::bloom_columns(table) {
+            let value = match table
+                .resolve_column(&column_name)
+                .and_then(|column| row.get_value(&column))
+            {
+                Some(value) if !value.is_null() => value,
+                _ => continue,
+            };
+
+            let value = value.collate(table.collation(&column_name));
+            blooms.insert(position, Self::bloom_key(&column_name, &value).as_bytes());
+        }
+    }
+
+    /// Returns the current snapshot of the main shard, counted as in use until dropped so the previous versions
+    /// of the rows it sees are kept.
+    pub fn snapshot(&self) -> SnapshotGuard {
+        self.clock.acquire()
+    }
+
+    /// Resolves the version of the row at `pointer` that is visible in `snapshot`, following
+    /// `previous_versions` for rows updated after the snapshot was taken.
+    /// Returns `None` when no version of the row is visible (e.g. it was inserted or deleted before the snapshot).
+    pub fn visible_version(&self, pointer: u64, snapshot: &Snapshot) -> Option<u64> {
+        let mut pointer = pointer;
+
+        if !snapshot.contains(pointer) {
+            let previous_versions = self.previous_versions.read().unwrap();
+            while !snapshot.contains(pointer) {
+                pointer = *previous_versions.get(&pointer)?;
+            }
+        }
+
+        if self.tombstones.is_dead_at(pointer, snapshot.epoch) {
+            None
+        } else {
+            Some(pointer)
+        }
+    }
+
+    /// Representation of `value` used to build the composite key of an index of type `index_type`.
+    /// Ordered indexes need a representation whose order matches the order of the values.
+    /// Nulls (members of composite keys can be null) get a key of their own that sorts first,
+    /// so they don't collide with `0`. Geo indexes key points by their geohash, so nearby points sort together.
+    pub fn index_value(index_type: &IndexType, value: &DataValue) -> String {
+        if value.is_null() {
+            return String::from("\0");
+        }
+
+        match index_type {
+            IndexType::Hash | IndexType::MultiEntry => value.to_string(),
+            IndexType::BTree => value.to_sortable_string(),
+            IndexType::Geo => match GeoPoint::from_value(value) {
+                Some(point) => point.geohash(GEOHASH_PRECISION),
+                None => value.to_string(),
+            },
+        }
+    }
+
+    /// Builds the composite key of `row` for `index`, out of the values of its members under their collation.
+    /// Returns `None` when every member of the index is null, in which case the row is not indexed.
+    pub fn composite_key(table: &Table, index: &TableIndex, row: &T) -> Option<CompositeKey> {
+        let mut can_index = false;
+        let mut composite_key_vals: Vec<(String, String)> = vec![];
+
+        for index_col in &index.members {
+            // Members are checked when the table is opened
+            let column = table.resolve_column(index_col)?;
+            let val = row
+                .get_value(&column)
+                .map_or(DataValue::Null, |val| column.collate(val));
+
+            if !val.is_null() {
+                can_index = true;
+            }
+
+            composite_key_vals.push((
+                index_col.clone(),
+                Self::index_value(&index.index_type, &val),
+            ))
+        }
+
+        if can_index {
+            Some(CompositeKey(composite_key_vals))
+        } else {
+            None
+        }
+    }
+
+    /// Keys of `row` for `index`, each of them pointing to the row. Rows hold a single key (see `composite_key`),
+    /// except for multi-entry indexes: members holding an array contribute each of their distinct items instead,
+    /// making a key per combination of the items (`["rust", "go"]` gets the keys `rust` and `go`).
+    /// Keys whose members are all null are left out, so rows with empty arrays are not indexed.
+    pub fn composite_keys(table: &Table, index: &TableIndex, row: &T) -> Vec<CompositeKey> {
+        if index.index_type != IndexType::MultiEntry {
+            return Self::composite_key(table, index, row).into_iter().collect();
+        }
+
+        let mut combinations: Vec<Vec<DataValue>> = vec![vec![]];
+        for index_col in &index.members {
+            let column = match table.resolve_column(index_col) {
+                Some(column) => column,
+                None => return vec![],
+            };
+            let values = match row.get_value(&column).map(|val| column.collate(val)) {
+                Some(DataValue::Array(mut items)) => {
+                    items.sort();
+                    items.dedup();
+                    items
+                }
+                Some(val) => vec![val],
+                None => vec![DataValue::Null],
+            };
+
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |val| {
+                        let mut combination = combination.clone();
+                        combination.push(val.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+
+        combinations
+            .into_iter()
+            .filter(|combination| combination.iter().any(|val| !val.is_null()))
+            .map(|combination| {
+                CompositeKey(
+                    index
+                        .members
+                        .iter()
+                        .zip(combination.iter())
+                        .map(|(index_col, val)| {
+                            (index_col.clone(), Self::index_value(&index.index_type, val))
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Keys of the unique indexes of `table` held by `row`, along with the name of their index.
+    pub fn unique_keys(table: &Table, row: &T) -> Vec<(String, CompositeKey)> {
+        table
+            .indexes
+            .iter()
+            .filter(|index| index.unique)
+            .flat_map(|index| {
+                Self::composite_keys(table, index, row)
+                    .into_iter()
+                    .map(|key| (index.name.clone(), key))
+            })
+            .collect()
+    }
+
+    /// Checks that the primary key column of the table exists and that `row` holds a value for it.
+    /// Its uniqueness is checked along with the other unique indexes by `reserve_unique_keys`.
+    /// Tables with an empty `primary_key` have no primary key and are not checked.
+    pub fn validate_primary_key(&self, row: &T) -> Result<(), QueryError> {
+        let primary_key = self.table.primary_key.clone();
+        if primary_key.is_empty() {
+            return Ok(());
+        }
+
+        let column = self
+            .table
+            .get_column(&primary_key)
+            .ok_or_else(|| QueryError::UnknownPrimaryColumn(primary_key.clone()))?;
+
+        match row.get_value(column) {
+            Some(value) if !value.is_null() => Ok(()),
+            _ => Err(QueryError::ValueNotPresent(primary_key)),
+        }
+    }
+
+    /// Prepares a row about to be inserted: checks the type of its values, stores them in their normalized
+    /// form (e.g. timestamps as epoch millis) and fills its default values and timestamps.
+    pub fn prepare_row(&self, row: &mut T) -> Result<(), QueryError> {
+        row.validate(&self.table)?;
+        self.normalize_row(row);
+        self.apply_defaults(row)?;
+        self.apply_timestamps(row, true);
+
+        Ok(())
+    }
+
+    /// Rewrites the values of `row` whose stored form differs from the one received (see `DataTypes::is_normalized`).
+    fn normalize_row(&self, row: &mut T) {
+        for column in self.table.columns.values() {
+            if column.data_type.is_normalized() {
+                continue;
+            }
+
+            if let Some(value) = row.get_value(column) {
+                row.set_value(column, value);
+            }
+        }
+    }
+
+    /// Fills the columns missing from `row` with their default value.
+    /// Fails with `InvalidDefaultValue` if a default does not fit the type of its column.
+    pub fn apply_defaults(&self, row: &mut T) -> Result<(), QueryError> {
+        for column in self.table.columns.values() {
+            if column.default_value.is_none() || row.has_value(column) {
+                continue;
+            }
+
+            let value = column
+                .generate_default_value()
+                .ok_or_else(|| QueryError::InvalidDefaultValue(column.name.clone()))?;
+            row.set_value(column, value);
+        }
+
+        Ok(())
+    }
+
+    /// Stamps the `_updated_at` column of `row`, and its `_created_at` column when `is_insert`,
+    /// if the table keeps timestamps.
+    pub fn apply_timestamps(&self, row: &mut T, is_insert: bool) {
+        if !self.table.timestamps {
+            return;
+        }
+
+        let updated_at = Table::get_updated_at();
+        let now = match updated_at.generate_default_value() {
+            Some(now) => now,
+            None => return,
+        };
+
+        if is_insert {
+            row.set_value(&Table::get_created_at(), now.clone());
+        }
+        row.set_value(&updated_at, now);
+    }
+
+    /// Checks the values of `row` against the `check` expression of their column.
+    /// Missing and null values are not checked, they fail with `NullValue` on columns that are not nullable.
+    pub fn validate_row(&self, row: &T) -> Result<(), QueryError> {
+        for column in self.table.columns.values() {
+            if !column.nullable && row.get_value(column).map_or(true, |value| value.is_null()) {
+                return Err(QueryError::NullValue(column.name.clone()));
+            }
+
+            let check = match &column.check {
+                Some(check) => check,
+                None => continue,
+            };
+
+            match row.get_value(column) {
+                Some(value) if !value.is_null() => {}
+                _ => continue,
+            }
+
+            if !parse_check(&self.table, check)?.matches(&self.table, row) {
+                return Err(QueryError::CheckViolation(
+                    column.name.clone(),
+                    check.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that none of `keys` is held by a row of the table (indexed or pending) and reserves them
+    /// until their rows are indexed. Fails with `UniqueViolation` without reserving anything otherwise.
+    ///
+    /// Reserved keys are released automatically once their rows are reconciled. Callers must release them
+    /// through `release_unique_keys` if the rows end up not being written to a temporary shard.
+    /// Keys of the primary key fail with `DuplicatePrimaryKey` instead.
+    pub fn reserve_unique_keys(
+        &self,
+        keys: Vec<(String, CompositeKey)>,
+    ) -> Result<Vec<(String, CompositeKey)>, QueryError> {
+        if keys.is_empty() {
+            return Ok(keys);
+        }
+
+        let mut pending = self.pending_unique_keys.lock().unwrap();
+        // Rows stop being pending only after they are published, so the snapshot holds every released key.
+        // Writes held back by a transaction being committed are part of it, the transaction must see them.
+        let snapshot = self.clock.latest();
+
+        for (position, (index_name, key)) in keys.iter().enumerate() {
+            let is_pending = pending
+                .get(index_name)
+                .map_or(false, |index_keys| index_keys.contains(key));
+
+            if is_pending || self.is_key_indexed(index_name, key, &snapshot) {
+                for (reserved_index, reserved_key) in &keys[..position] {
+                    if let Some(index_keys) = pending.get_mut(reserved_index) {
+                        index_keys.remove(reserved_key);
+                    }
+                }
+
+                let value = Self::key_value(key);
+
+                let primary_key = self.table.indexes.iter().find(|index| {
+                    index.name == *index_name && self.table.is_primary_key_index(index)
+                });
+
+                return Err(match primary_key {
+                    Some(index) => QueryError::DuplicatePrimaryKey(index.members[0].clone(), value),
+                    None => QueryError::UniqueViolation(index_name.clone(), value),
+                });
+            }
+
+            pending
+                .entry(index_name.clone())
+                .or_default()
+                .insert(key.clone());
+        }
+
+        Ok(keys)
+    }
+
+    /// Values of `key` as shown in unique violations.
+    fn key_value(key: &CompositeKey) -> String {
+        key.0
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Whether `key` is held by a row of `snapshot` in the index named `index_name`.
+    fn is_key_indexed(&self, index_name: &str, key: &CompositeKey, snapshot: &Snapshot) -> bool {
+        let indx_read = match self.indexes.get(index_name) {
+            Some(indx_read) => indx_read,
+            None => return false,
+        };
+        let indx = indx_read.as_index();
+
+        // Entries left behind by updates and deletes point to dead rows
+        indx.get_all(&indx.to_key(key.clone()))
+            .into_iter()
+            .any(|pointer| self.visible_version(pointer, snapshot) == Some(pointer))
+    }
+
+    /// Removes `keys` from `pending_unique_keys`, once their rows are indexed (or failed to be inserted).
+    pub fn release_unique_keys(
+        pending_unique_keys: &Mutex<HashMap<String, HashSet<CompositeKey>>>,
+        keys: &[(String, CompositeKey)],
+    ) {
+        if keys.is_empty() {
+            return;
+        }
+
+        let mut pending = pending_unique_keys.lock().unwrap();
+        for (index_name, key) in keys {
+            if let Some(index_keys) = pending.get_mut(index_name) {
+                index_keys.remove(key);
+            }
+        }
+    }
+
     /// This method handles automatically indexing the rows that match the index in the Table.
     /// It is called during the reconciling process through `set_on_reconcile` in the TempMapShard.
     pub fn insert_indexes(
         table: Arc<Table>,
         indexes: Arc<CHashMap<String, IndexTypeValue>>,
+        blooms: Arc<ShardBlooms>,
         data: Vec<DataWithIndex>,
     ) {
         let mut index_ordered_items: HashMap<String, Vec<(IndexKeyType, u64)>> = HashMap::new();
 
         for row in data {
             let row_t = T::from(&row.data);
+            Self::add_to_blooms(&table, &blooms, row.index, &row_t);
             for index in &table.indexes {
-                let mut can_index = false;
-                let mut composite_key_vals: Vec<(String, String)> = vec![];
-
-                for index_col in &index.members {
-                    let val = row_t
-                        .get_value(table.get_column(index_col).unwrap())
-                        .unwrap_or(DataValue::Null);
-
-                    if !val.is_null() {
-                        can_index = true;
-                    }
-
-                    composite_key_vals.push((index_col.clone(), val.to_string()))
-                }
-
-                if can_index {
+                for composite_key in Self::composite_keys(&table, index, &row_t) {
                     let real_indx = indexes.get(&index.name).unwrap();
-                    let composite_key = CompositeKey(composite_key_vals);
                     let indx = real_indx.as_index();
                     let key = indx.to_key(composite_key);
                     let insertion_value = (key, row.index);
@@ -193,4 +1213,546 @@ impl<T: Row<T>> TableShard<T> {
             indx.bulk_insert(rows);
         }
     }
+
+    /// Storage settings of the table.
+    pub fn storage(&self) -> TableStorage {
+        let encoding = match self.layout {
+            Some(_) => RowEncoding::Compact,
+            None => RowEncoding::Json,
+        };
+
+        self.data
+            .read()
+            .unwrap()
+            .config()
+            .storage()
+            .set_encoding(encoding)
+            .set_flush(self.temps.flush_policy().unwrap_or_default())
+    }
+
+    /// Serializes `row` with the encoding of the table.
+    pub fn serialize_row(&self, row: &T) -> Result<Vec<u8>, QueryError> {
+        let serialized = match (&self.serializer, &self.layout) {
+            (Some(serializer), _) => row.serialize_with(serializer, &self.table),
+            (None, Some(layout)) => row.serialize_compact(layout),
+            (None, None) => row.serialize(),
+        };
+
+        serialized.map_err(|_| QueryError::InvalidSerialization)
+    }
+
+    /// Appends `data` to the main shard and indexes it right away, skipping the temporary shards.
+    ///
+    /// # Returns:
+    /// - `Result<u64, QueryError>`: The position of the row in the main shard.
+    pub fn insert_row(&self, data: &[u8]) -> Result<u64, QueryError> {
+        let mut shard = self.data.write().unwrap();
+        let pointer = shard.insert_rows(&[data]) as u64;
+
+        Self::insert_indexes(
+            self.table.clone(),
+            self.indexes.clone(),
+            self.blooms.clone(),
+            vec![DataWithIndex {
+                data: data.to_vec(),
+                index: pointer,
+            }],
+        );
+        self.clock.publish(pointer + 1);
+
+        Ok(pointer)
+    }
+
+    /// Appends every item of `data` to the main shard in a single write and indexes them right away,
+    /// skipping the temporary shards. Used for bulk loads, where logging every row first isn't worth it.
+    ///
+    /// # Returns:
+    /// - `Result<u64, QueryError>`: The position of the first row in the main shard, the others follow it.
+    pub fn insert_rows(&self, data: &[&[u8]]) -> Result<u64, QueryError> {
+        let mut shard = self.data.write().unwrap();
+        let first = shard.len() as u64;
+
+        if data.is_empty() {
+            return Ok(first);
+        }
+
+        shard.insert_rows(data);
+
+        Self::insert_indexes(
+            self.table.clone(),
+            self.indexes.clone(),
+            self.blooms.clone(),
+            data.iter()
+                .enumerate()
+                .map(|(offset, item)| DataWithIndex {
+                    data: item.to_vec(),
+                    index: first + offset as u64,
+                })
+                .collect(),
+        );
+        self.clock.publish(first + data.len() as u64);
+
+        Ok(first)
+    }
+
+    /// Writes a new version of the row at `pointer` with `new_values` applied.
+    ///
+    /// The new version is appended to the main shard and the old position is marked as a tombstone.
+    /// Index entries whose key did not change are moved to the new position, otherwise the new key is indexed
+    /// and the stale entry is left behind (searches skip tombstoned positions).
+    /// The update is published once its index entries are in place: searches that started before it keep seeing
+    /// the previous version of the row.
+    /// Keys of unique indexes changed by the update are reserved until then, the ones the row already held are
+    /// left as they are.
+    ///
+    /// # Parameters:
+    /// - `pointer`: Position of the row in the main shard.
+    /// - `new_values`: Columns to overwrite and their new values.
+    ///
+    /// # Returns:
+    /// - `Result<u64, QueryError>`: The position of the new version of the row.
+    pub fn update_row(
+        &self,
+        pointer: u64,
+        new_values: &[(Column, DataValue)],
+    ) -> Result<u64, QueryError> {
+        let mut data = self.data.write().unwrap();
+
+        let old_data = data.get_element(pointer as usize)?;
+        let mut new_row = T::from(&old_data);
+
+        for (column, value) in new_values {
+            new_row.set_value(column, value.clone());
+        }
+
+        new_row.validate(&self.table)?;
+        self.normalize_row(&mut new_row);
+        self.apply_timestamps(&mut new_row, false);
+        self.validate_row(&new_row)?;
+
+        let serialized_value = self.serialize_row(&new_row)?;
+
+        let old_keys = Self::unique_keys(&self.table, &T::from(&old_data));
+        let changed_keys = Self::unique_keys(&self.table, &new_row)
+            .into_iter()
+            .filter(|key| !old_keys.contains(key))
+            .collect();
+        let reserved = self.reserve_unique_keys(changed_keys)?;
+
+        let new_pointer = self.write_version(&mut data, pointer, &old_data, &serialized_value);
+        Self::release_unique_keys(&self.pending_unique_keys, &reserved);
+
+        new_pointer
+    }
+
+    /// Same as `update_row`, but the new version of the row is given already serialized.
+    pub fn replace_row(&self, pointer: u64, new_data: &[u8]) -> Result<u64, QueryError> {
+        let mut data = self.data.write().unwrap();
+        let old_data = data.get_element(pointer as usize)?;
+
+        self.write_version(&mut data, pointer, &old_data, new_data)
+    }
+
+    /// Marks the row at `pointer` as deleted. Its index entries are left behind, searches skip tombstoned positions.
+    pub fn delete_row(&self, pointer: u64) -> Result<(), QueryError> {
+        let _data = self.data.write().unwrap();
+        self.tombstones.mark(pointer, self.clock.pending_epoch())?;
+        self.clock.publish(0);
+
+        Ok(())
+    }
+
+    fn write_version(
+        &self,
+        data: &mut MapShard<DataShard, DataShardConfig>,
+        pointer: u64,
+        old_data: &[u8],
+        new_data: &[u8],
+    ) -> Result<u64, QueryError> {
+        let old_row = T::from(old_data);
+        let new_row = T::from(new_data);
+
+        let new_pointer = data.insert_rows(&[new_data]) as u64;
+        {
+            let mut previous_versions = self.previous_versions.write().unwrap();
+            previous_versions.insert(new_pointer, pointer);
+
+            let horizon = self.clock.horizon();
+            while let Some((&position, _)) = previous_versions.first_key_value() {
+                if position >= horizon {
+                    break;
+                }
+                previous_versions.pop_first();
+            }
+        }
+        self.tombstones.mark(pointer, self.clock.pending_epoch())?;
+        Self::add_to_blooms(&self.table, &self.blooms, new_pointer, &new_row);
+
+        for index in &self.table.indexes {
+            let old_keys = Self::composite_keys(&self.table, index, &old_row);
+            let new_keys = Self::composite_keys(&self.table, index, &new_row);
+            if new_keys.is_empty() {
+                continue;
+            }
+
+            let real_indx = self.indexes.get(&index.name).unwrap();
+            let indx = real_indx.as_index();
+
+            for new_key in new_keys {
+                let replaced = match old_keys.contains(&new_key) {
+                    true => indx.replace(indx.to_key(new_key.clone()), pointer, new_pointer),
+                    false => false,
+                };
+
+                if !replaced {
+                    indx.insert(indx.to_key(new_key), new_pointer);
+                }
+            }
+        }
+
+        self.clock.publish(new_pointer + 1);
+
+        Ok(new_pointer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{
+        equals, test_db, user, user_names, user_row, users, users_by_country, users_manager,
+    };
+    use crate::managers::single::SingleQueryManager;
+    use crate::ops::query_ops::QueryOps;
+    use crate::row_json::{RowData, RowJson};
+    use schemajs_data::compression::Compression;
+    use schemajs_data::encryption::EncryptionKey;
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_data::utils::fs::list_files_with_prefix;
+    use schemajs_index::index_type::IndexType;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[flaky_test::flaky_test]
+    pub fn test_table_shard_wal_recovery() {
+        let (test_db, db_folder) = test_db();
+
+        {
+            let query_manager: SingleQueryManager<RowJson> =
+                SingleQueryManager::new(test_db.clone());
+            query_manager.register_table(users_by_country()).unwrap();
+
+            for (name, country) in [("andres", "US"), ("luis", "VE"), ("carlos", "US")] {
+                query_manager
+                    .insert(user_row(
+                        json!({ "user_name": name, "user_country": country }),
+                    ))
+                    .unwrap();
+            }
+
+            // The rows are still in temporary shards when the manager goes away, as in a crash
+            let table_shard = query_manager.tables.get("users").unwrap();
+            assert_eq!(table_shard.data.read().unwrap().len(), 0);
+        }
+
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+        query_manager.register_table(users_by_country()).unwrap();
+
+        let by_country = |country: &str| equals("user_country", country);
+        assert_eq!(
+            user_names(&query_manager, "users", &by_country("US")),
+            vec!["andres", "carlos"]
+        );
+        assert_eq!(
+            user_names(&query_manager, "users", &by_country("VE")),
+            vec!["luis"]
+        );
+
+        // Restored logs are removed, only the logs of the current temporary shards are left
+        let temps_folder = query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .path
+            .join("temps");
+        assert_eq!(
+            list_files_with_prefix(&temps_folder, "wal_").unwrap().len(),
+            5
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_table_shard_orphaned_temp_shards() {
+        let (test_db, db_folder) = test_db();
+        let temps =
+            |folder: &std::path::Path| list_files_with_prefix(folder, "temp_").unwrap().len();
+
+        let temps_folder = {
+            let query_manager: SingleQueryManager<RowJson> =
+                SingleQueryManager::new(test_db.clone());
+            query_manager.register_table(users_by_country()).unwrap();
+
+            let insert = |name: &str, country: &str| {
+                query_manager
+                    .insert(user_row(
+                        json!({ "user_name": name, "user_country": country }),
+                    ))
+                    .unwrap();
+            };
+
+            insert("andres", "US");
+            let table_shard = query_manager.tables.get("users").unwrap();
+            let temps_folder = table_shard.path.join("temps");
+
+            // Reconciled temporary shards are removed
+            assert_eq!(temps(&temps_folder), 1);
+            table_shard.temps.reconcile_all();
+            assert_eq!(temps(&temps_folder), 0);
+            drop(table_shard);
+
+            insert("luis", "VE");
+            insert("carlos", "US");
+            temps_folder
+        };
+
+        // A previous run without write-ahead logs only leaves its temporary shards behind
+        for log in list_files_with_prefix(&temps_folder, "wal_").unwrap() {
+            std::fs::remove_file(log).unwrap();
+        }
+        assert_eq!(temps(&temps_folder), 2);
+
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+        query_manager.register_table(users_by_country()).unwrap();
+        assert_eq!(temps(&temps_folder), 0);
+
+        let by_country = |country: &str| equals("user_country", country);
+        assert_eq!(
+            user_names(&query_manager, "users", &by_country("US")),
+            vec!["andres", "carlos"]
+        );
+        assert_eq!(
+            user_names(&query_manager, "users", &by_country("VE")),
+            vec!["luis"]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_table_shard_compression() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+        query_manager
+            .register_table_with_storage(users(), TableStorage::new(Compression::Zstd))
+            .unwrap();
+
+        for name in ["andres", "carlos", "luis"] {
+            query_manager.insert(user(&name.repeat(50))).unwrap();
+        }
+
+        {
+            let table_shard = query_manager.tables.get("users").unwrap();
+            table_shard.temps.reconcile_all();
+
+            let data = table_shard.data.read().unwrap();
+            let shard = &data.current_master_shard;
+            assert_eq!(shard.header.read().unwrap().compression, Compression::Zstd);
+            let bytes = std::fs::read(&shard.path).unwrap();
+            assert!(!bytes
+                .windows(12)
+                .any(|window| window == "carlos".repeat(2).as_bytes()));
+        }
+
+        assert_eq!(
+            user_names(
+                &query_manager,
+                "users",
+                &equals("user_name", "luis".repeat(50))
+            ),
+            vec!["luis".repeat(50)]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_table_shard_encryption() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db)
+            .set_encryption(Some(EncryptionKey::from_secret("secret")));
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_email", DataTypes::String))
+            .add_index(Index {
+                name: "emailindx".to_string(),
+                members: vec![String::from("user_email")],
+                index_type: IndexType::BTree,
+                unique: false,
+            });
+        query_manager.register_table(tbl).unwrap();
+
+        for email in [
+            "andres@outlook.com",
+            "carlos@outlook.com",
+            "luis@outlook.com",
+        ] {
+            query_manager
+                .insert(user_row(json!({ "user_email": email })))
+                .unwrap();
+        }
+
+        // Rows still in temporary shards (and their logs) are encrypted too
+        let table_folder = db_folder.join("users");
+        fn plaintext(folder: &std::path::Path) -> bool {
+            std::fs::read_dir(folder)
+                .unwrap()
+                .filter_map(|entry| entry.ok())
+                .any(|entry| match entry.path().is_dir() {
+                    true => plaintext(&entry.path()),
+                    false => std::fs::read(entry.path())
+                        .unwrap()
+                        .windows(11)
+                        .any(|window| window == b"outlook.com"),
+                })
+        }
+        assert!(!plaintext(&table_folder));
+
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+        assert!(!plaintext(&table_folder));
+
+        let results = query_manager
+            .search_manager()
+            .search(
+                "users".to_string(),
+                &equals("user_email", "luis@outlook.com"),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].value.value["user_email"],
+            json!("luis@outlook.com")
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_table_shard_retention() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+
+        let tbl = Table::new("events")
+            .add_column(Column::new("name", DataTypes::String))
+            .add_column(Column::new("seen_at", DataTypes::Timestamp))
+            .add_index(Index {
+                name: "nameindx".to_string(),
+                members: vec!["name".to_string()],
+                index_type: IndexType::Hash,
+                unique: false,
+            })
+            .set_ttl("seen_at", std::time::Duration::from_secs(60));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let row = |name: &str, seen_at: i64| {
+            RowJson::from(RowData {
+                table: String::from("events"),
+                value: json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "name": name,
+                    "seen_at": seen_at
+                }),
+            })
+        };
+
+        for i in 0..3 {
+            query_manager
+                .insert(row(&format!("stale{}", i), now - 120_000))
+                .unwrap();
+        }
+        query_manager.insert(row("fresh", now)).unwrap();
+        query_manager.insert(row("recent", now - 1000)).unwrap();
+
+        // Nothing is dead yet
+        assert_eq!(query_manager.compact_table("events").unwrap(), 0);
+        assert!(query_manager.compact_table("unknown").is_err());
+
+        assert_eq!(query_manager.enforce_retention().unwrap(), 3);
+        assert_eq!(query_manager.enforce_retention().unwrap(), 0);
+
+        let tables = query_manager.tables.clone();
+        {
+            let events = tables.get("events").unwrap();
+            assert_eq!(events.data.read().unwrap().len(), 2);
+            assert!(events.tombstones.is_empty());
+        }
+
+        // Indexes point to the new position of the rows
+        let by_name = |name: &str| {
+            query_manager
+                .search_manager()
+                .search("events".to_string(), &equals("name", name))
+                .unwrap()
+        };
+        assert!(by_name("stale0").is_empty());
+        let recent = by_name("recent");
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].value.value["name"], json!("recent"));
+
+        // Compacted tables keep taking writes
+        query_manager.insert(row("late", now)).unwrap();
+        tables.get("events").unwrap().temps.reconcile_all();
+        assert_eq!(by_name("late").len(), 1);
+
+        // Only the table folder is left behind
+        let leftovers: Vec<_> = std::fs::read_dir(&db_folder)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with('.'))
+            .collect();
+        assert!(leftovers.is_empty());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_table_shard_compact_all() {
+        let (query_manager, db_folder) = users_manager(&["andres", "carlos", "luis", "juan"]);
+        query_manager.register_table(Table::new("empty")).unwrap();
+
+        assert_eq!(
+            query_manager
+                .delete("users".to_string(), &equals("user_name", "carlos"))
+                .unwrap(),
+            1
+        );
+
+        // A quarter of the rows are dead
+        assert_eq!(query_manager.compact_all(0.5).unwrap(), 0);
+        assert_eq!(query_manager.compact_all(0.2).unwrap(), 1);
+        assert_eq!(query_manager.compact_all(0.0).unwrap(), 0);
+
+        let tables = query_manager.tables.clone();
+        assert_eq!(tables.get("users").unwrap().data.read().unwrap().len(), 3);
+        assert_eq!(
+            user_names(&query_manager, "users", &QueryOps::And(vec![])),
+            vec!["andres", "juan", "luis"]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
 }
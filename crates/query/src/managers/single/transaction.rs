@@ -0,0 +1,114 @@
+use crate::ops::query_ops::QueryOps;
+use crate::row::Row;
+use schemajs_primitives::column::types::DataValue;
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A write staged in a `Transaction`.
+#[derive(Debug)]
+pub enum TransactionOp<T> {
+    Insert(T),
+    Update {
+        table: String,
+        query: QueryOps,
+        values: HashMap<String, DataValue>,
+    },
+    Delete {
+        table: String,
+        query: QueryOps,
+    },
+}
+
+/// Set of writes across the tables of a `SingleQueryManager` that are applied together.
+///
+/// Nothing is written while operations are staged. `SingleQueryManager::commit` validates every operation
+/// before applying any of them and undoes the applied ones if one fails, while `rollback` simply discards them.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut tx = query_manager.begin();
+/// tx.insert(row);
+/// tx.delete("users", query);
+/// query_manager.commit(tx)?;
+/// ```
+#[derive(Debug)]
+pub struct Transaction<T: Row<T>> {
+    pub id: Uuid,
+    ops: Vec<TransactionOp<T>>,
+}
+
+impl<T: Row<T>> Transaction<T> {
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            ops: vec![],
+        }
+    }
+
+    pub fn insert(&mut self, row: T) -> &mut Self {
+        self.ops.push(TransactionOp::Insert(row));
+        self
+    }
+
+    pub fn update(
+        &mut self,
+        table: &str,
+        query: QueryOps,
+        values: HashMap<String, DataValue>,
+    ) -> &mut Self {
+        self.ops.push(TransactionOp::Update {
+            table: table.to_string(),
+            query,
+            values,
+        });
+        self
+    }
+
+    pub fn delete(&mut self, table: &str, query: QueryOps) -> &mut Self {
+        self.ops.push(TransactionOp::Delete {
+            table: table.to_string(),
+            query,
+        });
+        self
+    }
+
+    /// Discards every staged operation.
+    pub fn rollback(self) {}
+
+    pub fn ops(&self) -> &[TransactionOp<T>] {
+        &self.ops
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub(crate) fn into_ops(self) -> Vec<TransactionOp<T>> {
+        self.ops
+    }
+}
+
+impl<T: Row<T>> Default for Transaction<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Outcome of a committed `Transaction`.
+///
+/// # Fields:
+/// - `inserted`: The `_uid` of the inserted rows, in the order they were staged.
+/// - `updated`: The amount of updated rows.
+/// - `deleted`: The amount of deleted rows.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TransactionResult {
+    pub inserted: Vec<Uuid>,
+    pub updated: usize,
+    pub deleted: usize,
+}
@@ -0,0 +1,257 @@
+use crate::errors::QueryError;
+use schemajs_primitives::column::types::DataValue;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Display for AggregateFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Avg => "avg",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+impl FromStr for AggregateFunction {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "count" => Ok(AggregateFunction::Count),
+            "sum" => Ok(AggregateFunction::Sum),
+            "min" => Ok(AggregateFunction::Min),
+            "max" => Ok(AggregateFunction::Max),
+            "avg" => Ok(AggregateFunction::Avg),
+            _ => Err(QueryError::InvalidAggregation(s.to_string())),
+        }
+    }
+}
+
+/// An aggregation to compute over the rows matched by a query.
+///
+/// # Fields:
+/// - `function`: The aggregate function.
+/// - `column`: The column to aggregate. `count` without a column counts rows,
+///   with a column it counts the rows where the column is present and not null.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    pub column: Option<String>,
+}
+
+impl Aggregate {
+    pub fn new(function: AggregateFunction, column: &str) -> Self {
+        Self {
+            function,
+            column: Some(column.to_string()),
+        }
+    }
+
+    pub fn count() -> Self {
+        Self {
+            function: AggregateFunction::Count,
+            column: None,
+        }
+    }
+
+    /// Name of the aggregate in results, such as `count` or `sum_user_age`.
+    pub fn alias(&self) -> String {
+        match &self.column {
+            Some(column) => format!("{}_{}", self.function, column),
+            None => self.function.to_string(),
+        }
+    }
+}
+
+/// A bucket produced by a group-by.
+///
+/// # Fields:
+/// - `keys`: The grouping columns and the value shared by every row in the bucket (`Null` if missing).
+/// - `values`: The alias and result of each aggregate evaluated over the bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateGroup {
+    pub keys: Vec<(String, DataValue)>,
+    pub values: Vec<(String, DataValue)>,
+}
+
+impl AggregateGroup {
+    pub fn get_key(&self, column: &str) -> Option<&DataValue> {
+        self.keys
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_value(&self, alias: &str) -> Option<&DataValue> {
+        self.values
+            .iter()
+            .find(|(name, _)| name == alias)
+            .map(|(_, value)| value)
+    }
+
+    /// Flattens the group into a JSON object holding both the grouping columns and the aggregates.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut obj = serde_json::Map::new();
+        for (name, value) in self.keys.iter().chain(self.values.iter()) {
+            obj.insert(name.clone(), value.to_json());
+        }
+
+        serde_json::Value::Object(obj)
+    }
+}
+
+/// Running state of an `Aggregate`. Values are fed one at a time through `accumulate`,
+/// so rows never have to be kept in memory.
+///
+/// Numeric coercion: `sum` and `avg` coerce values through `DataValue::to_f64`
+/// and fail on values that can't be coerced. Null or missing values are ignored by every function
+/// except `count` without a column.
+#[derive(Debug, Clone)]
+pub struct AggregateState {
+    aggregate: Aggregate,
+    count: u64,
+    sum: f64,
+    min: Option<DataValue>,
+    max: Option<DataValue>,
+}
+
+impl AggregateState {
+    pub fn new(aggregate: Aggregate) -> Self {
+        Self {
+            aggregate,
+            count: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+        }
+    }
+
+    pub fn accumulate(&mut self, value: Option<&DataValue>) -> Result<(), QueryError> {
+        let value = match (&self.aggregate.column, value) {
+            (None, _) => {
+                self.count += 1;
+                return Ok(());
+            }
+            (Some(_), None) | (Some(_), Some(DataValue::Null)) => return Ok(()),
+            (Some(_), Some(value)) => value,
+        };
+
+        self.count += 1;
+
+        match self.aggregate.function {
+            AggregateFunction::Count => {}
+            AggregateFunction::Sum | AggregateFunction::Avg => {
+                let number = value.to_f64().ok_or_else(|| {
+                    QueryError::InvalidAggregationValue(
+                        self.aggregate.function.to_string(),
+                        value.to_string(),
+                    )
+                })?;
+                self.sum += number;
+            }
+            AggregateFunction::Min => {
+                if self.min.as_ref().map_or(true, |min| value < min) {
+                    self.min = Some(value.clone());
+                }
+            }
+            AggregateFunction::Max => {
+                if self.max.as_ref().map_or(true, |max| value > max) {
+                    self.max = Some(value.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Result of the aggregation. `sum` of no values is `0`, while `min`, `max` and `avg` are `Null`.
+    pub fn finish(&self) -> DataValue {
+        match self.aggregate.function {
+            AggregateFunction::Count => DataValue::Number(serde_json::Number::from(self.count)),
+            AggregateFunction::Sum => Self::to_number(self.sum),
+            AggregateFunction::Avg => {
+                if self.count == 0 {
+                    DataValue::Null
+                } else {
+                    Self::to_number(self.sum / self.count as f64)
+                }
+            }
+            AggregateFunction::Min => self.min.clone().unwrap_or(DataValue::Null),
+            AggregateFunction::Max => self.max.clone().unwrap_or(DataValue::Null),
+        }
+    }
+
+    fn to_number(value: f64) -> DataValue {
+        if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+            DataValue::Number(serde_json::Number::from(value as i64))
+        } else {
+            serde_json::Number::from_f64(value)
+                .map(DataValue::Number)
+                .unwrap_or(DataValue::Null)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::aggregate::{Aggregate, AggregateFunction, AggregateState};
+    use schemajs_primitives::column::types::DataValue;
+
+    #[test]
+    pub fn test_aggregate_state() {
+        let values = vec![
+            Some(DataValue::Number(serde_json::Number::from(10))),
+            Some(DataValue::String("5".to_string())),
+            Some(DataValue::Boolean(true)),
+            Some(DataValue::Null),
+            None,
+        ];
+
+        let run = |aggregate: Aggregate| {
+            let mut state = AggregateState::new(aggregate);
+            for value in values.iter() {
+                state.accumulate(value.as_ref()).unwrap();
+            }
+            state.finish()
+        };
+
+        let number = |n: i64| DataValue::Number(serde_json::Number::from(n));
+
+        assert_eq!(run(Aggregate::count()), number(5));
+        assert_eq!(
+            run(Aggregate::new(AggregateFunction::Count, "col")),
+            number(3)
+        );
+        assert_eq!(
+            run(Aggregate::new(AggregateFunction::Sum, "col")),
+            number(16)
+        );
+        assert_eq!(
+            run(Aggregate::new(AggregateFunction::Avg, "col")),
+            DataValue::Number(serde_json::Number::from_f64(16.0 / 3.0).unwrap())
+        );
+
+        let mut state = AggregateState::new(Aggregate::new(AggregateFunction::Sum, "col"));
+        assert!(state
+            .accumulate(Some(&DataValue::String("abc".to_string())))
+            .is_err());
+
+        let state = AggregateState::new(Aggregate::new(AggregateFunction::Max, "col"));
+        assert_eq!(state.finish(), DataValue::Null);
+    }
+}
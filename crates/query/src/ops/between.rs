@@ -0,0 +1,86 @@
+use crate::ops::query_ops::FilterType;
+use schemajs_primitives::column::types::DataValue;
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+/// Bounds of a `between` condition: `{ "min": 18, "max": 30 }`. Both bounds are included unless `minExclusive`
+/// or `maxExclusive` is `true`, `{ "min": 18, "max": 30, "maxExclusive": true }` being `18 <= value < 30`.
+///
+/// Like range filters, it never matches a null nor values of a different kind than its bounds.
+///
+/// # Fields:
+/// - `min`: Lower bound, never `Unbounded`.
+/// - `max`: Upper bound, never `Unbounded`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BetweenFilter {
+    pub min: Bound<DataValue>,
+    pub max: Bound<DataValue>,
+}
+
+impl BetweenFilter {
+    /// Reads the value of a `between` condition.
+    /// Returns `None` for values missing a bound, holding a null bound or bounds of different kinds.
+    pub fn from_value(value: &DataValue) -> Option<Self> {
+        let fields = value.as_object()?;
+        let exclusive = |name: &str| match fields.get(name) {
+            None | Some(DataValue::Null) => Some(false),
+            Some(DataValue::Boolean(exclusive)) => Some(*exclusive),
+            Some(_) => None,
+        };
+        let bound = |name: &str, exclusive: bool| match fields.get(name)? {
+            DataValue::Null => None,
+            value if exclusive => Some(Bound::Excluded(value.clone())),
+            value => Some(Bound::Included(value.clone())),
+        };
+
+        let filter = Self {
+            min: bound("min", exclusive("minExclusive")?)?,
+            max: bound("max", exclusive("maxExclusive")?)?,
+        };
+
+        filter
+            .min_value()
+            .same_kind(filter.max_value())
+            .then_some(filter)
+    }
+
+    fn min_value(&self) -> &DataValue {
+        match &self.min {
+            Bound::Included(value) | Bound::Excluded(value) => value,
+            Bound::Unbounded => &DataValue::Null,
+        }
+    }
+
+    fn max_value(&self) -> &DataValue {
+        match &self.max {
+            Bound::Included(value) | Bound::Excluded(value) => value,
+            Bound::Unbounded => &DataValue::Null,
+        }
+    }
+
+    /// Whether no value falls between the bounds, e.g. `min` is greater than `max`.
+    pub fn is_empty(&self) -> bool {
+        match self.min_value().cmp(self.max_value()) {
+            Ordering::Greater => true,
+            Ordering::Equal => {
+                matches!(self.min, Bound::Excluded(_)) || matches!(self.max, Bound::Excluded(_))
+            }
+            Ordering::Less => false,
+        }
+    }
+
+    pub fn matches(&self, value: &DataValue) -> bool {
+        let above_min = match &self.min {
+            Bound::Included(min) => FilterType::GreaterOrEqualTo.evaluate(value, min),
+            Bound::Excluded(min) => FilterType::GreaterThan.evaluate(value, min),
+            Bound::Unbounded => true,
+        };
+        let below_max = match &self.max {
+            Bound::Included(max) => FilterType::LowerOrEqualTo.evaluate(value, max),
+            Bound::Excluded(max) => FilterType::LowerThan.evaluate(value, max),
+            Bound::Unbounded => true,
+        };
+
+        above_min && below_max
+    }
+}
@@ -0,0 +1,274 @@
+use crate::errors::QueryError;
+use crate::ops::query_ops::{FilterType, QueryOps};
+use schemajs_primitives::table::Table;
+use serde_json::{json, Value};
+
+/// Fluent builder of queries, compiled into `QueryOps` by `build`:
+/// `Query::table("users").eq("user_country", "US").and().gt("user_age", 21)`.
+///
+/// Conditions are joined with `and` unless separated by `or`, which binds looser:
+/// `a.and().b.or().c` is `(a AND b) OR c`. Queries can be nested as a single condition through `nest`.
+///
+/// The builder produces the JSON representation of queries (see `QueryOps::from_json`) before typing its values
+/// after the columns of the table, the same representation the JS layer sends (its `Query` builder included).
+///
+/// # Fields:
+/// - `table`: Table the query runs against.
+/// - `groups`: Conditions joined with `or`, each group holding conditions joined with `and`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    table: String,
+    groups: Vec<Vec<Value>>,
+}
+
+impl Query {
+    pub fn table(name: &str) -> Self {
+        Self {
+            table: name.to_string(),
+            groups: vec![vec![]],
+        }
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table
+    }
+
+    /// Adds the condition `key <filter_type> value`.
+    pub fn condition<V: Into<Value>>(
+        mut self,
+        key: &str,
+        filter_type: FilterType,
+        value: V,
+    ) -> Self {
+        let condition = match filter_type {
+            FilterType::IsNull | FilterType::IsNotNull => {
+                json!({ "key": key, "filterType": filter_type.to_string() })
+            }
+            _ => {
+                json!({ "key": key, "filterType": filter_type.to_string(), "value": value.into() })
+            }
+        };
+
+        self.groups.last_mut().unwrap().push(condition);
+        self
+    }
+
+    pub fn eq<V: Into<Value>>(self, key: &str, value: V) -> Self {
+        self.condition(key, FilterType::Equal, value)
+    }
+
+    pub fn ne<V: Into<Value>>(self, key: &str, value: V) -> Self {
+        self.condition(key, FilterType::NotEqual, value)
+    }
+
+    pub fn gt<V: Into<Value>>(self, key: &str, value: V) -> Self {
+        self.condition(key, FilterType::GreaterThan, value)
+    }
+
+    pub fn gte<V: Into<Value>>(self, key: &str, value: V) -> Self {
+        self.condition(key, FilterType::GreaterOrEqualTo, value)
+    }
+
+    pub fn lt<V: Into<Value>>(self, key: &str, value: V) -> Self {
+        self.condition(key, FilterType::LowerThan, value)
+    }
+
+    pub fn lte<V: Into<Value>>(self, key: &str, value: V) -> Self {
+        self.condition(key, FilterType::LowerOrEqualTo, value)
+    }
+
+    pub fn is_in<V: Into<Value>, I: IntoIterator<Item = V>>(self, key: &str, values: I) -> Self {
+        let values: Vec<Value> = values.into_iter().map(Into::into).collect();
+        self.condition(key, FilterType::In, values)
+    }
+
+    /// Matches the rows whose array in `key` holds `value`.
+    pub fn contains<V: Into<Value>>(self, key: &str, value: V) -> Self {
+        self.condition(key, FilterType::Contains, value)
+    }
+
+    /// Matches the rows whose value in `key` is between `min` and `max`, both included.
+    pub fn between<V: Into<Value>>(self, key: &str, min: V, max: V) -> Self {
+        self.condition(
+            key,
+            FilterType::Between,
+            json!({ "min": min.into(), "max": max.into() }),
+        )
+    }
+
+    pub fn like(self, key: &str, pattern: &str) -> Self {
+        self.condition(key, FilterType::Like, pattern)
+    }
+
+    /// Matches the rows whose string in `key` matches the regular expression `pattern`.
+    pub fn regex(self, key: &str, pattern: &str) -> Self {
+        self.condition(key, FilterType::Regex, pattern)
+    }
+
+    pub fn starts_with(self, key: &str, prefix: &str) -> Self {
+        self.condition(key, FilterType::StartsWith, prefix)
+    }
+
+    pub fn is_null(self, key: &str) -> Self {
+        self.condition(key, FilterType::IsNull, Value::Null)
+    }
+
+    pub fn is_not_null(self, key: &str) -> Self {
+        self.condition(key, FilterType::IsNotNull, Value::Null)
+    }
+
+    /// Adds the condition `path <filter_type> value` on the values nested in `key`, e.g.
+    /// `path("profile", "address.country", FilterType::Equal, "US")`, see `QueryOps::from_json`.
+    pub fn path<V: Into<Value>>(
+        mut self,
+        key: &str,
+        path: &str,
+        filter_type: FilterType,
+        value: V,
+    ) -> Self {
+        let condition = json!({
+            "key": key,
+            "filterType": FilterType::Path.to_string(),
+            "value": { "path": path, "filterType": filter_type.to_string(), "value": value.into() },
+        });
+
+        self.groups.last_mut().unwrap().push(condition);
+        self
+    }
+
+    /// Adds `query` as a single condition, e.g. `a.and().nest(b.or().c)` is `a AND (b OR c)`.
+    /// The table of `query` is ignored.
+    pub fn nest(mut self, query: Query) -> Self {
+        let query = query.to_json();
+        if !query.is_null() {
+            self.groups.last_mut().unwrap().push(query);
+        }
+        self
+    }
+
+    /// Joins the previous condition and the next one with `and`, which conditions are joined with anyway.
+    pub fn and(self) -> Self {
+        self
+    }
+
+    /// Joins the conditions added so far and the next ones with `or`.
+    pub fn or(mut self) -> Self {
+        if !self.groups.last().unwrap().is_empty() {
+            self.groups.push(vec![]);
+        }
+        self
+    }
+
+    /// JSON representation of the query (see `QueryOps::from_json`), `null` when it has no conditions.
+    pub fn to_json(&self) -> Value {
+        let mut groups: Vec<Value> = self
+            .groups
+            .iter()
+            .filter(|group| !group.is_empty())
+            .map(|group| match group.len() {
+                1 => group[0].clone(),
+                _ => json!({ "and": group }),
+            })
+            .collect();
+
+        match groups.len() {
+            0 => Value::Null,
+            1 => groups.remove(0),
+            _ => json!({ "or": groups }),
+        }
+    }
+
+    /// Compiles the query, typing its values after the columns of `table`.
+    ///
+    /// # Returns:
+    /// - `Result<QueryOps, QueryError>`: Fails like `QueryOps::from_json` for unknown columns, or values not
+    ///   matching the type of their column.
+    pub fn build(&self, table: &Table) -> Result<QueryOps, QueryError> {
+        QueryOps::from_json(table, &self.to_json())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::builder::Query;
+    use crate::ops::query_ops::{QueryOps, QueryVal};
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+
+    #[test]
+    pub fn test_query_builder() {
+        let table = Table::new("users")
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_column(Column::new("user_name", DataTypes::String));
+
+        let condition = |key: &str, filter_type: &str, value: DataValue| {
+            QueryOps::Condition(QueryVal {
+                key: key.to_string(),
+                filter_type: filter_type.to_string(),
+                value,
+            })
+        };
+
+        let query = Query::table("users")
+            .eq("user_country", "US")
+            .and()
+            .gt("user_age", 21);
+        assert_eq!(query.table_name(), "users");
+        assert_eq!(
+            query.build(&table).unwrap(),
+            QueryOps::And(vec![
+                condition("user_country", "=", DataValue::String("US".to_string())),
+                condition(
+                    "user_age",
+                    ">",
+                    DataValue::Number(serde_json::Number::from(21))
+                ),
+            ])
+        );
+
+        // `or` binds looser than `and`, nested queries are a single condition
+        let query = Query::table("users")
+            .eq("user_country", "US")
+            .gt("user_age", 21)
+            .or()
+            .nest(
+                Query::table("users")
+                    .is_null("user_name")
+                    .or()
+                    .is_in("user_country", ["MX", "CO"]),
+            );
+        assert_eq!(
+            query.to_json(),
+            json!({ "or": [
+                { "and": [
+                    { "key": "user_country", "filterType": "=", "value": "US" },
+                    { "key": "user_age", "filterType": ">", "value": 21 }
+                ]},
+                { "or": [
+                    { "key": "user_name", "filterType": "is_null" },
+                    { "key": "user_country", "filterType": "in", "value": ["MX", "CO"] }
+                ]}
+            ]})
+        );
+        assert_eq!(
+            query.build(&table).unwrap().shape(),
+            "(user_country = ? AND user_age > ?) OR (user_name is_null OR user_country in ?)"
+        );
+
+        assert_eq!(
+            Query::table("users").or().build(&table).unwrap(),
+            QueryOps::And(vec![])
+        );
+        assert!(Query::table("users")
+            .gt("user_age", "old")
+            .build(&table)
+            .is_err());
+        assert!(Query::table("users")
+            .eq("unknown", 1)
+            .build(&table)
+            .is_err());
+    }
+}
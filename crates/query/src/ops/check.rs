@@ -0,0 +1,220 @@
+use crate::errors::QueryError;
+use crate::ops::query_ops::QueryOps;
+use schemajs_primitives::table::Table;
+use serde_json::Value;
+
+const OPERATORS: [(&str, &str); 7] = [
+    (">=", ">="),
+    ("<=", "<="),
+    ("!=", "!="),
+    ("==", "="),
+    ("=", "="),
+    (">", ">"),
+    ("<", "<"),
+];
+
+const WORD_OPERATORS: [&str; 5] = ["like", "in", "starts_with", "near", "within"];
+
+/// Parses the check expression of a column (such as `user_age >= 0 && user_age <= 150`) into a `QueryOps`
+/// that can be evaluated against the rows of `table`.
+///
+/// An expression is made of `column operator value` clauses joined by `&&` and `||` (`&&` binds tighter)
+/// and grouped with parentheses. Operators are the filter types of a query (`==` is accepted for `=`)
+/// and values are JSON literals, typed after the column just like in `QueryOps::from_json`.
+pub fn parse_check(table: &Table, expression: &str) -> Result<QueryOps, QueryError> {
+    let mut parser = CheckParser {
+        table,
+        expression,
+        position: 0,
+    };
+
+    let ops = parser.parse_or()?;
+    parser.skip_whitespace();
+
+    if parser.position != expression.len() {
+        return Err(parser.error());
+    }
+
+    Ok(ops)
+}
+
+struct CheckParser<'a> {
+    table: &'a Table,
+    expression: &'a str,
+    position: usize,
+}
+
+impl<'a> CheckParser<'a> {
+    fn error(&self) -> QueryError {
+        QueryError::InvalidCheck(self.expression.to_string())
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.expression[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn consume(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            return true;
+        }
+        false
+    }
+
+    fn parse_or(&mut self) -> Result<QueryOps, QueryError> {
+        let mut ops = vec![self.parse_and()?];
+        while self.consume("||") {
+            ops.push(self.parse_and()?);
+        }
+
+        Ok(match ops.len() {
+            1 => ops.remove(0),
+            _ => QueryOps::Or(ops),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<QueryOps, QueryError> {
+        let mut ops = vec![self.parse_atom()?];
+        while self.consume("&&") {
+            ops.push(self.parse_atom()?);
+        }
+
+        Ok(match ops.len() {
+            1 => ops.remove(0),
+            _ => QueryOps::And(ops),
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<QueryOps, QueryError> {
+        if self.consume("(") {
+            let ops = self.parse_or()?;
+            if !self.consume(")") {
+                return Err(self.error());
+            }
+            return Ok(ops);
+        }
+
+        let key = self.parse_identifier()?;
+        let filter_type = self.parse_operator()?;
+        let value = self.parse_value()?;
+
+        QueryOps::from_json(
+            self.table,
+            &serde_json::json!({ "key": key, "filterType": filter_type, "value": value }),
+        )
+    }
+
+    fn parse_identifier(&mut self) -> Result<&'a str, QueryError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+
+        if len == 0 {
+            return Err(self.error());
+        }
+
+        self.position += len;
+        Ok(&rest[..len])
+    }
+
+    fn parse_operator(&mut self) -> Result<&'static str, QueryError> {
+        for (token, filter_type) in OPERATORS {
+            if self.consume(token) {
+                return Ok(filter_type);
+            }
+        }
+
+        let word = self.parse_identifier()?;
+        WORD_OPERATORS
+            .into_iter()
+            .find(|operator| *operator == word)
+            .ok_or_else(|| self.error())
+    }
+
+    fn parse_value(&mut self) -> Result<Value, QueryError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+
+        // Strings, arrays and objects delimit themselves, anything else (numbers, booleans, null)
+        // ends where the expression moves on to the next token
+        if rest.starts_with(['"', '[', '{']) {
+            let mut values = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+            let value = match values.next() {
+                Some(Ok(value)) => value,
+                _ => return Err(self.error()),
+            };
+
+            self.position += values.byte_offset();
+            return Ok(value);
+        }
+
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, ')' | '&' | '|'))
+            .unwrap_or(rest.len());
+        let value = serde_json::from_str(&rest[..len]).map_err(|_| self.error())?;
+
+        self.position += len;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::check::parse_check;
+    use crate::row_json::{RowData, RowJson};
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+
+    #[test]
+    pub fn test_parse_check() {
+        let table = Table::new("users")
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_column(Column::new("user_email", DataTypes::String));
+
+        let row = |value: serde_json::Value| {
+            RowJson::from(RowData {
+                table: "users".to_string(),
+                value,
+            })
+        };
+
+        let check = parse_check(&table, "user_age >= 0 && user_age <= 150").unwrap();
+        assert_eq!(
+            check,
+            parse_check(&table, "(user_age>=0&&user_age<=150)").unwrap()
+        );
+        assert!(check.matches(&table, &row(serde_json::json!({ "user_age": 20 }))));
+        assert!(!check.matches(&table, &row(serde_json::json!({ "user_age": -1 }))));
+        assert!(!check.matches(&table, &row(serde_json::json!({ "user_age": 151 }))));
+
+        let check = parse_check(
+            &table,
+            "(user_email like \"%@outlook.com\" || user_email in [\"admin\"]) && user_age == 20",
+        )
+        .unwrap();
+        assert!(check.matches(
+            &table,
+            &row(serde_json::json!({ "user_email": "admin", "user_age": 20 }))
+        ));
+        assert!(!check.matches(
+            &table,
+            &row(serde_json::json!({ "user_email": "a@gmail.com", "user_age": 20 }))
+        ));
+
+        assert!(parse_check(&table, "user_age >= ").is_err());
+        assert!(parse_check(&table, "user_age >= 0 &&").is_err());
+        assert!(parse_check(&table, "user_age ~ 0").is_err());
+        assert!(parse_check(&table, "user_age >= \"0\"").is_err());
+        assert!(parse_check(&table, "unknown >= 0").is_err());
+        assert!(parse_check(&table, "(user_age >= 0").is_err());
+    }
+}
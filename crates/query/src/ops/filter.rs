@@ -0,0 +1,169 @@
+use crate::errors::QueryError;
+use crate::ops::query_ops::FilterType;
+use serde_json::{json, Map, Value};
+
+/// Converts a MongoDB-style filter into the JSON representation of queries (see `QueryOps::from_json`), which
+/// accepts them too: `{ "user_age": { "$gt": 21 }, "$or": [{ "user_country": "US" }, { "vip": true }] }`.
+///
+/// - `column: value` matches rows whose column equals `value`, `column: null` rows where it is null.
+/// - `column: { "$op": value, ... }` applies every operator to the column: `$eq`, `$ne`, `$gt`, `$gte`, `$lt`,
+///   `$lte`, `$in` (an array of candidates), `$contains` (an item of an array column), `$like`, `$regex`,
+///   `$startsWith`, `$near`, `$within` and `$exists`
+///   (`true` for non null values, `false` for null ones).
+/// - `$and` and `$or` combine an array of filters.
+///
+/// The entries of an object are joined with `and`. Filters without conditions are `null`, matching every row.
+///
+/// # Returns:
+/// - `Result<Value, QueryError>`: Fails for unknown operators, or values not shaped as described.
+pub fn from_filter(filter: &Value) -> Result<Value, QueryError> {
+    let obj = match filter {
+        Value::Null => return Ok(Value::Null),
+        Value::Object(obj) => obj,
+        _ => return Err(QueryError::InvalidQuery(filter.to_string())),
+    };
+
+    let mut conditions = vec![];
+    for (key, value) in obj {
+        match key.as_str() {
+            "$and" | "$or" => {
+                let filters = value
+                    .as_array()
+                    .ok_or_else(|| QueryError::InvalidQuery(value.to_string()))?
+                    .iter()
+                    .map(from_filter)
+                    .filter(|filter| !matches!(filter, Ok(Value::Null)))
+                    .collect::<Result<Vec<Value>, QueryError>>()?;
+                if !filters.is_empty() {
+                    let mut combined = Map::new();
+                    combined.insert(key[1..].to_string(), Value::Array(filters));
+                    conditions.push(Value::Object(combined));
+                }
+            }
+            _ if key.starts_with('$') => {
+                return Err(QueryError::InvalidFilterType(key.to_string()))
+            }
+            _ => conditions.extend(column_conditions(key, value)?),
+        }
+    }
+
+    Ok(match conditions.len() {
+        0 => Value::Null,
+        1 => conditions.remove(0),
+        _ => json!({ "and": conditions }),
+    })
+}
+
+// Conditions of the filter `key: value` on a column
+fn column_conditions(key: &str, value: &Value) -> Result<Vec<Value>, QueryError> {
+    let operators = match value {
+        Value::Object(obj) if obj.keys().any(|op| op.starts_with('$')) => obj,
+        Value::Null => return Ok(vec![condition(key, FilterType::IsNull, None)]),
+        _ => return Ok(vec![condition(key, FilterType::Equal, Some(value))]),
+    };
+
+    operators
+        .iter()
+        .map(|(op, value)| {
+            let filter_type = match op.as_str() {
+                "$eq" if value.is_null() => return Ok(condition(key, FilterType::IsNull, None)),
+                "$ne" if value.is_null() => return Ok(condition(key, FilterType::IsNotNull, None)),
+                "$exists" => {
+                    return match value.as_bool() {
+                        Some(true) => Ok(condition(key, FilterType::IsNotNull, None)),
+                        Some(false) => Ok(condition(key, FilterType::IsNull, None)),
+                        None => Err(QueryError::InvalidQueryValue(key.to_string())),
+                    }
+                }
+                "$eq" => FilterType::Equal,
+                "$ne" => FilterType::NotEqual,
+                "$gt" => FilterType::GreaterThan,
+                "$gte" => FilterType::GreaterOrEqualTo,
+                "$lt" => FilterType::LowerThan,
+                "$lte" => FilterType::LowerOrEqualTo,
+                "$in" => FilterType::In,
+                "$contains" => FilterType::Contains,
+                "$like" => FilterType::Like,
+                "$regex" => FilterType::Regex,
+                "$startsWith" => FilterType::StartsWith,
+                "$near" => FilterType::Near,
+                "$within" => FilterType::Within,
+                _ => return Err(QueryError::InvalidFilterType(op.to_string())),
+            };
+
+            Ok(condition(key, filter_type, Some(value)))
+        })
+        .collect()
+}
+
+fn condition(key: &str, filter_type: FilterType, value: Option<&Value>) -> Value {
+    let mut condition = Map::new();
+    condition.insert("key".to_string(), Value::from(key));
+    condition.insert(
+        "filterType".to_string(),
+        Value::from(filter_type.to_string()),
+    );
+    if let Some(value) = value {
+        condition.insert("value".to_string(), value.clone());
+    }
+    Value::Object(condition)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::filter::from_filter;
+    use crate::ops::query_ops::QueryOps;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+
+    #[test]
+    pub fn test_query_ops_from_filter() {
+        assert_eq!(
+            from_filter(&json!({ "user_age": { "$gt": 21, "$lte": 65 }, "user_name": null }))
+                .unwrap(),
+            json!({ "and": [
+                { "key": "user_age", "filterType": ">", "value": 21 },
+                { "key": "user_age", "filterType": "<=", "value": 65 },
+                { "key": "user_name", "filterType": "is_null" }
+            ]})
+        );
+        assert_eq!(
+            from_filter(&json!({ "$or": [
+                { "user_country": "US" },
+                { "user_country": { "$in": ["MX", "CO"] }, "user_name": { "$exists": true } }
+            ]}))
+            .unwrap(),
+            json!({ "or": [
+                { "key": "user_country", "filterType": "=", "value": "US" },
+                { "and": [
+                    { "key": "user_country", "filterType": "in", "value": ["MX", "CO"] },
+                    { "key": "user_name", "filterType": "is_not_null" }
+                ]}
+            ]})
+        );
+        assert_eq!(from_filter(&json!({})).unwrap(), json!(null));
+        assert_eq!(from_filter(&json!({ "$and": [{}] })).unwrap(), json!(null));
+        assert!(from_filter(&json!({ "user_age": { "$nin": [1] } }))
+            .unwrap_err()
+            .is_invalid_filter_type());
+        assert!(from_filter(&json!({ "$nor": [] })).is_err());
+        assert!(from_filter(&json!({ "$or": {} })).is_err());
+
+        // `QueryOps::from_json` takes filters as they are
+        let table = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+        let filter = json!({
+            "$or": [{ "user_name": "andres" }, { "user_name": { "$startsWith": "ca" } }],
+            "user_age": { "$gte": 21 }
+        });
+        assert_eq!(
+            QueryOps::from_json(&table, &filter).unwrap().shape(),
+            "(user_name = ? OR user_name starts_with ?) AND user_age >= ?"
+        );
+        assert!(QueryOps::from_json(&table, &json!({ "user_age": "old" })).is_err());
+        assert!(QueryOps::from_json(&table, &json!({ "unknown": 1 })).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::ops::query_ops::FilterType;
+use schemajs_primitives::column::types::DataValue;
+use std::collections::BTreeSet;
+
+/// Mean radius of the earth, in meters.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+const GEOHASH_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Length of the geohashes stored in geo indexes, cells of about 3.7cm by 1.9cm.
+pub const GEOHASH_PRECISION: usize = 12;
+
+/// Upper bound of the geohash cells scanned to answer a single geo filter.
+const MAX_COVERING_CELLS: f64 = 64.0;
+
+/// Point on the earth, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl GeoPoint {
+    /// Reads the value of a `Point` column (`{ "lat": .., "lng": .. }`).
+    pub fn from_value(value: &DataValue) -> Option<Self> {
+        let fields = value.as_object()?;
+
+        Some(GeoPoint {
+            lat: fields.get("lat")?.to_f64()?,
+            lng: fields.get("lng")?.to_f64()?,
+        })
+    }
+
+    /// Great-circle distance to `other`, in meters.
+    pub fn distance(&self, other: &GeoPoint) -> f64 {
+        let (lat, other_lat) = (self.lat.to_radians(), other.lat.to_radians());
+        let half_lat = (other_lat - lat) / 2.0;
+        let half_lng = (other.lng - self.lng).to_radians() / 2.0;
+
+        let a = half_lat.sin().powi(2) + lat.cos() * other_lat.cos() * half_lng.sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+
+    /// Geohash of the point with `precision` characters. Points sharing a prefix lie in the same cell,
+    /// so the geohashes of nearby points sort close to each other.
+    pub fn geohash(&self, precision: usize) -> String {
+        let mut lat_range = (-90.0, 90.0);
+        let mut lng_range = (-180.0, 180.0);
+        let mut hash = String::with_capacity(precision);
+        let (mut bits, mut bit_count, mut is_lng) = (0usize, 0, true);
+
+        while hash.len() < precision {
+            let (range, value) = if is_lng {
+                (&mut lng_range, self.lng)
+            } else {
+                (&mut lat_range, self.lat)
+            };
+
+            let mid = (range.0 + range.1) / 2.0;
+            bits <<= 1;
+            if value >= mid {
+                bits |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+
+            is_lng = !is_lng;
+            bit_count += 1;
+
+            if bit_count == 5 {
+                hash.push(GEOHASH_ALPHABET[bits] as char);
+                bits = 0;
+                bit_count = 0;
+            }
+        }
+
+        hash
+    }
+}
+
+/// Area between two latitudes and two longitudes, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+impl BoundingBox {
+    /// Smallest box holding every point within `radius` meters of `center`.
+    /// Boxes reaching a pole or the antimeridian span every longitude.
+    pub fn around(center: &GeoPoint, radius: f64) -> Self {
+        let angle = radius / EARTH_RADIUS;
+        let delta_lat = angle.to_degrees();
+
+        let mut bbox = BoundingBox {
+            min_lat: (center.lat - delta_lat).max(-90.0),
+            min_lng: -180.0,
+            max_lat: (center.lat + delta_lat).min(90.0),
+            max_lng: 180.0,
+        };
+
+        let reaches_pole = bbox.min_lat <= -90.0 || bbox.max_lat >= 90.0;
+        let sin_delta_lng = angle.sin() / center.lat.to_radians().cos();
+
+        if !reaches_pole && sin_delta_lng < 1.0 {
+            let delta_lng = sin_delta_lng.asin().to_degrees();
+            if center.lng - delta_lng >= -180.0 && center.lng + delta_lng <= 180.0 {
+                bbox.min_lng = center.lng - delta_lng;
+                bbox.max_lng = center.lng + delta_lng;
+            }
+        }
+
+        bbox
+    }
+
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        (self.min_lat..=self.max_lat).contains(&point.lat)
+            && (self.min_lng..=self.max_lng).contains(&point.lng)
+    }
+
+    /// Geohash prefixes whose cells cover the box: every point of the box has a geohash starting with one of them.
+    /// Uses the longest prefixes that need at most `MAX_COVERING_CELLS` cells.
+    pub fn covering_geohashes(&self) -> Vec<String> {
+        for precision in (1..=GEOHASH_PRECISION).rev() {
+            let lng_cells = 2f64.powi(((precision * 5 + 1) / 2) as i32);
+            let lat_cells = 2f64.powi((precision * 5 / 2) as i32);
+            let (cell_width, cell_height) = (360.0 / lng_cells, 180.0 / lat_cells);
+
+            let cell = |value: f64, min: f64, size: f64, cells: f64| {
+                ((value - min) / size).floor().clamp(0.0, cells - 1.0)
+            };
+            let (first_col, last_col) = (
+                cell(self.min_lng, -180.0, cell_width, lng_cells),
+                cell(self.max_lng, -180.0, cell_width, lng_cells),
+            );
+            let (first_row, last_row) = (
+                cell(self.min_lat, -90.0, cell_height, lat_cells),
+                cell(self.max_lat, -90.0, cell_height, lat_cells),
+            );
+
+            if (last_col - first_col + 1.0) * (last_row - first_row + 1.0) > MAX_COVERING_CELLS {
+                continue;
+            }
+
+            // The center of every cell is enough to get its geohash
+            let mut prefixes = BTreeSet::new();
+            for row in first_row as u64..=last_row as u64 {
+                for col in first_col as u64..=last_col as u64 {
+                    let center = GeoPoint {
+                        lat: -90.0 + (row as f64 + 0.5) * cell_height,
+                        lng: -180.0 + (col as f64 + 0.5) * cell_width,
+                    };
+                    prefixes.insert(center.geohash(precision));
+                }
+            }
+
+            return prefixes.into_iter().collect();
+        }
+
+        vec![]
+    }
+}
+
+/// Geo filter of a condition on a `Point` column.
+///
+/// - `near` matches points within `radius` meters of a point: `{ "lat": .., "lng": .., "radius": .. }`.
+/// - `within` matches points inside a bounding box: `{ "minLat": .., "minLng": .., "maxLat": .., "maxLng": .. }`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoFilter {
+    Near { center: GeoPoint, radius: f64 },
+    Within(BoundingBox),
+}
+
+impl GeoFilter {
+    /// Reads the value of a `near` or `within` condition.
+    /// Returns `None` for other filter types and for values missing a field or out of range.
+    pub fn from_value(filter_type: &FilterType, value: &DataValue) -> Option<Self> {
+        let fields = value.as_object()?;
+        let field = |name: &str| fields.get(name).and_then(|value| value.to_f64());
+        let is_point = |point: &GeoPoint| point.lat.abs() <= 90.0 && point.lng.abs() <= 180.0;
+
+        match filter_type {
+            FilterType::Near => {
+                let center = GeoPoint {
+                    lat: field("lat")?,
+                    lng: field("lng")?,
+                };
+                let radius = field("radius")?;
+
+                (is_point(&center) && radius >= 0.0).then_some(GeoFilter::Near { center, radius })
+            }
+            FilterType::Within => {
+                let bbox = BoundingBox {
+                    min_lat: field("minLat")?,
+                    min_lng: field("minLng")?,
+                    max_lat: field("maxLat")?,
+                    max_lng: field("maxLng")?,
+                };
+                let min = GeoPoint {
+                    lat: bbox.min_lat,
+                    lng: bbox.min_lng,
+                };
+                let max = GeoPoint {
+                    lat: bbox.max_lat,
+                    lng: bbox.max_lng,
+                };
+
+                (is_point(&min) && is_point(&max) && min.lat <= max.lat && min.lng <= max.lng)
+                    .then_some(GeoFilter::Within(bbox))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, point: &GeoPoint) -> bool {
+        match self {
+            GeoFilter::Near { center, radius } => center.distance(point) <= *radius,
+            GeoFilter::Within(bbox) => bbox.contains(point),
+        }
+    }
+
+    /// Box holding every point matched by the filter.
+    pub fn bounding_box(&self) -> BoundingBox {
+        match self {
+            GeoFilter::Near { center, radius } => BoundingBox::around(center, *radius),
+            GeoFilter::Within(bbox) => *bbox,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::geo::{BoundingBox, GeoPoint};
+
+    #[test]
+    pub fn test_geo() {
+        let caracas = GeoPoint {
+            lat: 10.4806,
+            lng: -66.9036,
+        };
+        let valencia = GeoPoint {
+            lat: 10.1620,
+            lng: -68.0077,
+        };
+
+        let reference = GeoPoint {
+            lat: 57.64911,
+            lng: 10.40744,
+        };
+        assert_eq!(reference.geohash(11), "u4pruydqqvj");
+        assert_eq!(caracas.geohash(5), "d9b7t");
+
+        let distance = caracas.distance(&valencia);
+        assert!(distance > 120_000.0 && distance < 130_000.0);
+
+        let bbox = BoundingBox::around(&caracas, 130_000.0);
+        assert!(bbox.contains(&valencia));
+        assert!(!BoundingBox::around(&caracas, 100_000.0).contains(&valencia));
+
+        let prefixes = bbox.covering_geohashes();
+        assert!(!prefixes.is_empty() && prefixes.len() <= 64);
+        for point in [caracas, valencia] {
+            let geohash = point.geohash(12);
+            assert!(prefixes.iter().any(|prefix| geohash.starts_with(prefix)));
+        }
+
+        // Every longitude once the box reaches a pole
+        let polar = BoundingBox::around(
+            &GeoPoint {
+                lat: 89.9,
+                lng: 0.0,
+            },
+            50_000.0,
+        );
+        assert_eq!((polar.min_lng, polar.max_lng), (-180.0, 180.0));
+    }
+}
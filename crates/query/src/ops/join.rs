@@ -0,0 +1,67 @@
+use crate::ops::query_ops::QueryOps;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinType {
+    /// Only rows with a match in both tables.
+    Inner,
+    /// Every row of the left table, with its match in the right table if any.
+    Left,
+}
+
+/// Describes how to combine the rows of a search with the rows of another table.
+///
+/// # Fields:
+/// - `table`: The table being joined (right side).
+/// - `query`: Filter applied to the rows of `table` before joining.
+/// - `left_column`: Column of the searched table (left side) compared for equality.
+/// - `right_column`: Column of `table` compared for equality.
+/// - `join_type`: Whether rows without a match on the right side are kept.
+///
+/// # Example
+///
+/// ```ignore
+/// let join = Join::inner("orders", "user_id", "user_id");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub table: String,
+    pub query: QueryOps,
+    pub left_column: String,
+    pub right_column: String,
+    pub join_type: JoinType,
+}
+
+impl Join {
+    pub fn new(table: &str, left_column: &str, right_column: &str, join_type: JoinType) -> Self {
+        Self {
+            table: table.to_string(),
+            query: QueryOps::And(vec![]),
+            left_column: left_column.to_string(),
+            right_column: right_column.to_string(),
+            join_type,
+        }
+    }
+
+    pub fn inner(table: &str, left_column: &str, right_column: &str) -> Self {
+        Self::new(table, left_column, right_column, JoinType::Inner)
+    }
+
+    pub fn left(table: &str, left_column: &str, right_column: &str) -> Self {
+        Self::new(table, left_column, right_column, JoinType::Left)
+    }
+
+    pub fn set_query(mut self, query: QueryOps) -> Self {
+        self.query = query;
+        self
+    }
+}
+
+/// A row of the searched table combined with a matching row of the joined table.
+/// `right` is only `None` for `JoinType::Left` when there is no match.
+#[derive(Debug)]
+pub struct JoinedRow<T> {
+    pub left: T,
+    pub right: Option<T>,
+}
@@ -1 +1,10 @@
+pub mod aggregate;
+pub mod between;
+pub mod builder;
+pub mod check;
+pub mod filter;
+pub mod geo;
+pub mod join;
+pub mod pattern;
+pub mod pipeline;
 pub mod query_ops;
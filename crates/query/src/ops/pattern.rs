@@ -0,0 +1,94 @@
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Patterns a thread keeps compiled, the least recently used one is evicted to make room for a new one.
+const MAX_CACHED_PATTERNS: usize = 64;
+
+/// Compiled `regex` patterns by source, `None` for invalid ones.
+///
+/// # Fields:
+/// - `patterns`: Compiled pattern and the last time it was used, by source.
+/// - `clock`: Bumped on every use, orders the patterns by how recently they were used.
+#[derive(Default)]
+struct PatternCache {
+    patterns: HashMap<String, (Option<Regex>, u64)>,
+    clock: u64,
+}
+
+impl PatternCache {
+    fn get(&mut self, pattern: &str) -> Option<&Regex> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        if !self.patterns.contains_key(pattern) {
+            if self.patterns.len() >= MAX_CACHED_PATTERNS {
+                self.evict();
+            }
+            self.patterns
+                .insert(pattern.to_string(), (Regex::new(pattern).ok(), clock));
+        }
+
+        let (regex, used) = self.patterns.get_mut(pattern)?;
+        *used = clock;
+        regex.as_ref()
+    }
+
+    /// Drops the least recently used pattern.
+    fn evict(&mut self) {
+        let oldest = self
+            .patterns
+            .iter()
+            .min_by_key(|(_, (_, used))| *used)
+            .map(|(pattern, _)| pattern.clone());
+        if let Some(oldest) = oldest {
+            self.patterns.remove(&oldest);
+        }
+    }
+}
+
+thread_local! {
+    static PATTERNS: RefCell<PatternCache> = RefCell::new(PatternCache::default());
+}
+
+/// Runs `f` with `pattern` compiled. Patterns are compiled once per thread and reused by the following calls,
+/// so a query evaluating a `regex` condition over many rows compiles its pattern once rather than once per row.
+fn with_pattern<R, F: FnOnce(Option<&Regex>) -> R>(pattern: &str, f: F) -> R {
+    PATTERNS.with(|patterns| f(patterns.borrow_mut().get(pattern)))
+}
+
+/// Whether `pattern` is a valid regular expression (see the syntax of the `regex` crate).
+pub fn is_valid(pattern: &str) -> bool {
+    with_pattern(pattern, |regex| regex.is_some())
+}
+
+/// Whether `pattern` matches somewhere in `value`, anchors (`^...$`) match it whole.
+/// Invalid patterns match nothing.
+pub fn is_match(value: &str, pattern: &str) -> bool {
+    with_pattern(pattern, |regex| {
+        regex.map_or(false, |regex| regex.is_match(value))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::pattern::{PatternCache, MAX_CACHED_PATTERNS};
+
+    #[test]
+    pub fn test_pattern_cache_evicts_least_recently_used() {
+        let mut cache = PatternCache::default();
+        assert!(cache.get("^a").is_some());
+        assert!(cache.get("(").is_none());
+
+        for i in 0..MAX_CACHED_PATTERNS {
+            // `^a` stays the most recently used one
+            cache.get("^a");
+            cache.get(&format!("^{i}"));
+            assert!(cache.patterns.len() <= MAX_CACHED_PATTERNS);
+        }
+
+        assert!(cache.patterns.contains_key("^a"));
+        assert!(!cache.patterns.contains_key("("));
+        assert!(cache.get("^a").unwrap().is_match("abc"));
+    }
+}
@@ -0,0 +1,503 @@
+use crate::errors::QueryError;
+use crate::ops::aggregate::{Aggregate, AggregateFunction, AggregateGroup, AggregateState};
+use crate::ops::query_ops::QueryOps;
+use crate::row::Row;
+use crate::row_json::{RowData, RowJson};
+use crate::search::search_opts::{SearchOpts, SortBy, SortDirection};
+use schemajs_primitives::column::types::{DataTypes, DataValue};
+use schemajs_primitives::column::Column;
+use schemajs_primitives::table::Table;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Stage of a `Pipeline`, reading the documents produced by the previous stage.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineStage {
+    /// Keeps the documents matched by a query, in any form `QueryOps::from_json` accepts. Its values are typed
+    /// after the fields of the documents, e.g. the aggregates of a previous `Group`.
+    Match(Value),
+    /// Buckets the documents by the values of `by` and evaluates `aggregates` per bucket, producing a document per
+    /// bucket holding both (see `AggregateGroup::to_json`). Buckets are ordered by their keys.
+    Group {
+        by: Vec<String>,
+        aggregates: Vec<Aggregate>,
+    },
+    /// Keeps the listed fields of the documents.
+    Project(Vec<String>),
+    /// Sorts the documents, criteria applied in order. Documents missing a field come first when sorting
+    /// ascending and last when sorting descending, as they do in searches.
+    Sort(Vec<SortBy>),
+    /// Keeps the first documents.
+    Limit(usize),
+}
+
+/// Multi-stage aggregation over the rows of a table, in the spirit of MongoDB's aggregation framework:
+/// every stage reads the documents produced by the previous one, the first stage reading the rows of the table.
+///
+/// Run through `QuerySearchManager::pipeline`. The stages the table can answer are not run in memory: leading
+/// `Match` stages are the query the table is searched with (so they use its indexes), a `Group` following them
+/// reads only the columns it needs, and a `Sort` or `Limit` following them is applied by the search.
+///
+/// Its JSON form (see `Pipeline::from_json`) is an array of stages:
+/// `[{ "$match": { "user_age": { "$gt": 21 } } }, { "$group": { "by": ["user_country"], "aggregates": [{ "function": "count" }] } },
+/// { "$project": ["user_country", "count"] }, { "$sort": [{ "column": "count", "direction": "desc" }] }, { "$limit": 10 }]`.
+///
+/// # Example
+///
+/// ```ignore
+/// let pipeline = Pipeline::new()
+///     .filter(json!({ "user_age": { "$gt": 21 } }))
+///     .group(&["user_country"], vec![Aggregate::count()])
+///     .sort("count", SortDirection::Desc)
+///     .limit(10);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    pub stages: Vec<PipelineStage>,
+}
+
+/// How the rows of a table are read to run a `Pipeline` over them, see `Pipeline::source`.
+///
+/// # Fields:
+/// - `ops`: The leading `Match` stages, joined with `and`.
+/// - `opts`: The `Sort` and `Limit` stages following them, unless a `Group` does.
+/// - `group`: The grouping columns and aggregates of the `Group` stage following them, if any.
+/// - `rest`: Stages run on the documents read.
+pub(crate) struct PipelineSource<'a> {
+    pub ops: QueryOps,
+    pub opts: SearchOpts,
+    pub group: Option<(&'a [String], &'a [Aggregate])>,
+    pub rest: &'a [PipelineStage],
+}
+
+#[derive(Deserialize)]
+struct GroupStage {
+    by: Vec<String>,
+    #[serde(default)]
+    aggregates: Vec<Aggregate>,
+}
+
+/// Reads the `value` of a stage of the JSON form of a pipeline.
+fn parse_stage<D: DeserializeOwned>(stage: &Value, value: &Value) -> Result<D, QueryError> {
+    serde_json::from_value(value.clone())
+        .map_err(|_| QueryError::InvalidPipeline(stage.to_string()))
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a `Match` stage, `query` being a query in JSON form, e.g. `Query::to_json`, or a MongoDB-style filter.
+    pub fn filter(mut self, query: Value) -> Self {
+        self.stages.push(PipelineStage::Match(query));
+        self
+    }
+
+    pub fn group(mut self, by: &[&str], aggregates: Vec<Aggregate>) -> Self {
+        self.stages.push(PipelineStage::Group {
+            by: by.iter().map(|column| column.to_string()).collect(),
+            aggregates,
+        });
+        self
+    }
+
+    pub fn project(mut self, fields: &[&str]) -> Self {
+        self.stages.push(PipelineStage::Project(
+            fields.iter().map(|field| field.to_string()).collect(),
+        ));
+        self
+    }
+
+    /// Adds a sort criteria, to the `Sort` stage right before if any.
+    pub fn sort(mut self, field: &str, direction: SortDirection) -> Self {
+        let sort_by = SortBy {
+            column: field.to_string(),
+            direction,
+        };
+        match self.stages.last_mut() {
+            Some(PipelineStage::Sort(sort)) => sort.push(sort_by),
+            _ => self.stages.push(PipelineStage::Sort(vec![sort_by])),
+        }
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.stages.push(PipelineStage::Limit(limit));
+        self
+    }
+
+    /// Builds a `Pipeline` out of its JSON representation (as sent from JS), see `Pipeline`.
+    ///
+    /// # Returns:
+    /// - `Result<Pipeline, QueryError>`: `InvalidPipeline` for anything but an array of stages, each an object
+    ///   holding a single `$match`, `$group`, `$project`, `$sort` or `$limit` entry.
+    pub fn from_json(pipeline: &Value) -> Result<Self, QueryError> {
+        let stages = pipeline
+            .as_array()
+            .ok_or_else(|| QueryError::InvalidPipeline(pipeline.to_string()))?;
+
+        let stages = stages
+            .iter()
+            .map(|stage| {
+                let invalid = || QueryError::InvalidPipeline(stage.to_string());
+                let (name, value) = match stage.as_object() {
+                    Some(stage) if stage.len() == 1 => stage.iter().next().unwrap(),
+                    _ => return Err(invalid()),
+                };
+                match name.as_str() {
+                    "$match" => Ok(PipelineStage::Match(value.clone())),
+                    "$group" => {
+                        let group: GroupStage = parse_stage(stage, value)?;
+                        Ok(PipelineStage::Group {
+                            by: group.by,
+                            aggregates: group.aggregates,
+                        })
+                    }
+                    "$project" => Ok(PipelineStage::Project(parse_stage(stage, value)?)),
+                    "$sort" => Ok(PipelineStage::Sort(parse_stage(stage, value)?)),
+                    "$limit" => Ok(PipelineStage::Limit(parse_stage(stage, value)?)),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect::<Result<Vec<PipelineStage>, QueryError>>()?;
+
+        Ok(Self { stages })
+    }
+
+    /// Splits the pipeline into the search reading the rows of `table` and the stages left to run in memory,
+    /// see `PipelineSource`.
+    pub(crate) fn source(&self, table: &Table) -> Result<PipelineSource, QueryError> {
+        let mut matches = vec![];
+        let mut opts = SearchOpts::new();
+        let mut stages = self.stages.as_slice();
+
+        while let Some((PipelineStage::Match(query), rest)) = stages.split_first() {
+            matches.push(QueryOps::from_json(table, query)?);
+            stages = rest;
+        }
+        let ops = match matches.len() {
+            1 => matches.remove(0),
+            _ => QueryOps::And(matches),
+        };
+
+        if let Some((PipelineStage::Group { by, aggregates }, rest)) = stages.split_first() {
+            return Ok(PipelineSource {
+                ops,
+                opts,
+                group: Some((by, aggregates)),
+                rest,
+            });
+        }
+        if let Some((PipelineStage::Sort(sort), rest)) = stages.split_first() {
+            opts.sort = sort.clone();
+            stages = rest;
+        }
+        if let Some((PipelineStage::Limit(limit), rest)) = stages.split_first() {
+            opts.limit = Some(*limit);
+            stages = rest;
+        }
+
+        Ok(PipelineSource {
+            ops,
+            opts,
+            group: None,
+            rest: stages,
+        })
+    }
+
+    /// Columns describing the documents the pipeline produces out of the rows of `table`.
+    ///
+    /// # Returns:
+    /// - `Result<Table, QueryError>`: `InvalidColumn` when a `Group` or `Project` stage reads a field the previous
+    ///   stages don't produce.
+    pub fn schema(&self, table: &Table) -> Result<Table, QueryError> {
+        self.stages
+            .iter()
+            .try_fold(table.clone(), |schema, stage| match stage {
+                PipelineStage::Group { by, aggregates } => {
+                    Self::group_schema(&schema, by, aggregates)
+                }
+                PipelineStage::Project(fields) => Self::project_schema(&schema, fields),
+                PipelineStage::Match(_) | PipelineStage::Sort(_) | PipelineStage::Limit(_) => {
+                    Ok(schema)
+                }
+            })
+    }
+
+    /// Runs `stages` over `documents`, whose fields are described by the columns of `schema`.
+    pub(crate) fn run(
+        stages: &[PipelineStage],
+        mut schema: Table,
+        mut documents: Vec<Value>,
+    ) -> Result<Vec<Value>, QueryError> {
+        for stage in stages {
+            let (next_schema, next_documents) = Self::run_stage(stage, schema, documents)?;
+            schema = next_schema;
+            documents = next_documents;
+        }
+
+        Ok(documents)
+    }
+
+    fn run_stage(
+        stage: &PipelineStage,
+        schema: Table,
+        mut documents: Vec<Value>,
+    ) -> Result<(Table, Vec<Value>), QueryError> {
+        let row = |document: &Value| {
+            RowJson::from(RowData {
+                table: schema.name.clone(),
+                value: document.clone(),
+            })
+        };
+        let column = |name: &String| {
+            schema
+                .resolve_column(name.as_str())
+                .map(|column| column.into_owned())
+                .ok_or_else(|| QueryError::InvalidColumn(name.clone()))
+        };
+
+        match stage {
+            PipelineStage::Match(query) => {
+                let ops = QueryOps::from_json(&schema, query)?;
+                documents.retain(|document| ops.matches(&schema, &row(document)));
+                Ok((schema, documents))
+            }
+            PipelineStage::Group { by, aggregates } => {
+                let group_schema = Self::group_schema(&schema, by, aggregates)?;
+                let by_columns = by.iter().map(column).collect::<Result<Vec<_>, _>>()?;
+                let aggregate_columns = aggregates
+                    .iter()
+                    .map(|aggregate| aggregate.column.as_ref().map(column).transpose())
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut groups: BTreeMap<Vec<DataValue>, Vec<AggregateState>> = BTreeMap::new();
+                for document in documents.iter() {
+                    let row = row(document);
+                    let key: Vec<DataValue> = by_columns
+                        .iter()
+                        .map(|column| row.get_value(column).unwrap_or(DataValue::Null))
+                        .collect();
+
+                    let states = groups.entry(key).or_insert_with(|| {
+                        aggregates
+                            .iter()
+                            .map(|aggregate| AggregateState::new(aggregate.clone()))
+                            .collect()
+                    });
+                    for (state, column) in states.iter_mut().zip(aggregate_columns.iter()) {
+                        let value = column.as_ref().and_then(|column| row.get_value(column));
+                        state.accumulate(value.as_ref())?;
+                    }
+                }
+
+                let documents = groups
+                    .into_iter()
+                    .map(|(key, states)| {
+                        AggregateGroup {
+                            keys: by.iter().cloned().zip(key).collect(),
+                            values: aggregates
+                                .iter()
+                                .zip(states.iter())
+                                .map(|(aggregate, state)| (aggregate.alias(), state.finish()))
+                                .collect(),
+                        }
+                        .to_json()
+                    })
+                    .collect();
+
+                Ok((group_schema, documents))
+            }
+            PipelineStage::Project(fields) => {
+                let projected = Self::project_schema(&schema, fields)?;
+
+                let documents = documents
+                    .iter()
+                    .map(|document| {
+                        let row = row(document);
+                        let values: Map<String, Value> = fields
+                            .iter()
+                            .filter_map(|field| {
+                                let value = row.field(field)?;
+                                Some((field.clone(), value.clone()))
+                            })
+                            .collect();
+                        Value::Object(values)
+                    })
+                    .collect();
+
+                Ok((projected, documents))
+            }
+            PipelineStage::Sort(sort) => {
+                let columns = sort
+                    .iter()
+                    .map(|sort_by| column(&sort_by.column))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut keyed: Vec<(Vec<Option<DataValue>>, Value)> = documents
+                    .into_iter()
+                    .map(|document| {
+                        let row = row(&document);
+                        let key = columns
+                            .iter()
+                            .map(|column| row.get_value(column).map(|value| column.collate(value)))
+                            .collect();
+                        (key, document)
+                    })
+                    .collect();
+
+                // Stable, so documents with equal keys keep the order they were read in
+                keyed.sort_by(|(a, _), (b, _)| {
+                    sort.iter()
+                        .zip(a.iter().zip(b.iter()))
+                        .map(|(sort_by, (a, b))| match sort_by.direction {
+                            SortDirection::Asc => a.cmp(b),
+                            SortDirection::Desc => b.cmp(a),
+                        })
+                        .find(|ordering| *ordering != Ordering::Equal)
+                        .unwrap_or(Ordering::Equal)
+                });
+
+                Ok((
+                    schema,
+                    keyed.into_iter().map(|(_, document)| document).collect(),
+                ))
+            }
+            PipelineStage::Limit(limit) => {
+                documents.truncate(*limit);
+                Ok((schema, documents))
+            }
+        }
+    }
+
+    /// Columns describing the documents produced by a `Group` stage over documents described by `schema`:
+    /// the grouping columns as they are, `count`, `sum` and `avg` as numbers, `min` and `max` as the column
+    /// they aggregate.
+    pub(crate) fn group_schema(
+        schema: &Table,
+        by: &[String],
+        aggregates: &[Aggregate],
+    ) -> Result<Table, QueryError> {
+        let column = |name: &String| {
+            schema
+                .resolve_column(name.as_str())
+                .map(|column| column.into_owned())
+                .ok_or_else(|| QueryError::InvalidColumn(name.clone()))
+        };
+
+        let mut group_schema = Table::new(&schema.name);
+        for name in by {
+            let mut by_column = column(name)?;
+            by_column.primary_key = false;
+            group_schema = group_schema.add_column(by_column);
+        }
+
+        for aggregate in aggregates {
+            let aggregated = aggregate.column.as_ref().map(column).transpose()?;
+            let value_column = match (aggregate.function, aggregated) {
+                (AggregateFunction::Min | AggregateFunction::Max, Some(mut aggregated)) => {
+                    aggregated.name = aggregate.alias();
+                    aggregated.primary_key = false;
+                    aggregated
+                }
+                _ => Column::new(&aggregate.alias(), DataTypes::Number),
+            };
+            group_schema = group_schema.add_column(value_column);
+        }
+
+        Ok(group_schema)
+    }
+
+    /// Columns describing the documents produced by a `Project` stage over documents described by `schema`.
+    fn project_schema(schema: &Table, fields: &[String]) -> Result<Table, QueryError> {
+        let mut projected = Table::new(&schema.name);
+        for field in fields {
+            let mut field_column = schema
+                .resolve_column(field.as_str())
+                .map(|column| column.into_owned())
+                .ok_or_else(|| QueryError::InvalidColumn(field.clone()))?;
+            field_column.primary_key = false;
+            projected = projected.add_column(field_column);
+        }
+
+        Ok(projected)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::aggregate::{Aggregate, AggregateFunction};
+    use crate::ops::pipeline::Pipeline;
+    use crate::search::search_opts::SortDirection;
+    use schemajs_primitives::column::types::DataTypes;
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+
+    #[test]
+    pub fn test_pipeline() {
+        let pipeline = Pipeline::from_json(&json!([
+            { "$match": { "user_age": { "$gt": 21 } } },
+            { "$group": { "by": ["user_country"], "aggregates": [{ "function": "count" }, { "function": "max", "column": "user_age" }] } },
+            { "$match": { "count": { "$gt": 1 } } },
+            { "$project": ["user_country", "max_user_age"] },
+            { "$sort": [{ "column": "max_user_age", "direction": "desc" }] },
+            { "$limit": 1 }
+        ]))
+        .unwrap();
+        assert_eq!(
+            pipeline,
+            Pipeline::new()
+                .filter(json!({ "user_age": { "$gt": 21 } }))
+                .group(
+                    &["user_country"],
+                    vec![
+                        Aggregate::count(),
+                        Aggregate::new(AggregateFunction::Max, "user_age")
+                    ]
+                )
+                .filter(json!({ "count": { "$gt": 1 } }))
+                .project(&["user_country", "max_user_age"])
+                .sort("max_user_age", SortDirection::Desc)
+                .limit(1)
+        );
+        assert!(Pipeline::from_json(&json!({ "$limit": 1 })).is_err());
+        assert!(Pipeline::from_json(&json!([{ "$limit": 1, "$project": [] }])).is_err());
+        assert!(Pipeline::from_json(&json!([{ "$unwind": "tags" }]))
+            .unwrap_err()
+            .is_invalid_pipeline());
+
+        // The leading match and group are answered by the table, the rest runs on the groups
+        let table = Table::new("users")
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+        let source = pipeline.source(&table).unwrap();
+        let (by, aggregates) = source.group.unwrap();
+        assert_eq!(source.rest.len(), 4);
+
+        let schema = Pipeline::group_schema(&table, by, aggregates).unwrap();
+        let groups = vec![
+            json!({ "user_country": "US", "count": 3, "max_user_age": 40 }),
+            json!({ "user_country": "MX", "count": 1, "max_user_age": 60 }),
+            json!({ "user_country": "CO", "count": 2, "max_user_age": 52 }),
+        ];
+        assert_eq!(
+            Pipeline::run(source.rest, schema.clone(), groups.clone()).unwrap(),
+            vec![json!({ "user_country": "CO", "max_user_age": 52 })]
+        );
+
+        assert_eq!(pipeline.schema(&table).unwrap().columns.len(), 3);
+
+        // Stages only know the fields produced by the previous ones
+        let unknown = Pipeline::new()
+            .project(&["user_country"])
+            .sort("count", SortDirection::Asc);
+        assert!(Pipeline::run(&unknown.stages, schema, groups)
+            .unwrap_err()
+            .is_invalid_column());
+    }
+}
@@ -1,7 +1,20 @@
+use crate::errors::QueryError;
+use crate::ops::between::BetweenFilter;
+use crate::ops::filter::from_filter;
+use crate::ops::geo::{GeoFilter, GeoPoint};
+use crate::ops::pattern;
+use crate::row::Row;
 use enum_as_inner::EnumAsInner;
-use schemajs_primitives::column::types::DataValue;
-use schemajs_primitives::index::Index;
+use schemajs_primitives::column::collation::Collation;
+use schemajs_primitives::column::types::{DataTypes, DataValue};
+use schemajs_primitives::column::Column;
+use schemajs_primitives::table::Table;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
+use std::str::FromStr;
 
 #[derive(Debug, Eq, PartialEq, Clone, EnumAsInner)]
 pub enum FilterType {
@@ -11,6 +24,17 @@ pub enum FilterType {
     GreaterOrEqualTo,
     LowerOrEqualTo,
     NotEqual,
+    In,
+    Like,
+    StartsWith,
+    IsNull,
+    IsNotNull,
+    Near,
+    Within,
+    Path,
+    Contains,
+    Between,
+    Regex,
 }
 
 impl Display for FilterType {
@@ -22,18 +46,261 @@ impl Display for FilterType {
             FilterType::GreaterOrEqualTo => String::from(">="),
             FilterType::LowerOrEqualTo => String::from("<="),
             FilterType::NotEqual => String::from("!="),
+            FilterType::In => String::from("in"),
+            FilterType::Like => String::from("like"),
+            FilterType::StartsWith => String::from("starts_with"),
+            FilterType::IsNull => String::from("is_null"),
+            FilterType::IsNotNull => String::from("is_not_null"),
+            FilterType::Near => String::from("near"),
+            FilterType::Within => String::from("within"),
+            FilterType::Path => String::from("path"),
+            FilterType::Contains => String::from("contains"),
+            FilterType::Between => String::from("between"),
+            FilterType::Regex => String::from("regex"),
         };
         write!(f, "{}", str)
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+impl FromStr for FilterType {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "=" => Ok(FilterType::Equal),
+            ">" => Ok(FilterType::GreaterThan),
+            "<" => Ok(FilterType::LowerThan),
+            ">=" => Ok(FilterType::GreaterOrEqualTo),
+            "<=" => Ok(FilterType::LowerOrEqualTo),
+            "!=" => Ok(FilterType::NotEqual),
+            "in" => Ok(FilterType::In),
+            "like" => Ok(FilterType::Like),
+            "starts_with" => Ok(FilterType::StartsWith),
+            "is_null" => Ok(FilterType::IsNull),
+            "is_not_null" => Ok(FilterType::IsNotNull),
+            "near" => Ok(FilterType::Near),
+            "within" => Ok(FilterType::Within),
+            "path" => Ok(FilterType::Path),
+            "contains" => Ok(FilterType::Contains),
+            "between" => Ok(FilterType::Between),
+            "regex" => Ok(FilterType::Regex),
+            _ => Err(QueryError::InvalidFilterType(s.to_string())),
+        }
+    }
+}
+
+impl FilterType {
+    /// Evaluates `lhs <filter> rhs`, where `lhs` is the value stored in the row
+    /// and `rhs` the value provided in the query.
+    ///
+    /// For `in`, `rhs` is expected to be a `DataValue::Array` holding the candidates.
+    /// `contains` matches arrays in `lhs` holding an item equal to `rhs`.
+    /// `between` expects the bounds described by `BetweenFilter` in `rhs`.
+    /// `like`, `starts_with` and `regex` only match string values. `like` supports `%` (any sequence)
+    /// and `_` (any single character) wildcards, `regex` expects a pattern of the `regex` crate in `rhs`.
+    ///
+    /// `is_null` and `is_not_null` ignore `rhs`. Range filters never match a null on either side, nor values of
+    /// different kinds (see `Ord for DataValue`), while `=` and `!=` follow the order of `DataValue`.
+    /// `near` and `within` expect a point in `lhs` and the shape described by `GeoFilter` in `rhs`.
+    /// `path` applies to the values nested in `lhs`, see `QueryVal::matches`, and never matches here.
+    pub fn evaluate(&self, lhs: &DataValue, rhs: &DataValue) -> bool {
+        match self {
+            FilterType::Path => false,
+            FilterType::Between => {
+                BetweenFilter::from_value(rhs).map_or(false, |filter| filter.matches(lhs))
+            }
+            FilterType::IsNull => lhs.is_null(),
+            FilterType::IsNotNull => !lhs.is_null(),
+            FilterType::Near | FilterType::Within => {
+                match (GeoPoint::from_value(lhs), GeoFilter::from_value(self, rhs)) {
+                    (Some(point), Some(filter)) => filter.matches(&point),
+                    _ => false,
+                }
+            }
+            _ if self.is_range() && (lhs.is_null() || rhs.is_null()) => false,
+            FilterType::Like => match (lhs, rhs) {
+                (DataValue::String(value), DataValue::String(pattern)) => {
+                    like_matches(value.as_str(), pattern.as_str())
+                }
+                _ => false,
+            },
+            FilterType::Regex => match (lhs, rhs) {
+                (DataValue::String(value), DataValue::String(pattern)) => {
+                    pattern::is_match(value.as_str(), pattern.as_str())
+                }
+                _ => false,
+            },
+            FilterType::StartsWith => match (lhs, rhs) {
+                (DataValue::String(value), DataValue::String(prefix)) => {
+                    value.starts_with(prefix.as_str())
+                }
+                _ => false,
+            },
+            FilterType::In => match rhs {
+                DataValue::Array(candidates) => candidates
+                    .iter()
+                    .any(|candidate| FilterType::Equal.evaluate(lhs, candidate)),
+                candidate => FilterType::Equal.evaluate(lhs, candidate),
+            },
+            FilterType::Contains => match lhs {
+                DataValue::Array(items) => items
+                    .iter()
+                    .any(|item| FilterType::Equal.evaluate(item, rhs)),
+                _ => false,
+            },
+            _ if self.is_range() && !lhs.same_kind(rhs) => false,
+            _ => self.evaluate_ordering(lhs.cmp(rhs)),
+        }
+    }
+
+    fn evaluate_ordering(&self, ordering: Ordering) -> bool {
+        match self {
+            FilterType::Equal => ordering == Ordering::Equal,
+            FilterType::GreaterThan => ordering == Ordering::Greater,
+            FilterType::LowerThan => ordering == Ordering::Less,
+            FilterType::GreaterOrEqualTo => ordering != Ordering::Less,
+            FilterType::LowerOrEqualTo => ordering != Ordering::Greater,
+            FilterType::NotEqual => ordering != Ordering::Equal,
+            FilterType::In
+            | FilterType::Like
+            | FilterType::StartsWith
+            | FilterType::IsNull
+            | FilterType::IsNotNull
+            | FilterType::Near
+            | FilterType::Within
+            | FilterType::Path
+            | FilterType::Contains
+            | FilterType::Between
+            | FilterType::Regex => false,
+        }
+    }
+
+    /// Whether values are compared under the collation of their column. `regex` patterns are not collated,
+    /// as it would change their meaning (`\W` being `\w` once lowercased), flags like `(?i)` do it instead.
+    pub fn is_collated(&self) -> bool {
+        *self != FilterType::Regex
+    }
+
+    /// Whether the filter matches points against a geographic shape (`near` and `within`).
+    pub fn is_geo(&self) -> bool {
+        matches!(self, FilterType::Near | FilterType::Within)
+    }
+
+    /// Whether the filter compares the order of the values (`>`, `<`, `>=` and `<=`).
+    pub fn is_range(&self) -> bool {
+        matches!(
+            self,
+            FilterType::GreaterThan
+                | FilterType::LowerThan
+                | FilterType::GreaterOrEqualTo
+                | FilterType::LowerOrEqualTo
+        )
+    }
+}
+
+fn like_matches(value: &str, pattern: &str) -> bool {
+    let value: Vec<char> = value.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+
+    let (mut v, mut p) = (0, 0);
+    // Position of the last `%` in the pattern and the value position it is currently absorbing up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() && pattern[p] == '%' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == '_' || pattern[p] == value[v]) {
+            v += 1;
+            p += 1;
+        } else if let Some((wildcard_p, wildcard_v)) = backtrack {
+            // Let the last `%` absorb one more character and retry
+            p = wildcard_p + 1;
+            v = wildcard_v + 1;
+            backtrack = Some((wildcard_p, wildcard_v + 1));
+        } else {
+            return false;
+        }
+    }
+
+    while p < pattern.len() && pattern[p] == '%' {
+        p += 1;
+    }
+
+    p == pattern.len()
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
 pub struct QueryVal {
     pub key: String,
     pub filter_type: String,
+    #[serde(serialize_with = "serialize_query_value")]
     pub value: DataValue,
 }
 
+fn serialize_query_value<S: Serializer>(
+    value: &DataValue,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.to_json().serialize(serializer)
+}
+
+impl QueryVal {
+    pub fn get_filter_type(&self) -> Result<FilterType, QueryError> {
+        FilterType::from_str(self.filter_type.as_str())
+    }
+
+    /// Parts of a `path` condition: the path inside the column, the filter applied to the value it leads to and
+    /// the value of the filter. `None` for any other condition.
+    pub fn path_filter(&self) -> Option<(&str, FilterType, &DataValue)> {
+        let parts = self.value.as_object()?;
+        let path = parts.get("path")?.as_string()?;
+        let filter_type = FilterType::from_str(parts.get("filterType")?.as_string()?).ok()?;
+
+        Some((path.as_str(), filter_type, parts.get("value")?))
+    }
+
+    /// Whether the value of `row` for `column` satisfies `filter_type`, both values compared under the collation
+    /// of the column. Rows that do not contain the column are only matched by `!=`, mirroring the anti-index path,
+    /// and `is_null`.
+    ///
+    /// `path` conditions apply their filter to the value their path leads to inside the value of the column
+    /// (see `DataValue::get_path`), under the collation of the nested field. Missing values are matched like
+    /// missing columns.
+    pub fn matches<T: Row<T>>(&self, column: &Column, filter_type: &FilterType, row: &T) -> bool {
+        if *filter_type == FilterType::Path {
+            let (path, filter_type, value) = match self.path_filter() {
+                Some(path_filter) => path_filter,
+                None => return false,
+            };
+            let collation = match filter_type.is_collated() {
+                true => path_type(column, path).map_or(Collation::Binary, |(_, c)| c),
+                false => Collation::Binary,
+            };
+
+            return match row
+                .get_value(column)
+                .and_then(|val| val.get_path(path).cloned())
+            {
+                Some(val) => {
+                    filter_type.evaluate(&val.collate(collation), &value.clone().collate(collation))
+                }
+                None => matches!(filter_type, FilterType::NotEqual | FilterType::IsNull),
+            };
+        }
+
+        match row.get_value(column) {
+            Some(val) if column.collation.is_binary() || !filter_type.is_collated() => {
+                filter_type.evaluate(&val, &self.value)
+            }
+            Some(val) => {
+                filter_type.evaluate(&column.collate(val), &column.collate(self.value.clone()))
+            }
+            None => matches!(filter_type, FilterType::NotEqual | FilterType::IsNull),
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum QueryOps {
     And(Vec<QueryOps>),
@@ -41,9 +308,837 @@ pub enum QueryOps {
     Condition(QueryVal),
 }
 
-#[derive(Debug, Clone, EnumAsInner, PartialEq)]
+impl QueryOps {
+    /// Builds a `QueryOps` out of its JSON representation (as sent from JS).
+    /// Values are typed after the columns of `table`.
+    ///
+    /// - `{ "and": [...] }` and `{ "or": [...] }` combine nested queries.
+    /// - `{ "key": "user_age", "filterType": ">", "value": 20 }` is a condition.
+    ///   `in` expects `value` to be an array of candidates, `contains` an item of the `Array` column,
+    ///   `between` the bounds described by `BetweenFilter`, `regex` a valid pattern,
+    ///   `is_null` and `is_not_null` need no `value`.
+    ///   `near` and `within` apply to `Point` columns and expect the shape described by `GeoFilter`.
+    ///   `path` applies a condition to a value nested in the column, see `path_condition`:
+    ///   `{ "key": "profile", "filterType": "path", "value": { "path": "address.country", "filterType": "=", "value": "US" } }`.
+    /// - `null` and `{}` have no conditions and match every row.
+    /// - Any other object is a MongoDB-style filter, converted by `from_filter`:
+    ///   `{ "user_age": { "$gt": 20 }, "$or": [...] }`.
+    pub fn from_json(table: &Table, query: &Value) -> Result<QueryOps, QueryError> {
+        let obj = match query {
+            Value::Null => return Ok(QueryOps::And(vec![])),
+            Value::Object(obj) => obj,
+            _ => return Err(QueryError::InvalidQuery(query.to_string())),
+        };
+
+        if obj.is_empty() {
+            return Ok(QueryOps::And(vec![]));
+        }
+
+        let parse_all = |ops: &Value| -> Result<Vec<QueryOps>, QueryError> {
+            ops.as_array()
+                .ok_or_else(|| QueryError::InvalidQuery(ops.to_string()))?
+                .iter()
+                .map(|op| QueryOps::from_json(table, op))
+                .collect()
+        };
+
+        if let Some(ops) = obj.get("and") {
+            return Ok(QueryOps::And(parse_all(ops)?));
+        }
+
+        if let Some(ops) = obj.get("or") {
+            return Ok(QueryOps::Or(parse_all(ops)?));
+        }
+
+        // Conditions always have a `filterType`, filters never do
+        if !obj.contains_key("filterType") {
+            return QueryOps::from_json(table, &from_filter(query)?);
+        }
+
+        let key = obj
+            .get("key")
+            .and_then(|key| key.as_str())
+            .ok_or_else(|| QueryError::InvalidQuery(query.to_string()))?;
+        let filter_type = obj
+            .get("filterType")
+            .and_then(|filter_type| filter_type.as_str())
+            .ok_or_else(|| QueryError::InvalidQuery(query.to_string()))?;
+        let column = table
+            .resolve_column(key)
+            .ok_or_else(|| QueryError::InvalidColumn(key.to_string()))?;
+
+        let parsed_filter_type = FilterType::from_str(filter_type)?;
+        let value = obj.get("value").unwrap_or(&Value::Null);
+        if parsed_filter_type == FilterType::Path {
+            return path_condition(table, &column, value);
+        }
+
+        Ok(QueryOps::Condition(QueryVal {
+            key: key.to_string(),
+            filter_type: filter_type.to_string(),
+            value: condition_value(&column, &parsed_filter_type, value)?,
+        }))
+    }
+
+    /// Checks that every condition of the query has a known filter type, so a typo in a query built by hand
+    /// fails instead of matching nothing.
+    ///
+    /// # Returns:
+    /// - `Result<(), QueryError>`: `InvalidFilterType` for the first unknown filter type.
+    pub fn check_filter_types(&self) -> Result<(), QueryError> {
+        match self {
+            QueryOps::And(ops) | QueryOps::Or(ops) => {
+                ops.iter().try_for_each(|op| op.check_filter_types())
+            }
+            QueryOps::Condition(cond) => cond.get_filter_type().map(|_| ()),
+        }
+    }
+
+    /// Evaluates the query against `row`, a row of `table`, without going through indexes.
+    pub fn matches<T: Row<T>>(&self, table: &Table, row: &T) -> bool {
+        match self {
+            QueryOps::And(ops) => ops.iter().all(|op| op.matches(table, row)),
+            QueryOps::Or(ops) => ops.iter().any(|op| op.matches(table, row)),
+            QueryOps::Condition(cond) => {
+                let filter_type = match cond.get_filter_type() {
+                    Ok(filter_type) => filter_type,
+                    Err(_) => return false,
+                };
+
+                match table.resolve_column(cond.key.as_str()) {
+                    Some(column) => cond.matches(&column, &filter_type, row),
+                    None => false,
+                }
+            }
+        }
+    }
+
+    /// Normalized form of the query, with its values replaced by `?`: `user_age > ? AND (user_country in ? OR user_name is_null)`.
+    /// Queries only differing in their values share the same shape. Queries without conditions are `*`.
+    pub fn shape(&self) -> String {
+        self.shape_with(false)
+    }
+
+    fn shape_with(&self, nested: bool) -> String {
+        let (ops, separator) = match self {
+            QueryOps::And(ops) if ops.is_empty() => return String::from("*"),
+            QueryOps::And(ops) => (ops, " AND "),
+            QueryOps::Or(ops) => (ops, " OR "),
+            QueryOps::Condition(cond) => {
+                return match cond.get_filter_type() {
+                    Ok(FilterType::IsNull) | Ok(FilterType::IsNotNull) => {
+                        format!("{} {}", cond.key, cond.filter_type)
+                    }
+                    _ => format!("{} {} ?", cond.key, cond.filter_type),
+                }
+            }
+        };
+
+        let shape = ops
+            .iter()
+            .map(|op| op.shape_with(true))
+            .collect::<Vec<String>>()
+            .join(separator);
+
+        match nested && ops.len() > 1 {
+            true => format!("({})", shape),
+            false => shape,
+        }
+    }
+}
+
+/// Parses a JSON object of column values, such as the changes of an update (`{ "user_age": 21 }`),
+/// typing every value after its column in `table`.
+pub fn values_from_json(
+    table: &Table,
+    values: &Value,
+) -> Result<HashMap<String, DataValue>, QueryError> {
+    let obj = values
+        .as_object()
+        .ok_or_else(|| QueryError::InvalidQuery(values.to_string()))?;
+
+    obj.iter()
+        .map(|(key, value)| {
+            let column = table
+                .get_column(key)
+                .ok_or_else(|| QueryError::InvalidColumn(key.to_string()))?;
+            Ok((key.clone(), to_query_value(column, value)?))
+        })
+        .collect()
+}
+
+/// Value of a condition on `column`, typed after the column.
+fn condition_value(
+    column: &Column,
+    filter_type: &FilterType,
+    value: &Value,
+) -> Result<DataValue, QueryError> {
+    let key = column.name.as_str();
+    let value_column = match (filter_type, &column.data_type) {
+        (FilterType::In, _) => {
+            Column::new(key, DataTypes::Array(Box::new(column.data_type.clone())))
+        }
+        (FilterType::Contains, DataTypes::Array(items)) => Column::new(key, *items.clone()),
+        (FilterType::Contains, _) => return Err(QueryError::InvalidQueryValue(key.to_string())),
+        (FilterType::Between, _) => Column::new(
+            key,
+            DataTypes::Object(vec![
+                Column::new("min", column.data_type.clone()),
+                Column::new("max", column.data_type.clone()),
+                Column::new("minExclusive", DataTypes::Boolean),
+                Column::new("maxExclusive", DataTypes::Boolean),
+            ]),
+        ),
+        (FilterType::Regex, _) => Column::new(key, DataTypes::String),
+        (FilterType::Near, _) => geo_column(key, &["lat", "lng", "radius"]),
+        (FilterType::Within, _) => geo_column(key, &["minLat", "minLng", "maxLat", "maxLng"]),
+        _ => column.clone(),
+    };
+    let value = to_query_value(&value_column, value)?;
+
+    if filter_type.is_geo()
+        && (!column.data_type.is_point() || GeoFilter::from_value(filter_type, &value).is_none())
+    {
+        return Err(QueryError::InvalidQueryValue(key.to_string()));
+    }
+    if *filter_type == FilterType::Between && BetweenFilter::from_value(&value).is_none() {
+        return Err(QueryError::InvalidQueryValue(key.to_string()));
+    }
+    if *filter_type == FilterType::Regex
+        && !value.as_string().map_or(false, |p| pattern::is_valid(p))
+    {
+        return Err(QueryError::InvalidQueryValue(key.to_string()));
+    }
+
+    Ok(value)
+}
+
+/// Parses the value of a `path` condition on `column`, a condition relative to the values of the column:
+/// `{ "path": "address.country", "filterType": "=", "value": "US" }`. The value is typed after the nested field.
+///
+/// Paths the schema resolves to a field (see `Table::resolve_column`) become a condition on the field itself,
+/// so they use the indexes holding it. Paths through arrays (`tags.0.label`) stay `path` conditions.
+fn path_condition(table: &Table, column: &Column, value: &Value) -> Result<QueryOps, QueryError> {
+    let invalid = || QueryError::InvalidQueryValue(column.name.clone());
+    let path = value
+        .get("path")
+        .and_then(|path| path.as_str())
+        .filter(|path| !path.is_empty())
+        .ok_or_else(invalid)?;
+    let filter_type = value
+        .get("filterType")
+        .and_then(|filter_type| filter_type.as_str())
+        .ok_or_else(invalid)?;
+    let parsed_filter_type = FilterType::from_str(filter_type)?;
+    if parsed_filter_type == FilterType::Path {
+        return Err(QueryError::InvalidFilterType(filter_type.to_string()));
+    }
+
+    let key = format!("{}.{}", column.name, path);
+    let value = value.get("value").unwrap_or(&Value::Null);
+
+    if let Some(field) = table.resolve_column(&key) {
+        return Ok(QueryOps::Condition(QueryVal {
+            value: condition_value(&field, &parsed_filter_type, value)?,
+            key,
+            filter_type: filter_type.to_string(),
+        }));
+    }
+
+    let (data_type, collation) =
+        path_type(column, path).ok_or_else(|| QueryError::InvalidColumn(key.clone()))?;
+    let field = Column::new(&key, data_type.clone()).set_collation(collation);
+
+    Ok(QueryOps::Condition(QueryVal {
+        key: column.name.clone(),
+        filter_type: FilterType::Path.to_string(),
+        value: DataValue::Object(BTreeMap::from([
+            (String::from("path"), DataValue::String(path.to_string())),
+            (
+                String::from("filterType"),
+                DataValue::String(filter_type.to_string()),
+            ),
+            (
+                String::from("value"),
+                condition_value(&field, &parsed_filter_type, value)?,
+            ),
+        ])),
+    }))
+}
+
+/// Type and collation of the values the dotted `path` leads to inside the values of `column`, following the
+/// fields of objects by name and the items of arrays by position. `None` if the schema has no such values.
+fn path_type<'a>(column: &'a Column, path: &str) -> Option<(&'a DataTypes, Collation)> {
+    let mut data_type = &column.data_type;
+    let mut collation = column.collation;
+
+    for segment in path.split('.') {
+        data_type = match data_type {
+            DataTypes::Object(fields) => {
+                let field = fields.iter().find(|field| field.name == segment)?;
+                collation = field.collation;
+                &field.data_type
+            }
+            DataTypes::Array(items) if segment.parse::<usize>().is_ok() => items,
+            _ => return None,
+        };
+    }
+
+    Some((data_type, collation))
+}
+
+/// Column describing the value of a geo condition: an object made of the numeric `fields`.
+fn geo_column(key: &str, fields: &[&str]) -> Column {
+    Column::new(
+        key,
+        DataTypes::Object(
+            fields
+                .iter()
+                .map(|field| Column::new(field, DataTypes::Number))
+                .collect(),
+        ),
+    )
+}
+
+/// Types a JSON value after `column`, failing (instead of panicking) when the value doesn't match the column type.
+fn to_query_value(column: &Column, value: &Value) -> Result<DataValue, QueryError> {
+    if !column.data_type.accepts(value) {
+        return Err(QueryError::InvalidQueryValue(column.name.clone()));
+    }
+
+    Ok(DataValue::from((column, value)))
+}
+
+/// Plan chosen by `QuerySearchManager` to resolve a `QueryOps`.
+/// Plans are serializable so they can be inspected through `QuerySearchManager::explain`.
+#[derive(Debug, Clone, EnumAsInner, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum QueryPlan {
-    And(Vec<QueryPlan>),  // Nested AND operations
-    Or(Vec<QueryPlan>),   // Nested OR operations
-    Index(Option<Index>), // A specific index to use
+    /// Nothing can match (e.g. the filter type is unknown).
+    Empty,
+    /// Every row of the table.
+    FullScan,
+    /// Equality lookup on `index`. Either a single `in` condition (a lookup per candidate)
+    /// or equality conditions covering every member of the index.
+    IndexLookup {
+        index: String,
+        conditions: Vec<QueryVal>,
+    },
+    /// Range (or prefix) scan over an ordered index.
+    IndexRange { index: String, condition: QueryVal },
+    /// Scan of the cells of a geo `index` covering a `near` or `within` condition.
+    /// Rows found in the cells are checked against the condition.
+    IndexGeo { index: String, condition: QueryVal },
+    /// Every row minus the rows whose value is found in `index` (`!=`).
+    IndexExclusion { index: String, condition: QueryVal },
+    /// Reads every row and evaluates the condition on it.
+    Scan { condition: QueryVal },
+    /// Rows of `input` that match every condition, read and checked one by one.
+    Filter {
+        input: Box<QueryPlan>,
+        conditions: Vec<QueryVal>,
+    },
+    /// Rows matched by every input.
+    Intersection { inputs: Vec<QueryPlan> },
+    /// Rows matched by any input.
+    Union { inputs: Vec<QueryPlan> },
+}
+
+impl QueryPlan {
+    /// Names of the indexes read by the plan, in the order they are found.
+    pub fn indexes(&self) -> Vec<String> {
+        let mut indexes = vec![];
+        self.collect_indexes(&mut indexes);
+        indexes
+    }
+
+    fn collect_indexes(&self, indexes: &mut Vec<String>) {
+        match self {
+            QueryPlan::Empty | QueryPlan::FullScan | QueryPlan::Scan { .. } => {}
+            QueryPlan::IndexLookup { index, .. }
+            | QueryPlan::IndexRange { index, .. }
+            | QueryPlan::IndexGeo { index, .. }
+            | QueryPlan::IndexExclusion { index, .. } => {
+                if !indexes.contains(index) {
+                    indexes.push(index.clone());
+                }
+            }
+            QueryPlan::Filter { input, .. } => input.collect_indexes(indexes),
+            QueryPlan::Intersection { inputs } | QueryPlan::Union { inputs } => {
+                for input in inputs {
+                    input.collect_indexes(indexes);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ops::query_ops::{FilterType, QueryOps, QueryVal};
+    use crate::row_json::{RowData, RowJson};
+    use schemajs_primitives::column::collation::Collation;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+
+    #[test]
+    pub fn test_like_filter() {
+        let like = |value: &str, pattern: &str| {
+            FilterType::Like.evaluate(
+                &DataValue::String(value.to_string()),
+                &DataValue::String(pattern.to_string()),
+            )
+        };
+
+        assert!(like("email@outlook.com", "%@outlook.com"));
+        assert!(like("email@outlook.com", "email%"));
+        assert!(like("email@outlook.com", "%@%.com"));
+        assert!(like("email@outlook.com", "emai_@outlook.com"));
+        assert!(like("email@outlook.com", "%"));
+        assert!(!like("email@outlook.com", "%@gmail.com"));
+        assert!(!like("email@outlook.com", "email"));
+        assert!(!like("email", "email_"));
+
+        assert!(FilterType::StartsWith.evaluate(
+            &DataValue::String("email@outlook.com".to_string()),
+            &DataValue::String("email@".to_string())
+        ));
+        assert!(!FilterType::StartsWith.evaluate(
+            &DataValue::Boolean(true),
+            &DataValue::String("t".to_string())
+        ));
+    }
+
+    #[test]
+    pub fn test_regex_filter() {
+        let regex = |value: &DataValue, pattern: &str| {
+            FilterType::Regex.evaluate(value, &DataValue::String(pattern.to_string()))
+        };
+        let email = DataValue::String("Email@outlook.com".to_string());
+
+        assert!(regex(&email, "outlook"));
+        assert!(regex(&email, r"^\w+@outlook\.com$"));
+        assert!(!regex(&email, "^email"));
+        assert!(regex(&email, "(?i)^email"));
+        assert!(!regex(&email, "(unclosed"));
+        assert!(!regex(&DataValue::Number(1.into()), "1"));
+
+        // Patterns are not collated, the values they are matched against neither
+        let table = Table::new("users").add_column(
+            Column::new("user_email", DataTypes::String).set_collation(Collation::CaseInsensitive),
+        );
+        let row = RowJson::from(RowData {
+            table: "users".to_string(),
+            value: serde_json::json!({ "user_email": "Email@outlook.com" }),
+        });
+        let condition = |pattern: &str| {
+            QueryOps::from_json(
+                &table,
+                &serde_json::json!({ "key": "user_email", "filterType": "regex", "value": pattern }),
+            )
+        };
+        assert!(!condition(r"^\W").unwrap().matches(&table, &row));
+        assert!(condition("^Email").unwrap().matches(&table, &row));
+        assert!(condition("(unclosed").is_err());
+    }
+
+    #[test]
+    pub fn test_filter_ordering() {
+        let number = |n: f64| DataValue::Number(serde_json::Number::from_f64(n).unwrap());
+        let string = |s: &str| DataValue::String(s.to_string());
+
+        // Numbers compare by value, whatever their representation
+        assert!(FilterType::Equal.evaluate(&DataValue::Number(1.into()), &number(1.0)));
+        assert!(FilterType::LowerThan.evaluate(&DataValue::Number((-2).into()), &number(-1.5)));
+        assert!(FilterType::GreaterThan.evaluate(
+            &DataValue::Number(u64::MAX.into()),
+            &DataValue::Number(i64::MIN.into())
+        ));
+
+        // Values of different kinds are never equal nor in range of each other
+        assert!(!FilterType::Equal.evaluate(&string("1"), &DataValue::Number(1.into())));
+        assert!(FilterType::NotEqual.evaluate(&string("1"), &DataValue::Number(1.into())));
+        assert!(!FilterType::LowerThan.evaluate(&DataValue::Number(1.into()), &string("a")));
+        assert!(!FilterType::GreaterOrEqualTo.evaluate(&string("a"), &DataValue::Number(1.into())));
+        assert!(!FilterType::GreaterOrEqualTo.evaluate(&DataValue::Null, &DataValue::Null));
+
+        // Sorting orders kinds, then contents
+        let uuid = DataValue::Uuid(uuid::Uuid::nil());
+        let mut values = vec![
+            string("b"),
+            DataValue::Array(vec![number(1.0), number(2.0)]),
+            number(2.5),
+            uuid.clone(),
+            DataValue::Null,
+            DataValue::Boolean(true),
+            string("a"),
+            DataValue::Array(vec![number(1.0)]),
+            DataValue::Number((-3).into()),
+            DataValue::Boolean(false),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                DataValue::Null,
+                DataValue::Boolean(false),
+                DataValue::Boolean(true),
+                DataValue::Number((-3).into()),
+                number(2.5),
+                string("a"),
+                string("b"),
+                uuid.clone(),
+                DataValue::Array(vec![number(1.0)]),
+                DataValue::Array(vec![number(1.0), number(2.0)]),
+            ]
+        );
+        assert_ne!(uuid, string(&uuid::Uuid::nil().to_string()));
+        assert_eq!(
+            number(-0.0).to_sortable_string(),
+            number(0.0).to_sortable_string()
+        );
+        assert!(number(-1.0).to_sortable_string() < number(0.5).to_sortable_string());
+    }
+
+    #[test]
+    pub fn test_number_ordering() {
+        let integer = |n: i64| DataValue::Number(n.into());
+        let float = |n: f64| DataValue::Number(serde_json::Number::from_f64(n).unwrap());
+
+        // 2^53 + 1 has no `f64` of its own, it's still above the float 2^53
+        let two_53 = 9_007_199_254_740_992;
+        let a = integer(two_53);
+        let b = integer(two_53 + 1);
+        let c = float(9_007_199_254_740_992.0);
+        assert_eq!(a, c);
+        assert!(a < b);
+        assert!(c < b);
+        assert_eq!(a.to_sortable_string(), c.to_sortable_string());
+        assert!(c.to_sortable_string() < b.to_sortable_string());
+
+        let ordered = vec![
+            float(-1e300),
+            integer(i64::MIN),
+            integer(-two_53 - 1),
+            float(-9_007_199_254_740_992.0),
+            float(-1.5),
+            integer(-1),
+            float(-0.5),
+            integer(0),
+            float(0.25),
+            integer(two_53),
+            integer(two_53 + 1),
+            float(9_007_199_254_740_994.0),
+            DataValue::Number(u64::MAX.into()),
+            float(1e300),
+        ];
+        for (lower, higher) in ordered.iter().zip(ordered.iter().skip(1)) {
+            assert!(lower < higher, "{:?} < {:?}", lower, higher);
+            assert!(lower.to_sortable_string() < higher.to_sortable_string());
+        }
+
+        // Sorting by keys gives the order of the values
+        let mut shuffled = ordered.clone();
+        shuffled.reverse();
+        shuffled.sort_by_key(|value| value.to_sortable_string());
+        assert_eq!(shuffled, ordered);
+        assert_eq!(
+            float(-0.0).to_sortable_string(),
+            integer(0).to_sortable_string()
+        );
+    }
+
+    #[test]
+    pub fn test_query_ops_from_json() {
+        let table = Table::new("users")
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        let query = QueryOps::from_json(
+            &table,
+            &serde_json::json!({
+                "and": [
+                    { "key": "user_country", "filterType": "in", "value": ["US", "VE"] },
+                    { "or": [{ "key": "user_age", "filterType": ">", "value": 20 }] }
+                ]
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(
+            query,
+            QueryOps::And(vec![
+                QueryOps::Condition(QueryVal {
+                    key: "user_country".to_string(),
+                    filter_type: "in".to_string(),
+                    value: DataValue::Array(vec![
+                        DataValue::String("US".to_string()),
+                        DataValue::String("VE".to_string())
+                    ]),
+                }),
+                QueryOps::Or(vec![QueryOps::Condition(QueryVal {
+                    key: "user_age".to_string(),
+                    filter_type: ">".to_string(),
+                    value: DataValue::Number(serde_json::Number::from(20)),
+                })])
+            ])
+        );
+
+        assert_eq!(
+            QueryOps::from_json(&table, &serde_json::Value::Null).unwrap(),
+            QueryOps::And(vec![])
+        );
+        assert!(QueryOps::from_json(
+            &table,
+            &serde_json::json!({ "key": "user_age", "filterType": "=", "value": "20" })
+        )
+        .is_err());
+        assert!(QueryOps::from_json(
+            &table,
+            &serde_json::json!({ "key": "unknown", "filterType": "=", "value": 1 })
+        )
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_query_shape() {
+        let table = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        let shape = |query: serde_json::Value| QueryOps::from_json(&table, &query).unwrap().shape();
+
+        assert_eq!(shape(serde_json::json!({})), "*");
+        assert_eq!(
+            shape(serde_json::json!({ "key": "user_age", "filterType": ">", "value": 20 })),
+            "user_age > ?"
+        );
+        // Values don't change the shape
+        assert_eq!(
+            shape(serde_json::json!({
+                "and": [
+                    { "key": "user_age", "filterType": ">", "value": 20 },
+                    { "or": [
+                        { "key": "user_country", "filterType": "in", "value": ["US", "VE"] },
+                        { "key": "user_name", "filterType": "is_null" }
+                    ] }
+                ]
+            })),
+            "user_age > ? AND (user_country in ? OR user_name is_null)"
+        );
+        assert_eq!(
+            shape(serde_json::json!({
+                "and": [
+                    { "key": "user_age", "filterType": ">", "value": 50 },
+                    { "or": [
+                        { "key": "user_country", "filterType": "in", "value": ["AR"] },
+                        { "key": "user_name", "filterType": "is_null" }
+                    ] }
+                ]
+            })),
+            "user_age > ? AND (user_country in ? OR user_name is_null)"
+        );
+    }
+
+    #[test]
+    pub fn test_numeric_types() {
+        let table = Table::new("products")
+            .add_column(Column::new("stock", DataTypes::Uint))
+            .add_column(Column::new("delta", DataTypes::Int))
+            .add_column(Column::new("price", DataTypes::Float));
+
+        let condition = |key: &str, value: serde_json::Value| {
+            QueryOps::from_json(
+                &table,
+                &serde_json::json!({ "key": key, "filterType": "=", "value": value }),
+            )
+        };
+
+        assert!(condition("stock", serde_json::json!(10)).is_ok());
+        assert!(condition("stock", serde_json::json!(-1)).is_err());
+        assert!(condition("stock", serde_json::json!(1.5)).is_err());
+        assert!(condition("delta", serde_json::json!(-1)).is_ok());
+        assert!(condition("delta", serde_json::json!(1.5)).is_err());
+
+        // Floats are normalized, so `1` and `1.0` are the same value
+        assert_eq!(
+            condition("price", serde_json::json!(1)).unwrap(),
+            condition("price", serde_json::json!(1.0)).unwrap()
+        );
+
+        let number =
+            |value: serde_json::Value| DataValue::Number(serde_json::from_value(value).unwrap());
+
+        // Integers are compared exactly, even when they don't fit in a `f64`
+        assert!(
+            number(serde_json::json!(9007199254740993i64))
+                > number(serde_json::json!(9007199254740992i64))
+        );
+        assert!(number(serde_json::json!(u64::MAX)) > number(serde_json::json!(-1)));
+        assert!(number(serde_json::json!(-1)) < number(serde_json::json!(0.5)));
+        assert_eq!(number(serde_json::json!(2)), number(serde_json::json!(2.0)));
+    }
+
+    #[test]
+    pub fn test_null_filters() {
+        let null = DataValue::Null;
+        let zero = DataValue::Number(serde_json::Number::from(0));
+
+        assert!(FilterType::IsNull.evaluate(&null, &DataValue::Null));
+        assert!(!FilterType::IsNull.evaluate(&zero, &DataValue::Null));
+        assert!(FilterType::IsNotNull.evaluate(&zero, &DataValue::Null));
+
+        assert!(!FilterType::LowerThan.evaluate(&null, &zero));
+        assert!(!FilterType::GreaterOrEqualTo.evaluate(&zero, &null));
+        assert!(FilterType::Equal.evaluate(&null, &DataValue::Null));
+        assert!(FilterType::NotEqual.evaluate(&null, &zero));
+    }
+
+    #[test]
+    pub fn test_geo_filters() {
+        let table = Table::new("places")
+            .add_column(Column::new("location", DataTypes::Point))
+            .add_column(Column::new("name", DataTypes::String));
+
+        let condition = |filter_type: &str, value: serde_json::Value| {
+            QueryOps::from_json(
+                &table,
+                &serde_json::json!({ "key": "location", "filterType": filter_type, "value": value }),
+            )
+        };
+        let value_of = |ops: QueryOps| match ops {
+            QueryOps::Condition(cond) => cond.value,
+            _ => DataValue::Null,
+        };
+        let point = |lat: f64, lng: f64| {
+            DataValue::from((
+                table.get_column("location").unwrap(),
+                &serde_json::json!({ "lat": lat, "lng": lng }),
+            ))
+        };
+
+        let near = condition(
+            "near",
+            serde_json::json!({ "lat": 10.4806, "lng": -66.9036, "radius": 130000 }),
+        )
+        .map(value_of)
+        .unwrap();
+        assert!(FilterType::Near.evaluate(&point(10.1620, -68.0077), &near));
+        assert!(!FilterType::Near.evaluate(&point(40.4168, -3.7038), &near));
+        assert!(!FilterType::Near.evaluate(&DataValue::Null, &near));
+
+        let within = condition(
+            "within",
+            serde_json::json!({ "minLat": 0, "minLng": -70, "maxLat": 12, "maxLng": -60 }),
+        )
+        .map(value_of)
+        .unwrap();
+        assert!(FilterType::Within.evaluate(&point(10.1620, -68.0077), &within));
+        assert!(!FilterType::Within.evaluate(&point(10.1620, -71.0), &within));
+
+        assert!(condition("near", serde_json::json!({ "lat": 10, "lng": 10 })).is_err());
+        assert!(condition(
+            "near",
+            serde_json::json!({ "lat": 91, "lng": 10, "radius": 1 })
+        )
+        .is_err());
+        assert!(condition(
+            "within",
+            serde_json::json!({ "minLat": 12, "minLng": -70, "maxLat": 0, "maxLng": -60 })
+        )
+        .is_err());
+        assert!(QueryOps::from_json(
+            &table,
+            &serde_json::json!({ "key": "name", "filterType": "near", "value": { "lat": 0, "lng": 0, "radius": 1 } }),
+        )
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_path_filters() {
+        let table = Table::new("users").add_column(Column::new(
+            "profile",
+            DataTypes::Object(vec![
+                Column::new(
+                    "address",
+                    DataTypes::Object(vec![Column::new("country", DataTypes::String)]),
+                ),
+                Column::new(
+                    "tags",
+                    DataTypes::Array(Box::new(DataTypes::Object(vec![Column::new(
+                        "label",
+                        DataTypes::String,
+                    )
+                    .set_collation(Collation::CaseInsensitive)]))),
+                ),
+            ]),
+        ));
+
+        let path = |path: &str, filter_type: &str, value: serde_json::Value| {
+            QueryOps::from_json(
+                &table,
+                &serde_json::json!({
+                    "key": "profile",
+                    "filterType": "path",
+                    "value": { "path": path, "filterType": filter_type, "value": value }
+                }),
+            )
+        };
+
+        // Fields the schema resolves become conditions on the field
+        assert_eq!(
+            path("address.country", "=", serde_json::json!("US")).unwrap(),
+            QueryOps::Condition(QueryVal {
+                key: "profile.address.country".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String("US".to_string()),
+            })
+        );
+
+        let first_tag = path("tags.0.label", "=", serde_json::json!("ADMIN")).unwrap();
+        let QueryOps::Condition(cond) = &first_tag else {
+            panic!("expected a condition");
+        };
+        assert_eq!(cond.key, "profile");
+        assert_eq!(
+            cond.path_filter(),
+            Some((
+                "tags.0.label",
+                FilterType::Equal,
+                &DataValue::String("ADMIN".to_string())
+            ))
+        );
+
+        let row = |profile: serde_json::Value| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({ "profile": profile }),
+            })
+        };
+        let admin = row(serde_json::json!({ "tags": [{ "label": "admin" }, { "label": "dev" }] }));
+        let dev = row(serde_json::json!({ "tags": [{ "label": "dev" }] }));
+        let untagged = row(serde_json::json!({ "address": { "country": "US" } }));
+
+        assert!(first_tag.matches(&table, &admin));
+        assert!(!first_tag.matches(&table, &dev));
+        assert!(!first_tag.matches(&table, &untagged));
+
+        let not_dev = path("tags.0.label", "!=", serde_json::json!("dev")).unwrap();
+        assert!(not_dev.matches(&table, &admin));
+        assert!(!not_dev.matches(&table, &dev));
+        assert!(not_dev.matches(&table, &untagged));
+
+        let second_tag = path("tags.1.label", "is_not_null", serde_json::Value::Null).unwrap();
+        assert!(second_tag.matches(&table, &admin));
+        assert!(!second_tag.matches(&table, &dev));
+
+        // Values are typed after the nested field, paths must exist in the schema
+        assert!(path("tags.0.label", "=", serde_json::json!(1)).is_err());
+        assert!(path("tags.label", "=", serde_json::json!("admin")).is_err());
+        assert!(path("address.city", "=", serde_json::json!("Caracas")).is_err());
+        assert!(path("", "=", serde_json::json!("US")).is_err());
+        assert!(path("tags.0", "path", serde_json::json!({})).is_err());
+    }
 }
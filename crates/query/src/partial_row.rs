@@ -0,0 +1,25 @@
+use schemajs_primitives::column::types::DataValue;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// `PartialRow` holds a subset of the columns of a row, as requested by a projection.
+///
+/// # Fields:
+/// - `table`: The name of the table the row belongs to.
+/// - `values`: The values of the projected columns, keyed by column name.
+///   Columns that were requested but are not present in the row are not included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialRow {
+    pub table: String,
+    pub values: HashMap<String, DataValue>,
+}
+
+impl PartialRow {
+    pub fn new(table: String, values: HashMap<String, DataValue>) -> Self {
+        Self { table, values }
+    }
+
+    pub fn get_value(&self, column_name: &str) -> Option<&DataValue> {
+        self.values.get(column_name)
+    }
+}
@@ -1,6 +1,11 @@
-use crate::serializer::RowSerializer;
+use crate::errors::QueryError;
+use crate::partial_row::PartialRow;
+use crate::serializer::compact::CompactLayout;
+use crate::serializer::registry::RegisteredSerializer;
+use crate::serializer::{RowSerializationError, RowSerializer};
 use schemajs_primitives::column::types::DataValue;
 use schemajs_primitives::column::Column;
+use schemajs_primitives::table::Table;
 use std::hash::Hash;
 
 /// The `Row` trait defines the core operations that any row in the database must implement.
@@ -12,26 +17,92 @@ use std::hash::Hash;
 /// # Required Methods:
 /// - `get_value`: Retrieves the value of a specific column from the row, returning `Option<DataValue>`.
 /// - `get_table_name`: Returns the name of the table to which the row belongs as a `String`.
-/// - `validate`: Validates the row, ensuring it adheres to certain rules or constraints, returning a `bool` indicating whether the row is valid.
+/// - `set_value`: Sets the value of a specific column in the row.
+/// - `validate`: Validates the row against the columns of its table, such as the type of its values.
+///
+/// # Provided Methods:
+/// - `has_value`: Whether the row holds a value for a column.
+/// - `project`: Builds a `PartialRow` with a subset of the columns out of a serialized row.
+/// - `serialize_compact`: Serializes the row positionally, following the columns of its table.
+/// - `serialize_with`: Serializes the row with a serializer of the serializer registry.
 pub trait Row<T>: RowSerializer<T> + for<'a> From<&'a [u8]> {
     /// Retrieves the value from a specific column in the row.
     ///
     /// # Parameters:
-    /// - `column`: A reference to the `Column` for which to get the value.
+    /// - `column`: A reference to the `Column` for which to get the value. Columns named after a dotted path
+    ///   (see `Table::resolve_column`) read the nested field the path leads to.
     ///
     /// # Returns:
-    /// - `Option<DataValue>`: The value of the column, if present. If the value is not found, it returns `None`.
+    /// - `Option<DataValue>`: The value of the column, if present. Rows missing the column (e.g. written before it
+    ///   was added) get its literal default (see `Column::literal_default_value`), `None` if it has none.
     fn get_value(&self, column: &Column) -> Option<DataValue>;
 
+    /// Whether the row itself holds a value for `column`, as opposed to `get_value` falling back to its default.
+    fn has_value(&self, column: &Column) -> bool {
+        self.get_value(column).is_some()
+    }
+
+    /// Sets the value of a specific column in the row, replacing the previous value if any.
+    ///
+    /// # Parameters:
+    /// - `column`: A reference to the `Column` whose value is set.
+    /// - `value`: The new value of the column.
+    fn set_value(&mut self, column: &Column, value: DataValue);
+
     /// Returns the name of the table to which the row belongs.
     ///
     /// # Returns:
     /// - `String`: The name of the table.
     fn get_table_name(&self) -> String;
 
-    /// Validates the row based on its internal data and constraints. (Such as data types)
+    /// Validates the row based on its internal data and the columns of `table`. (Such as data types)
+    /// It runs before any value is read through `get_value`, which expects values to match their column type.
+    ///
+    /// # Parameters:
+    /// - `table`: The table the row belongs to.
     ///
     /// # Returns:
-    /// - `bool`: `true` if the row is valid, otherwise `false`.
-    fn validate(&self) -> bool;
+    /// - `Result<(), QueryError>`: `InvalidColumnValue` with the first column whose value doesn't match its type.
+    fn validate(&self, table: &Table) -> Result<(), QueryError>;
+
+    /// Serializes the row following the columns of a `CompactLayout` (see `serializer::compact`), so the names
+    /// of the columns aren't stored with every row. Rows serialized this way must still be read back by
+    /// `From<&[u8]>`. The default implementation falls back to `serialize`.
+    fn serialize_compact(&self, _layout: &CompactLayout) -> Result<Vec<u8>, RowSerializationError> {
+        self.serialize()
+    }
+
+    /// Serializes the row, a row of `_table`, with a serializer registered by name (see
+    /// `serializer::registry`). Rows serialized this way must still be read back by `From<&[u8]>`.
+    /// The default implementation falls back to `serialize`.
+    fn serialize_with(
+        &self,
+        _serializer: &RegisteredSerializer,
+        _table: &Table,
+    ) -> Result<Vec<u8>, RowSerializationError> {
+        self.serialize()
+    }
+
+    /// Builds a `PartialRow` holding only `columns` out of the serialized row `data`.
+    /// The default implementation deserializes the whole row, implementations can override it
+    /// to skip the columns that were not requested.
+    ///
+    /// # Parameters:
+    /// - `data`: The serialized row.
+    /// - `columns`: The columns to keep.
+    ///
+    /// # Returns:
+    /// - `PartialRow`: The projected row. Columns not present in the row are left out.
+    fn project(data: &[u8], columns: &[Column]) -> PartialRow {
+        let row = Self::from(data);
+        let values = columns
+            .iter()
+            .filter_map(|column| {
+                row.get_value(column)
+                    .map(|value| (column.name.clone(), value))
+            })
+            .collect();
+
+        PartialRow::new(row.get_table_name(), values)
+    }
 }
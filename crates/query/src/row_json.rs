@@ -1,9 +1,17 @@
+use crate::errors::QueryError;
+use crate::partial_row::PartialRow;
 use crate::row::Row;
 use crate::serializer;
+use crate::serializer::compact;
+use crate::serializer::compact::CompactLayout;
+use crate::serializer::registry::{is_registered, RegisteredSerializer, SerializerRegistry};
 use crate::serializer::RowSerializationError;
 use schemajs_primitives::column::types::DataValue;
 use schemajs_primitives::column::Column;
-use serde::{Deserialize, Serialize};
+use schemajs_primitives::table::Table;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 
 /// `RowData` represents the core structure for storing a row's data in a JSON format.
@@ -36,6 +44,17 @@ impl RowJson {
     }
 
     fn _deserialize(data: &[u8]) -> Result<RowJson, RowSerializationError> {
+        if compact::is_compact(data) {
+            return Ok(Self {
+                value: compact::decode(data)?,
+            });
+        }
+        if is_registered(data) {
+            return Ok(Self {
+                value: SerializerRegistry::global().decode(data)?,
+            });
+        }
+
         let data = serde_json::from_slice::<RowData>(data).map_err(|e| {
             RowSerializationError::DeserializationError("Error Deserializing row".to_string())
         })?;
@@ -44,6 +63,61 @@ impl RowJson {
     }
 }
 
+impl RowJson {
+    /// Deserializes a row written by `serialize`, `serialize_compact` or `serialize_with`.
+    /// Unlike `From<&[u8]>`, invalid rows are an error.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, RowSerializationError> {
+        Self::_deserialize(data)
+    }
+
+    /// The row as exposed to JS: values of the columns of `table` are read back from the form they are
+    /// stored in (e.g. enum ordinals), anything else is returned as stored.
+    pub fn to_json(&self, table: &Table) -> serde_json::Value {
+        let obj = match self.value.value.as_object() {
+            Some(obj) => obj,
+            None => return self.value.value.clone(),
+        };
+
+        let mut values: serde_json::Map<String, serde_json::Value> = obj
+            .iter()
+            .filter(|(name, _)| !table.metadata.dropped_columns.contains(name))
+            .map(|(name, value)| {
+                let value = match table.get_column(name) {
+                    Some(column) => DataValue::from((column, value)).to_json(),
+                    None => value.clone(),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+
+        // Rows written before a column was added are read with its default
+        for column in table.columns.values() {
+            if values.contains_key(&column.name) {
+                continue;
+            }
+            if let Some(default) = column.literal_default_value() {
+                values.insert(column.name.clone(), default.to_json());
+            }
+        }
+
+        serde_json::Value::Object(values)
+    }
+}
+
+impl RowJson {
+    /// Value stored for `name` in the row, either a top-level field or the nested field a dotted path
+    /// (`profile.address.country`) leads to. Top-level fields whose name holds dots win over paths.
+    pub(crate) fn field(&self, name: &str) -> Option<&serde_json::Value> {
+        let value = &self.value.value;
+        value.get(name).or_else(|| match name.contains('.') {
+            true => name
+                .split('.')
+                .try_fold(value, |value, segment| value.get(segment)),
+            false => None,
+        })
+    }
+}
+
 impl From<RowData> for RowJson {
     fn from(value: RowData) -> Self {
         RowJson { value }
@@ -74,18 +148,178 @@ impl From<&[u8]> for RowJson {
 
 impl Row<RowJson> for RowJson {
     fn get_value(&self, column: &Column) -> Option<DataValue> {
-        let potential_val = self.value.value.get(column.name.to_string());
+        let potential_val = self.field(&column.name);
         match potential_val {
-            None => return None,
+            None => column.literal_default_value(),
             Some(val) => Some(DataValue::from((column, val))),
         }
     }
 
+    fn has_value(&self, column: &Column) -> bool {
+        self.field(&column.name).is_some()
+    }
+
+    fn set_value(&mut self, column: &Column, value: DataValue) {
+        if !self.value.value.is_object() {
+            self.value.value = serde_json::Value::Object(Default::default());
+        }
+
+        self.value
+            .value
+            .as_object_mut()
+            .unwrap()
+            .insert(column.name.clone(), column.data_type.to_stored_json(&value));
+    }
+
     fn get_table_name(&self) -> String {
         self.value.table.clone()
     }
 
-    fn validate(&self) -> bool {
-        todo!()
+    fn validate(&self, table: &Table) -> Result<(), QueryError> {
+        for column in table.columns.values() {
+            if let Some(value) = self.value.value.get(column.name.as_str()) {
+                if !column.data_type.accepts(value) {
+                    return Err(QueryError::InvalidColumnValue(column.name.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn serialize_compact(&self, layout: &CompactLayout) -> Result<Vec<u8>, RowSerializationError> {
+        compact::encode(layout, &self.value)
+    }
+
+    fn serialize_with(
+        &self,
+        serializer: &RegisteredSerializer,
+        table: &Table,
+    ) -> Result<Vec<u8>, RowSerializationError> {
+        serializer.encode(table, &self.value)
+    }
+
+    fn project(data: &[u8], columns: &[Column]) -> PartialRow {
+        // Only JSON rows can be projected while they are read, the others are decoded whole
+        if compact::is_compact(data) || is_registered(data) {
+            let row = Self::from(data);
+            let values = columns
+                .iter()
+                .filter_map(|column| {
+                    row.get_value(column)
+                        .map(|value| (column.name.clone(), value))
+                })
+                .collect();
+            return PartialRow::new(row.get_table_name(), values);
+        }
+
+        let mut deserializer = serde_json::Deserializer::from_slice(data);
+        ProjectedRowData { columns }
+            .deserialize(&mut deserializer)
+            .unwrap()
+    }
+}
+
+/// Deserializes a `RowData` into a `PartialRow`, skipping the columns that are not part of `columns`
+/// without building their `serde_json::Value`.
+struct ProjectedRowData<'a> {
+    columns: &'a [Column],
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for ProjectedRowData<'a> {
+    type Value = PartialRow;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ProjectedRowData<'a> {
+    type Value = PartialRow;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a serialized row")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut table = None;
+        let mut values = HashMap::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "table" => table = Some(map.next_value::<String>()?),
+                "value" => {
+                    values = map.next_value_seed(ProjectedValues {
+                        columns: self.columns,
+                    })?
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let table = table.ok_or_else(|| serde::de::Error::missing_field("table"))?;
+
+        Ok(PartialRow::new(table, values))
+    }
+}
+
+struct ProjectedValues<'a> {
+    columns: &'a [Column],
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for ProjectedValues<'a> {
+    type Value = HashMap<String, DataValue>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ProjectedValues<'a> {
+    type Value = HashMap<String, DataValue>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a map of column values")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut values = HashMap::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match self.columns.iter().find(|column| column.name == key) {
+                Some(column) => {
+                    let value = map.next_value::<serde_json::Value>()?;
+                    values.insert(key, DataValue::from((column, &value)));
+                }
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        for column in self.columns {
+            if values.contains_key(&column.name) {
+                continue;
+            }
+            if let Some(default) = column.literal_default_value() {
+                values.insert(column.name.clone(), default);
+            }
+        }
+
+        Ok(values)
     }
 }
@@ -0,0 +1,51 @@
+use crate::errors::QueryError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Tells a running query to stop, either because `cancel` was called (on any of its clones) or because
+/// its deadline passed. Queries check it between batches of rows and fail with `QueryError::Timeout`.
+///
+/// # Fields:
+/// - `cancelled`: Set by `cancel`, shared by every clone of the token.
+/// - `deadline`: When the query must be done by, no deadline when `None`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the token expire once `timeout` elapsed from now, keeping an earlier deadline if it has one.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        let deadline = Instant::now() + timeout;
+        self.deadline = Some(
+            self.deadline
+                .map_or(deadline, |current| current.min(deadline)),
+        );
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+
+    /// Fails with `QueryError::Timeout` once the token is cancelled.
+    pub fn check(&self) -> Result<(), QueryError> {
+        match self.is_cancelled() {
+            true => Err(QueryError::Timeout),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use serde::Serialize;
+
+/// Statistics of an index over the live rows of its table, used by the planner to compare indexes.
+/// They are computed by `TableShard::refresh_index_stats` and go stale as rows are written.
+///
+/// # Fields:
+/// - `entries`: Amount of indexed rows (rows whose key is entirely null are not indexed).
+/// - `distinct_keys`: Amount of different keys among the indexed rows, the cardinality of the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct IndexStats {
+    pub entries: u64,
+    pub distinct_keys: u64,
+}
+
+impl IndexStats {
+    /// Share of the indexed rows held by an average key, from `0` (no rows) to `1` (a single key).
+    pub fn selectivity(&self) -> f64 {
+        if self.distinct_keys == 0 {
+            return 0.0;
+        }
+
+        1.0 / self.distinct_keys as f64
+    }
+
+    /// Rows an equality lookup of a single key is expected to return.
+    pub fn estimated_rows(&self) -> f64 {
+        self.entries as f64 * self.selectivity()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::search::index_stats::IndexStats;
+
+    #[test]
+    pub fn test_index_stats() {
+        let stats = IndexStats {
+            entries: 100,
+            distinct_keys: 4,
+        };
+        assert_eq!(stats.selectivity(), 0.25);
+        assert_eq!(stats.estimated_rows(), 25.0);
+
+        assert_eq!(IndexStats::default().estimated_rows(), 0.0);
+    }
+}
@@ -1 +1,7 @@
-mod search_manager;
+pub mod cancel_token;
+pub mod index_stats;
+pub mod search_manager;
+pub mod search_opts;
+pub mod search_page;
+pub mod slow_query;
+pub mod table_view;
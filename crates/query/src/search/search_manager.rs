@@ -1,20 +1,138 @@
 use crate::errors::QueryError;
 use crate::managers::single::table_shard::TableShard;
-use crate::ops::query_ops::{QueryOps, QueryVal};
+use crate::ops::aggregate::{Aggregate, AggregateGroup, AggregateState};
+use crate::ops::between::BetweenFilter;
+use crate::ops::geo::GeoFilter;
+use crate::ops::join::{Join, JoinType, JoinedRow};
+use crate::ops::pipeline::Pipeline;
+use crate::ops::query_ops::{FilterType, QueryOps, QueryPlan, QueryVal};
+use crate::partial_row::PartialRow;
 use crate::row::Row;
-use chashmap::CHashMap;
+use crate::search::cancel_token::CancelToken;
+use crate::search::index_stats::IndexStats;
+use crate::search::search_opts::{SearchOpts, SortBy, SortDirection};
+use crate::search::search_page::{SearchCursor, SearchPage, SortKey};
+use crate::search::slow_query::{SlowQuery, SlowQueryLog};
+use crate::search::table_view::TableView;
+use chashmap::{CHashMap, ReadGuard};
+use schemajs_data::bloom::ShardBlooms;
+use schemajs_data::metrics::Metrics;
+use schemajs_data::shard::map_shard::MapShard;
+use schemajs_data::shard::shards::data_shard::config::DataShardConfig;
+use schemajs_data::shard::shards::data_shard::shard::DataShard;
+use schemajs_data::snapshot::Snapshot;
 use schemajs_index::composite_key::CompositeKey;
+use schemajs_index::index_type::IndexType;
+use schemajs_primitives::column::types::DataValue;
+use schemajs_primitives::column::Column;
 use schemajs_primitives::index::Index;
-use std::collections::HashSet;
+use schemajs_primitives::table::Table;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::{Bound, Range};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Amount of rows read by a query between two checks of its `CancelToken`.
+pub const CANCEL_CHECK_INTERVAL: usize = 1024;
 
 pub struct QuerySearchManager<T: Row<T>> {
     table_shards: Arc<CHashMap<String, TableShard<T>>>,
+    slow_queries: Option<Arc<SlowQueryLog>>,
+    cancel: CancelToken,
+    scan_threads: usize,
 }
 
 impl<T: Row<T>> QuerySearchManager<T> {
     pub fn new(table_shards: Arc<CHashMap<String, TableShard<T>>>) -> Self {
-        Self { table_shards }
+        Self {
+            table_shards,
+            slow_queries: None,
+            cancel: CancelToken::new(),
+            scan_threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+
+    /// Scans tables spanning several shard files with up to `threads` threads, each scanning whole shard files.
+    /// Defaults to the available parallelism, `1` scans every shard in the calling thread.
+    pub fn set_scan_threads(mut self, threads: usize) -> Self {
+        self.scan_threads = threads.max(1);
+        self
+    }
+
+    /// Makes the queries of this manager fail with `QueryError::Timeout` once `timeout` elapsed from now.
+    /// Search managers are meant to be created per query (see `SingleQueryManager::search_manager`), so this is
+    /// the deadline of the query about to run.
+    pub fn set_timeout(mut self, timeout: Option<Duration>) -> Self {
+        if let Some(timeout) = timeout {
+            self.cancel = self.cancel.with_timeout(timeout);
+        }
+        self
+    }
+
+    /// Makes the queries of this manager fail with `QueryError::Timeout` once `cancel` is cancelled, e.g. from
+    /// another thread when the client of the query went away.
+    pub fn set_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Whether a query that read `read` rows so far has to stop. The token is only looked at
+    /// every `CANCEL_CHECK_INTERVAL` rows.
+    fn should_stop(&self, read: usize) -> bool {
+        read % CANCEL_CHECK_INTERVAL == 0 && self.cancel.is_cancelled()
+    }
+
+    /// Records the searches taking longer than the threshold of `slow_queries` into it.
+    pub fn set_slow_queries(mut self, slow_queries: Option<Arc<SlowQueryLog>>) -> Self {
+        self.slow_queries = slow_queries;
+        self
+    }
+
+    /// Records `ops` into the slow query log if it took longer than its threshold since `started`.
+    /// The plan is only computed again (to know the indexes it used) for slow queries.
+    pub(crate) fn log_if_slow(
+        &self,
+        operation: &str,
+        shard: &TableShard<T>,
+        ops: &QueryOps,
+        pointers: usize,
+        started: Instant,
+    ) {
+        let slow_queries = match &self.slow_queries {
+            Some(slow_queries) => slow_queries,
+            None => return,
+        };
+
+        let elapsed = started.elapsed();
+        if !slow_queries.is_slow(elapsed) {
+            return;
+        }
+
+        slow_queries.record(SlowQuery {
+            operation: operation.to_string(),
+            table: shard.table.name.clone(),
+            shape: ops.shape(),
+            indexes: Self::plan_query(shard, ops).indexes(),
+            pointers,
+            elapsed,
+        });
+    }
+
+    /// Shard of `table_name` along with the rows a search on it sees, rows still sitting in its temporary
+    /// shards included (see `TableView`).
+    fn table_shard(
+        &self,
+        table_name: &str,
+    ) -> Result<(ReadGuard<'_, String, TableShard<T>>, TableView), QueryError> {
+        let table_shard = self
+            .table_shards
+            .get(table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.to_string()))?;
+        let view = TableView::new(&table_shard);
+
+        Ok((table_shard, view))
     }
 
     fn intersect_indices(a: Vec<u64>, b: Vec<u64>) -> Vec<u64> {
@@ -30,116 +148,851 @@ impl<T: Row<T>> QuerySearchManager<T> {
         set_a.into_iter().collect()
     }
 
-    fn get_index_for_condition(cond: &QueryVal, indexes: &Vec<Index>) -> Option<Index> {
-        for index in indexes.iter() {
-            if index.members.len() == 1 && index.members[0] == cond.key {
-                return Some(index.clone());
+    fn get_index_for_condition(
+        cond: &QueryVal,
+        indexes: &Vec<Index>,
+        stats: &HashMap<String, IndexStats>,
+    ) -> Option<Index> {
+        // Geo indexes only hold the cell of the points, multi-entry indexes the items of the arrays
+        Self::cheapest_index(
+            indexes.iter().filter(|index| {
+                index.index_type != IndexType::Geo
+                    && index.index_type != IndexType::MultiEntry
+                    && index.members.len() == 1
+                    && index.members[0] == cond.key
+            }),
+            stats,
+        )
+    }
+
+    /// Picks the index of `candidates` whose lookups are expected to return the fewest rows according to `stats`.
+    /// Indexes without statistics come last. On a tie, the index with the most members wins, then the one declared first.
+    fn cheapest_index<'a>(
+        candidates: impl Iterator<Item = &'a Index>,
+        stats: &HashMap<String, IndexStats>,
+    ) -> Option<Index> {
+        let mut cheapest: Option<(&Index, f64)> = None;
+
+        for index in candidates {
+            let cost = stats
+                .get(&index.name)
+                .map_or(f64::INFINITY, |stats| stats.estimated_rows());
+
+            let is_cheaper = match cheapest {
+                None => true,
+                Some((cheapest_index, cheapest_cost)) => {
+                    cost < cheapest_cost
+                        || (cost == cheapest_cost
+                            && index.members.len() > cheapest_index.members.len())
+                }
+            };
+
+            if is_cheaper {
+                cheapest = Some((index, cost));
+            }
+        }
+
+        cheapest.map(|(index, _)| index.clone())
+    }
+
+    /// Rows `plan` is expected to return, for the index lookups of indexes with statistics.
+    /// Other plans can't be estimated.
+    fn estimated_rows(plan: &QueryPlan, stats: &HashMap<String, IndexStats>) -> Option<f64> {
+        match plan {
+            QueryPlan::Empty => Some(0.0),
+            QueryPlan::IndexLookup { index, conditions } => {
+                let lookups = match conditions.as_slice() {
+                    [cond] => match &cond.value {
+                        DataValue::Array(candidates) => candidates.len(),
+                        _ => 1,
+                    },
+                    _ => 1,
+                };
+
+                stats
+                    .get(index)
+                    .map(|stats| stats.estimated_rows() * lookups as f64)
+            }
+            _ => None,
+        }
+    }
+
+    fn get_ordered_index_for_condition(cond: &QueryVal, indexes: &Vec<Index>) -> Option<Index> {
+        indexes
+            .iter()
+            .find(|index| {
+                index.index_type == IndexType::BTree
+                    && index.members.len() == 1
+                    && index.members[0] == cond.key
+            })
+            .cloned()
+    }
+
+    fn get_multi_entry_index_for_condition(
+        cond: &QueryVal,
+        indexes: &Vec<Index>,
+        stats: &HashMap<String, IndexStats>,
+    ) -> Option<Index> {
+        Self::cheapest_index(
+            indexes.iter().filter(|index| {
+                index.index_type == IndexType::MultiEntry
+                    && index.members.len() == 1
+                    && index.members[0] == cond.key
+            }),
+            stats,
+        )
+    }
+
+    fn get_geo_index_for_condition(cond: &QueryVal, indexes: &Vec<Index>) -> Option<Index> {
+        indexes
+            .iter()
+            .find(|index| {
+                index.index_type == IndexType::Geo
+                    && index.members.len() == 1
+                    && index.members[0] == cond.key
+            })
+            .cloned()
+    }
+
+    /// Returns the pointers of the live rows matched by `query`.
+    ///
+    /// The query runs on the snapshot of the table taken when it starts: rows written afterwards are ignored and
+    /// rows updated or deleted afterwards are seen as they were. Since an index may already point to the new version
+    /// of an updated row, the previous version is checked against `query` again before being returned.
+    ///
+    /// Scans stop early once the `CancelToken` of the manager is cancelled, so the pointers are only complete
+    /// if it is still not cancelled afterwards.
+    pub(crate) fn execute_query(&self, tbl: &TableShard<T>, query: &QueryOps) -> Vec<u64> {
+        self.execute_query_at(tbl, query, &tbl.snapshot())
+    }
+
+    /// Same as `execute_query`, followed by the pending rows of `view` matching `query` (see `TableView`).
+    fn execute_view(&self, tbl: &TableShard<T>, view: &TableView, query: &QueryOps) -> Vec<u64> {
+        let mut pointers = self.execute_query_at(tbl, query, &view.snapshot);
+
+        for (read, (pointer, data)) in view.pending().enumerate() {
+            if self.should_stop(read) {
+                break;
+            }
+
+            if query.matches(&tbl.table, &T::from(data)) {
+                pointers.push(pointer);
+            }
+        }
+
+        pointers
+    }
+
+    /// Same as `execute_query`, on `snapshot`.
+    pub(crate) fn execute_query_at(
+        &self,
+        tbl: &TableShard<T>,
+        query: &QueryOps,
+        snapshot: &Snapshot,
+    ) -> Vec<u64> {
+        let plan = Self::plan_query(tbl, query);
+        let pointers = self.execute_plan(tbl, &plan);
+
+        let mut seen = HashSet::new();
+        let mut visible = vec![];
+
+        for (read, pointer) in pointers.into_iter().enumerate() {
+            if self.should_stop(read) {
+                break;
+            }
+
+            let version = match tbl.visible_version(pointer, snapshot) {
+                Some(version) => version,
+                None => continue,
+            };
+
+            if seen.contains(&version) {
+                continue;
+            }
+
+            if version != pointer && !self.row_matches(tbl, query, version) {
+                continue;
             }
+
+            seen.insert(version);
+            visible.push(version);
         }
-        None
+
+        visible
+    }
+
+    /// Evaluates `query` against the row at `pointer`, without going through indexes.
+    fn row_matches(&self, tbl: &TableShard<T>, query: &QueryOps, pointer: u64) -> bool {
+        let row = match tbl.data.read().unwrap().get_element(pointer as usize) {
+            Ok(data) => T::from(data.as_slice()),
+            Err(_) => return false,
+        };
+
+        query.matches(&tbl.table, &row)
     }
 
-    fn execute_query(&self, tbl: &TableShard<T>, query: &QueryOps) -> Vec<u64> {
+    /// Builds the plan used to resolve `query`.
+    ///
+    /// When the query is made of equality conditions, the cheapest index (see `cheapest_index`) made of some of them
+    /// is looked up and the rows it returns are checked against the rest. Otherwise, conditions are planned one by one:
+    /// equality and `in` use the cheapest single-member index, `!=` uses the anti-index of one,
+    /// range and prefix filters need an ordered index, `near` and `within` need a geo index, `contains` needs a
+    /// multi-entry index and anything else falls back to a scan.
+    fn plan_query(tbl: &TableShard<T>, query: &QueryOps) -> QueryPlan {
+        let stats = tbl.index_stats.read().unwrap();
+        Self::plan_query_with(tbl, query, &stats)
+    }
+
+    fn plan_query_with(
+        tbl: &TableShard<T>,
+        query: &QueryOps,
+        stats: &HashMap<String, IndexStats>,
+    ) -> QueryPlan {
         let indexes = &tbl.table.indexes;
+
         // Try to find an index that can be used for the entire query
-        if let Some(index_query) = Self::find_index_for_query(query, indexes) {
-            if let Some(indx_manager) = tbl.indexes.get(&index_query.0.name) {
-                let manager = indx_manager.as_index();
-                let key = manager.to_key(index_query.1);
-                // TODO: get_all to return vec in index
-                if let Some(pointer) = manager.get(&key) {
-                    vec![pointer]
-                } else {
-                    Vec::new()
+        if let Some((index, conditions, rest)) = Self::find_index_for_query(query, indexes, stats) {
+            let lookup = QueryPlan::IndexLookup {
+                index: index.name,
+                conditions,
+            };
+
+            return match rest.is_empty() {
+                true => lookup,
+                false => QueryPlan::Filter {
+                    input: Box::new(lookup),
+                    conditions: rest,
+                },
+            };
+        }
+
+        // Plan recursively
+        match query {
+            QueryOps::Condition(cond) => Self::plan_condition(cond, indexes, stats),
+            // An empty AND has no conditions, so it matches every row
+            QueryOps::And(ops) if ops.is_empty() => QueryPlan::FullScan,
+            QueryOps::And(ops) => Self::plan_intersection(tbl, ops, stats),
+            QueryOps::Or(ops) => QueryPlan::Union {
+                inputs: ops
+                    .iter()
+                    .map(|op| Self::plan_query_with(tbl, op, stats))
+                    .collect(),
+            },
+        }
+    }
+
+    /// Plans the operations of an AND. The index lookup expected to return the fewest rows drives it:
+    /// conditions that would be scanned or looked up on an index returning more rows are checked on its rows instead.
+    fn plan_intersection(
+        tbl: &TableShard<T>,
+        ops: &[QueryOps],
+        stats: &HashMap<String, IndexStats>,
+    ) -> QueryPlan {
+        let inputs: Vec<QueryPlan> = ops
+            .iter()
+            .map(|op| Self::plan_query_with(tbl, op, stats))
+            .collect();
+
+        let driver_rows = inputs
+            .iter()
+            .filter_map(|input| Self::estimated_rows(input, stats))
+            .reduce(f64::min);
+
+        let driver_rows = match driver_rows {
+            Some(driver_rows) => driver_rows,
+            None => return QueryPlan::Intersection { inputs },
+        };
+
+        let mut kept = vec![];
+        let mut filtered = vec![];
+        let mut has_driver = false;
+
+        for (op, input) in ops.iter().zip(inputs) {
+            let rows = Self::estimated_rows(&input, stats);
+
+            if rows == Some(driver_rows) && !has_driver {
+                has_driver = true;
+                kept.push(input);
+                continue;
+            }
+
+            let costs_more = match &input {
+                QueryPlan::Scan { .. } | QueryPlan::IndexExclusion { .. } => true,
+                _ => rows.map_or(false, |rows| rows > driver_rows),
+            };
+
+            match op {
+                QueryOps::Condition(cond) if costs_more => filtered.push(cond.clone()),
+                _ => kept.push(input),
+            }
+        }
+
+        let input = match kept.len() {
+            1 => kept.remove(0),
+            _ => QueryPlan::Intersection { inputs: kept },
+        };
+
+        match filtered.is_empty() {
+            true => input,
+            false => QueryPlan::Filter {
+                input: Box::new(input),
+                conditions: filtered,
+            },
+        }
+    }
+
+    fn plan_condition(
+        cond: &QueryVal,
+        indexes: &Vec<Index>,
+        stats: &HashMap<String, IndexStats>,
+    ) -> QueryPlan {
+        let filter_type = match cond.get_filter_type() {
+            Ok(filter_type) => filter_type,
+            Err(_) => return QueryPlan::Empty,
+        };
+
+        let scan = QueryPlan::Scan {
+            condition: cond.clone(),
+        };
+
+        match filter_type {
+            FilterType::Equal | FilterType::In => {
+                match Self::get_index_for_condition(cond, indexes, stats) {
+                    Some(index) => QueryPlan::IndexLookup {
+                        index: index.name,
+                        conditions: vec![cond.clone()],
+                    },
+                    None => scan,
                 }
-            } else {
-                Vec::new()
             }
-        } else {
-            // Evaluate recursively
-            match query {
-                QueryOps::Condition(cond) => {
-                    return self.evaluate_condition(&tbl, cond, indexes);
+            FilterType::NotEqual => match Self::get_index_for_condition(cond, indexes, stats) {
+                Some(index) => QueryPlan::IndexExclusion {
+                    index: index.name,
+                    condition: cond.clone(),
+                },
+                None => scan,
+            },
+            // Ranges never match a null and nulls are not indexed
+            _ if filter_type.is_range() && cond.value.is_null() => QueryPlan::Empty,
+            FilterType::GreaterThan
+            | FilterType::LowerThan
+            | FilterType::GreaterOrEqualTo
+            | FilterType::LowerOrEqualTo
+            | FilterType::StartsWith => {
+                // Hash indexes have no notion of order, without an ordered index the table is scanned.
+                match Self::get_ordered_index_for_condition(cond, indexes) {
+                    Some(index) => QueryPlan::IndexRange {
+                        index: index.name,
+                        condition: cond.clone(),
+                    },
+                    None => scan,
                 }
-                QueryOps::And(ops) => {
-                    let mut results: Option<Vec<u64>> = None;
-                    for op in ops {
-                        let res = self.execute_query(tbl, op);
-                        results = match results {
-                            Some(existing) => Some(Self::intersect_indices(existing, res)),
-                            None => Some(res),
-                        };
+            }
+            // Both bounds are answered by a single range scan of the ordered index
+            FilterType::Between => match BetweenFilter::from_value(&cond.value) {
+                Some(filter) if !filter.is_empty() => {
+                    match Self::get_ordered_index_for_condition(cond, indexes) {
+                        Some(index) => QueryPlan::IndexRange {
+                            index: index.name,
+                            condition: cond.clone(),
+                        },
+                        None => scan,
+                    }
+                }
+                _ => QueryPlan::Empty,
+            },
+            // The rows holding the item are the ones indexed under it
+            FilterType::Contains => {
+                match Self::get_multi_entry_index_for_condition(cond, indexes, stats) {
+                    Some(index) => QueryPlan::IndexLookup {
+                        index: index.name,
+                        conditions: vec![cond.clone()],
+                    },
+                    None => scan,
+                }
+            }
+            FilterType::Near | FilterType::Within => {
+                match Self::get_geo_index_for_condition(cond, indexes) {
+                    Some(index) => QueryPlan::IndexGeo {
+                        index: index.name,
+                        condition: cond.clone(),
+                    },
+                    None => scan,
+                }
+            }
+            // Paths the indexes hold are planned as conditions on the nested field
+            FilterType::Path => match cond.path_filter() {
+                Some((path, filter_type, value)) => {
+                    let field = QueryVal {
+                        key: format!("{}.{}", cond.key, path),
+                        filter_type: filter_type.to_string(),
+                        value: value.clone(),
+                    };
+                    match indexes
+                        .iter()
+                        .any(|index| index.members.contains(&field.key))
+                    {
+                        true => Self::plan_condition(&field, indexes, stats),
+                        false => scan,
                     }
-                    return results.unwrap_or_else(Vec::new);
                 }
-                QueryOps::Or(ops) => {
-                    let mut results = Vec::new();
-                    for op in ops {
-                        let res = self.execute_query(tbl, op);
-                        results = Self::union_indices(results, res);
+                None => QueryPlan::Empty,
+            },
+            FilterType::Like | FilterType::Regex | FilterType::IsNull | FilterType::IsNotNull => {
+                scan
+            }
+        }
+    }
+
+    fn execute_plan(&self, shard: &TableShard<T>, plan: &QueryPlan) -> Vec<u64> {
+        let find_index = |name: &String| shard.table.indexes.iter().find(|i| &i.name == name);
+
+        match plan {
+            QueryPlan::Empty => Vec::new(),
+            QueryPlan::FullScan => self.all_pointers(shard),
+            QueryPlan::IndexLookup { index, conditions } => {
+                let index = match find_index(index) {
+                    Some(index) => index,
+                    None => return Vec::new(),
+                };
+
+                match conditions.as_slice() {
+                    [cond] if cond.get_filter_type().ok() == Some(FilterType::In) => {
+                        // Expanded into a union of index lookups, one per candidate
+                        let candidates = match &cond.value {
+                            DataValue::Array(candidates) => candidates.clone(),
+                            value => vec![value.clone()],
+                        };
+
+                        let mut results = Vec::new();
+                        for candidate in candidates.iter() {
+                            let res = self.lookup_value(shard, index, cond.key.as_str(), candidate);
+                            results = Self::union_indices(results, res);
+                        }
+                        results
                     }
-                    return results;
+                    _ => match Self::generate_index_key(&shard.table, index, conditions) {
+                        Some(key) => self.lookup_key(shard, index, key),
+                        None => Vec::new(),
+                    },
+                }
+            }
+            QueryPlan::IndexRange { index, condition } => {
+                let filter_type = match condition.get_filter_type() {
+                    Ok(filter_type) => filter_type,
+                    Err(_) => return Vec::new(),
+                };
+
+                find_index(index)
+                    .and_then(|index| self.range_condition(shard, index, condition, &filter_type))
+                    .unwrap_or_else(|| self.scan_condition(shard, condition, &filter_type))
+            }
+            QueryPlan::IndexGeo { index, condition } => {
+                let filter_type = match condition.get_filter_type() {
+                    Ok(filter_type) => filter_type,
+                    Err(_) => return Vec::new(),
+                };
+
+                match find_index(index) {
+                    Some(index) => self.geo_condition(shard, index, condition, &filter_type),
+                    None => self.scan_condition(shard, condition, &filter_type),
+                }
+            }
+            QueryPlan::IndexExclusion { index, condition } => {
+                let index = match find_index(index) {
+                    Some(index) => index,
+                    None => return Vec::new(),
+                };
+
+                // Anti-index: every pointer in the table minus the ones matching the value
+                let matched =
+                    self.lookup_value(shard, index, condition.key.as_str(), &condition.value);
+                Self::subtract_indices(self.all_pointers(shard), matched)
+            }
+            QueryPlan::Scan { condition } => match condition.get_filter_type() {
+                Ok(filter_type) => self.scan_condition(shard, condition, &filter_type),
+                Err(_) => Vec::new(),
+            },
+            QueryPlan::Filter { input, conditions } => {
+                let pointers = self.execute_plan(shard, input);
+                let data = shard.data.read().unwrap();
+
+                pointers
+                    .into_iter()
+                    .enumerate()
+                    .take_while(|(read, _)| !self.should_stop(*read))
+                    .map(|(_, pointer)| pointer)
+                    .filter(|pointer| match data.get_element(*pointer as usize) {
+                        Ok(item) => {
+                            let row = T::from(item.as_slice());
+                            conditions.iter().all(|cond| {
+                                QueryOps::Condition(cond.clone()).matches(&shard.table, &row)
+                            })
+                        }
+                        Err(_) => false,
+                    })
+                    .collect()
+            }
+            QueryPlan::Intersection { inputs } => {
+                let mut results: Option<Vec<u64>> = None;
+                for input in inputs {
+                    let res = self.execute_plan(shard, input);
+                    results = match results {
+                        Some(existing) => Some(Self::intersect_indices(existing, res)),
+                        None => Some(res),
+                    };
+                }
+                results.unwrap_or_else(Vec::new)
+            }
+            QueryPlan::Union { inputs } => {
+                let mut results = Vec::new();
+                for input in inputs {
+                    let res = self.execute_plan(shard, input);
+                    results = Self::union_indices(results, res);
                 }
+                results
             }
         }
     }
 
-    fn evaluate_condition(
+    /// Resolves the pointers stored under `comp_key` in `index`.
+    fn lookup_key(&self, shard: &TableShard<T>, index: &Index, comp_key: CompositeKey) -> Vec<u64> {
+        let indx_read = match shard.indexes.get(&index.name) {
+            Some(indx_read) => indx_read,
+            None => return vec![],
+        };
+        let indx = indx_read.as_index();
+        let key = indx.to_key(comp_key);
+
+        indx.get_all(&key)
+    }
+
+    /// Resolves the pointers whose indexed value for `key` is equal to `value` in a single-member index.
+    fn lookup_value(
         &self,
         shard: &TableShard<T>,
-        cond: &QueryVal,
-        indexes: &Vec<Index>,
+        index: &Index,
+        key: &str,
+        value: &DataValue,
     ) -> Vec<u64> {
-        if cond.filter_type != "=" {
-            // Only "=" is supported
-            return Vec::new();
-        }
+        let value = value.clone().collate(shard.table.collation(key));
+        let comp_key = CompositeKey(vec![(
+            key.to_string(),
+            TableShard::<T>::index_value(&index.index_type, &value),
+        )]);
+
+        self.lookup_key(shard, index, comp_key)
+    }
+
+    /// Answers a range (or prefix) condition through an index range scan, `between` conditions scanning
+    /// from one bound to the other.
+    /// Returns `None` if the index can't answer range scans.
+    fn range_condition(
+        &self,
+        shard: &TableShard<T>,
+        index: &Index,
+        cond: &QueryVal,
+        filter_type: &FilterType,
+    ) -> Option<Vec<u64>> {
+        let collation = shard.table.collation(&cond.key);
+        let index_value = |value: &DataValue| {
+            TableShard::<T>::index_value(&index.index_type, &value.clone().collate(collation))
+        };
+        let value = index_value(&cond.value);
 
-        if let Some(index) = Self::get_index_for_condition(cond, indexes) {
-            let comp_key = CompositeKey(vec![(cond.key.to_string(), (&cond.value).to_string())]);
+        let indx_read = shard.indexes.get(&index.name).unwrap();
+        let indx = indx_read.as_index();
+        let to_key = |val: String| indx.to_key(CompositeKey(vec![(cond.key.to_string(), val)]));
+        let to_bound = |bound: &Bound<DataValue>| match bound {
+            Bound::Included(value) => Bound::Included(to_key(index_value(value))),
+            Bound::Excluded(value) => Bound::Excluded(to_key(index_value(value))),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        let (from, to) = match filter_type {
+            FilterType::GreaterThan => (Bound::Excluded(to_key(value)), Bound::Unbounded),
+            FilterType::GreaterOrEqualTo => (Bound::Included(to_key(value)), Bound::Unbounded),
+            FilterType::LowerThan => (Bound::Unbounded, Bound::Excluded(to_key(value))),
+            FilterType::LowerOrEqualTo => (Bound::Unbounded, Bound::Included(to_key(value))),
+            FilterType::StartsWith => {
+                // Every key starting with `value` sorts between `value` and `value` followed by the highest char.
+                let upper = format!("{}{}", value, char::MAX);
+                (
+                    Bound::Included(to_key(value)),
+                    Bound::Included(to_key(upper)),
+                )
+            }
+            FilterType::Between => {
+                let filter = BetweenFilter::from_value(&cond.value)?;
+                (to_bound(&filter.min), to_bound(&filter.max))
+            }
+            _ => return None,
+        };
+
+        indx.range(from, to)
+    }
+
+    /// Answers a `near` or `within` condition through a geo index: rows stored in the geohash cells
+    /// covering the condition are read and checked against it.
+    fn geo_condition(
+        &self,
+        shard: &TableShard<T>,
+        index: &Index,
+        cond: &QueryVal,
+        filter_type: &FilterType,
+    ) -> Vec<u64> {
+        let (column, filter) = match (
+            shard.table.resolve_column(cond.key.as_str()),
+            GeoFilter::from_value(filter_type, &cond.value),
+        ) {
+            (Some(column), Some(filter)) => (column, filter),
+            _ => return Vec::new(),
+        };
 
-            let indx_read = shard.indexes.get(&index.name).unwrap();
+        let candidates = {
+            let indx_read = match shard.indexes.get(&index.name) {
+                Some(indx_read) => indx_read,
+                None => return Vec::new(),
+            };
             let indx = indx_read.as_index();
-            let key = indx.to_key(comp_key);
-            if let Some(pointer) = indx.get(&key) {
-                return vec![pointer];
+            let to_key = |val: String| indx.to_key(CompositeKey(vec![(cond.key.to_string(), val)]));
+
+            let mut candidates = Vec::new();
+            for prefix in filter.bounding_box().covering_geohashes() {
+                let upper = format!("{}{}", prefix, char::MAX);
+                let cell = indx
+                    .range(
+                        Bound::Included(to_key(prefix)),
+                        Bound::Included(to_key(upper)),
+                    )
+                    .unwrap_or_default();
+                candidates = Self::union_indices(candidates, cell);
             }
-        }
+            candidates
+        };
 
-        vec![]
+        let data = shard.data.read().unwrap();
+        candidates
+            .into_iter()
+            .enumerate()
+            .take_while(|(read, _)| !self.should_stop(*read))
+            .map(|(_, pointer)| pointer)
+            .filter(|pointer| match data.get_element(*pointer as usize) {
+                Ok(item) => cond.matches(&column, filter_type, &T::from(item.as_slice())),
+                Err(_) => false,
+            })
+            .collect()
     }
 
-    fn find_index_for_query(
-        query: &QueryOps,
-        indexes: &Vec<Index>,
-    ) -> Option<(Index, CompositeKey)> {
-        if let Some(conditions) = Self::collect_conditions(query) {
-            if let Some(index) = Self::find_index_for_conditions(&conditions, indexes) {
-                let key = Self::generate_index_key(&index, &conditions);
-                if let Some(key) = key {
-                    return Some((index, key));
+    /// Enumerates every row pointer stored in the table's master shards.
+    fn all_pointers(&self, shard: &TableShard<T>) -> Vec<u64> {
+        let len = shard.data.read().unwrap().len();
+        (0..len as u64).collect()
+    }
+
+    fn subtract_indices(a: Vec<u64>, b: Vec<u64>) -> Vec<u64> {
+        let set_b: HashSet<u64> = b.into_iter().collect::<HashSet<u64>>();
+        a.into_iter().filter(|i| !set_b.contains(i)).collect()
+    }
+
+    /// Walks every row in the table's master shards and returns the pointers of the rows
+    /// whose value for `cond.key` satisfies `filter_type`.
+    /// Rows that do not contain the column are only matched by `!=`, mirroring the anti-index path, and `is_null`.
+    ///
+    /// Tables spanning several shard files have their files scanned concurrently (see `set_scan_threads`),
+    /// pointers are returned in order either way.
+    fn scan_condition(
+        &self,
+        shard: &TableShard<T>,
+        cond: &QueryVal,
+        filter_type: &FilterType,
+    ) -> Vec<u64> {
+        let column = match shard.table.resolve_column(cond.key.as_str()) {
+            Some(column) => column,
+            None => return Vec::new(),
+        };
+        let column: &Column = &column;
+
+        let bloom_keys = Self::bloom_keys(shard, cond, filter_type);
+        let data = shard.data.read().unwrap();
+        let data: &MapShard<DataShard, DataShardConfig> = &data;
+        let blooms: &ShardBlooms = &shard.blooms;
+        let cancel = &self.cancel;
+
+        let scan = |range: Range<usize>| {
+            Self::scan_range(
+                data,
+                blooms,
+                bloom_keys.as_deref(),
+                column,
+                cond,
+                filter_type,
+                cancel,
+                range,
+            )
+        };
+
+        let ranges = data.shard_ranges();
+        match self.scan_threads > 1 && ranges.len() > 1 {
+            true => Self::scan_parallel(ranges, self.scan_threads, scan),
+            false => scan(0..data.len()),
+        }
+    }
+
+    /// Scans the rows of `data` at the positions of `range`, see `scan_condition`.
+    #[allow(clippy::too_many_arguments)]
+    fn scan_range(
+        data: &MapShard<DataShard, DataShardConfig>,
+        blooms: &ShardBlooms,
+        bloom_keys: Option<&[String]>,
+        column: &Column,
+        cond: &QueryVal,
+        filter_type: &FilterType,
+        cancel: &CancelToken,
+        range: Range<usize>,
+    ) -> Vec<u64> {
+        let mut pointers = vec![];
+        let mut pointer = range.start;
+        let mut read = 0;
+
+        while pointer < range.end {
+            if read % CANCEL_CHECK_INTERVAL == 0 && cancel.is_cancelled() {
+                break;
+            }
+            read += 1;
+
+            // Shards whose bloom filters hold none of the values can't have a match
+            if let Some(keys) = bloom_keys {
+                let bloom_shard = blooms.shard_of(pointer as u64);
+                if !keys
+                    .iter()
+                    .any(|key| blooms.might_contain(bloom_shard, key.as_bytes()))
+                {
+                    pointer = (blooms.next_shard_start(pointer as u64) as usize).min(range.end);
+                    continue;
+                }
+            }
+
+            if let Ok(item) = data.get_element(pointer) {
+                let row = T::from(item.as_slice());
+
+                if cond.matches(column, filter_type, &row) {
+                    pointers.push(pointer as u64);
                 }
             }
+
+            pointer += 1;
         }
 
-        None
+        pointers
     }
 
-    fn find_index_for_conditions(conditions: &[QueryVal], indexes: &Vec<Index>) -> Option<Index> {
-        let condition_keys: HashSet<String> =
-            conditions.iter().map(|cond| cond.key.clone()).collect();
-        for index in indexes.iter() {
-            let index_keys: HashSet<String> = index.members.iter().cloned().collect();
-            if condition_keys.is_subset(&index_keys) {
-                return Some(index.clone());
-            }
+    /// Runs `scan` over every range of `ranges` with up to `threads` threads, which pick the next range
+    /// to scan as they finish one. Pointers are merged in the order of the ranges.
+    fn scan_parallel<F>(ranges: Vec<Range<usize>>, threads: usize, scan: F) -> Vec<u64>
+    where
+        F: Fn(Range<usize>) -> Vec<u64> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+
+        let mut scanned: Vec<(usize, Vec<u64>)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.min(ranges.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut scanned = vec![];
+                        loop {
+                            let i = next.fetch_add(1, AtomicOrdering::Relaxed);
+                            match ranges.get(i) {
+                                Some(range) => scanned.push((i, scan(range.clone()))),
+                                None => return scanned,
+                            }
+                        }
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        scanned.sort_by_key(|(i, _)| *i);
+        scanned
+            .into_iter()
+            .flat_map(|(_, pointers)| pointers)
+            .collect()
+    }
+
+    /// Bloom filter items of the values an `=` or `in` condition can match, `None` when the bloom filters
+    /// can't tell whether a shard holds a match (e.g. the column is not tracked or a value is null).
+    fn bloom_keys(
+        shard: &TableShard<T>,
+        cond: &QueryVal,
+        filter_type: &FilterType,
+    ) -> Option<Vec<String>> {
+        if !TableShard::<T>::bloom_columns(&shard.table).contains(&cond.key) {
+            return None;
         }
-        None
+
+        let values = match (filter_type, &cond.value) {
+            (FilterType::Equal, value) => vec![value],
+            (FilterType::In, DataValue::Array(candidates)) => candidates.iter().collect(),
+            (FilterType::In, value) => vec![value],
+            _ => return None,
+        };
+
+        let collation = shard.table.collation(&cond.key);
+        values
+            .into_iter()
+            .map(|value| match value {
+                DataValue::String(_)
+                | DataValue::Boolean(_)
+                | DataValue::Number(_)
+                | DataValue::Uuid(_) => Some(TableShard::<T>::bloom_key(
+                    &cond.key,
+                    &value.clone().collate(collation),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Finds the cheapest index to look up the equality conditions of `query`.
+    /// Returns the index along with the conditions making its key and the remaining conditions.
+    fn find_index_for_query(
+        query: &QueryOps,
+        indexes: &Vec<Index>,
+        stats: &HashMap<String, IndexStats>,
+    ) -> Option<(Index, Vec<QueryVal>, Vec<QueryVal>)> {
+        let conditions = Self::collect_conditions(query)?;
+
+        // Every member of the index must be the key of exactly one condition
+        let index = Self::cheapest_index(
+            indexes.iter().filter(|index| {
+                index.index_type != IndexType::Geo
+                    && index.index_type != IndexType::MultiEntry
+                    && index.members.iter().all(|member| {
+                        conditions.iter().filter(|cond| &cond.key == member).count() == 1
+                    })
+            }),
+            stats,
+        )?;
+
+        let (covered, rest): (Vec<QueryVal>, Vec<QueryVal>) = conditions
+            .into_iter()
+            .partition(|cond| index.members.contains(&cond.key));
+
+        Some((index, covered, rest))
     }
 
     fn collect_conditions(query: &QueryOps) -> Option<Vec<QueryVal>> {
         match query {
-            QueryOps::Condition(cond) => Some(vec![cond.clone()]),
+            // Only equality conditions can be turned into an index key
+            QueryOps::Condition(cond) => match cond.get_filter_type() {
+                Ok(FilterType::Equal) => Some(vec![cond.clone()]),
+                _ => None,
+            },
             QueryOps::And(ops) => {
                 let mut conditions = Vec::new();
                 for op in ops {
@@ -156,11 +1009,19 @@ impl<T: Row<T>> QuerySearchManager<T> {
         }
     }
 
-    fn generate_index_key(index: &Index, conditions: &[QueryVal]) -> Option<CompositeKey> {
+    fn generate_index_key(
+        table: &Table,
+        index: &Index,
+        conditions: &[QueryVal],
+    ) -> Option<CompositeKey> {
         let mut key_parts = Vec::new();
         for member in &index.members {
             if let Some(cond) = conditions.iter().find(|c| &c.key == member) {
-                key_parts.push((cond.key.to_string(), (&cond.value).to_string()));
+                let value = cond.value.clone().collate(table.collation(&cond.key));
+                key_parts.push((
+                    cond.key.to_string(),
+                    TableShard::<T>::index_value(&index.index_type, &value),
+                ));
             } else {
                 // Missing condition for index member
                 return None;
@@ -170,40 +1031,674 @@ impl<T: Row<T>> QuerySearchManager<T> {
         Some(CompositeKey(key_parts))
     }
 
-    pub fn search(&self, table_name: String, ops: &QueryOps) -> Result<Vec<T>, QueryError> {
-        let get_table_shard = self
-            .table_shards
-            .get(&table_name)
-            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+    /// Orders `pointers` by walking an ordered index when the sort is a single column backed by one.
+    /// Returns `None` when no ordered index can answer the sort, in which case rows are sorted in memory.
+    fn index_sorted_pointers(
+        &self,
+        shard: &TableShard<T>,
+        sort: &[SortBy],
+        pointers: &[u64],
+    ) -> Option<Vec<u64>> {
+        let sort_by = match sort {
+            [sort_by] => sort_by,
+            _ => return None,
+        };
+
+        let index = shard.table.indexes.iter().find(|index| {
+            index.index_type == IndexType::BTree
+                && index.members.len() == 1
+                && index.members[0] == sort_by.column
+        })?;
+
+        let indx_read = shard.indexes.get(&index.name)?;
+        let ordered = indx_read
+            .as_index()
+            .range(Bound::Unbounded, Bound::Unbounded)?;
+
+        let matched: HashSet<u64> = pointers.iter().cloned().collect();
+        let mut sorted: Vec<u64> = ordered
+            .into_iter()
+            .filter(|pointer| matched.contains(pointer))
+            .collect();
+
+        // Rows without a value for the column are not indexed
+        let indexed: HashSet<u64> = sorted.iter().cloned().collect();
+        let mut missing: Vec<u64> = pointers
+            .iter()
+            .filter(|pointer| !indexed.contains(pointer))
+            .cloned()
+            .collect();
 
-        let pointers = self.execute_query(&get_table_shard, ops);
+        Some(match sort_by.direction {
+            SortDirection::Asc => {
+                missing.append(&mut sorted);
+                missing
+            }
+            SortDirection::Desc => {
+                sorted.reverse();
+                sorted.append(&mut missing);
+                sorted
+            }
+        })
+    }
+
+    fn sort_key(shard: &TableShard<T>, sort: &[SortBy], pointer: u64, row: &T) -> SortKey {
+        let values = sort
+            .iter()
+            .map(|sort_by| {
+                let column = shard.table.resolve_column(sort_by.column.as_str())?;
+                row.get_value(&column).map(|value| column.collate(value))
+            })
+            .collect();
+
+        (values, pointer)
+    }
+
+    /// Compares two sort keys following `sort`. Missing values (`None`) are lower than any value.
+    /// Ties are broken by the row pointer, in the direction of the first sort criteria.
+    fn compare_keys(sort: &[SortBy], a: &SortKey, b: &SortKey) -> Ordering {
+        let apply = |direction: SortDirection, ordering: Ordering| match direction {
+            SortDirection::Asc => ordering,
+            SortDirection::Desc => ordering.reverse(),
+        };
 
+        for (i, sort_by) in sort.iter().enumerate() {
+            let ordering = apply(sort_by.direction, a.0[i].cmp(&b.0[i]));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        let tie_direction = sort
+            .first()
+            .map(|sort_by| sort_by.direction)
+            .unwrap_or(SortDirection::Asc);
+
+        apply(tie_direction, a.1.cmp(&b.1))
+    }
+
+    fn get_rows(
+        &self,
+        shard: &TableShard<T>,
+        view: &TableView,
+        pointers: &[u64],
+    ) -> Result<Vec<T>, QueryError> {
+        let tbl_data = shard.data.read().unwrap();
         let mut results = vec![];
 
-        for pointer in pointers {
-            let tbl_data = get_table_shard.data.read().unwrap();
-            let data = tbl_data.get_element(pointer as usize).unwrap();
+        for (read, pointer) in pointers.iter().enumerate() {
+            if self.should_stop(read) {
+                break;
+            }
+
+            let data = view.get(&tbl_data, *pointer)?;
             results.push(T::from(&data))
         }
 
         Ok(results)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::managers::single::SingleQueryManager;
-    use crate::ops::query_ops::{QueryOps, QueryVal};
-    use crate::row::Row;
-    use crate::row_json::{RowData, RowJson};
-    use crate::search::search_manager::QuerySearchManager;
-    use schemajs_dirs::create_scheme_js_db;
-    use schemajs_index::index_type::IndexType;
-    use schemajs_primitives::column::types::{DataTypes, DataValue};
-    use schemajs_primitives::column::Column;
-    use schemajs_primitives::index::Index;
-    use schemajs_primitives::table::Table;
-    use uuid::Uuid;
+    /// Returns the pointers matched by `ops`, ordered following `opts.sort` and
+    /// starting right after the row pointed by `cursor` (if any).
+    fn ordered_pointers(
+        &self,
+        shard: &TableShard<T>,
+        view: &TableView,
+        ops: &QueryOps,
+        sort: &[SortBy],
+        cursor: Option<&SearchCursor>,
+    ) -> Result<Vec<u64>, QueryError> {
+        let mut pointers = self.execute_view(shard, view, ops);
+        pointers.sort_unstable();
+
+        if sort.is_empty() {
+            return Ok(match cursor {
+                Some(cursor) => pointers
+                    .into_iter()
+                    .filter(|pointer| *pointer > cursor.pointer)
+                    .collect(),
+                None => pointers,
+            });
+        }
+
+        // Pending rows are not indexed, and their positions may be held by other rows in the indexes
+        let index_sorted = match pointers.last() {
+            Some(last) if view.is_pending(*last) => None,
+            _ => self.index_sorted_pointers(shard, sort, &pointers),
+        };
+
+        if let Some(sorted) = index_sorted {
+            return match cursor {
+                Some(cursor) => {
+                    // The index already yields rows in order, rows are only read until the cursor is reached.
+                    let tbl_data = shard.data.read().unwrap();
+                    let cursor_key = cursor.key();
+                    let mut after_cursor = vec![];
+                    for pointer in sorted {
+                        if after_cursor.is_empty() {
+                            let row = T::from(&view.get(&tbl_data, pointer)?);
+                            let key = Self::sort_key(shard, sort, pointer, &row);
+                            if Self::compare_keys(sort, &key, &cursor_key) != Ordering::Greater {
+                                continue;
+                            }
+                        }
+                        after_cursor.push(pointer);
+                    }
+                    Ok(after_cursor)
+                }
+                None => Ok(sorted),
+            };
+        }
+
+        // Only the sort keys are kept in memory, rows are materialized once the page is known.
+        let mut keys: Vec<SortKey> = {
+            let tbl_data = shard.data.read().unwrap();
+            pointers
+                .into_iter()
+                .enumerate()
+                .take_while(|(read, _)| !self.should_stop(*read))
+                .map(|(_, pointer)| {
+                    let data = view.get(&tbl_data, pointer)?;
+                    let row = T::from(&data);
+                    Ok(Self::sort_key(shard, sort, pointer, &row))
+                })
+                .collect::<Result<Vec<SortKey>, QueryError>>()?
+        };
+
+        if let Some(cursor) = cursor {
+            let cursor_key = cursor.key();
+            keys.retain(|key| Self::compare_keys(sort, key, &cursor_key) == Ordering::Greater);
+        }
+
+        keys.sort_by(|a, b| Self::compare_keys(sort, a, b));
+        Ok(keys.into_iter().map(|(_, pointer)| pointer).collect())
+    }
+
+    /// Returns the plan that would be used to resolve `ops` in `table_name`, without executing it.
+    pub fn query_plan(&self, table_name: String, ops: &QueryOps) -> Result<QueryPlan, QueryError> {
+        ops.check_filter_types()?;
+        let get_table_shard = self
+            .table_shards
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+        Ok(Self::plan_query(&get_table_shard, ops))
+    }
+
+    /// JSON representation of `query_plan`, useful to debug why a query is slow.
+    ///
+    /// ```json
+    /// { "type": "intersection", "inputs": [
+    ///     { "type": "index_lookup", "index": "user_country_indx", "conditions": [...] },
+    ///     { "type": "scan", "condition": { "key": "user_name", "filter_type": "like", "value": "a%" } }
+    /// ] }
+    /// ```
+    pub fn explain(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+    ) -> Result<serde_json::Value, QueryError> {
+        let plan = self.query_plan(table_name, ops)?;
+        serde_json::to_value(plan).map_err(|_| QueryError::InvalidSerialization)
+    }
+
+    pub fn search(&self, table_name: String, ops: &QueryOps) -> Result<Vec<T>, QueryError> {
+        self.search_with(table_name, ops, &SearchOpts::default())
+    }
+
+    /// Same as `search`, applying `opts` (sorting, limit, offset and cursor) to the matched rows.
+    pub fn search_with(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+        opts: &SearchOpts,
+    ) -> Result<Vec<T>, QueryError> {
+        Ok(self.search_page(table_name, ops, opts)?.rows)
+    }
+
+    /// Resolves the pointers of the rows within the page described by `opts`.
+    /// The returned flag tells whether there are rows left after the page.
+    fn page_pointers(
+        &self,
+        shard: &TableShard<T>,
+        view: &TableView,
+        ops: &QueryOps,
+        opts: &SearchOpts,
+    ) -> Result<(Vec<u64>, bool), QueryError> {
+        for sort_by in opts.sort.iter() {
+            if shard
+                .table
+                .resolve_column(sort_by.column.as_str())
+                .is_none()
+            {
+                return Err(QueryError::InvalidColumn(sort_by.column.clone()));
+            }
+        }
+
+        let cursor = match &opts.cursor {
+            Some(cursor) => Some(SearchCursor::decode(cursor)?),
+            None => None,
+        };
+
+        let ordered = self.ordered_pointers(shard, view, ops, &opts.sort, cursor.as_ref())?;
+
+        let mut page: Vec<u64> = ordered.into_iter().skip(opts.offset.unwrap_or(0)).collect();
+
+        let has_more = match opts.limit {
+            Some(limit) if page.len() > limit => {
+                page.truncate(limit);
+                true
+            }
+            _ => false,
+        };
+
+        Ok((page, has_more))
+    }
+
+    /// Returns a page of the rows matched by `ops`.
+    /// Only the rows within the page are read and deserialized (unless sorting has to be done in memory).
+    ///
+    /// When `opts.limit` is set and there are rows left, the page contains a cursor that can be passed
+    /// to `SearchOpts::after` to fetch the next page. Cursors are keyset based: they hold the sort key of
+    /// the last row, so pages remain stable even if rows are inserted in between.
+    #[tracing::instrument(skip_all, fields(table = %table_name))]
+    pub fn search_page(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+        opts: &SearchOpts,
+    ) -> Result<SearchPage<T>, QueryError> {
+        let started = Instant::now();
+        ops.check_filter_types()?;
+        let (get_table_shard, view) = self.table_shard(&table_name)?;
+
+        let (page, has_more) = self.page_pointers(&get_table_shard, &view, ops, opts)?;
+        self.cancel.check()?;
+
+        let rows = self.get_rows(&get_table_shard, &view, &page)?;
+        self.cancel.check()?;
+
+        let cursor = match (has_more, page.last(), rows.last()) {
+            (true, Some(pointer), Some(row)) => Some(
+                SearchCursor::new(Self::sort_key(&get_table_shard, &opts.sort, *pointer, row))
+                    .encode(),
+            ),
+            _ => None,
+        };
+
+        Metrics::global().query_duration.observe(started.elapsed());
+        self.log_if_slow("search", &get_table_shard, ops, page.len(), started);
+        Ok(SearchPage { rows, cursor })
+    }
+
+    /// Same as `search_with` but only the columns in `opts.projection` are deserialized and returned.
+    /// If no projection is set, every column of the table is returned.
+    #[tracing::instrument(skip_all, fields(table = %table_name))]
+    pub fn search_partial(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+        opts: &SearchOpts,
+    ) -> Result<Vec<PartialRow>, QueryError> {
+        let started = Instant::now();
+        ops.check_filter_types()?;
+        let (get_table_shard, view) = self.table_shard(&table_name)?;
+
+        let columns: Vec<Column> = match &opts.projection {
+            Some(projection) => projection
+                .iter()
+                .map(|name| {
+                    get_table_shard
+                        .table
+                        .get_column(name.as_str())
+                        .cloned()
+                        .ok_or_else(|| QueryError::InvalidColumn(name.clone()))
+                })
+                .collect::<Result<Vec<Column>, QueryError>>()?,
+            None => get_table_shard.table.columns.values().cloned().collect(),
+        };
+
+        let (page, _) = self.page_pointers(&get_table_shard, &view, ops, opts)?;
+        self.cancel.check()?;
+
+        let tbl_data = get_table_shard.data.read().unwrap();
+        let mut results = vec![];
+
+        for (read, pointer) in page.into_iter().enumerate() {
+            if self.should_stop(read) {
+                break;
+            }
+
+            let data = view.get(&tbl_data, pointer)?;
+            results.push(T::project(&data, &columns));
+        }
+
+        self.cancel.check()?;
+
+        Metrics::global().query_duration.observe(started.elapsed());
+        self.log_if_slow("search", &get_table_shard, ops, results.len(), started);
+        Ok(results)
+    }
+
+    /// Evaluates `aggregates` over the rows matched by `ops`, returning one value per aggregate.
+    /// Rows are read one at a time and only the aggregated columns are deserialized.
+    /// When every aggregate is a row count, rows are not read at all.
+    #[tracing::instrument(skip_all, fields(table = %table_name))]
+    pub fn aggregate(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+        aggregates: &[Aggregate],
+    ) -> Result<Vec<DataValue>, QueryError> {
+        ops.check_filter_types()?;
+        let (get_table_shard, view) = self.table_shard(&table_name)?;
+
+        let columns = Self::aggregate_columns(&get_table_shard, aggregates)?;
+        let pointers = self.execute_view(&get_table_shard, &view, ops);
+        self.cancel.check()?;
+
+        let mut states: Vec<AggregateState> = aggregates
+            .iter()
+            .map(|aggregate| AggregateState::new(aggregate.clone()))
+            .collect();
+
+        let tbl_data = get_table_shard.data.read().unwrap();
+
+        for (read, pointer) in pointers.into_iter().enumerate() {
+            if self.should_stop(read) {
+                return Err(QueryError::Timeout);
+            }
+
+            let row = if columns.is_empty() {
+                None
+            } else {
+                let data = view.get(&tbl_data, pointer)?;
+                Some(T::project(&data, &columns))
+            };
+
+            for (state, aggregate) in states.iter_mut().zip(aggregates.iter()) {
+                let value = match (&row, &aggregate.column) {
+                    (Some(row), Some(column)) => row.get_value(column.as_str()),
+                    _ => None,
+                };
+                state.accumulate(value)?;
+            }
+        }
+
+        Ok(states.iter().map(|state| state.finish()).collect())
+    }
+
+    /// Buckets the rows matched by `ops` by the values of `group_by` and evaluates `aggregates` per bucket.
+    /// Groups are returned ordered by their keys. Rows missing a grouping column are bucketed under `Null`.
+    #[tracing::instrument(skip_all, fields(table = %table_name))]
+    pub fn group_by(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+        group_by: &[String],
+        aggregates: &[Aggregate],
+    ) -> Result<Vec<AggregateGroup>, QueryError> {
+        ops.check_filter_types()?;
+        let (get_table_shard, view) = self.table_shard(&table_name)?;
+
+        let mut columns = Self::aggregate_columns(&get_table_shard, aggregates)?;
+        for column_name in group_by.iter() {
+            let column = get_table_shard
+                .table
+                .get_column(column_name.as_str())
+                .ok_or_else(|| QueryError::InvalidColumn(column_name.clone()))?;
+
+            if !columns.iter().any(|c| c.name == column.name) {
+                columns.push(column.clone());
+            }
+        }
+
+        let pointers = self.execute_view(&get_table_shard, &view, ops);
+        self.cancel.check()?;
+        let mut groups: BTreeMap<Vec<DataValue>, Vec<AggregateState>> = BTreeMap::new();
+
+        let tbl_data = get_table_shard.data.read().unwrap();
+
+        for (read, pointer) in pointers.into_iter().enumerate() {
+            if self.should_stop(read) {
+                return Err(QueryError::Timeout);
+            }
+
+            let data = view.get(&tbl_data, pointer)?;
+            let row = T::project(&data, &columns);
+
+            let key: Vec<DataValue> = group_by
+                .iter()
+                .map(|column| {
+                    row.get_value(column.as_str())
+                        .cloned()
+                        .unwrap_or(DataValue::Null)
+                })
+                .collect();
+
+            let states = groups.entry(key).or_insert_with(|| {
+                aggregates
+                    .iter()
+                    .map(|aggregate| AggregateState::new(aggregate.clone()))
+                    .collect()
+            });
+
+            for (state, aggregate) in states.iter_mut().zip(aggregates.iter()) {
+                let value = aggregate
+                    .column
+                    .as_ref()
+                    .and_then(|column| row.get_value(column.as_str()));
+                state.accumulate(value)?;
+            }
+        }
+
+        Ok(groups
+            .into_iter()
+            .map(|(key, states)| AggregateGroup {
+                keys: group_by.iter().cloned().zip(key.into_iter()).collect(),
+                values: aggregates
+                    .iter()
+                    .zip(states.iter())
+                    .map(|(aggregate, state)| (aggregate.alias(), state.finish()))
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Runs `pipeline` over the rows of `table_name`, returning the documents produced by its last stage.
+    /// Leading `Match` stages and the `Group`, `Sort` or `Limit` stage following them are answered by the table
+    /// (see `Pipeline`), the stages left run in memory over the documents they produce.
+    #[tracing::instrument(skip_all, fields(table = %table_name))]
+    pub fn pipeline(
+        &self,
+        table_name: String,
+        pipeline: &Pipeline,
+    ) -> Result<Vec<serde_json::Value>, QueryError> {
+        let table = self
+            .table_shards
+            .get(&table_name)
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?
+            .table
+            .clone();
+        let source = pipeline.source(&table)?;
+
+        let (schema, documents) = match source.group {
+            Some((by, aggregates)) => (
+                Pipeline::group_schema(&table, by, aggregates)?,
+                self.group_by(table_name, &source.ops, by, aggregates)?
+                    .iter()
+                    .map(|group| group.to_json())
+                    .collect(),
+            ),
+            None => (
+                (*table).clone(),
+                self.search_with(table_name, &source.ops, &source.opts)?
+                    .iter()
+                    .map(|row| {
+                        let fields: serde_json::Map<String, serde_json::Value> = table
+                            .columns
+                            .values()
+                            .filter_map(|column| {
+                                let value = row.get_value(column)?;
+                                Some((column.name.clone(), value.to_json()))
+                            })
+                            .collect();
+                        serde_json::Value::Object(fields)
+                    })
+                    .collect(),
+            ),
+        };
+        self.cancel.check()?;
+
+        Pipeline::run(source.rest, schema, documents)
+    }
+
+    /// Combines the rows matched by `ops` in `table_name` with the rows of `join.table`
+    /// whose `join.right_column` is equal to `join.left_column`.
+    ///
+    /// This is a hash join: the rows of the joined table are bucketed by their join value first,
+    /// then every row of the searched table is matched against its bucket.
+    /// Null or missing join values never match.
+    #[tracing::instrument(skip_all, fields(table = %table_name))]
+    pub fn join(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+        join: &Join,
+    ) -> Result<Vec<JoinedRow<T>>, QueryError> {
+        // Keys are matched by the order of `DataValue`: `1` joins `1.0`, but values of different types
+        // (e.g. `1` and `"1"`) never join
+        let join_key = |value: Option<DataValue>| match value {
+            None | Some(DataValue::Null) => None,
+            Some(value) => Some(value),
+        };
+
+        let right_rows = self.matched_data(join.table.clone(), &join.query, &join.right_column)?;
+        let mut buckets: BTreeMap<DataValue, Vec<usize>> = BTreeMap::new();
+        for (i, (value, _)) in right_rows.iter().enumerate() {
+            if let Some(key) = join_key(value.clone()) {
+                buckets.entry(key).or_default().push(i);
+            }
+        }
+
+        let left_rows = self.matched_data(table_name, ops, &join.left_column)?;
+        let mut results = vec![];
+
+        for (value, left_data) in left_rows {
+            let matches = join_key(value)
+                .and_then(|key| buckets.get(&key))
+                .cloned()
+                .unwrap_or_default();
+
+            if matches.is_empty() && join.join_type == JoinType::Left {
+                results.push(JoinedRow {
+                    left: T::from(left_data.as_slice()),
+                    right: None,
+                });
+            }
+
+            for i in matches {
+                results.push(JoinedRow {
+                    left: T::from(left_data.as_slice()),
+                    right: Some(T::from(right_rows[i].1.as_slice())),
+                });
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Returns the serialized rows matched by `ops` along with their value for `column_name`.
+    fn matched_data(
+        &self,
+        table_name: String,
+        ops: &QueryOps,
+        column_name: &str,
+    ) -> Result<Vec<(Option<DataValue>, Vec<u8>)>, QueryError> {
+        let (get_table_shard, view) = self.table_shard(&table_name)?;
+
+        let column = get_table_shard
+            .table
+            .get_column(column_name)
+            .cloned()
+            .ok_or_else(|| QueryError::InvalidColumn(column_name.to_string()))?;
+
+        let mut pointers = self.execute_view(&get_table_shard, &view, ops);
+        self.cancel.check()?;
+        pointers.sort_unstable();
+
+        let tbl_data = get_table_shard.data.read().unwrap();
+        let mut results = vec![];
+
+        for (read, pointer) in pointers.into_iter().enumerate() {
+            if self.should_stop(read) {
+                return Err(QueryError::Timeout);
+            }
+
+            let data = view.get(&tbl_data, pointer)?;
+            let value = T::project(&data, std::slice::from_ref(&column))
+                .get_value(column.name.as_str())
+                .cloned();
+            results.push((value, data));
+        }
+
+        Ok(results)
+    }
+
+    /// Columns that must be deserialized to evaluate `aggregates`.
+    fn aggregate_columns(
+        shard: &TableShard<T>,
+        aggregates: &[Aggregate],
+    ) -> Result<Vec<Column>, QueryError> {
+        let mut columns: Vec<Column> = vec![];
+
+        for column_name in aggregates
+            .iter()
+            .filter_map(|aggregate| aggregate.column.as_ref())
+        {
+            let column = shard
+                .table
+                .get_column(column_name.as_str())
+                .ok_or_else(|| QueryError::InvalidColumn(column_name.clone()))?;
+
+            if !columns.iter().any(|c| c.name == column.name) {
+                columns.push(column.clone());
+            }
+        }
+
+        Ok(columns)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::commit_log::CommitLog;
+    use crate::managers::single::table_shard::TableShard;
+    use crate::managers::single::SingleQueryManager;
+    use crate::ops::aggregate::{Aggregate, AggregateFunction};
+    use crate::ops::builder::Query;
+    use crate::ops::join::Join;
+    use crate::ops::pipeline::Pipeline;
+    use crate::ops::query_ops::{FilterType, QueryOps, QueryVal};
+    use crate::row::Row;
+    use crate::row_json::{RowData, RowJson};
+    use crate::search::cancel_token::CancelToken;
+    use crate::search::index_stats::IndexStats;
+    use crate::search::search_manager::QuerySearchManager;
+    use crate::search::search_opts::{SearchOpts, SortDirection};
+    use crate::serializer::RowSerializer;
+    use crate::sql::{self, SqlResult};
+    use schemajs_data::shard::shards::data_shard::config::TableStorage;
+    use schemajs_dirs::create_scheme_js_db;
+    use schemajs_index::index_type::IndexType;
+    use schemajs_primitives::column::collation::Collation;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
+    use schemajs_primitives::table::Table;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::time::Duration;
+    use uuid::Uuid;
 
     #[flaky_test::flaky_test]
     pub fn test_search_manager() {
@@ -221,34 +1716,40 @@ mod test {
                 name: "user_id_indx".to_string(),
                 members: vec![String::from("user_id")],
                 index_type: IndexType::Hash,
+                unique: false,
             })
             .add_index(Index {
                 name: "user_email_indx".to_string(),
                 members: vec![String::from("user_email")],
                 index_type: IndexType::Hash,
+                unique: false,
             })
             .add_index(Index {
                 name: "user_country_indx".to_string(),
                 members: vec![String::from("user_country")],
                 index_type: IndexType::Hash,
+                unique: false,
             })
             .add_index(Index {
                 name: "user_age_indx".to_string(),
                 members: vec![String::from("user_age")],
                 index_type: IndexType::Hash,
+                unique: false,
             })
             .add_index(Index {
                 name: "user_name_indx".to_string(),
                 members: vec![String::from("user_name")],
                 index_type: IndexType::Hash,
+                unique: false,
             })
             .add_index(Index {
                 name: "age_country_indx".to_string(),
                 members: vec![String::from("user_age"), String::from("user_country")],
                 index_type: IndexType::Hash,
+                unique: false,
             });
 
-        query_manager.register_table(tbl);
+        query_manager.register_table(tbl).unwrap();
 
         let row_1 = query_manager
             .insert(RowJson::from(RowData {
@@ -375,4 +1876,4277 @@ mod test {
 
         println!("{}", res_name.to_string());
     }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_range_filters() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_id", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::String));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let ages = [("1", "19"), ("2", "20"), ("3", "21"), ("4", "22")];
+        for (user_id, user_age) in ages {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_id": user_id,
+                        "user_age": user_age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let age_filter = |filter_type: &str| {
+            QueryOps::Condition(QueryVal {
+                key: "user_age".to_string(),
+                filter_type: filter_type.to_string(),
+                value: DataValue::String("21".to_string()),
+            })
+        };
+
+        let results = search_manager
+            .search("users".to_string(), &age_filter(">="))
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let results = search_manager
+            .search("users".to_string(), &age_filter(">"))
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let col = tbl.table.get_column("user_id").unwrap();
+        assert_eq!(
+            results[0].get_value(col).unwrap(),
+            DataValue::String("4".to_string())
+        );
+
+        let results = search_manager
+            .search("users".to_string(), &age_filter("<"))
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let results = search_manager
+            .search("users".to_string(), &age_filter("<="))
+            .unwrap();
+        assert_eq!(results.len(), 3);
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_not_equal() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_id", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_index(Index {
+                name: "user_id_indx".to_string(),
+                members: vec![String::from("user_id")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        let users = [("1", "US"), ("2", "AR"), ("3", "US")];
+        for (user_id, user_country) in users {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_id": user_id,
+                        "user_country": user_country
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        // Indexed column (anti-index)
+        let results = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_id".to_string(),
+                    filter_type: "!=".to_string(),
+                    value: DataValue::String("2".to_string()),
+                }),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Non-indexed column (scan)
+        let results = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_country".to_string(),
+                    filter_type: "!=".to_string(),
+                    value: DataValue::String("US".to_string()),
+                }),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let col = tbl.table.get_column("user_id").unwrap();
+        assert_eq!(
+            results[0].get_value(col).unwrap(),
+            DataValue::String("2".to_string())
+        );
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_in_filter() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_id", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_index(Index {
+                name: "user_id_indx".to_string(),
+                members: vec![String::from("user_id")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        let users = [("1", "US"), ("2", "AR"), ("3", "VE"), ("4", "US")];
+        for (user_id, user_country) in users {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_id": user_id,
+                        "user_country": user_country
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        // Indexed column (union of lookups)
+        let results = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_id".to_string(),
+                    filter_type: "in".to_string(),
+                    value: DataValue::Array(vec![
+                        DataValue::String("1".to_string()),
+                        DataValue::String("3".to_string()),
+                        DataValue::String("10".to_string()),
+                    ]),
+                }),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Non-indexed column (scan)
+        let results = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_country".to_string(),
+                    filter_type: "in".to_string(),
+                    value: DataValue::Array(vec![
+                        DataValue::String("AR".to_string()),
+                        DataValue::String("VE".to_string()),
+                    ]),
+                }),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_btree_index() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_email", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_index(Index {
+                name: "user_email_indx".to_string(),
+                members: vec![String::from("user_email")],
+                index_type: IndexType::BTree,
+                unique: false,
+            })
+            .add_index(Index {
+                name: "user_age_indx".to_string(),
+                members: vec![String::from("user_age")],
+                index_type: IndexType::BTree,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        let users = [
+            ("c@outlook.com", 9),
+            ("a@outlook.com", 30),
+            ("b@gmail.com", 100),
+            ("ab@outlook.com", -5),
+        ];
+        for (user_email, user_age) in users {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_email": user_email,
+                        "user_age": user_age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let results = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_email".to_string(),
+                    filter_type: "starts_with".to_string(),
+                    value: DataValue::String("a".to_string()),
+                }),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Numbers are ordered numerically, not lexicographically
+        let results = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_age".to_string(),
+                    filter_type: ">=".to_string(),
+                    value: DataValue::Number(serde_json::Number::from(9)),
+                }),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 3);
+
+        let results = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_age".to_string(),
+                    filter_type: "<".to_string(),
+                    value: DataValue::Number(serde_json::Number::from(9)),
+                }),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let col = tbl.table.get_column("user_email").unwrap();
+        assert_eq!(
+            results[0].get_value(col).unwrap(),
+            DataValue::String("ab@outlook.com".to_string())
+        );
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_sort() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_index(Index {
+                name: "user_age_indx".to_string(),
+                members: vec![String::from("user_age")],
+                index_type: IndexType::BTree,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        let users = [
+            ("andreespirela", "US", 20),
+            ("Veronica", "VE", 35),
+            ("superman", "US", 3),
+            ("batman", "VE", 20),
+        ];
+        for (user_name, user_country, user_age) in users {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": user_name,
+                        "user_country": user_country,
+                        "user_age": user_age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let all = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">".to_string(),
+            value: DataValue::Number(serde_json::Number::from(0)),
+        });
+        let names = |rows: Vec<RowJson>| -> Vec<String> {
+            let col = tbl.table.get_column("user_name").unwrap();
+            rows.iter()
+                .map(|row| row.get_value(col).unwrap().to_string())
+                .collect()
+        };
+
+        // Single column backed by an ordered index
+        let results = search_manager
+            .search_with(
+                "users".to_string(),
+                &all,
+                &SearchOpts::new().sort_by("user_age", SortDirection::Desc),
+            )
+            .unwrap();
+        let col = tbl.table.get_column("user_age").unwrap();
+        let ages: Vec<DataValue> = results.iter().map(|r| r.get_value(col).unwrap()).collect();
+        assert_eq!(
+            ages,
+            vec![
+                DataValue::Number(serde_json::Number::from(35)),
+                DataValue::Number(serde_json::Number::from(20)),
+                DataValue::Number(serde_json::Number::from(20)),
+                DataValue::Number(serde_json::Number::from(3)),
+            ]
+        );
+
+        // Multiple columns are sorted in memory
+        let results = search_manager
+            .search_with(
+                "users".to_string(),
+                &all,
+                &SearchOpts::new()
+                    .sort_by("user_country", SortDirection::Desc)
+                    .sort_by("user_name", SortDirection::Asc),
+            )
+            .unwrap();
+        assert_eq!(
+            names(results),
+            vec!["Veronica", "batman", "andreespirela", "superman"]
+        );
+
+        let err = search_manager.search_with(
+            "users".to_string(),
+            &all,
+            &SearchOpts::new().sort_by("unknown", SortDirection::Asc),
+        );
+        assert!(err.is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_pagination() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        query_manager.register_table(tbl).unwrap();
+
+        for user_age in 0..10 {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": format!("user_{}", user_age),
+                        "user_age": user_age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let all = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">=".to_string(),
+            value: DataValue::Number(serde_json::Number::from(0)),
+        });
+        let col = tbl.table.get_column("user_age").unwrap();
+        let ages = |rows: &Vec<RowJson>| -> Vec<u64> {
+            rows.iter()
+                .map(|row| {
+                    row.get_value(col)
+                        .unwrap()
+                        .as_number()
+                        .unwrap()
+                        .as_u64()
+                        .unwrap()
+                })
+                .collect()
+        };
+
+        // Limit & Offset
+        let results = search_manager
+            .search_with(
+                "users".to_string(),
+                &all,
+                &SearchOpts::new()
+                    .sort_by("user_age", SortDirection::Desc)
+                    .offset(2)
+                    .limit(3),
+            )
+            .unwrap();
+        assert_eq!(ages(&results), vec![7, 6, 5]);
+
+        // Keyset pagination
+        let opts = SearchOpts::new()
+            .sort_by("user_age", SortDirection::Desc)
+            .limit(4);
+        let page = search_manager
+            .search_page("users".to_string(), &all, &opts)
+            .unwrap();
+        assert_eq!(ages(&page.rows), vec![9, 8, 7, 6]);
+
+        let page = search_manager
+            .search_page(
+                "users".to_string(),
+                &all,
+                &opts.clone().after(page.cursor.unwrap().as_str()),
+            )
+            .unwrap();
+        assert_eq!(ages(&page.rows), vec![5, 4, 3, 2]);
+
+        let page = search_manager
+            .search_page(
+                "users".to_string(),
+                &all,
+                &opts.clone().after(page.cursor.unwrap().as_str()),
+            )
+            .unwrap();
+        assert_eq!(ages(&page.rows), vec![1, 0]);
+        assert!(page.cursor.is_none());
+
+        // Without sorting rows are paged in storage order
+        let first_page = search_manager
+            .search_page("users".to_string(), &all, &SearchOpts::new().limit(5))
+            .unwrap();
+        assert_eq!(first_page.rows.len(), 5);
+        let second_page = search_manager
+            .search_page(
+                "users".to_string(),
+                &all,
+                &SearchOpts::new()
+                    .limit(5)
+                    .after(first_page.cursor.unwrap().as_str()),
+            )
+            .unwrap();
+        assert!(second_page.cursor.is_none());
+
+        let mut all_ages = ages(&first_page.rows);
+        all_ages.extend(ages(&second_page.rows));
+        all_ages.sort();
+        assert_eq!(all_ages, (0..10).collect::<Vec<u64>>());
+
+        let err = search_manager.search_page(
+            "users".to_string(),
+            &all,
+            &SearchOpts::new().after("not-a-cursor"),
+        );
+        assert!(err.is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_projection() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_bio", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (user_name, user_age) in [("andreespirela", 20), ("Veronica", 35)] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": user_name,
+                        "user_bio": "A very long biography",
+                        "user_age": user_age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let results = search_manager
+            .search_partial(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_age".to_string(),
+                    filter_type: ">".to_string(),
+                    value: DataValue::Number(serde_json::Number::from(25)),
+                }),
+                &SearchOpts::new().select(&["user_name", "user_age"]),
+            )
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].table, "users");
+        assert_eq!(results[0].values.len(), 2);
+        assert_eq!(
+            results[0].get_value("user_name"),
+            Some(&DataValue::String("Veronica".to_string()))
+        );
+        assert_eq!(
+            results[0].get_value("user_age"),
+            Some(&DataValue::Number(serde_json::Number::from(35)))
+        );
+        assert!(results[0].get_value("user_bio").is_none());
+
+        let err = search_manager.search_partial(
+            "users".to_string(),
+            &QueryOps::And(vec![]),
+            &SearchOpts::new().select(&["unknown"]),
+        );
+        assert!(err.is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_aggregate() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (user_country, user_age) in [("US", 20), ("US", 31), ("VE", 35), ("VE", 18)] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_country": user_country,
+                        "user_age": user_age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let results = search_manager
+            .aggregate(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_country".to_string(),
+                    filter_type: "=".to_string(),
+                    value: DataValue::String("US".to_string()),
+                }),
+                &[
+                    Aggregate::count(),
+                    Aggregate::new(AggregateFunction::Sum, "user_age"),
+                    Aggregate::new(AggregateFunction::Min, "user_age"),
+                    Aggregate::new(AggregateFunction::Max, "user_age"),
+                    Aggregate::new(AggregateFunction::Avg, "user_age"),
+                ],
+            )
+            .unwrap();
+
+        let number = |n: f64| DataValue::Number(serde_json::Number::from_f64(n).unwrap());
+        assert_eq!(results[0], DataValue::Number(serde_json::Number::from(2)));
+        assert_eq!(results[1], DataValue::Number(serde_json::Number::from(51)));
+        assert_eq!(results[2], DataValue::Number(serde_json::Number::from(20)));
+        assert_eq!(results[3], DataValue::Number(serde_json::Number::from(31)));
+        assert_eq!(results[4], number(25.5));
+
+        let err = search_manager.aggregate(
+            "users".to_string(),
+            &QueryOps::And(vec![]),
+            &[Aggregate::new(AggregateFunction::Sum, "unknown")],
+        );
+        assert!(err.is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_group_by() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (user_country, user_age) in [("VE", 35), ("US", 20), ("US", 31), ("VE", 18), ("AR", 40)]
+        {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_country": user_country,
+                        "user_age": user_age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let groups = search_manager
+            .group_by(
+                "users".to_string(),
+                &QueryOps::And(vec![]),
+                &["user_country".to_string()],
+                &[
+                    Aggregate::count(),
+                    Aggregate::new(AggregateFunction::Max, "user_age"),
+                ],
+            )
+            .unwrap();
+
+        let countries: Vec<String> = groups
+            .iter()
+            .map(|group| group.get_key("user_country").unwrap().to_string())
+            .collect();
+        assert_eq!(countries, vec!["AR", "US", "VE"]);
+
+        let number = |n: u64| DataValue::Number(serde_json::Number::from(n));
+        assert_eq!(groups[1].get_value("count"), Some(&number(2)));
+        assert_eq!(groups[1].get_value("max_user_age"), Some(&number(31)));
+        assert_eq!(groups[2].get_value("max_user_age"), Some(&number(35)));
+        assert_eq!(
+            groups[0].to_json(),
+            serde_json::json!({ "user_country": "AR", "count": 1, "max_user_age": 40 })
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_join() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_id", DataTypes::String))
+                    .add_column(Column::new("user_name", DataTypes::String)),
+            )
+            .unwrap();
+        query_manager
+            .register_table(
+                Table::new("orders")
+                    .add_column(Column::new("user_id", DataTypes::String))
+                    .add_column(Column::new("total", DataTypes::Number)),
+            )
+            .unwrap();
+
+        for (user_id, user_name) in [("1", "andreespirela"), ("2", "Veronica"), ("3", "superman")] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_id": user_id,
+                        "user_name": user_name
+                    }),
+                }))
+                .unwrap();
+        }
+
+        for (user_id, total) in [("1", 10), ("1", 25), ("2", 40)] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("orders"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_id": user_id,
+                        "total": total
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let search_manager = QuerySearchManager::new(tables.clone());
+        tables.get("users").unwrap().temps.reconcile_all();
+        tables.get("orders").unwrap().temps.reconcile_all();
+
+        let user_name = Column::new("user_name", DataTypes::String);
+        let total = Column::new("total", DataTypes::Number);
+
+        let results = search_manager
+            .join(
+                "users".to_string(),
+                &QueryOps::And(vec![]),
+                &Join::inner("orders", "user_id", "user_id").set_query(QueryOps::Condition(
+                    QueryVal {
+                        key: "total".to_string(),
+                        filter_type: ">".to_string(),
+                        value: DataValue::Number(serde_json::Number::from(15)),
+                    },
+                )),
+            )
+            .unwrap();
+
+        let mut pairs: Vec<(String, String)> = results
+            .iter()
+            .map(|joined| {
+                (
+                    joined.left.get_value(&user_name).unwrap().to_string(),
+                    joined
+                        .right
+                        .as_ref()
+                        .unwrap()
+                        .get_value(&total)
+                        .unwrap()
+                        .to_string(),
+                )
+            })
+            .collect();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            vec![
+                ("Veronica".to_string(), "40".to_string()),
+                ("andreespirela".to_string(), "25".to_string())
+            ]
+        );
+
+        let results = search_manager
+            .join(
+                "users".to_string(),
+                &QueryOps::And(vec![]),
+                &Join::left("orders", "user_id", "user_id"),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results
+                .iter()
+                .filter(|joined| joined.right.is_none())
+                .count(),
+            1
+        );
+
+        // Numbers join by value whatever their representation
+        query_manager
+            .register_table(
+                Table::new("payments").add_column(Column::new("amount", DataTypes::Number)),
+            )
+            .unwrap();
+        query_manager
+            .register_table(
+                Table::new("refunds").add_column(Column::new("amount", DataTypes::Number)),
+            )
+            .unwrap();
+        for (table, amount) in [
+            ("payments", serde_json::json!(1.0)),
+            ("refunds", serde_json::json!(1)),
+            ("refunds", serde_json::json!(2)),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from(table),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "amount": amount
+                    }),
+                }))
+                .unwrap();
+        }
+        tables.get("payments").unwrap().temps.reconcile_all();
+        tables.get("refunds").unwrap().temps.reconcile_all();
+
+        let results = query_manager
+            .search_manager()
+            .join(
+                "payments".to_string(),
+                &QueryOps::And(vec![]),
+                &Join::inner("refunds", "amount", "amount"),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].right.as_ref().unwrap().value.value["amount"],
+            serde_json::json!(1)
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_explain() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_index(Index {
+                name: "user_country_indx".to_string(),
+                members: vec![String::from("user_country")],
+                index_type: IndexType::Hash,
+                unique: false,
+            })
+            .add_index(Index {
+                name: "user_age_indx".to_string(),
+                members: vec![String::from("user_age")],
+                index_type: IndexType::BTree,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+        let search_manager = query_manager.search_manager();
+
+        let condition = |key: &str, filter_type: &str, value: DataValue| {
+            QueryOps::Condition(QueryVal {
+                key: key.to_string(),
+                filter_type: filter_type.to_string(),
+                value,
+            })
+        };
+
+        let query = QueryOps::And(vec![
+            condition("user_country", "=", DataValue::String("US".to_string())),
+            QueryOps::Or(vec![
+                condition(
+                    "user_age",
+                    ">",
+                    DataValue::Number(serde_json::Number::from(20)),
+                ),
+                condition("user_name", "like", DataValue::String("a%".to_string())),
+            ]),
+        ]);
+
+        let plan = search_manager
+            .query_plan("users".to_string(), &query)
+            .unwrap();
+        let inputs = plan.as_intersection().unwrap();
+        assert!(inputs[0].is_index_lookup());
+        let union = inputs[1].as_union().unwrap();
+        assert!(union[0].is_index_range());
+        assert!(union[1].is_scan());
+
+        let explain = search_manager.explain("users".to_string(), &query).unwrap();
+        assert_eq!(explain["type"], "intersection");
+        assert_eq!(explain["inputs"][0]["index"], "user_country_indx");
+        assert_eq!(explain["inputs"][1]["inputs"][0]["type"], "index_range");
+        assert_eq!(
+            explain["inputs"][1]["inputs"][1]["condition"],
+            serde_json::json!({ "key": "user_name", "filter_type": "like", "value": "a%" })
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_update() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_index(Index {
+                name: "user_name_indx".to_string(),
+                members: vec![String::from("user_name")],
+                index_type: IndexType::Hash,
+                unique: false,
+            })
+            .add_index(Index {
+                name: "user_country_indx".to_string(),
+                members: vec![String::from("user_country")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (name, country) in [("andreespirela", "US"), ("luis", "AR")] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_country": country
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let condition = |key: &str, value: &str| {
+            QueryOps::Condition(QueryVal {
+                key: key.to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(value.to_string()),
+            })
+        };
+
+        let updated = query_manager
+            .update(
+                "users".to_string(),
+                &condition("user_name", "andreespirela"),
+                &HashMap::from([(
+                    "user_country".to_string(),
+                    DataValue::String("VE".to_string()),
+                )]),
+            )
+            .unwrap();
+        assert_eq!(updated, 1);
+
+        let search_manager = query_manager.search_manager();
+
+        // The new value is reachable through the index, the old one is gone
+        let by_new_country = search_manager
+            .search("users".to_string(), &condition("user_country", "VE"))
+            .unwrap();
+        assert_eq!(by_new_country.len(), 1);
+        assert_eq!(
+            by_new_country[0].value.value["user_name"],
+            serde_json::json!("andreespirela")
+        );
+
+        let by_old_country = search_manager
+            .search("users".to_string(), &condition("user_country", "US"))
+            .unwrap();
+        assert!(by_old_country.is_empty());
+
+        // Unchanged index keys point to the new version of the row
+        let by_name = search_manager
+            .search(
+                "users".to_string(),
+                &condition("user_name", "andreespirela"),
+            )
+            .unwrap();
+        assert_eq!(by_name.len(), 1);
+        assert_eq!(
+            by_name[0].value.value["user_country"],
+            serde_json::json!("VE")
+        );
+
+        let all = search_manager
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert_eq!(all.len(), 2);
+
+        let unknown = query_manager.update(
+            "users".to_string(),
+            &QueryOps::And(vec![]),
+            &HashMap::from([("user_age".to_string(), DataValue::Null)]),
+        );
+        assert!(unknown.unwrap_err().is_invalid_column());
+
+        let immutable = query_manager.update(
+            "users".to_string(),
+            &QueryOps::And(vec![]),
+            &HashMap::from([("_uid".to_string(), DataValue::Uuid(Uuid::new_v4()))]),
+        );
+        assert!(immutable.unwrap_err().is_immutable_column());
+
+        // Values are checked against the type of their column, the row is left as it was
+        let mismatched = query_manager.update(
+            "users".to_string(),
+            &condition("user_name", "luis"),
+            &HashMap::from([(
+                "user_country".to_string(),
+                DataValue::Number(serde_json::Number::from(10)),
+            )]),
+        );
+        assert!(mismatched.unwrap_err().is_invalid_column_value());
+
+        let luis = search_manager
+            .search("users".to_string(), &condition("user_name", "luis"))
+            .unwrap();
+        assert_eq!(luis.len(), 1);
+        assert_eq!(luis[0].value.value["user_country"], serde_json::json!("AR"));
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_upsert() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_email", DataTypes::String))
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_index(Index {
+                name: "user_email_indx".to_string(),
+                members: vec![String::from("user_email")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |uid: Uuid, name: &str| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": uid.to_string(),
+                    "user_email": "andres@schemajs.com",
+                    "user_name": name
+                }),
+            })
+        };
+
+        let first_uid = Uuid::new_v4();
+        let inserted = query_manager
+            .upsert(row(first_uid, "andres"), Some("user_email_indx"))
+            .unwrap();
+        assert_eq!(inserted, first_uid);
+
+        // Same email, the existing row is updated and keeps its uid
+        let updated = query_manager
+            .upsert(
+                row(Uuid::new_v4(), "andreespirela"),
+                Some("user_email_indx"),
+            )
+            .unwrap();
+        assert_eq!(updated, first_uid);
+
+        let rows = query_manager
+            .search_manager()
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0].value.value["user_name"],
+            serde_json::json!("andreespirela")
+        );
+        assert_eq!(
+            rows[0].value.value["_uid"],
+            serde_json::json!(first_uid.to_string())
+        );
+
+        // Keyed by the primary key
+        let by_uid = query_manager
+            .upsert(row(first_uid, "andres"), None)
+            .unwrap();
+        assert_eq!(by_uid, first_uid);
+
+        let unknown_index = query_manager.upsert(row(first_uid, "andres"), Some("nope"));
+        assert!(unknown_index.unwrap_err().is_invalid_index());
+
+        let missing_key = query_manager.upsert(
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({ "_uid": Uuid::new_v4().to_string() }),
+            }),
+            Some("user_email_indx"),
+        );
+        assert!(missing_key.unwrap_err().is_value_not_present());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_insert_many() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_index(Index {
+                name: "user_country_indx".to_string(),
+                members: vec![String::from("user_country")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        // More rows than a temporary shard can hold
+        let rows: Vec<RowJson> = (0..2500)
+            .map(|i| {
+                RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_country": if i == 1234 { "VE" } else { "US" }
+                    }),
+                })
+            })
+            .collect();
+        let expected_uids: Vec<String> = rows
+            .iter()
+            .map(|row| row.value.value["_uid"].as_str().unwrap().to_string())
+            .collect();
+
+        let uids = query_manager.insert_many(rows).unwrap();
+        assert_eq!(
+            uids.iter().map(|uid| uid.to_string()).collect::<Vec<_>>(),
+            expected_uids
+        );
+
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let search_manager = query_manager.search_manager();
+        let all = search_manager
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert_eq!(all.len(), 2500);
+
+        let ve = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "user_country".to_string(),
+                    filter_type: "=".to_string(),
+                    value: DataValue::String("VE".to_string()),
+                }),
+            )
+            .unwrap();
+        assert_eq!(ve.len(), 1);
+        assert_eq!(
+            ve[0].value.value["_uid"],
+            serde_json::json!(expected_uids[1234])
+        );
+
+        let invalid = query_manager.insert_many(vec![RowJson::from(RowData {
+            table: String::from("unknown"),
+            value: serde_json::json!({ "_uid": Uuid::new_v4().to_string() }),
+        })]);
+        assert!(invalid.unwrap_err().is_invalid_table());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_transaction() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_index(Index {
+                        name: "user_name_indx".to_string(),
+                        members: vec![String::from("user_name")],
+                        index_type: IndexType::Hash,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+        query_manager
+            .register_table(
+                Table::new("orders").add_column(Column::new("user_name", DataTypes::String)),
+            )
+            .unwrap();
+
+        let row = |table: &str, name: &str| {
+            RowJson::from(RowData {
+                table: String::from(table),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name
+                }),
+            })
+        };
+        let by_name = |name: &str| {
+            QueryOps::Condition(QueryVal {
+                key: "user_name".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(name.to_string()),
+            })
+        };
+        let count = |table: &str| {
+            query_manager
+                .search_manager()
+                .search(table.to_string(), &QueryOps::And(vec![]))
+                .unwrap()
+                .len()
+        };
+
+        query_manager.insert(row("users", "luis")).unwrap();
+
+        // Operations can see the rows inserted earlier in the same transaction
+        let mut tx = query_manager.begin();
+        tx.insert(row("users", "andres"))
+            .insert(row("orders", "andres"))
+            .update(
+                "users",
+                by_name("andres"),
+                HashMap::from([(
+                    "user_name".to_string(),
+                    DataValue::String("andreespirela".to_string()),
+                )]),
+            )
+            .delete("users", by_name("luis"));
+        assert_eq!(tx.len(), 4);
+
+        let result = query_manager.commit(tx).unwrap();
+        assert_eq!(result.inserted.len(), 2);
+        assert_eq!(result.updated, 1);
+        assert_eq!(result.deleted, 1);
+
+        let users = query_manager
+            .search_manager()
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(
+            users[0].value.value["user_name"],
+            serde_json::json!("andreespirela")
+        );
+        assert_eq!(count("orders"), 1);
+
+        // Nothing is left to undo once the commit is done
+        assert!(CommitLog::load(&db_folder).unwrap().is_none());
+
+        // An invalid operation fails the whole transaction before anything is written
+        let mut tx = query_manager.begin();
+        tx.insert(row("orders", "luis")).update(
+            "users",
+            QueryOps::And(vec![]),
+            HashMap::from([("user_age".to_string(), DataValue::Null)]),
+        );
+        assert!(query_manager.commit(tx).unwrap_err().is_invalid_column());
+        assert_eq!(count("orders"), 1);
+
+        // Rolled back operations are never applied
+        let mut tx = query_manager.begin();
+        tx.delete("orders", QueryOps::And(vec![]));
+        tx.rollback();
+        assert_eq!(count("orders"), 1);
+
+        assert_eq!(
+            query_manager
+                .delete("orders".to_string(), &by_name("andres"))
+                .unwrap(),
+            1
+        );
+        assert_eq!(count("orders"), 0);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_snapshots() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            )
+            .unwrap();
+
+        let row = |name: &str| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name
+                }),
+            })
+        };
+
+        query_manager.insert(row("andres")).unwrap();
+        query_manager.insert(row("luis")).unwrap();
+
+        let tbl = query_manager.tables.get("users").unwrap();
+
+        // Rows sitting in temporary shards are not part of any snapshot until they are reconciled
+        assert_eq!(tbl.snapshot().len, 0);
+        tbl.temps.reconcile_all();
+        let before = tbl.snapshot();
+        assert_eq!(before.len, 2);
+
+        let name_column = tbl.table.get_column("user_name").unwrap().clone();
+        let new_pointer = tbl
+            .update_row(
+                0,
+                &[(
+                    name_column.clone(),
+                    DataValue::String("andreespirela".to_string()),
+                )],
+            )
+            .unwrap();
+        tbl.delete_row(1).unwrap();
+        let inserted = tbl.insert_row(&row("carlos").serialize().unwrap()).unwrap();
+
+        // The old snapshot still sees the rows as they were
+        assert_eq!(tbl.visible_version(new_pointer, &before), Some(0));
+        assert_eq!(tbl.visible_version(0, &before), Some(0));
+        assert_eq!(tbl.visible_version(1, &before), Some(1));
+        assert_eq!(tbl.visible_version(inserted, &before), None);
+
+        let after = tbl.snapshot();
+        assert_eq!(tbl.visible_version(new_pointer, &after), Some(new_pointer));
+        assert_eq!(tbl.visible_version(0, &after), None);
+        assert_eq!(tbl.visible_version(1, &after), None);
+        assert_eq!(tbl.visible_version(inserted, &after), Some(inserted));
+
+        // Once no snapshot can see them, the previous versions are dropped by the next update
+        drop(before);
+        drop(after);
+        let newest_pointer = tbl
+            .update_row(
+                new_pointer,
+                &[(name_column, DataValue::String("andreespirela".to_string()))],
+            )
+            .unwrap();
+        let previous_versions = tbl.previous_versions.read().unwrap();
+        assert!(!previous_versions.contains_key(&new_pointer));
+        assert_eq!(previous_versions.get(&newest_pointer), Some(&new_pointer));
+        drop(previous_versions);
+
+        drop(tbl);
+
+        let names: HashSet<String> = query_manager
+            .search_manager()
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap()
+            .iter()
+            .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            HashSet::from(["andreespirela".to_string(), "carlos".to_string()])
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_unique_index() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_email", DataTypes::String))
+                    .add_index(Index {
+                        name: "user_email_indx".to_string(),
+                        members: vec![String::from("user_email")],
+                        index_type: IndexType::Hash,
+                        unique: true,
+                    }),
+            )
+            .unwrap();
+
+        let row = |email: &str| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_email": email
+                }),
+            })
+        };
+
+        query_manager.insert(row("andres@schemajs.com")).unwrap();
+
+        // Caught while the first row is still in a temporary shard
+        let duplicate = query_manager.insert(row("andres@schemajs.com"));
+        assert!(duplicate.unwrap_err().is_unique_violation());
+
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        // And once it is indexed
+        let duplicate = query_manager.insert(row("andres@schemajs.com"));
+        assert!(duplicate.unwrap_err().is_unique_violation());
+
+        query_manager.insert(row("luis@schemajs.com")).unwrap();
+
+        // Duplicates within a batch reject the whole batch
+        let batch =
+            query_manager.insert_many(vec![row("carlos@schemajs.com"), row("carlos@schemajs.com")]);
+        assert!(batch.unwrap_err().is_unique_violation());
+        query_manager.insert(row("carlos@schemajs.com")).unwrap();
+
+        // Rows without a value for the index are not constrained
+        let no_email = || {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({ "_uid": Uuid::new_v4().to_string() }),
+            })
+        };
+        query_manager.insert(no_email()).unwrap();
+        query_manager.insert(no_email()).unwrap();
+
+        // Deleted rows release their key
+        let by_email = QueryOps::Condition(QueryVal {
+            key: "user_email".to_string(),
+            filter_type: "=".to_string(),
+            value: DataValue::String("andres@schemajs.com".to_string()),
+        });
+        assert_eq!(
+            query_manager
+                .delete("users".to_string(), &by_email)
+                .unwrap(),
+            1
+        );
+        query_manager.insert(row("andres@schemajs.com")).unwrap();
+
+        // Updates can't take the key of another row, but can keep their own
+        let email = |email: &str| {
+            QueryOps::Condition(QueryVal {
+                key: "user_email".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(email.to_string()),
+            })
+        };
+        let set_email = |email: &str| {
+            HashMap::from([(
+                "user_email".to_string(),
+                DataValue::String(email.to_string()),
+            )])
+        };
+        let taken = query_manager.update(
+            "users".to_string(),
+            &email("luis@schemajs.com"),
+            &set_email("andres@schemajs.com"),
+        );
+        assert!(taken.unwrap_err().is_unique_violation());
+        assert_eq!(
+            query_manager
+                .update(
+                    "users".to_string(),
+                    &email("luis@schemajs.com"),
+                    &set_email("luis@schemajs.com"),
+                )
+                .unwrap(),
+            1
+        );
+
+        // The key the row held before the update is released
+        assert_eq!(
+            query_manager
+                .update(
+                    "users".to_string(),
+                    &email("luis@schemajs.com"),
+                    &set_email("luisito@schemajs.com"),
+                )
+                .unwrap(),
+            1
+        );
+        query_manager.insert(row("luis@schemajs.com")).unwrap();
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_primary_key() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_email", DataTypes::String).set_primary_key(true))
+            .add_column(Column::new("user_name", DataTypes::String));
+
+        assert_eq!(tbl.primary_key, "user_email");
+        assert!(tbl.get_primary_key_index().is_some());
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |uid: Uuid, email: Option<&str>| {
+            let mut value = serde_json::json!({ "_uid": uid.to_string(), "user_name": "andres" });
+            if let Some(email) = email {
+                value["user_email"] = serde_json::json!(email);
+            }
+
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value,
+            })
+        };
+
+        let uid = Uuid::new_v4();
+        query_manager
+            .insert(row(uid, Some("andres@schemajs.com")))
+            .unwrap();
+
+        let missing = query_manager.insert(row(Uuid::new_v4(), None));
+        assert_eq!(
+            missing.unwrap_err().into_value_not_present().unwrap(),
+            "user_email"
+        );
+
+        let duplicate_key = query_manager.insert(row(Uuid::new_v4(), Some("andres@schemajs.com")));
+        assert_eq!(
+            duplicate_key
+                .unwrap_err()
+                .into_duplicate_primary_key()
+                .unwrap(),
+            ("user_email".to_string(), "andres@schemajs.com".to_string())
+        );
+
+        let duplicate_uid = query_manager.insert(row(uid, Some("luis@schemajs.com")));
+        assert_eq!(
+            duplicate_uid
+                .unwrap_err()
+                .into_duplicate_primary_key()
+                .unwrap()
+                .0,
+            "_uid"
+        );
+
+        query_manager
+            .insert(row(Uuid::new_v4(), Some("luis@schemajs.com")))
+            .unwrap();
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_check_constraint() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(
+                Column::new("user_age", DataTypes::Number)
+                    .set_check("user_age >= 0 && user_age <= 150"),
+            );
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |age: serde_json::Value| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": "andres",
+                    "user_age": age
+                }),
+            })
+        };
+
+        query_manager.insert(row(serde_json::json!(20))).unwrap();
+        // Null values are not checked
+        query_manager.insert(row(serde_json::Value::Null)).unwrap();
+
+        let violation = query_manager.insert(row(serde_json::json!(-1)));
+        assert_eq!(
+            violation.unwrap_err().into_check_violation().unwrap(),
+            (
+                "user_age".to_string(),
+                "user_age >= 0 && user_age <= 150".to_string()
+            )
+        );
+        assert!(query_manager
+            .insert_many(vec![
+                row(serde_json::json!(30)),
+                row(serde_json::json!(151))
+            ])
+            .is_err());
+
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        let all = QueryOps::And(vec![]);
+        let mut values = HashMap::new();
+        values.insert(
+            "user_age".to_string(),
+            DataValue::Number(serde_json::Number::from(200)),
+        );
+        assert!(query_manager
+            .update("users".to_string(), &all, &values)
+            .unwrap_err()
+            .is_check_violation());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_default_values() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String).set_default_value("US"))
+            .add_column(Column::new("user_active", DataTypes::Boolean).set_default_value("true"))
+            .add_column(Column::new("user_token", DataTypes::Uuid).set_default_value("uuid()"))
+            .add_column(Column::new("created_at", DataTypes::Number).set_default_value("now()"));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |value: serde_json::Value| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value,
+            })
+        };
+
+        query_manager
+            .insert(row(serde_json::json!({
+                "_uid": Uuid::new_v4().to_string(),
+                "user_name": "andres"
+            })))
+            .unwrap();
+        query_manager
+            .insert(row(serde_json::json!({
+                "_uid": Uuid::new_v4().to_string(),
+                "user_name": "luis",
+                "user_country": "VE"
+            })))
+            .unwrap();
+
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let by_name = |name: &str| {
+            let rows = query_manager
+                .search_manager()
+                .search(
+                    "users".to_string(),
+                    &QueryOps::Condition(QueryVal {
+                        key: "user_name".to_string(),
+                        filter_type: "=".to_string(),
+                        value: DataValue::String(name.to_string()),
+                    }),
+                )
+                .unwrap();
+            assert_eq!(rows.len(), 1);
+            rows.into_iter().next().unwrap().value.value
+        };
+
+        let andres = by_name("andres");
+        assert_eq!(andres["user_country"], "US");
+        assert_eq!(andres["user_active"], true);
+        assert!(Uuid::parse_str(andres["user_token"].as_str().unwrap()).is_ok());
+        assert!(andres["created_at"].as_i64().unwrap() > 0);
+
+        // Values present in the row are kept
+        assert_eq!(by_name("luis")["user_country"], "VE");
+
+        query_manager
+            .register_table(
+                Table::new("orders")
+                    .add_column(Column::new("paid", DataTypes::Boolean).set_default_value("maybe")),
+            )
+            .unwrap();
+        let invalid = query_manager.insert(RowJson::from(RowData {
+            table: String::from("orders"),
+            value: serde_json::json!({ "_uid": Uuid::new_v4().to_string() }),
+        }));
+        assert_eq!(
+            invalid.unwrap_err().into_invalid_default_value().unwrap(),
+            "paid"
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_timestamps() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .set_timestamps(true);
+        assert!(tbl.get_column("_created_at").is_some());
+        assert!(tbl.get_column("_updated_at").is_some());
+
+        query_manager.register_table(tbl).unwrap();
+
+        query_manager
+            .insert(RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": "andres",
+                    "_created_at": 1
+                }),
+            }))
+            .unwrap();
+
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let all = QueryOps::And(vec![]);
+        let timestamps = || {
+            let rows = query_manager
+                .search_manager()
+                .search("users".to_string(), &all)
+                .unwrap();
+            assert_eq!(rows.len(), 1);
+
+            let row = &rows[0];
+            let value = |column: Column| {
+                row.get_value(&column)
+                    .and_then(|value| value.to_f64())
+                    .unwrap()
+            };
+            (
+                value(Table::get_created_at()),
+                value(Table::get_updated_at()),
+            )
+        };
+
+        // The engine owns the timestamps, values sent with the row are overwritten
+        let (created_at, updated_at) = timestamps();
+        assert!(created_at > 1.0);
+        assert_eq!(created_at, updated_at);
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        let mut values = HashMap::new();
+        values.insert(
+            "user_name".to_string(),
+            DataValue::String("luis".to_string()),
+        );
+        query_manager
+            .update("users".to_string(), &all, &values)
+            .unwrap();
+
+        let (new_created_at, new_updated_at) = timestamps();
+        assert_eq!(new_created_at, created_at);
+        assert!(new_updated_at > updated_at);
+
+        let mut values = HashMap::new();
+        values.insert(
+            "_created_at".to_string(),
+            DataValue::Number(serde_json::Number::from(1)),
+        );
+        assert!(query_manager
+            .update("users".to_string(), &all, &values)
+            .unwrap_err()
+            .is_immutable_column());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_numeric_types() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("products")
+            .add_column(Column::new("stock", DataTypes::Uint))
+            .add_column(Column::new("price", DataTypes::Float));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |stock: serde_json::Value, price: serde_json::Value| {
+            RowJson::from(RowData {
+                table: String::from("products"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "stock": stock,
+                    "price": price
+                }),
+            })
+        };
+
+        query_manager
+            .insert(row(serde_json::json!(3), serde_json::json!(10)))
+            .unwrap();
+        query_manager
+            .insert(row(serde_json::json!(7), serde_json::json!(2.5)))
+            .unwrap();
+
+        // Values that don't match their column type are rejected instead of panicking later on
+        let invalid = query_manager.insert(row(serde_json::json!(-1), serde_json::json!(1)));
+        assert_eq!(
+            invalid.unwrap_err().into_invalid_column_value().unwrap(),
+            "stock"
+        );
+        let invalid = query_manager.insert(row(serde_json::json!(1), serde_json::json!("1")));
+        assert_eq!(
+            invalid.unwrap_err().into_invalid_column_value().unwrap(),
+            "price"
+        );
+
+        {
+            let tbl = query_manager.tables.get("products").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let table = query_manager.tables.get("products").unwrap().table.clone();
+        let search = |query: serde_json::Value| {
+            query_manager
+                .search_manager()
+                .search(
+                    "products".to_string(),
+                    &QueryOps::from_json(&table, &query).unwrap(),
+                )
+                .unwrap()
+                .len()
+        };
+
+        assert_eq!(
+            search(serde_json::json!({ "key": "price", "filterType": "=", "value": 10.0 })),
+            1
+        );
+        assert_eq!(
+            search(serde_json::json!({ "key": "price", "filterType": "<", "value": 3 })),
+            1
+        );
+        assert_eq!(
+            search(serde_json::json!({ "key": "stock", "filterType": ">=", "value": 3 })),
+            2
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_timestamp_type() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("events")
+            .add_column(Column::new("name", DataTypes::String))
+            .add_column(Column::new("happened_at", DataTypes::Timestamp));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |name: &str, happened_at: serde_json::Value| {
+            RowJson::from(RowData {
+                table: String::from("events"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "name": name,
+                    "happened_at": happened_at
+                }),
+            })
+        };
+
+        query_manager
+            .insert(row("launch", serde_json::json!("2024-05-01T10:00:00Z")))
+            .unwrap();
+        query_manager
+            .insert(row("review", serde_json::json!("2024-05-03")))
+            .unwrap();
+        query_manager
+            .insert(row("release", serde_json::json!(1717200000000i64)))
+            .unwrap();
+
+        let invalid = query_manager.insert(row("unknown", serde_json::json!("yesterday")));
+        assert_eq!(
+            invalid.unwrap_err().into_invalid_column_value().unwrap(),
+            "happened_at"
+        );
+
+        {
+            let tbl = query_manager.tables.get("events").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let table = query_manager.tables.get("events").unwrap().table.clone();
+        let search = |query: serde_json::Value| {
+            let mut names: Vec<String> = query_manager
+                .search_manager()
+                .search(
+                    "events".to_string(),
+                    &QueryOps::from_json(&table, &query).unwrap(),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|row| row.value.value["name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Stored as epoch millis, whatever the format they were sent in
+        let stored = query_manager
+            .search_manager()
+            .search("events".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert!(stored
+            .iter()
+            .all(|row| row.value.value["happened_at"].is_i64()));
+        assert_eq!(
+            search(
+                serde_json::json!({ "key": "happened_at", "filterType": "=", "value": 1714557600000i64 })
+            ),
+            vec!["launch"]
+        );
+
+        assert_eq!(
+            search(serde_json::json!({
+                "and": [
+                    { "key": "happened_at", "filterType": ">=", "value": "2024-05-01" },
+                    { "key": "happened_at", "filterType": "<", "value": "2024-06-01T00:00:00Z" }
+                ]
+            })),
+            vec!["launch", "review"]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_nested_types() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let address = DataTypes::Object(vec![
+            Column::new("city", DataTypes::String),
+            Column::new("zip", DataTypes::Uint),
+            Column::new("geo", DataTypes::Array(Box::new(DataTypes::Float))),
+        ]);
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("address", address))
+            .add_column(Column::new(
+                "tags",
+                DataTypes::Array(Box::new(DataTypes::Object(vec![Column::new(
+                    "label",
+                    DataTypes::String,
+                )]))),
+            ));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |address: serde_json::Value, tags: serde_json::Value| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": "andres",
+                    "address": address,
+                    "tags": tags
+                }),
+            })
+        };
+
+        query_manager
+            .insert(row(
+                serde_json::json!({ "city": "Caracas", "zip": 1010, "geo": [10, -66.9] }),
+                serde_json::json!([{ "label": "admin" }]),
+            ))
+            .unwrap();
+
+        let invalid_rows = vec![
+            // Nested value of the wrong type
+            row(
+                serde_json::json!({ "city": "Caracas", "zip": "1010" }),
+                serde_json::json!([]),
+            ),
+            // Field not described by the schema
+            row(
+                serde_json::json!({ "city": "Caracas", "country": "VE" }),
+                serde_json::json!([]),
+            ),
+            // Not an object
+            row(serde_json::json!("Caracas"), serde_json::json!([])),
+            // Invalid item in an array of objects
+            row(
+                serde_json::json!({ "city": "Caracas" }),
+                serde_json::json!([{ "label": 1 }]),
+            ),
+        ];
+        for invalid in invalid_rows {
+            assert!(query_manager
+                .insert(invalid)
+                .unwrap_err()
+                .is_invalid_column_value());
+        }
+
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let rows = query_manager
+            .search_manager()
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        let address = rows[0]
+            .get_value(table.get_column("address").unwrap())
+            .unwrap();
+        let address = address.as_object().unwrap();
+        assert_eq!(address["city"], DataValue::String("Caracas".to_string()));
+        assert_eq!(
+            address["geo"],
+            DataValue::Array(vec![
+                DataValue::Number(serde_json::Number::from_f64(10.0).unwrap()),
+                DataValue::Number(serde_json::Number::from_f64(-66.9).unwrap()),
+            ])
+        );
+
+        // Floats nested in the document are stored normalized as well
+        assert_eq!(
+            rows[0].value.value["address"]["geo"],
+            serde_json::json!([10.0, -66.9])
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_enum_type() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new(
+                "user_country",
+                DataTypes::Enum(vec!["US".to_string(), "VE".to_string()]),
+            ));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |name: &str, country: &str| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name,
+                    "user_country": country
+                }),
+            })
+        };
+
+        query_manager.insert(row("andres", "VE")).unwrap();
+        query_manager.insert(row("luis", "US")).unwrap();
+
+        let invalid = query_manager.insert(row("carlos", "AR"));
+        assert_eq!(
+            invalid.unwrap_err().into_invalid_column_value().unwrap(),
+            "user_country"
+        );
+
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        let by_country = |country: &str| {
+            query_manager
+                .search_manager()
+                .search(
+                    "users".to_string(),
+                    &QueryOps::from_json(
+                        &table,
+                        &serde_json::json!({ "key": "user_country", "filterType": "=", "value": country }),
+                    )
+                    .unwrap(),
+                )
+                .unwrap()
+        };
+
+        let venezuelans = by_country("VE");
+        assert_eq!(venezuelans.len(), 1);
+
+        // Stored as the ordinal of the variant, read back as the variant
+        let andres = &venezuelans[0];
+        assert_eq!(andres.value.value["user_country"], serde_json::json!(1));
+        assert_eq!(
+            andres.get_value(table.get_column("user_country").unwrap()),
+            Some(DataValue::String("VE".to_string()))
+        );
+        assert_eq!(andres.to_json(&table)["user_country"], "VE");
+
+        let mut values = HashMap::new();
+        values.insert(
+            "user_country".to_string(),
+            DataValue::String("US".to_string()),
+        );
+        query_manager
+            .update("users".to_string(), &QueryOps::And(vec![]), &values)
+            .unwrap();
+
+        assert_eq!(by_country("US").len(), 2);
+        assert!(by_country("US")
+            .iter()
+            .all(|row| row.value.value["user_country"] == serde_json::json!(0)));
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_null_values() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String).set_nullable(false))
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_index(Index {
+                name: "ageindx".to_string(),
+                members: vec!["user_age".to_string()],
+                index_type: IndexType::BTree,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |value: serde_json::Value| {
+            let mut value = value;
+            value["_uid"] = serde_json::json!(Uuid::new_v4().to_string());
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value,
+            })
+        };
+
+        query_manager
+            .insert(row(
+                serde_json::json!({ "user_name": "andres", "user_age": 0 }),
+            ))
+            .unwrap();
+        query_manager
+            .insert(row(
+                serde_json::json!({ "user_name": "luis", "user_age": null }),
+            ))
+            .unwrap();
+        query_manager
+            .insert(row(serde_json::json!({ "user_name": "carlos" })))
+            .unwrap();
+
+        // Not nullable columns reject both null and missing values
+        let null_name = query_manager.insert(row(serde_json::json!({ "user_name": null })));
+        assert_eq!(
+            null_name.unwrap_err().into_null_value().unwrap(),
+            "user_name"
+        );
+        let missing_name = query_manager.insert(row(serde_json::json!({ "user_age": 1 })));
+        assert!(missing_name.unwrap_err().is_null_value());
+
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+        }
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        let search = |query: serde_json::Value| {
+            let mut names: Vec<String> = query_manager
+                .search_manager()
+                .search(
+                    "users".to_string(),
+                    &QueryOps::from_json(&table, &query).unwrap(),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            search(serde_json::json!({ "key": "user_age", "filterType": "is_null" })),
+            vec!["carlos", "luis"]
+        );
+        assert_eq!(
+            search(serde_json::json!({ "key": "user_age", "filterType": "is_not_null" })),
+            vec!["andres"]
+        );
+
+        // Ranges don't match nulls, whether they go through the index or not
+        assert_eq!(
+            search(serde_json::json!({ "key": "user_age", "filterType": "<=", "value": 0 })),
+            vec!["andres"]
+        );
+        assert_eq!(
+            search(serde_json::json!({ "or": [
+                { "key": "user_age", "filterType": "<", "value": 1 },
+                { "key": "user_name", "filterType": "like", "value": "%s" }
+            ]})),
+            vec!["andres", "carlos", "luis"]
+        );
+        assert!(
+            search(serde_json::json!({ "key": "user_age", "filterType": ">", "value": null }))
+                .is_empty()
+        );
+
+        let mut values = HashMap::new();
+        values.insert("user_name".to_string(), DataValue::Null);
+        assert!(query_manager
+            .update("users".to_string(), &QueryOps::And(vec![]), &values)
+            .unwrap_err()
+            .is_null_value());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_ttl() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("sessions")
+            .add_column(Column::new("token", DataTypes::String))
+            .add_column(Column::new("seen_at", DataTypes::Timestamp))
+            .add_index(Index {
+                name: "tokenindx".to_string(),
+                members: vec!["token".to_string()],
+                index_type: IndexType::Hash,
+                unique: false,
+            })
+            .set_ttl("seen_at", std::time::Duration::from_secs(60));
+
+        query_manager.register_table(tbl).unwrap();
+        query_manager.register_table(Table::new("users")).unwrap();
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let row = |token: &str, seen_at: i64| {
+            RowJson::from(RowData {
+                table: String::from("sessions"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "token": token,
+                    "seen_at": seen_at
+                }),
+            })
+        };
+
+        query_manager.insert(row("fresh", now)).unwrap();
+        query_manager.insert(row("stale", now - 120_000)).unwrap();
+
+        // Tables without a TTL are left alone
+        assert_eq!(query_manager.purge_expired("users".to_string()).unwrap(), 0);
+
+        assert_eq!(query_manager.purge_all_expired().unwrap(), 1);
+        assert_eq!(query_manager.purge_all_expired().unwrap(), 0);
+
+        let by_token = |token: &str| {
+            query_manager
+                .search_manager()
+                .search(
+                    "sessions".to_string(),
+                    &QueryOps::Condition(QueryVal {
+                        key: "token".to_string(),
+                        filter_type: "=".to_string(),
+                        value: DataValue::String(token.to_string()),
+                    }),
+                )
+                .unwrap()
+                .len()
+        };
+
+        assert_eq!(by_token("fresh"), 1);
+        assert_eq!(by_token("stale"), 0);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_create_index() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |name: &str, age: u64| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name,
+                    "user_age": age
+                }),
+            })
+        };
+
+        query_manager.insert(row("andres", 20)).unwrap();
+        query_manager.insert(row("luis", 30)).unwrap();
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+        // Still in a temporary shard when the index is created
+        query_manager.insert(row("carlos", 40)).unwrap();
+
+        let age_index = |name: &str, unique: bool| Index {
+            name: name.to_string(),
+            members: vec![String::from("user_age")],
+            index_type: IndexType::BTree,
+            unique,
+        };
+
+        query_manager
+            .create_index("users", age_index("user_age_indx", false))
+            .unwrap()
+            .join()
+            .unwrap()
+            .unwrap();
+
+        assert!(query_manager
+            .create_index("users", age_index("user_age_indx", false))
+            .unwrap_err()
+            .is_duplicate_index());
+        assert!(query_manager
+            .create_index(
+                "users",
+                Index {
+                    name: "unknown_indx".to_string(),
+                    members: vec![String::from("unknown")],
+                    index_type: IndexType::Hash,
+                    unique: false,
+                }
+            )
+            .unwrap_err()
+            .is_invalid_column());
+
+        query_manager.insert(row("juan", 50)).unwrap();
+
+        let search_manager = query_manager.search_manager();
+        let query = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">".to_string(),
+            value: DataValue::Number(serde_json::Number::from(25)),
+        });
+
+        assert!(search_manager
+            .query_plan("users".to_string(), &query)
+            .unwrap()
+            .is_index_range());
+
+        let mut names: Vec<String> = search_manager
+            .search("users".to_string(), &query)
+            .unwrap()
+            .iter()
+            .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["carlos", "juan", "luis"]);
+
+        // Unique indexes are not published when the table holds duplicates
+        query_manager.insert(row("pedro", 50)).unwrap();
+        assert!(query_manager
+            .create_index("users", age_index("user_age_unique_indx", true))
+            .unwrap()
+            .join()
+            .unwrap()
+            .unwrap_err()
+            .is_unique_violation());
+        assert!(!query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .table
+            .indexes
+            .iter()
+            .any(|index| index.name == "user_age_unique_indx"));
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_create_index_online() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+
+        query_manager.register_table(tbl).unwrap();
+
+        let row = |name: &str, age: u64| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name,
+                    "user_age": age
+                }),
+            })
+        };
+        let age_index = Index {
+            name: "user_age_indx".to_string(),
+            members: vec![String::from("user_age")],
+            index_type: IndexType::BTree,
+            unique: true,
+        };
+
+        query_manager.insert(row("andres", 20)).unwrap();
+        query_manager.insert(row("luis", 30)).unwrap();
+        let len = {
+            let table_shard = query_manager.tables.get("users").unwrap();
+            table_shard.temps.reconcile_all();
+            table_shard.data.read().unwrap().len() as u64
+        };
+
+        // The rows scanned first are updated before the last pass, their new version holds the same key
+        let mut unique_keys = HashMap::new();
+        let scanned = query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .index_entries(&age_index, 0, len, &mut unique_keys)
+            .unwrap();
+        assert_eq!(scanned.len(), 2);
+
+        let query = QueryOps::Condition(QueryVal {
+            key: "user_name".to_string(),
+            filter_type: "=".to_string(),
+            value: DataValue::String("luis".to_string()),
+        });
+        let mut changes = HashMap::new();
+        changes.insert(
+            "user_name".to_string(),
+            DataValue::String("luis alberto".to_string()),
+        );
+        assert_eq!(
+            query_manager
+                .update("users".to_string(), &query, &changes)
+                .unwrap(),
+            1
+        );
+
+        let table_shard = query_manager.tables.get("users").unwrap();
+        table_shard.temps.reconcile_all();
+        let new_len = table_shard.data.read().unwrap().len() as u64;
+        let delta = table_shard
+            .index_entries(&age_index, len, new_len, &mut unique_keys)
+            .unwrap();
+        assert_eq!(delta.len(), 1);
+        drop(table_shard);
+
+        // Rows written while the index is built end up in it
+        let handle = query_manager.create_index("users", age_index).unwrap();
+        for age in 40..60 {
+            query_manager.insert(row("pedro", age)).unwrap();
+        }
+        handle.join().unwrap().unwrap();
+        query_manager.insert(row("juan", 70)).unwrap();
+
+        let search_manager = query_manager.search_manager();
+        let older = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">=".to_string(),
+            value: DataValue::Number(serde_json::Number::from(30)),
+        });
+        assert!(search_manager
+            .query_plan("users".to_string(), &older)
+            .unwrap()
+            .is_index_range());
+        assert_eq!(
+            search_manager
+                .search("users".to_string(), &older)
+                .unwrap()
+                .len(),
+            22
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_reindex() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_index(Index {
+                name: "nameindx".to_string(),
+                members: vec![String::from("user_name")],
+                index_type: IndexType::Hash,
+                unique: true,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        for name in ["andres", "luis", "carlos"] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name
+                    }),
+                }))
+                .unwrap();
+        }
+
+        query_manager.reindex("users", "nameindx").unwrap();
+        // Rebuilding twice starts from empty files again
+        query_manager.reindex("users", "nameindx").unwrap();
+
+        assert!(query_manager
+            .reindex("users", "unknown")
+            .unwrap_err()
+            .is_invalid_index());
+        assert!(query_manager
+            .reindex("unknown", "nameindx")
+            .unwrap_err()
+            .is_invalid_table());
+
+        let search_manager = query_manager.search_manager();
+        for name in ["andres", "luis", "carlos"] {
+            let query = QueryOps::Condition(QueryVal {
+                key: "user_name".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(name.to_string()),
+            });
+
+            assert!(search_manager
+                .query_plan("users".to_string(), &query)
+                .unwrap()
+                .is_index_lookup());
+            assert_eq!(
+                search_manager
+                    .search("users".to_string(), &query)
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_duplicate_index_keys() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_index(Index {
+                name: "countryindx".to_string(),
+                members: vec![String::from("user_country")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (name, country) in [
+            ("andres", "US"),
+            ("luis", "VE"),
+            ("carlos", "US"),
+            ("juan", "US"),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_country": country
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let search = |country: &str| {
+            let mut names: Vec<String> = query_manager
+                .search_manager()
+                .search(
+                    "users".to_string(),
+                    &QueryOps::Condition(QueryVal {
+                        key: "user_country".to_string(),
+                        filter_type: "=".to_string(),
+                        value: DataValue::String(country.to_string()),
+                    }),
+                )
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(search("US"), vec!["andres", "carlos", "juan"]);
+        assert_eq!(search("VE"), vec!["luis"]);
+        assert!(search("AR").is_empty());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_geo_index() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("places")
+            .add_column(Column::new("name", DataTypes::String))
+            .add_column(Column::new("location", DataTypes::Point))
+            .add_index(Index {
+                name: "locationindx".to_string(),
+                members: vec![String::from("location")],
+                index_type: IndexType::Geo,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (name, lat, lng) in [
+            ("caracas", 10.4806, -66.9036),
+            ("valencia", 10.1620, -68.0077),
+            ("maracaibo", 10.6427, -71.6125),
+            ("madrid", 40.4168, -3.7038),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("places"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "name": name,
+                        "location": { "lat": lat, "lng": lng }
+                    }),
+                }))
+                .unwrap();
+        }
+
+        assert!(query_manager
+            .insert(RowJson::from(RowData {
+                table: String::from("places"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "location": { "lat": 100, "lng": 0 }
+                }),
+            }))
+            .is_err());
+
+        let search_manager = query_manager.search_manager();
+        let tables = query_manager.tables.clone();
+        let search = |query: serde_json::Value| {
+            let query = QueryOps::from_json(&tables.get("places").unwrap().table, &query).unwrap();
+            assert!(search_manager
+                .query_plan("places".to_string(), &query)
+                .unwrap()
+                .is_index_geo());
+
+            let mut names: Vec<String> = search_manager
+                .search("places".to_string(), &query)
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let near = |radius: u64| {
+            search(serde_json::json!({
+                "key": "location",
+                "filterType": "near",
+                "value": { "lat": 10.4806, "lng": -66.9036, "radius": radius }
+            }))
+        };
+
+        assert_eq!(near(1_000), vec!["caracas"]);
+        assert_eq!(near(130_000), vec!["caracas", "valencia"]);
+        assert_eq!(near(600_000), vec!["caracas", "maracaibo", "valencia"]);
+        assert_eq!(
+            near(20_000_000),
+            vec!["caracas", "madrid", "maracaibo", "valencia"]
+        );
+
+        assert_eq!(
+            search(serde_json::json!({
+                "key": "location",
+                "filterType": "within",
+                "value": { "minLat": 0, "minLng": -70, "maxLat": 45, "maxLng": 0 }
+            })),
+            vec!["caracas", "madrid", "valencia"]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_bloom_filters() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        // A composite index can't answer a condition on one of its members, which is scanned instead
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number))
+            .add_index(Index {
+                name: "nameageindx".to_string(),
+                members: vec![String::from("user_name"), String::from("user_age")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (name, age) in [("andres", 20), ("luis", 25), ("carlos", 20)] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_age": age
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        let tbl = tables.get("users").unwrap();
+        tbl.temps.reconcile_all();
+
+        let bloom_key = |column: &str, value: DataValue| {
+            TableShard::<RowJson>::bloom_key(column, &value).into_bytes()
+        };
+        assert!(tbl
+            .blooms
+            .might_contain(0, &bloom_key("user_name", DataValue::from("luis"))));
+        assert!(tbl.blooms.might_contain(
+            0,
+            &bloom_key(
+                "user_age",
+                DataValue::from(serde_json::Number::from_f64(20.0).unwrap())
+            )
+        ));
+        assert!(!tbl
+            .blooms
+            .might_contain(0, &bloom_key("user_name", DataValue::from("juan"))));
+        assert!(!tbl.blooms.might_contain(
+            1,
+            &bloom_key("user_age", DataValue::from(serde_json::Number::from(20)))
+        ));
+        drop(tbl);
+
+        let search = |query: serde_json::Value| {
+            let query = QueryOps::from_json(&tables.get("users").unwrap().table, &query).unwrap();
+            let mut names: Vec<String> = query_manager
+                .search_manager()
+                .search("users".to_string(), &query)
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            search(serde_json::json!({ "key": "user_age", "filterType": "=", "value": 20 })),
+            vec!["andres", "carlos"]
+        );
+        assert!(
+            search(serde_json::json!({ "key": "user_age", "filterType": "=", "value": 30 }))
+                .is_empty()
+        );
+        assert_eq!(
+            search(serde_json::json!({
+                "key": "user_name",
+                "filterType": "in",
+                "value": ["luis", "juan"]
+            })),
+            vec!["luis"]
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_index_stats() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_country", DataTypes::String))
+            .add_index(Index {
+                name: "countryindx".to_string(),
+                members: vec![String::from("user_country")],
+                index_type: IndexType::Hash,
+                unique: false,
+            })
+            .add_index(Index {
+                name: "nameindx".to_string(),
+                members: vec![String::from("user_name")],
+                index_type: IndexType::Hash,
+                unique: false,
+            });
+
+        query_manager.register_table(tbl).unwrap();
+
+        for (name, country) in [
+            ("andres", "US"),
+            ("luis", "VE"),
+            ("carlos", "US"),
+            ("juan", "US"),
+            ("pedro", "US"),
+            ("maria", "VE"),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_country": country
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let tables = query_manager.tables.clone();
+        tables.get("users").unwrap().temps.reconcile_all();
+
+        let search_manager = query_manager.search_manager();
+        let query = QueryOps::from_json(
+            &tables.get("users").unwrap().table,
+            &serde_json::json!({ "and": [
+                { "key": "user_country", "filterType": "=", "value": "US" },
+                { "key": "user_name", "filterType": "=", "value": "carlos" }
+            ] }),
+        )
+        .unwrap();
+
+        let driver = || {
+            let plan = search_manager
+                .query_plan("users".to_string(), &query)
+                .unwrap();
+            let (input, conditions) = plan.as_filter().unwrap();
+            assert_eq!(conditions.len(), 1);
+            input.as_index_lookup().unwrap().0.clone()
+        };
+
+        // Stats were computed on the empty table, every index looks alike
+        assert_eq!(driver(), "countryindx");
+
+        query_manager.refresh_index_stats();
+        {
+            let tbl = tables.get("users").unwrap();
+            let stats = tbl.index_stats.read().unwrap();
+            assert_eq!(
+                stats["countryindx"],
+                IndexStats {
+                    entries: 6,
+                    distinct_keys: 2,
+                }
+            );
+            assert_eq!(stats["nameindx"].distinct_keys, 6);
+        }
+
+        // The name index returns a single row where the country one returns four
+        assert_eq!(driver(), "nameindx");
+
+        let rows = search_manager.search("users".to_string(), &query).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value.value["user_name"], "carlos");
+
+        // Scanned conditions are checked on the rows of the index lookup
+        let query = QueryOps::from_json(
+            &tables.get("users").unwrap().table,
+            &serde_json::json!({ "and": [
+                { "key": "user_country", "filterType": "=", "value": "VE" },
+                { "key": "user_name", "filterType": "like", "value": "m%" }
+            ] }),
+        )
+        .unwrap();
+        let plan = search_manager
+            .query_plan("users".to_string(), &query)
+            .unwrap();
+        assert!(plan.as_filter().unwrap().0.is_index_lookup());
+
+        let rows = search_manager.search("users".to_string(), &query).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value.value["user_name"], "maria");
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_verify() {
+        use std::os::unix::fs::FileExt;
+
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users").add_column(Column::new("user_name", DataTypes::String));
+        query_manager.register_table(tbl).unwrap();
+
+        for name in ["andres", "carlos", "luis"] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name
+                    }),
+                }))
+                .unwrap();
+        }
+
+        assert!(query_manager.verify("users").unwrap().is_empty());
+        assert!(query_manager.verify("unknown").is_err());
+
+        // Flips a byte of the second row in the file of the main shard
+        let path = query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .data
+            .read()
+            .unwrap()
+            .current_master_shard
+            .path
+            .clone();
+        let bytes = std::fs::read(&path).unwrap();
+        let position = bytes
+            .windows(6)
+            .position(|window| window == b"carlos")
+            .unwrap();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .write_at(&[bytes[position] ^ 0xff], position as u64)
+            .unwrap();
+
+        assert_eq!(query_manager.verify("users").unwrap(), vec![1]);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_timeout() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Number)),
+            )
+            .unwrap();
+
+        let rows: Vec<RowJson> = (0..3000)
+            .map(|i| {
+                RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": format!("user{}", i),
+                        "user_age": i % 90
+                    }),
+                })
+            })
+            .collect();
+        query_manager.insert_many(rows).unwrap();
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        // Scans the whole table, there is no index on the column
+        let older = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">".to_string(),
+            value: DataValue::Number(50.into()),
+        });
+
+        // A deadline that already passed
+        assert!(query_manager
+            .search_manager()
+            .set_timeout(Some(Duration::ZERO))
+            .search("users".to_string(), &older)
+            .err()
+            .unwrap()
+            .is_timeout());
+
+        // A cancelled token stops every query using it
+        let cancel = CancelToken::new();
+        let search_manager = query_manager
+            .search_manager()
+            .set_cancel_token(cancel.clone());
+        assert!(search_manager.search("users".to_string(), &older).is_ok());
+        cancel.cancel();
+        assert!(search_manager
+            .search_partial(
+                "users".to_string(),
+                &older,
+                &SearchOpts::new().select(&["user_name"])
+            )
+            .err()
+            .unwrap()
+            .is_timeout());
+        assert!(search_manager
+            .group_by(
+                "users".to_string(),
+                &older,
+                &["user_age".to_string()],
+                &[Aggregate::count()]
+            )
+            .err()
+            .unwrap()
+            .is_timeout());
+
+        // Queries finishing before the deadline are not affected
+        let results = query_manager
+            .search_manager()
+            .set_timeout(Some(Duration::from_secs(60)))
+            .search("users".to_string(), &older)
+            .unwrap();
+        assert_eq!(results.len(), 1287);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_parallel_scan() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+        // Every reconciliation writes to a new shard file
+        query_manager
+            .register_table_with_storage(tbl, TableStorage::default().set_max_shard_size(Some(1)))
+            .unwrap();
+
+        for batch in 0..6 {
+            for age in 0..10 {
+                query_manager
+                    .insert(RowJson::from(RowData {
+                        table: String::from("users"),
+                        value: serde_json::json!({
+                            "_uid": Uuid::new_v4().to_string(),
+                            "user_name": format!("user_{}_{}", batch, age),
+                            "user_age": age
+                        }),
+                    }))
+                    .unwrap();
+            }
+            query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .temps
+                .reconcile_all();
+        }
+        assert!(
+            query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .data
+                .read()
+                .unwrap()
+                .shard_ranges()
+                .len()
+                > 1
+        );
+
+        let query = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">=".to_string(),
+            value: DataValue::Number(serde_json::Number::from(7)),
+        });
+        let names = |threads: usize| {
+            let search_manager = query_manager.search_manager().set_scan_threads(threads);
+            assert!(search_manager
+                .query_plan("users".to_string(), &query)
+                .unwrap()
+                .is_scan());
+
+            search_manager
+                .search("users".to_string(), &query)
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        let sequential = names(1);
+        assert_eq!(sequential.len(), 18);
+        assert_eq!(names(4), sequential);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_sql() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Int)),
+            )
+            .unwrap();
+
+        let execute = |sql: &str| sql::parse(sql).unwrap().execute(&query_manager);
+
+        let inserted = execute(
+            "INSERT INTO users (user_name, user_age) VALUES ('andres', 25), ('carlos', 30), ('luis', 40)",
+        )
+        .unwrap();
+        assert_eq!(inserted.as_inserted().unwrap().len(), 3);
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        assert_eq!(
+            execute(
+                "SELECT user_name FROM users WHERE user_age > 20 ORDER BY user_age DESC LIMIT 2"
+            )
+            .unwrap(),
+            SqlResult::Rows(vec![
+                serde_json::json!({ "user_name": "luis" }),
+                serde_json::json!({ "user_name": "carlos" }),
+            ])
+        );
+
+        assert_eq!(
+            execute("UPDATE users SET user_age = 31 WHERE user_name = 'carlos'").unwrap(),
+            SqlResult::Affected(1)
+        );
+        assert_eq!(
+            execute("DELETE FROM users WHERE user_name IN ('andres', 'luis')").unwrap(),
+            SqlResult::Affected(2)
+        );
+
+        let rows = execute("SELECT * FROM users").unwrap();
+        let rows = rows.as_rows().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["user_name"], "carlos");
+        assert_eq!(rows[0]["user_age"], 31);
+
+        // Columns and values are checked against the table
+        assert!(execute("SELECT unknown FROM users").is_err());
+        assert!(execute("SELECT * FROM users WHERE user_age = 'old'").is_err());
+        assert!(execute("SELECT * FROM unknown").is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_unreconciled_rows() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_country", DataTypes::String))
+                    .add_index(Index {
+                        name: "country_indx".to_string(),
+                        members: vec!["user_country".to_string()],
+                        index_type: IndexType::Hash,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+
+        let insert = |name: &str, country: &str| {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_country": country
+                    }),
+                }))
+                .unwrap();
+        };
+        let by_country = |country: &str| {
+            QueryOps::Condition(QueryVal {
+                key: "user_country".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(country.to_string()),
+            })
+        };
+
+        insert("andres", "US");
+        insert("carlos", "MX");
+        assert_eq!(
+            query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .temps
+                .pending_rows(),
+            2
+        );
+
+        // Rows are found without reconciling them, searches leave them in the temporary shards
+        let found = query_manager
+            .search_manager()
+            .search("users".to_string(), &by_country("US"))
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].value.value["user_name"], "andres");
+        assert_eq!(
+            query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .temps
+                .pending_rows(),
+            2
+        );
+
+        // Every read sees the rows written before it
+        insert("luis", "MX");
+        let count = query_manager
+            .search_manager()
+            .aggregate(
+                "users".to_string(),
+                &by_country("MX"),
+                &[Aggregate::count()],
+            )
+            .unwrap();
+        assert_eq!(count, vec![DataValue::Number(serde_json::Number::from(2))]);
+
+        insert("maria", "CO");
+        let partial = query_manager
+            .search_manager()
+            .search_partial(
+                "users".to_string(),
+                &by_country("CO"),
+                &SearchOpts::new().select(&["user_name"]),
+            )
+            .unwrap();
+        assert_eq!(partial.len(), 1);
+
+        // Reconciled and pending rows are sorted and paged together
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+        insert("beatriz", "US");
+        let names = |rows: &[RowJson]| -> Vec<String> {
+            rows.iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let opts = SearchOpts::new()
+            .sort_by("user_name", SortDirection::Asc)
+            .limit(3);
+        let first = query_manager
+            .search_manager()
+            .search_page("users".to_string(), &QueryOps::And(vec![]), &opts)
+            .unwrap();
+        assert_eq!(names(&first.rows), vec!["andres", "beatriz", "carlos"]);
+        let second = query_manager
+            .search_manager()
+            .search_page(
+                "users".to_string(),
+                &QueryOps::And(vec![]),
+                &opts.clone().after(&first.cursor.unwrap()),
+            )
+            .unwrap();
+        assert_eq!(names(&second.rows), vec!["luis", "maria"]);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_collation() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(
+                        Column::new("user_name", DataTypes::String)
+                            .set_collation(Collation::Unicode),
+                    )
+                    .add_column(
+                        Column::new("user_country", DataTypes::String)
+                            .set_collation(Collation::CaseInsensitive),
+                    )
+                    .add_column(
+                        Column::new("user_city", DataTypes::String)
+                            .set_collation(Collation::CaseInsensitive),
+                    )
+                    .add_column(Column::new("user_email", DataTypes::String))
+                    .add_index(Index {
+                        name: "nameindx".to_string(),
+                        members: vec![String::from("user_name")],
+                        index_type: IndexType::Hash,
+                        unique: true,
+                    })
+                    .add_index(Index {
+                        name: "countryindx".to_string(),
+                        members: vec![String::from("user_country")],
+                        index_type: IndexType::BTree,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+
+        let user = |name: &str, country: &str, city: &str| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name,
+                    "user_country": country,
+                    "user_city": city,
+                    "user_email": format!("{}@example.com", city),
+                }),
+            })
+        };
+        query_manager
+            .insert(user("andr\u{e9}s", "US", "Miami"))
+            .unwrap();
+        query_manager.insert(user("luis", "ve", "CARACAS")).unwrap();
+        query_manager.insert(user("carlos", "us", "miami")).unwrap();
+
+        // Unique under the collation: case and encoding of `é` (composed or not) don't make a new name
+        assert!(query_manager
+            .insert(user("ANDRE\u{301}S", "US", "Boston"))
+            .unwrap_err()
+            .is_unique_violation());
+
+        let search = |key: &str, filter_type: &str, value: &str| {
+            let mut names: Vec<String> = query_manager
+                .search_manager()
+                .search(
+                    "users".to_string(),
+                    &QueryOps::Condition(QueryVal {
+                        key: key.to_string(),
+                        filter_type: filter_type.to_string(),
+                        value: DataValue::String(value.to_string()),
+                    }),
+                )
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Index lookups and ranges
+        assert_eq!(
+            search("user_country", "=", "Us"),
+            vec!["andr\u{e9}s", "carlos"]
+        );
+        assert_eq!(search("user_country", ">", "US"), vec!["luis"]);
+        assert_eq!(
+            search("user_name", "=", "Andre\u{301}s"),
+            vec!["andr\u{e9}s"]
+        );
+
+        // Scans
+        assert_eq!(
+            search("user_city", "=", "MIAMI"),
+            vec!["andr\u{e9}s", "carlos"]
+        );
+        assert_eq!(search("user_city", "like", "cara%"), vec!["luis"]);
+        assert!(search("user_email", "=", "MIAMI@example.com").is_empty());
+
+        // Values are stored as received, sorted under the collation
+        let rows = query_manager
+            .search_manager()
+            .search_with(
+                "users".to_string(),
+                &QueryOps::And(vec![]),
+                &SearchOpts::default()
+                    .sort_by("user_city", SortDirection::Asc)
+                    .sort_by("user_name", SortDirection::Asc),
+            )
+            .unwrap();
+        let cities: Vec<&str> = rows
+            .iter()
+            .map(|row| row.value.value["user_city"].as_str().unwrap())
+            .collect();
+        assert_eq!(cities, vec!["CARACAS", "Miami", "miami"]);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_nested_index() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let profile = DataTypes::Object(vec![
+            Column::new("email", DataTypes::String),
+            Column::new(
+                "address",
+                DataTypes::Object(vec![
+                    Column::new("country", DataTypes::String)
+                        .set_collation(Collation::CaseInsensitive),
+                    Column::new("zip", DataTypes::Uint),
+                ]),
+            ),
+        ]);
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("profile", profile))
+                    .add_index(Index {
+                        name: "countryindx".to_string(),
+                        members: vec![String::from("profile.address.country")],
+                        index_type: IndexType::Hash,
+                        unique: false,
+                    })
+                    .add_index(Index {
+                        name: "emailindx".to_string(),
+                        members: vec![String::from("profile.email")],
+                        index_type: IndexType::Hash,
+                        unique: true,
+                    }),
+            )
+            .unwrap();
+
+        let user = |name: &str, profile: serde_json::Value| {
+            RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({
+                    "_uid": Uuid::new_v4().to_string(),
+                    "user_name": name,
+                    "profile": profile,
+                }),
+            })
+        };
+        query_manager
+            .insert(user(
+                "andres",
+                serde_json::json!({ "email": "andres@example.com", "address": { "country": "VE", "zip": 1010 } }),
+            ))
+            .unwrap();
+        query_manager
+            .insert(user(
+                "luis",
+                serde_json::json!({ "email": "luis@example.com", "address": { "country": "us", "zip": 10001 } }),
+            ))
+            .unwrap();
+        query_manager
+            .insert(user(
+                "carlos",
+                serde_json::json!({ "email": "carlos@example.com" }),
+            ))
+            .unwrap();
+
+        // Nested keys are unique like top-level ones
+        assert!(query_manager
+            .insert(user(
+                "juan",
+                serde_json::json!({ "email": "andres@example.com" })
+            ))
+            .unwrap_err()
+            .is_unique_violation());
+
+        let search_manager = query_manager.search_manager();
+        let country = |value: &str| {
+            QueryOps::from_json(
+                &query_manager.tables.get("users").unwrap().table,
+                &serde_json::json!({ "key": "profile.address.country", "filterType": "=", "value": value }),
+            )
+            .unwrap()
+        };
+
+        let plan = search_manager
+            .query_plan("users".to_string(), &country("ve"))
+            .unwrap();
+        assert!(plan.is_index_lookup());
+
+        let rows = search_manager
+            .search("users".to_string(), &country("ve"))
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value.value["user_name"], "andres");
+
+        // Rows missing a segment of the path are not indexed
+        let rows = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "profile.address.country".to_string(),
+                    filter_type: "is_null".to_string(),
+                    value: DataValue::Null,
+                }),
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value.value["user_name"], "carlos");
+
+        // Paths are typed after the nested columns
+        let rows = search_manager
+            .search(
+                "users".to_string(),
+                &QueryOps::Condition(QueryVal {
+                    key: "profile.address.zip".to_string(),
+                    filter_type: ">".to_string(),
+                    value: DataValue::Number(serde_json::Number::from(5000)),
+                }),
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value.value["user_name"], "luis");
+
+        for member in [
+            "profile.phone",
+            "user_name.first",
+            "profile.address.zip.code",
+        ] {
+            assert!(query_manager
+                .create_index(
+                    "users",
+                    Index {
+                        name: "unknown_indx".to_string(),
+                        members: vec![member.to_string()],
+                        index_type: IndexType::Hash,
+                        unique: false,
+                    }
+                )
+                .unwrap_err()
+                .is_invalid_column());
+        }
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_path_filter() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let profile = DataTypes::Object(vec![
+            Column::new(
+                "address",
+                DataTypes::Object(vec![Column::new("country", DataTypes::String)]),
+            ),
+            Column::new("phones", DataTypes::Array(Box::new(DataTypes::String))),
+        ]);
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("profile", profile))
+                    .add_index(Index {
+                        name: "countryindx".to_string(),
+                        members: vec![String::from("profile.address.country")],
+                        index_type: IndexType::Hash,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+
+        for (name, profile) in [
+            (
+                "andres",
+                serde_json::json!({ "address": { "country": "US" }, "phones": ["555-0100", "555-0101"] }),
+            ),
+            (
+                "luis",
+                serde_json::json!({ "address": { "country": "VE" }, "phones": ["555-0200"] }),
+            ),
+            ("carlos", serde_json::json!({ "phones": [] })),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "profile": profile,
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        let search_manager = query_manager.search_manager();
+        let names = |ops: &QueryOps| {
+            let mut names: Vec<String> = search_manager
+                .search("users".to_string(), ops)
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Paths held by an index are looked up in it, whether built as a `path` condition or parsed from JSON
+        let in_us = QueryOps::Condition(QueryVal {
+            key: "profile".to_string(),
+            filter_type: "path".to_string(),
+            value: DataValue::Object(BTreeMap::from([
+                (
+                    "path".to_string(),
+                    DataValue::String("address.country".to_string()),
+                ),
+                ("filterType".to_string(), DataValue::String("=".to_string())),
+                ("value".to_string(), DataValue::String("US".to_string())),
+            ])),
+        });
+        assert!(search_manager
+            .query_plan("users".to_string(), &in_us)
+            .unwrap()
+            .is_index_lookup());
+        assert_eq!(names(&in_us), vec!["andres"]);
+
+        let in_ve = Query::table("users")
+            .path("profile", "address.country", FilterType::Equal, "VE")
+            .build(&table)
+            .unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &in_ve)
+            .unwrap()
+            .is_index_lookup());
+        assert_eq!(names(&in_ve), vec!["luis"]);
+
+        // Paths through arrays are scanned
+        let first_phone = Query::table("users")
+            .path("profile", "phones.0", FilterType::StartsWith, "555-01")
+            .build(&table)
+            .unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &first_phone)
+            .unwrap()
+            .is_scan());
+        assert_eq!(names(&first_phone), vec!["andres"]);
+
+        let second_phone = Query::table("users")
+            .path(
+                "profile",
+                "phones.1",
+                FilterType::IsNull,
+                serde_json::Value::Null,
+            )
+            .build(&table)
+            .unwrap();
+        assert_eq!(names(&second_phone), vec!["carlos", "luis"]);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_contains() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        let strings = || DataTypes::Array(Box::new(DataTypes::String));
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(
+                        Column::new("tags", strings()).set_collation(Collation::CaseInsensitive),
+                    )
+                    .add_column(Column::new("languages", strings()))
+                    .add_index(Index {
+                        name: "tagsindx".to_string(),
+                        members: vec![String::from("tags")],
+                        index_type: IndexType::MultiEntry,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+
+        for (name, tags, languages) in [
+            ("andres", vec!["Rust", "db", "rust"], vec!["es", "en"]),
+            ("luis", vec!["go", "db"], vec!["es"]),
+            ("carlos", vec![], vec!["pt"]),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "tags": tags,
+                        "languages": languages,
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        let search_manager = query_manager.search_manager();
+        let names = |query: Query| {
+            let mut names: Vec<String> = search_manager
+                .search("users".to_string(), &query.build(&table).unwrap())
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let users = || Query::table("users");
+
+        // An entry per distinct item, empty arrays are not indexed
+        {
+            let tbl = query_manager.tables.get("users").unwrap();
+            tbl.temps.reconcile_all();
+            tbl.refresh_index_stats();
+            assert_eq!(tbl.index_stats.read().unwrap()["tagsindx"].entries, 4);
+        }
+
+        let rust = users().contains("tags", "RUST").build(&table).unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &rust)
+            .unwrap()
+            .is_index_lookup());
+        assert_eq!(names(users().contains("tags", "RUST")), vec!["andres"]);
+        assert_eq!(
+            names(users().contains("tags", "db")),
+            vec!["andres", "luis"]
+        );
+        assert!(names(users().contains("tags", "java")).is_empty());
+
+        // Equality compares whole arrays, which the index can't answer
+        let whole = users().eq("tags", vec!["go", "db"]).build(&table).unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &whole)
+            .unwrap()
+            .is_scan());
+        assert_eq!(names(users().eq("tags", vec!["go", "db"])), vec!["luis"]);
+
+        // Without an index the column is scanned
+        assert_eq!(
+            names(users().contains("languages", "es")),
+            vec!["andres", "luis"]
+        );
+        assert!(users()
+            .contains("user_name", "andres")
+            .build(&table)
+            .is_err());
+        assert!(users().contains("languages", 1).build(&table).is_err());
+
+        // Updates move the rows to the entries of their new items
+        query_manager
+            .update(
+                "users".to_string(),
+                &users().eq("user_name", "carlos").build(&table).unwrap(),
+                &HashMap::from([(
+                    "tags".to_string(),
+                    DataValue::Array(vec![
+                        DataValue::String("rust".to_string()),
+                        DataValue::String("db".to_string()),
+                    ]),
+                )]),
+            )
+            .unwrap();
+        query_manager
+            .update(
+                "users".to_string(),
+                &users().eq("user_name", "luis").build(&table).unwrap(),
+                &HashMap::from([("tags".to_string(), DataValue::Array(vec![]))]),
+            )
+            .unwrap();
+        assert_eq!(
+            names(users().contains("tags", "rust")),
+            vec!["andres", "carlos"]
+        );
+        assert_eq!(
+            names(users().contains("tags", "db")),
+            vec!["andres", "carlos"]
+        );
+        assert!(query_manager
+            .check_integrity("users", false)
+            .unwrap()
+            .is_ok());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_between() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Number))
+                    .add_column(Column::new("user_score", DataTypes::Number))
+                    .add_index(Index {
+                        name: "ageindx".to_string(),
+                        members: vec![String::from("user_age")],
+                        index_type: IndexType::BTree,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+
+        for (name, age, score) in [
+            ("andres", 18, 5),
+            ("luis", 25, 7),
+            ("carlos", 30, 9),
+            ("maria", 42, 3),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_age": age,
+                        "user_score": score,
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        let search_manager = query_manager.search_manager();
+        let names = |query: Query| {
+            let mut names: Vec<String> = search_manager
+                .search("users".to_string(), &query.build(&table).unwrap())
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let users = || Query::table("users");
+
+        // A single range scan of the index, both bounds included
+        let adults = users().between("user_age", 18, 30).build(&table).unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &adults)
+            .unwrap()
+            .is_index_range());
+        assert_eq!(
+            names(users().between("user_age", 18, 30)),
+            vec!["andres", "carlos", "luis"]
+        );
+
+        let exclusive = || {
+            users().condition(
+                "user_age",
+                FilterType::Between,
+                serde_json::json!({ "min": 18, "max": 30, "minExclusive": true, "maxExclusive": true }),
+            )
+        };
+        assert_eq!(names(exclusive()), vec!["luis"]);
+
+        // Bounds no value falls between plan nothing
+        let empty = users().between("user_age", 30, 18).build(&table).unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &empty)
+            .unwrap()
+            .is_empty());
+        assert!(names(users().between("user_age", 30, 18)).is_empty());
+
+        // Without an index the column is scanned
+        let scored = users().between("user_score", 5, 7).build(&table).unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &scored)
+            .unwrap()
+            .is_scan());
+        assert_eq!(
+            names(users().between("user_score", 5, 7)),
+            vec!["andres", "luis"]
+        );
+
+        // Bounds must be of the type of the column
+        assert!(users().between("user_age", "a", "z").build(&table).is_err());
+        assert!(users()
+            .condition(
+                "user_age",
+                FilterType::Between,
+                serde_json::json!({ "min": 18 })
+            )
+            .build(&table)
+            .is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_regex() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_email", DataTypes::String))
+                    .add_index(Index {
+                        name: "emailindx".to_string(),
+                        members: vec![String::from("user_email")],
+                        index_type: IndexType::BTree,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+
+        for (name, email) in [
+            ("andres", "andres@outlook.com"),
+            ("luis", "luis@gmail.com"),
+            ("carlos", "Carlos@Outlook.com"),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_email": email,
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let table = query_manager.tables.get("users").unwrap().table.clone();
+        let search_manager = query_manager.search_manager();
+        let names = |query: Query| {
+            let mut names: Vec<String> = search_manager
+                .search("users".to_string(), &query.build(&table).unwrap())
+                .unwrap()
+                .iter()
+                .map(|row| row.value.value["user_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let users = || Query::table("users");
+
+        // Patterns are matched row by row, even over indexed columns
+        let outlook = users()
+            .regex("user_email", "@outlook")
+            .build(&table)
+            .unwrap();
+        assert!(search_manager
+            .query_plan("users".to_string(), &outlook)
+            .unwrap()
+            .is_scan());
+        assert_eq!(
+            names(users().regex("user_email", "@outlook")),
+            vec!["andres"]
+        );
+        assert_eq!(
+            names(users().regex("user_email", "(?i)@outlook\\.com$")),
+            vec!["andres", "carlos"]
+        );
+        assert_eq!(
+            names(
+                users()
+                    .regex("user_email", "^[a-z]+@")
+                    .and()
+                    .regex("user_name", "s$")
+            ),
+            vec!["andres", "luis"]
+        );
+        assert!(users().regex("user_email", "[a-").build(&table).is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_pipeline() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_country", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Number)),
+            )
+            .unwrap();
+
+        for (name, country, age) in [
+            ("andres", "CO", 28),
+            ("luis", "CO", 35),
+            ("carlos", "MX", 19),
+            ("maria", "MX", 42),
+            ("john", "US", 51),
+            ("jane", "US", 24),
+            ("pedro", "CO", 60),
+        ] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: String::from("users"),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name,
+                        "user_country": country,
+                        "user_age": age,
+                    }),
+                }))
+                .unwrap();
+        }
+
+        let search_manager = query_manager.search_manager();
+        let run = |pipeline: serde_json::Value| {
+            search_manager.pipeline(
+                "users".to_string(),
+                &Pipeline::from_json(&pipeline).unwrap(),
+            )
+        };
+
+        // Match, group, match on the aggregates, project and sort in one request
+        assert_eq!(
+            run(serde_json::json!([
+                { "$match": { "user_age": { "$gte": 21 } } },
+                { "$group": { "by": ["user_country"], "aggregates": [
+                    { "function": "count" },
+                    { "function": "avg", "column": "user_age" }
+                ] } },
+                { "$match": { "count": { "$gt": 1 } } },
+                { "$project": ["user_country", "avg_user_age"] },
+                { "$sort": [{ "column": "avg_user_age", "direction": "desc" }] }
+            ]))
+            .unwrap(),
+            vec![
+                serde_json::json!({ "user_country": "CO", "avg_user_age": 41 }),
+                serde_json::json!({ "user_country": "US", "avg_user_age": 37.5 }),
+            ]
+        );
+
+        // Without a group, sorting and limiting the rows is left to the search
+        assert_eq!(
+            run(serde_json::json!([
+                { "$match": { "user_country": "CO" } },
+                { "$sort": [{ "column": "user_age", "direction": "asc" }] },
+                { "$limit": 2 },
+                { "$project": ["user_name"] }
+            ]))
+            .unwrap(),
+            vec![
+                serde_json::json!({ "user_name": "andres" }),
+                serde_json::json!({ "user_name": "luis" }),
+            ]
+        );
+
+        // Stages can be repeated in any order, the Rust builder producing the same pipeline
+        let oldest = Pipeline::new()
+            .sort("user_age", SortDirection::Desc)
+            .limit(4)
+            .group(
+                &["user_country"],
+                vec![Aggregate::new(AggregateFunction::Min, "user_name")],
+            )
+            .sort("min_user_name", SortDirection::Asc);
+        assert_eq!(
+            search_manager
+                .pipeline("users".to_string(), &oldest)
+                .unwrap(),
+            vec![
+                serde_json::json!({ "user_country": "US", "min_user_name": "john" }),
+                serde_json::json!({ "user_country": "CO", "min_user_name": "luis" }),
+                serde_json::json!({ "user_country": "MX", "min_user_name": "maria" }),
+            ]
+        );
+        assert_eq!(
+            search_manager
+                .pipeline("users".to_string(), &Pipeline::new())
+                .unwrap()
+                .len(),
+            7
+        );
+
+        assert!(
+            run(serde_json::json!([{ "$group": { "by": ["unknown"] } }]))
+                .unwrap_err()
+                .is_invalid_column()
+        );
+        assert!(run(serde_json::json!([
+            { "$project": ["user_name"] },
+            { "$match": { "user_age": 28 } }
+        ]))
+        .unwrap_err()
+        .is_invalid_column());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_search_manager_invalid_filter_type() {
+        let test_db = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, test_db.as_str());
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db.clone());
+
+        query_manager
+            .register_table(
+                Table::new("users").add_column(Column::new("user_age", DataTypes::Number)),
+            )
+            .unwrap();
+        query_manager
+            .insert(RowJson::from(RowData {
+                table: String::from("users"),
+                value: serde_json::json!({ "_uid": Uuid::new_v4().to_string(), "user_age": 30 }),
+            }))
+            .unwrap();
+
+        // A typo in a query built by hand fails instead of matching nothing, however deep it is
+        let typo = QueryOps::And(vec![
+            QueryOps::Condition(QueryVal {
+                key: "user_age".to_string(),
+                filter_type: ">".to_string(),
+                value: DataValue::Number(20.into()),
+            }),
+            QueryOps::Or(vec![QueryOps::Condition(QueryVal {
+                key: "user_age".to_string(),
+                filter_type: "greater_than".to_string(),
+                value: DataValue::Number(20.into()),
+            })]),
+        ]);
+        let search_manager = query_manager.search_manager();
+        assert!(search_manager
+            .search("users".to_string(), &typo)
+            .unwrap_err()
+            .is_invalid_filter_type());
+        assert!(search_manager
+            .search_partial("users".to_string(), &typo, &SearchOpts::default())
+            .unwrap_err()
+            .is_invalid_filter_type());
+        assert!(search_manager
+            .aggregate("users".to_string(), &typo, &[Aggregate::count()])
+            .unwrap_err()
+            .is_invalid_filter_type());
+        assert!(search_manager
+            .group_by("users".to_string(), &typo, &[], &[Aggregate::count()])
+            .unwrap_err()
+            .is_invalid_filter_type());
+        assert!(search_manager
+            .explain("users".to_string(), &typo)
+            .unwrap_err()
+            .is_invalid_filter_type());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
 }
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// Sorts the results of a search by the value of `column`.
+/// Rows that do not contain the column are placed first when sorting ascending and last when sorting descending.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SortBy {
+    pub column: String,
+    pub direction: SortDirection,
+}
+
+/// Options applied to the rows matched by a query in `QuerySearchManager::search_with`.
+///
+/// # Fields:
+/// - `sort`: Sort criteria, applied in order.
+/// - `limit`: Maximum amount of rows to return.
+/// - `offset`: Amount of rows to skip (after applying `cursor`).
+/// - `cursor`: Continuation token returned by `QuerySearchManager::search_page`. Rows up to (and including)
+///   the row the cursor points to are skipped.
+/// - `projection`: Columns to return, used by `QuerySearchManager::search_partial`.
+///
+/// # Example
+///
+/// ```ignore
+/// let opts = SearchOpts::new()
+///     .sort_by("user_age", SortDirection::Desc)
+///     .sort_by("user_email", SortDirection::Asc)
+///     .limit(50);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOpts {
+    pub sort: Vec<SortBy>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub cursor: Option<String>,
+    pub projection: Option<Vec<String>>,
+}
+
+impl SearchOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sort criteria. Criteria are applied in the order they were added.
+    pub fn sort_by(mut self, column: &str, direction: SortDirection) -> Self {
+        self.sort.push(SortBy {
+            column: column.to_string(),
+            direction,
+        });
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Continues a previous search after the row pointed by `cursor`.
+    pub fn after(mut self, cursor: &str) -> Self {
+        self.cursor = Some(cursor.to_string());
+        self
+    }
+
+    /// Only returns `columns` when searching through `QuerySearchManager::search_partial`.
+    pub fn select(mut self, columns: &[&str]) -> Self {
+        self.projection = Some(columns.iter().map(|column| column.to_string()).collect());
+        self
+    }
+}
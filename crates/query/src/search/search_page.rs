@@ -0,0 +1,61 @@
+use crate::errors::QueryError;
+use schemajs_primitives::column::types::DataValue;
+use serde::{Deserialize, Serialize};
+
+/// A page of rows returned by `QuerySearchManager::search_page`.
+///
+/// # Fields:
+/// - `rows`: The rows in the page.
+/// - `cursor`: Opaque continuation token pointing right after the last row of the page.
+///   It is `None` when there are no more rows to fetch.
+#[derive(Debug)]
+pub struct SearchPage<T> {
+    pub rows: Vec<T>,
+    pub cursor: Option<String>,
+}
+
+/// Position of a row inside an ordered search result.
+/// It's made of the values of the sort columns and the row pointer, which is used as a tie breaker.
+pub(crate) type SortKey = (Vec<Option<DataValue>>, u64);
+
+/// Keyset cursor. Instead of counting rows, it holds the sort key of the last row that was returned
+/// so the next page starts at the first row whose sort key is greater.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SearchCursor {
+    pub values: Vec<Option<DataValue>>,
+    pub pointer: u64,
+}
+
+impl SearchCursor {
+    pub fn new(key: SortKey) -> Self {
+        Self {
+            values: key.0,
+            pointer: key.1,
+        }
+    }
+
+    pub fn key(&self) -> SortKey {
+        (self.values.clone(), self.pointer)
+    }
+
+    pub fn encode(&self) -> String {
+        let bytes = serde_json::to_vec(self).unwrap();
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, QueryError> {
+        let invalid = || QueryError::InvalidCursor(cursor.to_string());
+
+        if cursor.len() % 2 != 0 || !cursor.is_ascii() {
+            return Err(invalid());
+        }
+
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+
+        serde_json::from_slice(&bytes).map_err(|_| invalid())
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+/// Amount of slow queries kept by a `SlowQueryLog`, older ones are dropped first.
+pub const MAX_SLOW_QUERIES: usize = 100;
+
+/// Search or mutation that took longer than the threshold of the `SlowQueryLog`.
+///
+/// # Fields:
+/// - `operation`: What was executed (`search`, `update` or `delete`).
+/// - `table`: Table the query ran against.
+/// - `shape`: The query with its values replaced by `?` (see `QueryOps::shape`), so queries differing only
+///   in their values are grouped together.
+/// - `indexes`: Indexes used by the plan of the query, empty when every row had to be read.
+/// - `pointers`: Amount of rows matched (for searches, the rows of the returned page).
+/// - `elapsed`: Time taken by the query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowQuery {
+    pub operation: String,
+    pub table: String,
+    pub shape: String,
+    pub indexes: Vec<String>,
+    pub pointers: usize,
+    pub elapsed: Duration,
+}
+
+impl Display for SlowQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let indexes = match self.indexes.is_empty() {
+            true => String::from("none"),
+            false => self.indexes.join(", "),
+        };
+
+        write!(
+            f,
+            "Slow {} on '{}' ({:.3?}): {} [index: {}, pointers: {}]",
+            self.operation, self.table, self.elapsed, self.shape, indexes, self.pointers
+        )
+    }
+}
+
+/// Log of the queries of a database exceeding a threshold (`slow_query_threshold` under
+/// `[databases.<database name>]` in `SchemeJS.toml`), meant to find the queries missing an index.
+///
+/// Slow queries are logged (as warnings) as they happen and the last `MAX_SLOW_QUERIES` are kept to be inspected.
+/// Nothing is logged while there is no threshold.
+#[derive(Debug, Default)]
+pub struct SlowQueryLog {
+    threshold: RwLock<Option<Duration>>,
+    entries: Mutex<VecDeque<SlowQuery>>,
+}
+
+impl SlowQueryLog {
+    pub fn new(threshold: Option<Duration>) -> Self {
+        Self {
+            threshold: RwLock::new(threshold),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn threshold(&self) -> Option<Duration> {
+        *self.threshold.read().unwrap()
+    }
+
+    /// Changes the threshold, `None` stops logging.
+    pub fn set_threshold(&self, threshold: Option<Duration>) {
+        *self.threshold.write().unwrap() = threshold;
+    }
+
+    /// Whether a query taking `elapsed` has to be logged.
+    pub fn is_slow(&self, elapsed: Duration) -> bool {
+        self.threshold()
+            .map_or(false, |threshold| elapsed >= threshold)
+    }
+
+    pub fn record(&self, query: SlowQuery) {
+        tracing::warn!(
+            operation = %query.operation,
+            table = %query.table,
+            shape = %query.shape,
+            indexes = ?query.indexes,
+            pointers = query.pointers,
+            elapsed = ?query.elapsed,
+            "Slow query"
+        );
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == MAX_SLOW_QUERIES {
+            entries.pop_front();
+        }
+        entries.push_back(query);
+    }
+
+    /// Slow queries kept, from the oldest to the newest.
+    pub fn entries(&self) -> Vec<SlowQuery> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{equals, test_db, user_row};
+    use crate::managers::single::SingleQueryManager;
+    use crate::ops::query_ops::{QueryOps, QueryVal};
+    use crate::row_json::RowJson;
+    use schemajs_index::index_type::IndexType;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::index::Index;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[flaky_test::flaky_test]
+    pub fn test_slow_query_log() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Number))
+                    .add_index(Index {
+                        name: "user_name_indx".to_string(),
+                        members: vec![String::from("user_name")],
+                        index_type: IndexType::Hash,
+                        unique: false,
+                    }),
+            )
+            .unwrap();
+
+        for (name, age) in [("andres", 25), ("carlos", 30), ("luis", 40)] {
+            query_manager
+                .insert(user_row(json!({ "user_name": name, "user_age": age })))
+                .unwrap();
+        }
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        let by_name = |name: &str| equals("user_name", name);
+        let older = QueryOps::Condition(QueryVal {
+            key: "user_age".to_string(),
+            filter_type: ">".to_string(),
+            value: DataValue::Number(26.into()),
+        });
+
+        // Nothing is logged without a threshold
+        let search_manager = query_manager.search_manager();
+        search_manager
+            .search("users".to_string(), &by_name("andres"))
+            .unwrap();
+        assert!(query_manager.slow_queries().entries().is_empty());
+
+        // Every query is slow with a threshold of zero
+        query_manager.set_slow_query_threshold(Some(Duration::ZERO));
+        let search_manager = query_manager.search_manager();
+        search_manager
+            .search("users".to_string(), &by_name("carlos"))
+            .unwrap();
+        search_manager.search("users".to_string(), &older).unwrap();
+
+        let entries = query_manager.slow_queries().entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].operation, "search");
+        assert_eq!(entries[0].table, "users");
+        assert_eq!(entries[0].shape, "user_name = ?");
+        assert_eq!(entries[0].indexes, vec!["user_name_indx".to_string()]);
+        assert_eq!(entries[0].pointers, 1);
+        // Queries missing an index read every row
+        assert_eq!(entries[1].shape, "user_age > ?");
+        assert!(entries[1].indexes.is_empty());
+        assert_eq!(entries[1].pointers, 2);
+        assert!(entries[1].to_string().starts_with("Slow search on 'users'"));
+
+        // Mutations are logged too
+        let mut new_values = HashMap::new();
+        new_values.insert("user_age".to_string(), DataValue::Number(50.into()));
+        query_manager
+            .update("users".to_string(), &older, &new_values)
+            .unwrap();
+        query_manager
+            .delete("users".to_string(), &by_name("luis"))
+            .unwrap();
+
+        let entries = query_manager.slow_queries().entries();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[2].operation, "update");
+        assert_eq!(entries[2].pointers, 2);
+        assert_eq!(entries[3].operation, "delete");
+        assert_eq!(entries[3].indexes, vec!["user_name_indx".to_string()]);
+
+        // Queries under the threshold are not logged
+        query_manager.set_slow_query_threshold(Some(Duration::from_secs(60)));
+        query_manager
+            .search_manager()
+            .search("users".to_string(), &older)
+            .unwrap();
+        assert_eq!(query_manager.slow_queries().entries().len(), 4);
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
@@ -0,0 +1,57 @@
+use crate::managers::single::table_shard::TableShard;
+use crate::row::Row;
+use schemajs_data::errors::ShardErrors;
+use schemajs_data::shard::map_shard::MapShard;
+use schemajs_data::shard::shards::data_shard::config::DataShardConfig;
+use schemajs_data::shard::shards::data_shard::shard::DataShard;
+use schemajs_data::snapshot::SnapshotGuard;
+
+/// Rows of a table seen by a search: the rows of a snapshot of the main shard, plus the rows still sitting in
+/// its temporary shards when the snapshot was taken. Searches see every row written before they start
+/// (read-your-writes) without reconciling the temporary shards.
+///
+/// Pending rows are given the positions right after the snapshot, in the order they would be reconciled in,
+/// so they come after the rows of the main shard. Those positions may hold other rows in the main shard,
+/// rows must be read through `get`.
+///
+/// # Fields:
+/// - `snapshot`: Snapshot of the main shard the search runs on.
+/// - `pending`: Rows of the temporary shards that are not part of `snapshot`.
+#[derive(Debug)]
+pub struct TableView {
+    pub snapshot: SnapshotGuard,
+    pending: Vec<Vec<u8>>,
+}
+
+impl TableView {
+    pub fn new<T: Row<T>>(shard: &TableShard<T>) -> Self {
+        let (snapshot, pending) = shard.temps.read_pending(|| shard.snapshot());
+
+        Self { snapshot, pending }
+    }
+
+    /// Pending rows along with the position they are given.
+    pub fn pending(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.pending
+            .iter()
+            .enumerate()
+            .map(|(i, data)| (self.snapshot.len + i as u64, data.as_slice()))
+    }
+
+    /// Whether `pointer` is the position of a pending row.
+    pub fn is_pending(&self, pointer: u64) -> bool {
+        !self.snapshot.contains(pointer)
+    }
+
+    /// Reads the row at `pointer`, from the main shard `data` or from the pending rows.
+    pub fn get(
+        &self,
+        data: &MapShard<DataShard, DataShardConfig>,
+        pointer: u64,
+    ) -> Result<Vec<u8>, ShardErrors> {
+        match self.is_pending(pointer) {
+            true => Ok(self.pending[(pointer - self.snapshot.len) as usize].clone()),
+            false => data.get_element(pointer as usize),
+        }
+    }
+}
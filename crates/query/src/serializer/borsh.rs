@@ -1,5 +1,8 @@
+use crate::row_json::RowData;
+use crate::serializer::registry::RowCodec;
 use crate::serializer::{RowSerializationError, RowSerializer};
 use borsh::{BorshDeserialize, BorshSerialize};
+use schemajs_primitives::table::Table;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::io::Write;
@@ -12,6 +15,9 @@ pub enum BorshJsonValue {
     String(String),
     Array(Vec<BorshJsonValue>),
     Object(HashMap<String, BorshJsonValue>),
+    // Integers are kept apart from `Number`, which would turn them into floats
+    Int(i64),
+    Uint(u64),
 }
 
 impl From<&Value> for BorshJsonValue {
@@ -19,7 +25,11 @@ impl From<&Value> for BorshJsonValue {
         match value {
             Value::Null => BorshJsonValue::Null,
             Value::Bool(b) => BorshJsonValue::Bool(*b),
-            Value::Number(n) => BorshJsonValue::Number(n.as_f64().unwrap()), // assuming all numbers can be converted to f64
+            Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(n), _) => BorshJsonValue::Uint(n),
+                (None, Some(n)) => BorshJsonValue::Int(n),
+                _ => BorshJsonValue::Number(n.as_f64().unwrap()), // assuming all numbers can be converted to f64
+            },
             Value::String(s) => BorshJsonValue::String(s.clone()),
             Value::Array(arr) => {
                 BorshJsonValue::Array(arr.iter().map(BorshJsonValue::from).collect())
@@ -39,6 +49,8 @@ impl Into<Value> for BorshJsonValue {
             BorshJsonValue::Null => Value::Null,
             BorshJsonValue::Bool(b) => Value::Bool(b),
             BorshJsonValue::Number(n) => Value::Number(serde_json::Number::from_f64(n).unwrap()),
+            BorshJsonValue::Int(n) => Value::from(n),
+            BorshJsonValue::Uint(n) => Value::from(n),
             BorshJsonValue::String(s) => Value::String(s),
             BorshJsonValue::Array(arr) => Value::Array(arr.into_iter().map(Into::into).collect()),
             BorshJsonValue::Object(obj) => Value::Object(
@@ -67,3 +79,20 @@ impl RowSerializer<Value> for BorshRowSerializer {
         Ok(borsh_value.into())
     }
 }
+
+impl RowCodec for BorshRowSerializer {
+    fn encode(&self, _table: &Table, row: &RowData) -> Result<Vec<u8>, RowSerializationError> {
+        borsh::to_vec(&(row.table.clone(), BorshJsonValue::from(&row.value)))
+            .map_err(|e| RowSerializationError::SerializationError(e.to_string()))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<RowData, RowSerializationError> {
+        let (table, value) = <(String, BorshJsonValue)>::try_from_slice(data)
+            .map_err(|e| RowSerializationError::DeserializationError(e.to_string()))?;
+
+        Ok(RowData {
+            table,
+            value: value.into(),
+        })
+    }
+}
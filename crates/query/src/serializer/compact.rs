@@ -0,0 +1,494 @@
+use crate::row_json::RowData;
+use crate::serializer::RowSerializationError;
+use once_cell::sync::Lazy;
+use schemajs_primitives::column::types::DataTypes;
+use schemajs_primitives::table::Table;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/// First byte of the rows encoded by `encode`. JSON rows always start with `{`, so both can live in the same shard.
+pub const COMPACT_MAGIC: u8 = 0xC5;
+
+/// File of the folder of a table holding every `CompactLayout` its rows were encoded with.
+pub const LAYOUTS_FILE: &str = "layouts.json";
+
+const ABSENT: u8 = 0;
+const NULL: u8 = 1;
+const FALSE: u8 = 2;
+const TRUE: u8 = 3;
+const UINT: u8 = 4;
+const INT: u8 = 5;
+const FLOAT: u8 = 6;
+const STRING: u8 = 7;
+const UUID: u8 = 8;
+const JSON: u8 = 9;
+
+static LAYOUTS: Lazy<RwLock<HashMap<u64, Arc<CompactLayout>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Columns of a table in the order `encode` writes their values, sorted by name.
+///
+/// Rows only store the `fingerprint` of their layout, decoding looks the layout up among the registered ones
+/// (see `register`). Layouts are kept next to the shards of their table (see `CompactLayout::open`), so rows
+/// written before the columns of the table changed can still be read.
+///
+/// # Fields:
+/// - `fingerprint`: Hash of the name and type of every column.
+/// - `columns`: Name and type of every column.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactLayout {
+    pub fingerprint: u64,
+    pub columns: Vec<(String, DataTypes)>,
+}
+
+impl CompactLayout {
+    pub fn of(table: &Table) -> Self {
+        let mut columns: Vec<(String, DataTypes)> = table
+            .columns
+            .values()
+            .map(|column| (column.name.clone(), column.data_type.clone()))
+            .collect();
+        columns.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Self {
+            fingerprint: Self::fingerprint(&columns),
+            columns,
+        }
+    }
+
+    // FNV-1a, stable across processes unlike the hasher of the standard library
+    fn fingerprint(columns: &[(String, DataTypes)]) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for (name, data_type) in columns {
+            let data_type = serde_json::to_vec(data_type).unwrap_or_default();
+            for byte in name.bytes().chain([0]).chain(data_type).chain([0]) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        hash
+    }
+
+    /// Registers the layouts stored in the folder `table_path`, so the rows written with them can be decoded.
+    pub fn load(table_path: &Path) -> std::io::Result<Vec<Self>> {
+        let layouts: Vec<Self> = match std::fs::read(table_path.join(LAYOUTS_FILE)) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(_) => vec![],
+        };
+
+        for layout in &layouts {
+            register(layout.clone());
+        }
+
+        Ok(layouts)
+    }
+
+    /// Registers the layouts stored in the folder `table_path` along with the one of `table`, which is stored
+    /// there if it is new.
+    ///
+    /// # Returns:
+    /// - `std::io::Result<Arc<CompactLayout>>`: The layout of `table`, the one new rows are encoded with.
+    pub fn open(table_path: &Path, table: &Table) -> std::io::Result<Arc<Self>> {
+        let mut layouts = Self::load(table_path)?;
+
+        let layout = Self::of(table);
+        if !layouts.iter().any(|l| l.fingerprint == layout.fingerprint) {
+            layouts.push(layout.clone());
+            std::fs::write(table_path.join(LAYOUTS_FILE), serde_json::to_vec(&layouts)?)?;
+        }
+
+        Ok(register(layout))
+    }
+}
+
+/// Makes `layout` available to `decode`.
+pub fn register(layout: CompactLayout) -> Arc<CompactLayout> {
+    LAYOUTS
+        .write()
+        .unwrap()
+        .entry(layout.fingerprint)
+        .or_insert_with(|| Arc::new(layout))
+        .clone()
+}
+
+/// Registered layout with the given fingerprint.
+pub fn layout(fingerprint: u64) -> Option<Arc<CompactLayout>> {
+    LAYOUTS.read().unwrap().get(&fingerprint).cloned()
+}
+
+/// Whether `data` was encoded by `encode`.
+pub fn is_compact(data: &[u8]) -> bool {
+    data.first() == Some(&COMPACT_MAGIC)
+}
+
+/// Encodes `row` positionally with the columns of `layout`: every column takes a tag byte followed by its value,
+/// column names are not stored. Fields of the row that are not columns of the layout (e.g. dropped columns) are
+/// stored with their name after the columns.
+///
+/// Rows whose value is not an object are serialized as JSON.
+pub fn encode(layout: &CompactLayout, row: &RowData) -> Result<Vec<u8>, RowSerializationError> {
+    let fields = match row.value.as_object() {
+        Some(fields) => fields,
+        None => {
+            return serde_json::to_vec(row)
+                .map_err(|e| RowSerializationError::SerializationError(e.to_string()))
+        }
+    };
+
+    let mut data = Vec::with_capacity(64);
+    data.push(COMPACT_MAGIC);
+    data.extend_from_slice(&layout.fingerprint.to_le_bytes());
+    write_str(&mut data, &row.table);
+
+    for (name, data_type) in &layout.columns {
+        match fields.get(name) {
+            Some(value) => write_value(&mut data, value, matches!(data_type, DataTypes::Uuid))?,
+            None => data.push(ABSENT),
+        }
+    }
+
+    let extra: Vec<(&String, &Value)> = fields
+        .iter()
+        .filter(|(name, _)| {
+            layout
+                .columns
+                .binary_search_by(|(column, _)| column.as_str().cmp(name.as_str()))
+                .is_err()
+        })
+        .collect();
+    write_varint(&mut data, extra.len() as u64);
+    for (name, value) in extra {
+        write_str(&mut data, name);
+        write_value(&mut data, value, false)?;
+    }
+
+    Ok(data)
+}
+
+/// Decodes a row written by `encode`. Its layout must be registered (see `register`).
+pub fn decode(data: &[u8]) -> Result<RowData, RowSerializationError> {
+    let mut reader = Reader { data, position: 0 };
+    if reader.byte()? != COMPACT_MAGIC {
+        return Err(invalid("not a compact row"));
+    }
+
+    let fingerprint = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+    let layout =
+        layout(fingerprint).ok_or_else(|| invalid(&format!("unknown layout {:x}", fingerprint)))?;
+    let table = reader.string()?;
+
+    let mut fields = Map::new();
+    for (name, _) in &layout.columns {
+        if let Some(value) = reader.value()? {
+            fields.insert(name.clone(), value);
+        }
+    }
+
+    for _ in 0..reader.varint()? {
+        let name = reader.string()?;
+        if let Some(value) = reader.value()? {
+            fields.insert(name, value);
+        }
+    }
+
+    Ok(RowData {
+        table,
+        value: Value::Object(fields),
+    })
+}
+
+fn invalid(reason: &str) -> RowSerializationError {
+    RowSerializationError::DeserializationError(format!("Invalid compact row: {}", reason))
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        data.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn write_str(data: &mut Vec<u8>, value: &str) {
+    write_varint(data, value.len() as u64);
+    data.extend_from_slice(value.as_bytes());
+}
+
+fn write_value(
+    data: &mut Vec<u8>,
+    value: &Value,
+    uuid_column: bool,
+) -> Result<(), RowSerializationError> {
+    match value {
+        Value::Null => data.push(NULL),
+        Value::Bool(false) => data.push(FALSE),
+        Value::Bool(true) => data.push(TRUE),
+        Value::Number(number) => {
+            if let Some(uint) = number.as_u64() {
+                data.push(UINT);
+                write_varint(data, uint);
+            } else if let Some(int) = number.as_i64() {
+                data.push(INT);
+                write_varint(data, ((int << 1) ^ (int >> 63)) as u64);
+            } else {
+                data.push(FLOAT);
+                data.extend_from_slice(&number.as_f64().unwrap_or_default().to_le_bytes());
+            }
+        }
+        // Only canonical uuids take 16 bytes, so they read back as the same string
+        Value::String(string)
+            if uuid_column
+                && Uuid::from_str(string).map_or(false, |uuid| uuid.to_string() == *string) =>
+        {
+            data.push(UUID);
+            data.extend_from_slice(Uuid::from_str(string).unwrap().as_bytes());
+        }
+        Value::String(string) => {
+            data.push(STRING);
+            write_str(data, string);
+        }
+        Value::Array(_) | Value::Object(_) => {
+            let json = serde_json::to_vec(value)
+                .map_err(|e| RowSerializationError::SerializationError(e.to_string()))?;
+            data.push(JSON);
+            write_varint(data, json.len() as u64);
+            data.extend_from_slice(&json);
+        }
+    }
+
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RowSerializationError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| invalid("truncated"))?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, RowSerializationError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, RowSerializationError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint overflow"))
+    }
+
+    fn string(&mut self) -> Result<String, RowSerializationError> {
+        let len = self.varint()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("invalid utf-8"))
+    }
+
+    /// Next value, `None` for absent ones.
+    fn value(&mut self) -> Result<Option<Value>, RowSerializationError> {
+        let value = match self.byte()? {
+            ABSENT => return Ok(None),
+            NULL => Value::Null,
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            UINT => Value::from(self.varint()?),
+            INT => {
+                let zigzag = self.varint()?;
+                Value::from(((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64))
+            }
+            FLOAT => {
+                let float = f64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                Number::from_f64(float)
+                    .map(Value::Number)
+                    .ok_or_else(|| invalid("non-finite number"))?
+            }
+            STRING => Value::String(self.string()?),
+            UUID => {
+                let bytes: [u8; 16] = self.take(16)?.try_into().unwrap();
+                Value::String(Uuid::from_bytes(bytes).to_string())
+            }
+            JSON => {
+                let len = self.varint()? as usize;
+                serde_json::from_slice(self.take(len)?).map_err(|_| invalid("invalid json"))?
+            }
+            tag => return Err(invalid(&format!("unknown tag {}", tag))),
+        };
+
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{equals, test_db, user_row};
+    use crate::managers::single::SingleQueryManager;
+    use crate::ops::query_ops::QueryOps;
+    use crate::row_json::{RowData, RowJson};
+    use crate::serializer::compact::{decode, encode, is_compact, CompactLayout};
+    use schemajs_data::shard::shards::data_shard::config::{RowEncoding, TableStorage};
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    pub fn test_compact_encoding() {
+        let table = Table::new(&format!("compact_{}", Uuid::new_v4()))
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("age", DataTypes::Number))
+            .add_column(Column::new("balance", DataTypes::Number))
+            .add_column(Column::new("active", DataTypes::Boolean))
+            .add_column(Column::new(
+                "tags",
+                DataTypes::Array(Box::new(DataTypes::String)),
+            ))
+            .add_column(Column::new("nickname", DataTypes::String));
+        let dir = tempfile::tempdir().unwrap();
+        let layout = CompactLayout::open(dir.path(), &table).unwrap();
+
+        let row = RowData {
+            table: table.name.clone(),
+            value: json!({
+                "_uid": Uuid::new_v4().to_string(),
+                "user_name": "andres",
+                "age": 28,
+                "balance": -12.5,
+                "active": true,
+                "tags": ["a", "b"],
+                "legacy": -3
+            }),
+        };
+
+        let compact = encode(&layout, &row).unwrap();
+        let json = serde_json::to_vec(&row).unwrap();
+        assert!(is_compact(&compact));
+        assert!(!is_compact(&json));
+        assert!(compact.len() < json.len() / 2);
+
+        // Missing columns stay missing, fields that aren't columns are kept
+        let decoded = decode(&compact).unwrap();
+        assert_eq!(decoded.table, row.table);
+        assert_eq!(decoded.value, row.value);
+
+        // Layouts of previous definitions of the table are kept, their rows can still be read
+        let altered = table
+            .clone()
+            .add_column(Column::new("email", DataTypes::String));
+        let new_layout = CompactLayout::open(dir.path(), &altered).unwrap();
+        assert_ne!(new_layout.fingerprint, layout.fingerprint);
+        assert_eq!(decode(&compact).unwrap().value, row.value);
+        let stored: Vec<CompactLayout> =
+            serde_json::from_slice(&std::fs::read(dir.path().join("layouts.json")).unwrap())
+                .unwrap();
+        assert_eq!(stored.len(), 2);
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_compact_encoding_table() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+
+        let tbl = Table::new("users")
+            .add_column(Column::new("user_name", DataTypes::String))
+            .add_column(Column::new("user_age", DataTypes::Number));
+        query_manager
+            .register_table_with_storage(
+                tbl.clone(),
+                TableStorage::default().set_encoding(RowEncoding::Compact),
+            )
+            .unwrap();
+
+        for (name, age) in [("andres", 25), ("carlos", 30), ("luis", 40)] {
+            query_manager
+                .insert(user_row(json!({ "user_name": name, "user_age": age })))
+                .unwrap();
+        }
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        let carlos = equals("user_name", "carlos");
+        let mut new_values = HashMap::new();
+        new_values.insert(
+            "user_age".to_string(),
+            DataValue::Number(serde_json::Number::from(31)),
+        );
+        query_manager
+            .update("users".to_string(), &carlos, &new_values)
+            .unwrap();
+
+        // Rows are stored positionally, without the names of the columns
+        {
+            let table_shard = query_manager.tables.get("users").unwrap();
+            let data = table_shard.data.read().unwrap();
+            for position in 0..data.len() {
+                let item = data.get_element(position).unwrap();
+                assert!(is_compact(&item));
+                assert!(!item.windows(9).any(|window| window == b"user_name"));
+            }
+        }
+
+        let rows = query_manager
+            .search_manager()
+            .search("users".to_string(), &carlos)
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value.value["user_age"], json!(31));
+
+        // Rows written before the columns changed are read with the layout they were written with
+        query_manager
+            .reload_table(tbl.add_column(Column::new("user_email", DataTypes::String)))
+            .unwrap();
+        query_manager
+            .insert(user_row(json!({
+                "user_name": "maria",
+                "user_email": "maria@schemajs.com"
+            })))
+            .unwrap();
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+        let all = query_manager
+            .search_manager()
+            .search("users".to_string(), &QueryOps::And(vec![]))
+            .unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(
+            query_manager
+                .tables
+                .get("users")
+                .unwrap()
+                .storage()
+                .encoding,
+            RowEncoding::Compact
+        );
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
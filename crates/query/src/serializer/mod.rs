@@ -1,4 +1,6 @@
 pub mod borsh;
+pub mod compact;
+pub mod registry;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -0,0 +1,276 @@
+use crate::row_json::RowData;
+use crate::serializer::borsh::BorshRowSerializer;
+use crate::serializer::RowSerializationError;
+use once_cell::sync::Lazy;
+use schemajs_primitives::table::Table;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, RwLock};
+
+/// First byte of the rows written by a registered serializer, followed by the name of the serializer.
+pub const REGISTERED_MAGIC: u8 = 0xC6;
+
+/// Name of the Borsh serializer, registered by default.
+pub const BORSH_SERIALIZER: &str = "borsh";
+
+static GLOBAL_REGISTRY: Lazy<SerializerRegistry> = Lazy::new(|| {
+    let registry = SerializerRegistry::new();
+    registry
+        .register(BORSH_SERIALIZER, BorshRowSerializer)
+        .unwrap();
+    registry
+});
+
+/// Serializer of the rows of a table, registered by name in a `SerializerRegistry`.
+///
+/// Unlike `RowSerializer`, implemented by the rows themselves, a `RowCodec` is independent of the rows it
+/// serializes, so one instance can be shared by every table referencing it (see `Table::set_serializer`).
+pub trait RowCodec: Send + Sync + 'static {
+    /// Serializes `row`, a row of `table`.
+    fn encode(&self, table: &Table, row: &RowData) -> Result<Vec<u8>, RowSerializationError>;
+
+    /// Deserializes a row serialized by `encode`.
+    fn decode(&self, data: &[u8]) -> Result<RowData, RowSerializationError>;
+}
+
+/// A `RowCodec` along with the name it was registered with.
+///
+/// Rows written through `encode` start with `REGISTERED_MAGIC` and the name of the serializer, so they are
+/// read back with it whatever serializer the table uses when they are read (see `decode`).
+#[derive(Clone)]
+pub struct RegisteredSerializer {
+    pub name: String,
+    pub codec: Arc<dyn RowCodec>,
+}
+
+impl Debug for RegisteredSerializer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisteredSerializer")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl RegisteredSerializer {
+    pub fn encode(&self, table: &Table, row: &RowData) -> Result<Vec<u8>, RowSerializationError> {
+        let payload = self.codec.encode(table, row)?;
+
+        let mut data = Vec::with_capacity(2 + self.name.len() + payload.len());
+        data.push(REGISTERED_MAGIC);
+        data.push(self.name.len() as u8);
+        data.extend_from_slice(self.name.as_bytes());
+        data.extend_from_slice(&payload);
+
+        Ok(data)
+    }
+}
+
+/// Serializers the tables can reference by name (see `Table::set_serializer`), on top of the JSON and compact
+/// encodings of `TableStorage`.
+///
+/// Downstream crates register their own through `SerializerRegistry::global()` before the tables using them are
+/// opened. Serializers can't be unregistered: rows written with them must remain readable.
+#[derive(Debug, Default)]
+pub struct SerializerRegistry {
+    serializers: RwLock<HashMap<String, RegisteredSerializer>>,
+}
+
+impl SerializerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry used by the tables of every database.
+    pub fn global() -> &'static SerializerRegistry {
+        &GLOBAL_REGISTRY
+    }
+
+    /// Registers `codec` as `name`.
+    ///
+    /// # Returns:
+    /// - `Result<(), RowSerializationError>`: Fails if a serializer is already registered as `name`, or if
+    ///   `name` is empty or longer than 255 bytes.
+    pub fn register<C: RowCodec>(&self, name: &str, codec: C) -> Result<(), RowSerializationError> {
+        if name.is_empty() || name.len() > u8::MAX as usize {
+            return Err(RowSerializationError::SerializationError(format!(
+                "Invalid serializer name '{}'",
+                name
+            )));
+        }
+
+        let mut serializers = self.serializers.write().unwrap();
+        if serializers.contains_key(name) {
+            return Err(RowSerializationError::SerializationError(format!(
+                "Serializer '{}' is already registered",
+                name
+            )));
+        }
+
+        serializers.insert(
+            name.to_string(),
+            RegisteredSerializer {
+                name: name.to_string(),
+                codec: Arc::new(codec),
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<RegisteredSerializer> {
+        self.serializers.read().unwrap().get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.serializers.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Decodes a row written by a serializer of the registry (see `RegisteredSerializer::encode`).
+    pub fn decode(&self, data: &[u8]) -> Result<RowData, RowSerializationError> {
+        let invalid = |reason: String| {
+            RowSerializationError::DeserializationError(format!("Invalid row: {}", reason))
+        };
+
+        if data.first() != Some(&REGISTERED_MAGIC) || data.len() < 2 {
+            return Err(invalid(
+                "not written by a registered serializer".to_string(),
+            ));
+        }
+
+        let name_end = 2 + data[1] as usize;
+        let name = data
+            .get(2..name_end)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| invalid("truncated".to_string()))?;
+        let serializer = self
+            .get(name)
+            .ok_or_else(|| invalid(format!("unknown serializer '{}'", name)))?;
+
+        serializer.codec.decode(&data[name_end..])
+    }
+}
+
+/// Whether `data` was written by a registered serializer.
+pub fn is_registered(data: &[u8]) -> bool {
+    data.first() == Some(&REGISTERED_MAGIC)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::managers::single::fixtures::{equals, test_db, user_row};
+    use crate::managers::single::SingleQueryManager;
+    use crate::row_json::{RowData, RowJson};
+    use crate::serializer::registry::{
+        is_registered, RowCodec, SerializerRegistry, BORSH_SERIALIZER,
+    };
+    use crate::serializer::RowSerializationError;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    struct ReversedJson;
+
+    impl RowCodec for ReversedJson {
+        fn encode(&self, _table: &Table, row: &RowData) -> Result<Vec<u8>, RowSerializationError> {
+            let mut data = serde_json::to_vec(row).unwrap();
+            data.reverse();
+            Ok(data)
+        }
+
+        fn decode(&self, data: &[u8]) -> Result<RowData, RowSerializationError> {
+            let mut data = data.to_vec();
+            data.reverse();
+            serde_json::from_slice(&data)
+                .map_err(|e| RowSerializationError::DeserializationError(e.to_string()))
+        }
+    }
+
+    #[test]
+    pub fn test_serializer_registry() {
+        let registry = SerializerRegistry::new();
+        registry.register("reversed", ReversedJson).unwrap();
+        assert!(registry.register("reversed", ReversedJson).is_err());
+        assert!(registry.register("", ReversedJson).is_err());
+        assert_eq!(registry.names(), vec!["reversed".to_string()]);
+
+        let table = Table::new("users");
+        let row = RowData {
+            table: "users".to_string(),
+            value: json!({ "user_name": "andres", "age": 28, "balance": -1.5, "tags": ["a"] }),
+        };
+
+        let data = registry
+            .get("reversed")
+            .unwrap()
+            .encode(&table, &row)
+            .unwrap();
+        assert!(is_registered(&data));
+        assert_eq!(registry.decode(&data).unwrap().value, row.value);
+        assert!(SerializerRegistry::new().decode(&data).is_err());
+
+        // Borsh is always there, numbers keep their type
+        let borsh = SerializerRegistry::global().get(BORSH_SERIALIZER).unwrap();
+        let data = borsh.encode(&table, &row).unwrap();
+        let decoded = SerializerRegistry::global().decode(&data).unwrap();
+        assert_eq!(decoded.table, "users");
+        assert_eq!(decoded.value, row.value);
+    }
+
+    #[flaky_test::flaky_test]
+    pub fn test_registered_serializer_table() {
+        let (test_db, db_folder) = test_db();
+        let query_manager: SingleQueryManager<RowJson> = SingleQueryManager::new(test_db);
+
+        query_manager
+            .register_table(
+                Table::new("users")
+                    .add_column(Column::new("user_name", DataTypes::String))
+                    .add_column(Column::new("user_age", DataTypes::Int))
+                    .set_serializer(BORSH_SERIALIZER),
+            )
+            .unwrap();
+
+        for (name, age) in [("andres", 25), ("carlos", 30)] {
+            query_manager
+                .insert(user_row(json!({ "user_name": name, "user_age": age })))
+                .unwrap();
+        }
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        let carlos = equals("user_name", "carlos");
+        let mut new_values = HashMap::new();
+        new_values.insert(
+            "user_age".to_string(),
+            DataValue::Number(serde_json::Number::from(31)),
+        );
+        query_manager
+            .update("users".to_string(), &carlos, &new_values)
+            .unwrap();
+
+        {
+            let table_shard = query_manager.tables.get("users").unwrap();
+            let data = table_shard.data.read().unwrap();
+            for position in 0..data.len() {
+                assert!(is_registered(&data.get_element(position).unwrap()));
+            }
+        }
+
+        let rows = query_manager
+            .search_manager()
+            .search("users".to_string(), &carlos)
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value.value["user_age"], json!(31));
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+mod parser;
+
+use crate::errors::QueryError;
+use crate::managers::single::SingleQueryManager;
+use crate::ops::builder::Query;
+use crate::ops::query_ops::values_from_json;
+use crate::row_json::{RowData, RowJson};
+use crate::search::search_opts::SearchOpts;
+use crate::sql::parser::Parser;
+use enum_as_inner::EnumAsInner;
+use schemajs_primitives::table::Table;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Statement parsed by `parse`, whose conditions are compiled into `QueryOps` when executed against the tables
+/// of a database.
+///
+/// # Fields:
+/// - `Select::query`: Rows to return.
+/// - `Select::opts`: `ORDER BY`, `LIMIT` and `OFFSET` of the statement, its columns as `projection`
+///   (`None` for `*`).
+/// - `Insert::rows`: Rows to insert as JSON objects, without `_uid`.
+/// - `Update::changes`: Values to set as a JSON object of columns, typed like the changes of the JS `update`.
+/// - `Update::query` / `Delete::query`: Rows to update or delete, every row without `WHERE`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlStatement {
+    Select { query: Query, opts: SearchOpts },
+    Insert { table: String, rows: Vec<Value> },
+    Update { query: Query, changes: Value },
+    Delete { query: Query },
+}
+
+/// Outcome of an executed `SqlStatement`, serialized the way the JS ops return the results of the calls
+/// statements compile to.
+#[derive(Debug, Clone, PartialEq, Serialize, EnumAsInner)]
+#[serde(untagged)]
+pub enum SqlResult {
+    /// Rows returned by `SELECT`, as objects.
+    Rows(Vec<Value>),
+    /// Uids of the rows inserted by `INSERT`.
+    Inserted(Vec<Uuid>),
+    /// Amount of rows changed by `UPDATE` or `DELETE`.
+    Affected(usize),
+}
+
+/// Parses a single SQL statement:
+/// - `SELECT * | column, ... FROM table [WHERE ...] [ORDER BY column [ASC | DESC], ...] [LIMIT n] [OFFSET n]`
+/// - `INSERT INTO table (column, ...) VALUES (value, ...), ...`
+/// - `UPDATE table SET column = value, ... [WHERE ...]`
+/// - `DELETE FROM table [WHERE ...]`
+///
+/// `WHERE` conditions are `column <op> value` (`=`, `!=`, `<>`, `<`, `<=`, `>`, `>=`), `column IN (value, ...)`,
+/// `column LIKE 'pattern'`, `column REGEXP 'pattern'`, `column CONTAINS value` (arrays holding the value),
+/// `column BETWEEN value AND value` (bounds included) and `column IS [NOT] NULL`, joined with `AND` and `OR` and
+/// grouped by parentheses.
+/// Values are `'strings'` (quotes escaped by doubling them), numbers, `TRUE`, `FALSE` and `NULL`.
+/// Keywords are case insensitive, identifiers can be `"quoted"`.
+///
+/// # Returns:
+/// - `Result<SqlStatement, QueryError>`: Fails with `QueryError::InvalidSql` for anything else.
+pub fn parse(sql: &str) -> Result<SqlStatement, QueryError> {
+    Parser::new(sql)?.parse()
+}
+
+impl SqlStatement {
+    pub fn table(&self) -> &str {
+        match self {
+            SqlStatement::Insert { table, .. } => table,
+            SqlStatement::Select { query, .. }
+            | SqlStatement::Update { query, .. }
+            | SqlStatement::Delete { query } => query.table_name(),
+        }
+    }
+
+    /// Whether the statement only reads rows.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, SqlStatement::Select { .. })
+    }
+
+    /// Runs the statement through `query_manager`, like the equivalent `search_with`, `insert`, `update` and
+    /// `delete` calls.
+    ///
+    /// # Returns:
+    /// - `Result<SqlResult, QueryError>`: Fails for unknown tables or columns, values not matching the type of
+    ///   their column, or any error of the calls it runs. Rows are inserted one by one, the rows preceding
+    ///   a failing one remain inserted.
+    pub fn execute(
+        &self,
+        query_manager: &SingleQueryManager<RowJson>,
+    ) -> Result<SqlResult, QueryError> {
+        let table_name = self.table().to_string();
+        let table = query_manager
+            .tables
+            .get(&table_name)
+            .map(|table_shard| table_shard.table.clone())
+            .ok_or_else(|| QueryError::InvalidTable(table_name.clone()))?;
+
+        match self {
+            SqlStatement::Select { query, opts } => {
+                let ops = query.build(&table)?;
+                let columns = match &opts.projection {
+                    Some(columns) => Some(Self::columns(&table, columns)?),
+                    None => None,
+                };
+
+                let rows = query_manager
+                    .search_manager()
+                    .search_with(table_name, &ops, opts)?;
+
+                Ok(SqlResult::Rows(
+                    rows.iter()
+                        .map(|row| {
+                            let row = row.to_json(&table);
+                            match (&columns, row) {
+                                (Some(columns), Value::Object(mut obj)) => Value::Object(
+                                    columns
+                                        .iter()
+                                        .map(|column| {
+                                            let value = obj.remove(*column).unwrap_or(Value::Null);
+                                            (column.to_string(), value)
+                                        })
+                                        .collect(),
+                                ),
+                                (_, row) => row,
+                            }
+                        })
+                        .collect(),
+                ))
+            }
+            SqlStatement::Insert { rows, .. } => {
+                let mut uids = Vec::with_capacity(rows.len());
+                for row in rows {
+                    let mut row = row.clone();
+                    if let Value::Object(ref mut obj) = row {
+                        obj.insert(
+                            "_uid".to_string(),
+                            Value::String(Uuid::new_v4().to_string()),
+                        );
+                    }
+
+                    uids.push(query_manager.insert(RowJson::from(RowData {
+                        table: table_name.clone(),
+                        value: row,
+                    }))?);
+                }
+
+                Ok(SqlResult::Inserted(uids))
+            }
+            SqlStatement::Update { query, changes } => {
+                let ops = query.build(&table)?;
+                let values = values_from_json(&table, changes)?;
+
+                Ok(SqlResult::Affected(
+                    query_manager.update(table_name, &ops, &values)?,
+                ))
+            }
+            SqlStatement::Delete { query } => {
+                let ops = query.build(&table)?;
+
+                Ok(SqlResult::Affected(query_manager.delete(table_name, &ops)?))
+            }
+        }
+    }
+
+    // Columns selected by a statement, which have to exist in `table`
+    fn columns<'a>(table: &Table, columns: &'a [String]) -> Result<Vec<&'a str>, QueryError> {
+        columns
+            .iter()
+            .map(|column| match table.get_column(column) {
+                Some(_) => Ok(column.as_str()),
+                None => Err(QueryError::InvalidColumn(column.clone())),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sql::{parse, SqlStatement};
+    use serde_json::json;
+
+    #[test]
+    pub fn test_sql_parse() {
+        let statement = parse(
+            "select user_name, user_age from users where user_country = 'US' and (user_age >= 21 \
+             or user_name is null) order by user_age desc, user_name limit 10 offset 5;",
+        )
+        .unwrap();
+        assert_eq!(statement.table(), "users");
+        assert!(statement.is_read_only());
+        let SqlStatement::Select { query, opts } = statement else {
+            panic!("expected a SELECT");
+        };
+        assert_eq!(
+            query.to_json(),
+            json!({ "and": [
+                { "key": "user_country", "filterType": "=", "value": "US" },
+                { "or": [
+                    { "key": "user_age", "filterType": ">=", "value": 21 },
+                    { "key": "user_name", "filterType": "is_null" }
+                ]}
+            ]})
+        );
+        assert_eq!(
+            opts.projection,
+            Some(vec!["user_name".to_string(), "user_age".to_string()])
+        );
+        assert_eq!(opts.sort.len(), 2);
+        assert_eq!((opts.limit, opts.offset), (Some(10), Some(5)));
+
+        let SqlStatement::Select { query, opts } =
+            parse("SELECT * FROM users WHERE a <> 1 OR b IN ('x', 'it''s') AND c LIKE 'a%' AND d CONTAINS 'rust' AND e BETWEEN 1 AND 5 AND f REGEXP '^a'")
+                .unwrap()
+        else {
+            panic!("expected a SELECT");
+        };
+        assert_eq!(opts.projection, None);
+        assert_eq!(
+            query.to_json(),
+            json!({ "or": [
+                { "key": "a", "filterType": "!=", "value": 1 },
+                { "and": [
+                    { "key": "b", "filterType": "in", "value": ["x", "it's"] },
+                    { "key": "c", "filterType": "like", "value": "a%" },
+                    { "key": "d", "filterType": "contains", "value": "rust" },
+                    { "key": "e", "filterType": "between", "value": { "min": 1, "max": 5 } },
+                    { "key": "f", "filterType": "regex", "value": "^a" }
+                ]}
+            ]})
+        );
+
+        assert_eq!(
+            parse("INSERT INTO users (user_name, user_age, vip) VALUES ('andres', 28, TRUE), ('carlos', -1.5, NULL)")
+                .unwrap(),
+            SqlStatement::Insert {
+                table: "users".to_string(),
+                rows: vec![
+                    json!({ "user_name": "andres", "user_age": 28, "vip": true }),
+                    json!({ "user_name": "carlos", "user_age": -1.5, "vip": null }),
+                ],
+            }
+        );
+
+        let statement =
+            parse("UPDATE users SET user_age = 30 WHERE \"user_name\" = 'carlos'").unwrap();
+        assert!(!statement.is_read_only());
+        let SqlStatement::Update { query, changes } = statement else {
+            panic!("expected an UPDATE");
+        };
+        assert_eq!(changes, json!({ "user_age": 30 }));
+        assert_eq!(
+            query.to_json(),
+            json!({ "key": "user_name", "filterType": "=", "value": "carlos" })
+        );
+
+        let SqlStatement::Delete { query } = parse("delete from users").unwrap() else {
+            panic!("expected a DELETE");
+        };
+        assert!(query.to_json().is_null());
+
+        for invalid in [
+            "",
+            "DROP TABLE users",
+            "SELECT FROM users",
+            "SELECT * FROM users WHERE",
+            "SELECT * FROM users WHERE a = 'unterminated",
+            "SELECT * FROM users LIMIT -1",
+            "INSERT INTO users (a, b) VALUES (1)",
+            "DELETE FROM users; DELETE FROM users",
+        ] {
+            assert!(
+                parse(invalid).unwrap_err().as_invalid_sql().is_some(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use crate::errors::QueryError;
+use crate::ops::builder::Query;
+use crate::ops::query_ops::FilterType;
+use crate::search::search_opts::{SearchOpts, SortDirection};
+use crate::sql::SqlStatement;
+use serde_json::{Map, Number, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Unquoted word, keywords included.
+    Word(String),
+    /// `"quoted"` identifier.
+    QuotedIdent(String),
+    Str(String),
+    Number(Number),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 12] = [
+    "<=", ">=", "!=", "<>", "=", "<", ">", "(", ")", ",", "*", ";",
+];
+
+fn invalid(reason: impl Into<String>) -> QueryError {
+    QueryError::InvalidSql(reason.into())
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Strings and quoted identifiers escape their quote by doubling it
+        if c == '\'' || c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(invalid("unterminated quote")),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(match c {
+                '\'' => Token::Str(text),
+                _ => Token::QuotedIdent(text),
+            });
+            continue;
+        }
+
+        let negative = c == '-' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+        if c.is_ascii_digit() || negative {
+            let start = i;
+            i += 1;
+            while chars
+                .get(i)
+                .is_some_and(|c| c.is_ascii_digit() || *c == '.' || *c == 'e' || *c == 'E')
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text
+                .parse::<i64>()
+                .map(Number::from)
+                .or_else(|_| text.parse::<u64>().map(Number::from))
+                .ok()
+                .or_else(|| text.parse::<f64>().ok().and_then(Number::from_f64))
+                .ok_or_else(|| invalid(format!("invalid number '{}'", text)))?;
+            tokens.push(Token::Number(number));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while chars
+                .get(i)
+                .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            Some(symbol) => {
+                tokens.push(Token::Symbol(symbol));
+                i += symbol.len();
+            }
+            None => return Err(invalid(format!("unexpected character '{}'", c))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser of the statements described in `crate::sql::parse`.
+pub(crate) struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn new(sql: &str) -> Result<Self, QueryError> {
+        Ok(Self {
+            tokens: tokenize(sql)?,
+            pos: 0,
+        })
+    }
+
+    pub fn parse(mut self) -> Result<SqlStatement, QueryError> {
+        let statement = match self.next_word()?.to_ascii_uppercase().as_str() {
+            "SELECT" => self.select()?,
+            "INSERT" => self.insert()?,
+            "UPDATE" => self.update()?,
+            "DELETE" => self.delete()?,
+            other => {
+                return Err(invalid(format!(
+                    "expected SELECT, INSERT, UPDATE or DELETE, found '{}'",
+                    other
+                )))
+            }
+        };
+
+        self.symbol(";");
+        match self.tokens.get(self.pos) {
+            None => Ok(statement),
+            Some(token) => Err(invalid(format!("unexpected {:?}", token))),
+        }
+    }
+
+    fn select(&mut self) -> Result<SqlStatement, QueryError> {
+        let mut opts = SearchOpts::new();
+        if !self.symbol("*") {
+            let mut columns = vec![self.identifier()?];
+            while self.symbol(",") {
+                columns.push(self.identifier()?);
+            }
+            opts.projection = Some(columns);
+        }
+
+        self.expect_keyword("FROM")?;
+        let table = self.identifier()?;
+        let query = self.filter(&table)?;
+
+        if self.keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let column = self.identifier()?;
+                let direction = match self.keyword("DESC") {
+                    true => SortDirection::Desc,
+                    false => {
+                        self.keyword("ASC");
+                        SortDirection::Asc
+                    }
+                };
+                opts = opts.sort_by(&column, direction);
+                if !self.symbol(",") {
+                    break;
+                }
+            }
+        }
+        if self.keyword("LIMIT") {
+            opts = opts.limit(self.count()?);
+        }
+        if self.keyword("OFFSET") {
+            opts = opts.offset(self.count()?);
+        }
+
+        Ok(SqlStatement::Select { query, opts })
+    }
+
+    fn insert(&mut self) -> Result<SqlStatement, QueryError> {
+        self.expect_keyword("INTO")?;
+        let table = self.identifier()?;
+
+        self.expect_symbol("(")?;
+        let mut columns = vec![self.identifier()?];
+        while self.symbol(",") {
+            columns.push(self.identifier()?);
+        }
+        self.expect_symbol(")")?;
+
+        self.expect_keyword("VALUES")?;
+        let mut rows = vec![];
+        loop {
+            self.expect_symbol("(")?;
+            let mut values = vec![self.literal()?];
+            while self.symbol(",") {
+                values.push(self.literal()?);
+            }
+            self.expect_symbol(")")?;
+
+            if values.len() != columns.len() {
+                return Err(invalid(format!(
+                    "{} values for {} columns",
+                    values.len(),
+                    columns.len()
+                )));
+            }
+            let row: Map<String, Value> = columns.iter().cloned().zip(values).collect();
+            rows.push(Value::Object(row));
+
+            if !self.symbol(",") {
+                break;
+            }
+        }
+
+        Ok(SqlStatement::Insert { table, rows })
+    }
+
+    fn update(&mut self) -> Result<SqlStatement, QueryError> {
+        let table = self.identifier()?;
+        self.expect_keyword("SET")?;
+
+        let mut changes = Map::new();
+        loop {
+            let column = self.identifier()?;
+            self.expect_symbol("=")?;
+            changes.insert(column, self.literal()?);
+            if !self.symbol(",") {
+                break;
+            }
+        }
+
+        Ok(SqlStatement::Update {
+            query: self.filter(&table)?,
+            changes: Value::Object(changes),
+        })
+    }
+
+    fn delete(&mut self) -> Result<SqlStatement, QueryError> {
+        self.expect_keyword("FROM")?;
+        let table = self.identifier()?;
+
+        Ok(SqlStatement::Delete {
+            query: self.filter(&table)?,
+        })
+    }
+
+    /// The `WHERE` clause of a statement over `table`, a query without conditions if there is none.
+    fn filter(&mut self, table: &str) -> Result<Query, QueryError> {
+        match self.keyword("WHERE") {
+            true => self.or(table),
+            false => Ok(Query::table(table)),
+        }
+    }
+
+    // `AND` binds tighter than `OR`, as it does for the `Query` builder
+    fn or(&mut self, table: &str) -> Result<Query, QueryError> {
+        let mut query = Query::table(table);
+        loop {
+            query = self.condition(query, table)?;
+            while self.keyword("AND") {
+                query = self.condition(query.and(), table)?;
+            }
+            if !self.keyword("OR") {
+                return Ok(query);
+            }
+            query = query.or();
+        }
+    }
+
+    fn condition(&mut self, query: Query, table: &str) -> Result<Query, QueryError> {
+        if self.symbol("(") {
+            let nested = self.or(table)?;
+            self.expect_symbol(")")?;
+            return Ok(query.nest(nested));
+        }
+
+        let column = self.identifier()?;
+        if self.keyword("IS") {
+            let not = self.keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(match not {
+                true => query.is_not_null(&column),
+                false => query.is_null(&column),
+            });
+        }
+        if self.keyword("IN") {
+            self.expect_symbol("(")?;
+            let mut values = vec![self.literal()?];
+            while self.symbol(",") {
+                values.push(self.literal()?);
+            }
+            self.expect_symbol(")")?;
+            return Ok(query.is_in(&column, values));
+        }
+        if self.keyword("LIKE") {
+            return Ok(query.condition(&column, FilterType::Like, self.literal()?));
+        }
+        if self.keyword("REGEXP") {
+            return Ok(query.condition(&column, FilterType::Regex, self.literal()?));
+        }
+        if self.keyword("CONTAINS") {
+            return Ok(query.contains(&column, self.literal()?));
+        }
+        if self.keyword("BETWEEN") {
+            let min = self.literal()?;
+            self.expect_keyword("AND")?;
+            return Ok(query.between(&column, min, self.literal()?));
+        }
+
+        let filter_type = match self.next()? {
+            Token::Symbol("=") => FilterType::Equal,
+            Token::Symbol("!=" | "<>") => FilterType::NotEqual,
+            Token::Symbol("<") => FilterType::LowerThan,
+            Token::Symbol("<=") => FilterType::LowerOrEqualTo,
+            Token::Symbol(">") => FilterType::GreaterThan,
+            Token::Symbol(">=") => FilterType::GreaterOrEqualTo,
+            token => {
+                return Err(invalid(format!(
+                    "expected an operator after '{}', found {:?}",
+                    column, token
+                )))
+            }
+        };
+
+        Ok(query.condition(&column, filter_type, self.literal()?))
+    }
+
+    fn literal(&mut self) -> Result<Value, QueryError> {
+        match self.next()? {
+            Token::Str(text) => Ok(Value::String(text)),
+            Token::Number(number) => Ok(Value::Number(number)),
+            Token::Word(word) => match word.to_ascii_uppercase().as_str() {
+                "TRUE" => Ok(Value::Bool(true)),
+                "FALSE" => Ok(Value::Bool(false)),
+                "NULL" => Ok(Value::Null),
+                _ => Err(invalid(format!("expected a value, found '{}'", word))),
+            },
+            token => Err(invalid(format!("expected a value, found {:?}", token))),
+        }
+    }
+
+    fn count(&mut self) -> Result<usize, QueryError> {
+        match self.next()? {
+            Token::Number(number) if number.is_u64() => Ok(number.as_u64().unwrap() as usize),
+            token => Err(invalid(format!("expected a count, found {:?}", token))),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, QueryError> {
+        match self.next()? {
+            Token::Word(word) | Token::QuotedIdent(word) => Ok(word),
+            token => Err(invalid(format!("expected a name, found {:?}", token))),
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, QueryError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| invalid("unexpected end of statement"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn next_word(&mut self) -> Result<String, QueryError> {
+        match self.next()? {
+            Token::Word(word) => Ok(word),
+            token => Err(invalid(format!("expected a keyword, found {:?}", token))),
+        }
+    }
+
+    /// Consumes the keyword `keyword` (in any case) if it is next.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), QueryError> {
+        match self.keyword(keyword) {
+            true => Ok(()),
+            false => Err(invalid(format!(
+                "expected {}, found {:?}",
+                keyword,
+                self.tokens.get(self.pos)
+            ))),
+        }
+    }
+
+    /// Consumes `symbol` if it is next.
+    fn symbol(&mut self, symbol: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(next)) if *next == symbol => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), QueryError> {
+        match self.symbol(symbol) {
+            true => Ok(()),
+            false => Err(invalid(format!(
+                "expected '{}', found {:?}",
+                symbol,
+                self.tokens.get(self.pos)
+            ))),
+        }
+    }
+}
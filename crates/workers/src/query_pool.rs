@@ -0,0 +1,226 @@
+use schemajs_query::errors::QueryError;
+use schemajs_query::managers::single::SingleQueryManager;
+use schemajs_query::ops::query_ops::QueryOps;
+use schemajs_query::row_json::RowJson;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Search to run on a query worker.
+///
+/// # Fields:
+/// - `query_manager`: Database to search, as shared by the engine.
+/// - `table`: Table to search.
+/// - `ops`: Query selecting the rows.
+/// - `timeout`: Time the search can take, counted from when it is dispatched (waiting for a worker included).
+pub struct SearchJob {
+    pub query_manager: Arc<SingleQueryManager<RowJson>>,
+    pub table: String,
+    pub ops: QueryOps,
+    pub timeout: Option<Duration>,
+}
+
+type SearchResult = Result<Vec<RowJson>, QueryError>;
+
+struct Dispatched {
+    job: SearchJob,
+    span: tracing::Span,
+    dispatched_at: Instant,
+    reply: tokio::sync::oneshot::Sender<Result<SearchResult, Box<dyn Any + Send>>>,
+}
+
+/// Worker of a `QueryWorkerPool`: a thread running the searches of its queue, one at a time.
+///
+/// # Fields:
+/// - `sender`: Queue of the worker.
+/// - `pending`: Searches dispatched to the worker and not answered yet, the one it is running included.
+struct QueryWorker {
+    sender: Mutex<Sender<Dispatched>>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl QueryWorker {
+    fn new(number: usize) -> Self {
+        let (sender, receiver) = channel::<Dispatched>();
+        let pending = Arc::new(AtomicUsize::new(0));
+
+        let worker_pending = pending.clone();
+        std::thread::Builder::new()
+            .name(format!("schemajs-query-{}", number))
+            .spawn(move || Self::work(receiver, worker_pending))
+            .unwrap();
+
+        Self {
+            sender: Mutex::new(sender),
+            pending,
+        }
+    }
+
+    fn work(receiver: Receiver<Dispatched>, pending: Arc<AtomicUsize>) {
+        while let Ok(Dispatched {
+            job,
+            span,
+            dispatched_at,
+            reply,
+        }) = receiver.recv()
+        {
+            let _entered = span.enter();
+            let timeout = job
+                .timeout
+                .map(|timeout| timeout.saturating_sub(dispatched_at.elapsed()));
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                job.query_manager
+                    .search_manager()
+                    .set_timeout(timeout)
+                    .search(job.table, &job.ops)
+            }));
+
+            pending.fetch_sub(1, Ordering::SeqCst);
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Pool of query workers running searches against the databases of the engine concurrently, so runtimes
+/// keep serving other requests while their searches run.
+///
+/// `search` is the dispatcher: every search goes to the worker with the fewest pending searches (the first of
+/// them on ties), and its result is returned to the caller once the worker is done with it.
+pub struct QueryWorkerPool {
+    workers: Vec<QueryWorker>,
+}
+
+impl QueryWorkerPool {
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: (0..workers.max(1)).map(QueryWorker::new).collect(),
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Searches dispatched to every worker and not answered yet.
+    pub fn pending(&self) -> Vec<usize> {
+        self.workers
+            .iter()
+            .map(|worker| worker.pending.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    fn route(&self) -> &QueryWorker {
+        self.workers
+            .iter()
+            .min_by_key(|worker| worker.pending.load(Ordering::SeqCst))
+            .unwrap()
+    }
+
+    /// Runs `job` on a worker of the pool, resolving to the matched rows.
+    /// A panic of the search is resumed in the caller once the future is awaited, the worker survives it.
+    /// The search runs within the tracing span of the caller.
+    pub async fn search(&self, job: SearchJob) -> SearchResult {
+        let (reply, receiver) = tokio::sync::oneshot::channel();
+
+        let worker = self.route();
+        worker.pending.fetch_add(1, Ordering::SeqCst);
+        worker
+            .sender
+            .lock()
+            .unwrap()
+            .send(Dispatched {
+                job,
+                span: tracing::Span::current(),
+                dispatched_at: Instant::now(),
+                reply,
+            })
+            .expect("Query worker is gone");
+
+        match receiver.await.expect("Query worker dropped the search") {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::query_pool::{QueryWorkerPool, SearchJob};
+    use schemajs_dirs::create_scheme_js_db;
+    use schemajs_primitives::column::types::{DataTypes, DataValue};
+    use schemajs_primitives::column::Column;
+    use schemajs_primitives::table::Table;
+    use schemajs_query::managers::single::SingleQueryManager;
+    use schemajs_query::ops::query_ops::{QueryOps, QueryVal};
+    use schemajs_query::row_json::{RowData, RowJson};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    pub async fn test_query_worker_pool() {
+        let db_name = Uuid::new_v4().to_string();
+        let db_folder = create_scheme_js_db(None, &db_name);
+        let query_manager: Arc<SingleQueryManager<RowJson>> =
+            Arc::new(SingleQueryManager::new(db_name.clone()));
+        query_manager
+            .register_table(
+                Table::new("users").add_column(Column::new("user_name", DataTypes::String)),
+            )
+            .unwrap();
+        for name in ["andres", "carlos", "luis"] {
+            query_manager
+                .insert(RowJson::from(RowData {
+                    table: "users".to_string(),
+                    value: serde_json::json!({
+                        "_uid": Uuid::new_v4().to_string(),
+                        "user_name": name
+                    }),
+                }))
+                .unwrap();
+        }
+        query_manager
+            .tables
+            .get("users")
+            .unwrap()
+            .temps
+            .reconcile_all();
+
+        let pool = Arc::new(QueryWorkerPool::new(2));
+        assert_eq!(pool.workers(), 2);
+        let job = |name: &str| SearchJob {
+            query_manager: query_manager.clone(),
+            table: "users".to_string(),
+            ops: QueryOps::Condition(QueryVal {
+                key: "user_name".to_string(),
+                filter_type: "=".to_string(),
+                value: DataValue::String(name.to_string()),
+            }),
+            timeout: None,
+        };
+
+        // Searches run concurrently, each one gets its own rows back
+        let searches: Vec<_> = ["andres", "carlos", "luis", "andres"]
+            .into_iter()
+            .map(|name| {
+                let pool = pool.clone();
+                let job = job(name);
+                tokio::spawn(async move { pool.search(job).await })
+            })
+            .collect();
+        for search in searches {
+            assert_eq!(search.await.unwrap().unwrap().len(), 1);
+        }
+        assert_eq!(pool.pending(), vec![0, 0]);
+
+        let unknown = SearchJob {
+            table: "unknown".to_string(),
+            ..job("andres")
+        };
+        assert!(pool.search(unknown).await.is_err());
+
+        std::fs::remove_dir_all(db_folder).unwrap();
+    }
+}